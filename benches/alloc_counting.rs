@@ -0,0 +1,95 @@
+//! Counts the heap allocations made by each cache operation, for this crate and for `lru`, and prints them as a table.
+//!
+//! Keys and values are created before counting starts, so the figures only include allocations made by the cache
+//! itself.  Run with `cargo bench --bench alloc_counting`
+mod common;
+
+use common::*;
+use lru::LruCache;
+use lru_cache::{LruCache as MyLruCache, test_utils::*};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(new_size, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Allocations and bytes allocated per operation while running `ops` operations
+fn per_op(ops: usize, run: impl FnOnce()) -> (f64, f64) {
+    let (allocations, bytes) = (ALLOCATIONS.load(Ordering::Relaxed), BYTES.load(Ordering::Relaxed));
+    run();
+
+    (
+        (ALLOCATIONS.load(Ordering::Relaxed) - allocations) as f64 / ops as f64,
+        (BYTES.load(Ordering::Relaxed) - bytes) as f64 / ops as f64,
+    )
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Owned keys and values for items `from..to`
+fn items(from: usize, to: usize) -> Vec<(String, String)> {
+    (from..to).map(|i| (gen_item_key(i), gen_item_value(i as u32))).collect()
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Prints one row per operation for a cache of each size in `CACHE_SIZES`
+fn report<C: CacheUnderTest<String, String>>() {
+    for capacity in CACHE_SIZES {
+        let size = capacity.get();
+        let mut cache = C::new(capacity);
+        let fill = items(0, size);
+
+        let put = per_op(size, || fill.into_iter().for_each(|(k, v)| cache.put(k, v)));
+
+        let keys: Vec<String> = (0..size).map(gen_item_key).collect();
+        let get = per_op(size, || assert!(keys.iter().all(|k| cache.get(k).is_some())));
+
+        let evicting = items(size, 2 * size);
+        let put_evicting = per_op(size, || evicting.into_iter().for_each(|(k, v)| cache.put(k, v)));
+
+        for (operation, (allocations, bytes)) in [("get (hit)", get), ("put", put), ("put (evicting)", put_evicting)] {
+            println!("| {:<18} | {size:>6} | {operation:<15} | {allocations:>11.2} | {bytes:>10.1} |", C::NAME);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+pub fn main() {
+    println!(
+        "| {:<18} | {:>6} | {:<15} | {:>11} | {:>10} |",
+        "Cache", "Size", "Operation", "Allocs / op", "Bytes / op"
+    );
+    println!("|{:-<20}|{:->8}|{:-<17}|{:->13}|{:->12}|", "", "", "", "", "");
+
+    report::<MyLruCache<String, String>>();
+    report::<LruCache<String, String>>();
+    #[cfg(feature = "bench-extra")]
+    report::<hashlink::LruCache<String, String>>();
+    #[cfg(feature = "bench-extra")]
+    report::<MokaCache<String, String>>();
+}
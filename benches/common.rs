@@ -1,7 +1,327 @@
-use std::num::{NonZero, NonZeroUsize};
+//! Shared by every bench, so not every item is used by each one
+#![allow(dead_code)]
+
+use lru_cache::{EvictionMode, LruCacheBuilder};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::{
+    hash::Hash,
+    marker::PhantomData,
+    num::{NonZero, NonZeroUsize},
+};
 
 pub const CACHE_SIZES: [NonZero<usize>; 3] = [
     NonZeroUsize::new(1000).unwrap(),
     NonZeroUsize::new(5000).unwrap(),
     NonZeroUsize::new(10000).unwrap(),
 ];
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A cache that the benches compare.  Adding a contender to the shared comparisons only needs an implementation of
+/// this trait
+pub trait CacheUnderTest<K, V> {
+    /// Label used in benchmark ids and result tables
+    const NAME: &'static str;
+
+    fn new(capacity: NonZeroUsize) -> Self;
+    fn get(&mut self, key: &K) -> Option<&V>;
+    fn put(&mut self, key: K, value: V);
+}
+
+impl<K: Hash + Eq, V> CacheUnderTest<K, V> for lru_cache::LruCache<K, V> {
+    const NAME: &'static str = "MyLruCache";
+
+    fn new(capacity: NonZeroUsize) -> Self {
+        lru_cache::LruCache::new(capacity)
+    }
+
+    fn get(&mut self, key: &K) -> Option<&V> {
+        lru_cache::LruCache::get(self, key)
+    }
+
+    fn put(&mut self, key: K, value: V) {
+        lru_cache::LruCache::put(self, key, value);
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// An eviction mode of this crate's cache that the benches compare with exact LRU
+pub trait Mode {
+    const NAME: &'static str;
+    const MODE: EvictionMode;
+}
+
+pub struct Lfu;
+
+impl Mode for Lfu {
+    const NAME: &'static str = "MyLruCache (LFU)";
+    const MODE: EvictionMode = EvictionMode::Lfu;
+}
+
+pub struct Slru;
+
+impl Mode for Slru {
+    const NAME: &'static str = "MyLruCache (SLRU)";
+    const MODE: EvictionMode = EvictionMode::SEGMENTED;
+}
+
+pub struct TwoQueue;
+
+impl Mode for TwoQueue {
+    const NAME: &'static str = "MyLruCache (2Q)";
+    const MODE: EvictionMode = EvictionMode::TWO_QUEUE;
+}
+
+pub struct TinyLfu;
+
+impl Mode for TinyLfu {
+    const NAME: &'static str = "MyLruCache (W-TinyLFU)";
+    const MODE: EvictionMode = EvictionMode::TINY_LFU;
+}
+
+pub struct S3Fifo;
+
+impl Mode for S3Fifo {
+    const NAME: &'static str = "MyLruCache (S3-FIFO)";
+    const MODE: EvictionMode = EvictionMode::S3_FIFO;
+}
+
+pub struct Fifo;
+
+impl Mode for Fifo {
+    const NAME: &'static str = "MyLruCache (FIFO)";
+    const MODE: EvictionMode = EvictionMode::FIFO;
+}
+
+pub struct Random;
+
+impl Mode for Random {
+    const NAME: &'static str = "MyLruCache (Random)";
+    const MODE: EvictionMode = EvictionMode::Random;
+}
+
+/// This crate's cache in the eviction mode `M`
+pub struct WithMode<M, K, V> {
+    cache: lru_cache::LruCache<K, V>,
+    mode: PhantomData<M>,
+}
+
+impl<M: Mode, K: Hash + Eq, V> CacheUnderTest<K, V> for WithMode<M, K, V> {
+    const NAME: &'static str = M::NAME;
+
+    fn new(capacity: NonZeroUsize) -> Self {
+        WithMode {
+            cache: LruCacheBuilder::new(capacity).eviction_mode(M::MODE).build(),
+            mode: PhantomData,
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<&V> {
+        self.cache.get(key)
+    }
+
+    fn put(&mut self, key: K, value: V) {
+        self.cache.put(key, value);
+    }
+}
+
+impl<K: Hash + Eq, V> CacheUnderTest<K, V> for lru_cache::ArcCache<K, V> {
+    const NAME: &'static str = "MyArcCache";
+
+    fn new(capacity: NonZeroUsize) -> Self {
+        lru_cache::ArcCache::new(capacity)
+    }
+
+    fn get(&mut self, key: &K) -> Option<&V> {
+        lru_cache::ArcCache::get(self, key)
+    }
+
+    fn put(&mut self, key: K, value: V) {
+        lru_cache::ArcCache::put(self, key, value);
+    }
+}
+
+impl<K: Hash + Eq, V> CacheUnderTest<K, V> for lru::LruCache<K, V> {
+    const NAME: &'static str = "lru::LruCache";
+
+    fn new(capacity: NonZeroUsize) -> Self {
+        lru::LruCache::new(capacity)
+    }
+
+    fn get(&mut self, key: &K) -> Option<&V> {
+        lru::LruCache::get(self, key)
+    }
+
+    fn put(&mut self, key: K, value: V) {
+        lru::LruCache::put(self, key, value);
+    }
+}
+
+#[cfg(feature = "bench-extra")]
+impl<K: Hash + Eq, V> CacheUnderTest<K, V> for hashlink::LruCache<K, V> {
+    const NAME: &'static str = "hashlink::LruCache";
+
+    fn new(capacity: NonZeroUsize) -> Self {
+        hashlink::LruCache::new(capacity.get())
+    }
+
+    fn get(&mut self, key: &K) -> Option<&V> {
+        hashlink::LruCache::get(self, key)
+    }
+
+    fn put(&mut self, key: K, value: V) {
+        hashlink::LruCache::insert(self, key, value);
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// `moka`'s concurrent cache, used from a single thread.  `moka` returns values by clone, so the last value read is
+/// kept here to lend out.  Its admission policy is TinyLFU rather than LRU, so its hit ratios are not directly
+/// comparable
+#[cfg(feature = "bench-extra")]
+pub struct MokaCache<K, V> {
+    cache: moka::sync::Cache<K, V>,
+    last_read: Option<V>,
+}
+
+#[cfg(feature = "bench-extra")]
+impl<K, V> CacheUnderTest<K, V> for MokaCache<K, V>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    const NAME: &'static str = "moka::sync::Cache";
+
+    fn new(capacity: NonZeroUsize) -> Self {
+        MokaCache {
+            cache: moka::sync::Cache::new(capacity.get() as u64),
+            last_read: None,
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<&V> {
+        self.last_read = self.cache.get(key);
+        self.last_read.as_ref()
+    }
+
+    fn put(&mut self, key: K, value: V) {
+        self.cache.insert(key, value);
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Reads a key and, if it was missing, writes it.  Returns `true` if the read hit
+pub fn read_through<C: CacheUnderTest<u64, u64>>(cache: &mut C, key: u64) -> bool {
+    let hit = cache.get(&key).is_some();
+
+    if !hit {
+        cache.put(key, key);
+    }
+
+    hit
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Draws keys from `0..universe` so that key `k` is drawn with probability proportional to `1 / (k + 1)^s`.  Larger
+/// values of `s` concentrate traffic on fewer keys; `s = 0` is uniform
+pub struct Zipfian {
+    cumulative: Vec<f64>,
+}
+
+impl Zipfian {
+    pub fn new(universe: usize, s: f64) -> Self {
+        let mut total = 0.0;
+        let cumulative = (1..=universe)
+            .map(|rank| {
+                total += (rank as f64).powf(-s);
+                total
+            })
+            .collect();
+
+        Zipfian { cumulative }
+    }
+
+    pub fn sample(&self, rng: &mut impl Rng) -> u64 {
+        let target = rng.random::<f64>() * self.cumulative[self.cumulative.len() - 1];
+        self.cumulative.partition_point(|&c| c < target) as u64
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// `len` keys drawn from a Zipfian distribution over `0..universe`
+pub fn zipfian_keys(universe: usize, s: f64, len: usize, seed: u64) -> Vec<u64> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let zipf = Zipfian::new(universe, s);
+
+    (0..len).map(|_| zipf.sample(&mut rng)).collect()
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// `len` keys from `0..universe`, where `hot_probability` of the draws are from the first `hot_fraction` of the keys
+/// and the rest are uniform over the remainder
+pub fn hotspot_keys(universe: usize, hot_fraction: f64, hot_probability: f64, len: usize, seed: u64) -> Vec<u64> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let hot = ((universe as f64 * hot_fraction) as u64).max(1);
+
+    (0..len)
+        .map(|_| match rng.random_bool(hot_probability) {
+            true => rng.random_range(0..hot),
+            false => rng.random_range(hot..universe as u64),
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// `len` Zipfian keys over `0..universe`, interrupted every `period` keys by a sequential scan of `scan_len` keys that
+/// are never requested again, after which the Zipfian traffic returns.  A scan longer than the cache flushes every hot
+/// key out of a plain LRU cache
+pub fn scan_then_return_keys(
+    universe: usize,
+    s: f64,
+    len: usize,
+    period: usize,
+    scan_len: usize,
+    seed: u64,
+) -> Vec<u64> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let zipf = Zipfian::new(universe, s);
+    let mut next_scanned = universe as u64;
+    let mut keys = Vec::with_capacity(len);
+
+    while keys.len() < len {
+        keys.extend((0..period).map(|_| zipf.sample(&mut rng)));
+        keys.extend(next_scanned..next_scanned + scan_len as u64);
+        next_scanned += scan_len as u64;
+    }
+
+    keys.truncate(len);
+    keys
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// `len` keys alternating every `phase_len` keys between Zipfian traffic over `0..universe`, which rewards frequency,
+/// and uniform traffic over a fresh working set of `working_set` keys, which rewards recency.  Neither LRU nor LFU
+/// suits both phases
+pub fn shifting_keys(universe: usize, s: f64, len: usize, phase_len: usize, working_set: usize, seed: u64) -> Vec<u64> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let zipf = Zipfian::new(universe, s);
+    let mut next_working_set = universe as u64;
+    let mut keys = Vec::with_capacity(len);
+
+    while keys.len() < len {
+        keys.extend((0..phase_len).map(|_| zipf.sample(&mut rng)));
+        let working_keys = next_working_set..next_working_set + working_set as u64;
+        keys.extend((0..phase_len).map(|_| rng.random_range(working_keys.clone())));
+        next_working_set += working_set as u64;
+    }
+
+    keys.truncate(len);
+    keys
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Replays keys against a cache as a read-through workload: each key is read, and written if it was missing.  Returns
+/// the fraction of reads that hit
+pub fn replay(keys: &[u64], mut get_or_put: impl FnMut(u64) -> bool) -> f64 {
+    let hits = keys.iter().filter(|&&key| get_or_put(key)).count();
+    hits as f64 / keys.len() as f64
+}
@@ -4,10 +4,12 @@ use common::*;
 use lru_cache::test_utils::*;
 use criterion::{BenchmarkId, Criterion, Throughput};
 use lru::LruCache;
-use lru_cache::LruCache as MyLruCache;
+use lru_cache::{ConcurrentLruCache, LruCache as MyLruCache, LruCacheBuilder, RwLruCache};
 use rand::Rng;
 use std::{
-    sync::{Arc, Barrier, Mutex},
+    hint::black_box,
+    num::NonZeroUsize,
+    sync::{Arc, Barrier, Mutex, RwLock},
     thread,
     time::Duration,
 };
@@ -120,6 +122,30 @@ fn get(c: &mut Criterion) {
                 )
             },
         );
+
+        group.bench_with_input(
+            BenchmarkId::new("get", format!("lru_cache::ConcurrentLruCache-{cache_size}")),
+            &cache_size,
+            |b, &size| {
+                b.iter_batched(
+                    || {
+                        let cache = ConcurrentLruCache::new(size);
+
+                        for i in 0..size.get() {
+                            cache.put(gen_item_key(i), gen_item_value(i as u32));
+                        }
+
+                        Arc::new(cache)
+                    },
+                    |cache| {
+                        run_threads(&cache, &barrier, move |cache, rng| {
+                            black_box(cache.get(&gen_item_key(rng.random_range(0..size.get()))));
+                        })
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
     }
 
     group.finish();
@@ -254,11 +280,447 @@ fn put(c: &mut Criterion) {
                 )
             },
         );
+
+        group.bench_with_input(
+            BenchmarkId::new("put", format!("lru_cache::ConcurrentLruCache-{cache_size}")),
+            &cache_size,
+            |b, &size| {
+                b.iter_batched(
+                    || {
+                        let cache = ConcurrentLruCache::new(size);
+
+                        for i in 0..size.get() {
+                            cache.put(gen_item_key(i), gen_item_value(i as u32));
+                        }
+
+                        Arc::new(cache)
+                    },
+                    |cache| {
+                        let mut handles = vec![];
+
+                        for _ in 0..THREAD_COUNT {
+                            let cache = Arc::clone(&cache);
+                            let barrier = Arc::clone(&barrier);
+
+                            handles.push(thread::spawn(move || {
+                                barrier.wait();
+
+                                // The same mix as above, but each operation takes the lock for itself
+                                for idx in 0..OPERATIONS_PER_THREAD {
+                                    match idx % 10 {
+                                        0..=6 => _ = black_box(cache.get(&gen_item_key(idx))),
+                                        7..=8 => _ = cache.put(gen_item_key(idx), gen_item_value(idx as u32)),
+                                        _ => _ = cache.pop_mru(),
+                                    }
+                                }
+                            }));
+                        }
+
+                        for handle in handles {
+                            handle.join().unwrap();
+                        }
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("put", format!("lru_cache::ConcurrentLruCache-try_get-{cache_size}")),
+            &cache_size,
+            |b, &size| {
+                b.iter_batched(
+                    || {
+                        let cache = ConcurrentLruCache::new(size);
+                        (0..size.get()).for_each(|i| _ = cache.put(gen_item_key(i), gen_item_value(i as u32)));
+                        Arc::new(cache)
+                    },
+                    |cache| {
+                        let mut handles = vec![];
+
+                        for _ in 0..THREAD_COUNT {
+                            let cache = Arc::clone(&cache);
+                            let barrier = Arc::clone(&barrier);
+
+                            handles.push(thread::spawn(move || {
+                                barrier.wait();
+
+                                // Reads that would wait for the lock recompute the value instead
+                                for idx in 0..OPERATIONS_PER_THREAD {
+                                    match idx % 10 {
+                                        0..=6 => match cache.try_get(&gen_item_key(idx)) {
+                                            Ok(value) => _ = black_box(value),
+                                            Err(_) => _ = black_box(gen_item_value(idx as u32)),
+                                        },
+                                        7..=8 => _ = cache.put(gen_item_key(idx), gen_item_value(idx as u32)),
+                                        _ => _ = cache.pop_mru(),
+                                    }
+                                }
+                            }));
+                        }
+
+                        for handle in handles {
+                            handle.join().unwrap();
+                        }
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
     }
 
     group.finish();
 }
 
+// ---------------------------------------------------------------------------------------------------------------------
+/// Runs `op` on every thread once all threads are ready.  `op` is passed the thread's random number generator
+fn run_threads<T, F>(shared: &Arc<T>, barrier: &Arc<Barrier>, op: F)
+where
+    T: Send + Sync + 'static,
+    F: Fn(&T, &mut rand::rngs::ThreadRng) + Send + Copy + 'static,
+{
+    let handles: Vec<_> = (0..THREAD_COUNT)
+        .map(|_| {
+            let shared = Arc::clone(shared);
+            let barrier = Arc::clone(barrier);
+
+            thread::spawn(move || {
+                let mut rng = rand::rng();
+                barrier.wait();
+
+                for _ in 0..OPERATIONS_PER_THREAD {
+                    op(&shared, &mut rng);
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Multi-threaded 95% read / 5% write mix.  Compares promoting on every read under a mutex with deferring promotions
+/// to a read buffer so that readers can share a read lock, both by hand and through the wrapper types
+fn read_mostly(c: &mut Criterion) {
+    let mut group = c.benchmark_group("LRU Performance Comparison (Multi-threaded)");
+    let barrier = Arc::new(Barrier::new(THREAD_COUNT));
+    let read_buffer = NonZeroUsize::new(64).unwrap();
+
+    for cache_size in CACHE_SIZES {
+        group.throughput(Throughput::Elements((THREAD_COUNT * OPERATIONS_PER_THREAD) as u64));
+        group.bench_with_input(
+            BenchmarkId::new("read_mostly", format!("lru_cache::MyLruCache-mutex-{cache_size}")),
+            &cache_size,
+            |b, &size| {
+                b.iter_batched(
+                    || {
+                        let mut cache = MyLruCache::new(size);
+
+                        for i in 0..size.get() {
+                            cache.put(gen_item_key(i), gen_item_value(i as u32));
+                        }
+
+                        Arc::new(Mutex::new(cache))
+                    },
+                    |cache| {
+                        run_threads(&cache, &barrier, move |cache, rng| {
+                            let idx = rng.random_range(0..size.get());
+
+                            if rng.random_range(0..100) < 95 {
+                                cache.lock().unwrap().get(&gen_item_key(idx));
+                            } else {
+                                cache.lock().unwrap().put(gen_item_key(idx), gen_item_value(idx as u32));
+                            }
+                        })
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("read_mostly", format!("lru_cache::MyLruCache-read-buffer-{cache_size}")),
+            &cache_size,
+            |b, &size| {
+                b.iter_batched(
+                    || {
+                        let mut cache = LruCacheBuilder::new(size).read_buffer(read_buffer).build();
+
+                        for i in 0..size.get() {
+                            cache.put(gen_item_key(i), gen_item_value(i as u32));
+                        }
+
+                        Arc::new(RwLock::new(cache))
+                    },
+                    |cache| {
+                        run_threads(&cache, &barrier, move |cache, rng| {
+                            let idx = rng.random_range(0..size.get());
+
+                            if rng.random_range(0..100) < 95 {
+                                cache.read().unwrap().get_deferred(&gen_item_key(idx));
+                            } else {
+                                cache.write().unwrap().put(gen_item_key(idx), gen_item_value(idx as u32));
+                            }
+                        })
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("read_mostly", format!("lru_cache::ConcurrentLruCache-{cache_size}")),
+            &cache_size,
+            |b, &size| {
+                b.iter_batched(
+                    || {
+                        let cache = ConcurrentLruCache::new(size);
+                        (0..size.get()).for_each(|i| _ = cache.put(gen_item_key(i), gen_item_value(i as u32)));
+                        Arc::new(cache)
+                    },
+                    |cache| {
+                        run_threads(&cache, &barrier, move |cache, rng| {
+                            let idx = rng.random_range(0..size.get());
+
+                            if rng.random_range(0..100) < 95 {
+                                black_box(cache.get(&gen_item_key(idx)));
+                            } else {
+                                cache.put(gen_item_key(idx), gen_item_value(idx as u32));
+                            }
+                        })
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("read_mostly", format!("lru_cache::RwLruCache-{cache_size}")),
+            &cache_size,
+            |b, &size| {
+                b.iter_batched(
+                    || {
+                        let cache = RwLruCache::new(size);
+                        (0..size.get()).for_each(|i| _ = cache.put(gen_item_key(i), gen_item_value(i as u32)));
+                        Arc::new(cache)
+                    },
+                    |cache| {
+                        run_threads(&cache, &barrier, move |cache, rng| {
+                            let idx = rng.random_range(0..size.get());
+
+                            if rng.random_range(0..100) < 95 {
+                                black_box(cache.get(&gen_item_key(idx)));
+                            } else {
+                                cache.put(gen_item_key(idx), gen_item_value(idx as u32));
+                            }
+                        })
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The `read_mostly` scenario with non-promoting reads, which `RwLruCache` serves under its read lock and
+/// `EpochLruCache` serves without any lock.  Run with `--features lockfree-reads`
+#[cfg(feature = "lockfree-reads")]
+fn lockfree_reads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("LRU Performance Comparison (Multi-threaded)");
+    let barrier = Arc::new(Barrier::new(THREAD_COUNT));
+
+    for cache_size in CACHE_SIZES {
+        group.throughput(Throughput::Elements((THREAD_COUNT * OPERATIONS_PER_THREAD) as u64));
+        group.bench_with_input(
+            BenchmarkId::new("read_mostly_peek", format!("lru_cache::RwLruCache-{cache_size}")),
+            &cache_size,
+            |b, &size| {
+                b.iter_batched(
+                    || {
+                        let cache = RwLruCache::new(size);
+                        (0..size.get()).for_each(|i| _ = cache.put(gen_item_key(i), gen_item_value(i as u32)));
+                        Arc::new(cache)
+                    },
+                    |cache| {
+                        run_threads(&cache, &barrier, move |cache, rng| {
+                            let idx = rng.random_range(0..size.get());
+
+                            if rng.random_range(0..100) < 95 {
+                                black_box(cache.peek(&gen_item_key(idx)));
+                            } else {
+                                cache.put(gen_item_key(idx), gen_item_value(idx as u32));
+                            }
+                        })
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("read_mostly_peek", format!("lru_cache::EpochLruCache-{cache_size}")),
+            &cache_size,
+            |b, &size| {
+                b.iter_batched(
+                    || {
+                        let cache = lru_cache::EpochLruCache::new(size);
+                        (0..size.get()).for_each(|i| _ = cache.put(gen_item_key(i), gen_item_value(i as u32)));
+                        Arc::new(cache)
+                    },
+                    |cache| {
+                        run_threads(&cache, &barrier, move |cache, rng| {
+                            let idx = rng.random_range(0..size.get());
+
+                            if rng.random_range(0..100) < 95 {
+                                black_box(cache.peek(&gen_item_key(idx)));
+                            } else {
+                                cache.put(gen_item_key(idx), gen_item_value(idx as u32));
+                            }
+                        })
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The `get`, `put` and `read_mostly` scenarios above run against `moka`'s natively concurrent cache, which needs no
+/// lock of its own.  `moka` has no `pop_mru`, so the mixed scenario invalidates the key instead.  Run with
+/// `--features bench-extra`
+#[cfg(feature = "bench-extra")]
+fn moka(c: &mut Criterion) {
+    let mut group = c.benchmark_group("LRU Performance Comparison (Multi-threaded)");
+    let barrier = Arc::new(Barrier::new(THREAD_COUNT));
+    let prefilled = |size: NonZeroUsize| {
+        let cache = moka::sync::Cache::new(size.get() as u64);
+        (0..size.get()).for_each(|i| cache.insert(gen_item_key(i), gen_item_value(i as u32)));
+        Arc::new(cache)
+    };
+
+    for cache_size in CACHE_SIZES {
+        group.throughput(Throughput::Elements(cache_size.get() as u64));
+        group.bench_with_input(
+            BenchmarkId::new("get", format!("moka::sync::Cache-{cache_size}")),
+            &cache_size,
+            |b, &size| {
+                b.iter_batched(
+                    || prefilled(size),
+                    |cache| {
+                        run_threads(&cache, &barrier, move |cache, rng| {
+                            black_box(cache.get(&gen_item_key(rng.random_range(0..size.get()))));
+                        })
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("put", format!("moka::sync::Cache-{cache_size}")),
+            &cache_size,
+            |b, &size| {
+                b.iter_batched(
+                    || prefilled(size),
+                    |cache| {
+                        let mut handles = vec![];
+
+                        for _ in 0..THREAD_COUNT {
+                            let cache = Arc::clone(&cache);
+                            let barrier = Arc::clone(&barrier);
+
+                            handles.push(thread::spawn(move || {
+                                barrier.wait();
+
+                                for idx in 0..OPERATIONS_PER_THREAD {
+                                    match idx % 10 {
+                                        0..=6 => _ = black_box(cache.get(&gen_item_key(idx))),
+                                        7..=8 => cache.insert(gen_item_key(idx), gen_item_value(idx as u32)),
+                                        _ => cache.invalidate(&gen_item_key(idx)),
+                                    }
+                                }
+                            }));
+                        }
+
+                        for handle in handles {
+                            handle.join().unwrap();
+                        }
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
+
+        group.throughput(Throughput::Elements((THREAD_COUNT * OPERATIONS_PER_THREAD) as u64));
+        group.bench_with_input(
+            BenchmarkId::new("read_mostly", format!("moka::sync::Cache-{cache_size}")),
+            &cache_size,
+            |b, &size| {
+                b.iter_batched(
+                    || prefilled(size),
+                    |cache| {
+                        run_threads(&cache, &barrier, move |cache, rng| {
+                            let idx = rng.random_range(0..size.get());
+
+                            if rng.random_range(0..100) < 95 {
+                                black_box(cache.get(&gen_item_key(idx)));
+                            } else {
+                                cache.insert(gen_item_key(idx), gen_item_value(idx as u32));
+                            }
+                        })
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Build a cache from a million items, some with repeated keys, using sequential puts and using `par_fill`.  Run with
+/// `--features rayon`
+#[cfg(feature = "rayon")]
+fn bulk_load(c: &mut Criterion) {
+    const ITEMS: usize = 1_000_000;
+
+    let mut group = c.benchmark_group("Bulk Load");
+    let capacity = NonZeroUsize::new(ITEMS).unwrap();
+    let items: Vec<_> = (0..ITEMS).map(|i| (gen_item_key(i % (ITEMS - ITEMS / 10)), i)).collect();
+
+    group.throughput(Throughput::Elements(ITEMS as u64));
+    group.bench_function(BenchmarkId::new("fill", format!("put-{ITEMS}")), |b| {
+        b.iter_batched(
+            || items.clone(),
+            |items| {
+                let mut cache = MyLruCache::new(capacity);
+                items.into_iter().for_each(|(k, v)| _ = cache.put(k, v));
+                cache
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function(BenchmarkId::new("fill", format!("par_fill-{ITEMS}")), |b| {
+        b.iter_batched(
+            || items.clone(),
+            |items| MyLruCache::<String, usize>::par_fill(capacity, items),
+            criterion::BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
 // ---------------------------------------------------------------------------------------------------------------------
 pub fn main() {
     let mut criterion: Criterion<_> = Criterion::default()
@@ -267,6 +729,13 @@ pub fn main() {
 
     get(&mut criterion);
     put(&mut criterion);
+    read_mostly(&mut criterion);
+    #[cfg(feature = "lockfree-reads")]
+    lockfree_reads(&mut criterion);
+    #[cfg(feature = "bench-extra")]
+    moka(&mut criterion);
+    #[cfg(feature = "rayon")]
+    bulk_load(&mut criterion);
 
     criterion.final_summary();
 }
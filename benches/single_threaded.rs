@@ -2,111 +2,194 @@ mod common;
 
 use common::*;
 use lru_cache::test_utils::*;
-use criterion::{BenchmarkId, Criterion, Throughput};
+use criterion::{BenchmarkGroup, BenchmarkId, Criterion, Throughput, measurement::WallTime};
 use lru::LruCache;
-use lru_cache::LruCache as MyLruCache;
+use lru_cache::{ArrayLruCache, IndexedLruCache, LruCache as MyLruCache, LruCacheBuilder};
 use rand::Rng;
-use std::time::Duration;
+use std::{
+    hash::BuildHasher,
+    hint::black_box,
+    num::NonZeroUsize,
+    time::{Duration, Instant},
+};
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A cache of the given size holding items `0..size`
+fn prefilled<C: CacheUnderTest<String, String>>(size: NonZeroUsize) -> C {
+    let mut cache = C::new(size);
+    (0..size.get()).for_each(|i| cache.put(gen_item_key(i), gen_item_value(i as u32)));
+    cache
+}
 
 // ---------------------------------------------------------------------------------------------------------------------
 /// Exactly fill the cache
 fn insertion_without_eviction(c: &mut Criterion) {
+    fn bench<C: CacheUnderTest<String, String>>(group: &mut BenchmarkGroup<'_, WallTime>, size: NonZeroUsize) {
+        group.bench_function(BenchmarkId::new("insertion_without_eviction", format!("{}-{size}", C::NAME)), |b| {
+            b.iter_batched(
+                || C::new(size),
+                |mut cache| {
+                    for i in 0..size.get() {
+                        cache.put(gen_item_key(i), gen_item_value(i as u32));
+                    }
+                },
+                criterion::BatchSize::SmallInput,
+            )
+        });
+    }
+
+    let mut group = c.benchmark_group("LRU Performance Comparison (Single Threaded)");
+
+    for cache_size in CACHE_SIZES {
+        group.throughput(Throughput::Elements(cache_size.get() as u64));
+        bench::<MyLruCache<_, _>>(&mut group, cache_size);
+        bench::<LruCache<_, _>>(&mut group, cache_size);
+        #[cfg(feature = "bench-extra")]
+        bench::<hashlink::LruCache<_, _>>(&mut group, cache_size);
+        #[cfg(feature = "bench-extra")]
+        bench::<MokaCache<_, _>>(&mut group, cache_size);
+    }
+
+    group.finish();
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Randomly read known items from a pre-populated cache
+fn get(c: &mut Criterion) {
+    fn bench<C: CacheUnderTest<String, String>>(group: &mut BenchmarkGroup<'_, WallTime>, size: NonZeroUsize) {
+        group.bench_function(BenchmarkId::new("get", format!("{}-{size}", C::NAME)), |b| {
+            let mut rng = rand::rng();
+
+            b.iter_batched(
+                || prefilled::<C>(size),
+                |mut cache| _ = black_box(cache.get(&gen_item_key(rng.random_range(0..size.get())))),
+                criterion::BatchSize::SmallInput,
+            )
+        });
+    }
+
+    let mut group = c.benchmark_group("LRU Performance Comparison (Single Threaded)");
+
+    for cache_size in CACHE_SIZES {
+        group.throughput(Throughput::Elements(cache_size.get() as u64));
+        bench::<LruCache<_, _>>(&mut group, cache_size);
+        bench::<MyLruCache<_, _>>(&mut group, cache_size);
+        #[cfg(feature = "bench-extra")]
+        bench::<hashlink::LruCache<_, _>>(&mut group, cache_size);
+        #[cfg(feature = "bench-extra")]
+        bench::<MokaCache<_, _>>(&mut group, cache_size);
+    }
+
+    group.finish();
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Randomly write items to the cache that have a 50% likelihood of already being present
+fn put(c: &mut Criterion) {
+    fn bench<C: CacheUnderTest<String, String>>(group: &mut BenchmarkGroup<'_, WallTime>, size: NonZeroUsize) {
+        group.bench_function(BenchmarkId::new("put", format!("{}-{size}", C::NAME)), |b| {
+            let mut rng = rand::rng();
+
+            b.iter_batched(
+                || prefilled::<C>(size),
+                |mut cache| {
+                    cache.put(
+                        gen_item_key(rng.random_range(0..size.get() * 2)),
+                        gen_item_value(rng.random_range(0..size.get() * 2) as u32),
+                    );
+                },
+                criterion::BatchSize::SmallInput,
+            )
+        });
+    }
+
     let mut group = c.benchmark_group("LRU Performance Comparison (Single Threaded)");
 
+    for cache_size in CACHE_SIZES {
+        group.throughput(Throughput::Elements(cache_size.get() as u64));
+        bench::<LruCache<_, _>>(&mut group, cache_size);
+        bench::<MyLruCache<_, _>>(&mut group, cache_size);
+        #[cfg(feature = "bench-extra")]
+        bench::<hashlink::LruCache<_, _>>(&mut group, cache_size);
+        #[cfg(feature = "bench-extra")]
+        bench::<MokaCache<_, _>>(&mut group, cache_size);
+    }
+
+    group.finish();
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Randomly read and write items using the default hasher and a fast non-cryptographic hasher
+fn hasher(c: &mut Criterion) {
+    let mut group = c.benchmark_group("LRU Hasher Comparison (Single Threaded)");
+
+    fn prefilled<S: BuildHasher>(size: NonZeroUsize, hash_builder: S) -> MyLruCache<String, String, S> {
+        let mut cache = MyLruCache::with_hasher(size, hash_builder);
+
+        for i in 0..size.get() {
+            cache.put(gen_item_key(i), gen_item_value(i as u32));
+        }
+
+        cache
+    }
+
+    fn get_and_put<S: BuildHasher>(cache: &mut MyLruCache<String, String, S>, size: NonZeroUsize) {
+        let mut rng = rand::rng();
+
+        cache.get(&gen_item_key(rng.random_range(0..size.get())));
+        cache.put(
+            gen_item_key(rng.random_range(0..size.get() * 2)),
+            gen_item_value(rng.random_range(0..size.get() * 2) as u32),
+        );
+    }
+
     for cache_size in CACHE_SIZES {
         group.throughput(Throughput::Elements(cache_size.get() as u64));
         group.bench_with_input(
-            BenchmarkId::new(
-                "insertion_without_eviction",
-                format!("MyLruCache-{cache_size}"),
-            ),
+            BenchmarkId::new("get_and_put", format!("RandomState-{cache_size}")),
             &cache_size,
             |b, &size| {
                 b.iter_batched(
-                    || MyLruCache::new(size),
-                    |mut cache| {
-                        for i in 0..size.get() {
-                            cache.put(gen_item_key(i), gen_item_value(i as u32));
-                        }
-                    },
+                    || prefilled(size, std::hash::RandomState::new()),
+                    |mut cache| get_and_put(&mut cache, size),
                     criterion::BatchSize::SmallInput,
                 )
             },
         );
 
         group.bench_with_input(
-            BenchmarkId::new(
-                "insertion_without_eviction",
-                format!("lru::LruCache-{cache_size}"),
-            ),
+            BenchmarkId::new("get_and_put", format!("foldhash-{cache_size}")),
             &cache_size,
             |b, &size| {
                 b.iter_batched(
-                    || LruCache::new(size),
-                    |mut cache| {
-                        for i in 0..size.get() {
-                            cache.put(gen_item_key(i), gen_item_value(i as u32));
-                        }
-                    },
+                    || prefilled(size, foldhash::fast::RandomState::default()),
+                    |mut cache| get_and_put(&mut cache, size),
                     criterion::BatchSize::SmallInput,
                 )
             },
         );
-    }
-
-    group.finish();
-}
 
-// ---------------------------------------------------------------------------------------------------------------------
-/// Randomly read known items from a pre-populated cache
-fn get(c: &mut Criterion) {
-    let mut group = c.benchmark_group("LRU Performance Comparison (Single Threaded)");
-    let mut rng = rand::rng();
-
-    for cache_size in CACHE_SIZES {
-        group.throughput(Throughput::Elements(cache_size.get() as u64));
+        #[cfg(feature = "ahash")]
         group.bench_with_input(
-            BenchmarkId::new("get", format!("lru::LruCache-{cache_size}")),
+            BenchmarkId::new("get_and_put", format!("ahash-{cache_size}")),
             &cache_size,
             |b, &size| {
                 b.iter_batched(
-                    || {
-                        let mut cache = LruCache::new(size);
-
-                        // Pre-populate cache
-                        for i in 0..size.get() {
-                            cache.put(gen_item_key(i), gen_item_value(i as u32));
-                        }
-
-                        cache
-                    },
-                    |mut cache| {
-                        cache.get(&gen_item_key(rng.random_range(0..size.get())));
-                    },
+                    || prefilled(size, ahash::RandomState::new()),
+                    |mut cache| get_and_put(&mut cache, size),
                     criterion::BatchSize::SmallInput,
                 )
             },
         );
 
+        #[cfg(feature = "fxhash")]
         group.bench_with_input(
-            BenchmarkId::new("get", format!("lru_cache::MyLruCache-{cache_size}")),
+            BenchmarkId::new("get_and_put", format!("fxhash-{cache_size}")),
             &cache_size,
             |b, &size| {
                 b.iter_batched(
-                    || {
-                        let mut cache = MyLruCache::new(size);
-
-                        // Pre-populate cache
-                        for i in 0..size.get() {
-                            cache.put(gen_item_key(i), gen_item_value(i as u32));
-                        }
-
-                        cache
-                    },
-                    |mut cache| {
-                        let mut rng = rand::rng();
-                        cache.get(&gen_item_key(rng.random_range(0..size.get())));
-                    },
+                    || prefilled(size, fxhash::FxBuildHasher::default()),
+                    |mut cache| get_and_put(&mut cache, size),
                     criterion::BatchSize::SmallInput,
                 )
             },
@@ -117,33 +200,34 @@ fn get(c: &mut Criterion) {
 }
 
 // ---------------------------------------------------------------------------------------------------------------------
-/// Randomly write items to the cache that have a 50% likelihood of already being present
-fn put(c: &mut Criterion) {
-    let mut group = c.benchmark_group("LRU Performance Comparison (Single Threaded)");
-    let mut rng = rand::rng();
+/// Randomly read and write items whose 256 byte keys are slow to hash, so that every extra hash of a key shows up
+fn long_keys(c: &mut Criterion) {
+    let mut group = c.benchmark_group("LRU Long Key Comparison (Single Threaded)");
+
+    fn long_key(idx: usize) -> String {
+        format!("{:0>256}", gen_item_key(idx))
+    }
 
     for cache_size in CACHE_SIZES {
         group.throughput(Throughput::Elements(cache_size.get() as u64));
         group.bench_with_input(
-            BenchmarkId::new("put", format!("lru::LruCache-{cache_size}")),
+            BenchmarkId::new("get_and_put", format!("lru::LruCache-{cache_size}")),
             &cache_size,
             |b, &size| {
                 b.iter_batched(
                     || {
                         let mut cache = LruCache::new(size);
 
-                        // Pre-populate cache
                         for i in 0..size.get() {
-                            cache.put(gen_item_key(i), gen_item_value(i as u32));
+                            cache.put(long_key(i), i);
                         }
 
                         cache
                     },
                     |mut cache| {
-                        cache.put(
-                            gen_item_key(rng.random_range(0..size.get() * 2)),
-                            gen_item_value(rng.random_range(0..size.get() * 2) as u32),
-                        );
+                        let mut rng = rand::rng();
+                        cache.get(&long_key(rng.random_range(0..size.get())));
+                        cache.put(long_key(rng.random_range(0..size.get() * 2)), 0);
                     },
                     criterion::BatchSize::SmallInput,
                 )
@@ -151,25 +235,23 @@ fn put(c: &mut Criterion) {
         );
 
         group.bench_with_input(
-            BenchmarkId::new("put", format!("lru_cache::MyLruCache-{cache_size}")),
+            BenchmarkId::new("get_and_put", format!("lru_cache::MyLruCache-{cache_size}")),
             &cache_size,
             |b, &size| {
                 b.iter_batched(
                     || {
                         let mut cache = MyLruCache::new(size);
 
-                        // Pre-populate cache
                         for i in 0..size.get() {
-                            cache.put(gen_item_key(i), gen_item_value(i as u32));
+                            cache.put(long_key(i), i);
                         }
 
                         cache
                     },
                     |mut cache| {
-                        cache.put(
-                            gen_item_key(rng.random_range(0..size.get() * 2)),
-                            gen_item_value(rng.random_range(0..size.get() * 2) as u32),
-                        );
+                        let mut rng = rand::rng();
+                        cache.get(&long_key(rng.random_range(0..size.get())));
+                        cache.put(long_key(rng.random_range(0..size.get() * 2)), 0);
                     },
                     criterion::BatchSize::SmallInput,
                 )
@@ -180,6 +262,258 @@ fn put(c: &mut Criterion) {
     group.finish();
 }
 
+// ---------------------------------------------------------------------------------------------------------------------
+/// Alternately read and write keys drawn from twice the cache's capacity, for caches small enough that a linear
+/// search could beat hashing
+fn small_caches(c: &mut Criterion) {
+    let mut group = c.benchmark_group("LRU Small Cache Comparison (Single Threaded)");
+
+    fn small_cache<const N: usize>(group: &mut BenchmarkGroup<'_, WallTime>) {
+        let key_range = 2 * N as u64;
+
+        group.bench_function(BenchmarkId::new("get_and_put", format!("ArrayLruCache-{N}")), |b| {
+            let mut cache = ArrayLruCache::<u64, u64, N>::new();
+            let mut i = 0u64;
+
+            b.iter(|| {
+                i = i.wrapping_add(7);
+                black_box(cache.get(&(i % key_range)));
+                cache.put((i * 3) % key_range, i);
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("get_and_put", format!("MyLruCache-{N}")), |b| {
+            let mut cache = MyLruCache::<u64, u64>::new(NonZeroUsize::new(N).unwrap());
+            let mut i = 0u64;
+
+            b.iter(|| {
+                i = i.wrapping_add(7);
+                black_box(cache.get(&(i % key_range)));
+                cache.put((i * 3) % key_range, i);
+            })
+        });
+    }
+
+    small_cache::<8>(&mut group);
+    small_cache::<16>(&mut group);
+    small_cache::<64>(&mut group);
+
+    group.finish();
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Alternately read and write connection-id style keys from a universe of 65536, with a cache holding a quarter of them
+fn integer_keys(c: &mut Criterion) {
+    const UNIVERSE: usize = 65_536;
+    const CAPACITY: NonZeroUsize = NonZeroUsize::new(UNIVERSE / 4).unwrap();
+
+    let mut group = c.benchmark_group("LRU Integer Key Comparison (Single Threaded)");
+
+    group.bench_function(BenchmarkId::new("get_and_put", format!("IndexedLruCache-{UNIVERSE}")), |b| {
+        let mut cache = IndexedLruCache::new(CAPACITY, UNIVERSE);
+        let mut i = 0usize;
+
+        b.iter(|| {
+            i = i.wrapping_add(7919);
+            black_box(cache.get(i % UNIVERSE));
+            let _ = cache.put((i * 3) % UNIVERSE, i);
+        })
+    });
+
+    group.bench_function(BenchmarkId::new("get_and_put", format!("MyLruCache-{UNIVERSE}")), |b| {
+        let mut cache = MyLruCache::<usize, usize>::new(CAPACITY);
+        let mut i = 0usize;
+
+        b.iter(|| {
+            i = i.wrapping_add(7919);
+            black_box(cache.get(&(i % UNIVERSE)));
+            cache.put((i * 3) % UNIVERSE, i);
+        })
+    });
+
+    group.finish();
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Insert a cache's worth of new items into a full cache, so that every insertion evicts
+fn insertion_with_eviction(c: &mut Criterion) {
+    fn bench<C: CacheUnderTest<String, String>>(group: &mut BenchmarkGroup<'_, WallTime>, size: NonZeroUsize) {
+        group.bench_function(BenchmarkId::new("insertion_with_eviction", format!("{}-{size}", C::NAME)), |b| {
+            b.iter_batched(
+                || prefilled::<C>(size),
+                |mut cache| {
+                    for i in size.get()..2 * size.get() {
+                        cache.put(gen_item_key(i), gen_item_value(i as u32));
+                    }
+                },
+                criterion::BatchSize::SmallInput,
+            )
+        });
+    }
+
+    let mut group = c.benchmark_group("LRU Performance Comparison (Single Threaded)");
+
+    for cache_size in CACHE_SIZES {
+        group.throughput(Throughput::Elements(cache_size.get() as u64));
+        bench::<MyLruCache<_, _>>(&mut group, cache_size);
+        bench::<LruCache<_, _>>(&mut group, cache_size);
+        #[cfg(feature = "bench-extra")]
+        bench::<hashlink::LruCache<_, _>>(&mut group, cache_size);
+        #[cfg(feature = "bench-extra")]
+        bench::<MokaCache<_, _>>(&mut group, cache_size);
+    }
+
+    group.finish();
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Mixed reads and writes against a cache of a million entries, where most accesses miss the CPU caches.  Run once as
+/// is and once with `--features wide-index` to compare `u32` and `usize` entry indices
+fn large_cache(c: &mut Criterion) {
+    const ENTRIES: usize = 1_000_000;
+    const OPERATIONS: u64 = 10_000;
+
+    let mut group = c.benchmark_group("Large Cache");
+    let mut rng = rand::rng();
+    let mut cache = MyLruCache::new(NonZeroUsize::new(ENTRIES).unwrap());
+
+    for i in 0..ENTRIES as u64 {
+        cache.put(i, i);
+    }
+
+    println!("MyLruCache-{ENTRIES} holds {} bytes of entries and index", cache.memory_usage());
+
+    group.throughput(Throughput::Elements(OPERATIONS));
+    group.bench_function(BenchmarkId::new("mixed", format!("MyLruCache-{ENTRIES}")), |b| {
+        b.iter(|| {
+            for _ in 0..OPERATIONS {
+                // Keys span twice the capacity, so a fifth of the writes evict
+                let key = rng.random_range(0..2 * ENTRIES as u64);
+
+                match rng.random_range(0..10) {
+                    0..=7 => _ = black_box(cache.get(&key)),
+                    _ => _ = cache.put(key, key),
+                }
+            }
+        })
+    });
+
+    group.finish();
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Grow a cache from an initial capacity of 16 to a million entries, with and without incremental growth of the key
+/// index.  Criterion reports the total time; the slowest single insertions are printed separately, since they are what
+/// incremental growth is for
+fn growth_latency(c: &mut Criterion) {
+    const ENTRIES: usize = 1_000_000;
+
+    let mut group = c.benchmark_group("Growth Latency");
+    let new_cache = |incremental| {
+        LruCacheBuilder::new(NonZeroUsize::new(ENTRIES).unwrap())
+            .initial_capacity(16)
+            .incremental_growth(incremental)
+            .build::<u64, u64>()
+    };
+
+    for (name, incremental) in [("rehash", false), ("incremental", true)] {
+        let mut cache = new_cache(incremental);
+        let mut latencies: Vec<Duration> = (0..ENTRIES as u64)
+            .map(|i| {
+                let start = Instant::now();
+                cache.put(i, i);
+                start.elapsed()
+            })
+            .collect();
+
+        latencies.sort_unstable();
+        println!(
+            "{name}: p99.9 {:?}, worst {:?} per put",
+            latencies[latencies.len() * 999 / 1000],
+            latencies[latencies.len() - 1]
+        );
+
+        group.throughput(Throughput::Elements(ENTRIES as u64));
+        group.bench_function(BenchmarkId::new("fill", format!("MyLruCache-{name}-{ENTRIES}")), |b| {
+            b.iter_batched(
+                || new_cache(incremental),
+                |mut cache| (0..ENTRIES as u64).for_each(|i| _ = cache.put(i, i)),
+                criterion::BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Look up long keys in a full cache where nine lookups in ten are for keys that were never cached, with and without
+/// the doorkeeper filter
+fn miss_heavy(c: &mut Criterion) {
+    const CAPACITY: usize = 10_000;
+    const LOOKUPS: usize = 10_000;
+
+    let mut group = c.benchmark_group("Miss Heavy Lookups");
+    let key = |idx: usize| format!("{:0>256}", gen_item_key(idx));
+    let mut rng = rand::rng();
+
+    // Cached keys are 0..CAPACITY, so a lookup misses whenever it draws a larger index
+    let lookups: Vec<String> = (0..LOOKUPS)
+        .map(|_| match rng.random_range(0..10) {
+            0 => key(rng.random_range(0..CAPACITY)),
+            _ => key(rng.random_range(CAPACITY..100 * CAPACITY)),
+        })
+        .collect();
+
+    // Hashing with foldhash keeps the cost of hashing the long keys from hiding the cost of the probe
+    for (name, doorkeeper) in [("plain", false), ("doorkeeper", true)] {
+        let mut cache = LruCacheBuilder::new(NonZeroUsize::new(CAPACITY).unwrap())
+            .hasher(foldhash::fast::RandomState::default())
+            .doorkeeper(doorkeeper)
+            .build::<String, usize>();
+
+        (0..CAPACITY).for_each(|i| _ = cache.put(key(i), i));
+
+        group.throughput(Throughput::Elements(LOOKUPS as u64));
+        group.bench_function(BenchmarkId::new("get", format!("MyLruCache-{name}-{CAPACITY}")), |b| {
+            b.iter(|| lookups.iter().for_each(|k| _ = black_box(cache.get(k))))
+        });
+
+        println!("{name}: {} lookups rejected by the doorkeeper", cache.doorkeeper_rejections());
+    }
+
+    group.finish();
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Store a thousand items with a short time-to-live alongside many with a long one, then purge the short-lived items.
+/// The cost of a purge should stay the same however many long-lived items there are
+fn purge_expired(c: &mut Criterion) {
+    const EXPIRING: u64 = 1_000;
+
+    let mut group = c.benchmark_group("Purge Expired");
+
+    for long_lived in [10_000, 1_000_000] {
+        let clock = MockClock::new();
+        let capacity = NonZeroUsize::new(long_lived + EXPIRING as usize).unwrap();
+        let mut cache = MyLruCache::with_clock(capacity, clock.clone());
+        let far_future = Duration::from_secs(u32::MAX as u64);
+
+        (0..long_lived as u64).for_each(|i| _ = cache.put_with_ttl(i + EXPIRING, i, far_future));
+
+        group.throughput(Throughput::Elements(EXPIRING));
+        group.bench_function(BenchmarkId::new("purge", format!("MyLruCache-{long_lived}")), |b| {
+            b.iter(|| {
+                (0..EXPIRING).for_each(|i| _ = cache.put_with_ttl(i, i, Duration::from_secs(1)));
+                clock.advance(Duration::from_secs(1));
+                assert_eq!(cache.purge_expired(), EXPIRING as usize);
+            })
+        });
+    }
+
+    group.finish();
+}
+
 // ---------------------------------------------------------------------------------------------------------------------
 pub fn main() {
     let mut criterion: Criterion<_> = Criterion::default()
@@ -187,8 +521,17 @@ pub fn main() {
         .measurement_time(Duration::from_secs(10));
 
     insertion_without_eviction(&mut criterion);
+    insertion_with_eviction(&mut criterion);
     get(&mut criterion);
     put(&mut criterion);
+    hasher(&mut criterion);
+    long_keys(&mut criterion);
+    small_caches(&mut criterion);
+    integer_keys(&mut criterion);
+    large_cache(&mut criterion);
+    growth_latency(&mut criterion);
+    miss_heavy(&mut criterion);
+    purge_expired(&mut criterion);
 
     criterion.final_summary();
 }
@@ -0,0 +1,110 @@
+//! Throughput and hit ratio under skewed, scanning and shifting traffic, for this crate in each eviction mode, for its
+//! ARC cache and for `lru`.
+//!
+//! Each workload is a seeded sequence of keys replayed as read-through traffic: every key is read and, if missing,
+//! written.  Criterion reports the throughput; the hit ratio of each cache is printed as a table at the end.  Run with
+//! `cargo bench --bench workloads`
+mod common;
+
+use common::*;
+use criterion::{BenchmarkGroup, BenchmarkId, Criterion, Throughput, measurement::WallTime};
+use lru::LruCache;
+use lru_cache::{ArcCache, LruCache as MyLruCache};
+use std::{hint::black_box, num::NonZeroUsize, time::Duration};
+
+const SEED: u64 = 0x5eed;
+const WORKLOAD_LEN: usize = 100_000;
+
+/// Skew of the Zipfian workloads.  Web and storage traces typically fall between 0.6 and 1.2
+const ZIPF_S: [f64; 2] = [0.8, 1.1];
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The workloads run against a cache of the given size.  Keys are drawn from ten times as many keys as the cache holds
+fn workloads(size: NonZeroUsize) -> Vec<(String, Vec<u64>)> {
+    let universe = 10 * size.get();
+    let mut workloads: Vec<_> = ZIPF_S
+        .iter()
+        .map(|&s| (format!("zipfian-s{s}"), zipfian_keys(universe, s, WORKLOAD_LEN, SEED)))
+        .collect();
+
+    // 80% of requests go to 5% of the keys, which is half the size of the cache
+    workloads.push((String::from("hotspot"), hotspot_keys(universe, 0.05, 0.8, WORKLOAD_LEN, SEED)));
+
+    // Every 20000 requests, a scan of twice the cache's size flushes a plain LRU cache
+    workloads.push((
+        String::from("scan-then-return"),
+        scan_then_return_keys(universe, ZIPF_S[0], WORKLOAD_LEN, 20_000, 2 * size.get(), SEED),
+    ));
+
+    // Zipfian phases alternate with phases over a fresh working set of half the cache's size
+    workloads.push((
+        String::from("mixed"),
+        shifting_keys(universe, ZIPF_S[0], WORKLOAD_LEN, 10_000, size.get() / 2, SEED),
+    ));
+
+    workloads
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Benchmarks one cache on a workload and records its hit ratio in the summary
+fn bench_workload<C: CacheUnderTest<u64, u64>>(
+    group: &mut BenchmarkGroup<'_, WallTime>,
+    summary: &mut Vec<String>,
+    name: &str,
+    size: NonZeroUsize,
+    keys: &[u64],
+) {
+    group.bench_function(BenchmarkId::new(name, format!("{}-{size}", C::NAME)), |b| {
+        b.iter_batched(
+            || C::new(size),
+            |mut cache| black_box(replay(keys, |k| read_through(&mut cache, k))),
+            criterion::BatchSize::LargeInput,
+        )
+    });
+
+    let mut cache = C::new(size);
+    let ratio = replay(keys, |k| read_through(&mut cache, k));
+    summary.push(format!("| {name:<16} | {size:>6} | {:<22} | {ratio:>9.4} |", C::NAME));
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+fn workload_throughput(c: &mut Criterion, summary: &mut Vec<String>) {
+    let mut group = c.benchmark_group("Workloads");
+    group.throughput(Throughput::Elements(WORKLOAD_LEN as u64));
+
+    for size in CACHE_SIZES {
+        for (name, keys) in workloads(size) {
+            bench_workload::<MyLruCache<u64, u64>>(&mut group, summary, &name, size, &keys);
+            bench_workload::<WithMode<Lfu, u64, u64>>(&mut group, summary, &name, size, &keys);
+            bench_workload::<WithMode<Slru, u64, u64>>(&mut group, summary, &name, size, &keys);
+            bench_workload::<WithMode<TwoQueue, u64, u64>>(&mut group, summary, &name, size, &keys);
+            bench_workload::<WithMode<TinyLfu, u64, u64>>(&mut group, summary, &name, size, &keys);
+            bench_workload::<WithMode<S3Fifo, u64, u64>>(&mut group, summary, &name, size, &keys);
+            bench_workload::<WithMode<Fifo, u64, u64>>(&mut group, summary, &name, size, &keys);
+            bench_workload::<WithMode<Random, u64, u64>>(&mut group, summary, &name, size, &keys);
+            bench_workload::<ArcCache<u64, u64>>(&mut group, summary, &name, size, &keys);
+            bench_workload::<LruCache<u64, u64>>(&mut group, summary, &name, size, &keys);
+            #[cfg(feature = "bench-extra")]
+            bench_workload::<hashlink::LruCache<u64, u64>>(&mut group, summary, &name, size, &keys);
+            #[cfg(feature = "bench-extra")]
+            bench_workload::<MokaCache<u64, u64>>(&mut group, summary, &name, size, &keys);
+        }
+    }
+
+    group.finish();
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+pub fn main() {
+    let mut criterion: Criterion<_> = Criterion::default()
+        .configure_from_args()
+        .measurement_time(Duration::from_secs(5));
+    let mut summary = Vec::new();
+
+    workload_throughput(&mut criterion, &mut summary);
+    criterion.final_summary();
+
+    println!("| {:<16} | {:>6} | {:<22} | {:>9} |", "Workload", "Size", "Cache", "Hit ratio");
+    println!("|{:-<18}|{:->8}|{:-<24}|{:->11}|", "", "", "", "");
+    summary.iter().for_each(|row| println!("{row}"));
+}
@@ -0,0 +1,544 @@
+//! Adaptive Replacement Cache
+//!
+//! [`ArcCache`] implements ARC as described by Megiddo and Modha.  Items used once since they were inserted live in a
+//! recency list, `T1`, and items used again move to a frequency list, `T2`.  Each list is backed by a ghost list,
+//! `B1` and `B2`, that remembers the key hashes of the items recently evicted from it.  A key that returns while its
+//! hash is remembered shows which list was evicted from too eagerly, so the target size of `T1`, called `p`, moves
+//! towards that list.  The cache thereby tunes its own split between recency and frequency, where
+//! [`EvictionMode::Segmented`](crate::EvictionMode::Segmented) and
+//! [`EvictionMode::TwoQueue`](crate::EvictionMode::TwoQueue) need one chosen up front.
+//!
+//! As in the paper, `T1` and `B1` together never hold more than the capacity, and all four lists together never hold
+//! more than twice the capacity.  The ghost lists store one hash per key, not the keys or values.  Adaptation happens
+//! when a key is [`put`](ArcCache::put): a `get` that misses changes nothing, since there is no value to insert
+use crate::{
+    DefaultHashBuilder, ValidationError, check_capacity,
+    error::ensure,
+    eviction::Segment,
+    index_capacity,
+    key_index::KeyIndex,
+    nodes::{Handle, Nodes},
+};
+use alloc::collections::VecDeque;
+use core::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    mem,
+    num::NonZeroUsize,
+};
+use hashbrown::HashMap;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// One of the four lists of an [`ArcCache`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArcList {
+    /// `T1`: cached items used once since they were inserted
+    Recent,
+    /// `T2`: cached items used at least twice
+    Frequent,
+    /// `B1`: keys recently evicted from `T1`
+    RecentGhost,
+    /// `B2`: keys recently evicted from `T2`
+    FrequentGhost,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The target size of the recency list and the current size of each list of an [`ArcCache`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArcState {
+    /// `p`: how many items the cache aims to keep in the recency list
+    pub target_recent: usize,
+    pub recent: usize,
+    pub frequent: usize,
+    pub recent_ghosts: usize,
+    pub frequent_ghosts: usize,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Adaptive Replacement Cache
+///
+/// Keys are hashed using `S`, which defaults to [`DefaultHashBuilder`]
+pub struct ArcCache<K, V, S = DefaultHashBuilder> {
+    capacity: NonZeroUsize,
+    hash_builder: S,
+    /// Maps each key to the handle of its entry
+    index: KeyIndex,
+    /// Both cached lists: the frequent entries from most to least recently used, followed by the recent entries from
+    /// most to least recently used.  Recent entries are marked as [`Segment::Probation`] and frequent entries as
+    /// [`Segment::Protected`]
+    nodes: Nodes<K, V>,
+    /// The least recently used frequent entry, behind which the recent entries start
+    boundary: Option<Handle>,
+    recent_len: usize,
+    /// `p`, between `0` and the capacity
+    target_recent: usize,
+    recent_ghosts: Ghosts,
+    frequent_ghosts: Ghosts,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V> ArcCache<K, V>
+where
+    K: Eq + Hash,
+{
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        ArcCache::with_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> ArcCache<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Creates a cache that uses the given hash builder to hash keys
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capacity exceeds the number of entries the cache can address.  See the `wide-index` feature
+    pub fn with_hasher(capacity: NonZeroUsize, hash_builder: S) -> Self {
+        if let Err(e) = check_capacity(capacity) {
+            panic!("{e}");
+        }
+
+        ArcCache {
+            capacity,
+            hash_builder,
+            index: KeyIndex::with_capacity(index_capacity(capacity.get())),
+            nodes: Nodes::with_capacity(capacity.get()),
+            boundary: None,
+            recent_len: 0,
+            target_recent: 0,
+            recent_ghosts: Ghosts::default(),
+            frequent_ghosts: Ghosts::default(),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a reference to an item, moving it to the front of the frequency list
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.find(key)?;
+        self.promote(idx);
+        Some(&self.nodes.node(idx).value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a copy of an item, moving it to the front of the frequency list
+    pub fn get_cloned<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.get(key).cloned()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetch a reference to an item without counting it as a use
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).map(|idx| &self.nodes.node(idx).value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains the key.  The item's position is not changed
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the list that holds the key, or its hash if the key was recently evicted
+    pub fn list<Q>(&self, key: &Q) -> Option<ArcList>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.hash_builder.hash_one(key);
+
+        if let Some(idx) = self.find_hashed(hash, key) {
+            match self.nodes.node(idx).segment {
+                Segment::Protected => Some(ArcList::Frequent),
+                _ => Some(ArcList::Recent),
+            }
+        } else if self.recent_ghosts.contains(hash) {
+            Some(ArcList::RecentGhost)
+        } else if self.frequent_ghosts.contains(hash) {
+            Some(ArcList::FrequentGhost)
+        } else {
+            None
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an item, returning its value if it was present.  The key is not remembered in a ghost list
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.find(key)?;
+        Some(self.remove_at(idx).1)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item.
+    /// * If the item already exists, its value is replaced, it counts as a use, and the old value is returned
+    /// * If the key was recently evicted, the target size of the recency list adapts, and the item joins the frequency
+    ///   list
+    /// * Otherwise the item joins the recency list
+    ///
+    /// If the cache is full, the least recently used item of one list is evicted first and remembered in its ghost list
+    pub fn put(&mut self, key: K, new_value: V) -> Option<V> {
+        let hash = self.hash_builder.hash_one(&key);
+
+        if let Some(idx) = self.find_hashed(hash, &key) {
+            self.promote(idx);
+            return Some(mem::replace(&mut self.nodes.node_mut(idx).value, new_value));
+        }
+
+        let capacity = self.capacity.get();
+        let (b1, b2) = (self.recent_ghosts.len(), self.frequent_ghosts.len());
+
+        if self.recent_ghosts.contains(hash) {
+            // Case II: evicting from the recency list was a mistake, so give it more room
+            self.target_recent = capacity.min(self.target_recent + (b2 / b1).max(1));
+            self.replace(false);
+            self.recent_ghosts.remove(hash);
+            self.insert_frequent(hash, key, new_value);
+        } else if self.frequent_ghosts.contains(hash) {
+            // Case III: evicting from the frequency list was a mistake, so give it more room
+            self.target_recent = self.target_recent.saturating_sub((b1 / b2).max(1));
+            self.replace(true);
+            self.frequent_ghosts.remove(hash);
+            self.insert_frequent(hash, key, new_value);
+        } else {
+            // Case IV: a key the cache has not seen recently
+            if self.recent_len + b1 >= capacity {
+                if self.recent_len < capacity {
+                    self.recent_ghosts.pop_lru();
+                    self.replace(false);
+                } else if let Some(idx) = self.nodes.tail() {
+                    self.remove_at(idx);
+                }
+            } else if self.nodes.len() + b1 + b2 >= capacity {
+                if self.nodes.len() + b1 + b2 >= 2 * capacity {
+                    self.frequent_ghosts.pop_lru();
+                }
+
+                self.replace(false);
+            }
+
+            self.insert_recent(hash, key, new_value);
+        }
+
+        None
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the item that the cache would evict next, without remembering it in a ghost list
+    pub fn pop_lru(&mut self) -> Option<V> {
+        let idx = self.victim(false)?;
+        Some(self.remove_at(idx).1)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in the cache
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains no items
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 0
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the cache can hold
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.capacity
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the target size of the recency list and the size of each list, to observe how the cache adapts
+    pub fn state(&self) -> ArcState {
+        ArcState {
+            target_recent: self.target_recent,
+            recent: self.recent_len,
+            frequent: self.nodes.len() - self.recent_len,
+            recent_ghosts: self.recent_ghosts.len(),
+            frequent_ghosts: self.frequent_ghosts.len(),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Estimates the bytes of heap memory held by the cache's own structures, including the ghost lists.  Heap memory
+    /// owned by the keys and values themselves is not counted
+    pub fn memory_usage(&self) -> usize {
+        self.nodes.heap_size()
+            + self.index.allocation_size()
+            + self.recent_ghosts.allocated()
+            + self.frequent_ghosts.allocated()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes all items and forgets the ghost lists and the learnt target size
+    pub fn clear(&mut self) {
+        self.index.clear();
+        self.nodes.clear();
+        self.boundary = None;
+        self.recent_len = 0;
+        self.target_recent = 0;
+        self.recent_ghosts.clear();
+        self.frequent_ghosts.clear();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Checks that the lists agree with each other and keep within the bounds of the paper, describing the first
+    /// inconsistency found.  Intended for tests and debugging; the check walks every entry
+    pub fn debug_validate(&self) -> Result<(), ValidationError> {
+        self.nodes.validate()?;
+
+        let capacity = self.capacity.get();
+        let frequent = self
+            .nodes
+            .iter_from_head()
+            .take_while(|(_, node)| node.segment == Segment::Protected)
+            .count();
+        let last_frequent = self.nodes.iter_from_head().take(frequent).last().map(|(idx, _)| idx);
+        let misplaced = self.nodes.iter_from_head().skip(frequent).any(|(_, node)| node.segment == Segment::Protected);
+        let ghosts = self.recent_ghosts.len() + self.frequent_ghosts.len();
+
+        ensure!(self.index.len() == self.nodes.len(), "key index and lists hold different numbers of entries");
+        ensure!(!misplaced, "frequent entry found behind the recent entries");
+        ensure!(self.nodes.len() - frequent == self.recent_len, "recent entries number differently than counted");
+        ensure!(self.boundary == last_frequent, "boundary is not the last frequent entry");
+        ensure!(self.nodes.len() <= capacity, "cache holds more entries than its capacity");
+        ensure!(self.target_recent <= capacity, "target size of the recency list exceeds the capacity");
+        ensure!(self.recent_len + self.recent_ghosts.len() <= capacity, "T1 and B1 hold more than the capacity");
+        ensure!(self.nodes.len() + ghosts <= 2 * capacity, "lists hold more than twice the capacity");
+
+        for (idx, node) in self.nodes.iter_from_head() {
+            let found = self.index.find(node.hash, |&i| self.nodes.node(i).key == node.key);
+            ensure!(found == Some(idx), "key index does not point at entry {idx:?}");
+        }
+
+        self.recent_ghosts.validate()?;
+        self.frequent_ghosts.validate()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Panics with the inconsistency found by [`debug_validate`](Self::debug_validate), if there is one, in builds
+    /// with debug assertions.  Does nothing otherwise
+    #[track_caller]
+    pub fn debug_assert_valid(&self) {
+        #[cfg(debug_assertions)]
+        if let Err(error) = self.debug_validate() {
+            panic!("inconsistent cache: {error}");
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn find<Q>(&self, key: &Q) -> Option<Handle>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find_hashed(self.hash_builder.hash_one(key), key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn find_hashed<Q>(&self, hash: u64, key: &Q) -> Option<Handle>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.index.find(hash, |&i| {
+            let node = self.nodes.node(i);
+            node.hash == hash && node.key.borrow() == key
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Case I: moves a used entry to the front of the frequency list
+    fn promote(&mut self, idx: Handle) {
+        if self.nodes.node(idx).segment == Segment::Protected {
+            if self.boundary == Some(idx) {
+                self.boundary = self.nodes.prev(idx).or(self.boundary);
+            }
+        } else {
+            self.nodes.node_mut(idx).segment = Segment::Protected;
+            self.recent_len -= 1;
+            self.boundary = self.boundary.or(Some(idx));
+        }
+
+        self.nodes.move_to_front(idx);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the entry that `REPLACE` would evict: the least recently used recent entry while the recency list
+    /// exceeds its target, or reaches it when the new key was remembered by `B2`, otherwise the least recently used
+    /// frequent entry
+    fn victim(&self, in_frequent_ghosts: bool) -> Option<Handle> {
+        let from_recent = match (self.recent_len, self.boundary) {
+            (0, _) => false,
+            (_, None) => true,
+            (recent, Some(_)) => {
+                recent > self.target_recent || (in_frequent_ghosts && recent == self.target_recent)
+            }
+        };
+
+        if from_recent { self.nodes.tail() } else { self.boundary }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// `REPLACE`: if the cache is full, evicts an entry and remembers its key hash in the matching ghost list
+    fn replace(&mut self, in_frequent_ghosts: bool) {
+        if self.nodes.len() < self.capacity.get() {
+            return;
+        }
+
+        if let Some(idx) = self.victim(in_frequent_ghosts) {
+            let node = self.nodes.node(idx);
+            let hash = node.hash;
+
+            match node.segment {
+                Segment::Protected => self.frequent_ghosts.push(hash),
+                _ => self.recent_ghosts.push(hash),
+            }
+
+            self.remove_at(idx);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Stores a new item at the front of the recency list
+    fn insert_recent(&mut self, hash: u64, key: K, value: V) {
+        let idx = self.nodes.push_front(hash, key, value);
+        self.recent_len += 1;
+
+        if let Some(boundary) = self.boundary {
+            self.nodes.move_behind(idx, boundary);
+        }
+
+        self.index.insert(hash, idx, &self.nodes);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Stores a new item at the front of the frequency list
+    fn insert_frequent(&mut self, hash: u64, key: K, value: V) {
+        let idx = self.nodes.push_front(hash, key, value);
+        self.nodes.node_mut(idx).segment = Segment::Protected;
+        self.boundary = self.boundary.or(Some(idx));
+        self.index.insert(hash, idx, &self.nodes);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn remove_at(&mut self, idx: Handle) -> (K, V) {
+        let hash = self.nodes.node(idx).hash;
+        self.index.remove(hash, |&i| i == idx);
+
+        match self.nodes.node(idx).segment {
+            Segment::Protected => {
+                if self.boundary == Some(idx) {
+                    self.boundary = self.nodes.prev(idx);
+                }
+            }
+            _ => self.recent_len -= 1,
+        }
+
+        self.nodes.remove(idx)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The key hashes of recently evicted entries, from least to most recently evicted
+#[derive(Default)]
+struct Ghosts {
+    /// Stamp for the next hash, advanced on every push
+    next: u64,
+    /// The stamp and hash of each push.  A hash that has since been removed or pushed again leaves its earlier record
+    /// in place to be skipped when it comes up
+    order: VecDeque<(u64, u64)>,
+    /// The stamp of each remembered hash's latest record
+    members: HashMap<u64, u64>,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl Ghosts {
+    fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn contains(&self, hash: u64) -> bool {
+        self.members.contains_key(&hash)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Remembers a hash as the most recently evicted, rebuilding the order once out of date records outnumber the
+    /// remembered hashes
+    fn push(&mut self, hash: u64) {
+        self.next += 1;
+        self.members.insert(hash, self.next);
+        self.order.push_back((self.next, hash));
+
+        if self.order.len() > 2 * self.members.len() + 64 {
+            let members = &self.members;
+            self.order.retain(|(stamp, hash)| members.get(hash) == Some(stamp));
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn remove(&mut self, hash: u64) {
+        self.members.remove(&hash);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Forgets the least recently evicted hash
+    fn pop_lru(&mut self) {
+        while let Some((stamp, hash)) = self.order.pop_front() {
+            if self.members.get(&hash) == Some(&stamp) {
+                self.members.remove(&hash);
+                return;
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn clear(&mut self) {
+        self.order.clear();
+        self.members.clear();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Bytes allocated for the order and the set of members
+    fn allocated(&self) -> usize {
+        self.order.capacity() * mem::size_of::<(u64, u64)>() + self.members.allocation_size()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Checks that every remembered hash has exactly one current record
+    fn validate(&self) -> Result<(), ValidationError> {
+        let current = self.order.iter().filter(|(stamp, hash)| self.members.get(hash) == Some(stamp)).count();
+        ensure!(current == self.members.len(), "ghost list order and members disagree");
+
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(all(test, feature = "std"))]
+mod unit_tests;
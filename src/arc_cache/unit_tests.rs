@@ -0,0 +1,289 @@
+use super::*;
+use std::{format, string::String, vec::Vec};
+
+fn arc_cache(capacity: usize) -> ArcCache<u32, u32> {
+    ArcCache::new(NonZeroUsize::new(capacity).unwrap())
+}
+
+/// The expected state, with its fields in the order `p`, `|T1|`, `|T2|`, `|B1|`, `|B2|`
+fn state(p: usize, t1: usize, t2: usize, b1: usize, b2: usize) -> ArcState {
+    ArcState {
+        target_recent: p,
+        recent: t1,
+        frequent: t2,
+        recent_ghosts: b1,
+        frequent_ghosts: b2,
+    }
+}
+
+/// Builds the cache that the scripted tests start from: `T1` holds 5, 3 and 2, `T2` holds 4, and `B1` remembers 1
+fn cache_with_a_recent_ghost() -> ArcCache<u32, u32> {
+    let mut c = arc_cache(4);
+
+    for k in 1..=4 {
+        c.put(k, k);
+    }
+
+    c.get(&4);
+    c.put(5, 5);
+    c.debug_assert_valid();
+    c
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_move_keys_used_again_to_the_frequent_list() -> Result<(), String> {
+    let mut c = arc_cache(4);
+    c.put(1, 1);
+    c.put(2, 2);
+    let before = c.list(&1);
+
+    c.get(&1);
+    c.put(2, 20);
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (before, c.list(&1), c.list(&2), c.list(&3), c.state()) {
+        (Some(ArcList::Recent), Some(ArcList::Frequent), Some(ArcList::Frequent), None, s)
+            if s == state(0, 0, 2, 0, 0) =>
+        {
+            Ok(())
+        }
+        other => Err(format!("Expected keys 1 and 2 to move to the frequent list. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_evict_without_remembering_once_the_recent_list_fills_the_cache() -> Result<(), String> {
+    let mut c = arc_cache(4);
+
+    for k in 1..=5 {
+        c.put(k, k);
+    }
+
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (c.list(&1), c.list(&5), c.state()) {
+        (None, Some(ArcList::Recent), s) if s == state(0, 4, 0, 0, 0) => Ok(()),
+        other => Err(format!("Expected key 1 to be dropped from a full recency list. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_remember_keys_evicted_from_the_recent_list() -> Result<(), String> {
+    let c = cache_with_a_recent_ghost();
+
+    match (c.list(&1), c.list(&4), c.list(&5), c.peek(&1), c.state()) {
+        (Some(ArcList::RecentGhost), Some(ArcList::Frequent), Some(ArcList::Recent), None, s)
+            if s == state(0, 3, 1, 1, 0) =>
+        {
+            Ok(())
+        }
+        other => Err(format!("Expected key 1 to be remembered by B1. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_grow_the_recent_target_when_a_recent_ghost_returns() -> Result<(), String> {
+    let mut c = cache_with_a_recent_ghost();
+
+    // Case II: p grows by one, and the recent list is over its target so its oldest entry makes room
+    c.put(1, 10);
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (c.list(&1), c.list(&2), c.get(&1).copied(), c.state()) {
+        (Some(ArcList::Frequent), Some(ArcList::RecentGhost), Some(10), s) if s == state(1, 2, 2, 1, 0) => Ok(()),
+        other => Err(format!("Expected key 1 in T2 and key 2 in B1 with p at 1. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_shrink_the_recent_target_when_a_frequent_ghost_returns() -> Result<(), String> {
+    let mut c = cache_with_a_recent_ghost();
+    c.put(1, 1);
+
+    // Only frequent entries remain, so the next new key evicts the least recently used of them, key 4, into B2
+    c.get(&5);
+    c.get(&3);
+    c.put(6, 6);
+    c.debug_validate().map_err(|e| e.to_string())?;
+    let remembered = (c.list(&4), c.state());
+
+    // Case III: p shrinks by |B1| / |B2|, and key 6 is evicted since T1 now exceeds its target
+    c.put(4, 40);
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (remembered, c.list(&4), c.list(&6), c.state()) {
+        ((Some(ArcList::FrequentGhost), s), Some(ArcList::Frequent), Some(ArcList::RecentGhost), t)
+            if s == state(1, 1, 3, 1, 1) && t == state(0, 0, 4, 2, 0) =>
+        {
+            Ok(())
+        }
+        other => Err(format!("Expected key 4 to return from B2 and key 6 to move to B1 with p at 0. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_keep_the_ghost_lists_within_the_bounds_of_the_paper() -> Result<(), String> {
+    let mut c = arc_cache(8);
+    let mut seed = 0x2545_f491_u32;
+    let mut largest = 0;
+
+    for _ in 0..20_000 {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+
+        // A few hot keys among many that come and go
+        let key = if seed.is_multiple_of(3) { seed % 4 } else { seed % 64 };
+
+        if c.get(&key).is_none() {
+            c.put(key, key);
+        }
+
+        c.debug_validate().map_err(|e| e.to_string())?;
+        let s = c.state();
+        largest = largest.max(s.recent + s.frequent + s.recent_ghosts + s.frequent_ghosts);
+    }
+
+    match largest {
+        9..=16 => Ok(()),
+        other => Err(format!("Expected the ghost lists in use and at most 16 keys tracked. Got {other}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_adapt_towards_recency_or_frequency_as_the_traffic_changes() -> Result<(), String> {
+    let mut c = arc_cache(100);
+    let mut targets = Vec::new();
+
+    // Keys used twice fill the frequency list
+    for k in 0..100 {
+        c.put(k, k);
+        c.put(k, k);
+    }
+
+    // Every new key returns once, soon after its first use, which favours recency
+    for k in 1_000..10_000 {
+        c.put(k, k);
+
+        if k % 2 == 1 {
+            c.put(k - 51, k);
+        }
+    }
+
+    targets.push(c.state().target_recent);
+
+    // A fixed set of hot keys, each used twice on every visit between one-off keys, favours frequency
+    for k in 20_000..40_000 {
+        c.put(k, k);
+        c.put(k % 90, k);
+        c.put(k % 90, k);
+    }
+
+    c.debug_validate().map_err(|e| e.to_string())?;
+    targets.push(c.state().target_recent);
+
+    match targets[..] {
+        [recency, frequency] if recency > 50 && frequency < 20 => Ok(()),
+        _ => Err(format!("Expected p to rise under recency traffic and fall under frequency traffic. Got {targets:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn remove_should_not_remember_the_key() -> Result<(), String> {
+    let mut c = cache_with_a_recent_ghost();
+
+    match (c.remove(&5), c.remove(&4), c.remove(&1), c.list(&5), c.list(&4), c.state()) {
+        (Some(5), Some(4), None, None, None, s) if s == state(0, 2, 0, 1, 0) => Ok(()),
+        other => Err(format!("Expected removed keys to be forgotten. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn pop_lru_should_remove_the_next_victim() -> Result<(), String> {
+    let mut c = cache_with_a_recent_ghost();
+
+    // With p at 0, recent entries go first, oldest first, and then the frequent ones
+    let order: Vec<_> = core::iter::from_fn(|| c.pop_lru()).collect();
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (&order[..], c.state()) {
+        ([2, 3, 5, 4], s) if s == state(0, 0, 0, 1, 0) => Ok(()),
+        other => Err(format!("Expected the recent entries to be popped before the frequent one. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn clear_should_forget_the_ghosts_and_the_target() -> Result<(), String> {
+    let mut c = cache_with_a_recent_ghost();
+    c.put(1, 1);
+    c.clear();
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (c.is_empty(), c.list(&1), c.list(&2), c.state()) {
+        (true, None, None, s) if s == state(0, 0, 0, 0, 0) => Ok(()),
+        other => Err(format!("Expected an empty cache with no ghosts. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn memory_usage_should_count_the_ghost_lists() -> Result<(), String> {
+    let mut c = arc_cache(64);
+
+    for k in 0..64 {
+        c.put(k, k);
+        c.get(&k);
+    }
+
+    let full = c.memory_usage();
+
+    for k in 64..1_000 {
+        c.put(k, k);
+    }
+
+    let remembering = c.memory_usage();
+    let ghosts = c.state().recent_ghosts + c.state().frequent_ghosts;
+
+    match (ghosts, remembering >= full + ghosts * mem::size_of::<u64>()) {
+        (1.., true) => Ok(()),
+        other => Err(format!("Expected remembered hashes to add to {full} bytes, {remembering} used. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_stay_consistent_under_mixed_operations() -> Result<(), String> {
+    let mut c = arc_cache(16);
+    let mut seed = 0x9e37_79b9_u32;
+
+    for step in 0..20_000 {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        let key = seed % 48;
+
+        match seed % 6 {
+            0..=2 => drop(c.put(key, step)),
+            3 => drop(c.get(&key)),
+            4 => drop(c.remove(&key)),
+            _ => drop(c.pop_lru()),
+        }
+
+        c.debug_validate().map_err(|e| e.to_string())?;
+    }
+
+    match c.len() <= 16 {
+        true => Ok(()),
+        false => Err(format!("Expected at most 16 entries. Got {}", c.len())),
+    }
+}
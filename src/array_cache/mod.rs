@@ -0,0 +1,276 @@
+//! Small fixed-capacity LRU cache with linear key search
+//!
+//! [`ArrayLruCache`] keeps up to `N` entries contiguously at the front of an array and finds a key by comparing it
+//! against each occupied entry in turn.  Recency is an intrusive doubly linked list of array indices, and removing an
+//! entry moves the last one into the hole so the search never has to skip over gaps.
+//!
+//! Keys are never hashed, so they only need to implement `Eq`.  For a handful of entries a linear scan beats hashing;
+//! for more than about 64 entries use [`LruCache`](crate::LruCache) instead
+use core::{borrow::Borrow, mem};
+
+const NIL: usize = usize::MAX;
+
+// ---------------------------------------------------------------------------------------------------------------------
+struct Node<K, V> {
+    key: K,
+    value: V,
+    prev: usize,
+    next: usize,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache holding at most `N` items in a fixed-size array
+pub struct ArrayLruCache<K, V, const N: usize> {
+    nodes: [Option<Node<K, V>>; N],
+    len: usize,
+    head: usize,
+    tail: usize,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, const N: usize> ArrayLruCache<K, V, N> {
+    /// Creates an empty cache.  `N` must be greater than zero
+    pub const fn new() -> Self {
+        const { assert!(N > 0, "ArrayLruCache capacity must be greater than zero") };
+
+        ArrayLruCache {
+            nodes: [const { None }; N],
+            len: 0,
+            head: NIL,
+            tail: NIL,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in the cache
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains no items
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the cache can hold
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the most recently used item
+    pub fn pop_mru(&mut self) -> Option<V> {
+        match self.head {
+            NIL => None,
+            idx => Some(self.remove_node(idx).1),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the least recently used item
+    pub fn pop_lru(&mut self) -> Option<V> {
+        match self.tail {
+            NIL => None,
+            idx => Some(self.remove_node(idx).1),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn node(&self, idx: usize) -> &Node<K, V> {
+        self.nodes[idx].as_ref().expect("linked node is occupied")
+    }
+
+    fn node_mut(&mut self, idx: usize) -> &mut Node<K, V> {
+        self.nodes[idx].as_mut().expect("linked node is occupied")
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Detaches a node from the recency list
+    fn unlink(&mut self, idx: usize) {
+        let (prev, next) = {
+            let node = self.node(idx);
+            (node.prev, node.next)
+        };
+
+        match prev {
+            NIL => self.head = next,
+            p => self.node_mut(p).next = next,
+        }
+
+        match next {
+            NIL => self.tail = prev,
+            n => self.node_mut(n).prev = prev,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attaches a detached node to the front (MRU end) of the recency list
+    fn link_front(&mut self, idx: usize) {
+        let old_head = self.head;
+        {
+            let node = self.node_mut(idx);
+            node.prev = NIL;
+            node.next = old_head;
+        }
+
+        match old_head {
+            NIL => self.tail = idx,
+            h => self.node_mut(h).prev = idx,
+        }
+
+        self.head = idx;
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Makes the node at `idx` the most recently used
+    fn touch(&mut self, idx: usize) {
+        if self.head != idx {
+            self.unlink(idx);
+            self.link_front(idx);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the node at `idx` from the recency list and the node array
+    fn remove_node(&mut self, idx: usize) -> (K, V) {
+        self.unlink(idx);
+
+        let removed = self.nodes[idx].take().expect("removed node is occupied");
+        let last = self.len - 1;
+        self.len = last;
+
+        // Keep the occupied nodes contiguous by moving the last node into the hole
+        if idx != last {
+            self.nodes[idx] = self.nodes[last].take();
+
+            let (prev, next) = {
+                let moved = self.node(idx);
+                (moved.prev, moved.next)
+            };
+
+            match prev {
+                NIL => self.head = idx,
+                p => self.node_mut(p).next = idx,
+            }
+
+            match next {
+                NIL => self.tail = idx,
+                n => self.node_mut(n).prev = idx,
+            }
+        }
+
+        (removed.key, removed.value)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, const N: usize> ArrayLruCache<K, V, N>
+where
+    K: Eq,
+{
+    /// Returns the index of the node holding `key`
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.nodes[..self.len]
+            .iter()
+            .position(|node| node.as_ref().is_some_and(|n| n.key.borrow() == key))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a reference to an item, making it the most recently used
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let idx = self.find(key)?;
+        self.touch(idx);
+        Some(&self.node(idx).value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a copy of an item, making it the most recently used
+    pub fn get_cloned<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+        V: Clone,
+    {
+        self.get(key).cloned()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetch a reference to an item without changing its position in the recency order
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.find(key).map(|idx| &self.node(idx).value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains the key. The item's position in the recency order is not changed
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an item, returning its value if it was present
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let idx = self.find(key)?;
+        Some(self.remove_node(idx).1)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item.
+    /// * If the item already exists, it returns the old value else it returns `None`
+    /// * If the addition of the new item exceeds the cache's capacity, the oldest item is evicted before the new item is
+    ///   added
+    pub fn put(&mut self, key: K, new_value: V) -> Option<V> {
+        if let Some(idx) = self.find(&key) {
+            self.touch(idx);
+            return Some(mem::replace(&mut self.node_mut(idx).value, new_value));
+        }
+
+        if self.len == N {
+            self.pop_lru();
+        }
+
+        let idx = self.len;
+        self.len += 1;
+        self.nodes[idx] = Some(Node {
+            key,
+            value: new_value,
+            prev: NIL,
+            next: NIL,
+        });
+        self.link_front(idx);
+
+        None
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, const N: usize> Default for ArrayLruCache<K, V, N> {
+    fn default() -> Self {
+        ArrayLruCache::new()
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(all(test, feature = "std"))]
+mod unit_tests;
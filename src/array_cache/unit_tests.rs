@@ -0,0 +1,60 @@
+use super::*;
+use std::{format, string::String, vec::Vec};
+
+crate::scenarios::cache_scenarios!(ArrayLruCache::<String, String, 10>::new);
+
+// -----------------------------------------------------------------------------------------------------------------
+/// A key that can be compared but not hashed
+#[derive(Debug, PartialEq, Eq)]
+struct Colour(&'static str);
+
+#[test]
+fn should_cache_keys_that_are_not_hashable() -> Result<(), String> {
+    let mut c: ArrayLruCache<Colour, u32, 2> = ArrayLruCache::new();
+    c.put(Colour("red"), 1);
+    c.put(Colour("green"), 2);
+    c.put(Colour("blue"), 3);
+
+    match (c.contains_key(&Colour("red")), c.get(&Colour("blue"))) {
+        (false, Some(3)) => Ok(()),
+        other => Err(format!("Expected 'red' evicted and 'blue' present. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_keep_recency_order_when_removing_from_the_middle() -> Result<(), String> {
+    let mut c: ArrayLruCache<u32, u32, 4> = ArrayLruCache::new();
+
+    for i in 0..4 {
+        c.put(i, i);
+    }
+
+    // Removing key 1 moves the last stored node into its slot
+    c.remove(&1);
+    c.put(4, 4);
+
+    let mut popped = Vec::new();
+    while let Some(v) = c.pop_lru() {
+        popped.push(v);
+    }
+
+    match popped.as_slice() {
+        [0, 2, 3, 4] => Ok(()),
+        other => Err(format!("Expected [0, 2, 3, 4]. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_be_usable_in_a_static() -> Result<(), String> {
+    static CACHE: std::sync::Mutex<ArrayLruCache<u32, u32, 4>> = std::sync::Mutex::new(ArrayLruCache::new());
+
+    let mut c = CACHE.lock().map_err(|e| e.to_string())?;
+    c.put(1, 10);
+
+    match c.get(&1) {
+        Some(10) => Ok(()),
+        other => Err(format!("Expected Some(10). Got {other:?}")),
+    }
+}
@@ -0,0 +1,62 @@
+//! Read-through loading for [`AsyncLruCache`](super::AsyncLruCache)
+use core::{fmt, future::Future};
+use std::{error::Error, sync::Arc};
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Loads the value for a key missing from an [`AsyncLruCache`](super::AsyncLruCache) built with
+/// [`with_loader`](super::AsyncLruCache::with_loader).  Implementations may be written with `async fn load`, as long as
+/// the future it returns is `Send`
+pub trait AsyncCacheLoader<K, V>: Send + Sync {
+    type Error: Send + Sync + 'static;
+
+    fn load(&self, key: &K) -> impl Future<Output = Result<V, Self::Error>> + Send;
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The loader of an [`AsyncLruCache`](super::AsyncLruCache) that has none, whose `get` only looks in the cache
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoLoader;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The loader of an [`AsyncLruCache`](super::AsyncLruCache) built with
+/// [`with_loader`](super::AsyncLruCache::with_loader), whose `get` reads through to it.  Kept apart from [`NoLoader`]
+/// by its type, since a cache's `get` is synchronous without a loader and asynchronous with one
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReadThrough<L>(pub(super) L);
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Why a read-through [`get`](super::AsyncLruCache::get) returned no value
+#[derive(Debug)]
+pub enum LoadError<E> {
+    /// The key was missing and the cache has been closed, so the value was not loaded
+    Closed,
+    /// The loader failed.  Every caller that awaited the same load receives the same error, and nothing is cached
+    Failed(Arc<E>),
+}
+
+impl<E> Clone for LoadError<E> {
+    fn clone(&self) -> Self {
+        match self {
+            LoadError::Closed => LoadError::Closed,
+            LoadError::Failed(e) => LoadError::Failed(Arc::clone(e)),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for LoadError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Closed => write!(f, "the cache has been closed"),
+            LoadError::Failed(e) => write!(f, "loading the value failed: {e}"),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for LoadError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Closed => None,
+            LoadError::Failed(e) => Some(&**e),
+        }
+    }
+}
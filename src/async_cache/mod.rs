@@ -0,0 +1,791 @@
+//! LRU cache for async code
+//!
+//! [`AsyncLruCache`] shares an [`LruCache`] between tasks.  Its locks are held only for the map operation inside each
+//! call and never across an `.await`, so its methods can be called from async code, such as a web request handler,
+//! without stalling the executor.
+//!
+//! # Loading missing values
+//!
+//! [`AsyncLruCache::get_with`] awaits at most one loading future per key at a time.  Other tasks asking for the same
+//! key while it loads await that future's value instead of running their own.  If the task awaiting the loading future
+//! is cancelled, one of the waiting tasks runs its own future instead, so a cancelled load never leaves the others
+//! waiting
+//!
+//! A cache built with [`AsyncLruCache::with_loader`] reads through to an [`AsyncCacheLoader`]: its
+//! [`get`](AsyncLruCache::get) loads a missing value the same way, once however many tasks ask for it.  If the loader
+//! fails, every task that awaited the load receives the error, and the next `get` tries again, unless
+//! [`with_negative_caching`](AsyncLruCache::with_negative_caching) recognises the error as meaning that the key has no
+//! value.  Such an error is then returned without calling the loader until its own time-to-live runs out, as it is by
+//! [`ReadThroughCache`](crate::ReadThroughCache)
+//!
+//! After [`with_refresh_ahead`](AsyncLruCache::with_refresh_ahead), a read-through `get` that finds an item in the
+//! last part of its time-to-live returns it straight away and flags the key to be reloaded by
+//! [`run_pending_refreshes`](AsyncLruCache::run_pending_refreshes), which
+//! [`spawn_refreshes`](AsyncLruCache::spawn_refreshes) calls on a timer.  A reload that succeeds replaces the value and
+//! restarts its time-to-live, and one that fails leaves the old value until it expires.
+//! [`with_stale_while_revalidate`](AsyncLruCache::with_stale_while_revalidate) serves items for a grace period after
+//! they expire, flagged as stale by [`get_cached`](AsyncLruCache::get_cached), and flags their keys the same way.
+//! However many tasks find a stale item, its key is reloaded once.  Both options work with negative caching as they do
+//! in [`ReadThroughCache`](crate::ReadThroughCache)
+//!
+//! # Maintenance
+//!
+//! Work that the cache defers, such as replaying promotions recorded in a read buffer, is otherwise done by whichever
+//! call comes next.  [`AsyncLruCache::spawn_maintenance`] does it on a timer instead, off the path of requests, and
+//! [`AsyncLruCache::run_pending_tasks`] does it on demand.  The maintenance task only holds a weak reference to the
+//! cache, and finishes at its next tick after the cache is closed or dropped
+//!
+//! # Eviction events
+//!
+//! [`AsyncLruCache::eviction_events`] subscribes to evicted items as
+//! [`ConcurrentLruCache::eviction_events`] does.  Await them with [`EvictionEvents::recv_async`].  Under
+//! [`OverflowPolicy::Block`], a full queue blocks the thread whose write caused the eviction, which stalls a runtime
+//! worker, so async subscribers are better served by the default [`OverflowPolicy::DropOldest`]
+//!
+//! # Shutting down
+//!
+//! [`AsyncLruCache::close`] stops the cache accepting changes, waits for the values already being loaded to arrive,
+//! and then waits for the eviction event subscriber to take the events queued for it, so that nothing handed to the
+//! cache before shutdown is lost.  Afterwards, the methods that would change the cache return [`Closed`]
+use crate::{
+    CacheStats, CachedValue, ConcurrentLruCache, DefaultHashBuilder, EvictionEvents, Lookup, LruCache, OverflowPolicy,
+    sync::{Mutex, MutexGuard},
+};
+use core::{any::Any, fmt, future::Future, mem, pin::pin};
+pub use loader::{AsyncCacheLoader, LoadError, NoLoader, ReadThrough};
+use std::{
+    borrow::Borrow,
+    collections::{HashMap, HashSet},
+    error::Error,
+    hash::{BuildHasher, Hash},
+    num::NonZeroUsize,
+    sync::{
+        Arc, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{Notify, OnceCell},
+    task::JoinHandle,
+    time::{self, Instant, MissedTickBehavior},
+};
+
+mod loader;
+
+/// A loader's error, shared between every task awaiting the load.  Only an [`AsyncCacheLoader::Error`] is ever stored,
+/// so it can always be downcast back
+type Failure = Arc<dyn Any + Send + Sync>;
+
+/// Outcome of loading one key, shared between every task awaiting it
+type Cell<V> = OnceCell<Result<V, Failure>>;
+
+/// Cells for the keys whose values are being loaded
+type InFlight<K, V> = Mutex<HashMap<K, Arc<Cell<V>>>>;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The keys a loader recently reported to have no value, each with the error it reported
+struct Absent<K> {
+    errors: ConcurrentLruCache<K, Failure>,
+    ttl: Duration,
+    is_not_found: Box<dyn Fn(&Failure) -> bool + Send + Sync>,
+}
+
+/// What [`AsyncLruCache::lookup`] found, with the "not found" error for [`AsyncLruCache::get`] to return
+enum Fetched<V> {
+    Found(CachedValue<V>),
+    NotFound(Failure),
+    NegativeHit(Failure),
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Returned by the methods of an [`AsyncLruCache`] that would change it, once it has been closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Closed;
+
+impl fmt::Display for Closed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the cache has been closed")
+    }
+}
+
+impl Error for Closed {}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache that can be shared between tasks, with coalesced loading of missing values
+pub struct AsyncLruCache<K, V, S = DefaultHashBuilder, L = NoLoader> {
+    cache: ConcurrentLruCache<K, V, S>,
+    loader: L,
+    loading: InFlight<K, V>,
+    /// Notified whenever a key stops loading
+    settled: Notify,
+    /// Only set while holding the lock on `loading`, so that no load can start once [`close`](Self::close) has seen
+    /// that none are left
+    closed: AtomicBool,
+    /// Present if absences are cached, set through [`with_negative_caching`](AsyncLruCache::with_negative_caching)
+    absent: Option<Absent<K>>,
+    /// Fraction of its time-to-live below which an item is reloaded, set through
+    /// [`with_refresh_ahead`](AsyncLruCache::with_refresh_ahead)
+    refresh_ahead: Option<f64>,
+    /// How long after expiry an item is served stale, set through
+    /// [`with_stale_while_revalidate`](AsyncLruCache::with_stale_while_revalidate)
+    stale_grace: Option<Duration>,
+    /// Keys flagged to be reloaded by [`run_pending_refreshes`](AsyncLruCache::run_pending_refreshes)
+    pending: Mutex<HashSet<K>>,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V> AsyncLruCache<K, V>
+where
+    K: Eq + Hash,
+{
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        AsyncLruCache::from(LruCache::new(capacity))
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, L> AsyncLruCache<K, V, DefaultHashBuilder, ReadThrough<L>>
+where
+    K: Eq + Hash,
+    L: AsyncCacheLoader<K, V>,
+{
+    /// Creates a cache whose [`get`](Self::get) loads missing values with `loader`
+    pub fn with_loader(capacity: NonZeroUsize, loader: L) -> Self {
+        AsyncLruCache::with_parts(LruCache::new(capacity), ReadThrough(loader))
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> AsyncLruCache<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Creates a cache that uses the given hash builder to hash keys
+    pub fn with_hasher(capacity: NonZeroUsize, hash_builder: S) -> Self {
+        AsyncLruCache::from(LruCache::with_hasher(capacity, hash_builder))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a copy of an item, making it the most recently used.  Does not wait for a value being loaded
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.get_if_present(key)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S, L> AsyncLruCache<K, V, S, ReadThrough<L>>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+    L: AsyncCacheLoader<K, V>,
+{
+    /// Reads through to `loader` from a cache configured elsewhere, for instance by
+    /// [`LruCacheBuilder`](crate::LruCacheBuilder), whose time-to-live and other settings then apply to loaded values
+    pub fn with_cache_and_loader(cache: LruCache<K, V, S>, loader: L) -> Self {
+        AsyncLruCache::with_parts(cache, ReadThrough(loader))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Remembers for `ttl` that a key has no value, whenever the loader fails with an error for which `is_not_found`
+    /// returns `true`, so that lookups of the key return the error without calling the loader.  Other errors are never
+    /// remembered.  Up to the cache's capacity of keys are remembered apart from its values, measured on the cache's
+    /// clock if it has one
+    pub fn with_negative_caching(self, ttl: Duration, is_not_found: fn(&L::Error) -> bool) -> Self {
+        let mut errors = LruCache::new(self.capacity());
+        errors.clock = self.cache.with_lock(|cache| cache.clock.clone());
+
+        let absent = Absent {
+            errors: ConcurrentLruCache::from(errors),
+            ttl,
+            is_not_found: Box::new(move |failure| failure.downcast_ref().is_some_and(is_not_found)),
+        };
+
+        AsyncLruCache {
+            absent: Some(absent),
+            ..self
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Flags a key for reloading whenever a `get` finds its item with less than `fraction` of its time-to-live left,
+    /// as [`ReadThroughCache::with_refresh_ahead`](crate::ReadThroughCache::with_refresh_ahead) does.  Flagged keys are
+    /// reloaded by [`run_pending_refreshes`](Self::run_pending_refreshes)
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fraction` is not between 0 and 1
+    pub fn with_refresh_ahead(self, fraction: f64) -> Self {
+        assert!((0.0..=1.0).contains(&fraction), "refresh-ahead fraction must be between 0 and 1, not {fraction}");
+
+        AsyncLruCache {
+            refresh_ahead: Some(fraction),
+            ..self
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Serves an item for up to `grace` after it expires, flagged as stale, while its key waits to be reloaded by
+    /// [`run_pending_refreshes`](Self::run_pending_refreshes).  Once `grace` has passed, a `get` of the key misses and
+    /// awaits the load.  [`get_with`](Self::get_with) never serves stale items
+    pub fn with_stale_while_revalidate(self, grace: Duration) -> Self {
+        AsyncLruCache {
+            stale_grace: Some(grace),
+            ..self
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetches a copy of an item, making it the most recently used, or loads and inserts its value if the key is
+    /// missing.  If another task is already loading the key, awaits that task's value instead
+    pub async fn get(&self, key: &K) -> Result<V, LoadError<L::Error>>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.get_cached(key).await.map(|found| found.value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetches a copy of an item like [`get`](Self::get), saying whether it was served stale
+    pub async fn get_cached(&self, key: &K) -> Result<CachedValue<V>, LoadError<L::Error>>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        match self.fetch(key).await? {
+            Fetched::Found(found) => Ok(found),
+            Fetched::NotFound(failure) | Fetched::NegativeHit(failure) => Err(failed(failure)),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetches a copy of an item like [`get`](Self::get), but reports a key with no value as [`Lookup::NotFound`], or
+    /// [`Lookup::NegativeHit`] if negative caching answered without calling the loader.  Only transient errors are
+    /// returned as errors
+    pub async fn lookup(&self, key: &K) -> Result<Lookup<V>, LoadError<L::Error>>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        Ok(match self.fetch(key).await? {
+            Fetched::Found(found) => Lookup::Found(found.value),
+            Fetched::NotFound(_) => Lookup::NotFound,
+            Fetched::NegativeHit(_) => Lookup::NegativeHit,
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    async fn fetch(&self, key: &K) -> Result<Fetched<V>, LoadError<L::Error>>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        // A key with a value has no remembered absence, since every way of inserting the value forgets it
+        if let Some(failure) = self.absent.as_ref().and_then(|absent| absent.errors.get(key)) {
+            return Ok(Fetched::NegativeHit(failure));
+        }
+
+        let load_or_wait = match self.find_or_join(key, self.stale_grace).map_err(|Closed| LoadError::Closed)? {
+            Ok(found) => {
+                if found.is_stale() || self.is_due_for_refresh(key) {
+                    self.flag_for_reload(key);
+                }
+
+                return Ok(Fetched::Found(found));
+            }
+            Err(loading) => loading,
+        };
+
+        let outcome = load_or_wait
+            .cell()
+            .get_or_init(|| async {
+                let stopwatch = self.cache.load_started();
+
+                match self.loader.0.load(key).await {
+                    Ok(value) => {
+                        self.cache.put_loaded(key.clone(), value.clone(), stopwatch);
+                        Ok(value)
+                    }
+                    Err(e) => {
+                        let failure = Arc::new(e) as Failure;
+
+                        // Remembered before the cell is set, so that later callers find the absence
+                        if let Some(absent) = &self.absent
+                            && (absent.is_not_found)(&failure)
+                        {
+                            let remembered = (key.clone(), Arc::clone(&failure));
+                            absent.errors.with_lock(|errors| errors.put_with_ttl(remembered.0, remembered.1, absent.ttl));
+                        }
+
+                        Err(failure)
+                    }
+                }
+            })
+            .await
+            .clone();
+
+        match outcome {
+            Ok(value) => Ok(Fetched::Found(CachedValue { value, staleness: None })),
+            Err(failure) if self.absent.as_ref().is_some_and(|absent| (absent.is_not_found)(&failure)) => {
+                Ok(Fetched::NotFound(failure))
+            }
+            Err(failure) => Err(failed(failure)),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn is_due_for_refresh(&self, key: &K) -> bool {
+        self.refresh_ahead.is_some_and(|fraction| {
+            self.cache.with_lock(|cache| cache.entry_info(key)).is_some_and(|info| info.is_due_for_refresh(fraction))
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn flag_for_reload(&self, key: &K)
+    where
+        K: Clone,
+    {
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if !pending.contains(key) {
+            pending.insert(key.clone());
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Reloads the keys flagged by refresh-ahead or stale-while-revalidate, one at a time.  A reload that succeeds
+    /// replaces the value and restarts its time-to-live.  One that fails leaves the old value, unless negative caching
+    /// recognises the error as "not found", when the value is removed and the absence remembered.  Keys whose items
+    /// have been removed, or are past any grace period, since they were flagged are not reloaded, and nothing is
+    /// reloaded once the cache is closed
+    pub async fn run_pending_refreshes(&self)
+    where
+        K: Clone,
+    {
+        let grace = self.stale_grace.unwrap_or_default();
+        let pending = mem::take(&mut *self.pending.lock().unwrap_or_else(PoisonError::into_inner));
+
+        for key in pending {
+            if self.is_closed() || !self.cache.with_lock(|cache| cache.is_retained(&key, grace)) {
+                continue;
+            }
+
+            let stopwatch = self.cache.load_started();
+            let loaded = self.loader.0.load(&key).await;
+
+            if self.is_closed() {
+                continue;
+            }
+
+            match loaded {
+                Ok(value) => self.cache.put_loaded(key, value, stopwatch),
+                Err(e) => {
+                    let failure = Arc::new(e) as Failure;
+
+                    if let Some(absent) = &self.absent
+                        && (absent.is_not_found)(&failure)
+                    {
+                        self.cache.remove(&key);
+                        absent.errors.with_lock(|errors| errors.put_with_ttl(key, failure, absent.ttl));
+                    }
+                }
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Spawns a task on the current Tokio runtime that does what [`spawn_maintenance`](Self::spawn_maintenance)'s
+    /// does, then calls [`run_pending_refreshes`](Self::run_pending_refreshes), every `interval`.  The task finishes
+    /// once the cache has been closed or dropped
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a Tokio runtime, or if `interval` is zero
+    pub fn spawn_refreshes(self: &Arc<Self>, interval: Duration) -> JoinHandle<()>
+    where
+        K: Clone + Send + Sync + 'static,
+        V: Send + Sync + 'static,
+        S: Send + Sync + 'static,
+        L: 'static,
+    {
+        self.spawn_every(interval, |cache| async move {
+            cache.run_pending_tasks();
+            cache.run_pending_refreshes().await
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S, L> AsyncLruCache<K, V, S, L>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Sets how many eviction events can wait for a subscriber, and what happens to further events when that many are
+    /// waiting.  See [`ConcurrentLruCache::with_event_queue`]
+    pub fn with_event_queue(self, length: NonZeroUsize, overflow: OverflowPolicy) -> Self {
+        AsyncLruCache {
+            cache: self.cache.with_event_queue(length, overflow),
+            ..self
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetches a copy of an item, making it the most recently used, or awaits `load` and inserts its value if the key
+    /// is missing.  If another task is already loading the key, awaits that task's value instead, and only awaits
+    /// `load` if that task is cancelled.  The time `load` takes is counted in [`CacheStats::load_time`]
+    pub async fn get_with(&self, key: K, load: impl Future<Output = V>) -> Result<V, Closed>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.check_open()?;
+
+        let load_or_wait = match self.find_or_join(&key, None)? {
+            Ok(found) => return Ok(found.value),
+            Err(loading) => loading,
+        };
+
+        // Whichever task's future runs puts the value in the cache before the cell is set, so that by the time the
+        // cell is forgotten, later callers find the value in the cache
+        let mut load = Some(load);
+        let outcome = load_or_wait
+            .cell()
+            .get_or_init(|| async {
+                let stopwatch = self.cache.load_started();
+                let value = load.take().expect("only the first initializer runs").await;
+                self.forget_absence(&key);
+                self.cache.put_loaded(key.clone(), value.clone(), stopwatch);
+                Ok(value)
+            })
+            .await
+            .clone();
+
+        match (outcome, load) {
+            (Ok(value), _) => Ok(value),
+            // The key's read-through load failed, so this task falls back on its own future
+            (Err(_), Some(load)) => {
+                let stopwatch = self.cache.load_started();
+                let value = load.await;
+                self.forget_absence(&key);
+                self.cache.put_loaded(key.clone(), value.clone(), stopwatch);
+                Ok(value)
+            }
+            (Err(_), None) => unreachable!("only the loader's errors are shared between waiters"),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns a copy of the cached value, which may have expired less than `grace` ago, or else a [`Loading`] guard
+    /// on the cell that the key's loader will set.  Starting a load fails once the cache is closed, but finding a value
+    /// does not
+    fn find_or_join<'a>(
+        &'a self,
+        key: &'a K,
+        grace: Option<Duration>,
+    ) -> Result<Result<CachedValue<V>, Loading<'a, K, V>>, Closed>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let found = self.cache.with_lock(|cache| {
+            let found = match grace {
+                Some(grace) => cache.get_or_stale(key, grace).map(|(value, staleness)| CachedValue {
+                    value: value.clone(),
+                    staleness,
+                }),
+                None => cache.get_cloned(key).map(|value| CachedValue { value, staleness: None }),
+            };
+
+            match found {
+                Some(found) => Ok(Ok(found)),
+                None => {
+                    let mut loading = in_flight(&self.loading);
+                    self.check_open()?;
+                    Ok(Err(Arc::clone(loading.entry(key.clone()).or_insert_with(|| Arc::new(OnceCell::new())))))
+                }
+            }
+        })?;
+
+        Ok(found.map_err(|cell| Loading {
+            loading: &self.loading,
+            settled: &self.settled,
+            key,
+            cell: Some(cell),
+        }))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a copy of an item, making it the most recently used.  Never loads a missing value, nor waits
+    /// for one being loaded
+    pub fn get_if_present<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.cache.get(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains the key. The item's position in the recency order is not changed
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.contains_key(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item, returning the old value if the key was already present.  A value still being loaded for
+    /// the key will replace this one when it arrives.  Forgets that the key had no value
+    pub fn insert(&self, key: K, new_value: V) -> Result<Option<V>, Closed> {
+        self.check_open()?;
+        self.forget_absence(&key);
+        Ok(self.cache.put(key, new_value))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an item, returning its value if it was present.  A value still being loaded for the key is inserted
+    /// when it arrives
+    pub fn invalidate<Q>(&self, key: &Q) -> Result<Option<V>, Closed>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.check_open()?;
+        self.forget_absence(key);
+        Ok(self.cache.remove(key))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn forget_absence<Q>(&self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if let Some(absent) = &self.absent {
+            absent.errors.remove(key);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in the cache
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains no items
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the cache can hold
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.cache.capacity()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the activity counted since the cache was created, including the time taken by the loads of
+    /// [`get_with`](Self::get_with)
+    pub fn stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes all items.  Values still being loaded are inserted when they arrive
+    pub fn clear(&self) -> Result<(), Closed> {
+        self.check_open()?;
+        self.cache.clear();
+
+        if let Some(absent) = &self.absent {
+            absent.errors.clear();
+        }
+
+        Ok(())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Does the cache's deferred housekeeping now.  See [`LruCache::run_pending_tasks`]
+    pub fn run_pending_tasks(&self) {
+        self.cache.run_pending_tasks()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Subscribes to the items evicted from now on, ending any previous subscription
+    pub fn eviction_events(&self) -> EvictionEvents<K, V> {
+        self.cache.eviction_events()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Stops the cache accepting changes, then waits for every value being loaded by [`get_with`](Self::get_with) to
+    /// arrive and for the [`eviction_events`](Self::eviction_events) subscriber to take the events queued for it.
+    /// Reads carry on seeing whatever is left in the cache.  Closing again, or from several tasks at once, is harmless.
+    /// Called from the task that receives eviction events, it does not wait for that task to take them
+    pub async fn close(&self) {
+        {
+            let _loading = in_flight(&self.loading);
+            self.closed.store(true, Ordering::Relaxed);
+        }
+
+        loop {
+            let mut settled = pin!(self.settled.notified());
+            settled.as_mut().enable();
+
+            if in_flight(&self.loading).is_empty() {
+                break;
+            }
+
+            settled.await;
+        }
+
+        self.cache.finish_events().await
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` once [`close`](Self::close) has been called
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn check_open(&self) -> Result<(), Closed> {
+        match self.is_closed() {
+            true => Err(Closed),
+            false => Ok(()),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Spawns a task on the current Tokio runtime that calls [`run_pending_tasks`](Self::run_pending_tasks) every
+    /// `interval`, starting one interval from now.  The task finishes once the cache has been closed or dropped
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a Tokio runtime, or if `interval` is zero
+    pub fn spawn_maintenance(self: &Arc<Self>, interval: Duration) -> JoinHandle<()>
+    where
+        K: Send + Sync + 'static,
+        V: Send + Sync + 'static,
+        S: Send + Sync + 'static,
+        L: Send + Sync + 'static,
+    {
+        self.spawn_every(interval, |cache| async move { cache.run_pending_tasks() })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Spawns a task that passes the cache to `task` every `interval`, for as long as it is open and not dropped
+    fn spawn_every<F, T>(self: &Arc<Self>, interval: Duration, task: F) -> JoinHandle<()>
+    where
+        Self: Send + Sync + 'static,
+        F: Fn(Arc<Self>) -> T + Send + 'static,
+        T: Future<Output = ()> + Send,
+    {
+        let cache = Arc::downgrade(self);
+        let mut ticks = time::interval_at(Instant::now() + interval, interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        tokio::spawn(async move {
+            loop {
+                ticks.tick().await;
+
+                match cache.upgrade() {
+                    Some(cache) if !cache.is_closed() => task(cache).await,
+                    _ => break,
+                }
+            }
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Shares an existing cache, such as one configured through [`LruCacheBuilder`](crate::LruCacheBuilder)
+impl<K, V, S> From<LruCache<K, V, S>> for AsyncLruCache<K, V, S> {
+    fn from(cache: LruCache<K, V, S>) -> Self {
+        AsyncLruCache::with_parts(cache, NoLoader)
+    }
+}
+
+impl<K, V, S, L> AsyncLruCache<K, V, S, L> {
+    fn with_parts(cache: LruCache<K, V, S>, loader: L) -> Self {
+        AsyncLruCache {
+            cache: ConcurrentLruCache::from(cache),
+            loader,
+            loading: Mutex::new(HashMap::new()),
+            settled: Notify::new(),
+            closed: AtomicBool::new(false),
+            absent: None,
+            refresh_ahead: None,
+            stale_grace: None,
+            pending: Mutex::new(HashSet::new()),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Recovers the loader's error from a failure shared between waiters
+fn failed<E: Send + Sync + 'static>(failure: Failure) -> LoadError<E> {
+    LoadError::Failed(failure.downcast().expect("only the loader's errors are shared between waiters"))
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Locks the in-flight keys.  The lock is never held while awaiting, so a poisoned map is still consistent
+fn in_flight<K, V>(loading: &InFlight<K, V>) -> MutexGuard<'_, HashMap<K, Arc<Cell<V>>>> {
+    loading.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A task's interest in a key being loaded.  Dropping it, whether the task finished or was cancelled, forgets the key
+/// once its value has been set or no other task is waiting for it
+struct Loading<'a, K, V>
+where
+    K: Eq + Hash,
+{
+    loading: &'a InFlight<K, V>,
+    settled: &'a Notify,
+    key: &'a K,
+    /// Only taken when dropped
+    cell: Option<Arc<Cell<V>>>,
+}
+
+impl<K, V> Loading<'_, K, V>
+where
+    K: Eq + Hash,
+{
+    fn cell(&self) -> &Cell<V> {
+        self.cell.as_deref().expect("the cell is only taken when dropped")
+    }
+}
+
+impl<K, V> Drop for Loading<'_, K, V>
+where
+    K: Eq + Hash,
+{
+    fn drop(&mut self) {
+        let mut loading = in_flight(self.loading);
+
+        if let Some(cell) = self.cell.take() {
+            // Clones are only made and dropped under the lock, so the count is exact: the map's and this one
+            let abandoned = Arc::strong_count(&cell) == 2;
+
+            if (cell.initialized() || abandoned) && loading.get(self.key).is_some_and(|c| Arc::ptr_eq(c, &cell)) {
+                loading.remove(self.key);
+                self.settled.notify_waiters();
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,692 @@
+use super::*;
+use crate::{LruCacheBuilder, test_utils::MockClock};
+use std::{
+    format,
+    string::String,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+    vec::Vec,
+};
+use tokio::{sync::Notify, task::JoinSet, time::timeout};
+
+/// Long enough for a test that would otherwise hang to fail instead
+const PATIENCE: Duration = Duration::from_secs(5);
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_be_send_and_sync() -> Result<(), String> {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<AsyncLruCache<String, String>>();
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn get_with_should_run_one_load_for_many_tasks() -> Result<(), String> {
+    const TASKS: usize = 100;
+
+    let cache = Arc::new(AsyncLruCache::new(NonZeroUsize::new(4).unwrap()));
+    let loads = Arc::new(AtomicUsize::new(0));
+    let mut tasks = JoinSet::new();
+
+    for _ in 0..TASKS {
+        let (cache, loads) = (Arc::clone(&cache), Arc::clone(&loads));
+
+        tasks.spawn(async move {
+            cache
+                .get_with(String::from("popular"), async {
+                    loads.fetch_add(1, Ordering::Relaxed);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    String::from("value")
+                })
+                .await
+        });
+    }
+
+    let values: Vec<Result<String, Closed>> = timeout(PATIENCE, tasks.join_all())
+        .await
+        .map_err(|_| "Timed out waiting for the tasks")?;
+
+    match (loads.load(Ordering::Relaxed), values.iter().filter(|v| v.as_deref() == Ok("value")).count()) {
+        (1, TASKS) => (),
+        other => return Err(format!("Expected one load whose value reached all {TASKS} tasks. Got {other:?}")),
+    }
+
+    match (cache.get("popular"), in_flight(&cache.loading).len()) {
+        (Some(value), 0) if value == "value" => Ok(()),
+        other => Err(format!("Expected the value to be cached and no load left in flight. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn get_with_should_hand_over_to_a_waiter_when_the_load_is_cancelled() -> Result<(), String> {
+    let cache = Arc::new(AsyncLruCache::new(NonZeroUsize::new(4).unwrap()));
+    let started = Arc::new(Notify::new());
+
+    // This load never finishes, so it only ends by being cancelled
+    let stuck = {
+        let (cache, started) = (Arc::clone(&cache), Arc::clone(&started));
+
+        tokio::spawn(async move {
+            cache
+                .get_with(1, async {
+                    started.notify_one();
+                    std::future::pending::<u32>().await
+                })
+                .await
+        })
+    };
+
+    started.notified().await;
+
+    let waiter = {
+        let cache = Arc::clone(&cache);
+        tokio::spawn(async move { cache.get_with(1, async { 42 }).await })
+    };
+
+    // Let the waiter start waiting for the stuck load before cancelling it
+    tokio::task::yield_now().await;
+    stuck.abort();
+
+    let value = timeout(PATIENCE, waiter)
+        .await
+        .map_err(|_| "The waiter was left waiting for the cancelled load")?
+        .map_err(|e| format!("The waiter failed: {e}"))?;
+
+    match (value, cache.get(&1), in_flight(&cache.loading).len()) {
+        (Ok(42), Some(42), 0) => Ok(()),
+        other => Err(format!("Expected the waiter to load the value itself. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn get_with_should_forget_a_cancelled_load_that_nobody_awaits() -> Result<(), String> {
+    let cache = AsyncLruCache::new(NonZeroUsize::new(4).unwrap());
+
+    // Polling the load once and then dropping it cancels it part way through
+    let cancelled = timeout(Duration::from_millis(10), cache.get_with(1, std::future::pending::<u32>())).await;
+
+    if cancelled.is_ok() {
+        return Err(String::from("Expected the load to be cancelled"));
+    }
+
+    let in_flight_after_cancel = in_flight(&cache.loading).len();
+
+    match (in_flight_after_cancel, cache.get_with(1, async { 7 }).await, cache.len()) {
+        (0, Ok(7), 1) => Ok(()),
+        other => Err(format!("Expected the cancelled load to be forgotten and the next to succeed. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn should_insert_get_and_invalidate() -> Result<(), String> {
+    let cache = AsyncLruCache::new(NonZeroUsize::new(2).unwrap());
+    let _ = cache.insert("a", 1);
+    let _ = cache.insert("b", 2);
+    let _ = cache.get(&"a");
+    let _ = cache.insert("c", 3);
+
+    let loaded = cache.get_with("a", async { 0 }).await;
+
+    match (loaded, cache.contains_key(&"b"), cache.invalidate(&"c"), cache.len()) {
+        (Ok(1), false, Ok(Some(3)), 1) => Ok(()),
+        other => Err(format!("Expected b to be evicted and c invalidated, leaving a. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test(start_paused = true)]
+async fn maintenance_should_do_deferred_work_without_foreground_calls() -> Result<(), String> {
+    const INTERVAL: Duration = Duration::from_secs(1);
+
+    let cache = Arc::new(AsyncLruCache::from(
+        LruCacheBuilder::new(NonZeroUsize::new(256).unwrap())
+            .initial_capacity(16)
+            .read_buffer(NonZeroUsize::new(16).unwrap())
+            .incremental_growth(true)
+            .build(),
+    ));
+    let pending = || cache.cache.with_lock(|c| (c.recency_rank(&0), c.index.is_draining()));
+
+    // Stop inserting as soon as the key index starts to grow, so that entries remain to be moved across
+    for i in 0..256 {
+        let _ = cache.insert(i, i);
+
+        if pending().1 {
+            break;
+        }
+    }
+
+    let _ = cache.get(&0);
+    let before = pending();
+    let _maintenance = cache.spawn_maintenance(INTERVAL);
+    time::sleep(INTERVAL + Duration::from_millis(1)).await;
+
+    match (before, pending()) {
+        ((Some(rank), true), (Some(0), false)) if rank == cache.len() - 1 => Ok(()),
+        other => Err(format!("Expected the promotion to be applied and the index to finish growing. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test(start_paused = true)]
+async fn maintenance_should_finish_when_the_cache_is_dropped() -> Result<(), String> {
+    const INTERVAL: Duration = Duration::from_secs(1);
+
+    let cache = Arc::new(AsyncLruCache::<u32, u32>::new(NonZeroUsize::new(4).unwrap()));
+    let maintenance = cache.spawn_maintenance(INTERVAL);
+    time::sleep(3 * INTERVAL).await;
+    let running = !maintenance.is_finished();
+    drop(cache);
+
+    let finished = timeout(2 * INTERVAL, maintenance).await;
+
+    match (running, finished) {
+        (true, Ok(Ok(()))) => Ok(()),
+        other => Err(format!("Expected the task to run until the cache was dropped. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn eviction_events_should_be_awaitable() -> Result<(), String> {
+    let cache = Arc::new(AsyncLruCache::new(NonZeroUsize::new(1).unwrap()));
+    let events = cache.eviction_events();
+
+    let subscriber = tokio::spawn(async move {
+        let mut keys = Vec::new();
+
+        while let Some(event) = events.recv_async().await {
+            keys.push(event.key);
+        }
+
+        keys
+    });
+
+    for key in 0..3 {
+        let _ = cache.get_with(key, async move { key * 10 }).await;
+        tokio::task::yield_now().await;
+    }
+
+    let _ = cache.insert(3, 30);
+    drop(cache);
+
+    let keys = timeout(PATIENCE, subscriber)
+        .await
+        .map_err(|_| "The subscriber was left waiting after the cache was dropped")?
+        .map_err(|e| format!("The subscriber failed: {e}"))?;
+
+    match keys[..] {
+        [0, 1, 2] => Ok(()),
+        _ => Err(format!("Expected keys 0, 1 and 2 to be evicted in turn. Got {keys:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn close_should_wait_for_loads_then_refuse_changes() -> Result<(), String> {
+    let cache = Arc::new(AsyncLruCache::new(NonZeroUsize::new(4).unwrap()));
+    let (started, release) = (Arc::new(Notify::new()), Arc::new(Notify::new()));
+
+    let loader = {
+        let (cache, started, release) = (Arc::clone(&cache), Arc::clone(&started), Arc::clone(&release));
+
+        tokio::spawn(async move {
+            cache
+                .get_with(1, async {
+                    started.notify_one();
+                    release.notified().await;
+                    10
+                })
+                .await
+        })
+    };
+
+    started.notified().await;
+
+    let closing = {
+        let cache = Arc::clone(&cache);
+        tokio::spawn(async move { cache.close().await })
+    };
+
+    tokio::task::yield_now().await;
+    let waited = !closing.is_finished() && cache.is_closed();
+    release.notify_one();
+
+    timeout(PATIENCE, closing).await.map_err(|_| "Close was left waiting")?.map_err(|e| e.to_string())?;
+    let loaded = loader.await.map_err(|e| e.to_string())?;
+    timeout(PATIENCE, cache.close()).await.map_err(|_| "Closing again was left waiting")?;
+
+    let refused = (cache.get_with(2, async { 20 }).await, cache.insert(3, 30), cache.invalidate(&1), cache.clear());
+
+    match (waited, loaded, refused, cache.get(&1)) {
+        (true, Ok(10), (Err(Closed), Err(Closed), Err(Closed), Err(Closed)), Some(10)) => Ok(()),
+        other => Err(format!("Expected close to wait for the load, then changes to be refused. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn close_should_wait_for_the_subscriber_to_take_queued_events() -> Result<(), String> {
+    let cache = Arc::new(AsyncLruCache::new(NonZeroUsize::new(1).unwrap()));
+    let events = cache.eviction_events();
+
+    for key in 0..4 {
+        let _ = cache.insert(key, key);
+    }
+
+    let closing = {
+        let cache = Arc::clone(&cache);
+        tokio::spawn(async move { cache.close().await })
+    };
+
+    tokio::task::yield_now().await;
+    let waited = !closing.is_finished();
+
+    let subscriber = tokio::spawn(async move {
+        let mut keys = Vec::new();
+
+        while let Some(event) = events.recv_async().await {
+            keys.push(event.key);
+        }
+
+        keys
+    });
+
+    timeout(PATIENCE, closing).await.map_err(|_| "Close was left waiting")?.map_err(|e| e.to_string())?;
+    let keys = timeout(PATIENCE, subscriber).await.map_err(|_| "The stream did not end")?.map_err(|e| e.to_string())?;
+
+    match (waited, &keys[..]) {
+        (true, [0, 1, 2]) => Ok(()),
+        other => Err(format!("Expected close to wait for every event to be taken. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn close_should_not_deadlock_when_called_by_the_subscriber() -> Result<(), String> {
+    let cache = Arc::new(AsyncLruCache::new(NonZeroUsize::new(1).unwrap()));
+    let events = cache.eviction_events();
+
+    for key in 0..4 {
+        let _ = cache.insert(key, key);
+    }
+
+    let subscriber = {
+        let cache = Arc::clone(&cache);
+
+        tokio::spawn(async move {
+            let mut keys = Vec::new();
+
+            while let Some(event) = events.recv_async().await {
+                keys.push(event.key);
+                cache.close().await;
+            }
+
+            keys
+        })
+    };
+
+    let keys = timeout(PATIENCE, subscriber)
+        .await
+        .map_err(|_| "The subscriber deadlocked closing the cache")?
+        .map_err(|e| e.to_string())?;
+
+    match (&keys[..], cache.is_closed()) {
+        ([0, 1, 2], true) => Ok(()),
+        other => Err(format!("Expected the subscriber to close the cache and still take every event. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Loads each key as its decimal string after `delay`, failing the first `failures` loads
+struct CountingLoader {
+    loads: AtomicUsize,
+    failures: usize,
+    delay: Duration,
+}
+
+impl CountingLoader {
+    fn new(failures: usize, delay: Duration) -> Self {
+        CountingLoader {
+            loads: AtomicUsize::new(0),
+            failures,
+            delay,
+        }
+    }
+}
+
+impl AsyncCacheLoader<u32, String> for CountingLoader {
+    type Error = String;
+
+    async fn load(&self, key: &u32) -> Result<String, String> {
+        let load = self.loads.fetch_add(1, Ordering::Relaxed);
+        tokio::time::sleep(self.delay).await;
+
+        if load < self.failures {
+            Err(format!("load {load} failed"))
+        } else {
+            Ok(key.to_string())
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn read_through_get_should_load_a_miss_then_hit() -> Result<(), String> {
+    let cache = AsyncLruCache::with_loader(NonZeroUsize::new(2).unwrap(), CountingLoader::new(0, Duration::ZERO));
+
+    let first = cache.get(&7).await.map_err(|e| e.to_string())?;
+    let second = cache.get(&7).await.map_err(|e| e.to_string())?;
+
+    match (first.as_str(), second.as_str(), cache.loader.0.loads.load(Ordering::Relaxed), cache.get_if_present(&7)) {
+        ("7", "7", 1, Some(cached)) if cached == "7" => Ok(()),
+        other => Err(format!("Expected one load, after which the value is cached. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn read_through_get_should_run_one_load_for_many_tasks() -> Result<(), String> {
+    const TASKS: usize = 100;
+
+    let cache = Arc::new(AsyncLruCache::with_loader(
+        NonZeroUsize::new(4).unwrap(),
+        CountingLoader::new(0, Duration::from_millis(50)),
+    ));
+    let mut tasks = JoinSet::new();
+
+    for _ in 0..TASKS {
+        let cache = Arc::clone(&cache);
+        tasks.spawn(async move { cache.get(&42).await });
+    }
+
+    let values = timeout(PATIENCE, tasks.join_all())
+        .await
+        .map_err(|_| "Timed out waiting for the tasks")?;
+
+    match (
+        cache.loader.0.loads.load(Ordering::Relaxed),
+        values.iter().filter(|v| v.as_ref().is_ok_and(|v| v == "42")).count(),
+        in_flight(&cache.loading).len(),
+    ) {
+        (1, TASKS, 0) => Ok(()),
+        other => Err(format!("Expected one load whose value reached all {TASKS} tasks. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn read_through_get_should_give_every_waiter_the_error_without_caching_it() -> Result<(), String> {
+    const TASKS: usize = 10;
+
+    let cache = Arc::new(AsyncLruCache::with_loader(
+        NonZeroUsize::new(4).unwrap(),
+        CountingLoader::new(1, Duration::from_millis(50)),
+    ));
+    let mut tasks = JoinSet::new();
+
+    for _ in 0..TASKS {
+        let cache = Arc::clone(&cache);
+        tasks.spawn(async move { cache.get(&3).await });
+    }
+
+    let outcomes = timeout(PATIENCE, tasks.join_all())
+        .await
+        .map_err(|_| "Timed out waiting for the tasks")?;
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| matches!(outcome, Err(LoadError::Failed(e)) if e.as_str() == "load 0 failed"))
+        .count();
+
+    match (failed, cache.get_if_present(&3), in_flight(&cache.loading).len()) {
+        (TASKS, None, 0) => (),
+        other => return Err(format!("Expected all {TASKS} tasks to share the error and nothing cached. Got {other:?}")),
+    }
+
+    match (cache.get(&3).await, cache.loader.0.loads.load(Ordering::Relaxed)) {
+        (Ok(value), 2) if value == "3" => Ok(()),
+        other => Err(format!("Expected the next get to load again and succeed. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn read_through_get_should_only_hit_once_closed() -> Result<(), String> {
+    let cache = AsyncLruCache::with_loader(NonZeroUsize::new(2).unwrap(), CountingLoader::new(0, Duration::ZERO));
+    let _ = cache.get(&1).await;
+    cache.close().await;
+
+    match (cache.get(&1).await, cache.get(&2).await, cache.loader.0.loads.load(Ordering::Relaxed)) {
+        (Ok(value), Err(LoadError::Closed), 1) if value == "1" => Ok(()),
+        other => Err(format!("Expected a hit but no new load after closing. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn get_with_should_count_the_time_each_load_takes() -> Result<(), String> {
+    let clock = MockClock::new();
+    let cache = AsyncLruCache::from(
+        LruCacheBuilder::new(NonZeroUsize::new(4).unwrap())
+            .clock(clock.clone())
+            .build(),
+    );
+
+    for (key, millis) in [(1, 10), (2, 30), (1, 1_000)] {
+        let clock = clock.clone();
+        let load = async move {
+            clock.advance(Duration::from_millis(millis));
+            key
+        };
+        cache.get_with(key, load).await.map_err(|e| format!("Unexpected {e}"))?;
+    }
+
+    // The third call found key 1 cached, so its future never ran
+    let loads = cache.stats().load_time;
+
+    match (loads.count, loads.total, loads.mean(), loads.max) {
+        (2, total, mean, max) if total.as_millis() == 40 && mean.as_millis() == 20 && max.as_millis() == 30 => Ok(()),
+        other => Err(format!("Expected 2 loads taking 40ms, at most 30ms. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Loads keys below 100 as their decimal strings, and reports that larger keys have no value
+#[derive(Default)]
+struct DirectoryLoader {
+    loads: AtomicUsize,
+}
+
+impl AsyncCacheLoader<u32, String> for DirectoryLoader {
+    type Error = String;
+
+    async fn load(&self, key: &u32) -> Result<String, String> {
+        self.loads.fetch_add(1, Ordering::Relaxed);
+
+        match key {
+            0..100 => Ok(key.to_string()),
+            _ => Err(String::from("not found")),
+        }
+    }
+}
+
+fn is_not_found(e: &String) -> bool {
+    e == "not found"
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn read_through_get_should_remember_absence_until_it_expires() -> Result<(), String> {
+    let clock = MockClock::new();
+    let inner = LruCacheBuilder::new(NonZeroUsize::new(2).unwrap()).clock(clock.clone()).build();
+    let cache = AsyncLruCache::with_cache_and_loader(inner, DirectoryLoader::default())
+        .with_negative_caching(Duration::from_secs(10), is_not_found);
+
+    let first = cache.lookup(&500).await;
+    clock.advance(Duration::from_secs(9));
+    let remembered = cache.lookup(&500).await;
+    let error = cache.get(&500).await;
+    let loads_while_remembered = cache.loader.0.loads.load(Ordering::Relaxed);
+
+    clock.advance(Duration::from_secs(2));
+    let expired = cache.lookup(&500).await;
+
+    match (first, remembered, error, loads_while_remembered, expired, cache.loader.0.loads.load(Ordering::Relaxed)) {
+        (Ok(Lookup::NotFound), Ok(Lookup::NegativeHit), Err(LoadError::Failed(e)), 1, Ok(Lookup::NotFound), 2)
+            if e.as_str() == "not found" =>
+        {
+            Ok(())
+        }
+        other => Err(format!("Expected one load until the absence expired, then another. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn read_through_get_should_never_remember_transient_errors() -> Result<(), String> {
+    let cache = AsyncLruCache::with_loader(NonZeroUsize::new(2).unwrap(), CountingLoader::new(2, Duration::ZERO))
+        .with_negative_caching(Duration::from_secs(10), is_not_found);
+
+    let outcomes = (cache.lookup(&3).await, cache.get(&3).await, cache.lookup(&3).await);
+
+    match (outcomes, cache.loader.0.loads.load(Ordering::Relaxed)) {
+        ((Err(LoadError::Failed(_)), Err(LoadError::Failed(_)), Ok(Lookup::Found(value))), 3) if value == "3" => Ok(()),
+        other => Err(format!("Expected every lookup to call the loader until it succeeded. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn insert_should_forget_remembered_absence() -> Result<(), String> {
+    let cache = AsyncLruCache::with_loader(NonZeroUsize::new(2).unwrap(), DirectoryLoader::default())
+        .with_negative_caching(Duration::from_secs(10), is_not_found);
+
+    let missing = cache.lookup(&500).await;
+    cache.insert(500, String::from("inserted")).map_err(|e| e.to_string())?;
+
+    match (missing, cache.lookup(&500).await) {
+        (Ok(Lookup::NotFound), Ok(Lookup::Found(value))) if value == "inserted" => Ok(()),
+        other => Err(format!("Expected the inserted value to replace the absence. Got {other:?}")),
+    }
+}
+
+/// Builds a read-through cache whose items live for ten seconds and are reloaded in their last two
+fn refreshing_cache(clock: &MockClock) -> AsyncLruCache<u32, String, DefaultHashBuilder, ReadThrough<DirectoryLoader>> {
+    let inner = LruCacheBuilder::new(NonZeroUsize::new(2).unwrap())
+        .clock(clock.clone())
+        .ttl(Duration::from_secs(10))
+        .build();
+    AsyncLruCache::with_cache_and_loader(inner, DirectoryLoader::default()).with_refresh_ahead(0.2)
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn read_through_get_should_flag_reload_only_within_refresh_window() -> Result<(), String> {
+    let clock = MockClock::new();
+    let cache = refreshing_cache(&clock);
+    let loads = || cache.loader.0.loads.load(Ordering::Relaxed);
+
+    cache.get(&1).await.map_err(|e| e.to_string())?;
+    clock.advance(Duration::from_secs(7));
+    cache.get(&1).await.map_err(|e| e.to_string())?;
+    cache.run_pending_refreshes().await;
+    let loads_early = loads();
+
+    clock.advance(Duration::from_millis(1500));
+    let hit = cache.get(&1).await.map_err(|e| e.to_string())?;
+    let loads_flagged = loads();
+    cache.run_pending_refreshes().await;
+    let remaining = cache.cache.with_lock(|c| c.entry_info(&1)).and_then(|info| info.remaining_ttl());
+
+    match (loads_early, hit.as_str(), loads_flagged, loads(), remaining) {
+        (1, "1", 1, 2, Some(ttl)) if ttl == Duration::from_secs(10) => Ok(()),
+        other => Err(format!("Expected one reload, only once inside the window. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test(start_paused = true)]
+async fn spawned_refreshes_should_keep_a_hot_key_from_missing() -> Result<(), String> {
+    const INTERVAL: Duration = Duration::from_secs(1);
+
+    let clock = MockClock::new();
+    let cache = Arc::new(refreshing_cache(&clock));
+    let _refreshes = cache.spawn_refreshes(INTERVAL);
+    cache.get(&1).await.map_err(|e| e.to_string())?;
+    let mut absent = 0;
+
+    for _ in 0..30 {
+        clock.advance(Duration::from_secs(1));
+        absent += usize::from(!cache.contains_key(&1));
+        cache.get(&1).await.map_err(|e| e.to_string())?;
+        time::sleep(INTERVAL).await;
+    }
+
+    // The item is reloaded each time it is down to its last second: at 9, 18 and 27
+    match (absent, cache.loader.0.loads.load(Ordering::Relaxed)) {
+        (0, 4) => Ok(()),
+        other => Err(format!("Expected (0, 4). Got {other:?}")),
+    }
+}
+
+/// Builds a read-through cache whose items live for ten seconds and are served stale for five more
+fn stale_cache(clock: &MockClock) -> AsyncLruCache<u32, String, DefaultHashBuilder, ReadThrough<DirectoryLoader>> {
+    let inner = LruCacheBuilder::new(NonZeroUsize::new(2).unwrap())
+        .clock(clock.clone())
+        .ttl(Duration::from_secs(10))
+        .build();
+    AsyncLruCache::with_cache_and_loader(inner, DirectoryLoader::default())
+        .with_stale_while_revalidate(Duration::from_secs(5))
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn read_through_get_should_serve_stale_to_many_tasks_and_reload_once() -> Result<(), String> {
+    let clock = MockClock::new();
+    let cache = Arc::new(stale_cache(&clock));
+    cache.get(&1).await.map_err(|e| e.to_string())?;
+    clock.advance(Duration::from_secs(12));
+
+    let mut tasks = JoinSet::new();
+
+    for _ in 0..16 {
+        let cache = Arc::clone(&cache);
+        tasks.spawn(async move { cache.get_cached(&1).await });
+    }
+
+    let mut stale = 0;
+
+    while let Some(found) = tasks.join_next().await {
+        let found = found.map_err(|e| e.to_string())?.map_err(|e| e.to_string())?;
+        stale += usize::from(found.staleness == Some(Duration::from_secs(2)) && found.value == "1");
+    }
+
+    cache.run_pending_refreshes().await;
+    let fresh = cache.get_cached(&1).await.map_err(|e| e.to_string())?;
+
+    match (stale, cache.loader.0.loads.load(Ordering::Relaxed), fresh.is_stale()) {
+        (16, 2, false) => Ok(()),
+        other => Err(format!("Expected (16, 2, false). Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn read_through_get_should_miss_once_grace_period_has_passed() -> Result<(), String> {
+    let clock = MockClock::new();
+    let cache = stale_cache(&clock);
+
+    cache.get(&1).await.map_err(|e| e.to_string())?;
+    clock.advance(Duration::from_secs(20));
+    let found = cache.get_cached(&1).await.map_err(|e| e.to_string())?;
+    cache.run_pending_refreshes().await;
+
+    match (found.is_stale(), cache.loader.0.loads.load(Ordering::Relaxed)) {
+        (false, 2) => Ok(()),
+        other => Err(format!("Expected a blocking load and no refresh. Got {other:?}")),
+    }
+}
@@ -0,0 +1,627 @@
+//! Builder for caches that need more than a capacity and a hasher
+use crate::{
+    DefaultHashBuilder, EvictionMode, LruCache, RemovalCause, RemovalFn,
+    doorkeeper::Doorkeeper,
+    hot_keys::HotKeys,
+    listener::{CacheEventListener, CloneFn, Listener, Listeners},
+    eviction::{GreedyDual, Lfu, Policy, S3Fifo, Sampler, Slru, TinyLfu, TwoQueue},
+    nodes::SideTable,
+    random::Random,
+    read_buffer::ReadBuffer,
+};
+#[cfg(feature = "std")]
+use crate::{
+    Clock,
+    clock::{self, Jitter, TtlFn},
+    early_expiry::EarlyExpiry,
+    recorder::{self, TraceRecorder},
+    stats::StatsWindow,
+};
+#[cfg(feature = "tracing")]
+use crate::tracer::{KeyFmt, Tracer};
+#[cfg(feature = "zeroize")]
+use {
+    crate::zeroizing::{Zeroizing, ZeroizingLruCache},
+    zeroize::Zeroize,
+};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use alloc::{boxed::Box, sync::Arc};
+use core::{
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+    num::NonZeroUsize,
+};
+#[cfg(feature = "std")]
+use core::fmt;
+#[cfg(feature = "std")]
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::io::Write;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Configures and builds an [`LruCache`]
+///
+/// ```
+/// # use lru_cache::{LruCache, LruCacheBuilder};
+/// # use std::num::NonZeroUsize;
+/// let cache: LruCache<String, u32> = LruCacheBuilder::new(NonZeroUsize::new(100).unwrap())
+///     .initial_capacity(10)
+///     .read_buffer(NonZeroUsize::new(32).unwrap())
+///     .build();
+/// ```
+pub struct LruCacheBuilder<S = DefaultHashBuilder, C = ()> {
+    capacity: NonZeroUsize,
+    initial_capacity: Option<usize>,
+    hash_builder: S,
+    read_buffer: Option<NonZeroUsize>,
+    eviction_mode: EvictionMode,
+    incremental_growth: bool,
+    doorkeeper: bool,
+    track_access: bool,
+    #[cfg(feature = "std")]
+    ttl: Option<Duration>,
+    #[cfg(feature = "std")]
+    time_to_idle: Option<Duration>,
+    #[cfg(feature = "std")]
+    refresh_ttl_on_get: bool,
+    #[cfg(feature = "std")]
+    early_expiration: Option<(Duration, f64)>,
+    #[cfg(feature = "std")]
+    ttl_jitter: Option<f64>,
+    #[cfg(feature = "std")]
+    windowed_stats: Option<Duration>,
+    seed: Option<u64>,
+    #[cfg(feature = "std")]
+    clock: Option<Arc<dyn Clock>>,
+    #[cfg(feature = "tracing")]
+    tracing: Option<&'static str>,
+    #[cfg(feature = "std")]
+    trace_writer: Option<Box<dyn Write + Send>>,
+    /// `()` until a callback fixes the key and value types, then [`Callbacks`]
+    callbacks: C,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The callbacks given to an [`LruCacheBuilder`], such as [`ttl_fn`](LruCacheBuilder::ttl_fn), which fix the key and
+/// value types of the cache it builds
+pub struct Callbacks<K, V> {
+    #[cfg(feature = "std")]
+    ttl_fn: Option<TtlFn<K, V>>,
+    on_removal: Option<RemovalFn<K, V>>,
+    hot_keys: Option<HotKeys<K>>,
+    listeners: Vec<Listener<K, V>>,
+    clone_entry: Option<CloneFn<K, V>>,
+    #[cfg(feature = "tracing")]
+    key_fmt: Option<KeyFmt<K>>,
+    #[cfg(feature = "std")]
+    trace_keys: Option<recorder::KeyFmt<K>>,
+    types: PhantomData<fn(&K, &V)>,
+}
+
+impl<K, V> From<()> for Callbacks<K, V> {
+    fn from(_: ()) -> Self {
+        Callbacks {
+            #[cfg(feature = "std")]
+            ttl_fn: None,
+            on_removal: None,
+            hot_keys: None,
+            listeners: Vec::new(),
+            clone_entry: None,
+            #[cfg(feature = "tracing")]
+            key_fmt: None,
+            #[cfg(feature = "std")]
+            trace_keys: None,
+            types: PhantomData,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl LruCacheBuilder {
+    /// Starts building a cache that holds at most `capacity` items
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        LruCacheBuilder {
+            capacity,
+            initial_capacity: None,
+            hash_builder: DefaultHashBuilder::default(),
+            read_buffer: None,
+            eviction_mode: EvictionMode::Exact,
+            incremental_growth: false,
+            doorkeeper: false,
+            track_access: false,
+            #[cfg(feature = "std")]
+            ttl: None,
+            #[cfg(feature = "std")]
+            time_to_idle: None,
+            #[cfg(feature = "std")]
+            refresh_ttl_on_get: false,
+            #[cfg(feature = "std")]
+            early_expiration: None,
+            #[cfg(feature = "std")]
+            ttl_jitter: None,
+            #[cfg(feature = "std")]
+            windowed_stats: None,
+            seed: None,
+            #[cfg(feature = "std")]
+            clock: None,
+            #[cfg(feature = "tracing")]
+            tracing: None,
+            #[cfg(feature = "std")]
+            trace_writer: None,
+            callbacks: (),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<S, C> LruCacheBuilder<S, C> {
+    /// Hashes keys with the given hash builder instead of [`DefaultHashBuilder`]
+    pub fn hasher<T>(self, hash_builder: T) -> LruCacheBuilder<T, C> {
+        self.rebuild(|_, callbacks| (hash_builder, callbacks))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Moves the options to a builder with another hash builder or other callbacks
+    fn rebuild<T, D>(self, replace: impl FnOnce(S, C) -> (T, D)) -> LruCacheBuilder<T, D> {
+        let (hash_builder, callbacks) = replace(self.hash_builder, self.callbacks);
+
+        LruCacheBuilder {
+            capacity: self.capacity,
+            initial_capacity: self.initial_capacity,
+            hash_builder,
+            read_buffer: self.read_buffer,
+            eviction_mode: self.eviction_mode,
+            incremental_growth: self.incremental_growth,
+            doorkeeper: self.doorkeeper,
+            track_access: self.track_access,
+            #[cfg(feature = "std")]
+            ttl: self.ttl,
+            #[cfg(feature = "std")]
+            time_to_idle: self.time_to_idle,
+            #[cfg(feature = "std")]
+            refresh_ttl_on_get: self.refresh_ttl_on_get,
+            #[cfg(feature = "std")]
+            early_expiration: self.early_expiration,
+            #[cfg(feature = "std")]
+            ttl_jitter: self.ttl_jitter,
+            #[cfg(feature = "std")]
+            windowed_stats: self.windowed_stats,
+            seed: self.seed,
+            #[cfg(feature = "std")]
+            clock: self.clock,
+            #[cfg(feature = "tracing")]
+            tracing: self.tracing,
+            #[cfg(feature = "std")]
+            trace_writer: self.trace_writer,
+            callbacks,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Only allocates storage for `initial_capacity` items up front.  Defaults to the full capacity.  Ignored by
+    /// `build_zeroizing`, which the `zeroize` feature adds
+    pub fn initial_capacity(mut self, initial_capacity: usize) -> Self {
+        self.initial_capacity = Some(initial_capacity);
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Defers promotions caused by reads, recording up to `size` of them in a buffer that is replayed when it fills up
+    /// or before the next write.
+    ///
+    /// Reads through [`LruCache::get`] then relink the recency list once per `size` hits rather than on every hit, and
+    /// [`LruCache::get_deferred`] can promote entries through a shared borrow.  Hits, misses and eviction order are
+    /// unaffected, except that reads made through `get_deferred` while the buffer is full are not counted as uses.
+    /// Methods that inspect the order through a shared borrow, such as [`LruCache::recency_rank`], do not see
+    /// promotions that are still buffered
+    pub fn read_buffer(mut self, size: NonZeroUsize) -> Self {
+        self.read_buffer = Some(size);
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Chooses how entries are picked for eviction.  Defaults to [`EvictionMode::Exact`]
+    pub fn eviction_mode(mut self, eviction_mode: EvictionMode) -> Self {
+        self.eviction_mode = eviction_mode;
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Grows the key index a few entries at a time instead of rehashing it all at once when it runs out of room.
+    ///
+    /// Useful when the initial capacity is well below the capacity and the occasional insertion that rehashes every
+    /// key would break a latency budget.  While the index is growing, lookups and removals check two tables, and the
+    /// index briefly holds both.  Entry storage still grows by reallocating.  Disabled by default
+    pub fn incremental_growth(mut self, enabled: bool) -> Self {
+        self.incremental_growth = enabled;
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Checks a bloom filter of the cached keys before probing the cache, so that most lookups for keys that are not
+    /// cached return without comparing keys.
+    ///
+    /// Worthwhile when most lookups miss and keys are slow to compare.  The filter never turns away a cached key.  It
+    /// takes two bytes per item of capacity, allocated up front, and is rebuilt from the cached keys each time half the
+    /// capacity has been evicted or removed.  [`LruCache::doorkeeper_rejections`] counts the lookups it answered.
+    /// Disabled by default
+    pub fn doorkeeper(mut self, enabled: bool) -> Self {
+        self.doorkeeper = enabled;
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Counts how many times [`LruCache::get`] finds each item, for [`LruCache::entry_info`] and
+    /// [`LruCache::iter_with_metadata`] to report alongside when the item was last used.
+    ///
+    /// [`LruCache::peek`] and other lookups that leave the recency order alone are not counted, nor are reads through
+    /// [`LruCache::get_deferred`].  Updating an item keeps its count, while replacing an expired item or inserting the
+    /// key again after it was removed starts from zero.  Disabled by default, when no counting is done and no memory is
+    /// set aside for the counts
+    pub fn track_access(mut self, enabled: bool) -> Self {
+        self.track_access = enabled;
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Gives every item inserted by [`LruCache::put`] or [`LruCache::push`] a time-to-live of `ttl`.  Items inserted by
+    /// [`LruCache::put_with_ttl`] keep their own.  By default items never expire
+    #[cfg(feature = "std")]
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Expires items that have not been used for `time_to_idle`.  Inserting an item, and fetching it through
+    /// [`LruCache::get`] or [`LruCache::promote`], restarts its idle time; [`LruCache::peek`] does not.  Combined with
+    /// a time-to-live, an item expires at whichever deadline comes first.  By default items never go idle
+    #[cfg(feature = "std")]
+    pub fn time_to_idle(mut self, time_to_idle: Duration) -> Self {
+        self.time_to_idle = Some(time_to_idle);
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Restarts an item's time-to-live each time it is used, as [`time_to_idle`](Self::time_to_idle) does, so that
+    /// items in use keep sliding their deadlines forward.  Items stored with [`LruCache::put_with_expiry_at`] keep
+    /// their absolute deadline.  Disabled by default, so that items expire a fixed time after they were stored
+    #[cfg(feature = "std")]
+    pub fn refresh_ttl_on_get(mut self, enabled: bool) -> Self {
+        self.refresh_ttl_on_get = enabled;
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Decides each item's time-to-live from its key and value as it is inserted by [`LruCache::put`] or
+    /// [`LruCache::push`], including the values loaded by read-through methods such as
+    /// [`ConcurrentLruCache::get_or_insert_with`](crate::ConcurrentLruCache::get_or_insert_with).  `None` stores the
+    /// item without a time-to-live.  Takes the place of [`ttl`](Self::ttl), while [`LruCache::put_with_ttl`] and
+    /// [`LruCache::put_with_expiry_at`] still override it
+    #[cfg(feature = "std")]
+    pub fn ttl_fn<K, V>(
+        self,
+        ttl_fn: impl Fn(&K, &V) -> Option<Duration> + Send + Sync + 'static,
+    ) -> LruCacheBuilder<S, Callbacks<K, V>>
+    where
+        C: Into<Callbacks<K, V>>,
+    {
+        self.rebuild(|hash_builder, callbacks| {
+            let mut callbacks = callbacks.into();
+            callbacks.ttl_fn = Some(TtlFn::new(ttl_fn));
+            (hash_builder, callbacks)
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Scales each item's time-to-live by a random factor between `1 - fraction` and `1 + fraction`, so that items
+    /// loaded together with the same time-to-live do not all expire at once.  Applies to the default time-to-live and
+    /// to [`LruCache::put_with_ttl`], but not to deadlines given to [`LruCache::put_with_expiry_at`].  Disabled by
+    /// default
+    ///
+    /// # Panics
+    ///
+    /// If `fraction` is not between `0.0` and `1.0`
+    #[cfg(feature = "std")]
+    pub fn ttl_jitter(mut self, fraction: f64) -> Self {
+        assert!((0.0..=1.0).contains(&fraction), "TTL jitter must be between 0 and 1, not {fraction}");
+        self.ttl_jitter = Some(fraction);
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Refreshes popular items ahead of their deadlines, so that callers do not all miss at once when an item expires.
+    ///
+    /// Once an item is within `window` of its deadline, each [`LruCache::get`] may report it missing, so that the
+    /// caller loads a fresh value while other callers still find the old one.  Only one caller is asked.  The chance
+    /// grows as the deadline nears, and with the time the value took to load, scaled by `beta`; `1.0` is a good start.
+    /// Only items whose load time is known are refreshed early: see [`LruCache::set_load_cost`].  Disabled by default
+    #[cfg(feature = "std")]
+    pub fn early_expiration(mut self, window: Duration, beta: f64) -> Self {
+        self.early_expiration = Some((window, beta));
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Calls `on_removal` with every item that leaves the cache, and why, once the item is out of the cache: items
+    /// evicted to make room, expired items that the cache comes across, items removed or popped by the caller, values
+    /// overwritten by [`LruCache::put`] and the like, and items removed by [`LruCache::clear`].  Items are reported
+    /// even when they are also handed back to the caller, as by [`LruCache::push`] or [`LruCache::remove`].  Items
+    /// still in the cache when it is dropped are not reported.
+    ///
+    /// The cache owns the callback and calls it while borrowed mutably, so the callback cannot use the cache.  Nor may
+    /// it reach the cache some other way, such as through a shared
+    /// [`ConcurrentLruCache`](crate::ConcurrentLruCache), whose lock is held while it runs.
+    ///
+    /// If the callback panics, the cache is left consistent, holding every item but the one being reported, or the new
+    /// value if one was being overwritten.  An item that was being inserted, and needed room, is not stored
+    pub fn on_removal<K, V>(
+        self,
+        on_removal: impl FnMut(&K, &V, RemovalCause) + Send + Sync + 'static,
+    ) -> LruCacheBuilder<S, Callbacks<K, V>>
+    where
+        C: Into<Callbacks<K, V>>,
+    {
+        self.rebuild(|hash_builder, callbacks| {
+            let mut callbacks = callbacks.into();
+            callbacks.on_removal = Some(RemovalFn::new(on_removal));
+            (hash_builder, callbacks)
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Counts how often each key is used, by hits, insertions and updates, so that [`LruCache::hottest`] can report the
+    /// most used keys.  The counts are approximate, kept in a count-min sketch that never undercounts, and the report
+    /// is drawn from up to `candidates` keys, the most used seen so far, so asking for more than about half as many
+    /// keys as there are candidates gives a less reliable ranking.
+    ///
+    /// Memory is bounded whatever the number of distinct keys: 256 bytes of counters per candidate, rounded up to a
+    /// power of two, plus a clone of each candidate's key
+    pub fn track_hot_keys<K, V>(self, candidates: NonZeroUsize) -> LruCacheBuilder<S, Callbacks<K, V>>
+    where
+        K: Clone,
+        C: Into<Callbacks<K, V>>,
+    {
+        self.rebuild(|hash_builder, callbacks| {
+            let mut callbacks = callbacks.into();
+            callbacks.hot_keys = Some(HotKeys::new(candidates.get(), K::clone));
+            (hash_builder, callbacks)
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Tells `listener` of every insertion, update, hit and removal, after any listeners already added.
+    ///
+    /// A [`ConcurrentLruCache`](crate::ConcurrentLruCache) built from the cache calls its listeners once it has
+    /// released its lock, on the thread that caused the events, which is why keys and values must be [`Clone`]: each
+    /// event is queued with a copy of its item until then.  Hits found by
+    /// [`get_deferred`](crate::LruCache::get_deferred), and every event of the caches that share their lock with
+    /// readers, such as [`RwLruCache`](crate::RwLruCache), are delivered while the lock is held
+    pub fn listener<K, V>(
+        self,
+        listener: impl CacheEventListener<K, V> + 'static,
+    ) -> LruCacheBuilder<S, Callbacks<K, V>>
+    where
+        K: Clone,
+        V: Clone,
+        C: Into<Callbacks<K, V>>,
+    {
+        self.rebuild(|hash_builder, callbacks| {
+            let mut callbacks = callbacks.into();
+            callbacks.listeners.push(Listener::new(listener));
+            callbacks.clone_entry = Some(|key, value| (key.clone(), value.clone()));
+            (hash_builder, callbacks)
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Seeds the random choices made by sampled and random eviction, early expiration and TTL jitter, so that they can
+    /// be repeated.  By default the seed is derived from the hash builder
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Also counts activity in each of the last 60 intervals of width `interval`, so that
+    /// [`LruCache::stats_window`] can report the hit ratio and other counts over a recent window rather than since the
+    /// cache was created.  Intervals move on as the cache is used, measured on its clock, without a background thread.
+    ///
+    /// Costs a clock reading per counted event and about 3 KiB.  Disabled by default
+    #[cfg(feature = "std")]
+    pub fn windowed_stats(mut self, interval: Duration) -> Self {
+        self.windowed_stats = Some(interval);
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Measures deadlines on `clock` instead of a [`SystemClock`](crate::SystemClock)
+    #[cfg(feature = "std")]
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Emits a `tracing` event for each of the following, carrying `name` in the `cache` field:
+    ///
+    /// | Message | Level | Other fields
+    /// |---|---|---
+    /// | `hit` | `TRACE` | `key`
+    /// | `miss` | `TRACE` |
+    /// | `inserted`, `updated` | `TRACE` | `key`
+    /// | `evicted`, `expired` | `DEBUG` | `key`
+    /// | `removed` | `TRACE` | `key`, `cause`
+    /// | `loaded` | `DEBUG` | `key`, `latency_ns`
+    ///
+    /// Loads are those of read-through methods such as
+    /// [`ConcurrentLruCache::get_or_insert_with`](crate::ConcurrentLruCache::get_or_insert_with).  The `key` field is
+    /// only present if [`trace_keys_display`](Self::trace_keys_display) or [`trace_keys_debug`](Self::trace_keys_debug)
+    /// is also called, and never on a miss, whose key may be a borrowed form that cannot be formatted.  The shards of a
+    /// [`ShardedLruCache::with_tracing`](crate::ShardedLruCache::with_tracing) add a `shard` field
+    #[cfg(feature = "tracing")]
+    pub fn tracing(mut self, name: &'static str) -> Self {
+        self.tracing = Some(name);
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Adds each key, formatted with [`Display`](core::fmt::Display), to the events enabled by
+    /// [`tracing`](Self::tracing)
+    #[cfg(feature = "tracing")]
+    pub fn trace_keys_display<K, V>(self) -> LruCacheBuilder<S, Callbacks<K, V>>
+    where
+        K: fmt::Display,
+        C: Into<Callbacks<K, V>>,
+    {
+        self.trace_keys(<K as fmt::Display>::fmt)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Adds each key, formatted with [`Debug`](core::fmt::Debug), to the events enabled by [`tracing`](Self::tracing)
+    #[cfg(feature = "tracing")]
+    pub fn trace_keys_debug<K, V>(self) -> LruCacheBuilder<S, Callbacks<K, V>>
+    where
+        K: fmt::Debug,
+        C: Into<Callbacks<K, V>>,
+    {
+        self.trace_keys(<K as fmt::Debug>::fmt)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    #[cfg(feature = "tracing")]
+    fn trace_keys<K, V>(self, key_fmt: KeyFmt<K>) -> LruCacheBuilder<S, Callbacks<K, V>>
+    where
+        C: Into<Callbacks<K, V>>,
+    {
+        self.rebuild(|hash_builder, callbacks| {
+            let mut callbacks = callbacks.into();
+            callbacks.key_fmt = Some(key_fmt);
+            (hash_builder, callbacks)
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Writes a line to `writer` for every `get`, `put` and `remove` made on the cache, as
+    /// [`LruCache::set_trace_writer`] describes.  Disabled by default, when recording costs a check per operation
+    #[cfg(feature = "std")]
+    pub fn record_trace(mut self, writer: impl Write + Send + 'static) -> Self {
+        self.trace_writer = Some(Box::new(writer));
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Adds each key, formatted with [`Display`](core::fmt::Display), to the lines of a trace recorded through
+    /// [`record_trace`](Self::record_trace) or [`LruCache::set_trace_writer`].  Without it, a trace identifies keys
+    /// only by their hashes, so that it can be shared without revealing them
+    #[cfg(feature = "std")]
+    pub fn trace_literal_keys<K, V>(self) -> LruCacheBuilder<S, Callbacks<K, V>>
+    where
+        K: fmt::Display,
+        C: Into<Callbacks<K, V>>,
+    {
+        self.rebuild(|hash_builder, callbacks| {
+            let mut callbacks = callbacks.into();
+            callbacks.trace_keys = Some(<K as fmt::Display>::fmt);
+            (hash_builder, callbacks)
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Builds the cache
+    pub fn build<K, V>(self) -> LruCache<K, V, S>
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+        C: Into<Callbacks<K, V>>,
+    {
+        let initial_capacity = self.initial_capacity.unwrap_or(self.capacity.get());
+        let mut cache = LruCache::with_capacity_and_hasher(self.capacity, initial_capacity, self.hash_builder);
+        cache.read_buffer = self.read_buffer.map(|size| ReadBuffer::new(size.get()));
+        cache.index.set_incremental(self.incremental_growth);
+        cache.doorkeeper = self.doorkeeper.then(|| Doorkeeper::new(self.capacity.get()));
+        cache.hits = self.track_access.then(SideTable::default);
+        let callbacks: Callbacks<K, V> = self.callbacks.into();
+        cache.on_removal = callbacks.on_removal;
+        cache.hot_keys = callbacks.hot_keys;
+        cache.listeners = callbacks.clone_entry.map(|clone_entry| Listeners::new(callbacks.listeners, clone_entry));
+
+        #[cfg(feature = "tracing")]
+        {
+            cache.tracer = self.tracing.map(|name| Tracer::new(name, callbacks.key_fmt));
+        }
+
+        #[cfg(feature = "std")]
+        {
+            cache.default_ttl = self.ttl;
+            cache.time_to_idle = self.time_to_idle;
+            cache.refresh_ttl_on_get = self.refresh_ttl_on_get;
+            cache.early_expiry = self.early_expiration.map(|(window, beta)| {
+                let seed = self.seed.unwrap_or_else(|| cache.hash_builder.hash_one(window));
+                EarlyExpiry::new(clock::nanos(window), beta, seed)
+            });
+            cache.ttl_jitter = self.ttl_jitter.map(|fraction| {
+                let seed = self.seed.unwrap_or_else(|| cache.hash_builder.hash_one(fraction.to_bits()));
+                Jitter::new(fraction, seed)
+            });
+            cache.clock = self.clock;
+            cache.ttl_fn = callbacks.ttl_fn;
+            cache.trace_keys = callbacks.trace_keys;
+
+            if let Some(writer) = self.trace_writer {
+                cache.clock_now();
+                cache.recorder = Some(Box::new(TraceRecorder::new(writer, cache.trace_keys)));
+            }
+
+            if let Some(interval) = self.windowed_stats {
+                cache.clock_now();
+                cache.recent_stats = Some(Box::new(StatsWindow::new(interval)));
+            }
+        }
+
+        cache.policy = match self.eviction_mode {
+            EvictionMode::Exact => Policy::Exact,
+            EvictionMode::Sampled { sample_size } => {
+                // Seeding from the hash builder gives each cache its own sequence when the hasher is randomly keyed
+                let seed = self.seed.unwrap_or_else(|| cache.hash_builder.hash_one(sample_size));
+                Policy::Sampled(Sampler::new(sample_size, seed))
+            }
+            EvictionMode::Lfu => Policy::Lfu(Lfu::default()),
+            EvictionMode::Segmented { protected_percent } => {
+                Policy::Slru(Slru::new(self.capacity.get(), protected_percent))
+            }
+            EvictionMode::TwoQueue {
+                in_percent,
+                ghost_percent,
+            } => Policy::TwoQueue(TwoQueue::new(self.capacity, in_percent, ghost_percent)),
+            EvictionMode::TinyLfu { window_percent } => Policy::TinyLfu(TinyLfu::new(self.capacity, window_percent)),
+            EvictionMode::Fifo { refresh_on_put } => Policy::Fifo { refresh_on_put },
+            EvictionMode::Random => {
+                let seed = self.seed.unwrap_or_else(|| cache.hash_builder.hash_one(self.capacity));
+                Policy::Random(Random::new(seed))
+            }
+            EvictionMode::Mru => Policy::Mru,
+            EvictionMode::CostAware => Policy::CostAware(GreedyDual::default()),
+            EvictionMode::S3Fifo { small_percent } => Policy::S3Fifo(S3Fifo::new(self.capacity, small_percent)),
+        };
+
+        cache
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Builds a cache whose values are zeroized when they leave the cache.  Storage for the full capacity is allocated
+    /// up front whatever [`initial_capacity`](Self::initial_capacity) was given, because growing the storage would
+    /// move values without zeroizing the copies left behind
+    #[cfg(feature = "zeroize")]
+    pub fn build_zeroizing<K, V>(mut self) -> ZeroizingLruCache<K, V, S>
+    where
+        K: Eq + Hash,
+        V: Zeroize,
+        S: BuildHasher,
+        C: Into<Callbacks<K, Zeroizing<V>>>,
+    {
+        self.initial_capacity = None;
+        self.build()
+    }
+}
@@ -0,0 +1,85 @@
+//! Callbacks stored so that dropping a cache does not need its keys and values to be alive
+//!
+//! A boxed trait object such as `Box<dyn FnMut(&K, &V)>` counts as using `K` and `V` when it is dropped, so a cache
+//! holding one could not be dropped after the data its keys or values borrow, even though the callback is `'static`
+//! and cannot reach that data.  A [`Callback`] instead boxes the callback as [`Any`] and calls it through a function
+//! pointer that knows its concrete type.  Function pointers need nothing when dropped, so the cache's destructor only
+//! drops its keys and values themselves
+use crate::{
+    RemovalFn,
+    listener::{CacheEventListener, Event, Listener},
+};
+use alloc::boxed::Box;
+use core::any::Any;
+#[cfg(feature = "std")]
+use {crate::clock::TtlFn, core::time::Duration};
+
+/// A callback whose type has been forgotten
+pub(crate) type State = dyn Any + Send + Sync;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A `'static` callback and the function pointer that calls it
+pub(crate) struct Callback<F> {
+    state: Box<State>,
+    call: F,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V> RemovalFn<K, V> {
+    pub(crate) fn new<F>(on_removal: F) -> Self
+    where
+        F: FnMut(&K, &V, crate::RemovalCause) + Send + Sync + 'static,
+    {
+        Callback {
+            state: Box::new(on_removal),
+            call: |state, key, value, cause| {
+                if let Some(on_removal) = state.downcast_mut::<F>() {
+                    on_removal(key, value, cause);
+                }
+            },
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn call(&mut self, key: &K, value: &V, cause: crate::RemovalCause) {
+        (self.call)(&mut *self.state, key, value, cause)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(feature = "std")]
+impl<K, V> TtlFn<K, V> {
+    pub(crate) fn new<F>(ttl_fn: F) -> Self
+    where
+        F: Fn(&K, &V) -> Option<Duration> + Send + Sync + 'static,
+    {
+        Callback {
+            state: Box::new(ttl_fn),
+            call: |state, key, value| state.downcast_ref::<F>().and_then(|ttl_fn| ttl_fn(key, value)),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn call(&self, key: &K, value: &V) -> Option<Duration> {
+        (self.call)(&*self.state, key, value)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V> Listener<K, V> {
+    pub(crate) fn new<L: CacheEventListener<K, V> + 'static>(listener: L) -> Self {
+        Callback {
+            state: Box::new(listener),
+            call: |state, event, key, value| {
+                if let Some(listener) = state.downcast_ref::<L>() {
+                    event.dispatch(listener, key, value);
+                }
+            },
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn call(&self, event: Event, key: &K, value: &V) {
+        (self.call)(&*self.state, event, key, value)
+    }
+}
@@ -0,0 +1,193 @@
+//! Time source for expiring entries
+//!
+//! Deadlines are kept as nanoseconds on a monotonic clock with an arbitrary origin, so that a node stores a plain
+//! `u64` and no expiry check needs more than a comparison once the current time is known
+#[cfg(feature = "std")]
+use crate::random::Random;
+#[cfg(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown"))]
+use core::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "std")]
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Deadline of an entry that never expires
+pub(crate) const NEVER: u64 = u64::MAX;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A source of the current time, against which a cache measures time-to-live and time-to-idle deadlines.
+///
+/// Every expiry check asks the cache's clock, so a clock that only moves when told to, such as
+/// [`MockClock`](crate::test_utils::MockClock), makes expiry deterministic in tests
+pub trait Clock: Send + Sync {
+    /// Returns the time in nanoseconds since an arbitrary origin.  Must never go backwards
+    fn now(&self) -> u64;
+
+    /// Returns the time this clock will show at a given wall-clock time, so that a deadline such as an HTTP `Expires`
+    /// header can be converted without asking any other clock for the time.  Times before the clock's origin read as
+    /// zero.  By default the origin is taken to be the Unix epoch, as it is for
+    /// [`MockClock`](crate::test_utils::MockClock), so clocks with another origin must override this
+    #[cfg(feature = "std")]
+    fn reading_at(&self, time: SystemTime) -> u64 {
+        time.duration_since(UNIX_EPOCH).map_or(0, nanos)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The standard library's monotonic clock, measured from when the clock was created.  Used by caches that are not
+/// given a clock, except on `wasm32-unknown-unknown` with the `wasm` feature, where `Instant` is unavailable and
+/// `JsClock` is used instead.  Wall-clock times are converted from the system time at which the clock was created, so
+/// adjustments to the system time made since then shift them
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+pub struct SystemClock {
+    origin: Instant,
+    /// The system time at `origin`
+    wall_origin: SystemTime,
+}
+
+#[cfg(feature = "std")]
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock {
+            origin: Instant::now(),
+            wall_origin: SystemTime::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        u64::try_from(self.origin.elapsed().as_nanos()).unwrap_or(NEVER - 1)
+    }
+
+    fn reading_at(&self, time: SystemTime) -> u64 {
+        time.duration_since(self.wall_origin).map_or(0, nanos)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// JavaScript's `Date.now()`, measured from when the clock was created.  Used by caches that are not given a clock
+/// on `wasm32-unknown-unknown`.  Steps backwards in the system time are hidden by holding the clock still until it
+/// catches up
+#[cfg(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown"))]
+#[derive(Debug)]
+pub struct JsClock {
+    /// Milliseconds since the Unix epoch
+    origin: f64,
+    /// Latest time returned, so that the clock never goes backwards
+    latest: AtomicU64,
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown"))]
+impl JsClock {
+    pub fn new() -> Self {
+        JsClock {
+            origin: js_sys::Date::now(),
+            latest: AtomicU64::new(0),
+        }
+    }
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown"))]
+impl Default for JsClock {
+    fn default() -> Self {
+        JsClock::new()
+    }
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown"))]
+impl Clock for JsClock {
+    fn now(&self) -> u64 {
+        let now = ((js_sys::Date::now() - self.origin).max(0.0) * 1_000_000.0) as u64;
+        self.latest.fetch_max(now, Ordering::Relaxed).max(now)
+    }
+
+    fn reading_at(&self, time: SystemTime) -> u64 {
+        let millis = time.duration_since(UNIX_EPOCH).map_or(0.0, |since| since.as_secs_f64() * 1_000.0);
+        ((millis - self.origin).max(0.0) * 1_000_000.0) as u64
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The clock started by a cache that was not given one
+#[cfg(all(feature = "std", not(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown"))))]
+pub(crate) type DefaultClock = SystemClock;
+#[cfg(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown"))]
+pub(crate) type DefaultClock = JsClock;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Decides an item's time-to-live from its key and value, set through
+/// [`LruCacheBuilder::ttl_fn`](crate::LruCacheBuilder::ttl_fn)
+#[cfg(feature = "std")]
+pub(crate) type TtlFn<K, V> =
+    crate::callback::Callback<fn(&crate::callback::State, &K, &V) -> Option<core::time::Duration>>;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// When an entry expires, and the time-to-live that set the deadline
+#[derive(Clone, Copy)]
+pub(crate) struct Expiry {
+    pub(crate) at: u64,
+    /// In nanoseconds, or [`NEVER`] if using the entry never renews the deadline
+    pub(crate) ttl: u64,
+}
+
+impl Expiry {
+    pub(crate) const NEVER: Expiry = Expiry { at: NEVER, ttl: NEVER };
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Converts a duration to nanoseconds, saturating at [`NEVER`]
+#[cfg(feature = "std")]
+pub(crate) fn nanos(duration: core::time::Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(NEVER)
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Adds a time-to-live in nanoseconds to the current time, saturating short of [`NEVER`]
+#[cfg(feature = "std")]
+pub(crate) fn deadline(now: u64, ttl: u64) -> u64 {
+    now.saturating_add(ttl).min(NEVER - 1)
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Spreads out the deadlines of items inserted with the same time-to-live, set through
+/// [`LruCacheBuilder::ttl_jitter`](crate::LruCacheBuilder::ttl_jitter)
+#[cfg(feature = "std")]
+pub(crate) struct Jitter {
+    fraction: f64,
+    random: Random,
+}
+
+#[cfg(feature = "std")]
+impl Jitter {
+    pub(crate) fn new(fraction: f64, seed: u64) -> Self {
+        Jitter {
+            fraction,
+            random: Random::new(seed),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Scales a time-to-live in nanoseconds by a factor drawn uniformly from `[1 - fraction, 1 + fraction]`
+    pub(crate) fn apply(&mut self, ttl: u64) -> u64 {
+        let factor = 1.0 - self.fraction + 2.0 * self.fraction * self.random.next_unit();
+        (ttl as f64 * factor) as u64
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Times a load.  Uses the cache's clock if it has one, so that a mock clock controls the result, or else a clock of
+/// its own, since starting the cache's clock would make it stamp every entry from then on
+#[cfg(feature = "std")]
+pub(crate) enum Stopwatch {
+    /// Started at this time on the cache's clock
+    Shared(u64),
+    Own(DefaultClock),
+}
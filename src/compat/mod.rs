@@ -0,0 +1,203 @@
+//! Drop-in replacement for the [`lru`](https://crates.io/crates/lru) crate's `LruCache`
+//!
+//! Code written against `lru::LruCache` can switch to this implementation by changing its import to
+//! `use lru_cache::compat::LruCache;`.  The method names, signatures and semantics match those of the `lru` crate,
+//! and the extra operations provided by this crate (such as [`LruCache::pop_mru`]) are also available.
+use crate::{DefaultHashBuilder, LruCache as Inner};
+use core::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    num::NonZeroUsize,
+};
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache exposing the `lru` crate's API
+pub struct LruCache<K, V, S = DefaultHashBuilder> {
+    inner: Inner<K, V, S>,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V> LruCache<K, V>
+where
+    K: Eq + Hash,
+{
+    /// Creates a new cache that holds at most `cap` items
+    pub fn new(cap: NonZeroUsize) -> Self {
+        LruCache { inner: Inner::new(cap) }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> LruCache<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Creates a new cache that holds at most `cap` items and uses the given hash builder to hash keys
+    pub fn with_hasher(cap: NonZeroUsize, hash_builder: S) -> Self {
+        LruCache {
+            inner: Inner::with_hasher(cap, hash_builder),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Puts a key-value pair into the cache, returning the old value if the key already existed
+    pub fn put(&mut self, k: K, v: V) -> Option<V> {
+        self.inner.put(k, v)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Pushes a key-value pair into the cache, returning the replaced or evicted entry
+    pub fn push(&mut self, k: K, v: V) -> Option<(K, V)> {
+        self.inner.push(k, v)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns a reference to the value of the key, making it the most recently used
+    pub fn get<Q>(&mut self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.get(k)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns a mutable reference to the value of the key, making it the most recently used
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.get_mut_ref(k)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns a reference to the value of the key without updating the recency order
+    pub fn peek<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.peek(k)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns a mutable reference to the value of the key without updating the recency order
+    pub fn peek_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.peek_mut_ref(k)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the least recently used entry without updating the recency order
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        self.inner.peek_lru_entry()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains the key.  The recency order is not updated
+    pub fn contains<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.contains_key(k)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the key, returning its value if it was present
+    pub fn pop<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.remove(k)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the key, returning the stored key and its value if it was present
+    pub fn pop_entry<Q>(&mut self, k: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.pop_entry(k)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes and returns the least recently used entry
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        self.inner.pop_lru_entry()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes and returns the value of the most recently used entry.  Not available in the `lru` crate
+    pub fn pop_mru(&mut self) -> Option<V> {
+        self.inner.pop_mru()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Marks the key as the most recently used
+    pub fn promote<Q>(&mut self, k: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.promote(k);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Marks the key as the least recently used
+    pub fn demote<Q>(&mut self, k: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.demote(k);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of entries in the cache
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of entries the cache can hold
+    pub fn cap(&self) -> NonZeroUsize {
+        self.inner.capacity()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes all entries from the cache
+    pub fn clear(&mut self) {
+        self.inner.clear()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Unwraps the shim, returning this crate's native cache
+    pub fn into_inner(self) -> Inner<K, V, S> {
+        self.inner
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> From<Inner<K, V, S>> for LruCache<K, V, S> {
+    fn from(inner: Inner<K, V, S>) -> Self {
+        LruCache { inner }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,268 @@
+//! Examples from the `lru` crate's documentation, run against the compatibility shim
+use super::*;
+#[cfg(feature = "std")]
+use crate::test_utils::MockClock;
+#[cfg(feature = "std")]
+use core::time::Duration;
+use std::{format, string::String, vec::Vec};
+
+fn cap(n: usize) -> NonZeroUsize {
+    NonZeroUsize::new(n).unwrap()
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn readme_example() -> Result<(), String> {
+    let mut cache = LruCache::new(cap(2));
+    cache.put("apple", 3);
+    cache.put("banana", 2);
+
+    let first_reads = (cache.get(&"apple").copied(), cache.get(&"banana").copied(), cache.get(&"pear").copied());
+    let puts = (cache.put("banana", 4), cache.put("pear", 5));
+    let second_reads = (cache.get(&"pear").copied(), cache.get(&"banana").copied(), cache.get(&"apple").copied());
+
+    if let Some(v) = cache.get_mut(&"banana") {
+        *v = 6;
+    }
+
+    match (first_reads, puts, second_reads, cache.get(&"banana")) {
+        ((Some(3), Some(2), None), (Some(2), None), (Some(5), Some(4), None), Some(6)) => Ok(()),
+        other => Err(format!("Expected the lru crate's README results. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn push_example() -> Result<(), String> {
+    let mut cache = LruCache::new(cap(2));
+
+    let fresh = (cache.push(1, "a"), cache.push(2, "b"));
+    // This push call returns (2, "b") because that was previously 2's entry in the cache.
+    let replaced = cache.push(2, "beta");
+    // This push call returns (1, "a") because the cache is at capacity and 1's entry was the lru entry.
+    let evicted = cache.push(3, "alpha");
+    let reads = (cache.get(&1).copied(), cache.get(&2).copied(), cache.get(&3).copied());
+
+    match (fresh, replaced, evicted, reads) {
+        ((None, None), Some((2, "b")), Some((1, "a")), (None, Some("beta"), Some("alpha"))) => Ok(()),
+        other => Err(format!("Expected the replaced entry and then the LRU entry back. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn peek_example() -> Result<(), String> {
+    let mut cache = LruCache::new(cap(2));
+
+    cache.put(1, "a");
+    cache.put(2, "b");
+    let peeks = (cache.peek(&1).copied(), cache.peek(&2).copied());
+
+    // Peeking did not promote 1, so it is evicted
+    cache.put(3, "c");
+    let evicted = cache.peek(&1).copied();
+    *cache.peek_mut(&2).ok_or("Key 2 Not Found")? = "bb";
+
+    match (peeks, evicted, cache.peek(&2)) {
+        ((Some("a"), Some("b")), None, Some(&"bb")) => Ok(()),
+        other => Err(format!("Expected key 1 evicted and key 2 changed in place. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn peek_lru_example() -> Result<(), String> {
+    let mut cache = LruCache::new(cap(2));
+    let empty = cache.peek_lru().is_none();
+
+    cache.put(1, "a");
+    cache.put(2, "b");
+    cache.put(3, "c");
+
+    match (empty, cache.peek_lru()) {
+        (true, Some((2, &"b"))) => Ok(()),
+        other => Err(format!("Expected nothing, then key 2. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn contains_example() -> Result<(), String> {
+    let mut cache = LruCache::new(cap(2));
+
+    cache.put(1, "a");
+    cache.put(2, "b");
+    cache.put(3, "c");
+
+    match (cache.contains(&1), cache.contains(&2), cache.contains(&3)) {
+        (false, true, true) => Ok(()),
+        other => Err(format!("Expected only keys 2 and 3. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn pop_example() -> Result<(), String> {
+    let mut cache = LruCache::new(cap(2));
+
+    cache.put(2, "a");
+
+    match (cache.pop(&1), cache.pop(&2), cache.pop(&2), cache.len()) {
+        (None, Some("a"), None, 0) => Ok(()),
+        other => Err(format!("Expected key 2 popped once. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn pop_entry_example() -> Result<(), String> {
+    let mut cache = LruCache::new(cap(2));
+
+    cache.put(1, "a");
+    cache.put(2, "b");
+
+    match (cache.pop_entry(&1), cache.len()) {
+        (Some((1, "a")), 1) => Ok(()),
+        other => Err(format!("Expected key 1 popped with its value. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn pop_lru_example() -> Result<(), String> {
+    let mut cache = LruCache::new(cap(2));
+
+    cache.put(2, "a");
+    cache.put(3, "b");
+    cache.put(4, "c");
+    cache.get(&3);
+
+    match (cache.pop_lru(), cache.pop_lru(), cache.pop_lru(), cache.len()) {
+        (Some((4, "c")), Some((3, "b")), None, 0) => Ok(()),
+        other => Err(format!("Expected keys 4 and 3 popped in turn. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn promote_example() -> Result<(), String> {
+    let mut cache = LruCache::new(cap(3));
+
+    cache.put(1, "a");
+    cache.put(2, "b");
+    cache.put(3, "c");
+    cache.get(&1);
+    cache.get(&2);
+
+    // If we do `pop_lru` now, we would pop 3.
+    // By promoting 3, we make sure it isn't popped.
+    cache.promote(&3);
+
+    match cache.pop_lru() {
+        Some((1, "a")) => Ok(()),
+        other => Err(format!("Expected key 1 popped. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn demote_example() -> Result<(), String> {
+    let mut cache = LruCache::new(cap(3));
+
+    cache.put(1, "a");
+    cache.put(2, "b");
+    cache.put(3, "c");
+    cache.get(&1);
+    cache.get(&2);
+
+    // If we do `pop_lru` now, we would pop 3.
+    // By demoting 1 and 2, we make sure those are popped first.
+    cache.demote(&2);
+    cache.demote(&1);
+
+    match (cache.pop_lru(), cache.pop_lru()) {
+        (Some((1, "a")), Some((2, "b"))) => Ok(()),
+        other => Err(format!("Expected keys 1 and 2 popped in turn. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn len_cap_and_clear_example() -> Result<(), String> {
+    let mut cache = LruCache::new(cap(2));
+    let fresh = (cache.is_empty(), cache.cap().get());
+
+    cache.put(1, "a");
+    let one = cache.len();
+
+    cache.put(2, "b");
+    cache.put(3, "c");
+    let full = cache.len();
+
+    cache.clear();
+
+    match (fresh, one, full, cache.len(), cache.is_empty()) {
+        ((true, 2), 1, 2, 0, true) => Ok(()),
+        other => Err(format!("Expected the cache to fill to 2 and empty again. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_pop_mru_via_shim() -> Result<(), String> {
+    let mut cache = LruCache::new(cap(3));
+
+    cache.put(1, "a");
+    cache.put(2, "b");
+    cache.get(&1);
+
+    match (cache.pop_mru(), cache.into_inner().len()) {
+        (Some("a"), 1) => Ok(()),
+        other => Err(format!("Expected key 1 popped, leaving one item. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[cfg(feature = "std")]
+#[test]
+fn peek_mut_should_not_find_an_expired_item() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut cache = LruCache {
+        inner: Inner::with_clock(cap(2), clock.clone()),
+    };
+    cache.inner.put_with_ttl(1, "a", Duration::from_secs(1));
+    clock.advance(Duration::from_secs(1));
+    let peeked = cache.peek_mut(&1).is_some();
+
+    match (peeked, cache.contains(&1)) {
+        (false, false) => Ok(()),
+        other => Err(format!("Expected the expired key 1 to be absent. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_match_lru_crate_on_mixed_workload() -> Result<(), String> {
+    let mut ours = LruCache::new(cap(8));
+    let mut theirs = lru::LruCache::new(cap(8));
+    let mut diverged = Vec::new();
+
+    for i in 0..200u32 {
+        let k = (i * 7) % 13;
+        let pushed = (ours.push(k, i), theirs.push(k, i));
+        let read = (ours.get(&((i * 5) % 11)).copied(), theirs.get(&((i * 5) % 11)).copied());
+        let popped = match i % 9 {
+            0 => (ours.pop_lru(), theirs.pop_lru()),
+            _ => (None, None),
+        };
+
+        if pushed.0 != pushed.1 || read.0 != read.1 || popped.0 != popped.1 {
+            diverged.push((i, pushed, read, popped));
+        }
+    }
+
+    match (diverged.first(), ours.len() == theirs.len()) {
+        (None, true) => Ok(()),
+        other => Err(format!("Expected the same results as the lru crate. Got {other:?}")),
+    }
+}
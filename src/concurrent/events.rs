@@ -0,0 +1,315 @@
+//! Eviction events, handed from a [`ConcurrentLruCache`](super::ConcurrentLruCache) to a subscriber through a bounded
+//! queue
+//!
+//! Events are queued after the cache's lock has been released, so that a subscriber which uses the cache itself cannot
+//! deadlock with the thread whose write caused the eviction.  When the queue is full, the [`OverflowPolicy`] decides
+//! whether the oldest event is discarded or the evicting thread waits for the subscriber to catch up
+use crate::sync::{Arc, AtomicU64, Condvar, Mutex, MutexGuard, Ordering};
+use std::{collections::VecDeque, num::NonZeroUsize, sync::PoisonError};
+
+/// Events queued for a subscriber before the [`OverflowPolicy`] applies, unless configured otherwise
+pub(super) const DEFAULT_QUEUE_LENGTH: usize = 1_024;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// An item that left the cache without being handed back to the caller that caused it to leave
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvictionEvent<K, V> {
+    pub key: K,
+    pub value: V,
+    pub reason: EvictionReason,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Why an item was evicted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EvictionReason {
+    /// The cache was full, and the item was the least recently used when a new item was added
+    Capacity,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// What happens to a new event when the subscriber's queue is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Discard the oldest queued event to make room, counting it in [`EvictionEvents::dropped`]
+    #[default]
+    DropOldest,
+    /// Make the evicting thread wait until the subscriber takes an event or goes away.  The cache's lock is not held
+    /// while waiting, but the write that caused the eviction does not return until then
+    Block,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+struct State<K, V> {
+    events: VecDeque<EvictionEvent<K, V>>,
+    /// Cleared when the cache is dropped or a newer subscription replaces this one
+    sending: bool,
+    /// Cleared when the subscriber drops its [`EvictionEvents`]
+    receiving: bool,
+    /// The task that last awaited [`EvictionEvents::recv_async`]
+    #[cfg(feature = "tokio")]
+    receiving_task: Option<tokio::task::Id>,
+}
+
+/// The queue shared between the cache and one subscriber
+struct Queue<K, V> {
+    state: Mutex<State<K, V>>,
+    length: usize,
+    overflow: OverflowPolicy,
+    dropped: AtomicU64,
+    /// Signalled when an event is queued or sending stops
+    arrived: Condvar,
+    /// Signalled when an event is taken or receiving stops
+    taken: Condvar,
+    /// Wakes a subscriber awaiting [`EvictionEvents::recv_async`]
+    #[cfg(feature = "tokio")]
+    wake: tokio::sync::Notify,
+    /// Wakes tasks awaiting [`EventSender::finish`]
+    #[cfg(feature = "tokio")]
+    drained: tokio::sync::Notify,
+}
+
+impl<K, V> Queue<K, V> {
+    /// Locks the queue.  No user code runs under this lock, so a poisoned queue is still consistent
+    fn lock(&self) -> MutexGuard<'_, State<K, V>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn arrived(&self) {
+        self.arrived.notify_all();
+
+        #[cfg(feature = "tokio")]
+        self.wake.notify_one();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn taken(&self) {
+        self.taken.notify_all();
+
+        #[cfg(feature = "tokio")]
+        self.drained.notify_waiters();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn push(&self, event: EvictionEvent<K, V>) {
+        let mut state = self.lock();
+
+        while state.receiving && state.events.len() >= self.length {
+            match self.overflow {
+                OverflowPolicy::DropOldest => {
+                    state.events.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                OverflowPolicy::Block => state = self.taken.wait(state).unwrap_or_else(PoisonError::into_inner),
+            }
+        }
+
+        // Nobody will ever take an event once the subscriber has gone
+        if state.receiving {
+            state.events.push_back(event);
+            drop(state);
+            self.arrived();
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Takes the oldest event.  `Err` means the queue is empty and no more events will arrive
+    fn pop(&self, state: &mut State<K, V>) -> Result<Option<EvictionEvent<K, V>>, ()> {
+        match state.events.pop_front() {
+            Some(event) => {
+                self.taken();
+                Ok(Some(event))
+            }
+            None if state.sending => Ok(None),
+            None => Err(()),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn stop_sending(&self) {
+        self.lock().sending = false;
+        self.arrived();
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The cache's end of the current subscription, if there is one
+pub(super) struct EventSender<K, V> {
+    queue: Mutex<Option<Arc<Queue<K, V>>>>,
+    length: NonZeroUsize,
+    overflow: OverflowPolicy,
+}
+
+impl<K, V> EventSender<K, V> {
+    pub(super) fn new(length: NonZeroUsize, overflow: OverflowPolicy) -> Self {
+        EventSender {
+            queue: Mutex::new(None),
+            length,
+            overflow,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn current(&self) -> MutexGuard<'_, Option<Arc<Queue<K, V>>>> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Starts a new subscription, ending the previous one
+    pub(super) fn subscribe(&self) -> EvictionEvents<K, V> {
+        let queue = Arc::new(Queue {
+            state: Mutex::new(State {
+                events: VecDeque::new(),
+                sending: true,
+                receiving: true,
+                #[cfg(feature = "tokio")]
+                receiving_task: None,
+            }),
+            length: self.length.get(),
+            overflow: self.overflow,
+            dropped: AtomicU64::new(0),
+            arrived: Condvar::new(),
+            taken: Condvar::new(),
+            #[cfg(feature = "tokio")]
+            wake: tokio::sync::Notify::new(),
+            #[cfg(feature = "tokio")]
+            drained: tokio::sync::Notify::new(),
+        });
+
+        if let Some(previous) = self.current().replace(Arc::clone(&queue)) {
+            previous.stop_sending();
+        }
+
+        EvictionEvents { queue }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Queues an event for the subscriber, if there is one.  Must not be called while holding the cache's lock
+    pub(super) fn evicted(&self, evicted: Option<(K, V)>, reason: EvictionReason) {
+        let Some((key, value)) = evicted else {
+            return;
+        };
+
+        // Cloned so that a full queue under `OverflowPolicy::Block` does not stop others subscribing meanwhile
+        let queue = self.current().clone();
+
+        if let Some(queue) = queue {
+            queue.push(EvictionEvent { key, value, reason });
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<K, V> EventSender<K, V> {
+    /// Ends the current subscription, then waits until the subscriber has taken every queued event or gone away.
+    /// Does not wait when called from the task receiving the events, which could never take them while waiting
+    pub(crate) async fn finish(&self) {
+        let Some(queue) = self.current().clone() else {
+            return;
+        };
+
+        queue.stop_sending();
+
+        loop {
+            let drained = queue.drained.notified();
+            let mut drained = core::pin::pin!(drained);
+            drained.as_mut().enable();
+
+            {
+                let state = queue.lock();
+                let receiving_here = state.receiving_task.is_some() && state.receiving_task == tokio::task::try_id();
+
+                if state.events.is_empty() || !state.receiving || receiving_here {
+                    return;
+                }
+            }
+
+            drained.await;
+        }
+    }
+}
+
+impl<K, V> Drop for EventSender<K, V> {
+    fn drop(&mut self) {
+        if let Some(queue) = self.current().take() {
+            queue.stop_sending();
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The subscriber's end of a bounded queue of [`EvictionEvent`]s, returned by
+/// [`ConcurrentLruCache::eviction_events`](super::ConcurrentLruCache::eviction_events).  Events arrive in the order
+/// the evictions happened.  Iterating waits for each event in turn, and ends once no more can arrive
+pub struct EvictionEvents<K, V> {
+    queue: Arc<Queue<K, V>>,
+}
+
+impl<K, V> EvictionEvents<K, V> {
+    /// Waits for the next event.  Returns `None` once every queued event has been received and no more can arrive,
+    /// because the cache was dropped or a newer subscription replaced this one
+    pub fn recv(&self) -> Option<EvictionEvent<K, V>> {
+        let mut state = self.queue.lock();
+
+        loop {
+            match self.queue.pop(&mut state) {
+                Ok(Some(event)) => return Some(event),
+                Ok(None) => state = self.queue.arrived.wait(state).unwrap_or_else(PoisonError::into_inner),
+                Err(()) => return None,
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Takes the next event if one is queued, without waiting
+    pub fn try_recv(&self) -> Option<EvictionEvent<K, V>> {
+        self.queue.pop(&mut self.queue.lock()).ok().flatten()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Like [`recv`](Self::recv), but awaits the next event instead of blocking the thread
+    #[cfg(feature = "tokio")]
+    pub async fn recv_async(&self) -> Option<EvictionEvent<K, V>> {
+        loop {
+            // Bound first, so that the lock is released before awaiting
+            let next = {
+                let mut state = self.queue.lock();
+                state.receiving_task = tokio::task::try_id();
+                self.queue.pop(&mut state)
+            };
+
+            match next {
+                Ok(Some(event)) => return Some(event),
+                // A wake-up sent since the queue was checked is kept until this awaits it, so none is missed
+                Ok(None) => self.queue.wake.notified().await,
+                Err(()) => return None,
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of events discarded under [`OverflowPolicy::DropOldest`] because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<K, V> Iterator for EvictionEvents<K, V> {
+    type Item = EvictionEvent<K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+impl<K, V> Drop for EvictionEvents<K, V> {
+    fn drop(&mut self) {
+        let mut state = self.queue.lock();
+        state.receiving = false;
+        state.events.clear();
+        drop(state);
+        self.queue.taken();
+    }
+}
@@ -0,0 +1,99 @@
+//! A background thread that removes expired items from a [`ConcurrentLruCache`](super::ConcurrentLruCache)
+//!
+//! The thread holds only a weak reference to the cache, and waits between passes on a condvar rather than sleeping,
+//! so that dropping its [`ExpiryHandle`], or the last reference to the cache, stops it without waiting out the
+//! interval
+use std::{
+    sync::{Arc, Condvar, Mutex, PoisonError, Weak},
+    thread::{self, JoinHandle},
+    time::Duration,
+    vec::Vec,
+};
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Tells one expiry thread to stop
+#[derive(Default)]
+pub(super) struct Signal {
+    stopped: Mutex<bool>,
+    wake: Condvar,
+}
+
+impl Signal {
+    fn stop(&self) {
+        *self.stopped.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        self.wake.notify_all();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Waits for up to `timeout`, returning `true` if the thread has been told to stop
+    pub(super) fn wait(&self, timeout: Duration) -> bool {
+        let stopped = self.stopped.lock().unwrap_or_else(PoisonError::into_inner);
+        let (stopped, _) = self
+            .wake
+            .wait_timeout_while(stopped, timeout, |stopped| !*stopped)
+            .unwrap_or_else(PoisonError::into_inner);
+
+        *stopped
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The expiry threads started for a cache, which are told to stop when the cache is dropped
+#[derive(Default)]
+pub(super) struct Daemons {
+    signals: Mutex<Vec<Weak<Signal>>>,
+}
+
+impl Daemons {
+    pub(super) fn register(&self, signal: &Arc<Signal>) {
+        let mut signals = self.signals.lock().unwrap_or_else(PoisonError::into_inner);
+        signals.retain(|signal| signal.strong_count() > 0);
+        signals.push(Arc::downgrade(signal));
+    }
+}
+
+impl Drop for Daemons {
+    fn drop(&mut self) {
+        let signals = self.signals.get_mut().unwrap_or_else(PoisonError::into_inner);
+
+        for signal in signals.drain(..).filter_map(|signal| signal.upgrade()) {
+            signal.stop();
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Controls a thread started by
+/// [`ConcurrentLruCache::start_expiry_daemon`](super::ConcurrentLruCache::start_expiry_daemon).  Dropping the handle
+/// stops the thread and waits for it to finish any pass under way
+pub struct ExpiryHandle {
+    signal: Arc<Signal>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ExpiryHandle {
+    pub(super) fn new(signal: Arc<Signal>, thread: JoinHandle<()>) -> Self {
+        ExpiryHandle {
+            signal,
+            thread: Some(thread),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` once the thread has stopped, which it does by itself when the cache is dropped
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+}
+
+impl Drop for ExpiryHandle {
+    fn drop(&mut self) {
+        self.signal.stop();
+
+        if let Some(thread) = self.thread.take()
+            && thread.thread().id() != thread::current().id()
+        {
+            let _ = thread.join();
+        }
+    }
+}
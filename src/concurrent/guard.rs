@@ -0,0 +1,75 @@
+//! Guards returned by [`ConcurrentLruCache::get_guarded`](super::ConcurrentLruCache::get_guarded)
+//!
+//! Each guard counts towards its entry's guard count, which exempts the entry from eviction while it is above zero.
+//! Dropping the guard takes the lock again to decrement the count
+use super::ConcurrentLruCache;
+use core::{fmt, ops::Deref};
+use std::{
+    hash::{BuildHasher, Hash},
+    sync::PoisonError,
+};
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A copy of an item's value that keeps the item in the cache until it is dropped.  Capacity eviction skips a guarded
+/// item and evicts the next candidate instead, but the item can still be removed, popped, replaced or expire, in which
+/// case the guard keeps the value it was given
+pub struct EntryGuard<'a, K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    cache: &'a ConcurrentLruCache<K, V, S>,
+    key: K,
+    value: V,
+}
+
+impl<'a, K, V, S> EntryGuard<'a, K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    pub(super) fn new(cache: &'a ConcurrentLruCache<K, V, S>, key: K, value: V) -> Self {
+        EntryGuard { cache, key, value }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// The guarded item's key
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+}
+
+impl<K, V, S> Deref for EntryGuard<'_, K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        &self.value
+    }
+}
+
+impl<K, V, S> Drop for EntryGuard<'_, K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn drop(&mut self) {
+        // Ignore poisoning rather than risk panicking during a panic: a poisoned cache is emptied before its next use
+        let mut cache = self.cache.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        cache.release(&self.key);
+    }
+}
+
+impl<K, V, S> fmt::Debug for EntryGuard<'_, K, V, S>
+where
+    K: Eq + Hash + fmt::Debug,
+    V: fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EntryGuard").field("key", &self.key).field("value", &self.value).finish()
+    }
+}
@@ -0,0 +1,66 @@
+//! The lock guard of a [`ConcurrentLruCache`](super::ConcurrentLruCache)
+//!
+//! While a thread holds the lock, the cache queues the events for its listeners instead of delivering them.  Dropping
+//! the guard releases the lock first and then delivers the queued events, so that a listener can log, take other locks
+//! or use the cache without deadlocking
+use crate::{
+    LruCache,
+    listener::ListenerList,
+    sync::MutexGuard,
+};
+use core::ops::{Deref, DerefMut};
+use std::thread;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A locked [`LruCache`] whose queued listener events are delivered once the lock is released
+pub(super) struct Locked<'a, K, V, S> {
+    /// Only taken when the guard is dropped
+    guard: Option<MutexGuard<'a, LruCache<K, V, S>>>,
+    listeners: Option<&'a ListenerList<K, V>>,
+}
+
+impl<'a, K, V, S> Locked<'a, K, V, S> {
+    pub(super) fn new(guard: MutexGuard<'a, LruCache<K, V, S>>, listeners: Option<&'a ListenerList<K, V>>) -> Self {
+        Locked {
+            guard: Some(guard),
+            listeners,
+        }
+    }
+}
+
+impl<K, V, S> Deref for Locked<'_, K, V, S> {
+    type Target = LruCache<K, V, S>;
+
+    fn deref(&self) -> &LruCache<K, V, S> {
+        self.guard.as_ref().unwrap()
+    }
+}
+
+impl<K, V, S> DerefMut for Locked<'_, K, V, S> {
+    fn deref_mut(&mut self) -> &mut LruCache<K, V, S> {
+        self.guard.as_mut().unwrap()
+    }
+}
+
+impl<K, V, S> Drop for Locked<'_, K, V, S> {
+    fn drop(&mut self) {
+        let Some(mut guard) = self.guard.take() else {
+            return;
+        };
+        let Some(listeners) = self.listeners else {
+            return;
+        };
+        let events = guard.take_events();
+        drop(guard);
+
+        // A listener that panicked while the thread unwinds would abort the process, and the events may describe an
+        // operation that never finished
+        if thread::panicking() {
+            return;
+        }
+
+        for (event, key, value) in events {
+            event.deliver(listeners, &key, &value);
+        }
+    }
+}
@@ -0,0 +1,656 @@
+//! Thread-safe LRU cache
+//!
+//! [`ConcurrentLruCache`] owns an [`LruCache`] behind a mutex, so that it can be shared between threads (typically in
+//! an `Arc`) without callers locking it themselves.  Every method takes `&self` and holds the lock only for the
+//! duration of the call, so values are returned by clone rather than by reference.  Store values in an `Arc` when
+//! cloning them is expensive.
+//!
+//! # Poisoning
+//!
+//! If a thread panics while holding the lock, for example because a key's `Hash` or `Eq` implementation or the
+//! closure passed to [`ConcurrentLruCache::with_lock`] panicked, the cache may have been left part way through an
+//! update.  By default ([`PoisonPolicy::ClearAndContinue`]) the next call to lock the cache discards every item and
+//! clears the poisoned state, so later calls see an empty cache rather than panicking themselves, and
+//! [`ConcurrentLruCache::poison_recoveries`] counts how often that has happened.  Under [`PoisonPolicy::Propagate`],
+//! every call that finds the lock poisoned panics instead
+//!
+//! # Non-blocking access
+//!
+//! The `try_` methods never wait for the lock.  If another thread holds it they return [`WouldBlock`] straight away,
+//! so that latency-sensitive callers can skip the cache, for example by recomputing the value, rather than queue
+//!
+//! # Loading missing values
+//!
+//! [`ConcurrentLruCache::get_or_insert_with`] runs at most one loader per key at a time.  Other callers asking for the
+//! same key while it loads wait for that loader's value instead of running their own, so a popular key going missing
+//! does not send every thread to the backing store at once
+//!
+//! # Snapshots
+//!
+//! [`ConcurrentLruCache::snapshot`] and [`ConcurrentLruCache::snapshot_keys`] copy the cache's contents under the lock
+//! and release it before returning, so that slow work such as serializing the copy does not hold up other threads.
+//! The copy is a point-in-time view: it reflects no operation that started after it was taken
+//!
+//! # Statistics
+//!
+//! [`ConcurrentLruCache::stats`] reports the hits, misses, insertions, updates, evictions and removals caused by the
+//! cache's own methods, and the time taken by the loaders of
+//! [`get_or_insert_with`](ConcurrentLruCache::get_or_insert_with).  Operations performed through
+//! [`ConcurrentLruCache::with_lock`] are not counted
+//!
+//! # Guarded entries
+//!
+//! [`ConcurrentLruCache::get_guarded`] returns an [`EntryGuard`] holding a copy of the value.  Until every guard on an
+//! item is dropped, capacity eviction passes over it and evicts the next candidate, so that an item known to be in use
+//! elsewhere is not evicted and reloaded
+//!
+//! # Eviction events
+//!
+//! [`ConcurrentLruCache::eviction_events`] subscribes to the items that [`put`](ConcurrentLruCache::put) and
+//! [`get_or_insert_with`](ConcurrentLruCache::get_or_insert_with) evict, so that another thread can deal with them,
+//! for example by persisting them, away from the call that happened to cause the eviction.  Events wait in a bounded
+//! queue, [`ConcurrentLruCache::with_event_queue`] sets its length and what happens when it is full.  Items handed
+//! back to the caller, such as those displaced by [`push`](ConcurrentLruCache::push), and evictions made through
+//! [`with_lock`](ConcurrentLruCache::with_lock), are not reported
+mod events;
+#[cfg(not(loom))]
+mod expiry;
+mod guard;
+mod locked;
+mod single_flight;
+mod stats;
+
+use crate::{
+    DefaultHashBuilder, LruCache,
+    clock::Stopwatch,
+    listener::ListenerList,
+    sync::{Arc, AtomicU64, AtomicUsize, Mutex, MutexGuard, Ordering, ResetPoison},
+};
+use core::fmt;
+use events::{DEFAULT_QUEUE_LENGTH, EventSender};
+pub use crate::CacheStats;
+pub use events::{EvictionEvent, EvictionEvents, EvictionReason, OverflowPolicy};
+#[cfg(not(loom))]
+use expiry::{Daemons, Signal};
+#[cfg(not(loom))]
+pub use expiry::ExpiryHandle;
+pub use guard::EntryGuard;
+use locked::Locked;
+use single_flight::{Flight, InFlight, Latch, in_flight};
+use stats::Counters;
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    error::Error,
+    hash::{BuildHasher, Hash},
+    num::NonZeroUsize,
+    sync::{PoisonError, TryLockError},
+    vec::Vec,
+};
+#[cfg(not(loom))]
+use std::{thread, time::Duration};
+
+/// Message of the panic raised under [`PoisonPolicy::Propagate`]
+const POISONED: &str = "the cache's lock was poisoned by a panic in another thread";
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// What a [`ConcurrentLruCache`] does when a thread panicked while holding its lock
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PoisonPolicy {
+    /// Discard every item, count the recovery and carry on with an empty cache
+    #[default]
+    ClearAndContinue,
+    /// Panic, as `lock().unwrap()` on a poisoned mutex would
+    Propagate,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Returned by the `try_` methods of [`ConcurrentLruCache`] when another thread holds the lock.  Carries anything the
+/// call would have consumed, such as the key and value passed to [`ConcurrentLruCache::try_put`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WouldBlock<T = ()>(pub T);
+
+impl<T> WouldBlock<T> {
+    /// Returns whatever the failed call would have consumed
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Display for WouldBlock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the cache is locked by another thread")
+    }
+}
+
+impl<T: fmt::Debug> Error for WouldBlock<T> {}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache that can be shared between threads
+pub struct ConcurrentLruCache<K, V, S = DefaultHashBuilder> {
+    inner: Mutex<LruCache<K, V, S>>,
+    loading: InFlight<K, V>,
+    stats: Counters,
+    poison_policy: PoisonPolicy,
+    poison_recoveries: AtomicU64,
+    events: EventSender<K, V>,
+    /// The listeners of the wrapped cache, to which each lock delivers its events once released
+    listeners: Option<ListenerList<K, V>>,
+    #[cfg(not(loom))]
+    daemons: Daemons,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V> ConcurrentLruCache<K, V>
+where
+    K: Eq + Hash,
+{
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        ConcurrentLruCache::from(LruCache::new(capacity))
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> ConcurrentLruCache<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Creates a cache that uses the given hash builder to hash keys
+    pub fn with_hasher(capacity: NonZeroUsize, hash_builder: S) -> Self {
+        ConcurrentLruCache::from(LruCache::with_hasher(capacity, hash_builder))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Sets what happens when a thread panics while holding the lock.  The default is
+    /// [`PoisonPolicy::ClearAndContinue`]
+    pub fn with_poison_policy(mut self, policy: PoisonPolicy) -> Self {
+        self.poison_policy = policy;
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Sets how many eviction events can wait for a subscriber, and what happens to further events when that many are
+    /// waiting.  The default is 1,024 events and [`OverflowPolicy::DropOldest`]
+    pub fn with_event_queue(mut self, length: NonZeroUsize, overflow: OverflowPolicy) -> Self {
+        self.events = EventSender::new(length, overflow);
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Applies the poison policy to a lock that a panicking thread held
+    fn recover<'a>(
+        &self,
+        poisoned: PoisonError<MutexGuard<'a, LruCache<K, V, S>>>,
+    ) -> MutexGuard<'a, LruCache<K, V, S>> {
+        if self.poison_policy == PoisonPolicy::Propagate {
+            panic!("{POISONED}");
+        }
+
+        let mut cache = poisoned.into_inner();
+        cache.discard_all();
+        self.inner.reset_poison();
+        self.poison_recoveries.fetch_add(1, Ordering::Relaxed);
+        cache
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Locks the cache, applying the poison policy first if a thread panicked while holding the lock
+    fn lock(&self) -> Locked<'_, K, V, S> {
+        let guard = self.inner.lock().unwrap_or_else(|poisoned| self.recover(poisoned));
+        Locked::new(guard, self.listeners.as_ref())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Locks the cache if no other thread holds the lock, applying the poison policy first if a thread panicked while
+    /// holding it
+    fn try_lock(&self) -> Result<Locked<'_, K, V, S>, WouldBlock> {
+        let guard = match self.inner.try_lock() {
+            Ok(cache) => cache,
+            Err(TryLockError::WouldBlock) => return Err(WouldBlock(())),
+            Err(TryLockError::Poisoned(poisoned)) => self.recover(poisoned),
+        };
+
+        Ok(Locked::new(guard, self.listeners.as_ref()))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Number of times the cache has been emptied because a thread panicked while holding the lock
+    pub fn poison_recoveries(&self) -> u64 {
+        self.poison_recoveries.load(Ordering::Relaxed)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a copy of an item, making it the most recently used
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.stats.get(&mut self.lock(), key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Like [`get`](Self::get), but returns [`WouldBlock`] instead of waiting if another thread holds the lock
+    pub fn try_get<Q>(&self, key: &Q) -> Result<Option<V>, WouldBlock>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        Ok(self.stats.get(&mut *self.try_lock()?, key))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Like [`get`](Self::get), but returns the copy in an [`EntryGuard`] that exempts the item from capacity eviction
+    /// until it is dropped.  Any number of guards can be held on one item
+    pub fn get_guarded(&self, key: &K) -> Option<EntryGuard<'_, K, V, S>>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let value = {
+            let mut cache = self.lock();
+            let value = self.stats.get(&mut cache, key)?;
+            cache.hold(key);
+            value
+        };
+
+        Some(EntryGuard::new(self, key.clone(), value))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetches a copy of an item, making it the most recently used, or calls `loader` and inserts its value if the key
+    /// is missing.  If another thread is already loading the key, waits for that thread's value rather than calling
+    /// `loader`.  The lock is not held while `loader` runs.  If the loading thread panics, a waiting thread takes over.
+    ///
+    /// The time `loader` takes is counted in [`CacheStats::load_time`], on the cache's clock if it has one.  If the
+    /// cache was built with [`LruCacheBuilder::early_expiration`](crate::LruCacheBuilder::early_expiration), it is
+    /// also recorded as the item's load cost
+    pub fn get_or_insert_with(&self, key: K, loader: impl FnOnce() -> V) -> V
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let (mut flight, started) = loop {
+            let latch = {
+                let mut cache = self.lock();
+
+                if let Some(value) = self.stats.lookup(cache.get_cloned(&key)) {
+                    return value;
+                }
+
+                let mut loading = in_flight(&self.loading);
+
+                match loading.get(&key) {
+                    Some(latch) => Arc::clone(latch),
+                    None => {
+                        let latch = Arc::new(Latch::new());
+                        loading.insert(key.clone(), Arc::clone(&latch));
+                        break (Flight::new(&self.loading, key.clone(), latch), cache.load_started());
+                    }
+                }
+            };
+
+            // Another thread is loading the key.  If its loader panicked, go round again and perhaps load it here
+            if let Some(value) = latch.wait() {
+                return value;
+            }
+        };
+
+        let value = loader();
+        self.put_loaded(key, value.clone(), started);
+
+        // Dropping the flight hands the value to any waiting threads
+        flight.value = Some(value.clone());
+        value
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Starts timing a load of a missing value
+    #[cfg(feature = "tokio")]
+    pub(crate) fn load_started(&self) -> Stopwatch {
+        self.lock().load_started()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a value timed by `stopwatch`, as returned by [`load_started`](Self::load_started), and counts the load
+    pub(crate) fn put_loaded(&self, key: K, value: V, stopwatch: Stopwatch)
+    where
+        K: Clone,
+    {
+        let evicted = {
+            let mut cache = self.lock();
+            let (_, evicted) = self.stats.put(&mut cache, key.clone(), value);
+            cache.load_finished(&key, stopwatch);
+            evicted
+        };
+        self.events.evicted(evicted, EvictionReason::Capacity);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetch a copy of an item without changing its position in the recency order
+    pub fn peek<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.lock().peek(key).cloned()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains the key. The item's position in the recency order is not changed
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.lock().contains_key(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item.
+    /// * If the item already exists, it returns the old value else it returns `None`
+    /// * If the addition of the new item exceeds the cache's capacity, the oldest item is evicted before the new item is
+    ///   added
+    pub fn put(&self, key: K, new_value: V) -> Option<V> {
+        let (old_value, evicted) = self.stats.put(&mut self.lock(), key, new_value);
+        self.events.evicted(evicted, EvictionReason::Capacity);
+        old_value
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Like [`put`](Self::put), but hands the key and value back in [`WouldBlock`] instead of waiting if another thread
+    /// holds the lock
+    pub fn try_put(&self, key: K, new_value: V) -> Result<Option<V>, WouldBlock<(K, V)>> {
+        let (old_value, evicted) = match self.try_lock() {
+            Ok(mut cache) => self.stats.put(&mut cache, key, new_value),
+            Err(_) => return Err(WouldBlock((key, new_value))),
+        };
+
+        self.events.evicted(evicted, EvictionReason::Capacity);
+        Ok(old_value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Like [`put`](Self::put), but then evicts least recently used items until no more than `quota` remain.  The
+    /// quota is read under the lock, so a cache whose quota only shrinks through [`trim`](Self::trim) never holds more
+    pub(crate) fn put_within(&self, key: K, new_value: V, quota: &AtomicUsize) -> Option<V> {
+        let mut cache = self.lock();
+        let (old_value, evicted) = self.stats.put(&mut cache, key, new_value);
+        self.stats.evict_beyond(&mut cache, quota.load(Ordering::Relaxed));
+        drop(cache);
+
+        self.events.evicted(evicted, EvictionReason::Capacity);
+        old_value
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Like [`put_within`](Self::put_within), but hands the key and value back in [`WouldBlock`] instead of waiting if
+    /// another thread holds the lock
+    pub(crate) fn try_put_within(
+        &self,
+        key: K,
+        new_value: V,
+        quota: &AtomicUsize,
+    ) -> Result<Option<V>, WouldBlock<(K, V)>> {
+        let Ok(mut cache) = self.try_lock() else {
+            return Err(WouldBlock((key, new_value)));
+        };
+
+        let (old_value, evicted) = self.stats.put(&mut cache, key, new_value);
+        self.stats.evict_beyond(&mut cache, quota.load(Ordering::Relaxed));
+        drop(cache);
+
+        self.events.evicted(evicted, EvictionReason::Capacity);
+        Ok(old_value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Evicts items, chosen as they would be to make room for a new one, until no more than `quota` remain.  Returns
+    /// the number evicted
+    pub(crate) fn trim(&self, quota: usize) -> usize {
+        self.stats.evict_beyond(&mut self.lock(), quota)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item, returning the item it displaced.
+    /// * If the key already exists, its value is replaced and the key and old value are returned
+    /// * If the cache is full, the least recently used item is evicted and returned
+    pub fn push(&self, key: K, new_value: V) -> Option<(K, V)> {
+        self.stats.push(&mut self.lock(), key, new_value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an item, returning its value if it was present
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.stats.removal(self.lock().remove(key))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the most recently used item
+    pub fn pop_mru(&self) -> Option<V> {
+        self.stats.removal(self.lock().pop_mru())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the item that eviction would choose next.  See [`LruCache::pop_lru`]
+    pub fn pop_lru(&self) -> Option<V> {
+        self.stats.removal(self.lock().pop_lru())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Like [`pop_lru`](Self::pop_lru), but returns [`WouldBlock`] instead of waiting if another thread holds the lock
+    pub fn try_pop_lru(&self) -> Result<Option<V>, WouldBlock> {
+        Ok(self.stats.removal(self.try_lock()?.pop_lru()))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in the cache
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains no items
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the cache can hold
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.lock().capacity()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes all items
+    pub fn clear(&self) {
+        self.lock().clear()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Does the cache's deferred housekeeping now.  See [`LruCache::run_pending_tasks`]
+    pub fn run_pending_tasks(&self) {
+        self.lock().run_pending_tasks()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes every expired item under a single lock, returning how many were removed.  See
+    /// [`LruCache::purge_expired`]
+    pub fn purge_expired(&self) -> usize {
+        self.lock().purge_expired()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Starts a thread that calls [`purge_expired`](Self::purge_expired) every `interval`, so that expired items are
+    /// removed even if nothing looks them up again.  The lock is only held for one pass at a time.
+    ///
+    /// The thread stops as soon as the returned handle or the last reference to the cache is dropped
+    #[cfg(not(loom))]
+    pub fn start_expiry_daemon(self: &Arc<Self>, interval: Duration) -> ExpiryHandle
+    where
+        K: Send + 'static,
+        V: Send + 'static,
+        S: Send + 'static,
+    {
+        let signal = Arc::new(Signal::default());
+        self.daemons.register(&signal);
+
+        let cache = Arc::downgrade(self);
+        let stop = Arc::clone(&signal);
+
+        let thread = thread::spawn(move || {
+            while !stop.wait(interval) {
+                let Some(cache) = cache.upgrade() else {
+                    break;
+                };
+
+                cache.purge_expired();
+            }
+        });
+
+        ExpiryHandle::new(signal, thread)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Copies every item, from least to most recently used, under a single lock.  Putting the items into an empty cache
+    /// in this order recreates the recency order
+    pub fn snapshot(&self) -> Vec<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let mut cache = self.lock();
+        cache.apply_deferred_reads();
+        cache.iter_lru_first().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Copies every key, from least to most recently used, under a single lock.  Cheaper than
+    /// [`snapshot`](Self::snapshot) when values are large
+    pub fn snapshot_keys(&self) -> Vec<K>
+    where
+        K: Clone,
+    {
+        let mut cache = self.lock();
+        cache.apply_deferred_reads();
+        cache.iter_lru_first().map(|(k, _)| k.clone()).collect()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the activity counted since the cache was created or [`reset_stats`](Self::reset_stats) was last called.
+    /// The snapshot is taken under the lock, so it is consistent with the cache's contents
+    pub fn stats(&self) -> CacheStats {
+        let cache = self.lock();
+        let own = cache.stats();
+        CacheStats {
+            entries: own.entries,
+            load_time: own.load_time,
+            eviction_age: own.eviction_age,
+            eviction_idle: own.eviction_idle,
+            ..self.stats.snapshot()
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the activity counted within `window` of now by a cache built with
+    /// [`LruCacheBuilder::windowed_stats`](crate::LruCacheBuilder::windowed_stats), as [`LruCache::stats_window`] does
+    pub fn stats_window(&self, window: Duration) -> CacheStats {
+        self.lock().stats_window(window)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Sets every statistic back to zero
+    pub fn reset_stats(&self) {
+        self.lock().reset_stats();
+        self.stats.reset()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns up to `n` of the most used keys, most used first, as [`LruCache::hottest`] does
+    pub fn hottest(&self, n: usize) -> Vec<(K, u64)>
+    where
+        K: Clone,
+    {
+        self.lock().hottest(n)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Forgets the uses counted for [`hottest`](Self::hottest)
+    pub fn reset_frequency_stats(&self) {
+        self.lock().reset_frequency_stats()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Subscribes to the items evicted from now on.  There is one subscriber at a time: subscribing again ends the
+    /// previous subscription, whose receiver still yields the events already queued for it
+    pub fn eviction_events(&self) -> EvictionEvents<K, V> {
+        self.events.subscribe()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Ends the current subscription to eviction events and waits for the subscriber to take what was queued
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn finish_events(&self) {
+        self.events.finish().await
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Runs `f` with the cache locked, so that several operations happen without other threads seeing the cache in
+    /// between, or so that values can be inspected without cloning them.  Listeners hear of the events once `f` has
+    /// returned
+    pub fn with_lock<R>(&self, f: impl FnOnce(&mut LruCache<K, V, S>) -> R) -> R {
+        f(&mut self.lock())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the wrapped cache.  If a thread panicked while holding the lock, the returned cache is empty, or under
+    /// [`PoisonPolicy::Propagate`] this panics
+    pub fn into_inner(self) -> LruCache<K, V, S> {
+        let policy = self.poison_policy;
+
+        let mut cache = self.inner.into_inner().unwrap_or_else(|poisoned| {
+            if policy == PoisonPolicy::Propagate {
+                panic!("{POISONED}");
+            }
+
+            let mut cache = poisoned.into_inner();
+            cache.discard_all();
+            cache
+        });
+        cache.take_events();
+        cache.resume_events();
+        cache
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Shares an existing cache, such as one configured through [`LruCacheBuilder`](crate::LruCacheBuilder)
+impl<K, V, S> From<LruCache<K, V, S>> for ConcurrentLruCache<K, V, S> {
+    fn from(mut cache: LruCache<K, V, S>) -> Self {
+        let listeners = cache.defer_events();
+
+        ConcurrentLruCache {
+            inner: Mutex::new(cache),
+            loading: Mutex::new(HashMap::new()),
+            stats: Counters::new(),
+            poison_policy: PoisonPolicy::default(),
+            poison_recoveries: AtomicU64::new(0),
+            events: EventSender::new(NonZeroUsize::new(DEFAULT_QUEUE_LENGTH).unwrap(), OverflowPolicy::default()),
+            listeners,
+            #[cfg(not(loom))]
+            daemons: Daemons::default(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,91 @@
+//! Bookkeeping for [`ConcurrentLruCache::get_or_insert_with`](super::ConcurrentLruCache::get_or_insert_with)
+//!
+//! While a value is being loaded, its key maps to a [`Latch`] that other callers for the same key wait on.  The thread
+//! running the loader holds a [`Flight`], which removes the key and opens the latch when it is dropped, so that
+//! waiters are released even if the loader panics
+use crate::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::{collections::HashMap, hash::Hash, sync::PoisonError};
+
+/// Latches for the keys whose values are being loaded
+pub(super) type InFlight<K, V> = Mutex<HashMap<K, Arc<Latch<V>>>>;
+
+/// Locks the in-flight keys.  The lock is never held while running a loader, so a poisoned map is still consistent
+pub(super) fn in_flight<K, V>(loading: &InFlight<K, V>) -> MutexGuard<'_, HashMap<K, Arc<Latch<V>>>> {
+    loading.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Opened once by the thread loading a value, handing the value to every thread waiting for it
+pub(super) struct Latch<V> {
+    /// `None` while loading, then the loaded value, or `Some(None)` if the loader panicked
+    outcome: Mutex<Option<Option<V>>>,
+    opened: Condvar,
+}
+
+impl<V: Clone> Latch<V> {
+    pub(super) fn new() -> Self {
+        Latch {
+            outcome: Mutex::new(None),
+            opened: Condvar::new(),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Blocks until the latch is opened.  Returns the loaded value, or `None` if the loader panicked
+    pub(super) fn wait(&self) -> Option<V> {
+        let mut outcome = self.outcome.lock().unwrap_or_else(PoisonError::into_inner);
+
+        loop {
+            match &*outcome {
+                Some(value) => return value.clone(),
+                None => outcome = self.opened.wait(outcome).unwrap_or_else(PoisonError::into_inner),
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn open(&self, value: Option<V>) {
+        *self.outcome.lock().unwrap_or_else(PoisonError::into_inner) = Some(value);
+        self.opened.notify_all();
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Held by the thread running a loader.  Dropping it stops other threads waiting for the key
+pub(super) struct Flight<'a, K, V>
+where
+    K: Eq + Hash,
+    V: Clone,
+{
+    loading: &'a InFlight<K, V>,
+    key: K,
+    latch: Arc<Latch<V>>,
+    /// Handed to the waiters.  Left as `None` if the loader panics, which tells the waiters to try again
+    pub(super) value: Option<V>,
+}
+
+impl<'a, K, V> Flight<'a, K, V>
+where
+    K: Eq + Hash,
+    V: Clone,
+{
+    pub(super) fn new(loading: &'a InFlight<K, V>, key: K, latch: Arc<Latch<V>>) -> Self {
+        Flight {
+            loading,
+            key,
+            latch,
+            value: None,
+        }
+    }
+}
+
+impl<K, V> Drop for Flight<'_, K, V>
+where
+    K: Eq + Hash,
+    V: Clone,
+{
+    fn drop(&mut self) {
+        in_flight(self.loading).remove(&self.key);
+        self.latch.open(self.value.take());
+    }
+}
@@ -0,0 +1,153 @@
+//! Activity counters for the thread-safe caches
+//!
+//! Counters are updated while the cache's lock is held, as part of the operation they count, so that a snapshot taken
+//! under the same lock always agrees with the cache's contents
+use crate::{
+    CacheStats, LruCache,
+    sync::{AtomicU64, Ordering},
+};
+use core::hash::{BuildHasher, Hash};
+use std::borrow::Borrow;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Counters behind a [`CacheStats`] snapshot.  Each method performs an operation on the locked cache and counts it
+pub(crate) struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    insertions: AtomicU64,
+    updates: AtomicU64,
+    evictions: AtomicU64,
+    removals: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn new() -> Self {
+        Counters {
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            insertions: AtomicU64::new(0),
+            updates: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            removals: AtomicU64::new(0),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn count(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Counts a lookup made by other means, such as the first step of loading a missing value
+    pub(crate) fn lookup<T>(&self, found: Option<T>) -> Option<T> {
+        Counters::count(if found.is_some() { &self.hits } else { &self.misses });
+        found
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Counts an item removed or popped by the caller, if there was one
+    pub(crate) fn removal<T>(&self, removed: Option<T>) -> Option<T> {
+        if removed.is_some() {
+            Counters::count(&self.removals);
+        }
+
+        removed
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn get<K, V, S, Q>(&self, cache: &mut LruCache<K, V, S>, key: &Q) -> Option<V>
+    where
+        K: Eq + Hash + Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+        S: BuildHasher,
+    {
+        self.lookup(cache.get_cloned(key))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the old value if the key was already present, and the item evicted to make room if it was not
+    pub(crate) fn put<K, V, S>(
+        &self,
+        cache: &mut LruCache<K, V, S>,
+        key: K,
+        new_value: V,
+    ) -> (Option<V>, Option<(K, V)>)
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        if cache.contains_key(&key) {
+            Counters::count(&self.updates);
+            return (cache.put(key, new_value), None);
+        }
+
+        (None, self.insert(cache, key, new_value))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn push<K, V, S>(&self, cache: &mut LruCache<K, V, S>, key: K, new_value: V) -> Option<(K, V)>
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        if cache.contains_key(&key) {
+            Counters::count(&self.updates);
+            return cache.push(key, new_value);
+        }
+
+        self.insert(cache, key, new_value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Adds an item whose key is not present, returning the item evicted to make room
+    fn insert<K, V, S>(&self, cache: &mut LruCache<K, V, S>, key: K, new_value: V) -> Option<(K, V)>
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        Counters::count(&self.insertions);
+        let evicted = cache.push(key, new_value);
+
+        if evicted.is_some() {
+            Counters::count(&self.evictions);
+        }
+
+        evicted
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Evicts items until no more than `quota` remain, as [`LruCache::evict_to`] does.  Returns the number evicted
+    pub(crate) fn evict_beyond<K, V, S>(&self, cache: &mut LruCache<K, V, S>, quota: usize) -> usize
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        let evicted = cache.evict_to(quota);
+        self.evictions.fetch_add(evicted as u64, Ordering::Relaxed);
+        evicted
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// The number of entries and the load times are kept by the cache itself, so they are left at zero
+    pub(crate) fn snapshot(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            insertions: self.insertions.load(Ordering::Relaxed),
+            updates: self.updates.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            removals: self.removals.load(Ordering::Relaxed),
+            ..CacheStats::default()
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn reset(&self) {
+        let counters = [&self.hits, &self.misses, &self.insertions, &self.updates, &self.evictions, &self.removals];
+
+        for counter in counters {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
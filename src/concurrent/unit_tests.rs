@@ -0,0 +1,882 @@
+use super::*;
+use crate::{LoadTimeStats, LruCacheBuilder, test_utils::*};
+use std::{
+    format,
+    panic::{self, AssertUnwindSafe},
+    string::String,
+    sync::{
+        Arc, Barrier,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+    vec::Vec,
+};
+
+const THREADS: usize = 8;
+const ITEMS_PER_THREAD: usize = 500;
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_be_send_and_sync() -> Result<(), String> {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<ConcurrentLruCache<String, String>>();
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn threads_should_share_one_cache() -> Result<(), String> {
+    let capacity = NonZeroUsize::new(THREADS * ITEMS_PER_THREAD).unwrap();
+    let cache = Arc::new(ConcurrentLruCache::new(capacity));
+    let barrier = Arc::new(Barrier::new(THREADS));
+
+    let handles: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let cache = Arc::clone(&cache);
+            let barrier = Arc::clone(&barrier);
+
+            thread::spawn(move || {
+                barrier.wait();
+
+                // Each thread writes its own keys and reads back those of the thread before it
+                for i in 0..ITEMS_PER_THREAD {
+                    let idx = t * ITEMS_PER_THREAD + i;
+                    cache.put(gen_item_key(idx), gen_item_value(idx as u32));
+                    let _ = cache.get(&gen_item_key((idx + ITEMS_PER_THREAD) % capacity.get()));
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().map_err(|_| "A thread panicked")?;
+    }
+
+    let missing = (0..capacity.get()).find(|&idx| cache.peek(&gen_item_key(idx)) != Some(gen_item_value(idx as u32)));
+    cache.with_lock(|c| c.debug_validate()).map_err(|e| e.to_string())?;
+
+    match (cache.len(), missing) {
+        (len, None) if len == capacity.get() => Ok(()),
+        (len, missing) => Err(format!("Expected every item. Got {len} items with {missing:?} missing")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_pop_from_either_end() -> Result<(), String> {
+    let cache = ConcurrentLruCache::new(NonZeroUsize::new(3).unwrap());
+    cache.put("a", 1);
+    cache.put("b", 2);
+    cache.put("c", 3);
+    let _ = cache.get(&"a");
+
+    match (cache.pop_lru(), cache.pop_mru(), cache.len()) {
+        (Some(2), Some(1), 1) => Ok(()),
+        other => Err(format!("Expected to pop 2 then 1, leaving 1 item. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_empty_cache_after_panic_while_locked() -> Result<(), String> {
+    let cache = ConcurrentLruCache::new(NonZeroUsize::new(4).unwrap());
+    cache.put(1, 1);
+
+    let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+        cache.with_lock(|c| {
+            c.put(2, 2);
+            panic!("interrupted while holding the lock");
+        })
+    }));
+
+    if panicked.is_ok() {
+        return Err(String::from("Expected the closure to panic"));
+    }
+
+    // The first call after the panic finds the lock poisoned and starts again from an empty cache
+    match (cache.len(), cache.put(3, 3), cache.get(&3)) {
+        (0, None, Some(3)) => Ok(()),
+        other => Err(format!("Expected an empty, usable cache after the panic. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn try_methods_should_not_wait_for_a_held_lock() -> Result<(), String> {
+    let cache = Arc::new(ConcurrentLruCache::new(NonZeroUsize::new(4).unwrap()));
+    cache.put(1, String::from("one"));
+
+    let locked = Arc::new(Barrier::new(2));
+    let release = Arc::new(Barrier::new(2));
+
+    let holder = {
+        let (cache, locked, release) = (Arc::clone(&cache), Arc::clone(&locked), Arc::clone(&release));
+
+        thread::spawn(move || {
+            cache.with_lock(|_| {
+                locked.wait();
+                release.wait();
+            })
+        })
+    };
+
+    // The holder keeps the lock until released below, so any of these calls that waited would never return
+    locked.wait();
+    let contended = (cache.try_get(&1), cache.try_put(2, String::from("two")), cache.try_pop_lru());
+    release.wait();
+    holder.join().map_err(|_| "The lock holder panicked")?;
+
+    match contended {
+        (Err(WouldBlock(())), Err(WouldBlock((2, two))), Err(WouldBlock(()))) if two == "two" => (),
+        other => return Err(format!("Expected every call to report the held lock. Got {other:?}")),
+    }
+
+    match (cache.try_get(&1), cache.try_put(2, String::from("two")), cache.len()) {
+        (Ok(Some(one)), Ok(None), 2) if one == "one" => Ok(()),
+        other => Err(format!("Expected the calls to succeed once the lock was released. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn get_or_insert_with_should_run_one_loader_for_many_callers() -> Result<(), String> {
+    const CALLERS: usize = 64;
+
+    let cache = Arc::new(ConcurrentLruCache::new(NonZeroUsize::new(4).unwrap()));
+    let loads = Arc::new(AtomicUsize::new(0));
+    let barrier = Arc::new(Barrier::new(CALLERS));
+
+    let handles: Vec<_> = (0..CALLERS)
+        .map(|_| {
+            let (cache, loads, barrier) = (Arc::clone(&cache), Arc::clone(&loads), Arc::clone(&barrier));
+
+            thread::spawn(move || {
+                barrier.wait();
+
+                // The loader is slow enough for every other caller to arrive while it runs
+                cache.get_or_insert_with(String::from("popular"), || {
+                    loads.fetch_add(1, Ordering::Relaxed);
+                    thread::sleep(Duration::from_millis(50));
+                    gen_item_value(7)
+                })
+            })
+        })
+        .collect();
+
+    let values = handles
+        .into_iter()
+        .map(|handle| handle.join().map_err(|_| String::from("A caller panicked")))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match (loads.load(Ordering::Relaxed), values.iter().all(|v| *v == gen_item_value(7))) {
+        (1, true) => Ok(()),
+        (loads, all_loaded) => Err(format!(
+            "Expected one load shared by every caller. Got {loads} loads, and every caller received it: {all_loaded}"
+        )),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn get_or_insert_with_should_let_a_waiter_load_after_the_loader_panics() -> Result<(), String> {
+    let cache = Arc::new(ConcurrentLruCache::new(NonZeroUsize::new(4).unwrap()));
+    let loading = Arc::new(Barrier::new(2));
+
+    let failing = {
+        let (cache, loading) = (Arc::clone(&cache), Arc::clone(&loading));
+
+        thread::spawn(move || {
+            cache.get_or_insert_with(1, || {
+                loading.wait();
+                thread::sleep(Duration::from_millis(50));
+                panic!("the backing store is unavailable")
+            })
+        })
+    };
+
+    // Ask for the key while the first loader is still running, so that this call waits for it and then takes over
+    loading.wait();
+    let value = cache.get_or_insert_with(1, || 42);
+
+    if failing.join().is_ok() {
+        return Err(String::from("Expected the first loader to panic"));
+    }
+
+    match (value, cache.get_or_insert_with(1, || 0), cache.len()) {
+        (42, 42, 1) => Ok(()),
+        other => Err(format!("Expected the waiting caller to load the value after the panic. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn stats_should_account_for_every_operation_across_threads() -> Result<(), String> {
+    const CAPACITY: usize = 100;
+    const KEYS: u64 = 400;
+    const OPERATIONS: usize = 5_000;
+
+    let cache = Arc::new(ConcurrentLruCache::new(NonZeroUsize::new(CAPACITY).unwrap()));
+    let barrier = Arc::new(Barrier::new(THREADS));
+
+    // Each thread replays its own fixed sequence of 75% gets and 25% puts, and counts what it did
+    let handles: Vec<_> = (0..THREADS as u64)
+        .map(|t| {
+            let (cache, barrier) = (Arc::clone(&cache), Arc::clone(&barrier));
+
+            thread::spawn(move || {
+                let mut state = 0x9E37_79B9_7F4A_7C15 ^ (t + 1);
+                let mut next = move || {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state
+                };
+                let (mut gets, mut puts, mut replaced) = (0, 0, 0);
+                barrier.wait();
+
+                for _ in 0..OPERATIONS {
+                    let (choice, key) = (next() % 4, next() % KEYS);
+
+                    if choice == 0 {
+                        puts += 1;
+                        replaced += cache.put(key, key).is_some() as u64;
+                    } else {
+                        gets += 1;
+                        let _ = cache.get(&key);
+                    }
+                }
+
+                (gets, puts, replaced)
+            })
+        })
+        .collect();
+
+    let (mut gets, mut puts, mut replaced) = (0, 0, 0);
+
+    for handle in handles {
+        let (g, p, r) = handle.join().map_err(|_| "A worker thread panicked")?;
+        (gets, puts, replaced) = (gets + g, puts + p, replaced + r);
+    }
+
+    let stats = cache.stats();
+    let expected = CacheStats {
+        hits: stats.hits,
+        misses: gets - stats.hits,
+        insertions: puts - replaced,
+        updates: replaced,
+        evictions: puts - replaced - CAPACITY as u64,
+        entries: CAPACITY as u64,
+        ..CacheStats::default()
+    };
+
+    if stats != expected || cache.len() != CAPACITY {
+        return Err(format!("Expected {expected:?} with a full cache. Got {stats:?} with {} items", cache.len()));
+    }
+
+    cache.reset_stats();
+
+    match cache.stats() {
+        reset if reset == CacheStats { entries: CAPACITY as u64, ..CacheStats::default() } => Ok(()),
+        other => Err(format!("Expected every counter to be zero after a reset. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn stats_should_count_each_kind_of_write() -> Result<(), String> {
+    let cache = ConcurrentLruCache::new(NonZeroUsize::new(2).unwrap());
+    cache.put(1, 1);
+    cache.push(2, 2);
+    cache.put(1, 10);
+    cache.push(2, 20);
+    cache.push(3, 3);
+    cache.put(4, 4);
+    let _ = (cache.get(&4), cache.get(&1), cache.try_get(&3));
+    let _ = cache.get_or_insert_with(5, || 5);
+
+    let stats = cache.stats();
+    let expected = CacheStats {
+        hits: 2,
+        misses: 2,
+        insertions: 5,
+        updates: 2,
+        evictions: 3,
+        removals: 0,
+        entries: 2,
+        load_time: stats.load_time,
+        ..CacheStats::default()
+    };
+
+    match (stats, stats.load_time.count) {
+        (stats, 1) if stats == expected => Ok(()),
+        other => Err(format!("Expected {expected:?} after one load. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Panics on another thread while holding the cache's lock
+fn poison(cache: &Arc<ConcurrentLruCache<u32, u32>>) -> Result<(), String> {
+    let cache = Arc::clone(cache);
+
+    match thread::spawn(move || cache.with_lock(|_| panic!("interrupted while holding the lock"))).join() {
+        Ok(()) => Err(String::from("Expected the locking thread to panic")),
+        Err(_) => Ok(()),
+    }
+}
+
+#[test]
+fn should_stay_usable_from_other_threads_after_a_panic() -> Result<(), String> {
+    let cache = Arc::new(ConcurrentLruCache::new(NonZeroUsize::new(4).unwrap()));
+    cache.put(1, 1);
+    poison(&cache)?;
+
+    let user = {
+        let cache = Arc::clone(&cache);
+        thread::spawn(move || (cache.get(&1), cache.put(2, 2), cache.get(&2)))
+    };
+
+    match (user.join(), cache.poison_recoveries(), cache.len()) {
+        (Ok((None, None, Some(2))), 1, 1) => Ok(()),
+        (result, recoveries, len) => Err(format!(
+            "Expected one recovery to an empty cache that another thread could use. Got {result:?} after {recoveries} \
+             recoveries, leaving {len} items"
+        )),
+    }
+}
+
+#[test]
+fn should_propagate_a_panic_when_asked_to() -> Result<(), String> {
+    let cache = ConcurrentLruCache::new(NonZeroUsize::new(4).unwrap()).with_poison_policy(PoisonPolicy::Propagate);
+    let cache = Arc::new(cache);
+    cache.put(1, 1);
+    poison(&cache)?;
+
+    let user = {
+        let cache = Arc::clone(&cache);
+        thread::spawn(move || cache.get(&1))
+    };
+
+    match (user.join(), panic::catch_unwind(AssertUnwindSafe(|| cache.try_get(&1))), cache.poison_recoveries()) {
+        (Err(_), Err(_), 0) => Ok(()),
+        other => Err(format!("Expected every call on the poisoned cache to panic. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn snapshot_should_copy_items_from_least_to_most_recently_used() -> Result<(), String> {
+    let cache = ConcurrentLruCache::new(NonZeroUsize::new(3).unwrap());
+    cache.put("a", 1);
+    cache.put("b", 2);
+    cache.put("c", 3);
+    let _ = cache.get(&"a");
+
+    match (cache.snapshot(), cache.snapshot_keys()) {
+        (items, keys) if items == [("b", 2), ("c", 3), ("a", 1)] && keys == ["b", "c", "a"] => Ok(()),
+        other => Err(format!("Expected b, c then a. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn snapshots_taken_during_writes_should_be_consistent() -> Result<(), String> {
+    const CAPACITY: usize = 64;
+    const SNAPSHOTS: usize = 200;
+
+    let cache = Arc::new(ConcurrentLruCache::new(NonZeroUsize::new(CAPACITY).unwrap()));
+    let barrier = Arc::new(Barrier::new(THREADS + 1));
+
+    // Every value is derived from its key, so a torn copy would show up as a mismatched pair
+    let writers: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let (cache, barrier) = (Arc::clone(&cache), Arc::clone(&barrier));
+
+            thread::spawn(move || {
+                barrier.wait();
+
+                for i in 0..ITEMS_PER_THREAD {
+                    let key = (t * ITEMS_PER_THREAD + i * 7) % (4 * CAPACITY);
+                    cache.put(key, gen_item_value(key as u32));
+
+                    if i % 5 == 0 {
+                        let _ = cache.remove(&((key + 1) % (4 * CAPACITY)));
+                    }
+                }
+            })
+        })
+        .collect();
+
+    barrier.wait();
+    let mut problems = Vec::new();
+
+    for _ in 0..SNAPSHOTS {
+        let items = cache.snapshot();
+        let mut keys: Vec<_> = items.iter().map(|(k, _)| *k).collect();
+        keys.sort_unstable();
+        keys.dedup();
+
+        if keys.len() != items.len() || items.len() > CAPACITY {
+            problems.push(format!("{} items with {} distinct keys", items.len(), keys.len()));
+        }
+
+        if let Some((k, v)) = items.iter().find(|(k, v)| *v != gen_item_value(*k as u32)) {
+            problems.push(format!("key {k} paired with {v}"));
+        }
+
+        if cache.snapshot_keys().len() > CAPACITY {
+            problems.push(String::from("more keys than the capacity"));
+        }
+    }
+
+    for writer in writers {
+        writer.join().map_err(|_| "A writer panicked")?;
+    }
+
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(format!("Expected every snapshot to be consistent. Got {problems:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Keys and reasons of the events received so far
+fn received<K: Copy, V>(events: &EvictionEvents<K, V>) -> Vec<(K, EvictionReason)> {
+    core::iter::from_fn(|| events.try_recv()).map(|e| (e.key, e.reason)).collect()
+}
+
+#[test]
+fn eviction_events_should_report_every_eviction_in_order() -> Result<(), String> {
+    let cache = ConcurrentLruCache::new(NonZeroUsize::new(2).unwrap());
+    cache.put(0, 0);
+    let events = cache.eviction_events();
+
+    cache.put(1, 10);
+    cache.put(2, 20);
+    cache.put(1, 11);
+    cache.put(3, 30);
+    cache.get_or_insert_with(4, || 40);
+    let pushed = cache.push(5, 50);
+    let _ = cache.try_put(6, 60);
+
+    // Updating key 1 evicts nothing, and the item displaced by push is handed back instead of being reported
+    let expected = [0, 2, 1, 4].map(|key| (key, EvictionReason::Capacity));
+    let got = received(&events);
+    drop(cache);
+
+    match (pushed, events.recv()) {
+        (Some((3, 30)), None) if got == expected => Ok(()),
+        other => Err(format!("Expected events {expected:?}, then the end of the stream. Got {got:?}, {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn eviction_events_should_drop_the_oldest_when_the_queue_is_full() -> Result<(), String> {
+    let cache = ConcurrentLruCache::new(NonZeroUsize::new(1).unwrap())
+        .with_event_queue(NonZeroUsize::new(3).unwrap(), OverflowPolicy::DropOldest);
+    let events = cache.eviction_events();
+
+    for key in 0..9 {
+        cache.put(key, key);
+    }
+
+    let got: Vec<_> = received(&events).into_iter().map(|(key, _)| key).collect();
+
+    match (got, events.dropped()) {
+        (got, 5) if got == [5, 6, 7] => Ok(()),
+        other => Err(format!("Expected the last three of eight events, five dropped. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn eviction_events_should_block_the_evicting_writer_when_the_queue_is_full() -> Result<(), String> {
+    let cache = Arc::new(
+        ConcurrentLruCache::new(NonZeroUsize::new(2).unwrap())
+            .with_event_queue(NonZeroUsize::new(1).unwrap(), OverflowPolicy::Block),
+    );
+    cache.put(0, 0);
+    cache.put(1, 1);
+    let events = cache.eviction_events();
+    let written = Arc::new(AtomicUsize::new(0));
+
+    let writer = {
+        let (cache, written) = (Arc::clone(&cache), Arc::clone(&written));
+
+        thread::spawn(move || {
+            for key in 10..13 {
+                cache.put(key, key);
+                written.fetch_add(1, Ordering::Relaxed);
+            }
+        })
+    };
+
+    while written.load(Ordering::Relaxed) == 0 {
+        thread::yield_now();
+    }
+
+    // The second eviction finds the queue full.  The writer waits without holding the cache's lock
+    thread::sleep(Duration::from_millis(50));
+    let while_blocked = (written.load(Ordering::Relaxed), cache.len(), cache.contains_key(&11));
+    let got: Vec<_> = (0..3).filter_map(|_| events.recv()).map(|e| e.key).collect();
+    writer.join().map_err(|_| "The writer panicked")?;
+
+    match (while_blocked, got, events.dropped()) {
+        ((1, 2, true), got, 0) if got == [0, 1, 10] => Ok(()),
+        other => Err(format!("Expected the writer to wait for each event to be taken. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn eviction_events_should_end_when_the_subscriber_is_replaced_or_goes_away() -> Result<(), String> {
+    let cache = Arc::new(
+        ConcurrentLruCache::new(NonZeroUsize::new(1).unwrap())
+            .with_event_queue(NonZeroUsize::new(1).unwrap(), OverflowPolicy::Block),
+    );
+    let first = cache.eviction_events();
+    cache.put(0, 0);
+    cache.put(1, 1);
+
+    let second = cache.eviction_events();
+    cache.put(2, 2);
+    let from_first = (first.recv().map(|e| e.key), first.recv().map(|e| e.key));
+
+    // With nobody left to take them, a full queue must not block writers
+    drop(second);
+    cache.put(3, 3);
+    cache.put(4, 4);
+
+    match (from_first, cache.peek(&4)) {
+        ((Some(0), None), Some(4)) => Ok(()),
+        other => Err(format!("Expected the first subscription to end after its event. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn items_should_expire_as_the_test_thread_advances_a_shared_clock() -> Result<(), String> {
+    const WORKERS: usize = 4;
+    let clock = MockClock::new();
+    let cache = Arc::new(ConcurrentLruCache::from(
+        LruCacheBuilder::new(NonZeroUsize::new(16).unwrap())
+            .clock(clock.clone())
+            .ttl(Duration::from_secs(10))
+            .build(),
+    ));
+    let barrier = Arc::new(Barrier::new(WORKERS + 1));
+
+    let workers: Vec<_> = (0..WORKERS)
+        .map(|worker| {
+            let (cache, barrier) = (Arc::clone(&cache), Arc::clone(&barrier));
+
+            thread::spawn(move || {
+                for round in 0..3 {
+                    barrier.wait();
+                    cache.put((round, worker), worker);
+                    barrier.wait();
+                }
+            })
+        })
+        .collect();
+
+    // Rounds are stored at 0s, 5s and 10s, and the clock ends at 15s, so only the last round is still live
+    for _ in 0..3 {
+        barrier.wait();
+        barrier.wait();
+        clock.advance(Duration::from_secs(5));
+    }
+
+    for worker in workers {
+        worker.join().map_err(|_| "A worker panicked")?;
+    }
+
+    let mut live: Vec<_> = cache.snapshot_keys();
+    live.sort();
+
+    match live[..] {
+        [(2, 0), (2, 1), (2, 2), (2, 3)] => Ok(()),
+        _ => Err(format!("Expected only the items stored in the last round to remain. Got {live:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn get_or_insert_with_should_record_the_load_cost_for_early_expiration() -> Result<(), String> {
+    let clock = MockClock::new();
+    let cache = ConcurrentLruCache::from(
+        LruCacheBuilder::new(NonZeroUsize::new(4).unwrap())
+            .clock(clock.clone())
+            .ttl(Duration::from_secs(10))
+            .early_expiration(Duration::from_secs(10), 1.0)
+            .seed(7)
+            .build(),
+    );
+
+    // The loader takes 5s, so the item is stored at 5s and expires at 15s
+    cache.get_or_insert_with(1, || {
+        clock.advance(Duration::from_secs(5));
+        10
+    });
+
+    let mut refreshed_at = None;
+
+    for second in 5..15 {
+        if cache.get(&1).is_none() {
+            refreshed_at = Some(second);
+            break;
+        }
+
+        clock.advance(Duration::from_secs(1));
+    }
+
+    match (refreshed_at, cache.get(&1)) {
+        (Some(5..15), Some(10)) => Ok(()),
+        other => Err(format!("Expected one early miss before the deadline at 15s. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn get_or_insert_with_should_count_the_time_each_load_takes() -> Result<(), String> {
+    let clock = MockClock::new();
+    let cache = ConcurrentLruCache::from(
+        LruCacheBuilder::new(NonZeroUsize::new(8).unwrap())
+            .clock(clock.clone())
+            .build(),
+    );
+
+    for (key, millis) in [(1, 1), (2, 2), (3, 3), (4, 4), (5, 90)] {
+        cache.get_or_insert_with(key, || {
+            clock.advance(Duration::from_millis(millis));
+            key
+        });
+    }
+
+    // Found in the cache, so not loaded
+    cache.get_or_insert_with(5, || unreachable!("key 5 is cached"));
+    let loads = cache.stats().load_time;
+    let ms = Duration::from_millis;
+
+    // The median of 3ms is reported as the top of its power-of-two bucket, while everything else is exact
+    let exact = (loads.count, loads.total, loads.mean(), loads.max, loads.p90(), loads.p99());
+
+    match (exact, loads.p50()) {
+        (exact, p50) if exact == (5, ms(100), ms(20), ms(90), ms(90), ms(90)) && (ms(3)..ms(6)).contains(&p50) => {
+            Ok(())
+        }
+        other => Err(format!("Expected 5 loads taking 100ms, at most 90ms and a median near 3ms. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn reset_stats_should_forget_load_times() -> Result<(), String> {
+    let cache = ConcurrentLruCache::new(NonZeroUsize::new(4).unwrap());
+    cache.get_or_insert_with(1, || 1);
+    let before_reset = cache.stats().load_time.count;
+    cache.reset_stats();
+
+    match (before_reset, cache.stats().load_time) {
+        (1, load_time) if load_time == LoadTimeStats::default() => Ok(()),
+        other => Err(format!("Expected one load before the reset and none after. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn get_or_insert_with_should_apply_the_ttl_fn_to_loaded_values() -> Result<(), String> {
+    let clock = MockClock::new();
+    let cache = ConcurrentLruCache::from(
+        LruCacheBuilder::new(NonZeroUsize::new(4).unwrap())
+            .clock(clock.clone())
+            .ttl_fn(|_, value: &Result<u32, String>| match value {
+                Ok(_) => Some(Duration::from_secs(300)),
+                Err(_) => Some(Duration::from_secs(5)),
+            })
+            .build(),
+    );
+
+    let _ = cache.get_or_insert_with(1, || Ok(10));
+    let _ = cache.get_or_insert_with(2, || Err(String::from("Not found")));
+    clock.advance(Duration::from_secs(5));
+
+    match (cache.get(&1), cache.get(&2)) {
+        (Some(Ok(10)), None) => Ok(()),
+        other => Err(format!("Expected only the failed load to have expired. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Polls `done` until it returns `true`, giving up after a few seconds
+fn eventually(done: impl Fn() -> bool) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while !done() {
+        if Instant::now() > deadline {
+            return false;
+        }
+
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    true
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn expiry_daemon_should_remove_expired_items_without_lookups() -> Result<(), String> {
+    let clock = MockClock::new();
+    let cache = Arc::new(ConcurrentLruCache::from(
+        LruCacheBuilder::new(NonZeroUsize::new(8).unwrap()).clock(clock.clone()).build(),
+    ));
+
+    for k in 0..4 {
+        cache.with_lock(|c| c.put_with_ttl(k, k, Duration::from_secs(10)));
+    }
+
+    cache.put(4, 4);
+    let daemon = cache.start_expiry_daemon(Duration::from_millis(1));
+    clock.advance(Duration::from_secs(10));
+
+    let purged = eventually(|| cache.len() == 1);
+    drop(daemon);
+
+    match (purged, cache.peek(&4)) {
+        (true, Some(4)) => Ok(()),
+        other => Err(format!("Expected the daemon to remove the four expired items. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn dropping_the_expiry_handle_should_stop_the_daemon_without_waiting_out_the_interval() -> Result<(), String> {
+    let cache = Arc::new(ConcurrentLruCache::<u32, u32>::new(NonZeroUsize::new(8).unwrap()));
+    let daemon = cache.start_expiry_daemon(Duration::from_secs(3_600));
+
+    let started = Instant::now();
+    drop(daemon);
+
+    match started.elapsed() {
+        elapsed if elapsed < Duration::from_secs(5) => Ok(()),
+        elapsed => Err(format!("Expected the daemon to stop at once. Took {elapsed:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn dropping_the_cache_should_stop_the_daemon() -> Result<(), String> {
+    let cache = Arc::new(ConcurrentLruCache::<u32, u32>::new(NonZeroUsize::new(8).unwrap()));
+    let daemon = cache.start_expiry_daemon(Duration::from_secs(3_600));
+    let running = !daemon.is_finished();
+    drop(cache);
+
+    match (running, eventually(|| daemon.is_finished())) {
+        (true, true) => Ok(()),
+        other => Err(format!("Expected the daemon to stop once the cache was dropped. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn get_guarded_should_keep_the_least_recently_used_entry_until_the_guard_drops() -> Result<(), String> {
+    let cache = ConcurrentLruCache::new(NonZeroUsize::new(3).unwrap());
+    (1..=3).for_each(|key| _ = cache.put(key, key));
+
+    // The guarded read makes 1 the most recently used, and adding 4 and 5 makes it the least recently used again
+    let guard = cache.get_guarded(&1).ok_or("Expected to guard 1")?;
+    (4..=6).for_each(|key| _ = cache.put(key, key));
+    let while_guarded = (*guard, cache.contains_key(&1), cache.contains_key(&4), cache.len());
+
+    drop(guard);
+    cache.put(7, 7);
+
+    match (while_guarded, cache.contains_key(&1), cache.snapshot_keys()) {
+        ((1, true, false, 3), false, keys) if keys == [5, 6, 7] => Ok(()),
+        other => Err(format!("Expected 1 to outlive 4 while guarded, then be evicted. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn get_guarded_should_keep_an_entry_until_every_guard_drops() -> Result<(), String> {
+    let cache = ConcurrentLruCache::new(NonZeroUsize::new(2).unwrap());
+    cache.put(1, 1);
+    cache.put(2, 2);
+
+    let first = cache.get_guarded(&1).ok_or("Expected to guard 1")?;
+    let second = cache.get_guarded(&1).ok_or("Expected to guard 1 again")?;
+    cache.put(3, 3);
+    drop(first);
+    cache.put(4, 4);
+    let after_one_drop = cache.contains_key(&1);
+
+    drop(second);
+    cache.put(5, 5);
+
+    match (after_one_drop, cache.contains_key(&1), cache.snapshot_keys()) {
+        (true, false, keys) if keys == [4, 5] => Ok(()),
+        other => Err(format!("Expected 1 to stay until both guards dropped. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn get_guarded_should_return_none_for_a_missing_key() -> Result<(), String> {
+    let cache = ConcurrentLruCache::<u32, u32>::new(NonZeroUsize::new(2).unwrap());
+    cache.put(1, 1);
+
+    match (cache.get_guarded(&2).map(|guard| *guard), cache.stats().misses) {
+        (None, 1) => Ok(()),
+        other => Err(format!("Expected no guard and one miss. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn listeners_should_hear_events_once_the_lock_is_released() -> Result<(), String> {
+    use crate::{CacheEventListener, RemovalCause, listener::Event::*};
+    use std::sync::OnceLock;
+
+    /// Looks up every inserted key, which would deadlock if called with the lock held
+    struct Reentrant(Arc<OnceLock<Arc<ConcurrentLruCache<u32, u32>>>>);
+
+    impl CacheEventListener<u32, u32> for Reentrant {
+        fn on_insert(&self, key: &u32, _: &u32) {
+            self.0.get().unwrap().get(key);
+        }
+    }
+
+    let shared = Arc::new(OnceLock::new());
+    let listener = RecordingListener::default();
+    let cache = LruCacheBuilder::new(NonZeroUsize::new(2).unwrap())
+        .listener(listener.clone())
+        .listener(Reentrant(Arc::clone(&shared)))
+        .build();
+    let cache = Arc::new(ConcurrentLruCache::from(cache));
+    let _ = shared.set(Arc::clone(&cache));
+
+    cache.put(1, 1);
+    cache.put(1, 10);
+    cache.with_lock(|c| {
+        c.put(2, 2);
+        c.put(3, 3);
+    });
+    let expected = [
+        (Insert, 1, 1),
+        (Hit, 1, 1),
+        (Remove(RemovalCause::Replaced), 1, 1),
+        (Update, 1, 10),
+        (Insert, 2, 2),
+        (Hit, 2, 2),
+        (Remove(RemovalCause::Capacity), 1, 10),
+        (Insert, 3, 3),
+        (Hit, 3, 3),
+    ];
+
+    match listener.events() {
+        events if events == expected => Ok(()),
+        events => Err(format!("Expected {expected:?}. Got {events:?}")),
+    }
+}
@@ -0,0 +1,110 @@
+//! Bloom filter of the hashes of cached keys
+//!
+//! Lookups check the filter before probing the key index, so that most keys that were never cached are turned away
+//! without comparing keys.  A bloom filter can report a key that is absent, but never misses one that is present, so
+//! the filter only ever saves work.  Bits cannot be cleared when entries leave the cache, so the filter is rebuilt from
+//! the remaining entries once enough of them have left to let the false positive rate creep up
+use crate::sync::{AtomicU64, Ordering};
+use alloc::{boxed::Box, vec};
+use core::mem;
+
+/// Bits of filter per entry of capacity
+const BITS_PER_ENTRY: usize = 16;
+
+/// Bits set for each hash.  With 16 bits per entry, false positives stay below 1% while the filter holds up to one and
+/// a half times the capacity
+const PROBES: u64 = 4;
+
+// ---------------------------------------------------------------------------------------------------------------------
+pub(crate) struct Doorkeeper {
+    words: Box<[u64]>,
+    /// Number of bits in the filter, less one.  The number of bits is a power of two
+    mask: u64,
+    /// Entries that have left the cache since the filter was last rebuilt
+    stale: usize,
+    /// Number of stale entries that triggers a rebuild
+    rebuild_after: usize,
+    /// Lookups answered by the filter alone
+    rejections: AtomicU64,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl Doorkeeper {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let bits = capacity
+            .saturating_mul(BITS_PER_ENTRY)
+            .checked_next_power_of_two()
+            .unwrap_or(1 << (usize::BITS - 1))
+            .max(u64::BITS as usize);
+
+        Doorkeeper {
+            words: vec![0; bits / u64::BITS as usize].into_boxed_slice(),
+            mask: bits as u64 - 1,
+            stale: 0,
+            rebuild_after: capacity.div_ceil(2),
+            rejections: AtomicU64::new(0),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Bit positions for a hash, derived from its two halves by double hashing
+    fn bits(&self, hash: u64) -> impl Iterator<Item = u64> + use<> {
+        let (h1, h2) = (hash, hash.rotate_left(32) | 1);
+        let mask = self.mask;
+
+        (0..PROBES).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) & mask)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `false` if no key with this hash is cached, counting the lookup as a rejection
+    pub(crate) fn admits(&self, hash: u64) -> bool {
+        let admitted = self.bits(hash).all(|bit| self.words[(bit / 64) as usize] & (1 << (bit % 64)) != 0);
+
+        if !admitted {
+            self.rejections.fetch_add(1, Ordering::Relaxed);
+        }
+
+        admitted
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Records a key that has entered the cache
+    pub(crate) fn insert(&mut self, hash: u64) {
+        for bit in self.bits(hash) {
+            self.words[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Records a key that has left the cache.  Returns `true` if the filter should now be rebuilt
+    pub(crate) fn remove(&mut self) -> bool {
+        self.stale += 1;
+        self.stale >= self.rebuild_after
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Empties the filter and refills it with the hashes of the keys still cached
+    pub(crate) fn rebuild(&mut self, hashes: impl Iterator<Item = u64>) {
+        self.clear();
+        hashes.for_each(|hash| self.insert(hash));
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Forgets every key.  The rejection count is kept
+    pub(crate) fn clear(&mut self) {
+        self.words.fill(0);
+        self.stale = 0;
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Number of lookups the filter has answered without probing the key index
+    pub(crate) fn rejections(&self) -> u64 {
+        self.rejections.load(Ordering::Relaxed)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Bytes of heap memory held for the filter
+    pub(crate) fn heap_size(&self) -> usize {
+        mem::size_of_val(&*self.words)
+    }
+}
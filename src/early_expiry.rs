@@ -0,0 +1,39 @@
+//! Probabilistic early expiration, which spreads the refreshes of popular items out ahead of their deadlines
+//!
+//! Close to its deadline, a lookup of an item that records how long it took to load may report a miss, so that the
+//! caller loads a fresh value while other callers still find the old one.  The chance grows as the deadline nears and
+//! with the time the item took to load, following the XFetch algorithm of Vattani, Chierichetti and Lowenstein's
+//! "Optimal Probabilistic Cache Stampede Prevention"
+use crate::random::Random;
+
+// ---------------------------------------------------------------------------------------------------------------------
+pub(crate) struct EarlyExpiry {
+    /// How close to its deadline, in nanoseconds, an item must be before it may be refreshed early
+    window: u64,
+    /// Scales the load time.  Above 1 favours earlier refreshes, below 1 later ones
+    beta: f64,
+    random: Random,
+}
+
+impl EarlyExpiry {
+    pub(crate) fn new(window: u64, beta: f64, seed: u64) -> Self {
+        EarlyExpiry {
+            window,
+            beta,
+            random: Random::new(seed),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Decides whether an item that expires at `deadline`, and took `load_cost` nanoseconds to load, should be
+    /// refreshed now
+    pub(crate) fn refreshes(&mut self, now: u64, deadline: u64, load_cost: u64) -> bool {
+        let remaining = deadline.saturating_sub(now);
+
+        if load_cost == 0 || remaining > self.window {
+            return false;
+        }
+
+        load_cost as f64 * self.beta * -self.random.next_unit().ln() >= remaining as f64
+    }
+}
@@ -0,0 +1,182 @@
+//! Thread-safe LRU cache whose entries are locked one at a time
+//!
+//! [`EntryLruCache`] stores each value behind its own read-write lock, inside a [`ConcurrentLruCache`].  The cache's
+//! lock is held only long enough to find an entry and make it the most recently used; the [`Entry`] handed back is
+//! then locked on its own, so a thread mutating one large value in place holds up neither other threads' access to
+//! the cache nor their work on other entries.
+//!
+//! # Eviction while an entry is in use
+//!
+//! Eviction never waits for an entry's lock.  An [`Entry`] shares ownership of its value with the cache, so an entry
+//! evicted or removed while a thread holds it stays alive, and its guards stay valid, until the last [`Entry`] for it
+//! is dropped.  Changes made through such an entry are no longer visible through the cache.
+//!
+//! # Poisoning
+//!
+//! If a thread panics while holding an entry's write guard, the entry's lock is poisoned but the cache's is not.  The
+//! entry's value keeps whatever changes were made before the panic, and later guards are handed out as usual
+use crate::{
+    ConcurrentLruCache, DefaultHashBuilder,
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    num::NonZeroUsize,
+    sync::PoisonError,
+};
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A shared handle to one value of an [`EntryLruCache`], which can be locked for reading or writing independently of
+/// the cache and of other entries
+pub struct Entry<V> {
+    value: Arc<RwLock<V>>,
+}
+
+impl<V> Entry<V> {
+    /// Locks the value for reading, waiting while another thread holds its write guard
+    pub fn read(&self) -> RwLockReadGuard<'_, V> {
+        self.value.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Locks the value for writing, waiting while any other thread holds one of its guards
+    pub fn write(&self) -> RwLockWriteGuard<'_, V> {
+        self.value.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if both handles refer to the same value
+    pub fn ptr_eq(&self, other: &Entry<V>) -> bool {
+        Arc::ptr_eq(&self.value, &other.value)
+    }
+}
+
+impl<V> Clone for Entry<V> {
+    fn clone(&self) -> Self {
+        Entry {
+            value: Arc::clone(&self.value),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache that can be shared between threads, where each value is locked separately from the cache
+pub struct EntryLruCache<K, V, S = DefaultHashBuilder> {
+    cache: ConcurrentLruCache<K, Entry<V>, S>,
+}
+
+impl<K, V> EntryLruCache<K, V>
+where
+    K: Eq + Hash,
+{
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        EntryLruCache {
+            cache: ConcurrentLruCache::new(capacity),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> EntryLruCache<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Creates a cache that uses the given hash builder to hash keys
+    pub fn with_hasher(capacity: NonZeroUsize, hash_builder: S) -> Self {
+        EntryLruCache {
+            cache: ConcurrentLruCache::with_hasher(capacity, hash_builder),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns a handle to an item, making it the most recently used.  The cache is unlocked again before returning,
+    /// so locking the entry does not hold up other threads' use of the cache
+    pub fn entry<Q>(&self, key: &Q) -> Option<Entry<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.get(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Calls `f` with the item's value under its read lock, making it the most recently used
+    pub fn with_value<Q, R>(&self, key: &Q, f: impl FnOnce(&V) -> R) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.entry(key).map(|entry| f(&entry.read()))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Calls `f` with the item's value under its write lock, making it the most recently used
+    pub fn with_value_mut<Q, R>(&self, key: &Q, f: impl FnOnce(&mut V) -> R) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.entry(key).map(|entry| f(&mut entry.write()))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains the key, without changing its position
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.contains_key(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item as a new entry, returning the key's previous entry if it was already present.  Threads still
+    /// holding the previous entry keep its value
+    pub fn insert(&self, key: K, new_value: V) -> Option<Entry<V>> {
+        let entry = Entry {
+            value: Arc::new(RwLock::new(new_value)),
+        };
+
+        self.cache.put(key, entry)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an item, returning its entry if it was present
+    pub fn remove<Q>(&self, key: &Q) -> Option<Entry<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.remove(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in the cache
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains no items
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the cache can hold
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.cache.capacity()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes all items.  Entries still held elsewhere keep their values
+    pub fn clear(&self) {
+        self.cache.clear()
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
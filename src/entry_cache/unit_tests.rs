@@ -0,0 +1,125 @@
+use super::*;
+use std::{
+    format,
+    string::String,
+    sync::{Arc, Barrier, mpsc},
+    thread,
+    time::Duration,
+    vec,
+    vec::Vec,
+};
+
+/// Long enough for a test that would otherwise hang to fail instead
+const PATIENCE: Duration = Duration::from_secs(5);
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_be_send_and_sync() -> Result<(), String> {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<EntryLruCache<String, Vec<u8>>>();
+    assert_send_sync::<Entry<Vec<u8>>>();
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_mutate_values_in_place() -> Result<(), String> {
+    let cache = EntryLruCache::new(NonZeroUsize::new(2).unwrap());
+    cache.insert("a", vec![1]);
+    cache.with_value_mut("a", |v| v.push(2));
+
+    if let Some(entry) = cache.entry("a") {
+        entry.write().push(3);
+    }
+
+    match (cache.with_value("a", Vec::clone), cache.with_value_mut("b", |v| v.push(0))) {
+        (Some(v), None) if v == [1, 2, 3] => Ok(()),
+        other => Err(format!("Expected the changes to be kept in the cache. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn writers_to_different_entries_should_not_block_each_other() -> Result<(), String> {
+    let cache = Arc::new(EntryLruCache::new(NonZeroUsize::new(4).unwrap()));
+    cache.insert(1, 0);
+    cache.insert(2, 0);
+
+    // Each writer waits for the other while holding its own entry's write guard, so neither gets past the barrier
+    // unless both guards can be held at once
+    let both_locked = Arc::new(Barrier::new(2));
+    let (done, finished) = mpsc::channel();
+
+    for key in [1, 2] {
+        let (cache, both_locked, done) = (Arc::clone(&cache), Arc::clone(&both_locked), done.clone());
+
+        thread::spawn(move || {
+            let entry = cache.entry(&key).expect("both keys were inserted");
+            let mut value = entry.write();
+            both_locked.wait();
+
+            // The cache itself is not locked while an entry is
+            let other_key_cached = cache.contains_key(&(3 - key));
+            *value += key;
+            drop(value);
+
+            let _ = done.send(other_key_cached);
+        });
+    }
+
+    let mut outcomes = Vec::new();
+
+    for _ in 0..2 {
+        outcomes.push(finished.recv_timeout(PATIENCE).map_err(|_| "A writer was blocked by the other")?);
+    }
+
+    match (&outcomes[..], cache.with_value(&1, |v| *v), cache.with_value(&2, |v| *v)) {
+        ([true, true], Some(1), Some(2)) => Ok(()),
+        other => Err(format!("Expected both writers to finish without blocking. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn eviction_should_not_wait_for_a_held_entry() -> Result<(), String> {
+    let cache = Arc::new(EntryLruCache::new(NonZeroUsize::new(2).unwrap()));
+    cache.insert(1, String::from("one"));
+    let held = cache.entry(&1).ok_or("Expected key 1 to be cached")?;
+    let mut guard = held.write();
+
+    // Another thread fills the cache while this one holds the least recently used entry's write guard
+    let evicted = {
+        let cache = Arc::clone(&cache);
+        let (done, finished) = mpsc::channel();
+
+        thread::spawn(move || {
+            cache.insert(2, String::from("two"));
+            cache.insert(3, String::from("three"));
+            let _ = done.send(cache.contains_key(&1));
+        });
+
+        finished.recv_timeout(PATIENCE).map_err(|_| "Eviction waited for the held entry")?
+    };
+
+    guard.push_str(" (evicted)");
+    drop(guard);
+
+    match (evicted, cache.entry(&1).is_none(), cache.len(), held.read().as_str()) {
+        (false, true, 2, "one (evicted)") => Ok(()),
+        other => Err(format!("Expected the held entry to be evicted yet stay usable. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn insert_should_leave_holders_of_the_previous_entry_their_value() -> Result<(), String> {
+    let cache = EntryLruCache::new(NonZeroUsize::new(2).unwrap());
+    cache.insert(1, 10);
+    let before = cache.entry(&1).ok_or("Expected key 1 to be cached")?;
+    let replaced = cache.insert(1, 20).ok_or("Expected the previous entry to be returned")?;
+
+    match (*before.read(), before.ptr_eq(&replaced), cache.with_value(&1, |v| *v)) {
+        (10, true, Some(20)) => Ok(()),
+        other => Err(format!("Expected the new value to be a new entry. Got {other:?}")),
+    }
+}
@@ -0,0 +1,96 @@
+//! Timing and usage details of a cached item, for example to fill in HTTP `Age` and freshness headers
+#[cfg(feature = "std")]
+use core::time::Duration;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// When an item was stored and last used, and when it expires, as returned by
+/// [`LruCache::entry_info`](crate::LruCache::entry_info).
+///
+/// Times are readings of the cache's [`Clock`](crate::Clock), in nanoseconds since its origin.  Durations are measured
+/// up to the moment the information was taken
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EntryInfo {
+    pub(crate) now: u64,
+    pub(crate) inserted_at: u64,
+    pub(crate) last_accessed: u64,
+    pub(crate) expires_at: Option<u64>,
+    pub(crate) hits: Option<u64>,
+}
+
+#[cfg(feature = "std")]
+impl EntryInfo {
+    /// When the item's current value was stored
+    pub fn inserted_at(&self) -> u64 {
+        self.inserted_at
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// When the item was last stored or fetched through a promoting lookup
+    pub fn last_accessed(&self) -> u64 {
+        self.last_accessed
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// How many times [`LruCache::get`](crate::LruCache::get) found the item, or `None` unless the cache was built with
+    /// [`LruCacheBuilder::track_access`](crate::LruCacheBuilder::track_access)
+    pub fn hits(&self) -> Option<u64> {
+        self.hits
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// When the item expires, whether through its time-to-live or its time-to-idle, or `None` if it never does
+    pub fn expires_at(&self) -> Option<u64> {
+        self.expires_at
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// How long ago the item's current value was stored
+    pub fn age(&self) -> Duration {
+        Duration::from_nanos(self.now.saturating_sub(self.inserted_at))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// How long the item has gone without being used
+    pub fn idle(&self) -> Duration {
+        Duration::from_nanos(self.now.saturating_sub(self.last_accessed))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// How long the item has left before it expires, or `None` if it never does
+    pub fn remaining_ttl(&self) -> Option<Duration> {
+        self.expires_at.map(|expires_at| Duration::from_nanos(expires_at.saturating_sub(self.now)))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Whether the item has less than `fraction` left of the lifetime it was stored with
+    pub(crate) fn is_due_for_refresh(&self, fraction: f64) -> bool {
+        self.expires_at.is_some_and(|expires_at| {
+            let lifetime = expires_at.saturating_sub(self.inserted_at);
+            (expires_at.saturating_sub(self.now) as f64) < lifetime as f64 * fraction
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// How much an item has been used, as yielded by [`LruCache::iter_with_metadata`](crate::LruCache::iter_with_metadata)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EntryMeta {
+    pub(crate) hits: u64,
+    pub(crate) last_accessed: u64,
+}
+
+impl EntryMeta {
+    /// How many times [`LruCache::get`](crate::LruCache::get) found the item.  Always zero unless the cache was built
+    /// with [`LruCacheBuilder::track_access`](crate::LruCacheBuilder::track_access)
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// When the item was last stored or fetched through a promoting lookup, in nanoseconds since the origin of the
+    /// cache's [`Clock`](crate::Clock), or zero if the cache has no clock
+    pub fn last_accessed(&self) -> u64 {
+        self.last_accessed
+    }
+}
@@ -0,0 +1,272 @@
+//! Thread-safe LRU cache whose non-promoting reads take no lock
+//!
+//! [`EpochLruCache`] keeps its values in a fixed table of buckets, each published through an atomic pointer and
+//! reclaimed with [`crossbeam_epoch`].  [`peek`](EpochLruCache::peek), [`contains_key`](EpochLruCache::contains_key)
+//! and [`len`](EpochLruCache::len) only load from the table, so readers neither take a lock nor write to memory that
+//! other threads read.  The recency order lives in an [`LruCache`] behind a mutex: every change, and the promoting
+//! [`get`](EpochLruCache::get), takes that lock, updates the order, and then publishes a fresh copy of each bucket it
+//! changed.  A bucket holds the few keys that hash to it, so a write copies a handful of entries, not the table.
+//!
+//! This type is experimental, and only built with the `lockfree-reads` feature.  Keys and values must be `Send` and
+//! `'static`, since a replaced bucket may be freed by whichever thread next collects garbage, even after the cache has
+//! been dropped.
+//!
+//! # Consistency
+//!
+//! Each read sees one published version of the key's bucket, so a value is never torn, and a thread that wrote a key
+//! reads its own write from then on.  Another thread's read may however see a bucket published slightly before the
+//! latest, so it may still find an item that was just removed or evicted, or not yet find one that was just inserted.
+//! Successive reads of one key by one thread never go back to an older version.  A write that evicts unpublishes the
+//! evicted item before publishing the new one, so lock-free readers never see more items than the capacity.
+use crate::{
+    DefaultHashBuilder, LruCache,
+    sync::{AtomicUsize, Mutex, MutexGuard, Ordering, ResetPoison},
+};
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    num::NonZeroUsize,
+    vec::Vec,
+};
+
+/// The items whose keys hash to one slot of the table.  Published buckets are never changed, only replaced
+struct Bucket<K, V> {
+    entries: Vec<(K, V)>,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache that can be shared between threads, where non-promoting reads are lock-free
+pub struct EpochLruCache<K, V, S = DefaultHashBuilder> {
+    /// The recency order.  Its values are empty: the values themselves are only kept in `buckets`
+    order: Mutex<LruCache<K, (), S>>,
+    /// A null pointer is an empty bucket.  The length is a power of two of at least the capacity
+    buckets: Vec<Atomic<Bucket<K, V>>>,
+    hash_builder: S,
+    /// Only changed under the `order` lock
+    len: AtomicUsize,
+}
+
+impl<K, V> EpochLruCache<K, V>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Clone + Send + 'static,
+{
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        EpochLruCache::with_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> EpochLruCache<K, V, S>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Clone + Send + 'static,
+    S: BuildHasher + Clone,
+{
+    /// Creates a cache that uses the given hash builder to hash keys
+    pub fn with_hasher(capacity: NonZeroUsize, hash_builder: S) -> Self {
+        EpochLruCache {
+            order: Mutex::new(LruCache::with_hasher(capacity, hash_builder.clone())),
+            buckets: (0..capacity.get().next_power_of_two()).map(|_| Atomic::null()).collect(),
+            hash_builder,
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Locks the recency order.  Nothing is published until the order has been updated, and the order is rebuilt from
+    /// the buckets if a panic interrupted an update, so a poisoned lock is recovered from
+    fn lock(&self) -> MutexGuard<'_, LruCache<K, (), S>> {
+        self.order.lock().unwrap_or_else(|poisoned| {
+            let mut order = poisoned.into_inner();
+            self.resync(&mut order);
+            self.order.reset_poison();
+            order
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Makes the order and the buckets agree again, by unpublishing every item and forgetting the order
+    fn resync(&self, order: &mut LruCache<K, (), S>) {
+        order.clear();
+        let guard = epoch::pin();
+
+        for bucket in &self.buckets {
+            Self::retire(bucket.swap(Shared::null(), Ordering::AcqRel, &guard), &guard);
+        }
+
+        self.len.store(0, Ordering::Release);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn bucket<Q>(&self, key: &Q) -> &Atomic<Bucket<K, V>>
+    where
+        Q: Hash + ?Sized,
+    {
+        &self.buckets[self.hash_builder.hash_one(key) as usize & (self.buckets.len() - 1)]
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Looks the key up in the currently published version of its bucket
+    fn find<Q, R>(&self, key: &Q, f: impl FnOnce(&V) -> R) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let guard = epoch::pin();
+
+        // SAFETY: buckets are only freed through `Guard::defer_destroy` after being unpublished, which waits until
+        // every thread pinned at the time, including this one, has unpinned
+        let bucket = unsafe { self.bucket(key).load(Ordering::Acquire, &guard).as_ref() }?;
+
+        bucket.entries.iter().find(|(k, _)| k.borrow() == key).map(|(_, v)| f(v))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Publishes a copy of the key's bucket with the key set to `value`, or removed if `value` is `None`, and returns
+    /// the value it replaced.  Must only be called while holding the `order` lock
+    fn publish(&self, key: &K, value: Option<V>, guard: &Guard) -> Option<V> {
+        let slot = self.bucket(key);
+        let current = slot.load(Ordering::Acquire, guard);
+
+        // SAFETY: as in `find`; besides, only the holder of the `order` lock replaces buckets
+        let mut entries = unsafe { current.as_ref() }.map_or_else(Vec::new, |bucket| bucket.entries.clone());
+        let old = entries.iter().position(|(k, _)| k == key).map(|idx| entries.swap_remove(idx).1);
+
+        if let Some(value) = value {
+            entries.push((key.clone(), value));
+        }
+
+        let next = if entries.is_empty() {
+            Shared::null()
+        } else {
+            Owned::new(Bucket { entries }).into_shared(guard)
+        };
+
+        Self::retire(slot.swap(next, Ordering::AcqRel, guard), guard);
+        old
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Frees an unpublished bucket once no reader can still be looking at it
+    fn retire(bucket: Shared<'_, Bucket<K, V>>, guard: &Guard) {
+        if !bucket.is_null() {
+            // SAFETY: the bucket has just been swapped out, so no reader that pins from now on can reach it
+            unsafe { guard.defer_destroy(bucket) };
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetches a copy of an item without changing its position.  Takes no lock
+    pub fn peek<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key, V::clone)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains the key, without changing its position.  Takes no lock
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key, |_| ()).is_some()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetches a copy of an item, making it the most recently used.  Takes the lock to update the order
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut order = self.lock();
+
+        if order.promote(key) {
+            // Found under the lock, so the latest bucket is published
+            self.find(key, V::clone)
+        } else {
+            None
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item, returning the old value if the key was already present.  If the cache is full, the least
+    /// recently used item is evicted first
+    pub fn put(&self, key: K, new_value: V) -> Option<V> {
+        let mut order = self.lock();
+        let guard = epoch::pin();
+
+        if let Some((evicted, ())) = order.push(key.clone(), ())
+            && evicted != key
+        {
+            self.publish(&evicted, None, &guard);
+        }
+
+        let old = self.publish(&key, Some(new_value), &guard);
+        self.len.store(order.len(), Ordering::Release);
+        old
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an item, returning its value if it was present
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut order = self.lock();
+        let (key, ()) = order.pop_entry(key)?;
+        let old = self.publish(&key, None, &epoch::pin());
+        self.len.store(order.len(), Ordering::Release);
+        old
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in the cache.  Takes no lock
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains no items.  Takes no lock
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the cache can hold
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.lock().capacity()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes all items
+    pub fn clear(&self) {
+        self.resync(&mut self.lock());
+    }
+}
+
+impl<K, V, S> Drop for EpochLruCache<K, V, S> {
+    fn drop(&mut self) {
+        // SAFETY: the cache is borrowed mutably, so no other thread can be reading a bucket
+        let guard = unsafe { epoch::unprotected() };
+
+        for bucket in &self.buckets {
+            let bucket = bucket.swap(Shared::null(), Ordering::Relaxed, guard);
+
+            if !bucket.is_null() {
+                // SAFETY: as above, and the bucket has just been unpublished
+                drop(unsafe { bucket.into_owned() });
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
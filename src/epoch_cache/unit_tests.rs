@@ -0,0 +1,139 @@
+use super::*;
+use std::{
+    format,
+    string::String,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    vec,
+    vec::Vec,
+};
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_be_send_and_sync() -> Result<(), String> {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<EpochLruCache<String, String>>();
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_evict_the_least_recently_used() -> Result<(), String> {
+    let cache = EpochLruCache::new(NonZeroUsize::new(2).unwrap());
+    cache.put(1, "one");
+    cache.put(2, "two");
+
+    // `peek` does not promote, `get` does
+    let peeked = cache.peek(&1);
+    cache.put(3, "three");
+    let promoted = cache.get(&2);
+    cache.put(4, "four");
+
+    match (peeked, promoted, cache.contains_key(&1), cache.contains_key(&3), cache.peek(&4), cache.len()) {
+        (Some("one"), Some("two"), false, false, Some("four"), 2) => Ok(()),
+        other => Err(format!("Expected 1 then 3 to be evicted. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_replace_remove_and_clear() -> Result<(), String> {
+    let cache = EpochLruCache::new(NonZeroUsize::new(4).unwrap());
+    cache.put("a", 1);
+    cache.put("b", 2);
+
+    match (cache.put("a", 10), cache.peek("a"), cache.remove("b"), cache.peek("b"), cache.remove("b"), cache.len()) {
+        (Some(1), Some(10), Some(2), None, None, 1) => (),
+        other => return Err(format!("Expected a replacement and a removal. Got {other:?}")),
+    }
+
+    cache.clear();
+
+    match (cache.peek("a"), cache.is_empty(), cache.capacity().get()) {
+        (None, true, 4) => Ok(()),
+        other => Err(format!("Expected an empty cache. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_keep_colliding_keys_apart() -> Result<(), String> {
+    // A capacity of one gives a single bucket, shared by every key
+    let cache = EpochLruCache::new(NonZeroUsize::new(1).unwrap());
+    let values: Vec<_> = (0..8_u32)
+        .map(|key| {
+            cache.put(key, key * 10);
+            (cache.peek(&key), key.checked_sub(1).and_then(|previous| cache.peek(&previous)))
+        })
+        .collect();
+
+    match values.iter().all(|&(now, before)| now.is_some() && before.is_none()) {
+        true => Ok(()),
+        false => Err(format!("Expected each key to evict the one before. Got {values:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn lock_free_readers_should_never_see_torn_or_older_values() -> Result<(), String> {
+    const READERS: usize = 4;
+    const KEYS: u64 = 128;
+    const WRITES: u64 = 20_000;
+
+    let cache = Arc::new(EpochLruCache::new(NonZeroUsize::new(64).unwrap()));
+    let writing = Arc::new(AtomicBool::new(true));
+
+    let readers: Vec<_> = (0..READERS)
+        .map(|reader| {
+            let (cache, writing) = (Arc::clone(&cache), Arc::clone(&writing));
+
+            thread::spawn(move || {
+                let mut latest = vec![0; KEYS as usize];
+                let mut key = reader as u64;
+
+                while writing.load(Ordering::Relaxed) {
+                    key = (key * 31 + 7) % KEYS;
+
+                    if let Some((found, generation)) = cache.peek(&key) {
+                        if found != key || generation < latest[key as usize] {
+                            return Err(format!("Key {key} read as ({found}, {generation}) after {latest:?}"));
+                        }
+
+                        latest[key as usize] = generation;
+                    }
+                }
+
+                Ok(())
+            })
+        })
+        .collect();
+
+    // Values pair the key with an ever increasing generation, so a torn or stale read is recognisable
+    for generation in 1..=WRITES {
+        let key = (generation * 13) % KEYS;
+
+        if generation.is_multiple_of(10) {
+            cache.remove(&key);
+
+            if cache.peek(&key).is_some() {
+                return Err(format!("The writer read key {key} after removing it"));
+            }
+        } else {
+            cache.put(key, (key, generation));
+        }
+    }
+
+    writing.store(false, Ordering::Relaxed);
+
+    for reader in readers {
+        reader.join().map_err(|_| "A reader panicked")??;
+    }
+
+    match cache.len() {
+        64 => Ok(()),
+        other => Err(format!("Expected the cache to be full. Got {other}")),
+    }
+}
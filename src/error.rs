@@ -0,0 +1,84 @@
+use alloc::string::{String, ToString};
+use core::fmt;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Errors reported by fallible cache operations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheError {
+    /// The recency order contains a key that is not in the store
+    KeyNotInStore,
+    /// The recency order contains the same key more than once
+    DuplicateKey,
+    /// The store and the recency order contain a different number of keys
+    LengthMismatch { store_len: usize, order_len: usize },
+    /// The cache contents do not fit within its capacity
+    CapacityExceeded { len: usize, capacity: usize },
+    /// The capacity is larger than the cache can address.  See the `wide-index` feature
+    CapacityUnsupported { capacity: usize, max: usize },
+    /// The key lies outside the key universe of an [`IndexedLruCache`](crate::IndexedLruCache)
+    KeyOutOfRange { key: usize, universe: usize },
+    /// Every item in a full cache is pinned or guarded, so none can be evicted to make room
+    AllPinned { capacity: usize },
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheError::KeyNotInStore => write!(f, "recency order contains a key that is not in the store"),
+            CacheError::DuplicateKey => write!(f, "recency order contains a duplicate key"),
+            CacheError::LengthMismatch { store_len, order_len } => {
+                write!(f, "store contains {store_len} keys but recency order contains {order_len}")
+            }
+            CacheError::CapacityExceeded { len, capacity } => {
+                write!(f, "{len} items do not fit in a cache with capacity {capacity}")
+            }
+            CacheError::CapacityUnsupported { capacity, max } => {
+                write!(f, "capacity {capacity} exceeds the largest supported capacity of {max}")
+            }
+            CacheError::KeyOutOfRange { key, universe } => {
+                write!(f, "key {key} lies outside the key universe 0..{universe}")
+            }
+            CacheError::AllPinned { capacity } => {
+                write!(f, "all {capacity} items are pinned, so none can be evicted")
+            }
+        }
+    }
+}
+
+impl core::error::Error for CacheError {}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// An internal inconsistency found by [`LruCache::debug_validate`](crate::LruCache::debug_validate), describing the
+/// invariant that does not hold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    message: String,
+}
+
+impl ValidationError {
+    pub(crate) fn new(message: fmt::Arguments<'_>) -> Self {
+        ValidationError {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl core::error::Error for ValidationError {}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Returns a [`ValidationError`] with the formatted message unless the condition holds
+macro_rules! ensure {
+    ($condition:expr, $($message:tt)+) => {
+        if !$condition {
+            return Err($crate::ValidationError::new(format_args!($($message)+)));
+        }
+    };
+}
+
+pub(crate) use ensure;
@@ -0,0 +1,1073 @@
+//! Choice of eviction victim
+use crate::{
+    ValidationError,
+    error::ensure,
+    frequency_sketch::FrequencySketch,
+    key_index::KeyIndex,
+    nodes::{Handle, Nodes, SideTable},
+    random::Random,
+};
+use alloc::collections::{BinaryHeap, VecDeque};
+use core::{cmp::Reverse, mem, num::NonZeroUsize};
+use hashbrown::HashMap;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// How a cache chooses which entry to evict when it is full
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvictionMode {
+    /// Keeps every entry in an exact recency order and evicts the least recently used
+    #[default]
+    Exact,
+    /// Stamps each entry with the time of its last use instead of reordering entries on every access, and evicts the
+    /// least recently used of `sample_size` randomly chosen entries, as Redis does.
+    ///
+    /// Reads no longer touch the recency list, so eviction is only approximately LRU.  `pop_lru` removes the entry
+    /// that eviction would choose, and `demote` makes an entry the preferred victim whenever it is sampled.  Methods
+    /// that expose the recency order, such as `recency_rank`, `pop_mru` and `into_parts`, see the order in which
+    /// entries were inserted
+    Sampled { sample_size: NonZeroUsize },
+    /// Counts the uses of each entry and evicts the least frequently used, or the least recently used of those with
+    /// the fewest uses.
+    ///
+    /// Suits workloads whose popular keys stay popular, which a burst of one-off keys would flush out of an LRU cache.
+    /// An entry's count starts at one when it is inserted and is kept until it leaves the cache, so keys that were once
+    /// popular linger.  `pop_lru` removes the entry that eviction would choose, and `demote` resets an entry's count
+    /// to make it the next victim.  Methods that expose the recency order still see the order in which entries were
+    /// used.  Each use costs a heap insertion, making reads `O(log n)`
+    Lfu,
+    /// Segmented LRU, which keeps the entries that have been used since they were inserted in a protected segment of
+    /// up to `protected_percent` of the capacity, so that a scan through many new keys cannot flush them out.
+    ///
+    /// New entries join a probationary segment, and move to the front of the protected segment the first time they
+    /// are used again.  Eviction takes the least recently used probationary entry, or the least recently used
+    /// protected entry once probation is empty.  When the protected segment overflows, its least recently used entry
+    /// returns to the front of probation.  The recency order runs through the protected segment and then probation,
+    /// so `pop_lru` removes the entry that eviction would choose and `demote` puts an entry at the back of probation.
+    /// [`EvictionMode::SEGMENTED`] protects 80% of the capacity
+    Segmented { protected_percent: u8 },
+    /// 2Q, which holds new entries in a first-in first-out queue of about `in_percent` of the capacity, and remembers
+    /// the key hashes of the entries evicted from it in a ghost queue of up to `ghost_percent` of the capacity.  Only
+    /// a key that returns while the ghost queue remembers it joins the main queue, which is kept in LRU order.
+    ///
+    /// Like [`EvictionMode::Segmented`] this keeps a scan through many new keys from flushing out the hot entries, but
+    /// a use of a new entry costs nothing, since the queue of new entries is never reordered.  Eviction takes the
+    /// oldest new entry while they fill more than their share, or the least recently used main entry otherwise.  The
+    /// recency order runs through the main queue and then the new entries, so `demote` puts an entry at the back of
+    /// the new entries.  The ghost queue holds one hash per key, not the keys or values, and counts towards
+    /// `memory_usage`.  [`EvictionMode::TWO_QUEUE`] gives 25% of the capacity to new entries and remembers as many
+    /// hashes as half the capacity, as the paper suggests
+    TwoQueue { in_percent: u8, ghost_percent: u8 },
+    /// W-TinyLFU, as in Caffeine: new entries join an LRU admission window of about `window_percent` of the capacity,
+    /// and an entry leaving the window only displaces the victim of the main region, a segmented LRU that protects 80%
+    /// of its entries, if a frequency sketch estimates that it has been used more often.
+    ///
+    /// The sketch counts the uses of cached and recently evicted keys alike in 4-bit counters, halved every ten uses
+    /// per entry of capacity so that old popularity fades.  One-off keys then pass through the window without
+    /// displacing popular entries, while a burst of new keys can still be reused from the window.  The recency order
+    /// runs through the window and then the main region, protected entries first, so `demote` puts an entry at the
+    /// back of probation.  `pop_lru` removes the entry that eviction would choose, and may first move the window's
+    /// oldest entry into the main region.  `LruCache::sketch_stats` reports on the sketch.
+    /// [`EvictionMode::TINY_LFU`] gives 1% of the capacity to the window
+    TinyLfu { window_percent: u8 },
+    /// Evicts entries in the order they were inserted, however often they are used.
+    ///
+    /// Reads never reorder entries, whether through `get`, `get_mut`, `promote` or a read buffer, although they still
+    /// count as uses for idle times and statistics.  When `refresh_on_put` is set, replacing the value of an existing
+    /// key moves it to the front as if it had been inserted again.  [`EvictionMode::FIFO`] leaves it in place, so that
+    /// an entry's position only depends on when its key was first inserted.  `demote` still makes an entry the next
+    /// victim, and methods that expose the recency order see the order of insertion
+    Fifo { refresh_on_put: bool },
+    /// Evicts an entry chosen uniformly at random from those in the cache.
+    ///
+    /// Entries are never reordered and no use is recorded, so reads cost nothing beyond the lookup.  Useful as a
+    /// baseline when comparing policies, and competitive with LRU on workloads without much locality.  The choices are
+    /// repeatable when the cache is built with [`LruCacheBuilder::seed`](crate::LruCacheBuilder::seed).  `pop_lru`
+    /// removes a random entry, `demote` has no effect on eviction, and methods that expose the recency order see the
+    /// order of insertion
+    Random,
+    /// Keeps entries in exact recency order, like [`EvictionMode::Exact`], but evicts the most recently used.
+    ///
+    /// Suits workloads that loop over more keys than the cache holds, such as repeated table scans, where LRU evicts
+    /// every key just before it comes round again and never hits.  Evicting the most recently used entry instead keeps
+    /// a stable subset of the loop cached.  `pop_lru` and `pop_mru` still remove the least and most recently used
+    /// entries, and `demote` still marks an entry as the least recently used, which makes it the last to be evicted
+    Mru,
+    /// GreedyDual-Size, which weighs how recently an entry was used against what it would cost to recompute, as given
+    /// to `LruCache::put_with_cost`.
+    ///
+    /// Each entry has a priority of the cache's clock plus its cost, set when it is inserted and again whenever it is
+    /// used, and eviction takes the entry with the lowest priority, the least recently used of those that tie.  The
+    /// clock then advances to the evicted entry's priority, so an expensive entry that is never used again is
+    /// eventually overtaken by cheaper entries in use, and evicted.  Capacity counts entries, so every entry has a
+    /// size of one.  Items inserted with `put` cost one, which makes every priority tie and the mode evict in LRU
+    /// order.  `pop_lru` removes the entry that eviction would choose, and `demote` makes an entry the next victim.
+    /// Methods that expose the recency order still see the order in which entries were used.  Each use costs a heap
+    /// insertion, making reads `O(log n)`
+    CostAware,
+    /// S3-FIFO, which holds new entries in a small first-in first-out queue of about `small_percent` of the capacity,
+    /// in front of a main first-in first-out queue, and remembers the key hashes of entries evicted from the small
+    /// queue in a ghost queue as long as the main queue.
+    ///
+    /// A use of an entry only counts it, up to three, and never reorders the queues.  The oldest entry of the small
+    /// queue moves to the main queue if it has been used since it was inserted, and is evicted otherwise.  The oldest
+    /// entry of the main queue goes back to the front of the main queue, one count fewer, if it has been used, and is
+    /// evicted otherwise.  A key that returns while the ghost queue remembers it goes straight into the main queue.
+    /// Eviction works through the small queue while it fills its share and through the main queue otherwise, so
+    /// `pop_lru` may move entries between the queues before removing one.  The recency order runs through the main
+    /// queue and then the small queue, newest first, so `demote` puts an entry at the back of the small queue.
+    /// `LruCache::s3_fifo_queues` reports how full each queue is.  [`EvictionMode::S3_FIFO`] gives 10% of the
+    /// capacity to the small queue, as the paper suggests
+    S3Fifo { small_percent: u8 },
+}
+
+impl EvictionMode {
+    /// Segmented LRU with 20% of the capacity for probation and 80% protected
+    pub const SEGMENTED: EvictionMode = EvictionMode::Segmented { protected_percent: 80 };
+
+    /// 2Q with 25% of the capacity for new entries and a ghost queue of half the capacity
+    pub const TWO_QUEUE: EvictionMode = EvictionMode::TwoQueue {
+        in_percent: 25,
+        ghost_percent: 50,
+    };
+
+    /// W-TinyLFU with 1% of the capacity for the admission window
+    pub const TINY_LFU: EvictionMode = EvictionMode::TinyLfu { window_percent: 1 };
+
+    /// S3-FIFO with 10% of the capacity for the small queue
+    pub const S3_FIFO: EvictionMode = EvictionMode::S3Fifo { small_percent: 10 };
+
+    /// First in, first out, with a replaced value keeping its key's place
+    pub const FIFO: EvictionMode = EvictionMode::Fifo { refresh_on_put: false };
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The part of the cache that an entry belongs to, under eviction modes that divide it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Segment {
+    /// Entries that have not yet proved themselves.  Every entry starts here
+    Probation,
+    Protected,
+    /// New entries that have yet to be admitted to the main region, under [`EvictionMode::TinyLfu`]
+    Window,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The state that an [`EvictionMode`] keeps to choose victims
+pub(crate) enum Policy {
+    Exact,
+    Sampled(Sampler),
+    Lfu(Lfu),
+    Slru(Slru),
+    TwoQueue(TwoQueue),
+    TinyLfu(TinyLfu),
+    Fifo { refresh_on_put: bool },
+    Random(Random),
+    Mru,
+    CostAware(GreedyDual),
+    S3Fifo(S3Fifo),
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// State for [`EvictionMode::Sampled`]
+pub(crate) struct Sampler {
+    sample_size: usize,
+    /// Logical time, advanced on every use of an entry
+    clock: u64,
+    random: Random,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl Sampler {
+    pub(crate) fn new(sample_size: NonZeroUsize, seed: u64) -> Self {
+        Sampler {
+            sample_size: sample_size.get(),
+            clock: 0,
+            random: Random::new(seed),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn sample_size(&self) -> usize {
+        self.sample_size
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Advances the clock, returning the stamp for an entry that is being used now
+    pub(crate) fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn next_random(&mut self) -> u64 {
+        self.random.next_u64()
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Vacant positions drawn by [`random_victim`] before it settles for a less even choice
+const RANDOM_DRAWS: usize = 16;
+
+/// Returns an entry chosen uniformly at random for [`EvictionMode::Random`].  A draw that lands on a vacant storage
+/// position is drawn again, which keeps every entry equally likely.  Storage is nearly full whenever the cache evicts,
+/// but after many removals the first entry found after a random position is taken instead
+pub(crate) fn random_victim<K, V>(nodes: &Nodes<K, V>, random: &mut Random) -> Option<Handle> {
+    if nodes.len() == 0 {
+        return None;
+    }
+
+    (0..RANDOM_DRAWS)
+        .find_map(|_| nodes.handle_at((random.next_u64() % nodes.positions() as u64) as usize))
+        .or_else(|| nodes.sample(random.next_u64()))
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// State for [`EvictionMode::Lfu`]
+#[derive(Default)]
+pub(crate) struct Lfu {
+    /// Logical time, advanced on every use of an entry
+    clock: u64,
+    /// Uses counted for each entry
+    uses: SideTable<u64>,
+    /// The uses, time of last use and key hash of each entry, the fewest and oldest uses first.  An entry that has since
+    /// been used again, or has gone, leaves its earlier record in place to be skipped when it comes up
+    queue: BinaryHeap<Reverse<(u64, u64, u64)>>,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl Lfu {
+    /// Counts the first use of an entry that has just been inserted
+    pub(crate) fn admit<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        *self.uses.get_mut(nodes.slot(idx)) = 0;
+        self.record(nodes, idx);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Counts a use of an entry
+    pub(crate) fn record<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        self.clock += 1;
+        let uses = self.uses.get_mut(nodes.slot(idx));
+        *uses = uses.saturating_add(1);
+        nodes.node_mut(idx).last_access = self.clock;
+        self.enqueue(nodes, idx);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Forgets the uses of an entry, making it the next victim
+    pub(crate) fn reset<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        *self.uses.get_mut(nodes.slot(idx)) = 0;
+        nodes.node_mut(idx).last_access = 0;
+        self.enqueue(nodes, idx);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the entry with the fewest uses, dropping the out of date records in front of it
+    pub(crate) fn victim<K, V>(&mut self, nodes: &Nodes<K, V>, index: &KeyIndex) -> Option<Handle> {
+        while let Some(&Reverse((uses, last_access, hash))) = self.queue.peek() {
+            // Times of use are unique, so a record matches at most one entry
+            let current = index.find(hash, |&i| {
+                let node = nodes.node(i);
+                node.hash == hash && node.last_access == last_access && self.uses.get(nodes.slot(i)) == uses
+            });
+
+            if current.is_some() {
+                return current;
+            }
+
+            self.queue.pop();
+        }
+
+        None
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn clear(&mut self) {
+        self.queue.clear();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Bytes allocated for the queue and the counts
+    pub(crate) fn allocated(&self) -> usize {
+        self.queue.capacity() * mem::size_of::<Reverse<(u64, u64, u64)>>() + self.uses.heap_size()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Queues an entry's current record, rebuilding the queue once out of date records outnumber the entries
+    fn enqueue<K, V>(&mut self, nodes: &Nodes<K, V>, idx: Handle) {
+        let node = nodes.node(idx);
+        self.queue.push(Reverse((self.uses.get(nodes.slot(idx)), node.last_access, node.hash)));
+
+        if self.queue.len() > 2 * nodes.len() + 64 {
+            self.queue = nodes
+                .iter_from_head()
+                .map(|(idx, node)| Reverse((self.uses.get(nodes.slot(idx)), node.last_access, node.hash)))
+                .collect();
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// State for [`EvictionMode::CostAware`]
+#[derive(Default)]
+pub(crate) struct GreedyDual {
+    /// The priority of the last entry evicted, which new priorities start from
+    inflation: u64,
+    /// Logical time, advanced on every use of an entry to break ties between priorities
+    clock: u64,
+    /// The current priority of each entry
+    priorities: SideTable<u64>,
+    /// What it would cost to recompute each entry's value, as given to
+    /// [`LruCache::put_with_cost`](crate::LruCache::put_with_cost)
+    costs: SideTable<u64>,
+    /// The priority, time of last use and key hash of each entry, the lowest priority first.  An entry that has since
+    /// been used again, or has gone, leaves its earlier record in place to be skipped when it comes up
+    queue: BinaryHeap<Reverse<(u64, u64, u64)>>,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl GreedyDual {
+    /// The priority of the last entry evicted
+    pub(crate) fn inflation(&self) -> u64 {
+        self.inflation
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Gives an entry that has just been inserted a cost of one and the priority that follows from it
+    pub(crate) fn admit<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        self.set_cost(nodes, idx, 1);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Changes what it would cost to recompute an entry's value and restores its priority accordingly
+    pub(crate) fn set_cost<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle, cost: u64) {
+        *self.costs.get_mut(nodes.slot(idx)) = cost;
+        self.record(nodes, idx);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Restores an entry's priority to the current inflation plus its cost, on use
+    pub(crate) fn record<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        self.clock += 1;
+        let slot = nodes.slot(idx);
+        *self.priorities.get_mut(slot) = self.inflation.saturating_add(self.costs.get(slot));
+        nodes.node_mut(idx).last_access = self.clock;
+        self.enqueue(nodes, idx);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Drops an entry's priority below every other, making it the next victim
+    pub(crate) fn reset<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        *self.priorities.get_mut(nodes.slot(idx)) = 0;
+        nodes.node_mut(idx).last_access = 0;
+        self.enqueue(nodes, idx);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the entry with the lowest priority, dropping the out of date records in front of it
+    pub(crate) fn victim<K, V>(&mut self, nodes: &Nodes<K, V>, index: &KeyIndex) -> Option<Handle> {
+        while let Some(&Reverse((priority, last_access, hash))) = self.queue.peek() {
+            // Times of use are unique, so a record matches at most one entry
+            let current = index.find(hash, |&i| {
+                let node = nodes.node(i);
+                let current_priority = self.priorities.get(nodes.slot(i));
+                node.hash == hash && node.last_access == last_access && current_priority == priority
+            });
+
+            if current.is_some() {
+                return current;
+            }
+
+            self.queue.pop();
+        }
+
+        None
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Advances the inflation to the priority of an entry that is being evicted
+    pub(crate) fn evicted<K, V>(&mut self, nodes: &Nodes<K, V>, idx: Handle) {
+        self.inflation = self.inflation.max(self.priorities.get(nodes.slot(idx)));
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn clear(&mut self) {
+        self.inflation = 0;
+        self.queue.clear();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Bytes allocated for the queue, the priorities and the costs
+    pub(crate) fn allocated(&self) -> usize {
+        let queue = self.queue.capacity() * mem::size_of::<Reverse<(u64, u64, u64)>>();
+        queue + self.priorities.heap_size() + self.costs.heap_size()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Queues an entry's current record, rebuilding the queue once out of date records outnumber the entries
+    fn enqueue<K, V>(&mut self, nodes: &Nodes<K, V>, idx: Handle) {
+        let node = nodes.node(idx);
+        self.queue.push(Reverse((self.priorities.get(nodes.slot(idx)), node.last_access, node.hash)));
+
+        if self.queue.len() > 2 * nodes.len() + 64 {
+            self.queue = nodes
+                .iter_from_head()
+                .map(|(idx, node)| Reverse((self.priorities.get(nodes.slot(idx)), node.last_access, node.hash)))
+                .collect();
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// State for [`EvictionMode::Segmented`].  Both segments share the recency list: the protected entries come first,
+/// from most to least recently used, followed by the probationary entries.  Under [`EvictionMode::TinyLfu`] the
+/// segments follow the window entries instead
+pub(crate) struct Slru {
+    /// Most entries that the protected segment may hold
+    protected_capacity: usize,
+    protected_len: usize,
+    /// The least recently used protected entry, behind which the probationary segment starts
+    boundary: Option<Handle>,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl Slru {
+    pub(crate) fn new(capacity: usize, protected_percent: u8) -> Self {
+        Slru {
+            protected_capacity: capacity * usize::from(protected_percent.min(100)) / 100,
+            protected_len: 0,
+            boundary: None,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Moves a new entry from the front of the segments to the front of probation
+    pub(crate) fn admit<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        if let Some(boundary) = self.boundary {
+            nodes.move_behind(idx, boundary);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Moves a used entry to the front of the protected segment, which starts behind `front` or at the head of the list,
+    /// returning the least recently used protected entry to probation if the segment overflows
+    pub(crate) fn record<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle, front: Option<Handle>) {
+        if nodes.node(idx).segment == Segment::Protected {
+            if self.boundary == Some(idx) {
+                self.boundary = protected_before(nodes, idx).or(self.boundary);
+            }
+        } else {
+            nodes.node_mut(idx).segment = Segment::Protected;
+            self.protected_len += 1;
+            self.boundary = self.boundary.or(Some(idx));
+        }
+
+        match front {
+            Some(front) => nodes.move_behind(idx, front),
+            None => nodes.move_to_front(idx),
+        }
+
+        if self.protected_len > self.protected_capacity
+            && let Some(boundary) = self.boundary
+        {
+            nodes.node_mut(boundary).segment = Segment::Probation;
+            self.protected_len -= 1;
+            self.boundary = protected_before(nodes, boundary);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Takes an entry out of the protected segment, if it is there, before it leaves the cache or is demoted
+    pub(crate) fn forget<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        if nodes.node(idx).segment == Segment::Protected {
+            nodes.node_mut(idx).segment = Segment::Probation;
+            self.protected_len -= 1;
+
+            if self.boundary == Some(idx) {
+                self.boundary = protected_before(nodes, idx);
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn clear(&mut self) {
+        self.protected_len = 0;
+        self.boundary = None;
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Checks that the protected entries lead the segments, that they are counted, and that the boundary follows them
+    pub(crate) fn validate<K, V>(&self, nodes: &Nodes<K, V>) -> Result<(), ValidationError> {
+        let segments = || nodes.iter_from_head().skip_while(|(_, node)| node.segment == Segment::Window);
+        let protected = segments().take_while(|(_, node)| node.segment == Segment::Protected).count();
+        let last_protected = segments().take(protected).last().map(|(idx, _)| idx);
+        let misplaced = segments().skip(protected).any(|(_, node)| node.segment == Segment::Protected);
+
+        ensure!(!misplaced, "protected entry found behind the probationary segment");
+        ensure!(protected == self.protected_len, "protected segment holds a different number of entries than counted");
+        ensure!(protected <= self.protected_capacity, "protected segment holds more entries than its capacity");
+        ensure!(self.boundary == last_protected, "boundary is not the last protected entry");
+
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Returns the protected entry in front of an entry, if there is one
+fn protected_before<K, V>(nodes: &Nodes<K, V>, idx: Handle) -> Option<Handle> {
+    nodes.prev(idx).filter(|&prev| nodes.node(prev).segment == Segment::Protected)
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// State for [`EvictionMode::TwoQueue`].  Both live queues share the recency list: the main entries come first, from
+/// most to least recently used, followed by the new entries from newest to oldest.  New entries are marked as
+/// [`Segment::Probation`] and main entries as [`Segment::Protected`]
+pub(crate) struct TwoQueue {
+    /// Number of new entries above which eviction takes the oldest of them
+    in_capacity: usize,
+    in_len: usize,
+    /// The least recently used main entry, behind which the new entries start
+    boundary: Option<Handle>,
+    /// Hashes of the keys evicted from the new entries
+    ghost: GhostQueue,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl TwoQueue {
+    pub(crate) fn new(capacity: NonZeroUsize, in_percent: u8, ghost_percent: u8) -> Self {
+        TwoQueue {
+            in_capacity: capacity.get() * usize::from(in_percent.min(100)) / 100,
+            in_len: 0,
+            boundary: None,
+            ghost: GhostQueue::new(capacity.get() * usize::from(ghost_percent) / 100),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Places an entry that has just been pushed onto the front of the list: a key that the ghost queue remembers stays
+    /// there as the most recently used main entry, and any other joins the new entries.  The ghost queue is trimmed to
+    /// its capacity only afterwards, so that the eviction that made room cannot make it forget this key
+    pub(crate) fn admit<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        if self.ghost.remembers(nodes.node(idx).hash) {
+            nodes.node_mut(idx).segment = Segment::Protected;
+            self.boundary = self.boundary.or(Some(idx));
+        } else {
+            self.in_len += 1;
+
+            if let Some(boundary) = self.boundary {
+                nodes.move_behind(idx, boundary);
+            }
+        }
+
+        self.ghost.trim();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Moves a used main entry to the front of the list.  New entries keep their place
+    pub(crate) fn record<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        if nodes.node(idx).segment == Segment::Protected {
+            if self.boundary == Some(idx) {
+                self.boundary = nodes.prev(idx).or(self.boundary);
+            }
+
+            nodes.move_to_front(idx);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the oldest new entry while the new entries fill more than their share, otherwise the least recently
+    /// used main entry
+    pub(crate) fn victim<K, V>(&self, nodes: &Nodes<K, V>) -> Option<Handle> {
+        match self.boundary {
+            Some(boundary) if self.in_len <= self.in_capacity => Some(boundary),
+            _ => nodes.tail(),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Trims the ghost queue to its capacity after evictions that made room for nothing
+    pub(crate) fn trim_ghosts(&mut self) {
+        self.ghost.trim();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Remembers the key hash of an entry that is about to be evicted to make room for another, if it is a new entry
+    pub(crate) fn remember<K, V>(&mut self, nodes: &Nodes<K, V>, idx: Handle) {
+        let node = nodes.node(idx);
+
+        if node.segment != Segment::Protected {
+            self.ghost.push(node.hash);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Takes an entry out of the main queue, if it is there, before it is demoted to be the oldest new entry
+    pub(crate) fn demote<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        if nodes.node(idx).segment == Segment::Protected {
+            self.forget(nodes, idx);
+            self.in_len += 1;
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Takes an entry out of its queue before it leaves the cache
+    pub(crate) fn forget<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        match nodes.node(idx).segment {
+            Segment::Protected => {
+                nodes.node_mut(idx).segment = Segment::Probation;
+
+                if self.boundary == Some(idx) {
+                    self.boundary = nodes.prev(idx);
+                }
+            }
+            _ => self.in_len -= 1,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn clear(&mut self) {
+        self.in_len = 0;
+        self.boundary = None;
+        self.ghost.clear();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Bytes allocated for the ghost queue
+    pub(crate) fn allocated(&self) -> usize {
+        self.ghost.allocated()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Checks that the main entries lead the list, that the new entries are counted, that the boundary follows the
+    /// main entries and that the ghost queue agrees with its counts
+    pub(crate) fn validate<K, V>(&self, nodes: &Nodes<K, V>) -> Result<(), ValidationError> {
+        let main = nodes
+            .iter_from_head()
+            .take_while(|(_, node)| node.segment == Segment::Protected)
+            .count();
+        let last_main = nodes.iter_from_head().take(main).last().map(|(idx, _)| idx);
+        let misplaced = nodes.iter_from_head().skip(main).any(|(_, node)| node.segment == Segment::Protected);
+
+        ensure!(!misplaced, "main entry found behind the new entries");
+        ensure!(nodes.len() - main == self.in_len, "new entries number differently than counted");
+        ensure!(self.boundary == last_main, "boundary is not the last main entry");
+        self.ghost.validate()
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// How many entries and remembered hashes each queue of an [`EvictionMode::S3Fifo`] cache holds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct S3FifoQueues {
+    /// Entries in the small queue of new entries
+    pub small: usize,
+    /// Entries in the main queue
+    pub main: usize,
+    /// Key hashes in the ghost queue
+    pub ghost: usize,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// State for [`EvictionMode::S3Fifo`].  Both live queues share the recency list: the main entries come first, newest
+/// first, followed by the small queue, newest first.  Small entries are marked as [`Segment::Probation`] and main
+/// entries as [`Segment::Protected`].  Each entry's uses are counted since it last moved, up to [`MAX_USES`]
+pub(crate) struct S3Fifo {
+    /// Number of small entries at or above which eviction works through the small queue
+    small_capacity: usize,
+    small_len: usize,
+    /// The oldest main entry, behind which the small queue starts
+    boundary: Option<Handle>,
+    /// Hashes of the keys evicted from the small queue
+    ghost: GhostQueue,
+    /// Uses counted for each entry
+    uses: SideTable<u64>,
+}
+
+/// Uses counted per entry under [`EvictionMode::S3Fifo`]
+const MAX_USES: u64 = 3;
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl S3Fifo {
+    pub(crate) fn new(capacity: NonZeroUsize, small_percent: u8) -> Self {
+        let small_capacity = (capacity.get() * usize::from(small_percent.min(100)) / 100).max(1);
+
+        S3Fifo {
+            small_capacity,
+            small_len: 0,
+            boundary: None,
+            ghost: GhostQueue::new(capacity.get().saturating_sub(small_capacity)),
+            uses: SideTable::default(),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn queues<K, V>(&self, nodes: &Nodes<K, V>) -> S3FifoQueues {
+        S3FifoQueues {
+            small: self.small_len,
+            main: nodes.len() - self.small_len,
+            ghost: self.ghost.len(),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Places an entry that has just been pushed onto the front of the list: a key that the ghost queue remembers stays
+    /// there as the newest main entry, and any other becomes the newest small entry
+    pub(crate) fn admit<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        *self.uses.get_mut(nodes.slot(idx)) = 0;
+
+        if self.ghost.remembers(nodes.node(idx).hash) {
+            nodes.node_mut(idx).segment = Segment::Protected;
+            self.boundary = self.boundary.or(Some(idx));
+        } else {
+            self.small_len += 1;
+
+            if let Some(boundary) = self.boundary {
+                nodes.move_behind(idx, boundary);
+            }
+        }
+
+        self.ghost.trim();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Counts a use of an entry, leaving it in place
+    pub(crate) fn record<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        let uses = self.uses.get_mut(nodes.slot(idx));
+        *uses = (*uses + 1).min(MAX_USES);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the entry to evict, first moving the oldest entries that have been used: to the main queue from the
+    /// small queue, or back to the front of the main queue one use fewer.  Each move either shrinks the small queue or
+    /// spends a use, so the search ends
+    pub(crate) fn victim<K, V>(&mut self, nodes: &mut Nodes<K, V>) -> Option<Handle> {
+        loop {
+            let from_small = self.small_len > 0 && (self.small_len >= self.small_capacity || self.boundary.is_none());
+            let oldest = if from_small { nodes.tail() } else { self.boundary }?;
+            let uses = self.uses.get_mut(nodes.slot(oldest));
+
+            if *uses == 0 {
+                return Some(oldest);
+            }
+
+            if from_small {
+                *uses = 0;
+                nodes.node_mut(oldest).segment = Segment::Protected;
+                self.small_len -= 1;
+            } else {
+                *uses -= 1;
+                self.boundary = nodes.prev(oldest).or(Some(oldest));
+            }
+
+            nodes.move_to_front(oldest);
+            self.boundary = self.boundary.or(Some(oldest));
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Trims the ghost queue to its capacity after evictions that made room for nothing
+    pub(crate) fn trim_ghosts(&mut self) {
+        self.ghost.trim();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Remembers the key hash of an entry that is about to be evicted to make room for another, if it is a small entry
+    pub(crate) fn remember<K, V>(&mut self, nodes: &Nodes<K, V>, idx: Handle) {
+        let node = nodes.node(idx);
+
+        if node.segment != Segment::Protected {
+            self.ghost.push(node.hash);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Takes an entry out of the main queue, if it is there, and forgets its uses, before it is demoted to be the
+    /// oldest small entry
+    pub(crate) fn demote<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        if nodes.node(idx).segment == Segment::Protected {
+            self.forget(nodes, idx);
+            self.small_len += 1;
+        }
+
+        *self.uses.get_mut(nodes.slot(idx)) = 0;
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Takes an entry out of its queue before it leaves the cache
+    pub(crate) fn forget<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        match nodes.node(idx).segment {
+            Segment::Protected => {
+                nodes.node_mut(idx).segment = Segment::Probation;
+
+                if self.boundary == Some(idx) {
+                    self.boundary = nodes.prev(idx);
+                }
+            }
+            _ => self.small_len -= 1,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn clear(&mut self) {
+        self.small_len = 0;
+        self.boundary = None;
+        self.ghost.clear();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Bytes allocated for the ghost queue and the counts
+    pub(crate) fn allocated(&self) -> usize {
+        self.ghost.allocated() + self.uses.heap_size()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Checks that the main entries lead the list, that the small entries are counted, that the boundary follows the
+    /// main entries, that no entry counts too many uses and that the ghost queue agrees with its counts
+    pub(crate) fn validate<K, V>(&self, nodes: &Nodes<K, V>) -> Result<(), ValidationError> {
+        let main = nodes
+            .iter_from_head()
+            .take_while(|(_, node)| node.segment == Segment::Protected)
+            .count();
+        let last_main = nodes.iter_from_head().take(main).last().map(|(idx, _)| idx);
+        let misplaced = nodes.iter_from_head().skip(main).any(|(_, node)| node.segment == Segment::Protected);
+
+        ensure!(!misplaced, "main entry found behind the small queue");
+        ensure!(nodes.len() - main == self.small_len, "small entries number differently than counted");
+        ensure!(self.boundary == last_main, "boundary is not the oldest main entry");
+        let overused = nodes.iter_from_head().any(|(idx, _)| self.uses.get(nodes.slot(idx)) > MAX_USES);
+        ensure!(!overused, "entry counts too many uses");
+        self.ghost.validate()
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A first-in first-out queue of the key hashes of evicted entries, for [`EvictionMode::TwoQueue`] and
+/// [`EvictionMode::S3Fifo`]
+pub(crate) struct GhostQueue {
+    /// Most key hashes that the queue remembers
+    capacity: usize,
+    /// The hashes, oldest first
+    hashes: VecDeque<u64>,
+    /// How many times each hash appears in the queue
+    counts: HashMap<u64, u32>,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl GhostQueue {
+    pub(crate) fn new(capacity: usize) -> Self {
+        GhostQueue {
+            capacity,
+            hashes: VecDeque::new(),
+            counts: HashMap::new(),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn remembers(&self, hash: u64) -> bool {
+        self.counts.contains_key(&hash)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Remembers a hash as the newest.  The queue may exceed its capacity until it is next trimmed
+    pub(crate) fn push(&mut self, hash: u64) {
+        if self.capacity > 0 {
+            self.hashes.push_back(hash);
+            *self.counts.entry(hash).or_default() += 1;
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Forgets the oldest hashes until the queue fits its capacity
+    pub(crate) fn trim(&mut self) {
+        while self.hashes.len() > self.capacity
+            && let Some(oldest) = self.hashes.pop_front()
+            && let Some(count) = self.counts.get_mut(&oldest)
+        {
+            *count -= 1;
+
+            if *count == 0 {
+                self.counts.remove(&oldest);
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn clear(&mut self) {
+        self.hashes.clear();
+        self.counts.clear();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Bytes allocated for the hashes and their counts
+    pub(crate) fn allocated(&self) -> usize {
+        self.hashes.capacity() * mem::size_of::<u64>() + self.counts.allocation_size()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Checks that the queue fits its capacity and agrees with its counts
+    pub(crate) fn validate(&self) -> Result<(), ValidationError> {
+        let counted: u32 = self.counts.values().sum();
+
+        ensure!(self.hashes.len() <= self.capacity, "ghost queue holds more hashes than its capacity");
+        ensure!(counted as usize == self.hashes.len(), "ghost queue holds a different number of hashes than counted");
+
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// State for [`EvictionMode::TinyLfu`].  The window and the main region share the recency list: the window entries
+/// come first, from most to least recently used, followed by the main region laid out as for [`Slru`]
+pub(crate) struct TinyLfu {
+    window_capacity: usize,
+    window_len: usize,
+    /// The least recently used window entry, behind which the main region starts
+    window_boundary: Option<Handle>,
+    main: Slru,
+    sketch: FrequencySketch,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl TinyLfu {
+    pub(crate) fn new(capacity: NonZeroUsize, window_percent: u8) -> Self {
+        let window_capacity = (capacity.get() * usize::from(window_percent.min(100)) / 100).max(1);
+
+        TinyLfu {
+            window_capacity,
+            window_len: 0,
+            window_boundary: None,
+            main: Slru::new(capacity.get() - window_capacity, 80),
+            sketch: FrequencySketch::new(capacity.get()),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn sketch(&self) -> &FrequencySketch {
+        &self.sketch
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Counts a new entry, which has just been pushed onto the front of the list, as the most recently used window
+    /// entry.  If the window overflows, its least recently used entry moves to the main region unopposed, since the
+    /// cache had room for the new entry
+    pub(crate) fn admit<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        self.sketch.increment(nodes.node(idx).hash);
+        nodes.node_mut(idx).segment = Segment::Window;
+        self.window_len += 1;
+        self.window_boundary = self.window_boundary.or(Some(idx));
+
+        if self.window_len > self.window_capacity
+            && let Some(oldest) = self.window_boundary
+        {
+            self.leave_window(nodes, oldest);
+            self.main.admit(nodes, oldest);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Counts a use of an entry and moves it to the front of its region
+    pub(crate) fn record<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        self.sketch.increment(nodes.node(idx).hash);
+
+        if nodes.node(idx).segment == Segment::Window {
+            if self.window_boundary == Some(idx) {
+                self.window_boundary = window_before(nodes, idx).or(self.window_boundary);
+            }
+
+            nodes.move_to_front(idx);
+        } else {
+            self.main.record(nodes, idx, self.window_boundary);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the entry to evict to make room for a new one.  If the window is full, its least recently used entry is
+    /// a candidate for the main region: when the sketch estimates that it has been used more often than the main
+    /// region's victim, it moves to the front of probation and the victim is evicted instead of it
+    pub(crate) fn victim<K, V>(&mut self, nodes: &mut Nodes<K, V>) -> Option<Handle> {
+        let main_victim = nodes.tail().filter(|&idx| nodes.node(idx).segment != Segment::Window);
+
+        match (self.window_boundary, main_victim) {
+            (Some(candidate), Some(victim)) if self.window_len >= self.window_capacity => {
+                if self.admits(nodes, candidate, victim) {
+                    self.leave_window(nodes, candidate);
+                    self.main.admit(nodes, candidate);
+                    Some(victim)
+                } else {
+                    Some(candidate)
+                }
+            }
+            (_, Some(victim)) => Some(victim),
+            (candidate, None) => candidate,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the candidate has been used strictly more often than the victim.  Ties favour the victim, so a
+    /// key must prove itself before it displaces an entry of the main region
+    fn admits<K, V>(&self, nodes: &Nodes<K, V>, candidate: Handle, victim: Handle) -> bool {
+        self.sketch.frequency(nodes.node(candidate).hash) > self.sketch.frequency(nodes.node(victim).hash)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Takes an entry out of the window or the protected segment before it leaves the cache or is demoted
+    pub(crate) fn forget<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        match nodes.node(idx).segment {
+            Segment::Window => self.leave_window(nodes, idx),
+            _ => self.main.forget(nodes, idx),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Marks a window entry as probationary, leaving it in place
+    fn leave_window<K, V>(&mut self, nodes: &mut Nodes<K, V>, idx: Handle) {
+        nodes.node_mut(idx).segment = Segment::Probation;
+        self.window_len -= 1;
+
+        if self.window_boundary == Some(idx) {
+            self.window_boundary = window_before(nodes, idx);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn clear(&mut self) {
+        self.window_len = 0;
+        self.window_boundary = None;
+        self.main.clear();
+        self.sketch.clear();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Bytes allocated for the sketch
+    pub(crate) fn allocated(&self) -> usize {
+        self.sketch.heap_size()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Checks that the window entries lead the list, that they are counted and fit the window, and that the boundary
+    /// follows them, before checking the main region
+    pub(crate) fn validate<K, V>(&self, nodes: &Nodes<K, V>) -> Result<(), ValidationError> {
+        let window = nodes
+            .iter_from_head()
+            .take_while(|(_, node)| node.segment == Segment::Window)
+            .count();
+        let last_window = nodes.iter_from_head().take(window).last().map(|(idx, _)| idx);
+        let misplaced = nodes.iter_from_head().skip(window).any(|(_, node)| node.segment == Segment::Window);
+
+        ensure!(!misplaced, "window entry found behind the main region");
+        ensure!(window == self.window_len, "window holds a different number of entries than counted");
+        ensure!(window <= self.window_capacity, "window holds more entries than its capacity");
+        ensure!(self.window_boundary == last_window, "boundary is not the last window entry");
+        self.main.validate(nodes)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Returns the window entry in front of an entry, if there is one
+fn window_before<K, V>(nodes: &Nodes<K, V>, idx: Handle) -> Option<Handle> {
+    nodes.prev(idx).filter(|&prev| nodes.node(prev).segment == Segment::Window)
+}
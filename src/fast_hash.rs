@@ -0,0 +1,37 @@
+//! Convenience constructors for caches that use a fast, non-cryptographic hasher
+//!
+//! These hashers are not resistant to HashDoS attacks, so only use them when keys are not attacker controlled
+use crate::LruCache;
+use core::{hash::Hash, num::NonZeroUsize};
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache that hashes keys using `ahash`
+#[cfg(feature = "ahash")]
+pub type AHashLruCache<K, V> = LruCache<K, V, ahash::RandomState>;
+
+#[cfg(feature = "ahash")]
+impl<K, V> LruCache<K, V, ahash::RandomState>
+where
+    K: Eq + Hash,
+{
+    /// Creates a cache that hashes keys using `ahash`
+    pub fn with_ahash(capacity: NonZeroUsize) -> Self {
+        LruCache::with_hasher(capacity, ahash::RandomState::new())
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache that hashes keys using `fxhash`
+#[cfg(feature = "fxhash")]
+pub type FxLruCache<K, V> = LruCache<K, V, fxhash::FxBuildHasher>;
+
+#[cfg(feature = "fxhash")]
+impl<K, V> LruCache<K, V, fxhash::FxBuildHasher>
+where
+    K: Eq + Hash,
+{
+    /// Creates a cache that hashes keys using `fxhash`
+    pub fn with_fxhash(capacity: NonZeroUsize) -> Self {
+        LruCache::with_hasher(capacity, fxhash::FxBuildHasher::default())
+    }
+}
@@ -0,0 +1,137 @@
+//! Count-min sketch of how often keys have been used
+//!
+//! Each key hash selects one 4-bit counter in each of four rows, and the estimate for a key is the smallest of its four
+//! counters, so collisions can only inflate it.  The rows are interleaved: every word of the table holds four counters
+//! of each row, so one key's counters share a word per row.  Once the counters have been incremented ten times per
+//! entry of capacity, every counter is halved, so that keys which were popular long ago lose their advantage
+use alloc::{boxed::Box, vec};
+use core::mem;
+
+/// Multipliers that spread a hash over the table, one per row
+pub(crate) const SEEDS: [u64; 4] = [
+    0xc3a5_c85c_97cb_3127,
+    0xb492_b66f_be98_f273,
+    0x9ae1_6a3b_2f90_404f,
+    0xcbf2_9ce4_8422_2325,
+];
+
+/// Counters saturate at this value
+const MAX_COUNT: u64 = 15;
+
+/// Increments per entry of capacity between halvings
+const SAMPLE_FACTOR: usize = 10;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Counters of the frequency sketch that an [`EvictionMode::TinyLfu`](crate::EvictionMode::TinyLfu) cache consults to
+/// decide admission
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SketchStats {
+    /// Number of 4-bit counters across all four rows
+    pub counters: usize,
+    /// Increments since the counters were last halved
+    pub additions: usize,
+    /// Number of increments that halves every counter
+    pub sample_size: usize,
+    /// Number of times the counters have been halved
+    pub resets: u64,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+pub(crate) struct FrequencySketch {
+    /// Sixteen counters per word, four from each row.  The number of words is a power of two
+    table: Box<[u64]>,
+    additions: usize,
+    sample_size: usize,
+    resets: u64,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl FrequencySketch {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let words = capacity.checked_next_power_of_two().unwrap_or(1 << (usize::BITS - 1)).max(8);
+
+        FrequencySketch {
+            table: vec![0; words].into_boxed_slice(),
+            additions: 0,
+            sample_size: capacity.saturating_mul(SAMPLE_FACTOR).max(SAMPLE_FACTOR),
+            resets: 0,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// The word and bit offset of a hash's counter in each row
+    fn counters(&self, hash: u64) -> impl Iterator<Item = (usize, u32)> + use<> {
+        let mask = self.table.len() - 1;
+
+        SEEDS.into_iter().enumerate().map(move |(row, seed)| {
+            let spread = hash.wrapping_mul(seed);
+            let word = (spread >> 32) as usize & mask;
+            let counter = row as u32 * 4 + (spread >> 8) as u32 % 4;
+            (word, counter * 4)
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the estimated number of uses of a key since the counters were last halved, at most 15
+    pub(crate) fn frequency(&self, hash: u64) -> u8 {
+        self.counters(hash)
+            .map(|(word, shift)| (self.table[word] >> shift) & MAX_COUNT)
+            .min()
+            .unwrap_or(0) as u8
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Counts a use of a key, halving every counter once enough uses have been counted
+    pub(crate) fn increment(&mut self, hash: u64) {
+        let mut added = false;
+
+        for (word, shift) in self.counters(hash) {
+            if (self.table[word] >> shift) & MAX_COUNT < MAX_COUNT {
+                self.table[word] += 1 << shift;
+                added = true;
+            }
+        }
+
+        if added {
+            self.additions += 1;
+
+            if self.additions >= self.sample_size {
+                self.halve();
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Halves every counter, rounding down
+    fn halve(&mut self) {
+        for word in self.table.iter_mut() {
+            *word = (*word >> 1) & 0x7777_7777_7777_7777;
+        }
+
+        self.additions /= 2;
+        self.resets += 1;
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Forgets every use.  The number of halvings is kept
+    pub(crate) fn clear(&mut self) {
+        self.table.fill(0);
+        self.additions = 0;
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn stats(&self) -> SketchStats {
+        SketchStats {
+            counters: self.table.len() * 16,
+            additions: self.additions,
+            sample_size: self.sample_size,
+            resets: self.resets,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Bytes of heap memory held for the counters
+    pub(crate) fn heap_size(&self) -> usize {
+        mem::size_of_val(&*self.table)
+    }
+}
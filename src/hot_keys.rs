@@ -0,0 +1,119 @@
+//! Approximate access counts of the most used keys
+//!
+//! Every access is counted in a count-min sketch: each key hash selects one 32-bit counter in each of four rows, and
+//! the estimate for a key is the smallest of its counters.  Only the counters holding that smallest value are
+//! incremented (a conservative update), which keeps collisions from inflating the estimates more than they must.  The
+//! keys with the highest estimates are kept as candidates for the report, and a key joins them by overtaking the least
+//! counted candidate.
+//!
+//! Memory is fixed when the tracker is created: four rows of 16 counters per candidate, rounded up to a power of two,
+//! and the candidates themselves, however many distinct keys are used
+use crate::frequency_sketch::SEEDS;
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{cmp::Reverse, mem};
+use hashbrown::HashMap;
+
+/// Counters per row for each candidate
+const WIDTH_PER_CANDIDATE: usize = 16;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Tracks how often keys are used, set up through
+/// [`LruCacheBuilder::track_hot_keys`](crate::LruCacheBuilder::track_hot_keys)
+pub(crate) struct HotKeys<K> {
+    /// The rows of the sketch, one after another.  The width of a row is a power of two
+    counters: Box<[u32]>,
+    /// Candidates by key hash, with their estimates when last used
+    candidates: HashMap<u64, (K, u64)>,
+    limit: usize,
+    /// No candidate's estimate is below this, once there are `limit` of them
+    floor: u64,
+    clone_key: fn(&K) -> K,
+}
+
+impl<K> HotKeys<K> {
+    pub(crate) fn new(limit: usize, clone_key: fn(&K) -> K) -> Self {
+        let width = limit.saturating_mul(WIDTH_PER_CANDIDATE).checked_next_power_of_two().unwrap_or(1 << 30);
+
+        HotKeys {
+            counters: vec![0; width * SEEDS.len()].into_boxed_slice(),
+            candidates: HashMap::with_capacity(limit),
+            limit,
+            floor: 0,
+            clone_key,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// The position of a hash's counter in each row
+    fn positions(&self, hash: u64) -> [usize; SEEDS.len()] {
+        let width = self.counters.len() / SEEDS.len();
+        let mut row = 0;
+
+        SEEDS.map(|seed| {
+            let position = row * width + ((hash.wrapping_mul(seed) >> 32) as usize & (width - 1));
+            row += 1;
+            position
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Counts a use of a key, making it a candidate if it has now been used more often than the least used candidate
+    pub(crate) fn record(&mut self, hash: u64, key: &K) {
+        let positions = self.positions(hash);
+        let smallest = positions.iter().map(|&p| self.counters[p]).min().unwrap_or(0);
+        let estimate = smallest.saturating_add(1);
+
+        for position in positions {
+            self.counters[position] = self.counters[position].max(estimate);
+        }
+
+        let estimate = u64::from(estimate);
+
+        if let Some((_, count)) = self.candidates.get_mut(&hash) {
+            *count = estimate;
+        } else if self.candidates.len() < self.limit {
+            self.candidates.insert(hash, ((self.clone_key)(key), estimate));
+        } else if estimate > self.floor {
+            self.replace_coldest(hash, key, estimate);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Swaps the least used candidate for a key with the given estimate, if the key has been used more often, and
+    /// raises the floor to the least used candidate's estimate
+    fn replace_coldest(&mut self, hash: u64, key: &K, estimate: u64) {
+        let coldest = self.candidates.iter().min_by_key(|(_, (_, count))| *count).map(|(&h, &(_, count))| (h, count));
+
+        if let Some((coldest, count)) = coldest
+            && estimate > count
+        {
+            self.candidates.remove(&coldest);
+            self.candidates.insert(hash, ((self.clone_key)(key), estimate));
+        }
+
+        self.floor = self.candidates.values().map(|&(_, count)| count).min().unwrap_or(0);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns up to `n` candidates with their estimates, most used first
+    pub(crate) fn hottest(&self, n: usize) -> Vec<(&K, u64)> {
+        let mut hottest: Vec<_> = self.candidates.values().map(|(key, count)| (key, *count)).collect();
+        hottest.sort_unstable_by_key(|&(_, count)| Reverse(count));
+        hottest.truncate(n);
+        hottest
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Forgets every use
+    pub(crate) fn clear(&mut self) {
+        self.counters.fill(0);
+        self.candidates.clear();
+        self.floor = 0;
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Bytes of heap memory held for the counters and candidates, not counting memory owned by the keys
+    pub(crate) fn heap_size(&self) -> usize {
+        mem::size_of_val(&*self.counters) + self.candidates.allocation_size()
+    }
+}
@@ -0,0 +1,163 @@
+//! LRU cache for small, dense integer keys
+//!
+//! [`IndexedLruCache`] is keyed by `usize` values below a fixed key universe declared up front.  Instead of hashing
+//! keys it keeps a `Vec` with one slot per possible key, holding the handle of the key's entry, so a lookup is a
+//! single index.  Entries and their recency order are kept in the same storage as [`LruCache`](crate::LruCache).
+//!
+//! The key table costs one handle per possible key whether or not the key is cached, so the universe should be small
+//! enough to allocate in full, such as connection or file descriptor numbers
+use crate::{
+    CacheError,
+    nodes::{Handle, MAX_LEN, Nodes},
+};
+use alloc::{vec, vec::Vec};
+use core::{mem, num::NonZeroUsize};
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache keyed by integers in `0..universe`
+pub struct IndexedLruCache<V> {
+    capacity: NonZeroUsize,
+    /// The handle of each key's entry, indexed by key
+    slots: Vec<Option<Handle>>,
+    nodes: Nodes<usize, V>,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<V> IndexedLruCache<V> {
+    /// Creates a cache that holds at most `capacity` items, whose keys must be less than `universe`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capacity exceeds the number of entries the cache can address.  See the `wide-index` feature
+    pub fn new(capacity: NonZeroUsize, universe: usize) -> Self {
+        assert!(capacity.get() <= MAX_LEN, "capacity {capacity} exceeds the largest supported capacity of {MAX_LEN}");
+
+        IndexedLruCache {
+            capacity,
+            slots: vec![None; universe],
+            nodes: Nodes::with_capacity(capacity.get().min(universe)),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a reference to an item, making it the most recently used.  Returns `None` for keys outside the
+    /// universe
+    pub fn get(&mut self, key: usize) -> Option<&V> {
+        let idx = self.find(key)?;
+        self.nodes.move_to_front(idx);
+        Some(&self.nodes.node(idx).value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a copy of an item, making it the most recently used
+    pub fn get_cloned(&mut self, key: usize) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.get(key).cloned()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetch a reference to an item without changing its position in the recency order
+    pub fn peek(&self, key: usize) -> Option<&V> {
+        self.find(key).map(|idx| &self.nodes.node(idx).value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains the key. The item's position in the recency order is not changed
+    pub fn contains_key(&self, key: usize) -> bool {
+        self.find(key).is_some()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an item, returning its value if it was present
+    pub fn remove(&mut self, key: usize) -> Option<V> {
+        let idx = self.slots.get_mut(key)?.take()?;
+        Some(self.nodes.remove(idx).1)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item.
+    /// * If the item already exists, it returns the old value else it returns `None`
+    /// * If the addition of the new item exceeds the cache's capacity, the oldest item is evicted before the new item is
+    ///   added
+    /// * If the key is outside the universe, the cache is left unchanged and an error is returned
+    pub fn put(&mut self, key: usize, new_value: V) -> Result<Option<V>, CacheError> {
+        let universe = self.slots.len();
+        let slot = self.slots.get(key).ok_or(CacheError::KeyOutOfRange { key, universe })?;
+
+        if let Some(idx) = *slot {
+            self.nodes.move_to_front(idx);
+            return Ok(Some(mem::replace(&mut self.nodes.node_mut(idx).value, new_value)));
+        }
+
+        if self.nodes.len() >= self.capacity.get() {
+            self.pop_lru();
+        }
+
+        // The key doubles as its own hash, which nothing reads
+        self.slots[key] = Some(self.nodes.push_front(key as u64, key, new_value));
+        Ok(None)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the most recently used item
+    pub fn pop_mru(&mut self) -> Option<V> {
+        let idx = self.nodes.head()?;
+        Some(self.remove_at(idx))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the least recently used item
+    pub fn pop_lru(&mut self) -> Option<V> {
+        let idx = self.nodes.tail()?;
+        Some(self.remove_at(idx))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in the cache
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains no items
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 0
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the cache can hold
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.capacity
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of possible keys.  Every key must be less than this
+    pub fn universe(&self) -> usize {
+        self.slots.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes all items
+    pub fn clear(&mut self) {
+        self.slots.fill(None);
+        self.nodes.clear();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn find(&self, key: usize) -> Option<Handle> {
+        *self.slots.get(key)?
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn remove_at(&mut self, idx: Handle) -> V {
+        let (key, value) = self.nodes.remove(idx);
+        self.slots[key] = None;
+        value
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(all(test, feature = "std"))]
+mod unit_tests;
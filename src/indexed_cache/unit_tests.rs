@@ -0,0 +1,108 @@
+use super::*;
+use crate::LruCache;
+use std::{format, string::String};
+
+fn indexed_cache(capacity: usize, universe: usize) -> IndexedLruCache<u32> {
+    IndexedLruCache::new(NonZeroUsize::new(capacity).unwrap(), universe)
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_accept_keys_up_to_the_universe_boundary() -> Result<(), String> {
+    let mut c = indexed_cache(4, 100);
+
+    c.put(0, 1).map_err(|e| e.to_string())?;
+    c.put(99, 2).map_err(|e| e.to_string())?;
+
+    match (c.get(0).copied(), c.get(99).copied()) {
+        (Some(1), Some(2)) => Ok(()),
+        other => Err(format!("Expected both boundary keys to be cached. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_reject_keys_outside_the_universe() -> Result<(), String> {
+    let mut c = indexed_cache(4, 100);
+    c.put(5, 5).map_err(|e| e.to_string())?;
+
+    match c.put(100, 1) {
+        Err(CacheError::KeyOutOfRange { key: 100, universe: 100 }) => (),
+        other => return Err(format!("Expected KeyOutOfRange. Got {other:?}")),
+    }
+
+    match (c.get(100).copied(), c.peek(usize::MAX).copied(), c.contains_key(100), c.remove(100), c.len()) {
+        (None, None, false, None, 1) => Ok(()),
+        other => Err(format!("Expected out of range keys to be absent and the cache unchanged. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_hold_every_key_of_the_universe() -> Result<(), String> {
+    let mut c = indexed_cache(256, 256);
+
+    for key in 0..256 {
+        c.put(key, key as u32).map_err(|e| e.to_string())?;
+    }
+
+    // A full cache whose universe equals its capacity evicts nothing but still reorders
+    c.get(0);
+    c.put(1, 1_000).map_err(|e| e.to_string())?;
+
+    if let Some(missing) = (0..256).find(|&key| !c.contains_key(key)) {
+        return Err(format!("Key {missing} should still be cached"));
+    }
+
+    match (c.pop_mru(), c.pop_mru(), c.pop_lru(), c.len()) {
+        (Some(1_000), Some(0), Some(2), 253) => Ok(()),
+        other => Err(format!("Unexpected recency order {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_allow_an_empty_universe() -> Result<(), String> {
+    let mut c = indexed_cache(4, 0);
+
+    match (c.put(0, 1), c.get(0).copied(), c.is_empty()) {
+        (Err(CacheError::KeyOutOfRange { key: 0, universe: 0 }), None, true) => Ok(()),
+        other => Err(format!("Expected every key to be rejected. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+/// Drives the same operations through an `LruCache` and checks that both caches agree throughout
+#[test]
+fn should_match_generic_cache_under_churn() -> Result<(), String> {
+    let mut c = indexed_cache(7, 23);
+    let mut expected: LruCache<usize, u32> = LruCache::new(NonZeroUsize::new(7).unwrap());
+
+    for i in 0..2_000u32 {
+        let key = (i as usize * 31) % 23;
+
+        if c.put(key, i).map_err(|e| e.to_string())? != expected.put(key, i) {
+            return Err(format!("put({key}) diverged at step {i}"));
+        }
+
+        let probe = (i as usize * 17) % 23;
+        if c.get(probe) != expected.get(&probe) {
+            return Err(format!("get({probe}) diverged at step {i}"));
+        }
+
+        if i % 11 == 0 && c.remove(i as usize % 23) != expected.remove(&(i as usize % 23)) {
+            return Err(format!("remove({}) diverged at step {i}", i % 23));
+        }
+
+        if i % 13 == 0 && c.pop_lru() != expected.pop_lru() {
+            return Err(format!("pop_lru diverged at step {i}"));
+        }
+    }
+
+    c.clear();
+
+    match (c.is_empty(), (0..23).any(|key| c.contains_key(key))) {
+        (true, false) => Ok(()),
+        other => Err(format!("Expected clear to empty the cache. Got {other:?}")),
+    }
+}
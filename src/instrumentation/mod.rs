@@ -0,0 +1,87 @@
+//! Reporting through the [`metrics`](https://crates.io/crates/metrics) crate facade
+//!
+//! An instrumented cache reports the following metrics, each name being prefixed with the name given to
+//! [`LruCache::new_instrumented`]:
+//!
+//! | Metric | Type | Meaning
+//! |---|---|---
+//! | `{name}_hits` | counter | Lookups that found an item
+//! | `{name}_misses` | counter | Lookups that did not find an item
+//! | `{name}_insertions` | counter | Items added under a new key
+//! | `{name}_evictions` | counter | Items removed to make room for a new item
+//! | `{name}_entries` | gauge | Current number of items
+//!
+//! The shards of a [`ShardedLruCache::with_shards_instrumented`](crate::ShardedLruCache::with_shards_instrumented)
+//! each report the same metrics, told apart by a `shard` label holding the shard's index
+use crate::{DefaultHashBuilder, LruCache};
+use alloc::{format, string::ToString, vec::Vec};
+use core::{
+    hash::{BuildHasher, Hash},
+    num::NonZeroUsize,
+};
+use metrics::{Counter, Gauge, Label, counter, gauge};
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Handles to the metrics reported by an instrumented cache
+pub(crate) struct CacheMetrics {
+    pub(crate) hits: Counter,
+    pub(crate) misses: Counter,
+    pub(crate) insertions: Counter,
+    pub(crate) evictions: Counter,
+    pub(crate) entries: Gauge,
+}
+
+impl CacheMetrics {
+    /// Registers the cache's metrics with the currently installed recorder
+    pub(crate) fn register(name: &str) -> Self {
+        CacheMetrics::register_labelled(name, Vec::new())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Registers the metrics of one shard of a sharded cache, labelled with the shard's index
+    pub(crate) fn register_shard(name: &str, shard: usize) -> Self {
+        CacheMetrics::register_labelled(name, alloc::vec![Label::new("shard", shard.to_string())])
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn register_labelled(name: &str, labels: Vec<Label>) -> Self {
+        CacheMetrics {
+            hits: counter!(format!("{name}_hits"), labels.clone()),
+            misses: counter!(format!("{name}_misses"), labels.clone()),
+            insertions: counter!(format!("{name}_insertions"), labels.clone()),
+            evictions: counter!(format!("{name}_evictions"), labels.clone()),
+            entries: gauge!(format!("{name}_entries"), labels),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V> LruCache<K, V>
+where
+    K: Eq + Hash,
+{
+    /// Creates a cache that reports its activity through the `metrics` facade using metric names prefixed with `name`.
+    ///
+    /// The metrics are registered with the recorder installed at the time of construction
+    pub fn new_instrumented(capacity: NonZeroUsize, name: &str) -> Self {
+        LruCache::with_hasher_instrumented(capacity, DefaultHashBuilder::default(), name)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> LruCache<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Creates an instrumented cache that uses the given hash builder to hash keys
+    pub fn with_hasher_instrumented(capacity: NonZeroUsize, hash_builder: S, name: &str) -> Self {
+        let mut cache = LruCache::with_hasher(capacity, hash_builder);
+        cache.metrics = Some(CacheMetrics::register(name));
+        cache
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
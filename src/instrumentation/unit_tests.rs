@@ -0,0 +1,120 @@
+use super::*;
+use crate::test_utils::*;
+use metrics_util::{
+    CompositeKey, MetricKind,
+    debugging::{DebugValue, DebuggingRecorder},
+};
+use std::{collections::HashMap, string::String};
+
+fn metric_values(recorder: &DebuggingRecorder) -> HashMap<(MetricKind, String), DebugValue> {
+    recorder
+        .snapshotter()
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value): (CompositeKey, _, _, _)| ((key.kind(), key.key().name().to_string()), value))
+        .collect()
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_report_scripted_workload() -> Result<(), String> {
+    let recorder = DebuggingRecorder::new();
+
+    metrics::with_local_recorder(&recorder, || {
+        let mut c = LruCache::new_instrumented(NonZeroUsize::new(3).unwrap(), "user_cache");
+
+        for idx in 0..5 {
+            c.put(gen_item_key(idx), gen_item_value(idx as u32));
+        }
+
+        // Update an existing item: not an insertion
+        c.put(gen_item_key(4), gen_item_value(40));
+
+        c.get("item-4");
+        c.get("item-3");
+        c.get("item-0");
+        c.peek("item-2");
+        c.remove("item-2");
+    });
+
+    let values = metric_values(&recorder);
+    let expected = [
+        (MetricKind::Counter, "user_cache_hits", DebugValue::Counter(2)),
+        (MetricKind::Counter, "user_cache_misses", DebugValue::Counter(1)),
+        (MetricKind::Counter, "user_cache_insertions", DebugValue::Counter(5)),
+        (MetricKind::Counter, "user_cache_evictions", DebugValue::Counter(2)),
+    ];
+
+    for (kind, name, value) in expected {
+        match values.get(&(kind, name.to_string())) {
+            Some(actual) if *actual == value => (),
+            actual => return Err(format!("Expected {name} to be {value:?}. Got {actual:?} instead")),
+        }
+    }
+
+    match values.get(&(MetricKind::Gauge, String::from("user_cache_entries"))) {
+        Some(DebugValue::Gauge(len)) if len.into_inner() == 2.0 => Ok(()),
+        actual => Err(format!("Expected user_cache_entries to be 2. Got {actual:?} instead")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn uninstrumented_cache_should_report_nothing() -> Result<(), String> {
+    let recorder = DebuggingRecorder::new();
+
+    metrics::with_local_recorder(&recorder, || {
+        let mut c = LruCache::new(NonZeroUsize::new(3).unwrap());
+
+        for idx in 0..5 {
+            c.put(gen_item_key(idx), gen_item_value(idx as u32));
+        }
+
+        c.get("item-4");
+    });
+
+    if metric_values(&recorder).is_empty() {
+        Ok(())
+    } else {
+        Err(String::from("Cache constructed with new() should not report metrics"))
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn sharded_cache_should_label_each_shards_metrics() -> Result<(), String> {
+    let recorder = DebuggingRecorder::new();
+    let stats = metrics::with_local_recorder(&recorder, || {
+        let c = crate::ShardedLruCache::with_shards_instrumented(
+            NonZeroUsize::new(8).unwrap(),
+            NonZeroUsize::new(2).unwrap(),
+            "sessions",
+        );
+
+        for key in 0..20 {
+            c.put(key, key);
+            let _ = c.get(&(key / 2));
+        }
+
+        c.shard_stats()
+    });
+
+    let mut hits = [0; 2];
+
+    for (key, _, _, value) in recorder.snapshotter().snapshot().into_vec() {
+        let shard = key.key().labels().find(|label| label.key() == "shard").map(|label| label.value().to_string());
+
+        match (key.key().name(), shard.as_deref(), value) {
+            ("sessions_hits", Some("0"), DebugValue::Counter(n)) => hits[0] = n,
+            ("sessions_hits", Some("1"), DebugValue::Counter(n)) => hits[1] = n,
+            (_, Some("0" | "1"), _) => (),
+            other => return Err(format!("Expected every metric labelled with its shard. Got {other:?}")),
+        }
+    }
+
+    match hits == [stats[0].stats.hits, stats[1].stats.hits] {
+        true if hits.iter().sum::<u64>() > 0 => Ok(()),
+        _ => Err(format!("Expected each shard's hits reported under its label. Got {hits:?} for {stats:?}")),
+    }
+}
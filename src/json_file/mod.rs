@@ -0,0 +1,161 @@
+//! JSON files
+//!
+//! A cache is saved as its serde representation, its capacity followed by its entries from least to most recently used,
+//! encoded as JSON so that the file can be read or edited by hand.  [`LruCache::save_to_path`] writes to a temporary
+//! file in the same directory and then renames it over the target, so a crash part way through a save leaves the
+//! previous file intact rather than a truncated one.
+//!
+//! [`LruCache::load_from_path`] restores a cache with the capacity it is given rather than the one stored in the file.
+//! When the file holds more entries than that, only the most recently used are kept
+use crate::{LruCache, serde_impl::Repr};
+use core::{
+    fmt,
+    hash::{BuildHasher, Hash},
+    num::NonZeroUsize,
+};
+use hashbrown::HashSet;
+use serde::{Serialize, de::DeserializeOwned};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Errors that can occur while saving or loading a JSON file
+#[derive(Debug)]
+pub enum JsonFileError {
+    /// The file could not be created, read, written or renamed
+    Io(io::Error),
+    /// The file is not valid JSON or does not describe a valid cache
+    Corrupt(String),
+}
+
+impl fmt::Display for JsonFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonFileError::Io(e) => write!(f, "JSON file I/O failed: {e}"),
+            JsonFileError::Corrupt(reason) => write!(f, "JSON file is corrupt: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for JsonFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JsonFileError::Io(e) => Some(e),
+            JsonFileError::Corrupt(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for JsonFileError {
+    fn from(e: io::Error) -> Self {
+        JsonFileError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for JsonFileError {
+    fn from(e: serde_json::Error) -> Self {
+        if e.is_io() {
+            JsonFileError::Io(e.into())
+        } else {
+            JsonFileError::Corrupt(e.to_string())
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Names the temporary file a save writes before renaming it over `path`.  It sits in the same directory, so that the
+/// rename does not cross file systems, and carries the process id, so that two processes saving the same file do not
+/// write to the same temporary file
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{file_name}.{}.tmp", std::process::id()))
+}
+
+pub(crate) fn write_and_sync<T: Serialize>(path: &Path, value: &T) -> Result<(), JsonFileError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(&mut writer, value)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> LruCache<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    /// Saves the cache as a JSON file at `path`, replacing any file already there.  The entries are written to a
+    /// temporary file that is renamed over `path` once complete, so readers never see a partly written file
+    pub fn save_to_path(&self, path: impl AsRef<Path>) -> Result<(), JsonFileError>
+    where
+        K: Serialize,
+        V: Serialize,
+    {
+        let path = path.as_ref();
+        let temp = temp_path(path);
+
+        match write_and_sync(&temp, self).and_then(|_| Ok(fs::rename(&temp, path)?)) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                // The save has already failed, so a temporary file that cannot be removed is not worth reporting
+                let _ = fs::remove_file(&temp);
+                Err(e)
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Loads a cache with the given capacity from a JSON file written by [`save_to_path`](Self::save_to_path).  If the
+    /// file holds more entries than `capacity`, the least recently used are dropped.  A missing file is reported as
+    /// [`JsonFileError::Io`]
+    pub fn load_from_path(path: impl AsRef<Path>, capacity: NonZeroUsize) -> Result<Self, JsonFileError>
+    where
+        K: DeserializeOwned,
+        V: DeserializeOwned,
+    {
+        let reader = BufReader::new(File::open(path)?);
+        let repr: Repr<K, V> = serde_json::from_reader(reader)?;
+
+        {
+            let mut seen = HashSet::with_capacity(repr.entries.len());
+
+            if !repr.entries.iter().all(|(k, _)| seen.insert(k)) {
+                return Err(JsonFileError::Corrupt(String::from("duplicate key")));
+            }
+        }
+
+        // Entries run from least to most recently used, so the ones to drop come first
+        let surplus = repr.entries.len().saturating_sub(capacity.get());
+        let mut cache = LruCache::with_capacity_and_hasher(capacity, repr.entries.len() - surplus, S::default());
+
+        for (k, v) in repr.entries.into_iter().skip(surplus) {
+            cache.put(k, v);
+        }
+
+        Ok(cache)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// As [`load_from_path`](Self::load_from_path), except that a missing file gives an empty cache, as on the first run
+    /// of a program that saves its cache on exit
+    pub fn load_from_path_or_empty(path: impl AsRef<Path>, capacity: NonZeroUsize) -> Result<Self, JsonFileError>
+    where
+        K: DeserializeOwned,
+        V: DeserializeOwned,
+    {
+        match LruCache::load_from_path(path, capacity) {
+            Err(JsonFileError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+                Ok(LruCache::with_hasher(capacity, S::default()))
+            }
+            result => result,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,97 @@
+use super::*;
+use crate::test_utils::*;
+
+const CAPACITY: NonZeroUsize = NonZeroUsize::new(100).unwrap();
+
+fn non_trivial_cache() -> LruCache<String, String> {
+    let mut c = LruCache::new(CAPACITY);
+
+    // Overfill the cache and shuffle the recency order
+    for idx in 0..150 {
+        c.put(gen_item_key(idx), gen_item_value(idx as u32));
+    }
+
+    for idx in (60..140).step_by(3) {
+        c.get(gen_item_key(idx).as_str());
+    }
+
+    c
+}
+
+fn lru_first(c: &LruCache<String, String>) -> Vec<(String, String)> {
+    c.iter_lru_first().map(|(k, v)| (k.clone(), v.clone())).collect()
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_round_trip_through_json_file() -> Result<(), String> {
+    let dir = TempDir::new("json-round-trip");
+    let path = dir.path().join("cache.json");
+    let original = non_trivial_cache();
+
+    original.save_to_path(&path).map_err(|e| e.to_string())?;
+    let restored: LruCache<String, String> = LruCache::load_from_path(&path, CAPACITY).map_err(|e| e.to_string())?;
+
+    // Only the target file remains once the temporary file has been renamed
+    let files = dir.file_names().len();
+
+    match (lru_first(&restored) == lru_first(&original), files) {
+        (true, 1) => Ok(()),
+        other => Err(format!("Expected (true, 1). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_keep_most_recent_entries_when_loading_into_smaller_cache() -> Result<(), String> {
+    let dir = TempDir::new("json-truncate");
+    let path = dir.path().join("cache.json");
+    let original = non_trivial_cache();
+
+    original.save_to_path(&path).map_err(|e| e.to_string())?;
+    let restored: LruCache<String, String> =
+        LruCache::load_from_path(&path, NonZeroUsize::new(10).unwrap()).map_err(|e| e.to_string())?;
+
+    let expected = lru_first(&original).split_off(90);
+
+    match (restored.capacity().get(), lru_first(&restored)) {
+        (10, entries) if entries == expected => Ok(()),
+        other => Err(format!("Expected the 10 most recent entries. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_reject_corrupt_json_file() -> Result<(), String> {
+    let dir = TempDir::new("json-corrupt");
+    let truncated = dir.path().join("truncated.json");
+    let duplicated = dir.path().join("duplicated.json");
+
+    fs::write(&truncated, r#"{"capacity":10,"entries":[["a","1"],["b""#).map_err(|e| e.to_string())?;
+    fs::write(&duplicated, r#"{"capacity":10,"entries":[["a","1"],["a","2"]]}"#).map_err(|e| e.to_string())?;
+
+    let cap = NonZeroUsize::new(10).unwrap();
+
+    match (
+        LruCache::<String, String>::load_from_path(&truncated, cap).map(|c| c.len()),
+        LruCache::<String, String>::load_from_path(&duplicated, cap).map(|c| c.len()),
+    ) {
+        (Err(JsonFileError::Corrupt(_)), Err(JsonFileError::Corrupt(_))) => Ok(()),
+        other => Err(format!("Expected (Err(Corrupt), Err(Corrupt)). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_only_tolerate_missing_file_when_asked() -> Result<(), String> {
+    let dir = TempDir::new("json-missing");
+    let path = dir.path().join("missing.json");
+
+    match (
+        LruCache::<String, String>::load_from_path(&path, CAPACITY).map(|c| c.len()),
+        LruCache::<String, String>::load_from_path_or_empty(&path, CAPACITY).map(|c| c.len()),
+    ) {
+        (Err(JsonFileError::Io(e)), Ok(0)) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        other => Err(format!("Expected (Err(Io(NotFound)), Ok(0)). Got {other:?}")),
+    }
+}
@@ -0,0 +1,158 @@
+//! Maps key hashes to entry handles
+//!
+//! By default this is a single `HashTable`, which rehashes every entry at once whenever it has to grow.  With
+//! incremental growth enabled, a table that runs out of room is instead set aside as the draining table, a larger empty
+//! table takes its place, and each insertion moves a few entries across.  Lookups and removals consult both tables
+//! until the draining table is empty, so no single operation pays for rehashing the whole index.
+//!
+//! Entries are found for migration by walking the entry storage by position, since `HashTable` offers no way to resume
+//! iteration.  The walk wraps around until the draining table is empty, so entries that change position are still found
+use crate::nodes::{Handle, Nodes};
+use core::mem;
+use hashbrown::HashTable;
+
+/// Storage positions visited per insertion while a draining table remains
+const MIGRATION_STEPS: usize = 8;
+
+// ---------------------------------------------------------------------------------------------------------------------
+struct Draining {
+    table: HashTable<Handle>,
+    /// Next storage position to check for an entry still in `table`
+    cursor: usize,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+pub(crate) struct KeyIndex {
+    table: HashTable<Handle>,
+    draining: Option<Draining>,
+    incremental: bool,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl KeyIndex {
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        KeyIndex {
+            table: HashTable::with_capacity(capacity),
+            draining: None,
+            incremental: false,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Grows by migrating a few entries per insertion rather than rehashing every entry at once
+    pub(crate) fn set_incremental(&mut self, incremental: bool) {
+        self.incremental = incremental;
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn len(&self) -> usize {
+        self.table.len() + self.draining.as_ref().map_or(0, |d| d.table.len())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` while entries remain to be migrated out of a draining table
+    #[cfg(all(test, feature = "std"))]
+    pub(crate) fn is_draining(&self) -> bool {
+        self.draining.is_some()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn find(&self, hash: u64, mut eq: impl FnMut(&Handle) -> bool) -> Option<Handle> {
+        match self.table.find(hash, &mut eq) {
+            Some(&handle) => Some(handle),
+            None => self.draining.as_ref()?.table.find(hash, eq).copied(),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes and returns the first handle with the given hash that satisfies `eq`
+    pub(crate) fn remove(&mut self, hash: u64, mut eq: impl FnMut(&Handle) -> bool) -> Option<Handle> {
+        if let Ok(entry) = self.table.find_entry(hash, &mut eq) {
+            return Some(entry.remove().0);
+        }
+
+        let entry = self.draining.as_mut()?.table.find_entry(hash, eq).ok()?;
+        Some(entry.remove().0)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Adds the handle of an entry whose key is not yet present
+    pub(crate) fn insert<K, V>(&mut self, hash: u64, handle: Handle, nodes: &Nodes<K, V>) {
+        if self.incremental {
+            self.migrate(nodes, MIGRATION_STEPS);
+
+            // Inserting into a full table would make it rehash every entry
+            if self.table.len() == self.table.capacity() {
+                if self.draining.is_some() {
+                    self.migrate(nodes, usize::MAX);
+                }
+
+                self.start_draining();
+            }
+        }
+
+        self.table.insert_unique(hash, handle, |&h| nodes.node(h).hash);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Moves every entry remaining in the draining table across, if there is one
+    pub(crate) fn finish_draining<K, V>(&mut self, nodes: &Nodes<K, V>) {
+        self.migrate(nodes, usize::MAX);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn clear(&mut self) {
+        self.table.clear();
+        self.draining = None;
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Bytes of heap memory held by the tables
+    pub(crate) fn allocation_size(&self) -> usize {
+        self.table.allocation_size() + self.draining.as_ref().map_or(0, |d| d.table.allocation_size())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Replaces the full table with an empty one, keeping the full table around to be drained.  The new table doubles
+    /// the capacity unless most of the full table's room was taken up by tombstones
+    fn start_draining(&mut self) {
+        let capacity = self.table.capacity();
+        let new_capacity = if self.table.len() * 2 > capacity { 2 * capacity } else { capacity };
+        let table = mem::replace(&mut self.table, HashTable::with_capacity(new_capacity.max(4)));
+
+        self.draining = Some(Draining { table, cursor: 0 });
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Visits up to `steps` storage positions, moving each entry found there out of the draining table
+    fn migrate<K, V>(&mut self, nodes: &Nodes<K, V>, steps: usize) {
+        let Some(draining) = &mut self.draining else {
+            return;
+        };
+
+        for _ in 0..steps {
+            if draining.table.is_empty() {
+                break;
+            }
+
+            if draining.cursor >= nodes.positions() {
+                draining.cursor = 0;
+            }
+
+            if let Some(handle) = nodes.handle_at(draining.cursor) {
+                let hash = nodes.node(handle).hash;
+
+                if let Ok(entry) = draining.table.find_entry(hash, |&h| h == handle) {
+                    entry.remove();
+                    self.table.insert_unique(hash, handle, |&h| nodes.node(h).hash);
+                }
+            }
+
+            draining.cursor += 1;
+        }
+
+        if draining.table.is_empty() {
+            self.draining = None;
+        }
+    }
+}
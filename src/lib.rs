@@ -1,92 +1,2315 @@
-use std::{
-    collections::{HashMap, VecDeque},
-    hash::Hash,
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+use alloc::boxed::Box;
+use alloc::{
+    collections::{BinaryHeap, VecDeque},
+    sync::Arc,
+    vec::Vec,
+};
+use clock::{Expiry, NEVER};
+use error::ensure;
+use core::{
+    borrow::Borrow,
+    cmp::Reverse,
+    hash::{BuildHasher, Hash},
+    mem,
     num::NonZeroUsize,
 };
+#[cfg(feature = "std")]
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::SystemTime;
+use doorkeeper::Doorkeeper;
+use hashbrown::HashSet;
+use eviction::Policy;
+use key_index::KeyIndex;
+use listener::Event;
+use nodes::{Handle, MAX_LEN, Nodes, SideTable};
+use priority::Tiers;
+use read_buffer::ReadBuffer;
+
+pub use builder::{Callbacks, LruCacheBuilder};
+pub use clock::Clock;
+#[cfg(feature = "std")]
+pub use clock::SystemClock;
+#[cfg(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown"))]
+pub use clock::JsClock;
+#[cfg(feature = "std")]
+pub use entry_info::EntryInfo;
+pub use entry_info::EntryMeta;
+pub use error::{CacheError, ValidationError};
+pub use eviction::{EvictionMode, S3FifoQueues};
+pub use frequency_sketch::SketchStats;
+pub use listener::{CacheEventListener, RemovalCause};
+pub use priority::Priority;
+pub use stats::{CacheStats, DurationHistogram, LoadTimeStats};
+/// The map type returned by [`LruCache::into_parts`]
+pub use hashbrown::HashMap;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The hash builder used when none is specified.
+///
+/// With the `std` feature this is the standard library's SipHash-based `RandomState`, otherwise it is `hashbrown`'s
+/// default hasher
+#[cfg(feature = "std")]
+pub type DefaultHashBuilder = std::hash::RandomState;
+#[cfg(not(feature = "std"))]
+pub type DefaultHashBuilder = hashbrown::DefaultHashBuilder;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Called with each item that leaves the cache, set through [`LruCacheBuilder::on_removal`]
+type RemovalFn<K, V> = callback::Callback<fn(&mut callback::State, &K, &V, RemovalCause)>;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The outcome of inserting a new item: its handle and the item evicted to make room, or the new item handed back
+type Inserted<K, V> = Result<(Handle, Option<(K, V)>), (K, V)>;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Number of slots to reserve in the key index for `len` entries.
+///
+/// Removals leave tombstones in the index that are cleared by rehashing.  With at least twice as many slots as entries
+/// the rehash happens in place, so a full cache never has to grow its index to make room
+const fn index_capacity(len: usize) -> usize {
+    2 * (len + 1)
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Checks that a cache of the given capacity can address all of its entries
+pub(crate) fn check_capacity(capacity: NonZeroUsize) -> Result<(), CacheError> {
+    if capacity.get() <= MAX_LEN {
+        Ok(())
+    } else {
+        Err(CacheError::CapacityUnsupported {
+            capacity: capacity.get(),
+            max: MAX_LEN,
+        })
+    }
+}
 
 // ---------------------------------------------------------------------------------------------------------------------
 /// LRU cache
-pub struct LruCache<K, V> {
+///
+/// Keys are hashed using `S`, which defaults to [`DefaultHashBuilder`]
+pub struct LruCache<K, V, S = DefaultHashBuilder> {
     capacity: NonZeroUsize,
-    store: HashMap<K, V>,
-    order: VecDeque<K>,
+    hash_builder: S,
+    /// Maps each key to the handle of its entry
+    index: KeyIndex,
+    nodes: Nodes<K, V>,
+    /// Promotions deferred by reads, if enabled through [`LruCacheBuilder::read_buffer`]
+    read_buffer: Option<ReadBuffer>,
+    /// How entries are chosen for eviction, set through [`LruCacheBuilder::eviction_mode`]
+    policy: Policy,
+    /// Number of entries at each priority
+    tiers: Tiers,
+    /// Activity since the cache was created or [`reset_stats`](LruCache::reset_stats) was last called
+    stats: CacheStats,
+    /// Recent activity by interval, if enabled through [`LruCacheBuilder::windowed_stats`]
+    #[cfg(feature = "std")]
+    recent_stats: Option<Box<stats::StatsWindow>>,
+    /// Filter of cached keys checked before the index, if enabled through [`LruCacheBuilder::doorkeeper`]
+    doorkeeper: Option<Doorkeeper>,
+    /// Hits counted for each entry, if enabled through [`LruCacheBuilder::track_access`]
+    hits: Option<SideTable<u64>>,
+    /// Source of the current time, set once an item is given a time-to-live
+    clock: Option<Arc<dyn Clock>>,
+    /// The deadline and key hash of each entry that expires, soonest first.  A deadline that has since moved, or whose
+    /// entry has gone, is left in place and skipped when it comes up
+    deadlines: BinaryHeap<Reverse<(u64, u64)>>,
+    /// Time-to-live of items inserted without one of their own, if set through [`LruCacheBuilder::ttl`]
+    #[cfg(feature = "std")]
+    default_ttl: Option<Duration>,
+    /// How long an item may go unused before it expires, if set through [`LruCacheBuilder::time_to_idle`]
+    #[cfg(feature = "std")]
+    time_to_idle: Option<Duration>,
+    /// Whether using an item restarts its time-to-live, set through [`LruCacheBuilder::refresh_ttl_on_get`]
+    #[cfg(feature = "std")]
+    refresh_ttl_on_get: bool,
+    /// Present if items may be refreshed ahead of their deadlines, set through [`LruCacheBuilder::early_expiration`]
+    #[cfg(feature = "std")]
+    early_expiry: Option<early_expiry::EarlyExpiry>,
+    /// Present if time-to-live values are randomised, set through [`LruCacheBuilder::ttl_jitter`]
+    #[cfg(feature = "std")]
+    ttl_jitter: Option<clock::Jitter>,
+    /// Present if each item's time-to-live depends on its key and value, set through [`LruCacheBuilder::ttl_fn`]
+    #[cfg(feature = "std")]
+    ttl_fn: Option<clock::TtlFn<K, V>>,
+    /// Present if removed items are reported, set through [`LruCacheBuilder::on_removal`]
+    on_removal: Option<RemovalFn<K, V>>,
+    /// Present if the most used keys are tracked, set through [`LruCacheBuilder::track_hot_keys`]
+    hot_keys: Option<hot_keys::HotKeys<K>>,
+    /// Present if lifecycle events are reported, set through [`LruCacheBuilder::listener`]
+    listeners: Option<listener::Listeners<K, V>>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<instrumentation::CacheMetrics>,
+    /// Present if the cache emits `tracing` events, set through [`LruCacheBuilder::tracing`]
+    #[cfg(feature = "tracing")]
+    tracer: Option<tracer::Tracer<K>>,
+    /// Present while operations are written to a trace, set through [`LruCacheBuilder::record_trace`] or
+    /// [`set_trace_writer`](LruCache::set_trace_writer)
+    #[cfg(feature = "std")]
+    recorder: Option<Box<recorder::TraceRecorder<K>>>,
+    /// Formats the keys written to a trace, if set through [`LruCacheBuilder::trace_literal_keys`]
+    #[cfg(feature = "std")]
+    trace_keys: Option<recorder::KeyFmt<K>>,
 }
 
 // ---------------------------------------------------------------------------------------------------------------------
 impl<K, V> LruCache<K, V>
 where
-    K: Clone + Eq + Hash,
-    V: Clone,
+    K: Eq + Hash,
 {
     pub fn new(capacity: NonZeroUsize) -> Self {
-        LruCache {
+        LruCache::with_hasher(capacity, DefaultHashBuilder::default())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Creates a cache that measures its items' deadlines on `clock` instead of a [`SystemClock`]
+    #[cfg(feature = "std")]
+    pub fn with_clock(capacity: NonZeroUsize, clock: impl Clock + 'static) -> Self {
+        let mut cache = LruCache::new(capacity);
+        cache.clock = Some(Arc::new(clock));
+        cache
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> LruCache<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Creates a cache that uses the given hash builder to hash keys.  Storage for `capacity` items is allocated up front
+    pub fn with_hasher(capacity: NonZeroUsize, hash_builder: S) -> Self {
+        LruCache::with_capacity_and_hasher(capacity, capacity.get(), hash_builder)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Creates a cache that uses the given hash builder to hash keys, but only allocates storage for
+    /// `initial_capacity` items up front.  Useful for large caches that may never fill up
+    ///
+    /// # Panics
+    ///
+    /// Entries are addressed by `u32` indices, so the capacity must not exceed `u32::MAX` unless the `wide-index`
+    /// feature is enabled.  The `unsafe-fast` feature also lifts this limit.  Use
+    /// [`try_with_capacity_and_hasher`](Self::try_with_capacity_and_hasher) to get an error instead
+    pub fn with_capacity_and_hasher(capacity: NonZeroUsize, initial_capacity: usize, hash_builder: S) -> Self {
+        match LruCache::try_with_capacity_and_hasher(capacity, initial_capacity, hash_builder) {
+            Ok(cache) => cache,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Like [`with_capacity_and_hasher`](Self::with_capacity_and_hasher), but returns
+    /// [`CacheError::CapacityUnsupported`] instead of panicking when `capacity` is too large to address
+    pub fn try_with_capacity_and_hasher(
+        capacity: NonZeroUsize,
+        initial_capacity: usize,
+        hash_builder: S,
+    ) -> Result<Self, CacheError> {
+        check_capacity(capacity)?;
+        let initial_capacity = initial_capacity.min(capacity.get());
+
+        Ok(LruCache {
             capacity,
-            store: HashMap::with_capacity(capacity.get()),
-            order: VecDeque::with_capacity(capacity.get()),
+            hash_builder,
+            index: KeyIndex::with_capacity(index_capacity(initial_capacity)),
+            nodes: Nodes::with_capacity(initial_capacity),
+            read_buffer: None,
+            policy: Policy::Exact,
+            tiers: Tiers::default(),
+            stats: CacheStats::default(),
+            #[cfg(feature = "std")]
+            recent_stats: None,
+            doorkeeper: None,
+            hits: None,
+            deadlines: BinaryHeap::new(),
+            clock: None,
+            #[cfg(feature = "std")]
+            default_ttl: None,
+            #[cfg(feature = "std")]
+            time_to_idle: None,
+            #[cfg(feature = "std")]
+            refresh_ttl_on_get: false,
+            #[cfg(feature = "std")]
+            early_expiry: None,
+            #[cfg(feature = "std")]
+            ttl_jitter: None,
+            #[cfg(feature = "std")]
+            ttl_fn: None,
+            on_removal: None,
+            hot_keys: None,
+            listeners: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            #[cfg(feature = "tracing")]
+            tracer: None,
+            #[cfg(feature = "std")]
+            recorder: None,
+            #[cfg(feature = "std")]
+            trace_keys: None,
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a reference to an item, making it the most recently used
+    ///
+    /// The key may be any borrowed form of the cache's key type (e.g. `&str` for a `String` key).  If the cache has a
+    /// read buffer, the promotion is recorded there and only applied once the buffer fills up or before the next write.
+    /// Restarts the item's idle time, if the cache has a time-to-idle.
+    ///
+    /// If the cache was built with [`LruCacheBuilder::early_expiration`], an item close to its deadline may be
+    /// reported missing to one caller, so that it loads a fresh value while the others still find this one
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.find(key) {
+            Some(idx) if self.is_expired(idx) => {
+                self.apply_deferred_reads();
+                self.remove_at(idx, RemovalCause::Expired);
+                self.record_miss(key);
+                None
+            }
+            Some(idx) if self.refreshes_early(idx) => {
+                self.record_miss(key);
+                None
+            }
+            Some(idx) => {
+                self.record_hit(idx);
+
+                let deferred = match &self.read_buffer {
+                    Some(buffer) => buffer.record(self.nodes.node(idx).hash),
+                    None => false,
+                };
+
+                if deferred {
+                    self.record_access(idx);
+                } else {
+                    self.apply_deferred_reads();
+                    self.touch(idx);
+                }
+
+                Some(&self.nodes.node(idx).value)
+            }
+            None => {
+                self.record_miss(key);
+                None
+            }
         }
     }
 
     // -----------------------------------------------------------------------------------------------------------------
-    /// Attempt to fetch an item
-    pub fn get(&mut self, key: &K) -> Option<V> {
-        if let Some(value) = self.store.get(key).cloned() {
-            // Update key's order to MRU
-            if let Some(pos) = self.order.iter().position(|k| *k == *key) {
-                self.order.remove(pos);
+    /// Attempt to fetch a reference to an item through a shared borrow, so that readers can share the cache behind a
+    /// read lock.
+    ///
+    /// The promotion is recorded in the cache's read buffer and applied before the next write, which is also when the
+    /// item's idle time restarts.  If the cache has no read buffer, or the buffer is full, the item's position in the
+    /// recency order and its idle time are not changed
+    pub fn get_deferred<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_recorded(key).map(|(value, _)| value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetches an item through a shared borrow, recording its promotion in the read buffer.  Also returns whether the
+    /// promotion was recorded, which it is not if the cache has no read buffer or the buffer is full
+    pub(crate) fn get_recorded<Q>(&self, key: &Q) -> Option<(&V, bool)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.find_live(key) {
+            Some(idx) => {
+                self.report_hit(idx);
+                let node = self.nodes.node(idx);
+
+                if let Some(listeners) = &self.listeners {
+                    listeners.notify_now(Event::Hit, &node.key, &node.value);
+                }
+
+                let recorded = self.read_buffer.as_ref().is_some_and(|buffer| buffer.record(node.hash));
+
+                Some((&node.value, recorded))
             }
-            self.order.push_front(key.clone());
-            Some(value)
-        } else {
-            None
+            None => {
+                self.report_miss(key);
+                None
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a copy of an item, making it the most recently used
+    pub fn get_cloned<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.get(key).cloned()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetches an item like [`get`](Self::get), except that an item that expired less than `grace` ago is returned
+    /// rather than removed, along with how long ago it expired.  A stale item counts as a hit but is not promoted
+    #[cfg(feature = "std")]
+    pub(crate) fn get_or_stale<Q>(&mut self, key: &Q, grace: Duration) -> Option<(&V, Option<Duration>)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if let Some(idx) = self.find(key)
+            && let Some(staleness) = self.staleness(idx, grace)
+        {
+            self.record_hit(idx);
+            return Some((&self.nodes.node(idx).value, Some(staleness)));
+        }
+
+        self.get(key).map(|value| (value, None))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache holds the key, either live or expired less than `grace` ago
+    #[cfg(feature = "std")]
+    pub(crate) fn is_retained<Q>(&self, key: &Q, grace: Duration) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some_and(|idx| !self.is_expired(idx) || self.staleness(idx, grace).is_some())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// How long ago an entry expired, if it has and did so less than `grace` ago
+    #[cfg(feature = "std")]
+    fn staleness(&self, idx: Handle, grace: Duration) -> Option<Duration> {
+        if !self.is_expired(idx) {
+            return None;
+        }
+
+        let node = self.nodes.node(idx);
+        let since = self.now()?.saturating_sub(node.expires_at.min(node.idle_at));
+        (since < clock::nanos(grace)).then(|| Duration::from_nanos(since))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetch a reference to an item without changing its position in the recency order
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find_live(key).map(|idx| &self.nodes.node(idx).value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains the key. The item's position in the recency order is not changed
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find_live(key).is_some()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns when an item was stored and last used, and when it expires, without changing its position in the
+    /// recency order.
+    ///
+    /// Times are only kept by a cache that has a clock: one given through [`with_clock`](Self::with_clock) or
+    /// [`LruCacheBuilder::clock`], or the [`SystemClock`] started by the first time-to-live or time-to-idle.  Returns
+    /// `None` if the cache has no clock yet.  Items stored before the clock started report it as their insertion time
+    #[cfg(feature = "std")]
+    pub fn entry_info<Q>(&self, key: &Q) -> Option<EntryInfo>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let now = self.now()?;
+        let idx = self.find_live(key)?;
+        let node = self.nodes.node(idx);
+        let expires_at = node.expires_at.min(node.idle_at);
+
+        Some(EntryInfo {
+            now,
+            inserted_at: node.inserted_at,
+            last_accessed: node.accessed_at,
+            expires_at: (expires_at != NEVER).then_some(expires_at),
+            hits: self.hits(idx),
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Iterates over the items, from most to least recently used, together with how much each has been used.  Expired
+    /// items are skipped, and promotions still held in the read buffer are not reflected in the order.
+    ///
+    /// Hits are only counted if the cache was built with [`LruCacheBuilder::track_access`]
+    pub fn iter_with_metadata(&self) -> impl Iterator<Item = (&K, &V, EntryMeta)> {
+        let now = self.now();
+
+        self.nodes
+            .iter_from_head()
+            .filter(move |(_, node)| !expired(node.expires_at.min(node.idle_at), now))
+            .map(|(idx, node)| {
+                let meta = EntryMeta {
+                    hits: self.hits(idx).unwrap_or(0),
+                    last_accessed: node.accessed_at,
+                };
+
+                (&node.key, &node.value, meta)
+            })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Records how long an item's value took to load, which [`LruCacheBuilder::early_expiration`] uses to decide how
+    /// early to refresh it.  [`ConcurrentLruCache::get_or_insert_with`] records this for the values it loads.  Returns
+    /// `false` if the key is not found
+    #[cfg(feature = "std")]
+    pub fn set_load_cost<Q>(&mut self, key: &Q, load_cost: Duration) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.find_live(key) {
+            Some(idx) => {
+                self.nodes.node_mut(idx).load_cost = clock::nanos(load_cost).max(1);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Starts timing a load of a missing value
+    #[cfg(feature = "std")]
+    pub(crate) fn load_started(&self) -> clock::Stopwatch {
+        match self.now() {
+            Some(now) => clock::Stopwatch::Shared(now),
+            None => clock::Stopwatch::Own(clock::DefaultClock::new()),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Counts a load timed by `stopwatch`, as returned by [`load_started`](Self::load_started), in the load time
+    /// statistics, and records it as the item's load cost if the cache refreshes items early
+    #[cfg(feature = "std")]
+    pub(crate) fn load_finished(&mut self, key: &K, stopwatch: clock::Stopwatch) {
+        let elapsed = match stopwatch {
+            clock::Stopwatch::Shared(started) => self.now().map_or(0, |now| now.saturating_sub(started)),
+            clock::Stopwatch::Own(clock) => clock.now(),
+        };
+        self.stats.load_time.record(elapsed);
+
+        #[cfg(feature = "tracing")]
+        if let Some(tracer) = &self.tracer {
+            tracer.loaded(key, Duration::from_nanos(elapsed));
+        }
+
+        if self.early_expiry.is_some() {
+            self.set_load_cost(key, Duration::from_nanos(elapsed));
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the item's position in the recency order, where `0` is the most recently used item.  Expired items are
+    /// not ranked and are skipped when counting the items ahead
+    pub fn recency_rank<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.find_live(key)?;
+        self.nodes.iter_from_head().filter(|&(i, _)| !self.is_expired(i)).position(|(i, _)| i == idx)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Marks an item as the most recently used without fetching it.  Returns `false` if the key is not found or the
+    /// item has expired.  Under [`EvictionMode::Fifo`] the item keeps its place, and only its idle time restarts
+    pub fn promote<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.apply_deferred_reads();
+
+        match self.find_live(key) {
+            Some(idx) => {
+                self.touch(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Marks an item as the least recently used, making it the next eviction candidate.  Returns `false` if the key is
+    /// not found
+    pub fn demote<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.apply_deferred_reads();
+
+        match self.find(key) {
+            Some(idx) => {
+                match &mut self.policy {
+                    Policy::Lfu(lfu) => lfu.reset(&mut self.nodes, idx),
+                    Policy::Slru(slru) => slru.forget(&mut self.nodes, idx),
+                    Policy::TwoQueue(two_queue) => two_queue.demote(&mut self.nodes, idx),
+                    Policy::TinyLfu(tiny_lfu) => tiny_lfu.forget(&mut self.nodes, idx),
+                    Policy::CostAware(greedy_dual) => greedy_dual.reset(&mut self.nodes, idx),
+                    Policy::S3Fifo(s3_fifo) => s3_fifo.demote(&mut self.nodes, idx),
+                    _ => {}
+                }
+
+                self.nodes.move_to_back(idx);
+                self.nodes.node_mut(idx).last_access = 0;
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Exempts an item from eviction until it is unpinned.  When the eviction mode chooses a pinned item, the least
+    /// recently used item that is not pinned is evicted instead.  A pinned item still counts towards `len`, and can
+    /// still be removed, popped, replaced or expire.  Returns `false` if the key is not found or the item has expired
+    pub fn pin<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.set_pinned(key, true)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Makes a pinned item evictable again.  Returns `false` if the key is not found or the item has expired
+    pub fn unpin<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.set_pinned(key, false)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the key is present and pinned
+    pub fn is_pinned<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find_live(key).is_some_and(|idx| self.nodes.node(idx).pinned)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an item, returning its value if it was present
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.pop_entry(key).map(|(_, value)| value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an item, returning both the stored key and its value if it was present
+    pub fn pop_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let entry = self.take_entry(key, RemovalCause::Explicit);
+
+        #[cfg(feature = "std")]
+        if self.recorder.is_some() {
+            let hash = self.hash_builder.hash_one(key);
+            self.trace(TraceOp::Remove, entry.is_some(), hash, entry.as_ref().map(|(key, _)| key));
+        }
+
+        entry
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an item for the given cause, returning both the stored key and its value if it was present and had not
+    /// expired
+    fn take_entry<Q>(&mut self, key: &Q, cause: RemovalCause) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.apply_deferred_reads();
+
+        let hash = self.hash_builder.hash_one(key);
+
+        if !self.doorkeeper.as_ref().is_none_or(|filter| filter.admits(hash)) {
+            return None;
         }
+
+        let nodes = &self.nodes;
+        let idx = self.index.remove(hash, |&i| {
+            let node = nodes.node(i);
+            node.hash == hash && node.key.borrow() == key
+        })?;
+
+        let expired = self.is_expired(idx);
+        let entry = self.take_out(idx, cause).filter(|_| !expired)?;
+        self.record_removal();
+        Some(entry)
     }
 
     // -----------------------------------------------------------------------------------------------------------------
-    /// Removes the most recently used item
+    /// Removes the most recently used item.  Expired items found along the way are discarded
     pub fn pop_mru(&mut self) -> Option<V> {
-        if let Some(popped_key) = self.order.pop_front() {
-            self.store.remove(&popped_key)
-        } else {
-            None
+        self.apply_deferred_reads();
+
+        loop {
+            let idx = self.nodes.head()?;
+            let expired = self.is_expired(idx);
+            let entry = self.remove_at(idx, RemovalCause::Explicit);
+
+            if !expired {
+                self.record_removal();
+                return entry.map(|(_, value)| value);
+            }
         }
     }
 
     // -----------------------------------------------------------------------------------------------------------------
-    /// Removes the least recently used item
+    /// Removes the item that eviction would choose next, which is the least recently used in the default
+    /// [`EvictionMode::Exact`].  Expired items found along the way are discarded.
+    ///
+    /// Under the other eviction modes this is the mode's next victim, which may be a sampled, random or least
+    /// frequently used item rather than the least recently used one.  [`EvictionMode::Mru`] is the exception: it evicts
+    /// the most recently used item, but `pop_lru` still removes the least recently used.
+    ///
+    /// The item is drawn from every tier alike: unlike eviction, `pop_lru` does not prefer lower
+    /// [priorities](Self::put_with_priority), nor does it pass over pinned items
     pub fn pop_lru(&mut self) -> Option<V> {
-        if let Some(popped_key) = self.order.pop_back() {
-            self.store.remove(&popped_key)
-        } else {
-            None
+        self.pop_lru_entry().map(|(_, value)| value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Evicts items, chosen as they would be to make room for a new one, until no more than `len` remain.  Stops early
+    /// if only pinned or guarded items are left.  Returns the number evicted, which does not include expired items
+    /// removed along the way
+    pub fn evict_to(&mut self, len: usize) -> usize {
+        let mut evicted = 0;
+
+        while self.nodes.len() > len {
+            match self.evict_lru_entry() {
+                Ok(Some(_)) => evicted += 1,
+                Ok(None) => {}
+                Err(_) => break,
+            }
         }
+
+        match &mut self.policy {
+            Policy::TwoQueue(two_queue) => two_queue.trim_ghosts(),
+            Policy::S3Fifo(s3_fifo) => s3_fifo.trim_ghosts(),
+            _ => {}
+        }
+
+        self.check_invariants();
+        evicted
     }
 
     // -----------------------------------------------------------------------------------------------------------------
     /// Inserts a new item.
     /// * If the item already exists, it returns the old value else it returns `None`
     /// * If the addition of the new item exceeds the cache's capacity, the oldest item is evicted before the new item is
-    /// added
+    ///   added
+    /// * If the cache is full and every item is [pinned](Self::pin), the new item is dropped.
+    ///   [`try_put`](Self::try_put) reports this as an error
+    ///
+    /// The item expires after the time-to-live chosen by [`LruCacheBuilder::ttl_fn`] or the default set through
+    /// [`LruCacheBuilder::ttl`], and otherwise never, even if it replaces one that had a time-to-live
     pub fn put(&mut self, key: K, new_value: V) -> Option<V> {
-        if self.store.contains_key(&key) {
-            // Remove existing item's old position in order
-            if let Some(pos) = self.order.iter().position(|k| *k == key) {
-                self.order.remove(pos);
-            }
-        } else {
-            if self.store.len() >= self.capacity.get() {
-                if let Some(oldest) = self.order.pop_back() {
-                    self.store.remove(&oldest);
-                }
+        let expiry = self.default_expiry(&key, &new_value);
+        self.put_expiring(key, new_value, expiry).map_or(None, |(_, old_value)| old_value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item like [`put`](Self::put), recording what it would cost to recompute its value.  Under
+    /// [`EvictionMode::CostAware`] costlier items outlive cheaper ones that have been used as recently, while other
+    /// eviction modes ignore the cost.  Items inserted through `put` cost one
+    pub fn put_with_cost(&mut self, key: K, new_value: V, cost: u64) -> Option<V> {
+        let expiry = self.default_expiry(&key, &new_value);
+
+        // Every item was pinned and the new one was turned away
+        let Ok((idx, old_value)) = self.put_expiring(key, new_value, expiry) else {
+            return None;
+        };
+
+        if let Policy::CostAware(greedy_dual) = &mut self.policy {
+            greedy_dual.set_cost(&mut self.nodes, idx, cost);
+        }
+
+        old_value
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item like [`put`](Self::put), but fails with [`CacheError::AllPinned`] instead of dropping the
+    /// item when the cache is full and every item is pinned
+    pub fn try_put(&mut self, key: K, new_value: V) -> Result<Option<V>, CacheError> {
+        let expiry = self.default_expiry(&key, &new_value);
+
+        match self.put_expiring(key, new_value, expiry) {
+            Ok((_, old_value)) => Ok(old_value),
+            Err(_) => Err(CacheError::AllPinned {
+                capacity: self.capacity.get(),
+            }),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item like [`put`](Self::put), in the given tier.  Eviction takes items from the lowest tier that
+    /// has any, choosing between them as the eviction mode would, so that [`Priority::Critical`] items are evicted only
+    /// once no others are left.  Items inserted through `put` are [`Priority::Normal`], and replacing an item's value
+    /// through `put` keeps its priority
+    pub fn put_with_priority(&mut self, key: K, new_value: V, priority: Priority) -> Option<V> {
+        let expiry = self.default_expiry(&key, &new_value);
+        let (idx, old_value) = self.put_expiring(key, new_value, expiry).ok()?;
+
+        self.set_tier(idx, priority);
+        old_value
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Moves an item to another tier without changing its position in the recency order.  Returns `false` if the key
+    /// is not found or the item has expired
+    pub fn set_priority<Q>(&mut self, key: &Q, priority: Priority) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.find_live(key) {
+            Some(idx) => {
+                self.set_tier(idx, priority);
+                true
             }
+            None => false,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns an item's priority, or `None` if the key is not found or the item has expired
+    pub fn priority<Q>(&self, key: &Q) -> Option<Priority>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find_live(key).map(|idx| self.nodes.node(idx).priority)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item that expires once `ttl` has passed, returning the old value if the key was present and had
+    /// not expired.  An expired item is treated as absent by every lookup, and is removed when a lookup that can
+    /// change the cache comes across it.  If the cache was built with [`LruCacheBuilder::refresh_ttl_on_get`], each use
+    /// of the item restarts its time-to-live
+    #[cfg(feature = "std")]
+    pub fn put_with_ttl(&mut self, key: K, new_value: V, ttl: Duration) -> Option<V> {
+        let expiry = self.expiry_after(ttl);
+        self.put_expiring(key, new_value, expiry).map_or(None, |(_, old_value)| old_value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item that expires at `deadline`, returning the old value if the key was present and had not
+    /// expired.  The deadline is converted to the cache's clock with [`Clock::reading_at`], so a delay between
+    /// learning the deadline and inserting the item does not extend its life, and no other clock is consulted.
+    ///
+    /// If the deadline has already passed, the item is not stored and nothing is evicted to make room for it.  Any
+    /// value it would have replaced is still removed and returned.  Using the item never moves the deadline, even if
+    /// the cache refreshes time-to-live on use, though a time-to-idle can still expire the item sooner
+    #[cfg(feature = "std")]
+    pub fn put_with_expiry_at(&mut self, key: K, new_value: V, deadline: SystemTime) -> Option<V> {
+        let now = self.clock_now();
+        let at = self.clock.as_ref().map_or(now, |clock| clock.reading_at(deadline));
+
+        if at <= now {
+            return self.take_entry(&key, RemovalCause::Replaced).map(|(_, old_value)| old_value);
+        }
+
+        // Not jittered: the upstream chose this deadline
+        let expiry = Expiry {
+            at: at.min(NEVER - 1),
+            ttl: NEVER,
         };
 
-        self.order.push_front(key.clone());
-        self.store.insert(key, new_value)
+        self.put_expiring(key, new_value, expiry).map_or(None, |(_, old_value)| old_value)
     }
-}
 
-// ---------------------------------------------------------------------------------------------------------------------
-pub mod test_utils;
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item, returning the item it displaced.
+    /// * If the key already exists, its value is replaced and the key and old value are returned, unless the old value
+    ///   had expired
+    /// * If the cache is full, the least recently used item is evicted and returned, unless it had expired
+    /// * If the cache is full and every item is pinned, the new item is dropped and `None` is returned
+    ///
+    /// Like [`put`](Self::put), the item expires after the default time-to-live, if there is one
+    pub fn push(&mut self, key: K, new_value: V) -> Option<(K, V)> {
+        self.apply_deferred_reads();
+        let hash = self.hash_builder.hash_one(&key);
+        let expiry = self.default_expiry(&key, &new_value);
 
-#[cfg(test)]
-mod unit_tests;
+        if let Some(idx) = self.find_hashed(hash, &key) {
+            let expired = self.is_expired(idx);
+            let old_value = self.replace_value(idx, new_value, expiry, expired);
+            return (!expired).then_some((key, old_value));
+        }
+
+        self.insert_new(hash, key, new_value, expiry).map_or(None, |(_, evicted)| evicted)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts or replaces an item with the given deadline, returning its handle and the old value unless it had
+    /// expired.  Hands the item back if the cache is full of pinned items
+    fn put_expiring(&mut self, key: K, new_value: V, expiry: Expiry) -> Result<(Handle, Option<V>), (K, V)> {
+        self.apply_deferred_reads();
+        let hash = self.hash_builder.hash_one(&key);
+
+        if let Some(idx) = self.find_hashed(hash, &key) {
+            let expired = self.is_expired(idx);
+            let old_value = self.replace_value(idx, new_value, expiry, expired);
+            return Ok((idx, (!expired).then_some(old_value)));
+        }
+
+        self.insert_new(hash, key, new_value, expiry).map(|(idx, _)| (idx, None))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in the cache, including expired items that have not been removed yet
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains no items
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 0
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the cache can hold
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.capacity
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Estimates the bytes of heap memory held by the cache's own structures: the entries, the key index and any read
+    /// buffer.  Heap memory owned by the keys and values themselves, such as the contents of a `String`, is not counted
+    pub fn memory_usage(&self) -> usize {
+        let read_buffer = self.read_buffer.as_ref().map_or(0, ReadBuffer::heap_size);
+        let doorkeeper = self.doorkeeper.as_ref().map_or(0, Doorkeeper::heap_size);
+        let deadlines = self.deadlines.capacity() * mem::size_of::<Reverse<(u64, u64)>>();
+        let hot_keys = self.hot_keys.as_ref().map_or(0, hot_keys::HotKeys::heap_size);
+        let hits = self.hits.as_ref().map_or(0, SideTable::heap_size);
+        let policy = match &self.policy {
+            Policy::Lfu(lfu) => lfu.allocated(),
+            Policy::TwoQueue(two_queue) => two_queue.allocated(),
+            Policy::TinyLfu(tiny_lfu) => tiny_lfu.allocated(),
+            Policy::CostAware(greedy_dual) => greedy_dual.allocated(),
+            Policy::S3Fifo(s3_fifo) => s3_fifo.allocated(),
+            _ => 0,
+        };
+        let buffers = read_buffer + doorkeeper + deadlines + hot_keys + hits;
+        self.nodes.heap_size() + self.index.allocation_size() + buffers + policy
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the activity counted since the cache was created or [`reset_stats`](Self::reset_stats) was last called.
+    /// Lookups are counted by [`get`](Self::get) and the methods built on it, while [`peek`](Self::peek),
+    /// [`contains_key`](Self::contains_key) and [`get_deferred`](Self::get_deferred), which only borrows the cache, go
+    /// uncounted.  Expired items that are found and removed count as misses, not removals.  Load times are counted by
+    /// the caches built on this one that load missing values, such as [`ConcurrentLruCache`]
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.len() as u64,
+            ..self.stats
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fraction of counted lookups that found an item, or `0.0` if there have been none.  See [`stats`](Self::stats)
+    pub fn hit_ratio(&self) -> f64 {
+        self.stats.hit_ratio()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Sets every statistic back to zero, including those counted by [`stats_window`](Self::stats_window)
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+
+        #[cfg(feature = "std")]
+        if let Some(recent_stats) = &mut self.recent_stats {
+            recent_stats.clear();
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the activity counted within `window` of now, if the cache was built with
+    /// [`LruCacheBuilder::windowed_stats`], for example to follow the hit ratio after a change in traffic.
+    ///
+    /// The window is rounded up to a whole number of the intervals that activity is counted in, the last of which is
+    /// the interval still under way, and is limited to the 60 most recent intervals.  Only hits, misses, insertions,
+    /// updates, evictions and removals are counted by interval; the other statistics are left at zero, apart from
+    /// `entries`.  Without windowed statistics, every count is zero
+    #[cfg(feature = "std")]
+    pub fn stats_window(&self, window: Duration) -> CacheStats {
+        let recent = match (&self.recent_stats, self.now()) {
+            (Some(recent_stats), Some(now)) => recent_stats.sum(now, window),
+            _ => CacheStats::default(),
+        };
+
+        CacheStats {
+            entries: self.len() as u64,
+            ..recent
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Starts writing a line to `writer` for every `get`, `put` and `remove`, in the format described under
+    /// [`TraceRecord`], so that the access pattern can be replayed later.  Writes are buffered; call
+    /// [`stop_tracing`](Self::stop_tracing) to flush them and learn whether any failed.  A trace already being recorded
+    /// is flushed and replaced, and any error it met is lost
+    ///
+    /// Lookups through [`get`](Self::get) and [`get_deferred`](Self::get_deferred) are written as `get`, insertions
+    /// and updates as `put`, and [`remove`](Self::remove) and [`pop_entry`](Self::pop_entry) as `remove`.  A `put` that
+    /// the cache's admission policy turns away is not written.  Times come from the cache's clock, which is started if
+    /// the cache does not have one yet
+    #[cfg(feature = "std")]
+    pub fn set_trace_writer(&mut self, writer: impl std::io::Write + Send + 'static) {
+        let _ = self.stop_tracing();
+        self.clock_now();
+        self.recorder = Some(Box::new(recorder::TraceRecorder::new(Box::new(writer), self.trace_keys)));
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Stops writing the trace started by [`set_trace_writer`](Self::set_trace_writer) or
+    /// [`LruCacheBuilder::record_trace`], and flushes it.  Returns the first error met while writing the trace, after
+    /// which nothing more was written to it
+    #[cfg(feature = "std")]
+    pub fn stop_tracing(&mut self) -> std::io::Result<()> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns up to `n` of the most used keys, most used first, with estimates of their uses since the cache was
+    /// created or [`reset_frequency_stats`](Self::reset_frequency_stats) was last called.  Empty unless the cache was
+    /// built with [`LruCacheBuilder::track_hot_keys`], which describes what counts as a use
+    pub fn hottest(&self, n: usize) -> Vec<(K, u64)>
+    where
+        K: Clone,
+    {
+        match &self.hot_keys {
+            Some(hot_keys) => hot_keys.hottest(n).into_iter().map(|(key, uses)| (key.clone(), uses)).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Forgets the uses counted for [`hottest`](Self::hottest)
+    pub fn reset_frequency_stats(&mut self) {
+        if let Some(hot_keys) = &mut self.hot_keys {
+            hot_keys.clear();
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of lookups that the doorkeeper answered as misses without probing the cache.  Always `0` if
+    /// the cache was not built with [`LruCacheBuilder::doorkeeper`]
+    pub fn doorkeeper_rejections(&self) -> u64 {
+        self.doorkeeper.as_ref().map_or(0, Doorkeeper::rejections)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the counters of the frequency sketch that decides admission, or `None` unless the cache was built with
+    /// [`EvictionMode::TinyLfu`]
+    pub fn sketch_stats(&self) -> Option<SketchStats> {
+        match &self.policy {
+            Policy::TinyLfu(tiny_lfu) => Some(tiny_lfu.sketch().stats()),
+            _ => None,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the frequency sketch's estimate of the recent uses of a key, whether or not it is cached, or `None`
+    /// unless the cache was built with [`EvictionMode::TinyLfu`].  Estimates saturate at 15
+    pub fn estimated_frequency<Q>(&self, key: &Q) -> Option<u8>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match &self.policy {
+            Policy::TinyLfu(tiny_lfu) => Some(tiny_lfu.sketch().frequency(self.hash_builder.hash_one(key))),
+            _ => None,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the clock that [`EvictionMode::CostAware`] adds to an item's cost to give its priority, which is the
+    /// priority of the last item evicted, or `None` under other eviction modes
+    pub fn cost_clock(&self) -> Option<u64> {
+        match &self.policy {
+            Policy::CostAware(greedy_dual) => Some(greedy_dual.inflation()),
+            _ => None,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Reports how full each queue of an [`EvictionMode::S3Fifo`] cache is, or `None` under other eviction modes.
+    /// Expired items count until they are removed
+    pub fn s3_fifo_queues(&self) -> Option<S3FifoQueues> {
+        match &self.policy {
+            Policy::S3Fifo(s3_fifo) => Some(s3_fifo.queues(&self.nodes)),
+            _ => None,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes all items.  If the cache was built with [`LruCacheBuilder::on_removal`] or
+    /// [`LruCacheBuilder::listener`], the items are removed one by one, least recently used first, and reported
+    pub fn clear(&mut self) {
+        if self.on_removal.is_some() || self.listeners.is_some() {
+            self.apply_deferred_reads();
+
+            while let Some(idx) = self.nodes.tail() {
+                self.remove_at(idx, RemovalCause::Cleared);
+            }
+        }
+
+        self.discard_all();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes all items without reporting them, as when recovering a cache from a poisoned lock
+    pub(crate) fn discard_all(&mut self) {
+        if let Some(buffer) = &mut self.read_buffer {
+            buffer.clear();
+        }
+
+        if let Some(filter) = &mut self.doorkeeper {
+            filter.clear();
+        }
+
+        match &mut self.policy {
+            Policy::Lfu(lfu) => lfu.clear(),
+            Policy::Slru(slru) => slru.clear(),
+            Policy::TwoQueue(two_queue) => two_queue.clear(),
+            Policy::TinyLfu(tiny_lfu) => tiny_lfu.clear(),
+            Policy::CostAware(greedy_dual) => greedy_dual.clear(),
+            Policy::S3Fifo(s3_fifo) => s3_fifo.clear(),
+            _ => {}
+        }
+
+        self.index.clear();
+        self.nodes.clear();
+        self.tiers.clear();
+        self.deadlines.clear();
+        self.record_len();
+        self.check_invariants();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Does now the work that later operations would otherwise do along the way: replays the promotions recorded in
+    /// the read buffer and finishes moving entries out of a key index that is growing incrementally
+    pub fn run_pending_tasks(&mut self) {
+        self.apply_deferred_reads();
+        self.index.finish_draining(&self.nodes);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes every item that has passed its time-to-live or been idle for too long, returning how many were removed.
+    ///
+    /// Expired items are otherwise only removed when a lookup or write comes across them, and until then they take up
+    /// room that could hold live items.  Removed items do not count as evictions.  Deadlines are kept in order, so the
+    /// cost depends on the number of deadlines that have passed, not on the number of items
+    pub fn purge_expired(&mut self) -> usize {
+        let Some(now) = self.now() else {
+            return 0;
+        };
+
+        self.apply_deferred_reads();
+        let mut purged = 0;
+
+        while let Some(&Reverse((deadline, hash))) = self.deadlines.peek()
+            && deadline <= now
+        {
+            self.deadlines.pop();
+
+            // Several entries may share the hash, and the one the deadline was scheduled for may have moved it
+            while let Some(idx) = self.index.find(hash, |&i| {
+                let node = self.nodes.node(i);
+                node.hash == hash && expired(node.expires_at.min(node.idle_at), Some(now))
+            }) {
+                self.remove_at(idx, RemovalCause::Expired);
+                purged += 1;
+            }
+        }
+
+        purged
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Checks that the recency list, the key index and the bookkeeping of the eviction mode agree with each other,
+    /// describing the first inconsistency found.  Intended for tests and debugging; the check walks every entry
+    pub fn debug_validate(&self) -> Result<(), ValidationError> {
+        self.nodes.validate()?;
+
+        let (len, capacity) = (self.nodes.len(), self.capacity.get());
+        let indexed = self.index.len();
+        ensure!(indexed == len, "key index holds {indexed} entries but the recency list holds {len}");
+        ensure!(len <= capacity, "cache holds {len} entries, more than its capacity of {capacity}");
+
+        for priority in [Priority::BestEffort, Priority::Normal, Priority::Critical] {
+            let count = self.nodes.iter_from_head().filter(|(_, node)| node.priority == priority).count();
+            ensure!(self.tiers.count(priority) == count, "miscounted the entries at {priority:?} priority");
+        }
+
+        for (idx, node) in self.nodes.iter_from_head() {
+            let hash = self.hash_builder.hash_one(&node.key);
+            ensure!(node.hash == hash, "entry {idx:?} holds a stale hash");
+            let found = self.index.find(hash, |&i| self.nodes.node(i).key == node.key);
+            ensure!(found == Some(idx), "key index does not point at entry {idx:?}");
+
+            if let Some(filter) = &self.doorkeeper {
+                ensure!(filter.admits(hash), "doorkeeper rejects the key of entry {idx:?}");
+            }
+
+            let deadline = node.expires_at.min(node.idle_at);
+            let scheduled = deadline == NEVER || self.deadlines.iter().any(|&Reverse(d)| d == (deadline, hash));
+            ensure!(scheduled, "entry {idx:?} expires without its deadline being scheduled");
+        }
+
+        match &self.policy {
+            Policy::Slru(slru) => slru.validate(&self.nodes),
+            Policy::TwoQueue(two_queue) => two_queue.validate(&self.nodes),
+            Policy::TinyLfu(tiny_lfu) => tiny_lfu.validate(&self.nodes),
+            Policy::S3Fifo(s3_fifo) => s3_fifo.validate(&self.nodes),
+            _ => Ok(()),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Panics with the inconsistency found by [`debug_validate`](Self::debug_validate), if there is one, in builds
+    /// with debug assertions.  Does nothing otherwise
+    #[track_caller]
+    pub fn debug_assert_valid(&self) {
+        #[cfg(debug_assertions)]
+        if let Err(error) = self.debug_validate() {
+            panic!("inconsistent cache: {error}");
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Asserts that a small cache is consistent after each change to its contents, in this crate's own debug test
+    /// builds.  The check walks every entry, so making it after every change would make larger caches, and the debug
+    /// builds of other crates, quadratic.  They can call [`debug_assert_valid`](Self::debug_assert_valid) instead
+    #[inline(always)]
+    fn check_invariants(&self) {
+        #[cfg(all(test, debug_assertions))]
+        if self.capacity.get() <= 64 {
+            self.debug_assert_valid();
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetches a mutable reference to an item, making it the most recently used
+    #[cfg(feature = "compat")]
+    pub(crate) fn get_mut_ref<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.apply_deferred_reads();
+        let idx = self.find_live(key)?;
+        self.touch(idx);
+        Some(&mut self.nodes.node_mut(idx).value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetches a mutable reference to an item without changing its position in the recency order
+    #[cfg(feature = "compat")]
+    pub(crate) fn peek_mut_ref<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.find_live(key)?;
+        Some(&mut self.nodes.node_mut(idx).value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetches references to the least recently used key and value
+    #[cfg(feature = "compat")]
+    pub(crate) fn peek_lru_entry(&self) -> Option<(&K, &V)> {
+        let node = self.nodes.node(self.nodes.tail()?);
+        Some((&node.key, &node.value))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the item that eviction would choose next, or the least recently used under [`Policy::Mru`], returning
+    /// both its key and value.  Expired items found along the way are discarded
+    pub(crate) fn pop_lru_entry(&mut self) -> Option<(K, V)> {
+        self.apply_deferred_reads();
+
+        loop {
+            let idx = match self.policy {
+                Policy::Mru => self.nodes.tail()?,
+                _ => self.eviction_candidate()?,
+            };
+            let expired = self.is_expired(idx);
+            let entry = self.remove_at(idx, RemovalCause::Explicit);
+
+            if !expired {
+                self.record_removal();
+                return entry;
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the handle of the entry to evict next
+    fn eviction_candidate(&mut self) -> Option<Handle> {
+        let nodes = &mut self.nodes;
+
+        match &mut self.policy {
+            Policy::Exact | Policy::Slru(_) | Policy::Fifo { .. } => nodes.tail(),
+            Policy::Sampled(sampler) => (0..sampler.sample_size())
+                .filter_map(|_| nodes.sample(sampler.next_random()))
+                .min_by_key(|&idx| nodes.node(idx).last_access),
+            Policy::Lfu(lfu) => lfu.victim(nodes, &self.index),
+            Policy::TwoQueue(two_queue) => two_queue.victim(nodes),
+            Policy::TinyLfu(tiny_lfu) => tiny_lfu.victim(nodes),
+            Policy::Random(random) => eviction::random_victim(nodes, random),
+            Policy::Mru => nodes.head(),
+            Policy::CostAware(greedy_dual) => greedy_dual.victim(nodes, &self.index),
+            Policy::S3Fifo(s3_fifo) => s3_fifo.victim(nodes),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the least recently used item to make room for a new one.  If that item had expired, removing it makes
+    /// the room, and nothing counts as evicted.  Fails if every item is pinned
+    fn evict_lru_entry(&mut self) -> Result<Option<(K, V)>, CacheError> {
+        self.apply_deferred_reads();
+
+        let idx = match self.eviction_candidate() {
+            Some(idx) if self.is_passed_over(idx) && !self.is_expired(idx) => self.unpinned_victim(),
+            candidate => candidate,
+        };
+        let Some(idx) = idx else {
+            return match self.nodes.len() {
+                0 => Ok(None),
+                _ => Err(CacheError::AllPinned {
+                    capacity: self.capacity.get(),
+                }),
+            };
+        };
+        let expired = self.is_expired(idx);
+        let ages = self.now().map(|now| {
+            let node = self.nodes.node(idx);
+            (now.saturating_sub(node.inserted_at), now.saturating_sub(node.accessed_at))
+        });
+
+        match &mut self.policy {
+            Policy::TwoQueue(two_queue) if !expired => two_queue.remember(&self.nodes, idx),
+            Policy::S3Fifo(s3_fifo) if !expired => s3_fifo.remember(&self.nodes, idx),
+            Policy::CostAware(greedy_dual) if !expired => greedy_dual.evicted(&self.nodes, idx),
+            _ => {}
+        }
+
+        let Some(evicted) = self.remove_at(idx, RemovalCause::Capacity).filter(|_| !expired) else {
+            return Ok(None);
+        };
+        self.record_eviction(ages);
+        Ok(Some(evicted))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if eviction must pass over an entry that the eviction mode chose, because it is pinned or
+    /// guarded, or because a lower tier holds other entries
+    fn is_passed_over(&self, idx: Handle) -> bool {
+        self.is_held(idx) || Some(self.nodes.node(idx).priority) > self.tiers.lowest()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the entry to evict in place of one that the eviction mode chose but eviction must pass over: the least
+    /// recently used entry of the lowest tier that is neither pinned nor guarded, or the most recently used under
+    /// [`EvictionMode::Mru`].  An expired entry counts as belonging to the lowest tier
+    fn unpinned_victim(&self) -> Option<Handle> {
+        match self.policy {
+            Policy::Mru => self.lowest_evictable(self.nodes.iter_from_head().map(|(idx, _)| idx)),
+            _ => self.lowest_evictable(self.nodes.iter_from_tail().map(|(idx, _)| idx)),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the first evictable entry in `order` from the lowest tier that has one
+    fn lowest_evictable(&self, order: impl Iterator<Item = Handle>) -> Option<Handle> {
+        let floor = self.tiers.lowest()?;
+        let mut victim: Option<(Priority, Handle)> = None;
+
+        for idx in order {
+            let tier = match self.is_expired(idx) {
+                true => Priority::BestEffort,
+                false if self.is_held(idx) => continue,
+                false => self.nodes.node(idx).priority,
+            };
+
+            if tier <= floor {
+                return Some(idx);
+            }
+            if victim.is_none_or(|(lowest, _)| tier < lowest) {
+                victim = Some((tier, idx));
+            }
+        }
+
+        victim.map(|(_, idx)| idx)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Iterates over the items from least to most recently used
+    pub(crate) fn iter_lru_first(&self) -> impl Iterator<Item = (&K, &V)> {
+        let now = self.now();
+
+        self.nodes
+            .iter_from_tail()
+            .filter(move |(_, node)| !expired(node.expires_at.min(node.idle_at), now))
+            .map(|(_, node)| (&node.key, &node.value))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Calls `f` with each item's key and a mutable reference to its value, from least to most recently used, stopping
+    /// at the first error.  The recency order is not changed
+    pub(crate) fn try_for_each_lru_first_mut<E>(
+        &mut self,
+        mut f: impl FnMut(&K, &mut V) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let now = self.now();
+        let live: Vec<Handle> = self
+            .nodes
+            .iter_from_tail()
+            .filter(|(_, node)| !expired(node.expires_at.min(node.idle_at), now))
+            .map(|(idx, _)| idx)
+            .collect();
+
+        for idx in live {
+            let node = self.nodes.node_mut(idx);
+            f(&node.key, &mut node.value)?;
+        }
+
+        Ok(())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Replays the promotions recorded in the read buffer, oldest first
+    fn apply_deferred_reads(&mut self) {
+        let Some(mut buffer) = self.read_buffer.take_if(|buffer| !buffer.is_empty()) else {
+            return;
+        };
+
+        for hash in buffer.drain() {
+            // Entries are never removed while promotions are pending, so only a full 64-bit hash collision could
+            // promote the wrong entry.  One that expired since it was read stays expired
+            if let Some(idx) = self.index.find(hash, |&i| self.nodes.node(i).hash == hash)
+                && !self.is_expired(idx)
+            {
+                self.touch(idx);
+            }
+        }
+
+        self.read_buffer = Some(buffer);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Records a use of an entry, either by making it the most recently used or by stamping it with the current time,
+    /// and restarts its idle time
+    fn touch(&mut self, idx: Handle) {
+        match &mut self.policy {
+            Policy::Exact | Policy::Mru => self.nodes.move_to_front(idx),
+            Policy::Sampled(sampler) => self.nodes.node_mut(idx).last_access = sampler.tick(),
+            Policy::Lfu(lfu) => {
+                self.nodes.move_to_front(idx);
+                lfu.record(&mut self.nodes, idx);
+            }
+            Policy::Slru(slru) => slru.record(&mut self.nodes, idx, None),
+            Policy::TwoQueue(two_queue) => two_queue.record(&mut self.nodes, idx),
+            Policy::TinyLfu(tiny_lfu) => tiny_lfu.record(&mut self.nodes, idx),
+            Policy::Fifo { .. } | Policy::Random(_) => {}
+            Policy::CostAware(greedy_dual) => {
+                self.nodes.move_to_front(idx);
+                greedy_dual.record(&mut self.nodes, idx);
+            }
+            Policy::S3Fifo(s3_fifo) => s3_fifo.record(&mut self.nodes, idx),
+        }
+
+        self.record_access(idx);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Stamps an entry with the time of its use, if the cache has a clock.  Also restarts its idle time, if the cache
+    /// has a time-to-idle, and its time-to-live, if the cache refreshes those on use and the entry's deadline is not
+    /// absolute
+    fn record_access(&mut self, idx: Handle) {
+        #[cfg(feature = "std")]
+        if self.time_to_idle.is_some() || self.refresh_ttl_on_get {
+            let now = self.clock_now();
+            let node = self.nodes.node_mut(idx);
+            node.accessed_at = now;
+
+            if let Some(time_to_idle) = self.time_to_idle {
+                node.idle_at = clock::deadline(now, clock::nanos(time_to_idle));
+            }
+
+            if self.refresh_ttl_on_get && node.ttl != NEVER {
+                node.expires_at = clock::deadline(now, node.ttl);
+            }
+
+            self.schedule(idx);
+            return;
+        }
+
+        if let Some(now) = self.now() {
+            self.nodes.node_mut(idx).accessed_at = now;
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if an entry should be refreshed ahead of its deadline.  The entry then forgets its load cost, so
+    /// that only one caller is asked to refresh it
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    fn refreshes_early(&mut self, idx: Handle) -> bool {
+        #[cfg(feature = "std")]
+        if let Some(early_expiry) = &mut self.early_expiry
+            && let Some(clock) = &self.clock
+        {
+            let node = self.nodes.node_mut(idx);
+
+            if early_expiry.refreshes(clock.now(), node.expires_at.min(node.idle_at), node.load_cost) {
+                node.load_cost = 0;
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Records an entry's deadline for [`purge_expired`](Self::purge_expired), if it has one.  Deadlines left behind
+    /// by moved or removed entries are dropped once they outnumber the entries
+    fn schedule(&mut self, idx: Handle) {
+        let node = self.nodes.node(idx);
+        let deadline = node.expires_at.min(node.idle_at);
+
+        if deadline == NEVER {
+            return;
+        }
+
+        self.deadlines.push(Reverse((deadline, node.hash)));
+
+        if self.deadlines.len() > 2 * self.nodes.len() + 64 {
+            self.deadlines = self
+                .nodes
+                .iter_from_head()
+                .map(|(_, node)| (node.expires_at.min(node.idle_at), node.hash))
+                .filter(|&(deadline, _)| deadline != NEVER)
+                .map(Reverse)
+                .collect();
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the current time on the cache's clock, or `None` if it has no clock, in which case no item has been
+    /// given a deadline
+    fn now(&self) -> Option<u64> {
+        self.clock.as_ref().map(|clock| clock.now())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the expiry of an item inserted now without a time-to-live of its own
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    fn default_expiry(&mut self, key: &K, value: &V) -> Expiry {
+        #[cfg(feature = "std")]
+        if let Some(ttl_fn) = &self.ttl_fn {
+            return match ttl_fn.call(key, value) {
+                Some(ttl) => self.expiry_after(ttl),
+                None => Expiry::NEVER,
+            };
+        }
+
+        #[cfg(feature = "std")]
+        if let Some(ttl) = self.default_ttl {
+            return self.expiry_after(ttl);
+        }
+
+        Expiry::NEVER
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the expiry of an item inserted now with the given time-to-live, after any jitter, starting the clock if
+    /// need be
+    #[cfg(feature = "std")]
+    fn expiry_after(&mut self, ttl: Duration) -> Expiry {
+        let ttl = match &mut self.ttl_jitter {
+            Some(jitter) => jitter.apply(clock::nanos(ttl)),
+            None => clock::nanos(ttl),
+        };
+
+        Expiry {
+            at: clock::deadline(self.clock_now(), ttl),
+            ttl,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the current time on the cache's clock, starting a [`SystemClock`] if the cache has none, or a `JsClock`
+    /// on `wasm32-unknown-unknown` with the `wasm` feature
+    #[cfg(feature = "std")]
+    fn clock_now(&mut self) -> u64 {
+        self.clock.get_or_insert_with(|| Arc::new(clock::DefaultClock::new())).now()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the entry has passed its time-to-live or been idle for too long.  Only consults the clock for
+    /// entries that have a deadline
+    fn is_expired(&self, idx: Handle) -> bool {
+        let node = self.nodes.node(idx);
+        let expires_at = node.expires_at.min(node.idle_at);
+        expires_at != NEVER && expired(expires_at, self.now())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the handle of a key's entry, unless it has expired
+    fn find_live<Q>(&self, key: &Q) -> Option<Handle>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).filter(|&idx| !self.is_expired(idx))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Pins or unpins a key's entry.  Returns `false` if the key is not found or the entry has expired
+    fn set_pinned<Q>(&mut self, key: &Q, pinned: bool) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.find_live(key) {
+            Some(idx) => {
+                self.nodes.node_mut(idx).pinned = pinned;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Moves an entry to another tier, leaving its place in the recency order alone
+    fn set_tier(&mut self, idx: Handle, priority: Priority) {
+        let node = self.nodes.node_mut(idx);
+        let old_priority = mem::replace(&mut node.priority, priority);
+
+        self.tiers.remove(old_priority);
+        self.tiers.add(priority);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if an entry is pinned or guarded, and so must not be evicted
+    fn is_held(&self, idx: Handle) -> bool {
+        let node = self.nodes.node(idx);
+
+        node.pinned || node.guards > 0
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Adds a guard to a key's entry, exempting it from eviction until every guard is released.  Returns `false` if
+    /// the key is not found or the entry has expired
+    #[cfg(feature = "std")]
+    pub(crate) fn hold<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.find_live(key) {
+            Some(idx) => {
+                self.nodes.node_mut(idx).guards += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Releases a guard added by [`hold`](Self::hold).  Does nothing if the entry has since been removed
+    #[cfg(feature = "std")]
+    pub(crate) fn release<Q>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if let Some(idx) = self.find(key) {
+            let node = self.nodes.node_mut(idx);
+            node.guards = node.guards.saturating_sub(1);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the handle of a key's entry
+    fn find<Q>(&self, key: &Q) -> Option<Handle>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find_hashed(self.hash_builder.hash_one(key), key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the handle of a key's entry, given the key's hash
+    fn find_hashed<Q>(&self, hash: u64, key: &Q) -> Option<Handle>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        if !self.doorkeeper.as_ref().is_none_or(|filter| filter.admits(hash)) {
+            return None;
+        }
+
+        self.index.find(hash, |&i| {
+            let node = self.nodes.node(i);
+            node.hash == hash && node.key.borrow() == key
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Replaces the value and deadline of an existing item and counts it as used, returning the old value once it has
+    /// been reported to the removal callback.  Replacing an expired value counts as an insertion rather than an update,
+    /// so the item's hit count starts again from zero, whereas an update keeps it
+    fn replace_value(&mut self, idx: Handle, new_value: V, expiry: Expiry, expired: bool) -> V {
+        let cause = match expired {
+            true => RemovalCause::Expired,
+            false => RemovalCause::Replaced,
+        };
+
+        match expired {
+            true => self.record_insertion(idx),
+            false => self.record_update(idx),
+        }
+
+        let node = self.nodes.node_mut(idx);
+        node.expires_at = expiry.at;
+        node.ttl = expiry.ttl;
+        let old_value = mem::replace(&mut node.value, new_value);
+
+        if expired {
+            self.reset_hits(idx);
+        }
+
+        if let Policy::Fifo { refresh_on_put: true } = self.policy {
+            self.nodes.move_to_front(idx);
+        }
+
+        self.touch(idx);
+        self.schedule(idx);
+        let node = self.nodes.node_mut(idx);
+        node.inserted_at = node.accessed_at;
+        self.record_len();
+        self.check_invariants();
+
+        if let Some(on_removal) = &mut self.on_removal {
+            on_removal.call(&self.nodes.node(idx).key, &old_value, cause);
+        }
+
+        if let Some(listeners) = &mut self.listeners {
+            let node = self.nodes.node(idx);
+            listeners.notify(Event::Remove(cause), &node.key, &old_value);
+
+            match expired {
+                true => listeners.notify(Event::Insert, &node.key, &node.value),
+                false => listeners.notify(Event::Update, &node.key, &node.value),
+            }
+        }
+
+        old_value
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts an item whose key is not present, first evicting the least recently used item if the cache is full.
+    /// Returns the new item's handle and the evicted item, or hands the new item back if every item is pinned
+    fn insert_new(&mut self, hash: u64, key: K, value: V, expiry: Expiry) -> Inserted<K, V> {
+        let evicted = if self.nodes.len() >= self.capacity.get() {
+            match self.evict_lru_entry() {
+                Ok(evicted) => evicted,
+                Err(_) => return Err((key, value)),
+            }
+        } else {
+            None
+        };
+
+        let idx = self.insert_front(hash, key, value);
+        self.record_insertion(idx);
+        let node = self.nodes.node_mut(idx);
+        node.expires_at = expiry.at;
+        node.ttl = expiry.ttl;
+        self.schedule(idx);
+        self.record_len();
+        self.check_invariants();
+        self.notify(Event::Insert, idx);
+        Ok((idx, evicted))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Stores a new item as the most recently used, returning its handle.  The key must not already be present
+    fn insert_front(&mut self, hash: u64, key: K, value: V) -> Handle {
+        let idx = self.nodes.push_front(hash, key, value);
+        self.tiers.add(Priority::Normal);
+
+        match &mut self.policy {
+            Policy::Exact | Policy::Fifo { .. } | Policy::Random(_) | Policy::Mru => {}
+            Policy::Sampled(sampler) => self.nodes.node_mut(idx).last_access = sampler.tick(),
+            Policy::Lfu(lfu) => lfu.admit(&mut self.nodes, idx),
+            Policy::Slru(slru) => slru.admit(&mut self.nodes, idx),
+            Policy::TwoQueue(two_queue) => two_queue.admit(&mut self.nodes, idx),
+            Policy::TinyLfu(tiny_lfu) => tiny_lfu.admit(&mut self.nodes, idx),
+            Policy::CostAware(greedy_dual) => greedy_dual.admit(&mut self.nodes, idx),
+            Policy::S3Fifo(s3_fifo) => s3_fifo.admit(&mut self.nodes, idx),
+        }
+
+        self.record_access(idx);
+        self.reset_hits(idx);
+        let node = self.nodes.node_mut(idx);
+        node.inserted_at = node.accessed_at;
+        self.index.insert(hash, idx, &self.nodes);
+
+        if let Some(filter) = &mut self.doorkeeper {
+            filter.insert(hash);
+        }
+
+        idx
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the entry with the given handle, reporting it to the removal callback with the given cause, or as
+    /// expired if it has
+    fn remove_at(&mut self, idx: Handle, cause: RemovalCause) -> Option<(K, V)> {
+        let hash = self.nodes.node(idx).hash;
+        self.index.remove(hash, |&i| i == idx)?;
+        self.take_out(idx, cause)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Takes an entry that has left the key index out of the list, and reports it to the removal callback with the
+    /// given cause, or as expired if it has
+    fn take_out(&mut self, idx: Handle, cause: RemovalCause) -> Option<(K, V)> {
+        let evicting = cause == RemovalCause::Capacity;
+        let cause = match self.reports_removals() && self.is_expired(idx) {
+            true => RemovalCause::Expired,
+            false => cause,
+        };
+        let entry = self.unlink(idx);
+        self.forget_in_doorkeeper();
+        self.record_len();
+
+        // An eviction leaves the ghost queue over its capacity until the insertion or trim that follows
+        if !evicting {
+            self.check_invariants();
+        }
+
+        #[cfg(feature = "tracing")]
+        if let Some(tracer) = &self.tracer {
+            tracer.removed(&entry.0, cause);
+        }
+
+        if let Some(on_removal) = &mut self.on_removal {
+            on_removal.call(&entry.0, &entry.1, cause);
+        }
+
+        if let Some(listeners) = &mut self.listeners {
+            listeners.notify(Event::Remove(cause), &entry.0, &entry.1);
+        }
+
+        Some(entry)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if removed items are reported, to the removal callback, the listeners or as `tracing` events, so
+    /// that their cause matters
+    fn reports_removals(&self) -> bool {
+        #[cfg(feature = "tracing")]
+        if self.tracer.is_some() {
+            return true;
+        }
+
+        self.on_removal.is_some() || self.listeners.is_some()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Takes an entry out of the list, once it has left the key index
+    fn unlink(&mut self, idx: Handle) -> (K, V) {
+        match &mut self.policy {
+            Policy::Slru(slru) => slru.forget(&mut self.nodes, idx),
+            Policy::TwoQueue(two_queue) => two_queue.forget(&mut self.nodes, idx),
+            Policy::TinyLfu(tiny_lfu) => tiny_lfu.forget(&mut self.nodes, idx),
+            Policy::S3Fifo(s3_fifo) => s3_fifo.forget(&mut self.nodes, idx),
+            _ => {}
+        }
+
+        self.tiers.remove(self.nodes.node(idx).priority);
+        self.nodes.remove(idx)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Records that an entry has left the cache, rebuilding the doorkeeper from the remaining entries once it holds too
+    /// many stale keys
+    fn forget_in_doorkeeper(&mut self) {
+        if let Some(filter) = &mut self.doorkeeper
+            && filter.remove()
+        {
+            filter.rebuild(self.nodes.iter_from_head().map(|(_, node)| node.hash));
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> LruCache<K, V, S>
+where
+    K: Clone + Eq + Hash,
+    S: BuildHasher,
+{
+    /// Decomposes the cache into its capacity, store and recency order (most recently used first).  Each key appears in
+    /// both the store and the recency order, so keys must be `Clone`
+    pub fn into_parts(mut self) -> (NonZeroUsize, HashMap<K, V, S>, VecDeque<K>) {
+        self.apply_deferred_reads();
+        let order: VecDeque<K> = self.nodes.iter_from_head().map(|(_, node)| node.key.clone()).collect();
+        let mut store = HashMap::with_capacity_and_hasher(self.nodes.len(), self.hash_builder);
+        store.extend(self.nodes.into_entries());
+
+        (self.capacity, store, order)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> LruCache<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Clone,
+{
+    /// Rebuilds a cache from parts previously obtained from [`LruCache::into_parts`].
+    ///
+    /// The parts are checked to ensure that both structures contain the same keys, that no key appears twice in the
+    /// recency order, and that the number of items does not exceed the capacity
+    pub fn from_parts(capacity: NonZeroUsize, store: HashMap<K, V, S>, order: VecDeque<K>) -> Result<Self, CacheError> {
+        check_capacity(capacity)?;
+
+        if store.len() != order.len() {
+            return Err(CacheError::LengthMismatch {
+                store_len: store.len(),
+                order_len: order.len(),
+            });
+        }
+
+        if store.len() > capacity.get() {
+            return Err(CacheError::CapacityExceeded {
+                len: store.len(),
+                capacity: capacity.get(),
+            });
+        }
+
+        {
+            let mut seen = HashSet::with_capacity(order.len());
+
+            for key in &order {
+                if !store.contains_key(key) {
+                    return Err(CacheError::KeyNotInStore);
+                }
+
+                if !seen.insert(key) {
+                    return Err(CacheError::DuplicateKey);
+                }
+            }
+        }
+
+        let mut store = store;
+        let mut cache = LruCache::with_capacity_and_hasher(capacity, store.len(), store.hasher().clone());
+
+        for key in order.iter().rev() {
+            if let Some((k, v)) = store.remove_entry(key) {
+                let hash = cache.hash_builder.hash_one(&k);
+                cache.insert_front(hash, k, v);
+            }
+        }
+
+        Ok(cache)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Delivery of listener events by a [`ConcurrentLruCache`], once it has released its lock
+#[cfg(feature = "std")]
+impl<K, V, S> LruCache<K, V, S> {
+    /// Queues the events for the cache's listeners until taken by [`take_events`](Self::take_events), so that a
+    /// concurrent cache can deliver them once it has released its lock.  Returns the listeners, if there are any
+    pub(crate) fn defer_events(&mut self) -> Option<listener::ListenerList<K, V>> {
+        self.listeners.as_mut().map(listener::Listeners::defer)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Takes the events queued for the cache's listeners, oldest first
+    pub(crate) fn take_events(&mut self) -> Vec<(Event, K, V)> {
+        self.listeners.as_mut().map(listener::Listeners::take_queued).unwrap_or_default()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Delivers events to the cache's listeners as they happen again
+    pub(crate) fn resume_events(&mut self) {
+        if let Some(listeners) = &mut self.listeners {
+            listeners.resume();
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Activity hooks.  Each counts towards the cache's [`CacheStats`], and reports to any enabled feature that observes
+/// cache activity
+impl<K, V, S> LruCache<K, V, S> {
+    #[inline(always)]
+    fn record_hit(&mut self, idx: Handle) {
+        self.stats.hits += 1;
+        self.record_recent(|counts| counts.hits += 1);
+
+        if let Some(hits) = &mut self.hits {
+            *hits.get_mut(self.nodes.slot(idx)) += 1;
+        }
+
+        self.record_use(idx);
+        self.report_hit(idx);
+        self.notify(Event::Hit, idx);
+    }
+
+    /// How many times an entry has been found, or `None` unless the cache counts hits
+    fn hits(&self, idx: Handle) -> Option<u64> {
+        self.hits.as_ref().map(|hits| hits.get(self.nodes.slot(idx)))
+    }
+
+    /// Starts an entry's count of hits from zero, for a value that has just been stored afresh
+    fn reset_hits(&mut self, idx: Handle) {
+        if let Some(hits) = &mut self.hits {
+            *hits.get_mut(self.nodes.slot(idx)) = 0;
+        }
+    }
+
+    /// Reports a hit to the features that observe cache activity, without counting it in [`CacheStats`]
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    #[inline(always)]
+    fn report_hit(&self, idx: Handle) {
+        #[cfg(feature = "metrics")]
+        if let Some(m) = &self.metrics {
+            m.hits.increment(1);
+        }
+
+        #[cfg(feature = "tracing")]
+        if let Some(tracer) = &self.tracer {
+            tracer.hit(&self.nodes.node(idx).key);
+        }
+
+        #[cfg(feature = "std")]
+        {
+            let node = self.nodes.node(idx);
+            self.trace(TraceOp::Get, true, node.hash, Some(&node.key));
+        }
+    }
+
+    #[inline(always)]
+    fn record_miss<Q>(&mut self, key: &Q)
+    where
+        S: BuildHasher,
+        Q: Hash + ?Sized,
+    {
+        self.stats.misses += 1;
+        self.record_recent(|counts| counts.misses += 1);
+        self.report_miss(key);
+    }
+
+    /// Reports a miss to the features that observe cache activity, without counting it in [`CacheStats`]
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    #[inline(always)]
+    fn report_miss<Q>(&self, key: &Q)
+    where
+        S: BuildHasher,
+        Q: Hash + ?Sized,
+    {
+        #[cfg(feature = "metrics")]
+        if let Some(m) = &self.metrics {
+            m.misses.increment(1);
+        }
+
+        #[cfg(feature = "tracing")]
+        if let Some(tracer) = &self.tracer {
+            tracer.miss();
+        }
+
+        // Only hashed again when recording, as the lookup that missed does not hand its hash back
+        #[cfg(feature = "std")]
+        if self.recorder.is_some() {
+            self.trace(TraceOp::Get, false, self.hash_builder.hash_one(key), None);
+        }
+    }
+
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    #[inline(always)]
+    fn record_insertion(&mut self, idx: Handle) {
+        self.stats.insertions += 1;
+        self.record_recent(|counts| counts.insertions += 1);
+        self.record_use(idx);
+
+        #[cfg(feature = "metrics")]
+        if let Some(m) = &self.metrics {
+            m.insertions.increment(1);
+        }
+
+        #[cfg(feature = "tracing")]
+        if let Some(tracer) = &self.tracer {
+            tracer.inserted(&self.nodes.node(idx).key);
+        }
+
+        #[cfg(feature = "std")]
+        {
+            let node = self.nodes.node(idx);
+            self.trace(TraceOp::Put, false, node.hash, Some(&node.key));
+        }
+    }
+
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    #[inline(always)]
+    fn record_update(&mut self, idx: Handle) {
+        self.stats.updates += 1;
+        self.record_recent(|counts| counts.updates += 1);
+        self.record_use(idx);
+
+        #[cfg(feature = "tracing")]
+        if let Some(tracer) = &self.tracer {
+            tracer.updated(&self.nodes.node(idx).key);
+        }
+
+        #[cfg(feature = "std")]
+        {
+            let node = self.nodes.node(idx);
+            self.trace(TraceOp::Put, true, node.hash, Some(&node.key));
+        }
+    }
+
+    /// Writes an operation to the trace, if one is being recorded
+    #[cfg(feature = "std")]
+    #[inline(always)]
+    fn trace(&self, op: TraceOp, hit: bool, hash: u64, key: Option<&K>) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(self.clock.as_ref().map_or(0, |clock| clock.now()), op, hit, hash, key);
+        }
+    }
+
+    /// Counts a use of an entry towards the most used keys
+    #[inline(always)]
+    fn record_use(&mut self, idx: Handle) {
+        if let Some(hot_keys) = &mut self.hot_keys {
+            let node = self.nodes.node(idx);
+            hot_keys.record(node.hash, &node.key);
+        }
+    }
+
+    /// Counts activity towards the current interval of the windowed statistics, if there are any
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    #[inline(always)]
+    fn record_recent(&mut self, count: fn(&mut stats::IntervalCounts)) {
+        #[cfg(feature = "std")]
+        if let (Some(recent_stats), Some(clock)) = (&mut self.recent_stats, &self.clock) {
+            recent_stats.record(clock.now(), count);
+        }
+    }
+
+    /// Reports an event concerning an entry to the cache's listeners
+    #[inline(always)]
+    fn notify(&mut self, event: Event, idx: Handle) {
+        if let Some(listeners) = &mut self.listeners {
+            let node = self.nodes.node(idx);
+            listeners.notify(event, &node.key, &node.value);
+        }
+    }
+
+    #[inline(always)]
+    fn record_removal(&mut self) {
+        self.stats.removals += 1;
+        self.record_recent(|counts| counts.removals += 1);
+    }
+
+    /// Counts an eviction, with the evicted entry's age and idle time in nanoseconds if the cache has a clock
+    #[inline(always)]
+    fn record_eviction(&mut self, ages: Option<(u64, u64)>) {
+        self.stats.evictions += 1;
+        self.record_recent(|counts| counts.evictions += 1);
+
+        if let Some((age, idle)) = ages {
+            self.stats.eviction_age.record(age);
+            self.stats.eviction_idle.record(idle);
+        }
+
+        #[cfg(feature = "metrics")]
+        if let Some(m) = &self.metrics {
+            m.evictions.increment(1);
+        }
+    }
+
+    #[inline(always)]
+    fn record_len(&self) {
+        #[cfg(feature = "metrics")]
+        if let Some(m) = &self.metrics {
+            m.entries.set(self.nodes.len() as f64);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Returns `true` if a deadline has passed.  Without a clock, no entry has a deadline
+fn expired(expires_at: u64, now: Option<u64>) -> bool {
+    now.is_some_and(|now| now >= expires_at)
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+pub mod arc_cache;
+pub mod array_cache;
+#[cfg(feature = "tokio")]
+pub mod async_cache;
+mod builder;
+mod callback;
+mod clock;
+#[cfg(feature = "compat")]
+pub mod compat;
+#[cfg(feature = "std")]
+pub mod concurrent;
+mod doorkeeper;
+#[cfg(feature = "std")]
+mod early_expiry;
+#[cfg(feature = "std")]
+pub mod entry_cache;
+mod entry_info;
+mod error;
+#[cfg(feature = "lockfree-reads")]
+pub mod epoch_cache;
+mod eviction;
+#[cfg(any(feature = "ahash", feature = "fxhash"))]
+mod fast_hash;
+mod frequency_sketch;
+mod hot_keys;
+pub mod indexed_cache;
+#[cfg(feature = "metrics")]
+mod instrumentation;
+#[cfg(feature = "json")]
+mod json_file;
+mod key_index;
+mod listener;
+#[cfg(feature = "std")]
+pub mod local_async;
+mod nodes;
+#[cfg(feature = "bincode")]
+pub mod oplog;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod priority;
+mod random;
+mod read_buffer;
+#[cfg(feature = "std")]
+mod recorder;
+#[cfg(feature = "std")]
+pub mod read_through;
+pub mod resource;
+#[cfg(feature = "std")]
+pub mod rw_cache;
+#[cfg(feature = "serde")]
+mod serde_impl;
+#[cfg(feature = "std")]
+pub mod sharded;
+#[cfg(feature = "std")]
+pub mod simulate;
+#[cfg(feature = "bincode")]
+mod snapshot;
+#[cfg(feature = "static-cache")]
+pub mod static_cache;
+mod stats;
+mod sync;
+#[cfg(feature = "tiered")]
+pub mod tiered;
+#[cfg(feature = "tracing")]
+mod tracer;
+pub mod write_back;
+pub mod write_through;
+#[cfg(feature = "zeroize")]
+pub mod zeroizing;
+pub mod test_utils;
+
+pub use arc_cache::{ArcCache, ArcList, ArcState};
+pub use array_cache::ArrayLruCache;
+#[cfg(feature = "tokio")]
+pub use async_cache::{AsyncCacheLoader, AsyncLruCache, Closed, LoadError, NoLoader, ReadThrough};
+#[cfg(feature = "std")]
+pub use concurrent::{
+    ConcurrentLruCache, EntryGuard, EvictionEvent, EvictionEvents, EvictionReason, OverflowPolicy, PoisonPolicy,
+    WouldBlock,
+};
+#[cfg(all(feature = "std", not(loom)))]
+pub use concurrent::ExpiryHandle;
+#[cfg(feature = "std")]
+pub use entry_cache::{Entry, EntryLruCache};
+#[cfg(feature = "lockfree-reads")]
+pub use epoch_cache::EpochLruCache;
+#[cfg(feature = "ahash")]
+pub use fast_hash::AHashLruCache;
+#[cfg(feature = "fxhash")]
+pub use fast_hash::FxLruCache;
+pub use indexed_cache::IndexedLruCache;
+#[cfg(feature = "json")]
+pub use json_file::JsonFileError;
+pub use resource::{CacheResource, Resource, ResourceLruCache};
+#[cfg(feature = "std")]
+pub use local_async::LocalAsyncLruCache;
+#[cfg(feature = "bincode")]
+pub use oplog::LoggedLruCache;
+#[cfg(feature = "std")]
+pub use read_through::{CacheLoader, CachedValue, Lookup, ReadThroughCache};
+#[cfg(feature = "std")]
+pub use recorder::{TraceOp, TraceParseError, TraceRecord};
+#[cfg(feature = "std")]
+pub use rw_cache::RwLruCache;
+#[cfg(feature = "std")]
+pub use sharded::{ShardStats, ShardedLruCache};
+#[cfg(feature = "bincode")]
+pub use snapshot::SnapshotError;
+#[cfg(feature = "static-cache")]
+pub use static_cache::StaticLruCache;
+pub use write_back::{WriteBackCache, WriteFailurePolicy};
+pub use write_through::{BackingStore, WriteThroughCache};
+#[cfg(feature = "tiered")]
+pub use tiered::TieredCache;
+#[cfg(feature = "zeroize")]
+pub use zeroizing::ZeroizingLruCache;
+
+#[cfg(all(test, feature = "std"))]
+mod scenarios;
+#[cfg(all(test, feature = "std"))]
+mod unit_tests;
+
+#[cfg(all(test, not(feature = "std")))]
+mod no_std_tests;
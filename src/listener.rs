@@ -0,0 +1,162 @@
+//! Notifications of items leaving the cache
+//!
+//! A cache built with [`LruCacheBuilder::on_removal`](crate::LruCacheBuilder::on_removal) passes every item that
+//! leaves it to the callback, together with the [`RemovalCause`], once the item is out of the cache.  A
+//! [`CacheEventListener`] registered through [`LruCacheBuilder::listener`](crate::LruCacheBuilder::listener) also hears
+//! of insertions, updates and hits
+use crate::callback::{Callback, State};
+use alloc::{sync::Arc, vec::Vec};
+#[cfg(feature = "std")]
+use core::mem;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Why an item left the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RemovalCause {
+    /// Evicted to make room for another item, or by [`LruCache::evict_to`](crate::LruCache::evict_to)
+    Capacity,
+    /// Its time-to-live or time-to-idle had passed when the cache came across it
+    Expired,
+    /// Removed or popped by the caller
+    Explicit,
+    /// Its value was overwritten by a new one for the same key
+    Replaced,
+    /// Removed by [`LruCache::clear`](crate::LruCache::clear)
+    Cleared,
+}
+
+impl RemovalCause {
+    /// Returns `true` if the item was evicted to make room, rather than removed, replaced or expired
+    pub fn was_evicted(self) -> bool {
+        self == RemovalCause::Capacity
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Observer of everything that happens to a cache's items, registered through
+/// [`LruCacheBuilder::listener`](crate::LruCacheBuilder::listener).  Each method does nothing unless overridden.
+///
+/// An [`LruCache`](crate::LruCache) calls its listeners as each event happens, while it is borrowed, so they cannot
+/// use the cache.  A [`ConcurrentLruCache`](crate::ConcurrentLruCache) calls them on the thread that caused the events,
+/// once it has released its lock, so they may log, lock or even use the cache
+pub trait CacheEventListener<K, V>: Send + Sync {
+    /// An item was added under a new key, or under a key whose item had expired
+    fn on_insert(&self, key: &K, value: &V) {
+        let _ = (key, value);
+    }
+
+    /// The value of an item was replaced, and is now `value`.  The old value is passed to
+    /// [`on_remove`](Self::on_remove) with [`RemovalCause::Replaced`] first
+    fn on_update(&self, key: &K, value: &V) {
+        let _ = (key, value);
+    }
+
+    /// A lookup found an item
+    fn on_hit(&self, key: &K, value: &V) {
+        let _ = (key, value);
+    }
+
+    /// An item left the cache, as reported to [`LruCacheBuilder::on_removal`](crate::LruCacheBuilder::on_removal)
+    fn on_remove(&self, key: &K, value: &V, cause: RemovalCause) {
+        let _ = (key, value, cause);
+    }
+}
+
+/// A listener registered through [`LruCacheBuilder::listener`](crate::LruCacheBuilder::listener)
+pub(crate) type Listener<K, V> = Callback<fn(&State, Event, &K, &V)>;
+
+/// The listeners of a cache, shared with the concurrent cache that calls them
+pub(crate) type ListenerList<K, V> = Arc<[Listener<K, V>]>;
+
+/// Copies the key and value of an event that is queued rather than delivered straight away
+pub(crate) type CloneFn<K, V> = fn(&K, &V) -> (K, V);
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// What happened to an item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Event {
+    Insert,
+    Update,
+    Hit,
+    Remove(RemovalCause),
+}
+
+impl Event {
+    /// Calls each listener's method for the event
+    pub(crate) fn deliver<K, V>(self, listeners: &[Listener<K, V>], key: &K, value: &V) {
+        for listener in listeners {
+            listener.call(self, key, value);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Calls one listener's method for the event
+    pub(crate) fn dispatch<K, V>(self, listener: &impl CacheEventListener<K, V>, key: &K, value: &V) {
+        match self {
+            Event::Insert => listener.on_insert(key, value),
+            Event::Update => listener.on_update(key, value),
+            Event::Hit => listener.on_hit(key, value),
+            Event::Remove(cause) => listener.on_remove(key, value, cause),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The listeners registered with a cache, and the events waiting for them while a concurrent cache holds its lock
+pub(crate) struct Listeners<K, V> {
+    listeners: ListenerList<K, V>,
+    clone_entry: CloneFn<K, V>,
+    /// Present while a concurrent cache delivers the events once it has released its lock
+    queue: Option<Vec<(Event, K, V)>>,
+}
+
+impl<K, V> Listeners<K, V> {
+    pub(crate) fn new(listeners: Vec<Listener<K, V>>, clone_entry: CloneFn<K, V>) -> Self {
+        Listeners {
+            listeners: listeners.into(),
+            clone_entry,
+            queue: None,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Delivers an event, or queues a copy of it while deliveries are deferred
+    pub(crate) fn notify(&mut self, event: Event, key: &K, value: &V) {
+        match &mut self.queue {
+            Some(queue) => {
+                let (key, value) = (self.clone_entry)(key, value);
+                queue.push((event, key, value));
+            }
+            None => event.deliver(&self.listeners, key, value),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Delivers an event straight away, even while deliveries are deferred, for callers that only borrow the cache
+    pub(crate) fn notify_now(&self, event: Event, key: &K, value: &V) {
+        event.deliver(&self.listeners, key, value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Queues events from now on, returning the listeners that the caller will deliver them to
+    #[cfg(feature = "std")]
+    pub(crate) fn defer(&mut self) -> ListenerList<K, V> {
+        self.queue = Some(Vec::new());
+        Arc::clone(&self.listeners)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Delivers events as they happen again
+    #[cfg(feature = "std")]
+    pub(crate) fn resume(&mut self) {
+        self.queue = None;
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Takes the events queued so far, oldest first
+    #[cfg(feature = "std")]
+    pub(crate) fn take_queued(&mut self) -> Vec<(Event, K, V)> {
+        self.queue.as_mut().map(mem::take).unwrap_or_default()
+    }
+}
@@ -0,0 +1,289 @@
+//! LRU cache for async code on a single thread
+//!
+//! [`LocalAsyncLruCache`] is the counterpart of `AsyncLruCache` for executors that run every task on one thread, such
+//! as a Tokio `LocalSet` or a browser's event loop.  It keeps its state in [`RefCell`]s rather than behind locks, so
+//! it is neither [`Send`] nor [`Sync`], and in return its loading futures need not be [`Send`] either: they may capture
+//! `Rc`s and other thread-bound values.  It needs no particular runtime.
+//!
+//! # Loading missing values
+//!
+//! [`LocalAsyncLruCache::get_with`] awaits at most one loading future per key at a time.  Other tasks asking for the
+//! same key while it loads register their wakers and await that future's value instead of running their own.  If the
+//! task awaiting the loading future is cancelled, the waiting tasks are woken and one of them runs its own future
+//! instead, so a cancelled load never leaves the others waiting
+//!
+//! No borrow of the cache's state is held across an `.await`, so a loading future may itself use the cache
+use crate::{CacheStats, DefaultHashBuilder, LruCache};
+use core::{
+    cell::RefCell,
+    future::{Future, poll_fn},
+    mem,
+    task::{Poll, Waker},
+};
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    hash::{BuildHasher, Hash},
+    num::NonZeroUsize,
+    rc::Rc,
+    vec::Vec,
+};
+
+/// Progress of one key's load, shared between every task asking for it
+struct Slot<V> {
+    /// Set once the loading future has finished
+    value: Option<V>,
+    /// Set while some task is awaiting its loading future
+    loading: bool,
+    /// Tasks waiting for the value, or for the chance to load it themselves
+    waiting: Vec<Waker>,
+}
+
+/// Slots for the keys whose values are being loaded
+type InFlight<K, V> = RefCell<HashMap<K, Rc<RefCell<Slot<V>>>>>;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache that can be shared between the tasks of a single-threaded executor, with coalesced loading of missing
+/// values
+///
+/// It cannot be moved to another thread:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<lru_cache::LocalAsyncLruCache<u32, u32>>();
+/// ```
+pub struct LocalAsyncLruCache<K, V, S = DefaultHashBuilder> {
+    cache: RefCell<LruCache<K, V, S>>,
+    loading: InFlight<K, V>,
+}
+
+impl<K, V> LocalAsyncLruCache<K, V>
+where
+    K: Eq + Hash,
+{
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        LocalAsyncLruCache::from(LruCache::new(capacity))
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> LocalAsyncLruCache<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Creates a cache that uses the given hash builder to hash keys
+    pub fn with_hasher(capacity: NonZeroUsize, hash_builder: S) -> Self {
+        LocalAsyncLruCache::from(LruCache::with_hasher(capacity, hash_builder))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetches a copy of an item, making it the most recently used, or awaits `load` and inserts its value if the key
+    /// is missing.  If another task is already loading the key, awaits that task's value instead, and only awaits
+    /// `load` if that task is cancelled first.  The time `load` takes is counted in [`CacheStats::load_time`]
+    pub async fn get_with(&self, key: K, load: impl Future<Output = V>) -> V
+    where
+        K: Clone,
+        V: Clone,
+    {
+        if let Some(value) = self.cache.borrow_mut().get_cloned(&key) {
+            return value;
+        }
+
+        let slot = {
+            let mut loading = self.loading.borrow_mut();
+            let slot = loading.entry(key.clone()).or_insert_with(|| {
+                Rc::new(RefCell::new(Slot {
+                    value: None,
+                    loading: false,
+                    waiting: Vec::new(),
+                }))
+            });
+            Rc::clone(slot)
+        };
+
+        let mut interest = Loading {
+            loading: &self.loading,
+            key: &key,
+            slot,
+            loader: false,
+        };
+
+        // Either the value arrives, or nobody is loading it any more and this task takes over
+        let found = poll_fn(|cx| {
+            let mut slot = interest.slot.borrow_mut();
+
+            if let Some(value) = &slot.value {
+                Poll::Ready(Some(value.clone()))
+            } else if !slot.loading {
+                slot.loading = true;
+                Poll::Ready(None)
+            } else {
+                if !slot.waiting.iter().any(|waker| waker.will_wake(cx.waker())) {
+                    slot.waiting.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        })
+        .await;
+
+        if let Some(value) = found {
+            return value;
+        }
+
+        interest.loader = true;
+        let stopwatch = self.cache.borrow().load_started();
+        let value = load.await;
+
+        // Put in the cache before the slot is forgotten, so that later callers find it there
+        {
+            let mut cache = self.cache.borrow_mut();
+            cache.put(key.clone(), value.clone());
+            cache.load_finished(&key, stopwatch);
+        }
+        let waiting = {
+            let mut slot = interest.slot.borrow_mut();
+            slot.value = Some(value.clone());
+            mem::take(&mut slot.waiting)
+        };
+        waiting.into_iter().for_each(Waker::wake);
+
+        value
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a copy of an item, making it the most recently used.  Does not wait for a value being loaded
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.cache.borrow_mut().get_cloned(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache holds a value for the key, without changing its position
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.borrow().contains_key(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item, returning the old value if the key was already present.  A value still being loaded for
+    /// the key will replace this one when it arrives
+    pub fn insert(&self, key: K, new_value: V) -> Option<V> {
+        self.cache.borrow_mut().put(key, new_value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an item, returning its value if it was present.  A value still being loaded for the key is inserted
+    /// when it arrives
+    pub fn invalidate<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.borrow_mut().remove(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in the cache
+    pub fn len(&self) -> usize {
+        self.cache.borrow().len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains no items
+    pub fn is_empty(&self) -> bool {
+        self.cache.borrow().is_empty()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the cache can hold
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.cache.borrow().capacity()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the activity counted since the cache was created, including the time taken by the loads of
+    /// [`get_with`](Self::get_with)
+    pub fn stats(&self) -> CacheStats {
+        self.cache.borrow().stats()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes all items.  Values still being loaded are inserted when they arrive
+    pub fn clear(&self) {
+        self.cache.borrow_mut().clear()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Does the cache's deferred housekeeping now.  See [`LruCache::run_pending_tasks`]
+    pub fn run_pending_tasks(&self) {
+        self.cache.borrow_mut().run_pending_tasks()
+    }
+}
+
+impl<K, V, S> From<LruCache<K, V, S>> for LocalAsyncLruCache<K, V, S> {
+    fn from(cache: LruCache<K, V, S>) -> Self {
+        LocalAsyncLruCache {
+            cache: RefCell::new(cache),
+            loading: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A task's interest in a key being loaded.  Dropping it, whether the task finished or was cancelled, hands the load
+/// over to a waiting task if this one was loading, and forgets the key once its value has been set or nobody else
+/// wants it
+struct Loading<'a, K, V>
+where
+    K: Eq + Hash,
+{
+    loading: &'a InFlight<K, V>,
+    key: &'a K,
+    slot: Rc<RefCell<Slot<V>>>,
+    /// Set once this task has taken over awaiting its own loading future
+    loader: bool,
+}
+
+impl<K, V> Drop for Loading<'_, K, V>
+where
+    K: Eq + Hash,
+{
+    fn drop(&mut self) {
+        let mut loading = self.loading.borrow_mut();
+
+        let (settled, waiting) = {
+            let mut slot = self.slot.borrow_mut();
+
+            // Every waiter is woken, since any of them may have been cancelled too
+            let waiting = if self.loader && slot.value.is_none() {
+                slot.loading = false;
+                mem::take(&mut slot.waiting)
+            } else {
+                Vec::new()
+            };
+
+            // Nothing but the map and this guard refer to an abandoned slot
+            let abandoned = !slot.loading && Rc::strong_count(&self.slot) == 2;
+            (slot.value.is_some() || abandoned, waiting)
+        };
+
+        if settled && loading.get(self.key).is_some_and(|slot| Rc::ptr_eq(slot, &self.slot)) {
+            loading.remove(self.key);
+        }
+
+        drop(loading);
+        waiting.into_iter().for_each(Waker::wake);
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
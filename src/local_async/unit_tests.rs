@@ -0,0 +1,197 @@
+use super::*;
+use core::cell::Cell;
+use std::{format, rc::Rc, string::String, time::Duration, vec::Vec};
+use tokio::{
+    sync::Notify,
+    task::{self, LocalSet},
+    time::timeout,
+};
+
+/// Long enough for a test that would otherwise hang to fail instead
+const PATIENCE: Duration = Duration::from_secs(5);
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn get_with_should_run_one_rc_capturing_load_for_many_tasks() -> Result<(), String> {
+    const TASKS: usize = 100;
+
+    let cache = Rc::new(LocalAsyncLruCache::new(NonZeroUsize::new(4).unwrap()));
+    let loads = Rc::new(Cell::new(0));
+
+    let values = LocalSet::new()
+        .run_until(async {
+            let tasks: Vec<_> = (0..TASKS)
+                .map(|_| {
+                    let (cache, loads) = (Rc::clone(&cache), Rc::clone(&loads));
+
+                    task::spawn_local(async move {
+                        cache
+                            .get_with(String::from("popular"), async move {
+                                loads.set(loads.get() + 1);
+                                tokio::time::sleep(Duration::from_millis(50)).await;
+                                String::from("value")
+                            })
+                            .await
+                    })
+                })
+                .collect();
+
+            let mut values = Vec::new();
+
+            for task in tasks {
+                values.push(timeout(PATIENCE, task).await.map_err(|_| "Timed out waiting for a task")?);
+            }
+
+            Ok::<_, &str>(values)
+        })
+        .await?;
+
+    match (loads.get(), values.iter().filter(|v| v.as_ref().is_ok_and(|v| v == "value")).count()) {
+        (1, TASKS) => (),
+        other => return Err(format!("Expected one load whose value reached all {TASKS} tasks. Got {other:?}")),
+    }
+
+    match (cache.get("popular"), cache.loading.borrow().len()) {
+        (Some(value), 0) if value == "value" => Ok(()),
+        other => Err(format!("Expected the value to be cached and no load left in flight. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn get_with_should_load_each_key_once() -> Result<(), String> {
+    let cache = Rc::new(LocalAsyncLruCache::new(NonZeroUsize::new(4).unwrap()));
+    let loads = Rc::new(RefCell::new(Vec::<u32>::new()));
+
+    LocalSet::new()
+        .run_until(async {
+            let tasks: Vec<_> = [1, 2, 1, 3, 2, 1]
+                .into_iter()
+                .map(|key| {
+                    let (cache, loads) = (Rc::clone(&cache), Rc::clone(&loads));
+
+                    task::spawn_local(async move {
+                        cache
+                            .get_with(key, async move {
+                                loads.borrow_mut().push(key);
+                                task::yield_now().await;
+                                key * 10
+                            })
+                            .await
+                    })
+                })
+                .collect();
+
+            for task in tasks {
+                let _ = task.await;
+            }
+        })
+        .await;
+
+    let mut loaded = loads.take();
+    loaded.sort_unstable();
+
+    match (&loaded[..], cache.get(&1), cache.get(&2), cache.get(&3)) {
+        ([1, 2, 3], Some(10), Some(20), Some(30)) => Ok(()),
+        other => Err(format!("Expected one load per key. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn get_with_should_hand_over_to_a_waiter_when_the_load_is_cancelled() -> Result<(), String> {
+    let cache = Rc::new(LocalAsyncLruCache::new(NonZeroUsize::new(4).unwrap()));
+    let started = Rc::new(Notify::new());
+
+    let value = LocalSet::new()
+        .run_until(async {
+            // This load never finishes, so it only ends by being cancelled
+            let stuck = {
+                let (cache, started) = (Rc::clone(&cache), Rc::clone(&started));
+
+                task::spawn_local(async move {
+                    cache
+                        .get_with(1, async {
+                            started.notify_one();
+                            std::future::pending::<u32>().await
+                        })
+                        .await
+                })
+            };
+
+            started.notified().await;
+
+            let waiter = {
+                let cache = Rc::clone(&cache);
+                task::spawn_local(async move { cache.get_with(1, async { 42 }).await })
+            };
+
+            // Let the waiter start waiting for the stuck load before cancelling it
+            task::yield_now().await;
+            stuck.abort();
+
+            timeout(PATIENCE, waiter)
+                .await
+                .map_err(|_| String::from("The waiter was left waiting for the cancelled load"))?
+                .map_err(|e| format!("The waiter failed: {e}"))
+        })
+        .await?;
+
+    match (value, cache.get(&1), cache.loading.borrow().len()) {
+        (42, Some(42), 0) => Ok(()),
+        other => Err(format!("Expected the waiter to load the value itself. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn get_with_should_forget_a_cancelled_load_that_nobody_awaits() -> Result<(), String> {
+    let cache = LocalAsyncLruCache::new(NonZeroUsize::new(4).unwrap());
+
+    // Polling the load once and then dropping it cancels it part way through
+    let cancelled = timeout(Duration::from_millis(10), cache.get_with(1, std::future::pending::<u32>())).await;
+
+    if cancelled.is_ok() {
+        return Err(String::from("Expected the load to be cancelled"));
+    }
+
+    let in_flight_after_cancel = cache.loading.borrow().len();
+
+    match (in_flight_after_cancel, cache.get_with(1, async { 7 }).await, cache.len()) {
+        (0, 7, 1) => Ok(()),
+        other => Err(format!("Expected the cancelled load to be forgotten and the next to succeed. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[tokio::test]
+async fn get_with_should_let_a_load_use_the_cache() -> Result<(), String> {
+    let cache = LocalAsyncLruCache::new(NonZeroUsize::new(4).unwrap());
+
+    let outer = cache
+        .get_with(1, async {
+            let inner = cache.get_with(2, async { 20 }).await;
+            let _ = cache.insert(3, 30);
+            inner + 1
+        })
+        .await;
+
+    match (outer, cache.get(&1), cache.get(&2), cache.get(&3)) {
+        (21, Some(21), Some(20), Some(30)) => Ok(()),
+        other => Err(format!("Expected a load to read and write the cache without a borrow conflict. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_insert_get_and_invalidate() -> Result<(), String> {
+    let cache = LocalAsyncLruCache::new(NonZeroUsize::new(2).unwrap());
+    cache.insert(1, "one");
+    cache.insert(2, "two");
+    cache.insert(3, "three");
+
+    match (cache.get(&1), cache.invalidate(&2), cache.contains_key(&3), cache.len()) {
+        (None, Some("two"), true, 1) => Ok(()),
+        other => Err(format!("Expected LRU eviction and removal. Got {other:?}")),
+    }
+}
@@ -1,34 +1,80 @@
-use lru_cache::LruCache;
-use std::{
-    num::NonZeroUsize,
-    sync::{Arc, Mutex},
-    thread,
-};
+use lru_cache::{ConcurrentLruCache, simulate};
+use std::{env, fs::File, io::BufReader, num::NonZeroUsize, process, sync::Arc, thread};
 
 fn main() {
-    let am_cache = Arc::new(Mutex::new(LruCache::new(NonZeroUsize::new(2).unwrap())));
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    let cache1 = Arc::clone(&am_cache);
-    let cache2 = Arc::clone(&am_cache);
+    if args.first().map(String::as_str) == Some("simulate") {
+        return run_simulation(&args[1..]);
+    }
+
+    let cache = Arc::new(ConcurrentLruCache::new(NonZeroUsize::new(2).unwrap()));
+
+    let cache1 = Arc::clone(&cache);
+    let cache2 = Arc::clone(&cache);
     let mut handles = Vec::new();
 
     handles.push(thread::spawn(move || {
-        let mut unlocked_cache = cache1.lock().unwrap();
-        unlocked_cache.put("banana", 1);
-        unlocked_cache.put("pear", 2);
+        cache1.put("banana", 1);
+        cache1.put("pear", 2);
     }));
 
     handles.push(thread::spawn(move || {
-        let mut unlocked_cache = cache2.lock().unwrap();
-        unlocked_cache.put("apple", 3);
+        cache2.put("apple", 3);
     }));
 
     for handle in handles {
         handle.join().unwrap();
     }
 
-    let mut cache = am_cache.lock().unwrap();
     println!("banana: {:?}", cache.get(&"banana")); // Might have been evicted
     println!("apple:  {:?}", cache.get(&"apple"));  // Might have been evicted
     println!("pear:   {:?}", cache.get(&"pear"));   // Should still be there
+
+    // Values are returned by clone, so they can be used while other threads carry on using the cache.  To inspect a
+    // value without cloning it, or to run several operations under one lock, use `with_lock`
+    let pear_doubled = cache.with_lock(|c| c.get(&"pear").map(|v| v * 2));
+    println!("pear doubled: {pear_doubled:?}");
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// `lru-cache simulate <trace file> <capacity>...` replays a trace recorded with `LruCache::set_trace_writer` at each
+/// capacity and prints a table of the results
+fn run_simulation(args: &[String]) {
+    let usage = || -> ! {
+        eprintln!("Usage: lru-cache simulate <trace file> <capacity>...");
+        process::exit(2)
+    };
+
+    let Some((path, capacities)) = args.split_first() else { usage() };
+    let capacities: Vec<NonZeroUsize> = capacities.iter().map(|c| c.parse().unwrap_or_else(|_| usage())).collect();
+
+    if capacities.is_empty() {
+        usage();
+    }
+
+    let reports = File::open(path)
+        .map_err(Into::into)
+        .and_then(|file| simulate::simulate(BufReader::new(file), &capacities))
+        .unwrap_or_else(|e| {
+            eprintln!("{path}: {e}");
+            process::exit(1)
+        });
+
+    println!(
+        "{:>12} {:>12} {:>12} {:>9} {:>12} {:>12}",
+        "capacity", "hits", "misses", "hit ratio", "evictions", "peak bytes"
+    );
+
+    for r in reports {
+        println!(
+            "{:>12} {:>12} {:>12} {:>9.4} {:>12} {:>12}",
+            r.capacity,
+            r.hits,
+            r.misses,
+            r.hit_ratio(),
+            r.evictions,
+            r.peak_memory
+        );
+    }
 }
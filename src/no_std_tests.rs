@@ -0,0 +1,66 @@
+//! Tests run when the crate is built without the `std` feature.
+//!
+//! The library itself is `no_std`, but the test harness still links `std`, so these tests prove that the `alloc`-only
+//! build behaves exactly like the `std` build
+extern crate std;
+
+use super::*;
+use alloc::{format, string::String};
+use test_utils::*;
+
+const CAPACITY: NonZeroUsize = NonZeroUsize::new(10).unwrap();
+
+fn default_prefilled_cache() -> LruCache<String, String> {
+    let mut c = LruCache::new(CAPACITY);
+
+    for idx in 0..CAPACITY.get() {
+        let _ = c.put(gen_item_key(idx), gen_item_value(idx as u32));
+    }
+
+    c
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_put_and_get_an_item() -> Result<(), String> {
+    let mut c = LruCache::new(CAPACITY);
+    let k = gen_item_key(1);
+    let v = gen_item_value(1);
+
+    c.put(k.clone(), v.clone());
+
+    match c.get(&k) {
+        Some(found) if *found == v => Ok(()),
+        Some(found) => Err(format!("Expected '{v}'. Got '{found}' instead")),
+        None => Err(format!("{k} Not Found")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_fail_to_get_evicted_item() -> Result<(), String> {
+    let mut c = default_prefilled_cache();
+    let old_k = gen_item_key(0);
+
+    // Adding a new item to a full cache evicts the oldest item
+    c.put(gen_item_key(10), gen_item_value(10));
+
+    if c.get(&old_k).is_some() {
+        Err(format!("Found item '{old_k}' when it should have been evicted"))
+    } else {
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_pop_expected_mru_and_lru_after_reorder() -> Result<(), String> {
+    let mut c = default_prefilled_cache();
+
+    c.get("item-6").ok_or("item-6 not found")?;
+
+    match (c.pop_mru(), c.pop_lru()) {
+        (Some(mru), Some(lru)) if mru == gen_item_value(6) && lru == gen_item_value(0) => Ok(()),
+        popped => Err(format!("Expected (value-6, value-0). Got {popped:?} instead")),
+    }
+}
@@ -0,0 +1,56 @@
+//! Storage for cache entries and their recency order
+//!
+//! The safe, slab-backed implementation is used by default.  The `unsafe-fast` feature swaps in an implementation that
+//! links individually allocated entries with raw pointers.  Both expose the same methods, so the cache is written once
+//! against whichever is selected
+#[cfg(feature = "unsafe-fast")]
+mod pointer;
+#[cfg(any(not(feature = "unsafe-fast"), test))]
+#[cfg_attr(feature = "unsafe-fast", allow(dead_code))]
+#[cfg_attr(feature = "wide-index", allow(clippy::unnecessary_cast))]
+mod slab;
+
+#[cfg(feature = "unsafe-fast")]
+pub(crate) use pointer::{Handle, MAX_LEN, PointerList as Nodes};
+#[cfg(not(feature = "unsafe-fast"))]
+pub(crate) use slab::{Handle, MAX_LEN, Slab as Nodes};
+
+use alloc::vec::Vec;
+use core::mem;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Per-entry values kept beside the entries rather than in them, so that state only some caches need costs nothing in
+/// the others.  A value is addressed by its entry's [`Nodes::slot`], which stays the same while the entry is present
+/// and is reused by a later entry once it has gone, so whoever fills a slot must overwrite what the last entry left.
+/// The table grows when a slot beyond its end is first written
+#[derive(Default)]
+pub(crate) struct SideTable<T> {
+    values: Vec<T>,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<T: Copy + Default> SideTable<T> {
+    /// The value in a slot, or the default if the slot has never been written
+    pub(crate) fn get(&self, slot: usize) -> T {
+        self.values.get(slot).copied().unwrap_or_default()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn get_mut(&mut self, slot: usize) -> &mut T {
+        if slot >= self.values.len() {
+            self.values.resize(slot + 1, T::default());
+        }
+
+        &mut self.values[slot]
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Bytes of heap memory held for the values
+    pub(crate) fn heap_size(&self) -> usize {
+        self.values.capacity() * mem::size_of::<T>()
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(all(test, feature = "std"))]
+mod unit_tests;
@@ -0,0 +1,469 @@
+//! Pointer-linked storage for cache entries
+//!
+//! Each entry lives in its own heap allocation and is linked to its neighbours in the recency order by raw pointers, so
+//! following a link is a single dereference rather than an index into a `Vec`.  Allocations freed by removal are kept
+//! and reused by the next insertion, so a full cache does not allocate in steady state.
+//!
+//! Every method that takes a [`Handle`] requires it to refer to a live entry of the same list.  The cache maintains this
+//! by only using handles obtained from its key index, which is updated in step with the list
+use crate::{Priority, ValidationError, clock::NEVER, error::ensure, eviction::Segment};
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    marker::PhantomData,
+    mem::{self, MaybeUninit},
+    ptr::NonNull,
+};
+
+type Link<K, V> = Option<NonNull<Node<K, V>>>;
+
+/// The most entries a list can hold, bounded by the largest `Vec` of entry pointers
+pub(crate) const MAX_LEN: usize = isize::MAX as usize / mem::size_of::<usize>();
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Identifies an entry by its address
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Handle(NonNull<u8>);
+
+// SAFETY: a handle is only an address; it is never dereferenced except through the list that owns the entry, which
+// carries the appropriate `Send` and `Sync` bounds
+unsafe impl Send for Handle {}
+unsafe impl Sync for Handle {}
+
+impl Handle {
+    fn new<K, V>(ptr: NonNull<Node<K, V>>) -> Self {
+        Handle(ptr.cast())
+    }
+
+    fn ptr<K, V>(self) -> NonNull<Node<K, V>> {
+        self.0.cast()
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// An entry in the recency order.  `prev` points towards the most recently used entry and `next` towards the least
+/// recently used
+pub(crate) struct Node<K, V> {
+    /// The key's hash, kept so that the key never needs to be hashed again
+    pub(crate) hash: u64,
+    /// When the entry was last used, if the cache keeps track
+    pub(crate) last_access: u64,
+    /// Part of the cache that the entry belongs to, under eviction modes that divide it
+    pub(crate) segment: Segment,
+    /// Set while the entry is exempt from eviction
+    pub(crate) pinned: bool,
+    /// Number of [`EntryGuard`](crate::EntryGuard)s held on the entry, which is exempt from eviction while any are
+    pub(crate) guards: u32,
+    /// Tier that eviction takes the entry from
+    pub(crate) priority: Priority,
+    /// When the entry expires, on the cache's clock, or [`NEVER`]
+    pub(crate) expires_at: u64,
+    /// The time-to-live that set `expires_at`, in nanoseconds, or [`NEVER`] if using the entry never renews it
+    pub(crate) ttl: u64,
+    /// When the entry expires unless it is used again, on the cache's clock, or [`NEVER`]
+    pub(crate) idle_at: u64,
+    /// When the entry's value was stored, on the cache's clock, or zero if the cache had no clock
+    pub(crate) inserted_at: u64,
+    /// When the entry was last stored or fetched, on the cache's clock, or zero if the cache had no clock
+    pub(crate) accessed_at: u64,
+    /// How long the entry's value took to load, in nanoseconds, or zero if unknown or already being refreshed early
+    #[cfg(feature = "std")]
+    pub(crate) load_cost: u64,
+    pub(crate) key: K,
+    pub(crate) value: V,
+    prev: Link<K, V>,
+    next: Link<K, V>,
+    /// Position of this entry in the list's `live` array
+    live_idx: usize,
+    /// Number of the allocation holding this entry, which addresses it in a [`SideTable`](super::SideTable)
+    slot: usize,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Doubly linked recency list of individually allocated entries
+pub(crate) struct PointerList<K, V> {
+    head: Link<K, V>,
+    tail: Link<K, V>,
+    len: usize,
+    allocations: Allocations,
+    _owns: PhantomData<Box<Node<K, V>>>,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Every allocation of a list.  The type knows nothing of the keys and values, so dropping a list only drops them and
+/// does not need what they borrow to be alive, as with a `Vec`.  The functions that free the allocations are
+/// instantiated for the list's entry type when it is created
+struct Allocations {
+    /// Every live entry in no particular order, so that entries can be picked at random
+    live: Vec<Handle>,
+    /// Allocations that currently hold no entry, with their slot numbers
+    spare: Vec<(Handle, usize)>,
+    /// Drops the entry held by a live allocation and frees it
+    drop_live: unsafe fn(Handle),
+    /// Frees a spare allocation
+    free_spare: unsafe fn(Handle),
+}
+
+// SAFETY: the list owns its entries outright, so it may cross threads whenever the keys and values can
+unsafe impl<K: Send, V: Send> Send for PointerList<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for PointerList<K, V> {}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V> PointerList<K, V> {
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        PointerList {
+            head: None,
+            tail: None,
+            len: 0,
+            allocations: Allocations {
+                live: Vec::with_capacity(capacity),
+                spare: Vec::with_capacity(capacity),
+                drop_live: drop_live::<K, V>,
+                free_spare: free_spare::<K, V>,
+            },
+            _owns: PhantomData,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Handle of the most recently used entry
+    pub(crate) fn head(&self) -> Option<Handle> {
+        self.head.map(Handle::new)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Handle of the least recently used entry
+    pub(crate) fn tail(&self) -> Option<Handle> {
+        self.tail.map(Handle::new)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Position of an entry in a [`SideTable`](super::SideTable), which is the number of its allocation
+    pub(crate) fn slot(&self, handle: Handle) -> usize {
+        self.node(handle).slot
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn node(&self, handle: Handle) -> &Node<K, V> {
+        // SAFETY: the handle refers to a live entry of this list
+        unsafe { handle.ptr().as_ref() }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn node_mut(&mut self, handle: Handle) -> &mut Node<K, V> {
+        // SAFETY: the handle refers to a live entry of this list, which is borrowed mutably
+        unsafe { handle.ptr().as_mut() }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Stores a new entry as the most recently used, reusing a spare allocation if there is one, and returns its handle
+    pub(crate) fn push_front(&mut self, hash: u64, key: K, value: V) -> Handle {
+        // While no allocation is spare, every allocation holds a live entry, so the new one is numbered after them
+        let (ptr, slot) = self.allocations.spare.pop().map_or_else(
+            || {
+                let ptr = NonNull::from(Box::leak(Box::<MaybeUninit<Node<K, V>>>::new_uninit())).cast();
+                (ptr, self.len)
+            },
+            |(handle, slot)| (handle.ptr(), slot),
+        );
+
+        // SAFETY: spare allocations hold no entry, so writing does not leak or overwrite a live value
+        unsafe {
+            ptr.as_ptr().write(Node {
+                hash,
+                last_access: 0,
+                segment: Segment::Probation,
+                pinned: false,
+                guards: 0,
+                priority: Priority::Normal,
+                expires_at: NEVER,
+                ttl: NEVER,
+                idle_at: NEVER,
+                inserted_at: 0,
+                accessed_at: 0,
+                #[cfg(feature = "std")]
+                load_cost: 0,
+                key,
+                value,
+                prev: None,
+                next: None,
+                live_idx: self.allocations.live.len(),
+                slot,
+            })
+        };
+
+        self.allocations.live.push(Handle::new(ptr));
+        self.len += 1;
+        self.link_front(ptr);
+        Handle::new(ptr)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an entry, returning its key and value.  The allocation is kept for reuse
+    pub(crate) fn remove(&mut self, handle: Handle) -> (K, V) {
+        let ptr = handle.ptr();
+        self.unlink(ptr);
+
+        // SAFETY: the entry is live and, now that it is unlinked, is moved out exactly once before its allocation
+        // becomes spare
+        let node = unsafe { ptr.as_ptr().read() };
+        self.allocations.live.swap_remove(node.live_idx);
+
+        if let Some(&moved) = self.allocations.live.get(node.live_idx) {
+            // SAFETY: every handle in `live` is a live entry of this list
+            unsafe { (*moved.ptr::<K, V>().as_ptr()).live_idx = node.live_idx };
+        }
+
+        self.allocations.spare.push((handle, node.slot));
+        self.len -= 1;
+        (node.key, node.value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Makes an entry the most recently used
+    pub(crate) fn move_to_front(&mut self, handle: Handle) {
+        let ptr = handle.ptr();
+
+        if self.head != Some(ptr) {
+            self.unlink(ptr);
+            self.link_front(ptr);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Makes an entry the least recently used
+    pub(crate) fn move_to_back(&mut self, handle: Handle) {
+        let ptr = handle.ptr();
+
+        if self.tail != Some(ptr) {
+            self.unlink(ptr);
+            self.link_back(ptr);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Places an entry immediately behind `anchor`, on the least recently used side
+    pub(crate) fn move_behind(&mut self, handle: Handle, anchor: Handle) {
+        let (ptr, anchor) = (handle.ptr(), anchor.ptr::<K, V>());
+
+        // SAFETY: `anchor` is a live entry of this list
+        if ptr == anchor || unsafe { anchor.as_ref().next } == Some(ptr) {
+            return;
+        }
+
+        self.unlink(ptr);
+
+        // SAFETY: `ptr`, `anchor` and the entry behind `anchor` are live entries of this list
+        unsafe {
+            let next = anchor.as_ref().next;
+            (*ptr.as_ptr()).prev = Some(anchor);
+            (*ptr.as_ptr()).next = next;
+            (*anchor.as_ptr()).next = Some(ptr);
+
+            match next {
+                Some(n) => (*n.as_ptr()).prev = Some(ptr),
+                None => self.tail = Some(ptr),
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns an entry's neighbour on the most recently used side
+    pub(crate) fn prev(&self, handle: Handle) -> Option<Handle> {
+        self.node(handle).prev.map(Handle::new)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Picks an entry using the random number `seed`, or returns `None` if the list is empty
+    pub(crate) fn sample(&self, seed: u64) -> Option<Handle> {
+        if self.len == 0 {
+            return None;
+        }
+
+        Some(self.allocations.live[(seed % self.len as u64) as usize])
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Number of storage positions that [`PointerList::handle_at`] accepts
+    pub(crate) fn positions(&self) -> usize {
+        self.allocations.live.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the entry at `position` in the live array.  Removing an entry moves the last entry into its position
+    pub(crate) fn handle_at(&self, position: usize) -> Option<Handle> {
+        self.allocations.live.get(position).copied()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes every entry, keeping the allocations
+    pub(crate) fn clear(&mut self) {
+        while let Some(handle) = self.head() {
+            self.remove(handle);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Iterates over the handles and entries from most to least recently used
+    pub(crate) fn iter_from_head(&self) -> impl Iterator<Item = (Handle, &Node<K, V>)> {
+        let mut cursor = self.head;
+
+        core::iter::from_fn(move || {
+            let ptr = cursor?;
+            // SAFETY: every link points at a live entry of this list, which stays borrowed for the iterator's lifetime
+            let node = unsafe { ptr.as_ref() };
+            cursor = node.next;
+            Some((Handle::new(ptr), node))
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Iterates over the handles and entries from least to most recently used
+    pub(crate) fn iter_from_tail(&self) -> impl Iterator<Item = (Handle, &Node<K, V>)> {
+        let mut cursor = self.tail;
+
+        core::iter::from_fn(move || {
+            let ptr = cursor?;
+            // SAFETY: every link points at a live entry of this list, which stays borrowed for the iterator's lifetime
+            let node = unsafe { ptr.as_ref() };
+            cursor = node.prev;
+            Some((Handle::new(ptr), node))
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Consumes the list, yielding the keys and values from most to least recently used
+    pub(crate) fn into_entries(mut self) -> impl Iterator<Item = (K, V)> {
+        let mut entries = Vec::with_capacity(self.len);
+
+        while let Some(handle) = self.head() {
+            entries.push(self.remove(handle));
+        }
+
+        entries.into_iter()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Walks the recency list, describing the first inconsistency found
+    pub(crate) fn validate(&self) -> Result<(), ValidationError> {
+        let mut expected_prev = None;
+        let mut count = 0;
+
+        for (handle, node) in self.iter_from_head() {
+            ensure!(node.prev == expected_prev, "entry {handle:?} has a broken prev link");
+            ensure!(count < self.len, "recency list is longer than the number of entries");
+            expected_prev = Some(handle.ptr());
+            count += 1;
+        }
+
+        ensure!(self.tail == expected_prev, "tail does not point at the last entry in the recency list");
+        ensure!(count == self.len, "recency list does not reach every entry");
+        ensure!(self.allocations.live.len() == self.len, "live array does not hold every entry");
+
+        for (idx, &handle) in self.allocations.live.iter().enumerate() {
+            ensure!(self.node(handle).live_idx == idx, "entry {handle:?} has a stale live index");
+        }
+
+        Ok(())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Bytes of heap memory held for entries, excluding any owned by the keys and values themselves
+    pub(crate) fn heap_size(&self) -> usize {
+        let Allocations { live, spare, .. } = &self.allocations;
+        let handles = live.capacity() * mem::size_of::<Handle>() + spare.capacity() * mem::size_of::<(Handle, usize)>();
+        handles + (self.len + spare.len()) * mem::size_of::<Node<K, V>>()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Number of node allocations held, whether live or spare
+    #[cfg(all(test, feature = "std"))]
+    pub(crate) fn allocated(&self) -> usize {
+        self.len + self.allocations.spare.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn unlink(&mut self, ptr: NonNull<Node<K, V>>) {
+        // SAFETY: `ptr` and its neighbours are live entries of this list
+        unsafe {
+            let node = ptr.as_ref();
+            let (prev, next) = (node.prev, node.next);
+
+            match prev {
+                Some(p) => (*p.as_ptr()).next = next,
+                None => self.head = next,
+            }
+
+            match next {
+                Some(n) => (*n.as_ptr()).prev = prev,
+                None => self.tail = prev,
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn link_front(&mut self, ptr: NonNull<Node<K, V>>) {
+        // SAFETY: `ptr` and the current head are live entries of this list
+        unsafe {
+            (*ptr.as_ptr()).prev = None;
+            (*ptr.as_ptr()).next = self.head;
+
+            match self.head {
+                Some(h) => (*h.as_ptr()).prev = Some(ptr),
+                None => self.tail = Some(ptr),
+            }
+        }
+
+        self.head = Some(ptr);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn link_back(&mut self, ptr: NonNull<Node<K, V>>) {
+        // SAFETY: `ptr` and the current tail are live entries of this list
+        unsafe {
+            (*ptr.as_ptr()).prev = self.tail;
+            (*ptr.as_ptr()).next = None;
+
+            match self.tail {
+                Some(t) => (*t.as_ptr()).next = Some(ptr),
+                None => self.head = Some(ptr),
+            }
+        }
+
+        self.tail = Some(ptr);
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl Drop for Allocations {
+    fn drop(&mut self) {
+        for &handle in &self.live {
+            // SAFETY: each live allocation holds an entry of the type that `drop_live` was instantiated for, and is
+            // dropped exactly once
+            unsafe { (self.drop_live)(handle) };
+        }
+
+        for &(handle, _) in &self.spare {
+            // SAFETY: each spare allocation was made for the type that `free_spare` was instantiated for
+            unsafe { (self.free_spare)(handle) };
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Drops the entry held by an allocation and frees it.  The allocation must hold a live `Node<K, V>`
+unsafe fn drop_live<K, V>(handle: Handle) {
+    // SAFETY: the allocation came from `Box<MaybeUninit<Node>>`, which is laid out as `Box<Node>`, and holds an entry
+    drop(unsafe { Box::from_raw(handle.ptr::<K, V>().as_ptr()) });
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Frees an allocation that holds no entry.  The allocation must have been made for a `Node<K, V>`
+unsafe fn free_spare<K, V>(handle: Handle) {
+    // SAFETY: the allocation came from `Box<MaybeUninit<Node>>` and holds no entry, so freeing it drops nothing
+    drop(unsafe { Box::from_raw(handle.ptr::<K, V>().as_ptr().cast::<MaybeUninit<Node<K, V>>>()) });
+}
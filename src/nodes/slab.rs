@@ -0,0 +1,423 @@
+//! Vec-backed storage for cache entries
+//!
+//! Entries live in a contiguous `Vec` and are addressed by their index, which stays stable for as long as the entry is
+//! present.  Each occupied slot carries the indices of its neighbours in the recency order, so moving an entry is a
+//! constant time relinking of indices.  Vacated slots are chained into a free list and reused by the next insertion,
+//! so once the slab has grown to the cache's capacity it never allocates again
+use crate::{Priority, ValidationError, clock::NEVER, error::ensure, eviction::Segment};
+use alloc::vec::Vec;
+use core::mem;
+
+/// Slot index.  A `u32` keeps both the links between entries and the key index small, but limits a slab to
+/// [`MAX_LEN`] entries.  The `wide-index` feature widens it to `usize` to lift that limit
+#[cfg(not(feature = "wide-index"))]
+type Index = u32;
+#[cfg(feature = "wide-index")]
+type Index = usize;
+
+/// Marks the absence of a neighbour or of a free slot
+const NIL: Index = Index::MAX;
+
+/// The most entries a slab can hold.  Every slot index must differ from [`NIL`], and no `Vec` can hold more than
+/// `isize::MAX` slots
+pub(crate) const MAX_LEN: usize = if (NIL as u128) < isize::MAX as u128 {
+    NIL as usize
+} else {
+    isize::MAX as usize
+};
+
+/// Identifies an entry by its slot index
+pub(crate) type Handle = Index;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// An entry in the recency order.  `prev` points towards the most recently used entry and `next` towards the least
+/// recently used
+pub(crate) struct Node<K, V> {
+    /// The key's hash, kept so that the key never needs to be hashed again
+    pub(crate) hash: u64,
+    /// When the entry was last used, if the cache keeps track
+    pub(crate) last_access: u64,
+    /// Part of the cache that the entry belongs to, under eviction modes that divide it
+    pub(crate) segment: Segment,
+    /// Set while the entry is exempt from eviction
+    pub(crate) pinned: bool,
+    /// Number of [`EntryGuard`](crate::EntryGuard)s held on the entry, which is exempt from eviction while any are
+    pub(crate) guards: u32,
+    /// Tier that eviction takes the entry from
+    pub(crate) priority: Priority,
+    /// When the entry expires, on the cache's clock, or [`NEVER`]
+    pub(crate) expires_at: u64,
+    /// The time-to-live that set `expires_at`, in nanoseconds, or [`NEVER`] if using the entry never renews it
+    pub(crate) ttl: u64,
+    /// When the entry expires unless it is used again, on the cache's clock, or [`NEVER`]
+    pub(crate) idle_at: u64,
+    /// When the entry's value was stored, on the cache's clock, or zero if the cache had no clock
+    pub(crate) inserted_at: u64,
+    /// When the entry was last stored or fetched, on the cache's clock, or zero if the cache had no clock
+    pub(crate) accessed_at: u64,
+    /// How long the entry's value took to load, in nanoseconds, or zero if unknown or already being refreshed early
+    #[cfg(feature = "std")]
+    pub(crate) load_cost: u64,
+    pub(crate) key: K,
+    pub(crate) value: V,
+    prev: Index,
+    next: Index,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+enum Slot<K, V> {
+    Occupied(Node<K, V>),
+    /// Holds the index of the next free slot
+    Vacant(Index),
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Slab of entries threaded onto a doubly linked recency list
+pub(crate) struct Slab<K, V> {
+    slots: Vec<Slot<K, V>>,
+    free: Index,
+    head: Index,
+    tail: Index,
+    len: usize,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V> Slab<K, V> {
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Slab {
+            slots: Vec::with_capacity(capacity),
+            free: NIL,
+            head: NIL,
+            tail: NIL,
+            len: 0,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Index of the most recently used entry
+    pub(crate) fn head(&self) -> Option<Index> {
+        (self.head != NIL).then_some(self.head)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Index of the least recently used entry
+    pub(crate) fn tail(&self) -> Option<Index> {
+        (self.tail != NIL).then_some(self.tail)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Position of the entry at `idx` in a [`SideTable`](super::SideTable), which is its slot index
+    pub(crate) fn slot(&self, idx: Index) -> usize {
+        idx as usize
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the entry at `idx`.  Panics if the slot is vacant
+    pub(crate) fn node(&self, idx: Index) -> &Node<K, V> {
+        match &self.slots[idx as usize] {
+            Slot::Occupied(node) => node,
+            Slot::Vacant(_) => panic!("slab slot {idx} is vacant"),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the entry at `idx` mutably.  Panics if the slot is vacant
+    pub(crate) fn node_mut(&mut self, idx: Index) -> &mut Node<K, V> {
+        match &mut self.slots[idx as usize] {
+            Slot::Occupied(node) => node,
+            Slot::Vacant(_) => panic!("slab slot {idx} is vacant"),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Stores a new entry as the most recently used, reusing a vacant slot if there is one, and returns its index
+    pub(crate) fn push_front(&mut self, hash: u64, key: K, value: V) -> Index {
+        let node = Node {
+            hash,
+            last_access: 0,
+            segment: Segment::Probation,
+            pinned: false,
+            guards: 0,
+            priority: Priority::Normal,
+            expires_at: NEVER,
+            ttl: NEVER,
+            idle_at: NEVER,
+            inserted_at: 0,
+            accessed_at: 0,
+            #[cfg(feature = "std")]
+            load_cost: 0,
+            key,
+            value,
+            prev: NIL,
+            next: NIL,
+        };
+
+        let idx = if self.free == NIL {
+            self.slots.push(Slot::Occupied(node));
+            (self.slots.len() - 1) as Index
+        } else {
+            let idx = self.free;
+
+            match mem::replace(&mut self.slots[idx as usize], Slot::Occupied(node)) {
+                Slot::Vacant(next_free) => self.free = next_free,
+                Slot::Occupied(_) => unreachable!("free list points at an occupied slot"),
+            }
+
+            idx
+        };
+
+        self.len += 1;
+        self.link_front(idx);
+        idx
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the entry at `idx`, returning its key and value.  The slot joins the free list
+    pub(crate) fn remove(&mut self, idx: Index) -> (K, V) {
+        self.unlink(idx);
+
+        match mem::replace(&mut self.slots[idx as usize], Slot::Vacant(self.free)) {
+            Slot::Occupied(node) => {
+                self.free = idx;
+                self.len -= 1;
+                (node.key, node.value)
+            }
+            Slot::Vacant(_) => panic!("slab slot {idx} is vacant"),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Makes the entry at `idx` the most recently used
+    pub(crate) fn move_to_front(&mut self, idx: Index) {
+        if self.head != idx {
+            self.unlink(idx);
+            self.link_front(idx);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Makes the entry at `idx` the least recently used
+    pub(crate) fn move_to_back(&mut self, idx: Index) {
+        if self.tail != idx {
+            self.unlink(idx);
+            self.link_back(idx);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Places the entry at `idx` immediately behind the entry at `anchor`, on the least recently used side
+    pub(crate) fn move_behind(&mut self, idx: Index, anchor: Index) {
+        if idx == anchor || self.node(anchor).next == idx {
+            return;
+        }
+
+        self.unlink(idx);
+        let next = self.node(anchor).next;
+        let node = self.node_mut(idx);
+        node.prev = anchor;
+        node.next = next;
+        self.node_mut(anchor).next = idx;
+
+        if next == NIL {
+            self.tail = idx;
+        } else {
+            self.node_mut(next).prev = idx;
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the neighbour of the entry at `idx` on the most recently used side
+    pub(crate) fn prev(&self, idx: Index) -> Option<Index> {
+        let prev = self.node(idx).prev;
+        (prev != NIL).then_some(prev)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Picks an entry using the random number `seed`, or returns `None` if the slab is empty.  Entries that follow a
+    /// run of vacant slots are more likely to be picked
+    pub(crate) fn sample(&self, seed: u64) -> Option<Index> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let start = (seed % self.slots.len() as u64) as usize;
+
+        (start..self.slots.len())
+            .chain(0..start)
+            .find(|&idx| matches!(self.slots[idx], Slot::Occupied(_)))
+            .map(|idx| idx as Index)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Number of storage positions, occupied or not, that [`Slab::handle_at`] accepts
+    pub(crate) fn positions(&self) -> usize {
+        self.slots.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the entry stored at `position`, if any.  Positions are stable for as long as an entry is present
+    pub(crate) fn handle_at(&self, position: usize) -> Option<Index> {
+        match self.slots.get(position)? {
+            Slot::Occupied(_) => Some(position as Index),
+            Slot::Vacant(_) => None,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes every entry, keeping the allocated storage
+    pub(crate) fn clear(&mut self) {
+        self.slots.clear();
+        self.free = NIL;
+        self.head = NIL;
+        self.tail = NIL;
+        self.len = 0;
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Iterates over the indices and entries from most to least recently used
+    pub(crate) fn iter_from_head(&self) -> impl Iterator<Item = (Index, &Node<K, V>)> {
+        let mut cursor = self.head;
+
+        core::iter::from_fn(move || {
+            let idx = cursor;
+            let node = self.get(idx)?;
+            cursor = node.next;
+            Some((idx, node))
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Iterates over the indices and entries from least to most recently used
+    pub(crate) fn iter_from_tail(&self) -> impl Iterator<Item = (Index, &Node<K, V>)> {
+        let mut cursor = self.tail;
+
+        core::iter::from_fn(move || {
+            let idx = cursor;
+            let node = self.get(idx)?;
+            cursor = node.prev;
+            Some((idx, node))
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Consumes the slab, yielding the keys and values in no particular order
+    pub(crate) fn into_entries(self) -> impl Iterator<Item = (K, V)> {
+        self.slots.into_iter().filter_map(|slot| match slot {
+            Slot::Occupied(node) => Some((node.key, node.value)),
+            Slot::Vacant(_) => None,
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Walks the recency list and the free list, describing the first inconsistency found
+    pub(crate) fn validate(&self) -> Result<(), ValidationError> {
+        let mut expected_prev = NIL;
+        let mut count = 0;
+
+        for (idx, node) in self.iter_from_head() {
+            ensure!(node.prev == expected_prev, "slot {idx} has a broken prev link");
+            ensure!(count < self.len, "recency list is longer than the number of entries");
+            expected_prev = idx;
+            count += 1;
+        }
+
+        ensure!(self.tail == expected_prev, "tail does not point at the last entry in the recency list");
+        ensure!(count == self.len, "recency list does not reach every entry");
+
+        let mut vacant = 0;
+        let mut cursor = self.free;
+
+        while cursor != NIL {
+            let Some(&Slot::Vacant(next_free)) = self.slots.get(cursor as usize) else {
+                let message = format_args!("free list points at slot {cursor}, which is not vacant");
+                return Err(ValidationError::new(message));
+            };
+            cursor = next_free;
+
+            vacant += 1;
+            ensure!(vacant <= self.slots.len(), "free list contains a cycle");
+        }
+
+        ensure!(vacant + self.len == self.slots.len(), "some slots are neither occupied nor free");
+
+        Ok(())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Bytes of heap memory held for entries, excluding any owned by the keys and values themselves
+    pub(crate) fn heap_size(&self) -> usize {
+        self.slots.capacity() * mem::size_of::<Slot<K, V>>()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Number of slots allocated, whether occupied or free
+    #[cfg(all(test, feature = "std"))]
+    pub(crate) fn allocated(&self) -> usize {
+        self.slots.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn get(&self, idx: Index) -> Option<&Node<K, V>> {
+        match self.slots.get(idx as usize)? {
+            Slot::Occupied(node) => Some(node),
+            Slot::Vacant(_) => None,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn unlink(&mut self, idx: Index) {
+        let (prev, next) = {
+            let node = self.node(idx);
+            (node.prev, node.next)
+        };
+
+        if prev == NIL {
+            self.head = next;
+        } else {
+            self.node_mut(prev).next = next;
+        }
+
+        if next == NIL {
+            self.tail = prev;
+        } else {
+            self.node_mut(next).prev = prev;
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn link_front(&mut self, idx: Index) {
+        let old_head = self.head;
+        let node = self.node_mut(idx);
+        node.prev = NIL;
+        node.next = old_head;
+
+        if old_head == NIL {
+            self.tail = idx;
+        } else {
+            self.node_mut(old_head).prev = idx;
+        }
+
+        self.head = idx;
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn link_back(&mut self, idx: Index) {
+        let old_tail = self.tail;
+        let node = self.node_mut(idx);
+        node.prev = old_tail;
+        node.next = NIL;
+
+        if old_tail == NIL {
+            self.head = idx;
+        } else {
+            self.node_mut(old_tail).next = idx;
+        }
+
+        self.tail = idx;
+    }
+}
@@ -0,0 +1,244 @@
+//! The same scenarios are run against each storage implementation compiled into the crate
+use std::{format, string::String, vec::Vec};
+
+macro_rules! node_store_tests {
+    ($backend:ident, $store:ty) => {
+        mod $backend {
+            use super::*;
+
+            type Store = $store;
+
+            fn keys_from_head(store: &Store) -> Vec<u32> {
+                store.iter_from_head().map(|(_, node)| node.key).collect()
+            }
+
+            // ---------------------------------------------------------------------------------------------------------
+            #[test]
+            fn should_reuse_vacated_slot() -> Result<(), String> {
+                let mut store = Store::with_capacity(4);
+                let _ = store.push_front(0, 1, 10);
+                let second = store.push_front(0, 2, 20);
+                let _ = store.push_front(0, 3, 30);
+
+                store.remove(second);
+                let reused = store.push_front(0, 4, 40);
+                store.validate().map_err(|e| e.to_string())?;
+
+                if reused != second {
+                    Err(format!("Expected {second:?} to be reused. Got {reused:?}"))
+                } else if store.allocated() != 3 {
+                    Err(format!("Expected 3 allocated entries. Got {}", store.allocated()))
+                } else {
+                    Ok(())
+                }
+            }
+
+            // ---------------------------------------------------------------------------------------------------------
+            #[test]
+            fn should_survive_alternating_inserts_and_removals() -> Result<(), String> {
+                let mut store = Store::with_capacity(8);
+                let mut live = Vec::new();
+
+                for i in 0..5_000u32 {
+                    // Grow to eight entries, then alternate between removing from the front, back and middle
+                    if live.len() < 8 && i % 3 != 0 {
+                        live.push((store.push_front(0, i, i), i));
+                    } else if !live.is_empty() {
+                        let (handle, key) = live.remove((i as usize * 7) % live.len());
+                        let (removed, _) = store.remove(handle);
+
+                        if removed != key {
+                            return Err(format!("Removed key {removed}. Expected {key}"));
+                        }
+                    }
+
+                    store.validate().map_err(|e| e.to_string())?;
+
+                    if store.allocated() > 8 {
+                        return Err(format!("Storage grew to {} entries despite never holding more than 8", store.allocated()));
+                    }
+                }
+
+                Ok(())
+            }
+
+            // ---------------------------------------------------------------------------------------------------------
+            #[test]
+            fn should_empty_and_refill_through_free_list() -> Result<(), String> {
+                let mut store = Store::with_capacity(4);
+                let handles: Vec<_> = (0..4).map(|i| store.push_front(0, i, i)).collect();
+
+                for handle in handles {
+                    store.remove(handle);
+                    store.validate().map_err(|e| e.to_string())?;
+                }
+
+                for i in 10..14 {
+                    store.push_front(0, i, i);
+                }
+                store.validate().map_err(|e| e.to_string())?;
+
+                if keys_from_head(&store) != [13, 12, 11, 10] {
+                    Err(format!("Unexpected recency order {:?}", keys_from_head(&store)))
+                } else if store.allocated() != 4 {
+                    Err(format!("Expected 4 allocated entries. Got {}", store.allocated()))
+                } else {
+                    Ok(())
+                }
+            }
+
+            // ---------------------------------------------------------------------------------------------------------
+            #[test]
+            fn should_give_live_entries_distinct_slots_below_the_allocation_count() -> Result<(), String> {
+                let mut store = Store::with_capacity(4);
+                let handles: Vec<_> = (0..4).map(|i| store.push_front(0, i, i)).collect();
+                let vacated = store.slot(handles[1]);
+
+                store.remove(handles[1]);
+                let reused = store.push_front(0, 4, 4);
+                let reused = store.slot(reused);
+                let _ = store.push_front(0, 5, 5);
+
+                let mut slots: Vec<_> = store.iter_from_head().map(|(handle, _)| store.slot(handle)).collect();
+                slots.sort_unstable();
+
+                match (reused == vacated, slots.as_slice()) {
+                    (true, [0, 1, 2, 3, 4]) => Ok(()),
+                    other => Err(format!("Expected the vacated slot to be reused and slots 0 to 4. Got {other:?}")),
+                }
+            }
+
+            // ---------------------------------------------------------------------------------------------------------
+            #[test]
+            fn should_move_entries_to_either_end() -> Result<(), String> {
+                let mut store = Store::with_capacity(3);
+                let a = store.push_front(0, 1, 1);
+                let _ = store.push_front(0, 2, 2);
+                let c = store.push_front(0, 3, 3);
+
+                store.move_to_front(a);
+                store.move_to_back(c);
+                store.validate().map_err(|e| e.to_string())?;
+
+                match keys_from_head(&store).as_slice() {
+                    [1, 2, 3] => Ok(()),
+                    order => Err(format!("Expected order [1, 2, 3]. Got {order:?}")),
+                }
+            }
+
+            // ---------------------------------------------------------------------------------------------------------
+            #[test]
+            fn should_move_entries_behind_others() -> Result<(), String> {
+                let mut store = Store::with_capacity(4);
+                let a = store.push_front(0, 1, 1);
+                let b = store.push_front(0, 2, 2);
+                let c = store.push_front(0, 3, 3);
+                let d = store.push_front(0, 4, 4);
+
+                store.move_behind(d, a);
+                store.move_behind(b, c);
+                store.move_behind(c, c);
+                store.validate().map_err(|e| e.to_string())?;
+
+                match (keys_from_head(&store).as_slice(), store.prev(b), store.prev(c), store.tail()) {
+                    ([3, 2, 1, 4], Some(p), None, Some(t)) if p == c && t == d => Ok(()),
+                    other => Err(format!("Expected order [3, 2, 1, 4]. Got {other:?}")),
+                }
+            }
+
+            // ---------------------------------------------------------------------------------------------------------
+            #[test]
+            fn should_only_sample_live_entries() -> Result<(), String> {
+                let mut store = Store::with_capacity(8);
+                let handles: Vec<_> = (0..8).map(|i| store.push_front(0, i, i)).collect();
+
+                for &handle in handles.iter().step_by(2) {
+                    store.remove(handle);
+                }
+                store.validate().map_err(|e| e.to_string())?;
+
+                let mut seen = Vec::new();
+
+                for seed in 0..64 {
+                    let key = store.node(store.sample(seed).ok_or("Sampled nothing from a non-empty store")?).key;
+
+                    if key % 2 == 0 {
+                        return Err(format!("Sampled removed key {key}"));
+                    }
+
+                    if !seen.contains(&key) {
+                        seen.push(key);
+                    }
+                }
+
+                while let Some(handle) = store.head() {
+                    store.remove(handle);
+                }
+
+                match (seen.len(), store.sample(0)) {
+                    (4, None) => Ok(()),
+                    other => Err(format!("Expected 4 keys sampled and nothing from an empty store. Got {other:?}")),
+                }
+            }
+        }
+    };
+}
+
+node_store_tests!(slab, super::super::slab::Slab<u32, u32>);
+#[cfg(feature = "unsafe-fast")]
+node_store_tests!(pointer, super::super::pointer::PointerList<u32, u32>);
+
+// -----------------------------------------------------------------------------------------------------------------
+/// Drives both implementations with the same pseudo-random operations and checks that they always agree
+#[cfg(feature = "unsafe-fast")]
+#[test]
+fn pointer_list_should_match_slab() -> Result<(), String> {
+    use super::{pointer::PointerList, slab::Slab};
+
+    let mut slab = Slab::with_capacity(16);
+    let mut list = PointerList::with_capacity(16);
+    let mut live = Vec::new();
+    let mut seed = 0x2545_f491_u32;
+
+    for step in 0..20_000u32 {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+
+        let pick = if live.is_empty() { 0 } else { seed as usize % live.len() };
+
+        match seed % 6 {
+            0 | 1 if live.len() < 16 => live.push((slab.push_front(0, step, step), list.push_front(0, step, step))),
+            2 if !live.is_empty() => {
+                let (s, p) = live.remove(pick);
+
+                if slab.remove(s) != list.remove(p) {
+                    return Err(format!("Removals diverged at step {step}"));
+                }
+            }
+            3 if !live.is_empty() => {
+                slab.move_to_front(live[pick].0);
+                list.move_to_front(live[pick].1);
+            }
+            4 if !live.is_empty() => {
+                slab.move_to_back(live[pick].0);
+                list.move_to_back(live[pick].1);
+            }
+            5 if !live.is_empty() => {
+                let anchor = (pick + 1) % live.len();
+                slab.move_behind(live[pick].0, live[anchor].0);
+                list.move_behind(live[pick].1, live[anchor].1);
+            }
+            _ => (),
+        }
+
+        list.validate().map_err(|e| e.to_string())?;
+
+        let slab_keys = slab.iter_from_head().map(|(_, n)| n.key);
+        if !slab_keys.eq(list.iter_from_head().map(|(_, n)| n.key)) {
+            return Err(format!("Recency order diverged at step {step}"));
+        }
+    }
+
+    Ok(())
+}
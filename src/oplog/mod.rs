@@ -0,0 +1,393 @@
+//! Append-only operation logs
+//!
+//! A [`LoggedLruCache`] appends a record to a log file for every `put` and `remove` it makes, rather than writing out
+//! the whole cache from time to time as a snapshot would.  At startup, [`LruCache::recover`] replays the log into a new
+//! cache, so the cache ends up with the same contents and its recency order follows the order of the records.
+//!
+//! The log starts with magic bytes and a format version.  Each record is framed by its length and a CRC-32 checksum of
+//! its body, which holds a sequence number, the key and either the value or, for a removal, a tombstone.  A record cut
+//! short by a crash fails its checksum, so replaying stops there and [`LoggedLruCache::open`] truncates the log back to
+//! the last whole record before appending to it.  A write that fails part way through a record is cut off straight
+//! away, so that the records written after it can still be replayed.  If even that fails, every later write returns an
+//! error until the log is reopened.
+//!
+//! The log grows with every write, including overwrites of keys already logged.  [`LoggedLruCache::compact`] rewrites
+//! it to hold one record per item in the cache
+use crate::{DefaultHashBuilder, LruCache, SnapshotError, snapshot::bincode_options};
+use bincode::Options;
+use core::hash::{BuildHasher, Hash};
+use serde::{Serialize, de::DeserializeOwned};
+use std::{
+    borrow::Borrow,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+const MAGIC: &[u8; 4] = b"LRUL";
+const FORMAT_VERSION: u8 = 1;
+const HEADER_LEN: u64 = MAGIC.len() as u64 + 1;
+
+/// Length of the length and checksum fields that precede each record's body
+const FRAME_LEN: usize = 8;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// CRC-32 (IEEE) checksum of a record's body
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+
+    !crc
+}
+
+/// Frames one record.  `value` is `None` for a removal
+fn encode_record<K, V>(seq: u64, key: &K, value: Option<&V>) -> Result<Vec<u8>, SnapshotError>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    let body = bincode_options().serialize(&(seq, key, value))?;
+    let mut frame = Vec::with_capacity(FRAME_LEN + body.len());
+    frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&crc32(&body).to_le_bytes());
+    frame.extend_from_slice(&body);
+    Ok(frame)
+}
+
+/// Appends one framed record to `writer`.  `value` is `None` for a removal
+fn write_record<K, V, W>(writer: &mut W, seq: u64, key: &K, value: Option<&V>) -> Result<(), SnapshotError>
+where
+    K: Serialize,
+    V: Serialize,
+    W: Write,
+{
+    // One write per record, so that a crash can only tear the last one
+    writer.write_all(&encode_record(seq, key, value)?)?;
+    Ok(())
+}
+
+/// Reads the next record, returning `None` at the end of the log or at a record that is incomplete or damaged
+fn read_record<K, V, R>(reader: &mut R) -> Result<Option<(u64, K, Option<V>)>, SnapshotError>
+where
+    K: DeserializeOwned,
+    V: DeserializeOwned,
+    R: Read,
+{
+    let mut frame = [0u8; FRAME_LEN];
+
+    match reader.read_exact(&mut frame) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+
+    let len = u32::from_le_bytes([frame[0], frame[1], frame[2], frame[3]]) as u64;
+    let checksum = u32::from_le_bytes([frame[4], frame[5], frame[6], frame[7]]);
+
+    // Reading through `take` stops a damaged length from allocating more than the log holds
+    let mut body = Vec::new();
+    reader.take(len).read_to_end(&mut body)?;
+
+    if body.len() as u64 != len || crc32(&body) != checksum {
+        return Ok(None);
+    }
+
+    Ok(bincode_options().deserialize(&body).ok())
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The file a log is appended to, behind a trait so that tests can make writes fail part way through
+trait LogFile: Write + Send + Sync {
+    /// Cuts the file back to `len` bytes and moves the write position there
+    fn truncate(&mut self, len: u64) -> io::Result<()>;
+
+    fn sync(&self) -> io::Result<()>;
+}
+
+impl LogFile for File {
+    fn truncate(&mut self, len: u64) -> io::Result<()> {
+        self.set_len(len)?;
+        self.seek(SeekFrom::Start(len)).map(|_| ())
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        self.sync_data()
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The result of replaying a log
+struct Replayed<K, V, S> {
+    cache: LruCache<K, V, S>,
+    /// The sequence number for the next record
+    next_seq: u64,
+    /// The length of the log up to the end of the last whole record
+    valid_len: u64,
+}
+
+/// Replays the log in `file` into a new cache
+fn replay<K, V, S>(file: &File, capacity: NonZeroUsize) -> Result<Replayed<K, V, S>, SnapshotError>
+where
+    K: Eq + Hash + DeserializeOwned,
+    V: DeserializeOwned,
+    S: BuildHasher + Default,
+{
+    let mut reader = BufReader::new(file);
+    let mut header = [0u8; HEADER_LEN as usize];
+
+    reader.read_exact(&mut header).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => SnapshotError::InvalidHeader,
+        _ => SnapshotError::Io(e),
+    })?;
+
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(SnapshotError::InvalidHeader);
+    } else if header[MAGIC.len()] != FORMAT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion(header[MAGIC.len()]));
+    }
+
+    let mut replayed = Replayed {
+        cache: LruCache::with_hasher(capacity, S::default()),
+        next_seq: 0,
+        valid_len: HEADER_LEN,
+    };
+
+    while let Some((seq, key, value)) = read_record::<K, V, _>(&mut reader)? {
+        // Sequence numbers only increase, so one that does not belongs to a damaged record
+        if seq < replayed.next_seq {
+            break;
+        }
+
+        match value {
+            Some(value) => replayed.cache.put(key, value),
+            None => replayed.cache.remove(&key),
+        };
+
+        replayed.next_seq = seq + 1;
+        replayed.valid_len = reader.stream_position()?;
+    }
+
+    Ok(replayed)
+}
+
+/// Creates a log holding just the header at `path`, replacing any file already there
+fn create_log(path: &Path) -> Result<File, SnapshotError> {
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+    file.write_all(MAGIC)?;
+    file.write_all(&[FORMAT_VERSION])?;
+    Ok(file)
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> LruCache<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    /// Restores a cache with the given capacity by replaying the operation log at `path`, written by a
+    /// [`LoggedLruCache`].  Items are inserted in the order they were logged, so the most recently written items are
+    /// the most recently used.  Replaying stops at the first record that is incomplete or fails its checksum
+    pub fn recover(path: impl AsRef<Path>, capacity: NonZeroUsize) -> Result<Self, SnapshotError>
+    where
+        K: DeserializeOwned,
+        V: DeserializeOwned,
+    {
+        Ok(replay(&File::open(path)?, capacity)?.cache)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache that logs every `put` and `remove` to an append-only file
+pub struct LoggedLruCache<K, V, S = DefaultHashBuilder> {
+    cache: LruCache<K, V, S>,
+    path: PathBuf,
+    log: Box<dyn LogFile>,
+    /// The length of the log up to the end of the last whole record
+    log_len: u64,
+    /// Set if a torn record could not be cut off, after which nothing more is written
+    damaged: bool,
+    next_seq: u64,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> LoggedLruCache<K, V, S>
+where
+    K: Eq + Hash + Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+    S: BuildHasher + Default,
+{
+    /// Opens the log at `path`, replaying it into a cache with the given capacity, or starts a new log if there is no
+    /// file at `path`.  Anything after the last whole record is cut off, so that new records follow on from it
+    pub fn open(path: impl AsRef<Path>, capacity: NonZeroUsize) -> Result<Self, SnapshotError> {
+        let path = path.as_ref().to_path_buf();
+
+        let (cache, log, log_len, next_seq) = match OpenOptions::new().read(true).write(true).open(&path) {
+            Ok(mut log) => {
+                let replayed = replay(&log, capacity)?;
+                log.truncate(replayed.valid_len)?;
+                (replayed.cache, log, replayed.valid_len, replayed.next_seq)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                (LruCache::with_hasher(capacity, S::default()), create_log(&path)?, HEADER_LEN, 0)
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(LoggedLruCache {
+            cache,
+            path,
+            log: Box::new(log),
+            log_len,
+            damaged: false,
+            next_seq,
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a reference to an item, making it the most recently used.  Nothing is logged
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.get(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetch a reference to an item without changing its position in the recency order
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.peek(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains the key. The item's position in the recency order is not changed
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.contains_key(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Logs the item and then inserts it, returning the value it replaced.  If the record cannot be written, the error
+    /// is returned and the cache is not changed
+    pub fn put(&mut self, key: K, new_value: V) -> Result<Option<V>, SnapshotError> {
+        self.append(&key, Some(&new_value))?;
+        Ok(self.cache.put(key, new_value))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Logs the removal and then removes the item, returning its value if it was present.  If the record cannot be
+    /// written, the error is returned and the cache is not changed
+    pub fn remove(&mut self, key: &K) -> Result<Option<V>, SnapshotError> {
+        self.append(key, None)?;
+        Ok(self.cache.remove(key))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Appends a record to the log.  If the write fails, whatever part of the record reached the file is cut off, so
+    /// that the next record follows the last whole one and is not lost behind a torn record when the log is replayed
+    fn append(&mut self, key: &K, value: Option<&V>) -> Result<(), SnapshotError> {
+        if self.damaged {
+            return Err(SnapshotError::Io(io::Error::other("the log holds a torn record; reopen it to repair it")));
+        }
+
+        let frame = encode_record(self.next_seq, key, value)?;
+
+        match self.log.write_all(&frame) {
+            Ok(()) => {
+                self.log_len += frame.len() as u64;
+                self.next_seq += 1;
+                Ok(())
+            }
+            Err(e) => {
+                self.damaged = self.log.truncate(self.log_len).is_err();
+                Err(e.into())
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Rewrites the log to hold one record for each item in the cache, from least to most recently used.  The new log
+    /// is written to a temporary file that replaces the old one once complete, so a crash during compaction leaves the
+    /// old log intact
+    pub fn compact(&mut self) -> Result<(), SnapshotError> {
+        let file_name = self.path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        let temp = self.path.with_file_name(format!(".{file_name}.{}.tmp", std::process::id()));
+
+        let result = (|| {
+            let mut log = create_log(&temp)?;
+            let mut seq = self.next_seq;
+
+            for (key, value) in self.cache.iter_lru_first() {
+                write_record(&mut log, seq, key, Some(value))?;
+                seq += 1;
+            }
+
+            log.sync_all()?;
+            fs::rename(&temp, &self.path)?;
+            let log_len = log.stream_position()?;
+            Ok((log, log_len, seq))
+        })();
+
+        match result {
+            Ok((log, log_len, seq)) => {
+                self.log = Box::new(log);
+                self.log_len = log_len;
+                self.damaged = false;
+                self.next_seq = seq;
+                Ok(())
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&temp);
+                Err(e)
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Flushes the records written so far to the disk
+    pub fn sync(&self) -> Result<(), SnapshotError> {
+        Ok(self.log.sync()?)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in the cache
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains no items
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the cache can hold
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.cache.capacity()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the in-memory cache
+    pub fn cache(&self) -> &LruCache<K, V, S> {
+        &self.cache
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,212 @@
+use super::*;
+use crate::test_utils::TempDir;
+
+const CAPACITY: NonZeroUsize = NonZeroUsize::new(3).unwrap();
+
+fn lru_first<S: BuildHasher>(c: &LruCache<u32, String, S>) -> Vec<(u32, String)> {
+    c.iter_lru_first().map(|(k, v)| (*k, v.clone())).collect()
+}
+
+/// A log file whose first write stops half way through and fails, as it would on a full disk
+struct TearingFile {
+    file: File,
+    torn: bool,
+    truncate_fails: bool,
+}
+
+impl TearingFile {
+    /// Opens the log at `path` for appending, in place of the file a cache opened
+    fn install(c: &mut LoggedLruCache<u32, String>, path: &Path, truncate_fails: bool) -> Result<(), String> {
+        let mut file = OpenOptions::new().write(true).open(path).map_err(|e| e.to_string())?;
+        file.seek(SeekFrom::End(0)).map_err(|e| e.to_string())?;
+        c.log = Box::new(TearingFile {
+            file,
+            torn: false,
+            truncate_fails,
+        });
+        Ok(())
+    }
+}
+
+impl Write for TearingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.torn {
+            return self.file.write(buf);
+        }
+
+        self.torn = true;
+        self.file.write_all(&buf[..buf.len() / 2])?;
+        Err(io::Error::other("no space left on device"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl LogFile for TearingFile {
+    fn truncate(&mut self, len: u64) -> io::Result<()> {
+        match self.truncate_fails {
+            true => Err(io::Error::other("I/O error")),
+            false => self.file.truncate(len),
+        }
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        self.file.sync()
+    }
+}
+
+fn log_len(path: &Path) -> Result<u64, String> {
+    fs::metadata(path).map(|m| m.len()).map_err(|e| e.to_string())
+}
+
+/// Fills a log with a workload that overwrites, removes and evicts items
+fn write_workload(path: &Path) -> Result<Vec<(u32, String)>, String> {
+    let mut c = LoggedLruCache::<u32, String>::open(path, CAPACITY).map_err(|e| e.to_string())?;
+
+    for key in 0..5 {
+        c.put(key, format!("value-{key}")).map_err(|e| e.to_string())?;
+    }
+
+    c.put(3, String::from("updated")).map_err(|e| e.to_string())?;
+    c.remove(&4).map_err(|e| e.to_string())?;
+    c.put(1, String::from("revived")).map_err(|e| e.to_string())?;
+
+    Ok(lru_first(c.cache()))
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_recover_contents_and_recency_order_from_log() -> Result<(), String> {
+    let dir = TempDir::new("oplog-replay");
+    let path = dir.path().join("cache.log");
+    let expected = write_workload(&path)?;
+
+    let recovered: LruCache<u32, String> = LruCache::recover(&path, CAPACITY).map_err(|e| e.to_string())?;
+
+    // Expected, least recently used first: 2, 3 (updated) and 1 (revived)
+    match lru_first(&recovered) {
+        entries if entries == expected && entries.len() == 3 => Ok(()),
+        other => Err(format!("Expected {expected:?}. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_compact_log_to_live_entries() -> Result<(), String> {
+    let dir = TempDir::new("oplog-compact");
+    let path = dir.path().join("cache.log");
+    write_workload(&path)?;
+    let len_before = log_len(&path)?;
+
+    let mut c = LoggedLruCache::<u32, String>::open(&path, CAPACITY).map_err(|e| e.to_string())?;
+    c.compact().map_err(|e| e.to_string())?;
+    let len_after = log_len(&path)?;
+
+    // Writes after compaction are appended to the new log
+    c.put(7, String::from("after")).map_err(|e| e.to_string())?;
+    let expected = lru_first(c.cache());
+    drop(c);
+
+    let recovered: LruCache<u32, String> = LruCache::recover(&path, CAPACITY).map_err(|e| e.to_string())?;
+
+    match (len_after < len_before, lru_first(&recovered), dir.file_names().len()) {
+        (true, entries, 1) if entries == expected => Ok(()),
+        other => Err(format!("Expected (true, {expected:?}, 1). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_skip_torn_final_record() -> Result<(), String> {
+    let dir = TempDir::new("oplog-torn");
+    let path = dir.path().join("cache.log");
+
+    {
+        let mut c = LoggedLruCache::<u32, String>::open(&path, CAPACITY).map_err(|e| e.to_string())?;
+        c.put(1, String::from("one")).map_err(|e| e.to_string())?;
+        c.put(2, String::from("two")).map_err(|e| e.to_string())?;
+    }
+
+    // Cut the last record short, as a crash part way through writing it would
+    let file = OpenOptions::new().write(true).open(&path).map_err(|e| e.to_string())?;
+    file.set_len(log_len(&path)? - 2).map_err(|e| e.to_string())?;
+    drop(file);
+
+    let recovered: LruCache<u32, String> = LruCache::recover(&path, CAPACITY).map_err(|e| e.to_string())?;
+
+    // Reopening cuts off the torn record, so the next record can be read back
+    let mut c = LoggedLruCache::<u32, String>::open(&path, CAPACITY).map_err(|e| e.to_string())?;
+    c.put(3, String::from("three")).map_err(|e| e.to_string())?;
+    drop(c);
+
+    let reopened: LruCache<u32, String> = LruCache::recover(&path, CAPACITY).map_err(|e| e.to_string())?;
+    let keys = |c: &LruCache<u32, String>| lru_first(c).into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+
+    match (keys(&recovered), keys(&reopened)) {
+        (before, after) if before == [1] && after == [1, 3] => Ok(()),
+        other => Err(format!("Expected ([1], [1, 3]). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_reject_file_that_is_not_a_log() -> Result<(), String> {
+    let dir = TempDir::new("oplog-not-a-log");
+    let path = dir.path().join("cache.log");
+    fs::write(&path, b"not a log").map_err(|e| e.to_string())?;
+
+    match LruCache::<u32, String>::recover(&path, CAPACITY).map(|c| c.len()) {
+        Err(SnapshotError::InvalidHeader) => Ok(()),
+        other => Err(format!("Expected Err(InvalidHeader). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_keep_writes_that_follow_a_torn_record() -> Result<(), String> {
+    let dir = TempDir::new("oplog-torn-write");
+    let path = dir.path().join("cache.log");
+
+    let mut c = LoggedLruCache::<u32, String>::open(&path, CAPACITY).map_err(|e| e.to_string())?;
+    c.put(1, String::from("one")).map_err(|e| e.to_string())?;
+    TearingFile::install(&mut c, &path, false)?;
+
+    let failed = c.put(2, String::from("two")).is_err();
+    c.put(3, String::from("three")).map_err(|e| e.to_string())?;
+    c.remove(&1).map_err(|e| e.to_string())?;
+    drop(c);
+
+    let recovered: LruCache<u32, String> = LruCache::recover(&path, CAPACITY).map_err(|e| e.to_string())?;
+    let keys = lru_first(&recovered).into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+
+    match (failed, keys) {
+        (true, keys) if keys == [3] => Ok(()),
+        other => Err(format!("Expected (true, [3]). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_refuse_writes_once_a_torn_record_cannot_be_cut_off() -> Result<(), String> {
+    let dir = TempDir::new("oplog-damaged");
+    let path = dir.path().join("cache.log");
+
+    let mut c = LoggedLruCache::<u32, String>::open(&path, CAPACITY).map_err(|e| e.to_string())?;
+    c.put(1, String::from("one")).map_err(|e| e.to_string())?;
+    TearingFile::install(&mut c, &path, true)?;
+
+    let outcomes = (c.put(2, String::from("two")).is_err(), c.put(3, String::from("three")).is_err());
+    let cached = lru_first(c.cache()).into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+    drop(c);
+
+    // Reopening cuts off the torn record, after which writes succeed again
+    let mut c = LoggedLruCache::<u32, String>::open(&path, CAPACITY).map_err(|e| e.to_string())?;
+    let reopened = c.put(4, String::from("four")).is_ok();
+
+    match (outcomes, cached, reopened) {
+        ((true, true), cached, true) if cached == [1] => Ok(()),
+        other => Err(format!("Expected ((true, true), [1], true). Got {other:?}")),
+    }
+}
@@ -0,0 +1,101 @@
+//! Bulk construction using every core
+//!
+//! [`LruCache::par_fill`] hashes the items in parallel, then splits them into shards by hash so that each shard can
+//! discard duplicate keys on its own thread.  The surviving items are sorted back into input order in parallel, and
+//! only the final linking of entries into the cache happens on one thread, without any further hashing or lookups
+use crate::LruCache;
+use alloc::vec::Vec;
+use core::{
+    hash::{BuildHasher, Hash},
+    num::NonZeroUsize,
+};
+use hashbrown::HashTable;
+use rayon::prelude::*;
+
+/// Item tagged with its position in the input and its key's hash
+type Tagged<K, V> = (usize, u64, K, V);
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> LruCache<K, V, S>
+where
+    K: Eq + Hash + Send,
+    V: Send,
+    S: BuildHasher + Default + Sync,
+{
+    /// Builds a cache from `items` in parallel.
+    ///
+    /// The result is the same as calling [`put`](LruCache::put) with each item in turn: when a key appears more than
+    /// once the last occurrence wins, items later in the input are more recently used, and if there are more distinct
+    /// keys than `capacity`, only the most recently used are kept
+    pub fn par_fill<I>(capacity: NonZeroUsize, items: I) -> Self
+    where
+        I: IntoParallelIterator<Item = (K, V)>,
+        I::Iter: IndexedParallelIterator,
+    {
+        let hash_builder = S::default();
+
+        // Sharding only pays for itself when the shards can be processed at the same time
+        if rayon::current_num_threads() == 1 {
+            let mut cache = LruCache::with_capacity_and_hasher(capacity, 0, hash_builder);
+            items.into_par_iter().collect::<Vec<_>>().into_iter().for_each(|(k, v)| _ = cache.put(k, v));
+            return cache;
+        }
+
+        let tagged: Vec<Tagged<K, V>> = items
+            .into_par_iter()
+            .enumerate()
+            .map(|(pos, (k, v))| (pos, hash_builder.hash_one(&k), k, v))
+            .collect();
+
+        let mut survivors = dedup_keep_last(tagged);
+        survivors.par_sort_unstable_by_key(|&(pos, ..)| pos);
+
+        let kept = survivors.len().min(capacity.get());
+        let mut cache = LruCache::with_capacity_and_hasher(capacity, kept, hash_builder);
+
+        for (_, hash, k, v) in survivors.drain(survivors.len() - kept..) {
+            cache.insert_front(hash, k, v);
+        }
+
+        cache.record_len();
+        cache
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Drops every item whose key appears again later in the input.  Items are sharded by hash, so that equal keys always
+/// land in the same shard, and each shard is deduplicated on its own thread
+fn dedup_keep_last<K, V>(tagged: Vec<Tagged<K, V>>) -> Vec<Tagged<K, V>>
+where
+    K: Eq + Send,
+    V: Send,
+{
+    let shard_count = 4 * rayon::current_num_threads();
+    let mut shards: Vec<Vec<Tagged<K, V>>> = (0..shard_count).map(|_| Vec::new()).collect();
+
+    for item in tagged {
+        // The high bits are the least likely to have been used by the shard's own table
+        shards[((item.1 >> 32) % shard_count as u64) as usize].push(item);
+    }
+
+    shards
+        .into_par_iter()
+        .flat_map_iter(|shard| {
+            let mut latest: HashTable<Tagged<K, V>> = HashTable::with_capacity(shard.len());
+
+            for item in shard {
+                match latest.find_mut(item.1, |other| other.1 == item.1 && other.2 == item.2) {
+                    // Items arrive in input order, so a later occurrence always replaces an earlier one
+                    Some(existing) => *existing = item,
+                    None => _ = latest.insert_unique(item.1, item, |other| other.1),
+                }
+            }
+
+            latest.into_iter()
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,66 @@
+use super::*;
+use crate::test_utils::*;
+use std::{format, string::String, vec::Vec};
+
+fn sequential(capacity: NonZeroUsize, items: &[(String, u32)]) -> LruCache<String, u32> {
+    let mut c = LruCache::new(capacity);
+
+    for (k, v) in items {
+        c.put(k.clone(), *v);
+    }
+
+    c
+}
+
+/// Items with repeated keys, where each key's value records its position in the input
+fn items_with_duplicates(count: usize) -> Vec<(String, u32)> {
+    (0..count).map(|i| (gen_item_key((i * 7919) % (count / 3)), i as u32)).collect()
+}
+
+/// Fills a cache on thread pools of one and of four threads, checking both against sequential puts
+fn check_matches_sequential(capacity: usize, items: Vec<(String, u32)>) -> Result<(), String> {
+    let capacity = NonZeroUsize::new(capacity).unwrap();
+    let (_, expected_store, expected_order) = sequential(capacity, &items).into_parts();
+
+    for threads in [1, 4] {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().map_err(|e| e.to_string())?;
+        let parallel = pool.install(|| LruCache::<String, u32>::par_fill(capacity, items.clone()));
+        parallel.debug_validate().map_err(|e| e.to_string())?;
+
+        let (_, store, order) = parallel.into_parts();
+
+        if store != expected_store {
+            return Err(format!("Filling on {threads} threads holds different entries from sequential puts"));
+        }
+
+        if order != expected_order {
+            return Err(format!("Filling on {threads} threads gives a different recency order from sequential puts"));
+        }
+    }
+
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn par_fill_should_match_sequential_puts() -> Result<(), String> {
+    check_matches_sequential(50_000, items_with_duplicates(30_000))
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn par_fill_should_keep_most_recent_keys_when_over_capacity() -> Result<(), String> {
+    check_matches_sequential(1_000, items_with_duplicates(30_000))
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn par_fill_should_accept_empty_input() -> Result<(), String> {
+    let c = LruCache::<String, u32>::par_fill(NonZeroUsize::new(10).unwrap(), Vec::new());
+
+    if c.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Expected an empty cache. Got {} items", c.len()))
+    }
+}
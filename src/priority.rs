@@ -0,0 +1,58 @@
+//! Priority tiers for cached items
+//!
+//! Every item belongs to one of three tiers.  Eviction takes items from the lowest tier that has any, choosing between
+//! them as the eviction mode would, and only moves up a tier once the one below is empty.  The cache counts the items
+//! in each tier so that it knows the lowest tier without looking at the items
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// How reluctant the cache is to evict an item, given to [`LruCache::put_with_priority`](crate::LruCache::put_with_priority) and
+/// [`LruCache::set_priority`](crate::LruCache::set_priority)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Evicted before any other item
+    BestEffort,
+    /// The priority of items inserted without one
+    #[default]
+    Normal,
+    /// Evicted only once no other item is left to evict
+    Critical,
+}
+
+impl Priority {
+    /// Every priority, from the first evicted to the last
+    const ALL: [Priority; 3] = [Priority::BestEffort, Priority::Normal, Priority::Critical];
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Number of items in each tier
+#[derive(Debug, Default)]
+pub(crate) struct Tiers {
+    counts: [usize; 3],
+}
+
+impl Tiers {
+    pub(crate) fn add(&mut self, priority: Priority) {
+        self.counts[priority as usize] += 1;
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn remove(&mut self, priority: Priority) {
+        self.counts[priority as usize] -= 1;
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn count(&self, priority: Priority) -> usize {
+        self.counts[priority as usize]
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the lowest tier holding any items, or `None` if the cache is empty
+    pub(crate) fn lowest(&self) -> Option<Priority> {
+        Priority::ALL.into_iter().find(|&priority| self.count(priority) > 0)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn clear(&mut self) {
+        self.counts = [0; 3];
+    }
+}
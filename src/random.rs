@@ -0,0 +1,32 @@
+//! Pseudo-random numbers for the cache's random choices
+//!
+//! The numbers come from a seeded xorshift generator, which is small and fast and lets tests fix the sequence through
+//! [`LruCacheBuilder::seed`](crate::LruCacheBuilder::seed).  They are not meant to be unpredictable
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// xorshift64
+pub(crate) struct Random {
+    /// Never zero
+    state: u64,
+}
+
+impl Random {
+    pub(crate) fn new(seed: u64) -> Self {
+        Random { state: seed | 1 }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns a number drawn uniformly from `(0, 1]`
+    #[cfg(feature = "std")]
+    pub(crate) fn next_unit(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1_u64 << 53) as f64
+    }
+}
@@ -0,0 +1,66 @@
+//! Bounded buffer of deferred promotions
+//!
+//! Reads record the hash of the entry they hit instead of relinking it in the recency list.  The recorded promotions
+//! are replayed in order the next time the cache is borrowed mutably, so that concurrent readers sharing the cache
+//! through a read lock never touch the list.  Recording only needs a shared borrow; once the buffer is full, reads that
+//! cannot wait for a mutable borrow are dropped
+use crate::sync::{AtomicU64, AtomicUsize, Ordering};
+use alloc::{boxed::Box, vec::Vec};
+use core::mem;
+
+// ---------------------------------------------------------------------------------------------------------------------
+pub(crate) struct ReadBuffer {
+    slots: Box<[AtomicU64]>,
+    /// Number of slots claimed so far.  May exceed the number of slots once the buffer is full
+    claimed: AtomicUsize,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl ReadBuffer {
+    pub(crate) fn new(size: usize) -> Self {
+        ReadBuffer {
+            slots: (0..size).map(|_| AtomicU64::new(0)).collect::<Vec<_>>().into_boxed_slice(),
+            claimed: AtomicUsize::new(0),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Records a read of the entry with the given hash.  Returns `false` if the buffer is full and nothing was recorded
+    pub(crate) fn record(&self, hash: u64) -> bool {
+        let idx = self.claimed.fetch_add(1, Ordering::Relaxed);
+
+        match self.slots.get(idx) {
+            Some(slot) => {
+                slot.store(hash, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Bytes of heap memory held for recorded reads
+    pub(crate) fn heap_size(&self) -> usize {
+        mem::size_of_val(&*self.slots)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if no reads are waiting to be replayed
+    pub(crate) fn is_empty(&mut self) -> bool {
+        self.claimed.load(Ordering::Relaxed) == 0
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Discards every recorded read
+    pub(crate) fn clear(&mut self) {
+        self.claimed.store(0, Ordering::Relaxed);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Empties the buffer, yielding the recorded hashes in the order they were recorded
+    pub(crate) fn drain(&mut self) -> impl Iterator<Item = u64> + '_ {
+        let recorded = self.claimed.swap(0, Ordering::Relaxed).min(self.slots.len());
+
+        self.slots[..recorded].iter().map(|slot| slot.load(Ordering::Relaxed))
+    }
+}
@@ -0,0 +1,444 @@
+//! Reading through to a loader
+//!
+//! A [`ReadThroughCache`] owns a [`CacheLoader`] and calls it whenever [`get`](ReadThroughCache::get) misses, so that
+//! callers no longer need to follow every `get` with a load and a `put`.  A value that loads successfully is cached
+//! and returned, and evicts the least recently used item if the cache is full.  A loader error is returned to the
+//! caller and nothing is cached, so the next `get` for the key tries again.
+//!
+//! Hits are served from the cache without calling the loader.  The time each load takes is counted in
+//! [`CacheStats::load_time`](crate::CacheStats::load_time), as it is for `AsyncLruCache`
+//!
+//! # Negative caching
+//!
+//! A loader whose most expensive answer is that a key has no value can have that answer remembered.  After
+//! [`ReadThroughCache::with_negative_caching`], an error that the given function recognises as "not found" is kept
+//! for its own time-to-live, usually much shorter than that of values, and lookups of the key return it without
+//! calling the loader until it expires.  Any other error is transient and is never kept.  [`ReadThroughCache::lookup`]
+//! tells a fresh "not found" from a remembered one, and both from a value
+//!
+//! # Refresh-ahead
+//!
+//! A hot key whose item expires costs a miss and a load on the request path, however often it is used.  After
+//! [`ReadThroughCache::with_refresh_ahead`], a `get` that finds an item in the last part of its time-to-live still
+//! returns the item straight away, but flags the key to be reloaded by the next
+//! [`run_pending_tasks`](ReadThroughCache::run_pending_tasks).  A reload that succeeds replaces the value and restarts
+//! its time-to-live, so a key that is used and refreshed often enough never misses.  A reload that fails leaves the old
+//! value in place until it expires
+//!
+//! # Stale-while-revalidate
+//!
+//! After [`ReadThroughCache::with_stale_while_revalidate`], an item that expired less than the given grace period ago
+//! is still served, by [`get_cached`](ReadThroughCache::get_cached) as a [`CachedValue`] that says how stale it is,
+//! and its key is flagged to be reloaded by the next `run_pending_tasks` as it would be by refresh-ahead.  However many
+//! lookups find the stale item, it is reloaded once.  A stale item is only served while it is still in the cache, so
+//! one that has been evicted or purged in the meantime, or whose grace period has run out, is a miss that loads the
+//! value before returning, as without the option.
+//!
+//! Both options apply to values only.  A remembered absence expires at the end of its own time-to-live, with no grace
+//! period, and is never refreshed.  A reload that fails with an error that negative caching recognises as "not found"
+//! removes the old value and remembers the absence, since the loader has said that there is no longer a value to
+//! serve.  A reload that fails with any other error leaves the old value to be served until it expires, and then
+//! stale until the grace period runs out, when the next lookup flags it again
+use crate::{DefaultHashBuilder, LruCache};
+use std::{
+    borrow::Borrow,
+    collections::HashSet,
+    hash::{BuildHasher, Hash},
+    mem,
+    num::NonZeroUsize,
+    time::Duration,
+};
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Loads the value for a key missing from a [`ReadThroughCache`].  Implemented for closures taking the key and
+/// returning a `Result`
+pub trait CacheLoader<K, V> {
+    type Error;
+
+    fn load(&self, key: &K) -> Result<V, Self::Error>;
+}
+
+impl<K, V, E, F> CacheLoader<K, V> for F
+where
+    F: Fn(&K) -> Result<V, E>,
+{
+    type Error = E;
+
+    fn load(&self, key: &K) -> Result<V, E> {
+        self(key)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A value returned by [`ReadThroughCache::get_cached`], which may have been served stale
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedValue<V> {
+    pub value: V,
+    /// How long ago the value expired, if it was served stale while a reload is pending
+    pub staleness: Option<Duration>,
+}
+
+impl<V> CachedValue<V> {
+    /// Returns `true` if the value has expired and is being served stale
+    pub fn is_stale(&self) -> bool {
+        self.staleness.is_some()
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The outcome of a lookup that may have been answered by negative caching, as returned by
+/// [`ReadThroughCache::lookup`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lookup<V> {
+    /// The key's value, found in the cache or just loaded
+    Found(V),
+    /// The loader has just reported that the key has no value
+    NotFound,
+    /// The loader reported that the key has no value recently enough that it was not asked again
+    NegativeHit,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The keys a loader recently reported to have no value, each with the error it reported
+struct Absent<K, E> {
+    errors: LruCache<K, E>,
+    ttl: Duration,
+    is_not_found: fn(&E) -> bool,
+    clone_error: fn(&E) -> E,
+}
+
+/// What [`ReadThroughCache::lookup`] found, with the "not found" error for [`ReadThroughCache::get`] to return
+enum Fetched<V, E> {
+    Found(CachedValue<V>),
+    NotFound(E),
+    NegativeHit(E),
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache that loads missing values with a [`CacheLoader`]
+pub struct ReadThroughCache<K, V, L, S = DefaultHashBuilder>
+where
+    L: CacheLoader<K, V>,
+{
+    cache: LruCache<K, V, S>,
+    loader: L,
+    /// Present if absences are cached, set through [`with_negative_caching`](ReadThroughCache::with_negative_caching)
+    absent: Option<Absent<K, L::Error>>,
+    /// Fraction of its time-to-live below which an item is reloaded, set through
+    /// [`with_refresh_ahead`](ReadThroughCache::with_refresh_ahead)
+    refresh_ahead: Option<f64>,
+    /// How long after expiry an item is served stale, set through
+    /// [`with_stale_while_revalidate`](ReadThroughCache::with_stale_while_revalidate)
+    stale_grace: Option<Duration>,
+    /// Keys flagged to be reloaded by [`run_pending_tasks`](ReadThroughCache::run_pending_tasks)
+    pending: HashSet<K>,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, L> ReadThroughCache<K, V, L>
+where
+    K: Eq + Hash,
+    L: CacheLoader<K, V>,
+{
+    /// Creates a cache that holds at most `capacity` items and loads missing values with `loader`
+    pub fn new(capacity: NonZeroUsize, loader: L) -> Self {
+        ReadThroughCache::with_cache(LruCache::new(capacity), loader)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, L, S> ReadThroughCache<K, V, L, S>
+where
+    K: Eq + Hash,
+    L: CacheLoader<K, V>,
+    S: BuildHasher,
+{
+    /// Loads missing values into a cache configured elsewhere, for instance by
+    /// [`LruCacheBuilder`](crate::LruCacheBuilder), whose time-to-live and other settings then apply to loaded values
+    pub fn with_cache(cache: LruCache<K, V, S>, loader: L) -> Self {
+        ReadThroughCache {
+            cache,
+            loader,
+            absent: None,
+            refresh_ahead: None,
+            stale_grace: None,
+            pending: HashSet::new(),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Remembers for `ttl` that a key has no value, whenever the loader fails with an error for which `is_not_found`
+    /// returns `true`, so that lookups of the key return a copy of the error without calling the loader.  Other errors
+    /// are never remembered.  Up to the cache's capacity of keys are remembered apart from its values, measured on the
+    /// cache's clock if it has one
+    pub fn with_negative_caching(mut self, ttl: Duration, is_not_found: fn(&L::Error) -> bool) -> Self
+    where
+        L::Error: Clone,
+    {
+        let mut errors = LruCache::new(self.cache.capacity());
+        errors.clock = self.cache.clock.clone();
+
+        self.absent = Some(Absent {
+            errors,
+            ttl,
+            is_not_found,
+            clone_error: L::Error::clone,
+        });
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Flags a key for reloading whenever a `get` finds its item with less than `fraction` of its time-to-live left,
+    /// for example `0.2` for the last fifth.  Flagged keys are reloaded by
+    /// [`run_pending_tasks`](Self::run_pending_tasks), and each key is flagged at most once until then.  Items that
+    /// never expire are never reloaded
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fraction` is not between 0 and 1
+    pub fn with_refresh_ahead(mut self, fraction: f64) -> Self {
+        assert!((0.0..=1.0).contains(&fraction), "refresh-ahead fraction must be between 0 and 1, not {fraction}");
+
+        self.refresh_ahead = Some(fraction);
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Serves an item for up to `grace` after it expires, flagged as stale, while its key waits to be reloaded by
+    /// [`run_pending_tasks`](Self::run_pending_tasks).  Once `grace` has passed, a lookup of the key misses and loads
+    /// the value before returning
+    pub fn with_stale_while_revalidate(mut self, grace: Duration) -> Self {
+        self.stale_grace = Some(grace);
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetches a copy of an item, making it the most recently used, or loads and inserts it if the key is missing.  If
+    /// the loader fails, its error is returned and nothing is cached, unless negative caching remembers it
+    pub fn get(&mut self, key: &K) -> Result<V, L::Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.get_cached(key).map(|found| found.value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetches a copy of an item like [`get`](Self::get), saying whether it was served stale
+    pub fn get_cached(&mut self, key: &K) -> Result<CachedValue<V>, L::Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        match self.fetch(key)? {
+            Fetched::Found(found) => Ok(found),
+            Fetched::NotFound(error) | Fetched::NegativeHit(error) => Err(error),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetches a copy of an item like [`get`](Self::get), but reports a key with no value as [`Lookup::NotFound`], or
+    /// [`Lookup::NegativeHit`] if negative caching answered without calling the loader.  Only transient errors are
+    /// returned as errors
+    pub fn lookup(&mut self, key: &K) -> Result<Lookup<V>, L::Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        Ok(match self.fetch(key)? {
+            Fetched::Found(found) => Lookup::Found(found.value),
+            Fetched::NotFound(_) => Lookup::NotFound,
+            Fetched::NegativeHit(_) => Lookup::NegativeHit,
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn fetch(&mut self, key: &K) -> Result<Fetched<V, L::Error>, L::Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let found = match self.stale_grace {
+            Some(grace) => self.cache.get_or_stale(key, grace).map(|(value, staleness)| CachedValue {
+                value: value.clone(),
+                staleness,
+            }),
+            None => self.cache.get_cloned(key).map(|value| CachedValue { value, staleness: None }),
+        };
+
+        if let Some(found) = found {
+            if found.is_stale() || self.is_due_for_refresh(key) {
+                self.flag_for_reload(key);
+            }
+
+            return Ok(Fetched::Found(found));
+        }
+
+        if let Some(absent) = &mut self.absent
+            && let Some(error) = absent.errors.get(key)
+        {
+            return Ok(Fetched::NegativeHit((absent.clone_error)(error)));
+        }
+
+        let stopwatch = self.cache.load_started();
+
+        match self.loader.load(key) {
+            Ok(value) => {
+                self.cache.put(key.clone(), value.clone());
+                self.cache.load_finished(key, stopwatch);
+                Ok(Fetched::Found(CachedValue { value, staleness: None }))
+            }
+            Err(error) => match &mut self.absent {
+                Some(absent) if (absent.is_not_found)(&error) => {
+                    absent.errors.put_with_ttl(key.clone(), (absent.clone_error)(&error), absent.ttl);
+                    Ok(Fetched::NotFound(error))
+                }
+                _ => Err(error),
+            },
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn is_due_for_refresh(&self, key: &K) -> bool {
+        self.refresh_ahead
+            .is_some_and(|fraction| self.cache.entry_info(key).is_some_and(|info| info.is_due_for_refresh(fraction)))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn flag_for_reload(&mut self, key: &K)
+    where
+        K: Clone,
+    {
+        if !self.pending.contains(key) {
+            self.pending.insert(key.clone());
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Reloads the keys flagged by refresh-ahead or stale-while-revalidate, then runs the cache's own
+    /// [`run_pending_tasks`](LruCache::run_pending_tasks).  A reload that succeeds replaces the value and restarts its
+    /// time-to-live.  One that fails leaves the old value, unless negative caching recognises the error as "not
+    /// found", when the value is removed and the absence remembered.  Keys whose items have been removed, or are past
+    /// any grace period, since they were flagged are not reloaded
+    pub fn run_pending_tasks(&mut self)
+    where
+        K: Clone,
+    {
+        let grace = self.stale_grace.unwrap_or_default();
+
+        for key in mem::take(&mut self.pending) {
+            if !self.cache.is_retained(&key, grace) {
+                continue;
+            }
+
+            let stopwatch = self.cache.load_started();
+
+            match self.loader.load(&key) {
+                Ok(value) => {
+                    self.cache.put(key.clone(), value);
+                    self.cache.load_finished(&key, stopwatch);
+                }
+                Err(error) => {
+                    if let Some(absent) = &mut self.absent
+                        && (absent.is_not_found)(&error)
+                    {
+                        self.cache.remove(&key);
+                        absent.errors.put_with_ttl(key, error, absent.ttl);
+                    }
+                }
+            }
+        }
+
+        self.cache.run_pending_tasks();
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetch a reference to an item without changing its position in the recency order or calling the loader
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.peek(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains the key. The item's position in the recency order is not changed
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.contains_key(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts an item without calling the loader, returning the value it replaced.  Forgets that the key had no value
+    pub fn put(&mut self, key: K, new_value: V) -> Option<V> {
+        self.forget_absence(&key);
+        self.cache.put(key, new_value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an item, returning its value if it was present.  The next `get` for the key loads it again, even if
+    /// the key was remembered to have no value
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.forget_absence(key);
+        self.cache.remove(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn forget_absence<Q>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if let Some(absent) = &mut self.absent {
+            absent.errors.remove(key);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in the cache
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains no items
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the cache can hold
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.cache.capacity()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the in-memory cache, for instance to read its statistics
+    pub fn cache(&self) -> &LruCache<K, V, S> {
+        &self.cache
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the loader
+    pub fn loader(&self) -> &L {
+        &self.loader
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Separates the cache from its loader
+    pub fn into_inner(self) -> (LruCache<K, V, S>, L) {
+        (self.cache, self.loader)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,301 @@
+use super::*;
+use crate::{LruCacheBuilder, test_utils::MockClock};
+use std::cell::RefCell;
+
+const CAPACITY: NonZeroUsize = NonZeroUsize::new(2).unwrap();
+
+/// Loads ten times the key, except for the keys listed as failing or missing, and logs every key it is asked for
+#[derive(Default)]
+struct CountingLoader {
+    loads: RefCell<Vec<u32>>,
+    failing: RefCell<Vec<u32>>,
+    missing: RefCell<Vec<u32>>,
+}
+
+impl CacheLoader<u32, u32> for CountingLoader {
+    type Error = String;
+
+    fn load(&self, key: &u32) -> Result<u32, String> {
+        self.loads.borrow_mut().push(*key);
+
+        if self.failing.borrow().contains(key) {
+            Err(format!("cannot load {key}"))
+        } else if self.missing.borrow().contains(key) {
+            Err(format!("no value for {key}"))
+        } else {
+            Ok(key * 10)
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_load_on_miss_and_not_on_hit() -> Result<(), String> {
+    let mut c = ReadThroughCache::new(CAPACITY, CountingLoader::default());
+
+    let first = c.get(&1)?;
+    let second = c.get(&1)?;
+    let stats = c.cache().stats();
+
+    match (first, second, c.loader().loads.borrow().clone(), stats.hits, stats.misses, stats.load_time.count) {
+        (10, 10, loads, 1, 1, 1) if loads == [1] => Ok(()),
+        other => Err(format!("Expected (10, 10, [1], 1, 1, 1). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_not_cache_load_failures() -> Result<(), String> {
+    let mut c = ReadThroughCache::new(CAPACITY, CountingLoader::default());
+    c.loader().failing.borrow_mut().push(7);
+
+    let failed = c.get(&7);
+    let cached_after_failure = c.contains_key(&7);
+
+    // The next get tries again, and succeeds once the loader does
+    c.loader().failing.borrow_mut().clear();
+    let retried = c.get(&7);
+
+    match (failed, cached_after_failure, retried, c.loader().loads.borrow().clone()) {
+        (Err(_), false, Ok(70), loads) if loads == [7, 7] => Ok(()),
+        other => Err(format!("Expected (Err, false, Ok(70), [7, 7]). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_evict_lru_item_when_loaded_value_is_inserted() -> Result<(), String> {
+    let mut c = ReadThroughCache::new(CAPACITY, CountingLoader::default());
+
+    c.get(&1)?;
+    c.get(&2)?;
+    c.get(&1)?;
+
+    // Loading 3 evicts 2, the least recently used, so only 2 is loaded again
+    c.get(&3)?;
+    c.get(&1)?;
+    c.get(&2)?;
+
+    match (c.loader().loads.borrow().clone(), c.cache().stats().evictions) {
+        (loads, 2) if loads == [1, 2, 3, 2] => Ok(()),
+        other => Err(format!("Expected ([1, 2, 3, 2], 2). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_accept_closure_as_loader() -> Result<(), String> {
+    let cache = LruCacheBuilder::new(CAPACITY).ttl(Duration::from_secs(60)).build();
+    let mut c = ReadThroughCache::with_cache(cache, |key: &String| Ok::<_, String>(key.len()));
+
+    match (c.get(&String::from("four")), c.cache().entry_info("four").and_then(|info| info.expires_at())) {
+        (Ok(4), Some(_)) => Ok(()),
+        other => Err(format!("Expected (Ok(4), Some(_)). Got {other:?}")),
+    }
+}
+
+/// Builds a cache that remembers for ten seconds the keys that `CountingLoader` reports missing
+fn negative_cache(clock: &MockClock) -> ReadThroughCache<u32, u32, CountingLoader> {
+    let cache = LruCacheBuilder::new(CAPACITY).clock(clock.clone()).build();
+    let is_not_found = |e: &String| e.starts_with("no value");
+    ReadThroughCache::with_cache(cache, CountingLoader::default())
+        .with_negative_caching(Duration::from_secs(10), is_not_found)
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_remember_missing_key_until_negative_ttl_expires() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut c = negative_cache(&clock);
+    c.loader().missing.borrow_mut().push(5);
+
+    let first = c.lookup(&5);
+    clock.advance(Duration::from_secs(9));
+    let remembered = (c.lookup(&5), c.get(&5));
+
+    // Once the absence expires the loader is asked again, and by now it has a value
+    c.loader().missing.borrow_mut().clear();
+    clock.advance(Duration::from_secs(2));
+    let expired = c.lookup(&5);
+
+    match (first, remembered, expired, c.loader().loads.borrow().clone()) {
+        (Ok(Lookup::NotFound), (Ok(Lookup::NegativeHit), Err(e)), Ok(Lookup::Found(50)), loads)
+            if e == "no value for 5" && loads == [5, 5] =>
+        {
+            Ok(())
+        }
+        other => Err(format!("Expected (NotFound, (NegativeHit, Err), Found(50), [5, 5]). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_never_remember_transient_errors() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut c = negative_cache(&clock);
+    c.loader().failing.borrow_mut().push(7);
+
+    let outcomes = (c.lookup(&7), c.lookup(&7), c.get(&7));
+
+    match (outcomes, c.loader().loads.borrow().clone()) {
+        ((Err(_), Err(_), Err(_)), loads) if loads == [7, 7, 7] => Ok(()),
+        other => Err(format!("Expected ((Err, Err, Err), [7, 7, 7]). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_forget_absence_when_key_is_put_or_removed() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut c = negative_cache(&clock);
+    c.loader().missing.borrow_mut().extend([5, 6]);
+
+    c.lookup(&5)?;
+    c.lookup(&6)?;
+    c.put(5, 1);
+    c.remove(&6);
+    c.loader().missing.borrow_mut().clear();
+
+    match (c.lookup(&5), c.lookup(&6), c.loader().loads.borrow().clone()) {
+        (Ok(Lookup::Found(1)), Ok(Lookup::Found(60)), loads) if loads == [5, 6, 6] => Ok(()),
+        other => Err(format!("Expected (Found(1), Found(60), [5, 6, 6]). Got {other:?}")),
+    }
+}
+
+/// Builds a cache whose items live for ten seconds and are reloaded in their last two
+fn refreshing_cache(clock: &MockClock) -> ReadThroughCache<u32, u32, CountingLoader> {
+    let cache = LruCacheBuilder::new(CAPACITY).clock(clock.clone()).ttl(Duration::from_secs(10)).build();
+    ReadThroughCache::with_cache(cache, CountingLoader::default()).with_refresh_ahead(0.2)
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_reload_only_within_refresh_window() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut c = refreshing_cache(&clock);
+
+    c.get(&1)?;
+    clock.advance(Duration::from_secs(7));
+    c.get(&1)?;
+    c.run_pending_tasks();
+    let loads_early = c.loader().loads.borrow().len();
+
+    // With 1.5 seconds left the hit is served from the cache and the reload waits for the pending tasks
+    clock.advance(Duration::from_millis(1500));
+    let hit = c.get(&1)?;
+    let loads_flagged = c.loader().loads.borrow().len();
+    c.run_pending_tasks();
+    let remaining = c.cache().entry_info(&1).and_then(|info| info.remaining_ttl());
+
+    match (loads_early, hit, loads_flagged, c.loader().loads.borrow().len(), remaining) {
+        (1, 10, 1, 2, Some(ttl)) if ttl == Duration::from_secs(10) => Ok(()),
+        other => Err(format!("Expected (1, 10, 1, 2, Some(10s)). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_keep_old_value_until_expiry_when_reload_fails() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut c = refreshing_cache(&clock);
+
+    c.get(&1)?;
+    clock.advance(Duration::from_secs(9));
+    c.get(&1)?;
+    c.loader().failing.borrow_mut().push(1);
+    c.run_pending_tasks();
+    let kept = c.get(&1);
+
+    clock.advance(Duration::from_secs(1));
+
+    match (kept, c.get(&1), c.cache().stats().misses) {
+        (Ok(10), Err(e), 2) if e == "cannot load 1" => Ok(()),
+        other => Err(format!("Expected (Ok(10), Err(\"cannot load 1\"), 2). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_never_miss_continuously_accessed_key() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut c = refreshing_cache(&clock);
+    c.get(&1)?;
+
+    for _ in 0..50 {
+        clock.advance(Duration::from_secs(1));
+        c.get(&1)?;
+        c.run_pending_tasks();
+    }
+
+    // Only the first get misses.  The item is reloaded each time it is down to its last second: at 9, 18, 27, 36 and 45
+    match (c.cache().stats().misses, c.loader().loads.borrow().len()) {
+        (1, 6) => Ok(()),
+        other => Err(format!("Expected (1, 6). Got {other:?}")),
+    }
+}
+
+/// Builds a cache whose items live for ten seconds and are served stale for five more
+fn stale_cache(clock: &MockClock) -> ReadThroughCache<u32, u32, CountingLoader> {
+    let cache = LruCacheBuilder::new(CAPACITY).clock(clock.clone()).ttl(Duration::from_secs(10)).build();
+    ReadThroughCache::with_cache(cache, CountingLoader::default()).with_stale_while_revalidate(Duration::from_secs(5))
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_serve_stale_value_within_grace_and_reload_it_once() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut c = stale_cache(&clock);
+
+    c.get(&1)?;
+    clock.advance(Duration::from_secs(12));
+    let stale = (c.get_cached(&1)?, c.get_cached(&1)?);
+    let loads_stale = c.loader().loads.borrow().len();
+
+    c.run_pending_tasks();
+    let fresh = c.get_cached(&1)?;
+    let expected_stale = CachedValue {
+        value: 10,
+        staleness: Some(Duration::from_secs(2)),
+    };
+
+    match (stale, loads_stale, fresh, c.loader().loads.borrow().len()) {
+        ((first, second), 1, fresh, 2) if first == expected_stale && second == expected_stale && !fresh.is_stale() => {
+            Ok(())
+        }
+        other => Err(format!("Expected two stale hits, then one reload. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_miss_once_grace_period_has_passed() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut c = stale_cache(&clock);
+
+    c.get(&1)?;
+    clock.advance(Duration::from_secs(15));
+    let found = c.get_cached(&1)?;
+
+    match (found, c.loader().loads.borrow().len(), c.cache().stats().misses) {
+        (CachedValue { value: 10, staleness: None }, 2, 2) => Ok(()),
+        other => Err(format!("Expected a blocking load of a fresh value. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_remember_absence_when_reload_of_stale_value_finds_none() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut c = stale_cache(&clock).with_negative_caching(Duration::from_secs(10), |e| e.starts_with("no value"));
+
+    c.get(&5)?;
+    clock.advance(Duration::from_secs(12));
+    c.loader().missing.borrow_mut().push(5);
+    let stale = c.get_cached(&5)?;
+    c.run_pending_tasks();
+
+    match (stale.is_stale(), c.lookup(&5), c.loader().loads.borrow().clone()) {
+        (true, Ok(Lookup::NegativeHit), loads) if loads == [5, 5] => Ok(()),
+        other => Err(format!("Expected (true, NegativeHit, [5, 5]). Got {other:?}")),
+    }
+}
@@ -0,0 +1,242 @@
+//! Recording every operation on a cache to a trace
+//!
+//! A cache given a writer through [`LruCacheBuilder::record_trace`](crate::LruCacheBuilder::record_trace) or
+//! [`LruCache::set_trace_writer`](crate::LruCache::set_trace_writer) writes one line to it for each `get`, `put` and
+//! `remove`, so that a production access pattern can be replayed later.  Each line holds four fields separated by
+//! spaces, and sometimes a fifth:
+//!
+//! ```text
+//! <time> <op> <outcome> <hash> [<key>]
+//! ```
+//!
+//! - `time` is the time of the operation in nanoseconds on the cache's clock
+//! - `op` is `get`, `put` or `remove`
+//! - `outcome` is `hit` or `miss`.  A `put` hits if it replaced a value, and a `remove` if it removed one
+//! - `hash` is the key's hash as 16 hexadecimal digits, from the cache's own hash builder
+//! - `key` is the key itself, but only if the cache was built with
+//!   [`trace_literal_keys`](crate::LruCacheBuilder::trace_literal_keys).  Backslashes, carriage returns and line feeds
+//!   are escaped as `\\`, `\r` and `\n`.  Keys are never written on a missing `get` or `remove`, whose key may be a
+//!   borrowed form that cannot be formatted
+//!
+//! Hashes are only comparable between traces if the caches hash keys the same way, which the randomly keyed
+//! [`DefaultHashBuilder`](crate::DefaultHashBuilder) does not.  [`TraceRecord`] writes and parses a line
+use core::{fmt, str::FromStr};
+use std::{
+    io::{self, BufWriter, Write},
+    string::{String, ToString},
+    sync::{Mutex, PoisonError},
+};
+
+/// Formats a key for the `key` field
+pub(crate) type KeyFmt<K> = fn(&K, &mut fmt::Formatter<'_>) -> fmt::Result;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The kind of operation a [`TraceRecord`] describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TraceOp {
+    Get,
+    Put,
+    Remove,
+}
+
+impl TraceOp {
+    fn name(self) -> &'static str {
+        match self {
+            TraceOp::Get => "get",
+            TraceOp::Put => "put",
+            TraceOp::Remove => "remove",
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// One line of a trace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    /// Time of the operation in nanoseconds on the cache's clock
+    pub time: u64,
+    pub op: TraceOp,
+    /// Whether the key was present
+    pub hit: bool,
+    pub key_hash: u64,
+    /// The key, if the cache writes literal keys
+    pub key: Option<String>,
+}
+
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = if self.hit { "hit" } else { "miss" };
+        write!(f, "{} {} {outcome} {:016x}", self.time, self.op.name(), self.key_hash)?;
+
+        match &self.key {
+            Some(key) => write!(f, " {}", escape(key)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromStr for TraceRecord {
+    type Err = TraceParseError;
+
+    /// Parses a line written by a cache, without its line feed
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut fields = line.splitn(5, ' ');
+        let mut field = |name| fields.next().filter(|f| !f.is_empty()).ok_or(TraceParseError::MissingField(name));
+
+        let time = field("time")?.parse().map_err(|_| TraceParseError::InvalidField("time"))?;
+        let op = match field("op")? {
+            "get" => TraceOp::Get,
+            "put" => TraceOp::Put,
+            "remove" => TraceOp::Remove,
+            _ => return Err(TraceParseError::InvalidField("op")),
+        };
+        let hit = match field("outcome")? {
+            "hit" => true,
+            "miss" => false,
+            _ => return Err(TraceParseError::InvalidField("outcome")),
+        };
+        let key_hash = u64::from_str_radix(field("hash")?, 16).map_err(|_| TraceParseError::InvalidField("hash"))?;
+        let key = match fields.next() {
+            Some(key) => Some(unescape(key).ok_or(TraceParseError::InvalidField("key"))?),
+            None => None,
+        };
+
+        Ok(TraceRecord {
+            time,
+            op,
+            hit,
+            key_hash,
+            key,
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Why a line could not be parsed as a [`TraceRecord`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceParseError {
+    /// The line ends before the named field
+    MissingField(&'static str),
+    /// The named field holds something other than the expected value
+    InvalidField(&'static str),
+}
+
+impl fmt::Display for TraceParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceParseError::MissingField(name) => write!(f, "trace line has no {name} field"),
+            TraceParseError::InvalidField(name) => write!(f, "trace line has an invalid {name} field"),
+        }
+    }
+}
+
+impl std::error::Error for TraceParseError {}
+
+// ---------------------------------------------------------------------------------------------------------------------
+fn escape(key: &str) -> String {
+    let mut escaped = String::with_capacity(key.len());
+
+    for c in key.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn unescape(field: &str) -> Option<String> {
+    let mut key = String::with_capacity(field.len());
+    let mut chars = field.chars();
+
+    while let Some(c) = chars.next() {
+        key.push(match c {
+            '\\' => match chars.next()? {
+                '\\' => '\\',
+                'r' => '\r',
+                'n' => '\n',
+                _ => return None,
+            },
+            c => c,
+        });
+    }
+
+    Some(key)
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Writes the trace of a cache.  The writer sits behind a lock so that reads through a shared borrow, such as
+/// [`LruCache::get_deferred`](crate::LruCache::get_deferred), can be recorded too
+pub(crate) struct TraceRecorder<K> {
+    key_fmt: Option<KeyFmt<K>>,
+    output: Mutex<Output>,
+}
+
+struct Output {
+    writer: BufWriter<Box<dyn Write + Send>>,
+    /// The first write that failed, after which nothing more is written
+    error: Option<io::Error>,
+}
+
+impl<K> TraceRecorder<K> {
+    pub(crate) fn new(writer: Box<dyn Write + Send>, key_fmt: Option<KeyFmt<K>>) -> Self {
+        TraceRecorder {
+            key_fmt,
+            output: Mutex::new(Output {
+                writer: BufWriter::new(writer),
+                error: None,
+            }),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Writes a line for an operation.  `key` is the stored key, if the operation found one
+    pub(crate) fn record(&self, time: u64, op: TraceOp, hit: bool, key_hash: u64, key: Option<&K>) {
+        let record = TraceRecord {
+            time,
+            op,
+            hit,
+            key_hash,
+            key: key.zip(self.key_fmt).map(|(key, fmt)| FormattedKey { key, fmt }.to_string()),
+        };
+
+        let mut output = self.output.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if output.error.is_none()
+            && let Err(e) = writeln!(output.writer, "{record}")
+        {
+            output.error = Some(e);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Flushes the trace, returning the first error met while writing it
+    pub(crate) fn finish(self) -> io::Result<()> {
+        let mut output = self.output.into_inner().unwrap_or_else(PoisonError::into_inner);
+
+        match output.error.take() {
+            Some(e) => Err(e),
+            None => output.writer.flush(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A key with the formatter chosen for it
+struct FormattedKey<'a, K> {
+    key: &'a K,
+    fmt: KeyFmt<K>,
+}
+
+impl<K> fmt::Display for FormattedKey<'_, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.fmt)(self.key, f)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
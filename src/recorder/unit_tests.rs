@@ -0,0 +1,171 @@
+use super::*;
+use crate::{LruCache, LruCacheBuilder, test_utils::MockClock};
+use std::{
+    hash::{BuildHasher, BuildHasherDefault, DefaultHasher},
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+    time::Duration,
+    vec::Vec,
+};
+
+type Fixed = BuildHasherDefault<DefaultHasher>;
+
+const CAPACITY: NonZeroUsize = NonZeroUsize::new(2).unwrap();
+
+/// A writer whose output can still be read once the cache holds the writer
+#[derive(Clone, Default)]
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuf {
+    fn records(&self) -> Result<Vec<TraceRecord>, String> {
+        let bytes = self.0.lock().map_err(|e| e.to_string())?.clone();
+        let text = String::from_utf8(bytes).map_err(|e| e.to_string())?;
+        text.lines().map(|line| line.parse().map_err(|e: TraceParseError| e.to_string())).collect()
+    }
+}
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().map_err(|e| io::Error::other(e.to_string()))?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A writer that fails every write
+struct Broken;
+
+impl Write for Broken {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::other("broken"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Err(io::Error::other("broken"))
+    }
+}
+
+fn record(time: u64, op: TraceOp, hit: bool, key: u32) -> TraceRecord {
+    TraceRecord {
+        time,
+        op,
+        hit,
+        key_hash: Fixed::default().hash_one(key),
+        key: None,
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_record_scripted_workload_with_outcomes() -> Result<(), String> {
+    let buf = SharedBuf::default();
+    let clock = MockClock::new();
+    let mut c: LruCache<u32, u32, Fixed> = LruCacheBuilder::new(CAPACITY)
+        .hasher(Fixed::default())
+        .clock(clock.clone())
+        .record_trace(buf.clone())
+        .build();
+    let tick = || clock.advance(Duration::from_nanos(10));
+
+    c.put(1, 10);
+    tick();
+    c.put(2, 20);
+    tick();
+    c.get(&1);
+    tick();
+    c.get(&3);
+    tick();
+    c.put(1, 11);
+    tick();
+    c.put(3, 30);
+    tick();
+    c.get_deferred(&2);
+    tick();
+    c.remove(&3);
+    tick();
+    c.remove(&3);
+    c.stop_tracing().map_err(|e| e.to_string())?;
+
+    // Operations after tracing stops are not written
+    c.get(&1);
+
+    // Putting 3 evicts 2, the least recently used item
+    let expected = vec![
+        record(0, TraceOp::Put, false, 1),
+        record(10, TraceOp::Put, false, 2),
+        record(20, TraceOp::Get, true, 1),
+        record(30, TraceOp::Get, false, 3),
+        record(40, TraceOp::Put, true, 1),
+        record(50, TraceOp::Put, false, 3),
+        record(60, TraceOp::Get, false, 2),
+        record(70, TraceOp::Remove, true, 3),
+        record(80, TraceOp::Remove, false, 3),
+    ];
+
+    match buf.records()? {
+        records if records == expected => Ok(()),
+        other => Err(format!("Expected {expected:?}. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_write_literal_keys_only_when_found() -> Result<(), String> {
+    let buf = SharedBuf::default();
+    let mut c: LruCache<String, u32> = LruCacheBuilder::new(CAPACITY).trace_literal_keys().build();
+    c.set_trace_writer(buf.clone());
+
+    let key = String::from("two words\nand a \\ backslash");
+    c.put(key.clone(), 1);
+    c.get(key.as_str());
+    c.get("absent");
+    c.stop_tracing().map_err(|e| e.to_string())?;
+
+    let keys = buf.records()?.into_iter().map(|r| (r.op, r.key)).collect::<Vec<_>>();
+    let expected = vec![(TraceOp::Put, Some(key.clone())), (TraceOp::Get, Some(key)), (TraceOp::Get, None)];
+
+    match keys {
+        keys if keys == expected => Ok(()),
+        other => Err(format!("Expected {expected:?}. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_report_first_write_error_when_stopping() -> Result<(), String> {
+    let mut c: LruCache<u32, u32> = LruCache::new(CAPACITY);
+    let stopped_idle = c.stop_tracing().is_ok();
+
+    c.set_trace_writer(Broken);
+    c.put(1, 10);
+    c.get(&1);
+    let stopped = c.stop_tracing().map_err(|e| e.to_string());
+
+    match (stopped_idle, stopped, c.get(&1).copied()) {
+        (true, Err(message), Some(10)) if message == "broken" => Ok(()),
+        other => Err(format!("Expected (true, Err(\"broken\"), Some(10)). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_reject_malformed_lines() -> Result<(), String> {
+    let parsed = ["", "5 get hit", "x get hit 0000000000000001", "5 peek hit 01", "5 get maybe 01", "5 get hit 0g"]
+        .map(|line| line.parse::<TraceRecord>().err());
+
+    let expected = [
+        Some(TraceParseError::MissingField("time")),
+        Some(TraceParseError::MissingField("hash")),
+        Some(TraceParseError::InvalidField("time")),
+        Some(TraceParseError::InvalidField("op")),
+        Some(TraceParseError::InvalidField("outcome")),
+        Some(TraceParseError::InvalidField("hash")),
+    ];
+
+    match parsed {
+        parsed if parsed == expected => Ok(()),
+        other => Err(format!("Expected {expected:?}. Got {other:?}")),
+    }
+}
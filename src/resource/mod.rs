@@ -0,0 +1,94 @@
+//! Caches whose values hold resources that must be released exactly once
+//!
+//! A [`ResourceLruCache`] stores every value inside [`Resource`], which calls [`CacheResource::release`] when it is
+//! dropped.  Because the cache drops a value whenever it is evicted, expires, is cleared or the cache itself is dropped,
+//! no code path can leave a file descriptor or a GPU buffer unreleased, and because the release happens on drop, none
+//! can release it twice.
+//!
+//! Values handed back to the caller (by `put` returning the overwritten value, `remove`, `pop_lru` etc.) are still
+//! wrapped in `Resource`, and the cache does not release them: they are released when the caller drops them, or never,
+//! if the caller takes the value out with [`Resource::into_inner`].  If the caller ignores the returned value, it is
+//! released immediately.  A value turned away because every item is pinned was never in the cache, and is released
+//! when dropped like any other
+use crate::{DefaultHashBuilder, LruCache};
+use core::{
+    hash::Hash,
+    num::NonZeroUsize,
+    ops::{Deref, DerefMut},
+};
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A value that holds something to give back, such as a file descriptor or a GPU buffer, once the cache lets go of it
+pub trait CacheResource {
+    /// Gives back whatever the value holds.  Called once, when the [`Resource`] wrapping the value is dropped
+    fn release(&mut self);
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A value that is released when dropped, unless taken out with [`into_inner`](Self::into_inner)
+#[derive(Debug)]
+pub struct Resource<V: CacheResource>(
+    /// Only `None` once [`into_inner`](Self::into_inner) has taken the value
+    Option<V>,
+);
+
+impl<V: CacheResource> Resource<V> {
+    /// Wraps a value so that it is released when dropped
+    pub fn new(value: V) -> Self {
+        Resource(Some(value))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Takes the value out without releasing it, leaving the caller responsible for it
+    pub fn into_inner(mut self) -> V {
+        self.0.take().expect("a resource holds its value until taken")
+    }
+}
+
+impl<V: CacheResource> Deref for Resource<V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        self.0.as_ref().expect("a resource holds its value until taken")
+    }
+}
+
+impl<V: CacheResource> DerefMut for Resource<V> {
+    fn deref_mut(&mut self) -> &mut V {
+        self.0.as_mut().expect("a resource holds its value until taken")
+    }
+}
+
+impl<V: CacheResource> Drop for Resource<V> {
+    fn drop(&mut self) {
+        if let Some(value) = &mut self.0 {
+            value.release();
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache that releases its values when they leave the cache
+pub type ResourceLruCache<K, V, S = DefaultHashBuilder> = LruCache<K, Resource<V>, S>;
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V> LruCache<K, Resource<V>>
+where
+    K: Eq + Hash,
+    V: CacheResource,
+{
+    /// Creates a cache whose values are released when they leave the cache
+    pub fn new_releasing(capacity: NonZeroUsize) -> Self {
+        LruCache::new(capacity)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Wraps `new_value` in [`Resource`] and inserts it
+    pub fn put_resource(&mut self, key: K, new_value: V) -> Option<Resource<V>> {
+        self.put(key, Resource::new(new_value))
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(all(test, feature = "std"))]
+mod unit_tests;
@@ -0,0 +1,124 @@
+use super::*;
+use std::{
+    format,
+    string::String,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+    vec::Vec,
+};
+
+/// Handle that records each release of its id, and counts the handles dropped
+struct Handle {
+    id: u32,
+    released: Arc<Mutex<Vec<u32>>>,
+    drops: Arc<AtomicUsize>,
+}
+
+impl CacheResource for Handle {
+    fn release(&mut self) {
+        self.released.lock().unwrap().push(self.id);
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Default)]
+struct Fixture {
+    released: Arc<Mutex<Vec<u32>>>,
+    drops: Arc<AtomicUsize>,
+}
+
+impl Fixture {
+    fn handle(&self, id: u32) -> Handle {
+        Handle {
+            id,
+            released: Arc::clone(&self.released),
+            drops: Arc::clone(&self.drops),
+        }
+    }
+
+    /// The ids released so far, in ascending order
+    fn released(&self) -> Vec<u32> {
+        let mut released = self.released.lock().unwrap().clone();
+        released.sort_unstable();
+        released
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_release_every_value_exactly_once_whichever_way_it_leaves() -> Result<(), String> {
+    let f = Fixture::default();
+    let mut cache = LruCache::new_releasing(NonZeroUsize::new(2).unwrap());
+
+    // Eviction
+    cache.put_resource(1, f.handle(1));
+    cache.put_resource(2, f.handle(2));
+    cache.put_resource(3, f.handle(3));
+    let after_eviction = f.released();
+
+    // Explicit removal, released once the caller drops the value
+    let removed = cache.remove(&2).ok_or("Expected key 2")?;
+    let while_held = f.released();
+    drop(removed);
+
+    // Overwrite, with the returned value ignored
+    cache.put_resource(3, f.handle(30));
+
+    // Clear, then dropping the cache
+    cache.put_resource(4, f.handle(4));
+    cache.clear();
+    cache.put_resource(5, f.handle(5));
+    drop(cache);
+
+    match (&after_eviction[..], &while_held[..], &f.released()[..], f.drops.load(Ordering::SeqCst)) {
+        ([1], [1], [1, 2, 3, 4, 5, 30], 6) => Ok(()),
+        other => Err(format!("Expected each of 6 values released once, as it left. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_not_release_a_value_the_caller_takes_over() -> Result<(), String> {
+    let f = Fixture::default();
+    let mut cache = LruCache::new_releasing(NonZeroUsize::new(2).unwrap());
+    cache.put_resource(1, f.handle(1));
+    cache.put_resource(2, f.handle(2));
+
+    let overwritten = cache.put_resource(1, f.handle(10)).ok_or("Expected the old value of key 1")?.into_inner();
+    let popped = cache.pop_lru().ok_or("Expected key 2")?.into_inner();
+    drop(cache);
+    let released = f.released();
+    let ids = (overwritten.id, popped.id);
+
+    match (&released[..], ids) {
+        ([10], (1, 2)) => Ok(()),
+        other => Err(format!("Expected only the value left in the cache released. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_release_expired_values_as_they_are_discarded() -> Result<(), String> {
+    let f = Fixture::default();
+    let clock = crate::test_utils::MockClock::new();
+    let mut cache: ResourceLruCache<u32, Handle> = LruCache::with_clock(NonZeroUsize::new(4).unwrap(), clock.clone());
+    cache.put_with_ttl(1, Resource::new(f.handle(1)), Duration::from_secs(1));
+    cache.put_with_ttl(2, Resource::new(f.handle(2)), Duration::from_secs(1));
+
+    clock.advance(Duration::from_secs(1));
+    let found = cache.get(&1).is_some();
+    let purged = cache.purge_expired();
+
+    match (found, purged, &f.released()[..]) {
+        (false, 1, [1, 2]) => Ok(()),
+        other => Err(format!("Expected both expired values released. Got {other:?}")),
+    }
+}
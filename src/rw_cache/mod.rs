@@ -0,0 +1,223 @@
+//! Thread-safe LRU cache for read-mostly workloads
+//!
+//! [`RwLruCache`] keeps an [`LruCache`] behind a read-write lock.  Reads that do not change the recency order, such as
+//! [`peek`](RwLruCache::peek) and [`len`](RwLruCache::len), take the read lock, so any number of them run at once.
+//! [`get`](RwLruCache::get) also takes only the read lock: rather than relinking the entry it hits, it records the
+//! promotion in the cache's read buffer, which is lock-free.  Writes take the write lock and replay the recorded
+//! promotions, oldest first, before doing anything else.  When the buffer is full, the `get` that finds it full takes
+//! the write lock itself to replay it, so no promotion is ever dropped.
+//!
+//! # Consistency
+//!
+//! Values are always up to date: every change to a value or to the set of cached keys happens under the write lock.
+//! Promotions lag behind the reads that caused them until the next write or until the buffer fills, but are always
+//! applied before anything is evicted, so the eviction order is the same as if every `get` had promoted immediately.
+//! Reads from different threads are applied in the order they were recorded, which may differ slightly from the order
+//! in which the threads called `get`.  Methods that inspect the order through the read lock, such as
+//! [`LruCache::recency_rank`] inside [`RwLruCache::with_read`], do not see promotions that are still buffered.
+//!
+//! # Poisoning
+//!
+//! As with [`ConcurrentLruCache`](crate::ConcurrentLruCache), if a thread panics while holding the write lock, the
+//! next call empties the cache and clears the poisoned state rather than panicking itself
+use crate::{
+    DefaultHashBuilder, LruCache,
+    read_buffer::ReadBuffer,
+    sync::{ResetPoison, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    num::NonZeroUsize,
+    sync::PoisonError,
+};
+
+/// Promotions recorded between writes before a `get` takes the write lock to replay them
+const DEFAULT_READ_BUFFER: usize = 128;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache that can be shared between threads, where reads only take a read lock
+pub struct RwLruCache<K, V, S = DefaultHashBuilder> {
+    inner: RwLock<LruCache<K, V, S>>,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V> RwLruCache<K, V>
+where
+    K: Eq + Hash,
+{
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        RwLruCache::from(LruCache::new(capacity))
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> RwLruCache<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Takes the read lock, first emptying the cache if a thread panicked while holding the write lock
+    fn read(&self) -> RwLockReadGuard<'_, LruCache<K, V, S>> {
+        if self.inner.poisoned() {
+            drop(self.write());
+        }
+
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Takes the write lock, emptying the cache first if a thread panicked while holding it
+    fn write(&self) -> RwLockWriteGuard<'_, LruCache<K, V, S>> {
+        self.inner.write().unwrap_or_else(|poisoned| {
+            let mut cache = poisoned.into_inner();
+            cache.discard_all();
+            self.inner.reset_poison();
+            cache
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a copy of an item, making it the most recently used.  The promotion is recorded under the read
+    /// lock and applied later; see the [module documentation](self)
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        if let (value, true) = self.read().get_recorded(key)? {
+            return Some(value.clone());
+        }
+
+        // The buffer is full.  Promoting under the write lock replays it first, so this read lands after the recorded
+        // ones.  The key may have been removed since the read lock was released, in which case the read missed
+        let mut cache = self.write();
+        cache.promote(key);
+        cache.peek(key).cloned()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetch a copy of an item without changing its position in the recency order
+    pub fn peek<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.read().peek(key).cloned()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains the key. The item's position in the recency order is not changed
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.read().contains_key(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item.
+    /// * If the item already exists, it returns the old value else it returns `None`
+    /// * If the addition of the new item exceeds the cache's capacity, the oldest item is evicted before the new item is
+    ///   added
+    pub fn put(&self, key: K, new_value: V) -> Option<V> {
+        self.write().put(key, new_value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item, returning the item it displaced.
+    /// * If the key already exists, its value is replaced and the key and old value are returned
+    /// * If the cache is full, the least recently used item is evicted and returned
+    pub fn push(&self, key: K, new_value: V) -> Option<(K, V)> {
+        self.write().push(key, new_value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an item, returning its value if it was present
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.write().remove(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the most recently used item
+    pub fn pop_mru(&self) -> Option<V> {
+        self.write().pop_mru()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the least recently used item
+    pub fn pop_lru(&self) -> Option<V> {
+        self.write().pop_lru()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in the cache
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains no items
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the cache can hold
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.read().capacity()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes all items
+    pub fn clear(&self) {
+        self.write().clear()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Runs `f` under the read lock, for example to inspect values without cloning them
+    pub fn with_read<R>(&self, f: impl FnOnce(&LruCache<K, V, S>) -> R) -> R {
+        f(&self.read())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Runs `f` under the write lock, so that several operations happen without other threads seeing the cache in
+    /// between
+    pub fn with_write<R>(&self, f: impl FnOnce(&mut LruCache<K, V, S>) -> R) -> R {
+        f(&mut self.write())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the wrapped cache.  If a thread panicked while holding the write lock, the returned cache is empty
+    pub fn into_inner(self) -> LruCache<K, V, S> {
+        self.inner.into_inner().unwrap_or_else(|poisoned| {
+            let mut cache = poisoned.into_inner();
+            cache.discard_all();
+            cache
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Shares an existing cache, such as one configured through [`LruCacheBuilder`](crate::LruCacheBuilder).  A cache
+/// built without a read buffer is given one holding 128 promotions
+impl<K, V, S> From<LruCache<K, V, S>> for RwLruCache<K, V, S> {
+    fn from(mut cache: LruCache<K, V, S>) -> Self {
+        cache.read_buffer.get_or_insert_with(|| ReadBuffer::new(DEFAULT_READ_BUFFER));
+
+        RwLruCache {
+            inner: RwLock::new(cache),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,129 @@
+use super::*;
+use crate::LruCacheBuilder;
+use std::{
+    format,
+    string::String,
+    sync::{Arc, Barrier},
+    thread,
+    vec::Vec,
+};
+
+const READERS: usize = 4;
+const KEYS_PER_READER: u64 = 32;
+const READS_PER_READER: usize = 5_000;
+const WRITER_KEYS: u64 = 64;
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_be_send_and_sync() -> Result<(), String> {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<RwLruCache<String, String>>();
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn get_should_promote_before_next_eviction() -> Result<(), String> {
+    let cache = RwLruCache::new(NonZeroUsize::new(3).unwrap());
+    cache.put(1, 1);
+    cache.put(2, 2);
+    cache.put(3, 3);
+
+    // The promotion of 1 is only recorded, but must be applied before the put chooses what to evict
+    let read = cache.get(&1);
+    cache.put(4, 4);
+
+    match (read, cache.contains_key(&1), cache.contains_key(&2)) {
+        (Some(1), true, false) => Ok(()),
+        other => Err(format!("Expected 2 to be evicted after reading 1. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Each reader reads only its own keys, so the promotions it records keep their relative order however they interleave
+/// with other threads.  Once every promotion is applied, each reader's keys must be ordered by when that reader last
+/// read them.  A lost promotion would leave a key too far back, and a duplicated one would move an earlier read in front
+/// of a later one
+#[test]
+fn should_apply_every_read_once_while_writes_flush_the_buffer() -> Result<(), String> {
+    let capacity = READERS as u64 * KEYS_PER_READER + WRITER_KEYS;
+    let cache = LruCacheBuilder::new(NonZeroUsize::new(capacity as usize).unwrap())
+        .read_buffer(NonZeroUsize::new(8).unwrap())
+        .build();
+    let cache = Arc::new(RwLruCache::from(cache));
+
+    for key in 0..capacity {
+        cache.put(key, key);
+    }
+
+    let barrier = Arc::new(Barrier::new(READERS + 1));
+
+    let readers: Vec<_> = (0..READERS as u64)
+        .map(|r| {
+            let cache = Arc::clone(&cache);
+            let barrier = Arc::clone(&barrier);
+
+            thread::spawn(move || {
+                let first = WRITER_KEYS + r * KEYS_PER_READER;
+                let mut seed = 0x9e37_79b9_u64 + r;
+                let mut last_read = Vec::new();
+                barrier.wait();
+
+                // Read every key once, then keep reading keys at random
+                for i in 0..READS_PER_READER as u64 {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+
+                    let key = first + if i < KEYS_PER_READER { i } else { seed % KEYS_PER_READER };
+
+                    if cache.get(&key) != Some(key) {
+                        return Err(format!("Reader {r} missed key {key}"));
+                    }
+
+                    last_read.retain(|&k| k != key);
+                    last_read.push(key);
+                }
+
+                Ok(last_read)
+            })
+        })
+        .collect();
+
+    let writer = {
+        let cache = Arc::clone(&cache);
+        let barrier = Arc::clone(&barrier);
+
+        thread::spawn(move || {
+            barrier.wait();
+
+            // Overwriting existing keys never evicts, but every write replays the buffer
+            for i in 0..READS_PER_READER as u64 {
+                cache.put(i % WRITER_KEYS, i % WRITER_KEYS);
+            }
+        })
+    };
+
+    writer.join().map_err(|_| "The writer panicked")?;
+
+    let expected: Vec<Vec<u64>> = readers
+        .into_iter()
+        .map(|reader| reader.join().map_err(|_| String::from("A reader panicked"))?)
+        .collect::<Result<_, _>>()?;
+
+    let cache = Arc::into_inner(cache).ok_or("Cache still shared")?.into_inner();
+    cache.debug_validate().map_err(|e| e.to_string())?;
+    let (_, _, order) = cache.into_parts();
+
+    for (r, last_read) in expected.into_iter().enumerate() {
+        let first = WRITER_KEYS + r as u64 * KEYS_PER_READER;
+        let mut actual: Vec<u64> = order.iter().copied().filter(|k| (first..first + KEYS_PER_READER).contains(k)).collect();
+        actual.reverse();
+
+        if actual != last_read {
+            return Err(format!("Reader {r}'s keys are out of order.\nExpected {last_read:?}\nGot      {actual:?}"));
+        }
+    }
+
+    Ok(())
+}
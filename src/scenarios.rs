@@ -0,0 +1,148 @@
+//! Test scenarios shared by every cache type that offers the core `put`/`get`/`pop_lru`/`pop_mru`/`len` methods
+//!
+//! `cache_scenarios!(new_cache)` expands to a set of tests, where `new_cache` is a function returning an empty
+//! `String` to `String` cache with a capacity of 10
+
+macro_rules! cache_scenarios {
+    ($new_cache:expr) => {
+        const SCENARIO_CAPACITY: usize = 10;
+
+        macro_rules! prefilled {
+            () => {{
+                let mut c = $new_cache();
+
+                for idx in 0..SCENARIO_CAPACITY {
+                    let _ = c.put($crate::test_utils::gen_item_key(idx), $crate::test_utils::gen_item_value(idx as u32));
+                }
+
+                c
+            }};
+        }
+
+        // -------------------------------------------------------------------------------------------------------------
+        #[test]
+        fn scenario_should_put_and_get_an_item() -> Result<(), String> {
+            let mut c = $new_cache();
+            let k = $crate::test_utils::gen_item_key(1);
+            let v = $crate::test_utils::gen_item_value(1);
+
+            c.put(k.clone(), v.clone());
+
+            match c.get(&k) {
+                Some(found) if *found == v => Ok(()),
+                other => Err(format!("Expected '{v}'. Got {other:?}")),
+            }
+        }
+
+        // -------------------------------------------------------------------------------------------------------------
+        #[test]
+        fn scenario_last_inserted_item_should_be_mru() -> Result<(), String> {
+            let mut c = prefilled!();
+            let v = $crate::test_utils::gen_item_value(SCENARIO_CAPACITY as u32 - 1);
+
+            match c.pop_mru() {
+                Some(mru) if mru == v => Ok(()),
+                other => Err(format!("MRU item should be '{v}'. Got {other:?}")),
+            }
+        }
+
+        // -------------------------------------------------------------------------------------------------------------
+        #[test]
+        fn scenario_should_pop_expected_mru_after_reorder() -> Result<(), String> {
+            let mut c = prefilled!();
+            let v = $crate::test_utils::gen_item_value(6);
+
+            c.get("item-6").ok_or("item-6 not found")?;
+
+            match c.pop_mru() {
+                Some(mru) if mru == v => Ok(()),
+                other => Err(format!("MRU item should be '{v}'. Got {other:?}")),
+            }
+        }
+
+        // -------------------------------------------------------------------------------------------------------------
+        #[test]
+        fn scenario_should_fail_to_get_nonexistent_item() -> Result<(), String> {
+            let mut c = prefilled!();
+
+            match c.get("item-10") {
+                None => Ok(()),
+                Some(v) => Err(format!("Found '{v}' for 'item-10', which should not exist in cache")),
+            }
+        }
+
+        // -------------------------------------------------------------------------------------------------------------
+        #[test]
+        fn scenario_should_evict_lru_item_when_full() -> Result<(), String> {
+            let mut c = prefilled!();
+
+            c.put($crate::test_utils::gen_item_key(10), $crate::test_utils::gen_item_value(10));
+
+            if c.get("item-0").is_some() {
+                Err(String::from("Found item 'item-0' when it should have been evicted"))
+            } else if c.len() != SCENARIO_CAPACITY {
+                Err(format!("Expected {SCENARIO_CAPACITY} items. Got {}", c.len()))
+            } else {
+                Ok(())
+            }
+        }
+
+        // -------------------------------------------------------------------------------------------------------------
+        #[test]
+        fn scenario_should_pop_mru_after_item_eviction() -> Result<(), String> {
+            let mut c = prefilled!();
+            let v = $crate::test_utils::gen_item_value(10);
+
+            c.put($crate::test_utils::gen_item_key(10), v.clone());
+
+            match c.pop_mru() {
+                Some(mru) if mru == v => Ok(()),
+                other => Err(format!("MRU item should be '{v}'. Got {other:?}")),
+            }
+        }
+
+        // -------------------------------------------------------------------------------------------------------------
+        #[test]
+        fn scenario_should_pop_lru_in_recency_order() -> Result<(), String> {
+            let mut c = prefilled!();
+
+            // Reading item-0 moves it from the LRU end to the MRU end
+            c.get("item-0").ok_or("item-0 not found")?;
+
+            let mut popped = Vec::new();
+            while let Some(v) = c.pop_lru() {
+                popped.push(v);
+            }
+
+            let expected: Vec<String> = (1..SCENARIO_CAPACITY as u32)
+                .chain(core::iter::once(0))
+                .map($crate::test_utils::gen_item_value)
+                .collect();
+
+            if popped == expected {
+                Ok(())
+            } else {
+                Err(format!("Expected {expected:?}. Got {popped:?}"))
+            }
+        }
+
+        // -------------------------------------------------------------------------------------------------------------
+        #[test]
+        fn scenario_should_return_old_value_on_replace() -> Result<(), String> {
+            let mut c = prefilled!();
+
+            match c.put($crate::test_utils::gen_item_key(3), $crate::test_utils::gen_item_value(33)) {
+                Some(old) if old == $crate::test_utils::gen_item_value(3) => (),
+                old => return Err(format!("Expected old value 'value-3'. Got {old:?}")),
+            }
+
+            if c.len() == SCENARIO_CAPACITY {
+                Ok(())
+            } else {
+                Err(format!("Replacing an item should not change the length. Got {}", c.len()))
+            }
+        }
+    };
+}
+
+pub(crate) use cache_scenarios;
@@ -0,0 +1,108 @@
+//! Serde support
+//!
+//! A cache is represented as its capacity followed by its entries, ordered from least to most recently used, so that
+//! replaying the entries with `put` reproduces the original recency order
+use crate::{CacheError, LruCache, check_capacity};
+use alloc::vec::Vec;
+use core::{
+    hash::{BuildHasher, Hash},
+    num::NonZeroUsize,
+};
+use hashbrown::HashSet;
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de,
+    ser::{SerializeSeq, SerializeStruct},
+};
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Serialized form of a cache
+#[derive(Deserialize)]
+#[serde(rename = "LruCache")]
+pub(crate) struct Repr<K, V> {
+    pub(crate) capacity: NonZeroUsize,
+    pub(crate) entries: Vec<(K, V)>,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Serializes the entries of a cache from least to most recently used
+struct EntriesLruFirst<'a, K, V, S>(&'a LruCache<K, V, S>);
+
+impl<K, V, S> Serialize for EntriesLruFirst<'_, K, V, S>
+where
+    K: Serialize + Eq + Hash,
+    V: Serialize,
+    S: BuildHasher,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+
+        for entry in self.0.iter_lru_first() {
+            seq.serialize_element(&entry)?;
+        }
+
+        seq.end()
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> Serialize for LruCache<K, V, S>
+where
+    K: Serialize + Eq + Hash,
+    V: Serialize,
+    S: BuildHasher,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let mut state = serializer.serialize_struct("LruCache", 2)?;
+        state.serialize_field("capacity", &self.capacity())?;
+        state.serialize_field("entries", &EntriesLruFirst(self))?;
+        state.end()
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<'de, K, V, S> Deserialize<'de> for LruCache<K, V, S>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = Repr::deserialize(deserializer)?;
+        rebuild(repr.capacity, repr.entries).map_err(de::Error::custom)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Rebuilds a cache from its capacity and entries, rejecting unsupported capacities, duplicate keys and entries that
+/// exceed the capacity
+fn rebuild<K, V, S>(capacity: NonZeroUsize, entries: Vec<(K, V)>) -> Result<LruCache<K, V, S>, CacheError>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    check_capacity(capacity)?;
+
+    if entries.len() > capacity.get() {
+        return Err(CacheError::CapacityExceeded {
+            len: entries.len(),
+            capacity: capacity.get(),
+        });
+    }
+
+    {
+        let mut seen = HashSet::with_capacity(entries.len());
+
+        if !entries.iter().all(|(k, _)| seen.insert(k)) {
+            return Err(CacheError::DuplicateKey);
+        }
+    }
+
+    let mut cache = LruCache::with_capacity_and_hasher(capacity, entries.len(), S::default());
+
+    for (k, v) in entries {
+        cache.put(k, v);
+    }
+
+    Ok(cache)
+}
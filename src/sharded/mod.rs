@@ -0,0 +1,418 @@
+//! LRU cache split into independently locked shards
+//!
+//! [`ShardedLruCache`] spreads its items over several [`ConcurrentLruCache`] shards, choosing each key's shard from its
+//! hash, so that threads working on different keys rarely wait for the same lock.  Each shard evicts its own least
+//! recently used item when it reaches its share of the capacity, so across the whole cache the eviction order is only
+//! approximately LRU.  There is no `pop_lru` or `pop_mru` for the same reason.
+//!
+//! # Rebalancing
+//!
+//! An equal split of the capacity wastes space when keys are unevenly spread: the shards that receive most keys evict
+//! while the others sit half empty.  [`ShardedLruCache::rebalance`] moves capacity between shards, keeping the total
+//! fixed.  Each shard keeps at least an eighth of an equal share, and the rest is divided in proportion to each shard's
+//! demand since the last rebalance: the items it holds plus the items it evicted.  Shards that lose capacity evict
+//! their least recently used items straight away.  By default the cache rebalances itself after every `capacity`
+//! writes; [`ShardedLruCache::with_rebalance_interval`] changes that or turns it off.
+//!
+//! # Visiting every shard
+//!
+//! Rebalancing and the methods that report on the whole cache, such as [`len`](ShardedLruCache::len),
+//! [`stats`](ShardedLruCache::stats) and [`snapshot`](ShardedLruCache::snapshot), lock one shard at a time and never
+//! hold two shard locks at once, so they cannot deadlock with each other or with other threads.  Their results are
+//! consistent within each shard, but shards visited later may reflect operations that happened after earlier shards
+//! were visited
+use crate::{
+    CacheStats, ConcurrentLruCache, DefaultHashBuilder, LruCache, WouldBlock, stats,
+    sync::{AtomicU64, AtomicUsize, Mutex, Ordering},
+};
+#[cfg(feature = "metrics")]
+use crate::instrumentation::CacheMetrics;
+#[cfg(feature = "tracing")]
+use crate::tracer::Tracer;
+use std::{
+    borrow::Borrow,
+    boxed::Box,
+    format,
+    hash::{BuildHasher, Hash},
+    num::{NonZeroU64, NonZeroUsize},
+    sync::TryLockError,
+    thread,
+    vec::Vec,
+};
+
+/// Shards per available core when the number of shards is not given
+const SHARDS_PER_CORE: usize = 4;
+
+/// Each shard's smallest quota is an equal share of the capacity divided by this
+const MIN_QUOTA_DIVISOR: usize = 8;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Occupancy and activity of one shard of a [`ShardedLruCache`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShardStats {
+    /// Number of items in the shard
+    pub len: usize,
+    /// Most items the shard may currently hold
+    pub capacity: usize,
+    /// The shard's activity since it was created or its statistics were last reset
+    pub stats: CacheStats,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+struct Shard<K, V, S> {
+    cache: ConcurrentLruCache<K, V, S>,
+    /// Most items the shard may hold.  Only read under the shard's lock
+    quota: AtomicUsize,
+    /// The shard's eviction count at the last rebalance
+    evictions_seen: AtomicU64,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache that can be shared between threads, split into shards that are locked independently
+pub struct ShardedLruCache<K, V, S = DefaultHashBuilder> {
+    shards: Box<[Shard<K, V, S>]>,
+    hash_builder: S,
+    capacity: NonZeroUsize,
+    /// Turns the bits of a hash below the seven hashbrown keeps for itself into a shard index
+    shift: u32,
+    rebalance_interval: Option<NonZeroU64>,
+    writes: AtomicU64,
+    rebalancing: Mutex<()>,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V> ShardedLruCache<K, V>
+where
+    K: Eq + Hash,
+{
+    /// Creates a cache with four shards per available core
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        let cores = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        ShardedLruCache::with_shards(capacity, NonZeroUsize::new(cores * SHARDS_PER_CORE).unwrap())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Creates a cache with the given number of shards, rounded up to a power of two but no more than the capacity
+    pub fn with_shards(capacity: NonZeroUsize, shards: NonZeroUsize) -> Self {
+        ShardedLruCache::with_shards_and_hasher(capacity, shards, DefaultHashBuilder::default())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Creates a cache with the given number of shards, each of which reports its activity through the `metrics`
+    /// facade as [`LruCache::new_instrumented`] does, under metric names prefixed with `name` and with the shard's
+    /// index in a `shard` label
+    #[cfg(feature = "metrics")]
+    pub fn with_shards_instrumented(capacity: NonZeroUsize, shards: NonZeroUsize, name: &str) -> Self {
+        let cache = ShardedLruCache::with_shards(capacity, shards);
+
+        for (idx, shard) in cache.shards.iter().enumerate() {
+            shard.cache.with_lock(|shard| shard.metrics = Some(CacheMetrics::register_shard(name, idx)));
+        }
+
+        cache
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> ShardedLruCache<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Creates a cache with the given number of shards, rounded up to a power of two but no more than the capacity,
+    /// that uses the given hash builder both to choose shards and within each shard
+    pub fn with_shards_and_hasher(capacity: NonZeroUsize, shards: NonZeroUsize, hash_builder: S) -> Self
+    where
+        S: Clone,
+    {
+        let most_shards = 1 << capacity.get().ilog2();
+        let count = shards.get().checked_next_power_of_two().unwrap_or(most_shards).min(most_shards);
+
+        // Every shard's cache could be given the whole capacity by rebalancing, but only preallocates its first quota
+        let shards = (0..count)
+            .map(|i| {
+                let quota = capacity.get() / count + usize::from(i < capacity.get() % count);
+                let cache = LruCache::with_capacity_and_hasher(capacity, quota, hash_builder.clone());
+
+                Shard {
+                    cache: ConcurrentLruCache::from(cache),
+                    quota: AtomicUsize::new(quota),
+                    evictions_seen: AtomicU64::new(0),
+                }
+            })
+            .collect();
+
+        ShardedLruCache {
+            shards,
+            hash_builder,
+            capacity,
+            shift: u64::BITS - count.ilog2(),
+            rebalance_interval: NonZeroU64::new(capacity.get() as u64),
+            writes: AtomicU64::new(0),
+            rebalancing: Mutex::new(()),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Sets how many writes pass between automatic rebalances, or turns them off with `None`.  The default is the
+    /// cache's capacity
+    pub fn with_rebalance_interval(mut self, writes: Option<NonZeroU64>) -> Self {
+        self.rebalance_interval = writes;
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Emits the events listed under [`LruCacheBuilder::tracing`](crate::LruCacheBuilder::tracing) from every shard,
+    /// carrying `name` in the `cache` field and the shard's index in the `shard` field.  Keys are left out
+    #[cfg(feature = "tracing")]
+    pub fn with_tracing(self, name: &'static str) -> Self {
+        for (idx, shard) in self.shards.iter().enumerate() {
+            shard.cache.with_lock(|shard| shard.tracer = Some(Tracer::new(name, None).for_shard(idx)));
+        }
+
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn shard_index(&self, hash: u64) -> usize {
+        (hash << 7).checked_shr(self.shift).unwrap_or(0) as usize
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn shard<Q>(&self, key: &Q) -> &Shard<K, V, S>
+    where
+        Q: Hash + ?Sized,
+    {
+        &self.shards[self.shard_index(self.hash_builder.hash_one(key))]
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Counts a write, rebalancing if it completes an interval
+    fn wrote(&self) {
+        if let Some(interval) = self.rebalance_interval
+            && (self.writes.fetch_add(1, Ordering::Relaxed) + 1).is_multiple_of(interval.get())
+        {
+            self.rebalance();
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a copy of an item, making it the most recently used in its shard
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.shard(key).cache.get(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Like [`get`](Self::get), but returns [`WouldBlock`] instead of waiting if another thread holds the shard's lock
+    pub fn try_get<Q>(&self, key: &Q) -> Result<Option<V>, WouldBlock>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.shard(key).cache.try_get(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetch a copy of an item without changing its position in the recency order
+    pub fn peek<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.shard(key).cache.peek(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains the key. The item's position in the recency order is not changed
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(key).cache.contains_key(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item.
+    /// * If the item already exists, it returns the old value else it returns `None`
+    /// * If the key's shard is full, its least recently used item is evicted
+    pub fn put(&self, key: K, new_value: V) -> Option<V> {
+        let shard = self.shard(&key);
+        let old_value = shard.cache.put_within(key, new_value, &shard.quota);
+        self.wrote();
+        old_value
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Like [`put`](Self::put), but hands the key and value back in [`WouldBlock`] instead of waiting if another thread
+    /// holds the shard's lock
+    pub fn try_put(&self, key: K, new_value: V) -> Result<Option<V>, WouldBlock<(K, V)>> {
+        let shard = self.shard(&key);
+        let old_value = shard.cache.try_put_within(key, new_value, &shard.quota)?;
+        self.wrote();
+        Ok(old_value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an item, returning its value if it was present
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(key).cache.remove(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in the cache
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.cache.len()).sum()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains no items
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| shard.cache.is_empty())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the cache can hold, across all shards
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.capacity
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes all items.  The shards' quotas are kept
+    pub fn clear(&self) {
+        self.shards.iter().for_each(|shard| shard.cache.clear());
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Copies every item, shard by shard.  Within each shard, items run from least to most recently used
+    pub fn snapshot(&self) -> Vec<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.shards.iter().flat_map(|shard| shard.cache.snapshot()).collect()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Copies every key, shard by shard.  Cheaper than [`snapshot`](Self::snapshot) when values are large
+    pub fn snapshot_keys(&self) -> Vec<K>
+    where
+        K: Clone,
+    {
+        self.shards.iter().flat_map(|shard| shard.cache.snapshot_keys()).collect()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the activity of every shard added together
+    pub fn stats(&self) -> CacheStats {
+        self.shards.iter().map(|shard| shard.cache.stats()).sum()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Sets every statistic of every shard back to zero
+    pub fn reset_stats(&self) {
+        for shard in &self.shards {
+            shard.cache.reset_stats();
+            shard.evictions_seen.store(0, Ordering::Relaxed);
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the occupancy, current capacity and activity of each shard
+    pub fn shard_stats(&self) -> Vec<ShardStats> {
+        self.shards
+            .iter()
+            .map(|shard| ShardStats {
+                len: shard.cache.len(),
+                capacity: shard.quota.load(Ordering::Relaxed),
+                stats: shard.cache.stats(),
+            })
+            .collect()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Formats each shard's statistics in the Prometheus text exposition format, as [`CacheStats::to_prometheus`]
+    /// does, with the shard's index in a `shard` label.  Sum over the label for the whole cache
+    pub fn to_prometheus(&self, name: &str) -> String {
+        let samples: Vec<_> = self
+            .shard_stats()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, shard)| (format!("{{shard=\"{idx}\"}}"), shard.stats))
+            .collect();
+
+        stats::prometheus(name, &samples)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Moves capacity from shards with little demand to shards with much, keeping the total fixed.  See the
+    /// [module documentation](self).  Returns straight away if another thread is already rebalancing
+    pub fn rebalance(&self) {
+        let _rebalancing = match self.rebalancing.try_lock() {
+            Err(TryLockError::WouldBlock) => return,
+            guard => guard,
+        };
+
+        let demand: Vec<u64> = self
+            .shards
+            .iter()
+            .map(|shard| {
+                let evictions = shard.cache.stats().evictions;
+                let evicted = evictions.saturating_sub(shard.evictions_seen.swap(evictions, Ordering::Relaxed));
+                shard.cache.len() as u64 + evicted
+            })
+            .collect();
+
+        let Some(quotas) = quotas(self.capacity.get(), &demand) else {
+            return;
+        };
+
+        // Shrink before growing, so that the quotas never add up to more than the capacity.  Items evicted by shrinking
+        // are not demand, so they are marked as seen
+        for growing in [false, true] {
+            for (shard, &quota) in self.shards.iter().zip(&quotas) {
+                if (quota > shard.quota.load(Ordering::Relaxed)) == growing {
+                    shard.quota.store(quota, Ordering::Relaxed);
+                    let evicted = shard.cache.trim(quota);
+                    shard.evictions_seen.fetch_add(evicted as u64, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Splits `capacity` between shards: each receives a minimum, and the rest is shared in proportion to demand.  Returns
+/// `None` if there has been no demand at all
+fn quotas(capacity: usize, demand: &[u64]) -> Option<Vec<usize>> {
+    let total: u128 = demand.iter().map(|&d| d as u128).sum();
+
+    if total == 0 {
+        return None;
+    }
+
+    let minimum = (capacity / demand.len() / MIN_QUOTA_DIVISOR).max(1);
+    let spare = (capacity - minimum * demand.len()) as u128;
+    let mut quotas: Vec<usize> = demand.iter().map(|&d| minimum + (spare * d as u128 / total) as usize).collect();
+
+    // Rounding down leaves a few items of capacity over, which go to the shard in most demand
+    let busiest = (0..demand.len()).max_by_key(|&i| demand[i]).unwrap_or(0);
+    quotas[busiest] += capacity - quotas.iter().sum::<usize>();
+
+    Some(quotas)
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,345 @@
+use super::*;
+use crate::test_utils::*;
+use std::{
+    format,
+    hash::{BuildHasherDefault, DefaultHasher, Hasher},
+    string::String,
+    sync::{Arc, Barrier},
+    thread,
+};
+
+/// A fixed hasher, so that keys land in the same shards on every run
+type Fixed = BuildHasherDefault<DefaultHasher>;
+
+const THREADS: usize = 8;
+
+fn sharded(capacity: usize, shards: usize) -> ShardedLruCache<u64, u64, Fixed> {
+    let (capacity, shards) = (NonZeroUsize::new(capacity).unwrap(), NonZeroUsize::new(shards).unwrap());
+    ShardedLruCache::with_shards_and_hasher(capacity, shards, Fixed::default())
+}
+
+/// Repeatable pseudo-random numbers
+fn xorshift(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = 0x9E37_79B9_7F4A_7C15 ^ seed;
+
+    move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_be_send_and_sync() -> Result<(), String> {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<ShardedLruCache<String, String>>();
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_round_the_shard_count_to_a_power_of_two_within_the_capacity() -> Result<(), String> {
+    let counts = [sharded(100, 6), sharded(3, 16), sharded(1, 4)].map(|c| c.shard_stats().len());
+    let capacities = [sharded(10, 4)].map(|c| c.shard_stats().iter().map(|s| s.capacity).collect::<Vec<_>>());
+
+    match (counts, &capacities[0][..]) {
+        ([8, 2, 1], [3, 3, 2, 2]) => Ok(()),
+        other => Err(format!("Expected 8, 2 and 1 shards, and 10 split as 3, 3, 2, 2. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_put_get_and_remove_across_shards() -> Result<(), String> {
+    let cache = sharded(1_000, 8);
+
+    for i in 0..500 {
+        cache.put(i, i * 10);
+    }
+
+    let found = (0..500).filter(|i| cache.get(i) == Some(i * 10)).count();
+
+    match (found, cache.remove(&7), cache.contains_key(&7), cache.peek(&8), cache.len()) {
+        (500, Some(70), false, Some(80), 499) => Ok(()),
+        other => Err(format!("Expected every item to be found, and one removed. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn quotas_should_share_the_capacity_in_proportion_to_demand() -> Result<(), String> {
+    match (quotas(800, &[0, 0, 300, 100]), quotas(800, &[0; 4]), quotas(5, &[1, 0, 0, 9])) {
+        (Some(q), None, Some(small)) if q == [25, 25, 550, 200] && small == [1, 1, 1, 2] => Ok(()),
+        other => Err(format!("Unexpected quotas {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Hit ratio of a read-through workload over the given keys, measured after the cache has warmed up
+fn skewed_hit_ratio(cache: &ShardedLruCache<u64, u64, Fixed>, keys: &[u64]) -> f64 {
+    let mut next = xorshift(1);
+    let mut read_through = || {
+        let key = keys[(next() % keys.len() as u64) as usize];
+
+        if cache.get(&key).is_none() {
+            cache.put(key, key);
+        }
+    };
+
+    (0..20_000).for_each(|_| read_through());
+    cache.reset_stats();
+    (0..20_000).for_each(|_| read_through());
+    cache.stats().hit_ratio()
+}
+
+#[test]
+fn rebalancing_should_give_hot_shards_more_capacity() -> Result<(), String> {
+    const CAPACITY: usize = 800;
+    const SHARDS: usize = 8;
+
+    let fixed = sharded(CAPACITY, SHARDS).with_rebalance_interval(None);
+    let rebalanced = sharded(CAPACITY, SHARDS).with_rebalance_interval(NonZeroU64::new(1_000));
+
+    // Every key lands in one of two shards, whose equal shares of 100 items each cannot hold all 600
+    let hot = [0, 1].map(|shard| fixed.shards[shard].quota.load(Ordering::Relaxed));
+    let in_hot_shard = |key: &u64| fixed.shard_index(fixed.hash_builder.hash_one(key)) < 2;
+    let keys: Vec<u64> = (0..).filter(in_hot_shard).take(600).collect();
+
+    let (fixed_ratio, rebalanced_ratio) = (skewed_hit_ratio(&fixed, &keys), skewed_hit_ratio(&rebalanced, &keys));
+    let shards = rebalanced.shard_stats();
+    let total: usize = shards.iter().map(|s| s.capacity).sum();
+
+    if hot != [100, 100] || shards[..2].iter().any(|s| s.capacity <= 300) || total != CAPACITY {
+        return Err(format!("Expected the two hot shards to grow from {hot:?} within {CAPACITY}. Got {shards:?}"));
+    }
+
+    if shards.iter().any(|s| s.len > s.capacity) || rebalanced.len() > CAPACITY {
+        return Err(format!("Expected every shard to keep within its capacity. Got {shards:?}"));
+    }
+
+    match (fixed_ratio, rebalanced_ratio) {
+        (fixed, rebalanced) if fixed < 0.4 && rebalanced > 0.95 => Ok(()),
+        other => Err(format!("Expected rebalancing to raise the hit ratio from about 1/3 to nearly 1. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn stats_should_add_up_across_shards_and_threads() -> Result<(), String> {
+    const OPERATIONS: usize = 5_000;
+
+    let cache = Arc::new(sharded(256, 8).with_rebalance_interval(NonZeroU64::new(500)));
+    let barrier = Arc::new(Barrier::new(THREADS));
+
+    let handles: Vec<_> = (0..THREADS as u64)
+        .map(|t| {
+            let (cache, barrier) = (Arc::clone(&cache), Arc::clone(&barrier));
+
+            thread::spawn(move || {
+                let mut next = xorshift(t + 1);
+                let (mut gets, mut puts, mut replaced) = (0, 0, 0);
+                barrier.wait();
+
+                for _ in 0..OPERATIONS {
+                    // Keys are skewed towards the low end so that the shards are unevenly loaded
+                    let key = next() % 1_024 % (next() % 1_024 + 1);
+
+                    if next().is_multiple_of(4) {
+                        puts += 1;
+                        replaced += cache.put(key, key).is_some() as u64;
+                    } else {
+                        gets += 1;
+                        let _ = cache.get(&key);
+                    }
+                }
+
+                (gets, puts, replaced)
+            })
+        })
+        .collect();
+
+    let (mut gets, mut puts, mut replaced) = (0, 0, 0);
+
+    for handle in handles {
+        let (g, p, r) = handle.join().map_err(|_| "A worker thread panicked")?;
+        (gets, puts, replaced) = (gets + g, puts + p, replaced + r);
+    }
+
+    // Nothing is removed, so every item inserted is either still cached or was evicted, by its shard or by rebalancing
+    let stats = cache.stats();
+    let expected = CacheStats {
+        hits: stats.hits,
+        misses: gets - stats.hits,
+        insertions: puts - replaced,
+        updates: replaced,
+        evictions: puts - replaced - cache.len() as u64,
+        entries: cache.len() as u64,
+        ..CacheStats::default()
+    };
+
+    match (stats == expected, stats == cache.shard_stats().iter().map(|s| s.stats).sum()) {
+        (true, true) => Ok(()),
+        other => Err(format!("Expected {expected:?}, also the sum of the shards. Got {stats:?}, {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn snapshots_taken_during_writes_should_be_consistent() -> Result<(), String> {
+    const CAPACITY: usize = 128;
+
+    let cache = Arc::new(sharded(CAPACITY, 4).with_rebalance_interval(NonZeroU64::new(100)));
+    let barrier = Arc::new(Barrier::new(THREADS + 1));
+
+    // Every value is derived from its key, so a torn copy would show up as a mismatched pair.  The total is not checked
+    // against the capacity, since a rebalance may move capacity between shards the snapshot visits before and after it
+    let writers: Vec<_> = (0..THREADS as u64)
+        .map(|t| {
+            let (cache, barrier) = (Arc::clone(&cache), Arc::clone(&barrier));
+
+            thread::spawn(move || {
+                let mut next = xorshift(t + 1);
+                barrier.wait();
+
+                for _ in 0..2_000 {
+                    let key = next() % (4 * CAPACITY as u64);
+                    cache.put(key, key * 3);
+                }
+            })
+        })
+        .collect();
+
+    barrier.wait();
+    let mut problems = Vec::new();
+
+    for _ in 0..200 {
+        let items = cache.snapshot();
+        let mut keys = cache.snapshot_keys();
+        let torn = items.iter().filter(|(k, v)| *v != k * 3).count();
+        keys.sort_unstable();
+        let count = keys.len();
+        keys.dedup();
+
+        if keys.len() != count || torn > 0 {
+            problems.push(format!("{} items, {torn} torn, {count} keys of which {} distinct", items.len(), keys.len()));
+        }
+    }
+
+    for writer in writers {
+        writer.join().map_err(|_| "A writer panicked")?;
+    }
+
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(format!("Expected every snapshot to be consistent. Got {problems:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn try_methods_should_only_wait_for_the_keys_shard() -> Result<(), String> {
+    let cache = Arc::new(sharded(64, 2).with_rebalance_interval(None));
+    let shard_of = |key: &u64| cache.shard_index(cache.hash_builder.hash_one(key));
+    let (mut busy, mut free) = (0..64).partition::<Vec<u64>, _>(|k| shard_of(k) == 0);
+    let (busy, free) = (busy.remove(0), free.remove(0));
+    let locked = Arc::new(Barrier::new(2));
+    let release = Arc::new(Barrier::new(2));
+
+    let holder = {
+        let (cache, locked, release) = (Arc::clone(&cache), Arc::clone(&locked), Arc::clone(&release));
+
+        thread::spawn(move || {
+            cache.shards[0].cache.with_lock(|_| {
+                locked.wait();
+                release.wait();
+            })
+        })
+    };
+
+    locked.wait();
+    let results = (cache.try_get(&busy), cache.try_put(busy, 1), cache.try_put(free, 2), cache.try_get(&free));
+    release.wait();
+    holder.join().map_err(|_| "The lock holder panicked")?;
+
+    match results {
+        (Err(WouldBlock(())), Err(WouldBlock((key, 1))), Ok(None), Ok(Some(2))) if key == busy => Ok(()),
+        other => Err(format!("Expected only the locked shard to report the held lock. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_hold_large_values_in_every_shard() -> Result<(), String> {
+    let cache = ShardedLruCache::with_shards(NonZeroUsize::new(100).unwrap(), NonZeroUsize::new(4).unwrap());
+
+    for i in 0..200 {
+        cache.put(gen_item_key(i), gen_item_value(i as u32));
+    }
+
+    match (cache.len(), cache.shard_stats().iter().all(|s| s.len <= s.capacity)) {
+        (len, true) if len <= 100 => Ok(()),
+        other => Err(format!("Expected no more than 100 items, each shard within its capacity. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn to_prometheus_should_label_each_shard() -> Result<(), String> {
+    let cache = sharded(64, 4);
+
+    for key in 0..100 {
+        cache.put(key, key);
+        let _ = cache.get(&(key / 2));
+    }
+
+    let samples = parse_prometheus(&cache.to_prometheus("sharded"))?;
+    let total = |family: &str| -> f64 {
+        (0..4).filter_map(|idx| samples.get(&format!("{family}{{shard=\"{idx}\"}}"))).sum()
+    };
+    let stats = cache.stats();
+    let expected = [stats.hits, stats.misses, stats.evictions, stats.entries].map(|n| n as f64);
+    let sums = ["sharded_hits_total", "sharded_misses_total", "sharded_evictions_total", "sharded_entries"].map(total);
+
+    match (samples.len(), sums) {
+        (28, sums) if sums == expected => Ok(()),
+        other => Err(format!("Expected 7 samples for each of 4 shards adding up to {stats:?}. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A hasher that sends every key to shard 0
+#[derive(Default)]
+struct Zero;
+
+impl Hasher for Zero {
+    fn finish(&self) -> u64 {
+        0
+    }
+
+    fn write(&mut self, _: &[u8]) {}
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn shard_stats_should_show_a_skewed_hash_overloading_one_shard() -> Result<(), String> {
+    let (capacity, shards) = (NonZeroUsize::new(64).unwrap(), NonZeroUsize::new(4).unwrap());
+    let cache = ShardedLruCache::with_shards_and_hasher(capacity, shards, BuildHasherDefault::<Zero>::default())
+        .with_rebalance_interval(None);
+
+    for key in 0..40_u64 {
+        cache.put(key, key);
+        let _ = cache.get(&key);
+        let _ = cache.get(&(key + 1_000));
+    }
+
+    let shards = cache.shard_stats();
+    let summary: Vec<_> = shards.iter().map(|s| (s.len, s.stats.evictions, s.stats.hits, s.stats.misses)).collect();
+    let total: CacheStats = shards.iter().map(|s| s.stats).sum();
+
+    match summary[..] {
+        [(16, 24, 40, 40), (0, 0, 0, 0), (0, 0, 0, 0), (0, 0, 0, 0)] if total == cache.stats() => Ok(()),
+        _ => Err(format!("Expected shard 0 to take every key and evict 24. Got {summary:?}")),
+    }
+}
@@ -0,0 +1,258 @@
+//! Replaying a recorded trace to predict the hit ratio at other capacities
+//!
+//! [`simulate`] reads a trace in the format written by [`LruCache::set_trace_writer`] and replays it through an
+//! [`LruCache`] of each capacity asked for, to answer "what hit ratio would a cache of this size have had?" without
+//! deploying one.  Each `get` in the trace looks its key up, each `put` inserts it and each `remove` removes it, so
+//! the replay follows the recorded program rather than the recorded outcomes: a `get` that missed in production may
+//! hit in a larger cache, and the `put` that followed it then updates the item.
+//!
+//! Keys are identified by their hashes alone, which is all a trace is guaranteed to hold.  The trace is read once,
+//! in batches that are shared by one thread per capacity, so the trace never has to fit in memory
+use crate::{LruCache, LruCacheBuilder, TraceOp, TraceParseError, TraceRecord};
+use core::{
+    fmt,
+    hash::{BuildHasherDefault, Hasher},
+    num::NonZeroUsize,
+};
+use std::{
+    io::{self, BufRead},
+    string::String,
+    sync::{Arc, mpsc},
+    thread,
+    vec::Vec,
+};
+
+/// Number of operations sent to the replaying threads at a time
+const BATCH_LEN: usize = 4096;
+
+/// Number of batches each replaying thread may fall behind the reader
+const BATCHES_AHEAD: usize = 4;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Why a trace could not be read
+#[derive(Debug)]
+pub enum TraceError {
+    Io(io::Error),
+    /// The line with the given number, counting from 1, is not a [`TraceRecord`]
+    Parse { line: u64, error: TraceParseError },
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceError::Io(e) => write!(f, "failed to read trace: {e}"),
+            TraceError::Parse { line, error } => write!(f, "line {line}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TraceError::Io(e) => Some(e),
+            TraceError::Parse { error, .. } => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for TraceError {
+    fn from(e: io::Error) -> Self {
+        TraceError::Io(e)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Iterator over the records of a trace, returned by [`read_trace`]
+pub struct TraceReader<R> {
+    reader: R,
+    line: String,
+    line_number: u64,
+}
+
+/// Reads a trace one line at a time.  Blank lines are skipped
+pub fn read_trace<R: BufRead>(reader: R) -> TraceReader<R> {
+    TraceReader {
+        reader,
+        line: String::new(),
+        line_number: 0,
+    }
+}
+
+impl<R: BufRead> Iterator for TraceReader<R> {
+    type Item = Result<TraceRecord, TraceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line.clear();
+
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => self.line_number += 1,
+                Err(e) => return Some(Err(e.into())),
+            }
+
+            let line = self.line.trim_end_matches(['\n', '\r']);
+
+            if !line.is_empty() {
+                return Some(line.parse().map_err(|error| TraceError::Parse {
+                    line: self.line_number,
+                    error,
+                }));
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The outcome of replaying a trace through a cache of one capacity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimulationReport {
+    pub capacity: NonZeroUsize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// The most items the cache held at once
+    pub peak_entries: usize,
+    /// The [`memory_usage`](LruCache::memory_usage) of the replaying cache when it held the most items.  Its keys are
+    /// 8-byte hashes and it holds no values, so see [`estimated_memory`](Self::estimated_memory) for real entries
+    pub peak_memory: usize,
+}
+
+impl SimulationReport {
+    /// Fraction of `get`s that found an item, or `0.0` if there were none
+    pub fn hit_ratio(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Estimates the peak memory of a cache whose keys and values take `entry_bytes` more than the replaying cache's,
+    /// for example `size_of::<(K, V)>()` less 8, plus any heap memory they own
+    pub fn estimated_memory(&self, entry_bytes: usize) -> usize {
+        self.peak_memory + self.peak_entries * entry_bytes
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Replays the trace read from `reader` through a cache of each of `capacities` in parallel, returning a report for
+/// each in the same order.  Stops at the first line that cannot be read
+pub fn simulate<R: BufRead>(reader: R, capacities: &[NonZeroUsize]) -> Result<Vec<SimulationReport>, TraceError> {
+    thread::scope(|scope| {
+        let (senders, workers): (Vec<_>, Vec<_>) = capacities
+            .iter()
+            .map(|&capacity| {
+                let (sender, receiver) = mpsc::sync_channel::<Arc<[(TraceOp, u64)]>>(BATCHES_AHEAD);
+                let worker = scope.spawn(move || {
+                    let mut replay = Replay::new(capacity);
+                    receiver.iter().for_each(|batch| batch.iter().for_each(|&(op, hash)| replay.apply(op, hash)));
+                    replay.report()
+                });
+                (sender, worker)
+            })
+            .unzip();
+
+        // Dropping the senders on an error lets the threads finish, so that the scope can end
+        let send = |batch: &mut Vec<_>| {
+            let batch: Arc<[_]> = core::mem::take(batch).into();
+
+            for sender in &senders {
+                let _ = sender.send(Arc::clone(&batch));
+            }
+        };
+        let mut batch = Vec::with_capacity(BATCH_LEN);
+
+        for record in read_trace(reader) {
+            let record = record?;
+            batch.push((record.op, record.key_hash));
+
+            if batch.len() == BATCH_LEN {
+                send(&mut batch);
+            }
+        }
+
+        send(&mut batch);
+        drop(senders);
+
+        Ok(workers.into_iter().map(|worker| worker.join().unwrap_or_else(|e| std::panic::resume_unwind(e))).collect())
+    })
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The cache replaying a trace at one capacity
+struct Replay {
+    cache: LruCache<u64, (), BuildHasherDefault<Prehashed>>,
+    peak_entries: usize,
+    peak_memory: usize,
+}
+
+impl Replay {
+    fn new(capacity: NonZeroUsize) -> Self {
+        // Growing as items arrive, rather than allocating the capacity up front, makes the memory usage meaningful
+        let cache = LruCacheBuilder::new(capacity).initial_capacity(0).hasher(BuildHasherDefault::default()).build();
+
+        Replay {
+            cache,
+            peak_entries: 0,
+            peak_memory: 0,
+        }
+    }
+
+    fn apply(&mut self, op: TraceOp, hash: u64) {
+        match op {
+            TraceOp::Get => {
+                self.cache.get(&hash);
+            }
+            TraceOp::Put => {
+                self.cache.put(hash, ());
+
+                if self.cache.len() > self.peak_entries {
+                    self.peak_entries = self.cache.len();
+                    self.peak_memory = self.peak_memory.max(self.cache.memory_usage());
+                }
+            }
+            TraceOp::Remove => {
+                self.cache.remove(&hash);
+            }
+        }
+    }
+
+    fn report(&self) -> SimulationReport {
+        let stats = self.cache.stats();
+
+        SimulationReport {
+            capacity: self.cache.capacity(),
+            hits: stats.hits,
+            misses: stats.misses,
+            evictions: stats.evictions,
+            peak_entries: self.peak_entries,
+            peak_memory: self.peak_memory,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Passes a key that is already a hash straight through
+#[derive(Default)]
+struct Prehashed(u64);
+
+impl Hasher for Prehashed {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 << 8) | u64::from(byte);
+        }
+    }
+
+    fn write_u64(&mut self, hash: u64) {
+        self.0 = hash;
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
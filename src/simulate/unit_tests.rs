@@ -0,0 +1,74 @@
+use super::*;
+
+const FIXTURE: &[u8] = include_bytes!("fixture.trace");
+
+const fn cap(n: usize) -> NonZeroUsize {
+    NonZeroUsize::new(n).unwrap()
+}
+
+/// The counts of a report, leaving out its memory usage
+fn counts(r: &SimulationReport) -> (usize, u64, u64, u64, usize) {
+    (r.capacity.get(), r.hits, r.misses, r.evictions, r.peak_entries)
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_replay_fixture_at_each_capacity() -> Result<(), String> {
+    let reports = simulate(FIXTURE, &[cap(1), cap(2), cap(3)]).map_err(|e| e.to_string())?;
+    let memory_grows = reports.windows(2).all(|r| r[0].peak_memory > 0 && r[0].peak_memory <= r[1].peak_memory);
+
+    // At capacity 2, putting 3 evicts 2, so only the later lookup of 1 hits again
+    match (reports.iter().map(counts).collect::<Vec<_>>(), memory_grows) {
+        (counts, true) if counts == [(1, 0, 7, 2, 1), (2, 2, 5, 1, 2), (3, 3, 4, 0, 3)] => Ok(()),
+        other => Err(format!("Expected ([(1, 0, 7, 2, 1), (2, 2, 5, 1, 2), (3, 3, 4, 0, 3)], true). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_read_records_skipping_blank_lines() -> Result<(), String> {
+    let records = read_trace(FIXTURE).collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+
+    let expected = TraceRecord {
+        time: 9,
+        op: TraceOp::Remove,
+        hit: true,
+        key_hash: 3,
+        key: Some(String::from("gamma")),
+    };
+
+    match (records.len(), records.get(9)) {
+        (11, Some(record)) if *record == expected => Ok(()),
+        other => Err(format!("Expected (11, Some({expected:?})). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_report_line_that_cannot_be_parsed() -> Result<(), String> {
+    let trace = b"0 put miss 0000000000000001\n\n2 get sometimes 0000000000000001\n";
+
+    match simulate(&trace[..], &[cap(1), cap(2)]) {
+        Err(TraceError::Parse { line: 3, error: TraceParseError::InvalidField("outcome") }) => Ok(()),
+        other => Err(format!("Expected Err(Parse {{ line: 3, error: InvalidField(\"outcome\") }}). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_compute_hit_ratio_and_memory_estimate() -> Result<(), String> {
+    let report = SimulationReport {
+        capacity: cap(4),
+        hits: 3,
+        misses: 1,
+        evictions: 0,
+        peak_entries: 4,
+        peak_memory: 1000,
+    };
+    let empty = SimulationReport { hits: 0, misses: 0, ..report };
+
+    match (report.hit_ratio(), empty.hit_ratio(), report.estimated_memory(16)) {
+        (0.75, 0.0, 1064) => Ok(()),
+        other => Err(format!("Expected (0.75, 0.0, 1064). Got {other:?}")),
+    }
+}
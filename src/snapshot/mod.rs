@@ -0,0 +1,250 @@
+//! Binary snapshots
+//!
+//! A snapshot is a small envelope (magic bytes, format version and a flags byte) followed by the cache's serde
+//! representation encoded with `bincode`.  Entries are stored from least to most recently used, so restoring a snapshot
+//! reproduces the original recency order.
+//!
+//! With the `compression` feature, [`LruCache::write_snapshot_compressed`] writes the body as a zstd stream.  The flags
+//! byte records whether the body is compressed, so [`LruCache::read_snapshot`] reads either kind of snapshot.
+//!
+//! [`LruCache::load_archived`] decodes directly from a byte slice (for instance a memory-mapped file) in a single linear
+//! pass.  When the key or value type borrows from the input (e.g. `&str` or `&[u8]`), no key or value data is copied.
+use crate::LruCache;
+use bincode::Options;
+use core::{
+    fmt,
+    hash::{BuildHasher, Hash},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 4] = b"LRUC";
+const FORMAT_VERSION: u8 = 2;
+const HEADER_LEN: usize = MAGIC.len() + 2;
+
+/// Flags byte value for a body that is not compressed
+const FLAG_NONE: u8 = 0;
+/// Flags byte value for a body compressed as a zstd stream
+const FLAG_ZSTD: u8 = 1;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Errors that can occur while writing or reading a snapshot
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The underlying reader or writer failed
+    Io(io::Error),
+    /// The data does not start with the snapshot magic bytes
+    InvalidHeader,
+    /// The snapshot was written using an unknown format version
+    UnsupportedVersion(u8),
+    /// The snapshot body could not be decoded or does not describe a valid cache
+    Corrupt(String),
+    /// The snapshot body is compressed, but the `compression` feature is not enabled
+    CompressionUnsupported,
+    /// The compressed snapshot body could not be decompressed
+    Decompression(String),
+    /// A compressed snapshot cannot be loaded without copying; use `read_snapshot` instead
+    CompressedArchive,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "snapshot I/O failed: {e}"),
+            SnapshotError::InvalidHeader => write!(f, "data is not a cache snapshot"),
+            SnapshotError::UnsupportedVersion(v) => write!(f, "unsupported snapshot format version {v}"),
+            SnapshotError::Corrupt(reason) => write!(f, "snapshot is corrupt: {reason}"),
+            SnapshotError::CompressionUnsupported => {
+                write!(f, "snapshot is compressed but the compression feature is not enabled")
+            }
+            SnapshotError::Decompression(reason) => write!(f, "snapshot could not be decompressed: {reason}"),
+            SnapshotError::CompressedArchive => write!(f, "compressed snapshots cannot be loaded in place"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SnapshotError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for SnapshotError {
+    fn from(e: bincode::Error) -> Self {
+        match *e {
+            #[cfg(feature = "compression")]
+            bincode::ErrorKind::Io(e) if e.get_ref().is_some_and(|inner| inner.is::<DecompressionFailed>()) => {
+                SnapshotError::Decompression(e.into_inner().map(|inner| inner.to_string()).unwrap_or_default())
+            }
+            bincode::ErrorKind::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                SnapshotError::Corrupt(String::from("snapshot is truncated"))
+            }
+            bincode::ErrorKind::Io(e) => SnapshotError::Io(e),
+            other => SnapshotError::Corrupt(other.to_string()),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+pub(crate) fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new().reject_trailing_bytes()
+}
+
+/// Validates the envelope, returning the flags byte
+fn check_header(header: &[u8]) -> Result<u8, SnapshotError> {
+    if header.len() < HEADER_LEN || &header[..MAGIC.len()] != MAGIC {
+        Err(SnapshotError::InvalidHeader)
+    } else if header[MAGIC.len()] != FORMAT_VERSION {
+        Err(SnapshotError::UnsupportedVersion(header[MAGIC.len()]))
+    } else {
+        match header[MAGIC.len() + 1] {
+            flags @ (FLAG_NONE | FLAG_ZSTD) => Ok(flags),
+            flags => Err(SnapshotError::Corrupt(format!("unknown flags {flags:#04x}"))),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Marks an I/O error raised by the zstd decoder, so it can be reported as [`SnapshotError::Decompression`] after
+/// passing through `bincode`
+#[cfg(feature = "compression")]
+#[derive(Debug)]
+struct DecompressionFailed(io::Error);
+
+#[cfg(feature = "compression")]
+impl fmt::Display for DecompressionFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(feature = "compression")]
+impl std::error::Error for DecompressionFailed {}
+
+/// Reader that tags every error raised by the zstd decoder
+#[cfg(feature = "compression")]
+struct ZstdReader<'a, R: Read>(zstd::Decoder<'a, io::BufReader<R>>);
+
+#[cfg(feature = "compression")]
+impl<R: Read> Read for ZstdReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0
+            .read(buf)
+            .map_err(|e| io::Error::new(e.kind(), DecompressionFailed(e)))
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, S> LruCache<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    /// Writes a binary snapshot of the cache
+    pub fn write_snapshot<W: Write>(&self, mut writer: W) -> Result<(), SnapshotError>
+    where
+        K: Serialize,
+        V: Serialize,
+    {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[FORMAT_VERSION, FLAG_NONE])?;
+        bincode_options().serialize_into(&mut writer, self)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Writes a binary snapshot of the cache whose body is compressed with zstd at the given compression `level`
+    /// (`1` to `22`, or `0` for zstd's default)
+    #[cfg(feature = "compression")]
+    pub fn write_snapshot_compressed<W: Write>(&self, mut writer: W, level: i32) -> Result<(), SnapshotError>
+    where
+        K: Serialize,
+        V: Serialize,
+    {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[FORMAT_VERSION, FLAG_ZSTD])?;
+
+        let mut encoder = zstd::Encoder::new(writer, level)?;
+        bincode_options().serialize_into(&mut encoder, self)?;
+        encoder.finish()?.flush()?;
+
+        Ok(())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns a binary snapshot of the cache
+    pub fn to_snapshot(&self) -> Result<Vec<u8>, SnapshotError>
+    where
+        K: Serialize,
+        V: Serialize,
+    {
+        let mut buf = Vec::new();
+        self.write_snapshot(&mut buf)?;
+        Ok(buf)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Restores a cache from a binary snapshot read from `reader`, decompressing it if necessary
+    pub fn read_snapshot<R: Read>(mut reader: R) -> Result<Self, SnapshotError>
+    where
+        K: DeserializeOwned,
+        V: DeserializeOwned,
+    {
+        let mut header = [0u8; HEADER_LEN];
+        reader.read_exact(&mut header).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => SnapshotError::InvalidHeader,
+            _ => SnapshotError::Io(e),
+        })?;
+
+        match check_header(&header)? {
+            FLAG_NONE => Ok(bincode_options().deserialize_from(reader)?),
+            #[cfg(feature = "compression")]
+            _ => {
+                let decoder = zstd::Decoder::new(reader).map_err(|e| SnapshotError::Decompression(e.to_string()))?;
+                let mut zstd_reader = ZstdReader(decoder);
+                let cache = bincode_options().deserialize_from(&mut zstd_reader)?;
+
+                // Drain the rest of the frame so that a damaged frame checksum is detected
+                io::copy(&mut zstd_reader, &mut io::sink()).map_err(|e| SnapshotError::Decompression(e.to_string()))?;
+                Ok(cache)
+            }
+            #[cfg(not(feature = "compression"))]
+            _ => Err(SnapshotError::CompressionUnsupported),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Restores a cache from a binary snapshot held in memory, validating it in a single linear pass.
+    ///
+    /// Keys and values whose types borrow from `bytes` (such as `&str` or `&[u8]`) are not copied.  Compressed snapshots
+    /// cannot be loaded this way
+    pub fn load_archived<'a>(bytes: &'a [u8]) -> Result<Self, SnapshotError>
+    where
+        K: Deserialize<'a>,
+        V: Deserialize<'a>,
+    {
+        if check_header(bytes)? != FLAG_NONE {
+            return Err(SnapshotError::CompressedArchive);
+        }
+
+        // Limiting decoding to the size of the input stops a corrupt length prefix triggering a huge allocation
+        Ok(bincode_options()
+            .with_limit(bytes.len() as u64)
+            .deserialize(&bytes[HEADER_LEN..])?)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
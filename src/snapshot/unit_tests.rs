@@ -0,0 +1,204 @@
+use super::*;
+use crate::test_utils::*;
+use std::num::NonZeroUsize;
+
+const CAPACITY: NonZeroUsize = NonZeroUsize::new(100).unwrap();
+
+fn non_trivial_cache() -> LruCache<String, Vec<u8>> {
+    let mut c = LruCache::new(CAPACITY);
+
+    // Overfill the cache and shuffle the recency order
+    for idx in 0..150 {
+        c.put(gen_item_key(idx), gen_item_value(idx as u32).into_bytes());
+    }
+
+    for idx in (60..140).step_by(3) {
+        c.get(gen_item_key(idx).as_str());
+    }
+
+    c
+}
+
+fn drain_lru_first<K, V, S>(mut c: LruCache<K, V, S>) -> Vec<V>
+where
+    K: Eq + Hash,
+    V: Clone,
+    S: BuildHasher,
+{
+    let mut values = Vec::new();
+
+    while let Some(v) = c.pop_lru() {
+        values.push(v);
+    }
+
+    values
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_round_trip_through_reader_and_writer() -> Result<(), String> {
+    let original = non_trivial_cache();
+    let snapshot = original.to_snapshot().map_err(|e| e.to_string())?;
+    let restored: LruCache<String, Vec<u8>> =
+        LruCache::read_snapshot(snapshot.as_slice()).map_err(|e| e.to_string())?;
+
+    if drain_lru_first(original) == drain_lru_first(restored) {
+        Ok(())
+    } else {
+        Err(String::from("Restored cache does not match the original"))
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_load_archived_snapshot_without_copying() -> Result<(), String> {
+    let original = non_trivial_cache();
+    let snapshot = original.to_snapshot().map_err(|e| e.to_string())?;
+    let restored: LruCache<&str, &[u8]> = LruCache::load_archived(&snapshot).map_err(|e| e.to_string())?;
+
+    // Borrowed values point into the snapshot buffer
+    let buf_range = snapshot.as_ptr_range();
+    let lru = restored.peek("item-50").ok_or("item-50 not found")?;
+
+    if !buf_range.contains(&lru.as_ptr()) {
+        return Err(String::from("Value was copied out of the snapshot buffer"));
+    }
+
+    let expected: Vec<Vec<u8>> = drain_lru_first(original);
+    let actual: Vec<Vec<u8>> = drain_lru_first(restored).into_iter().map(<[u8]>::to_vec).collect();
+
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(String::from("Archived cache does not reproduce the original recency order"))
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_reject_corrupted_snapshots() -> Result<(), String> {
+    let snapshot = non_trivial_cache().to_snapshot().map_err(|e| e.to_string())?;
+
+    let mut bad_magic = snapshot.clone();
+    bad_magic[0] = b'X';
+
+    let mut bad_version = snapshot.clone();
+    bad_version[MAGIC.len()] = FORMAT_VERSION + 1;
+
+    let truncated = &snapshot[..snapshot.len() / 2];
+
+    let mut bad_length = snapshot.clone();
+    bad_length[HEADER_LEN..HEADER_LEN + 9].copy_from_slice(&[0xfd, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]);
+
+    match LruCache::<&str, &[u8]>::load_archived(&bad_magic) {
+        Err(SnapshotError::InvalidHeader) => (),
+        other => return Err(format!("Expected InvalidHeader. Got {:?}", other.err())),
+    }
+
+    match LruCache::<&str, &[u8]>::load_archived(&bad_version) {
+        Err(SnapshotError::UnsupportedVersion(_)) => (),
+        other => return Err(format!("Expected UnsupportedVersion. Got {:?}", other.err())),
+    }
+
+    for (name, bytes) in [("truncated", truncated), ("bad length", &bad_length)] {
+        match LruCache::<&str, &[u8]>::load_archived(bytes) {
+            Err(SnapshotError::Corrupt(_)) => (),
+            other => return Err(format!("Expected Corrupt for {name} snapshot. Got {:?}", other.err())),
+        }
+    }
+
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_reject_snapshot_exceeding_capacity() -> Result<(), String> {
+    let mut c: LruCache<u32, u32> = LruCache::new(NonZeroUsize::new(3).unwrap());
+    c.put(1, 1);
+    c.put(2, 2);
+
+    let mut snapshot = c.to_snapshot().map_err(|e| e.to_string())?;
+
+    // Shrink the recorded capacity (a varint immediately after the header) below the number of entries
+    snapshot[HEADER_LEN] = 1;
+
+    match LruCache::<u32, u32>::load_archived(&snapshot) {
+        Err(SnapshotError::Corrupt(_)) => Ok(()),
+        other => Err(format!("Expected Corrupt. Got {:?}", other.err())),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[cfg(feature = "compression")]
+fn compressible_cache() -> LruCache<String, String> {
+    let mut c = LruCache::new(CAPACITY);
+
+    for idx in 0..CAPACITY.get() {
+        c.put(gen_item_key(idx), format!(r#"{{"id":{idx},"payload":"{}"}}"#, "abc".repeat(50)));
+    }
+
+    c.get("item-3");
+    c
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[cfg(feature = "compression")]
+#[test]
+fn should_round_trip_compressed_snapshot() -> Result<(), String> {
+    let original = compressible_cache();
+    let plain = original.to_snapshot().map_err(|e| e.to_string())?;
+    let mut compressed = Vec::new();
+    original
+        .write_snapshot_compressed(&mut compressed, 3)
+        .map_err(|e| e.to_string())?;
+
+    if compressed.len() >= plain.len() / 2 {
+        return Err(format!("Compressed snapshot is {} bytes, plain is {}", compressed.len(), plain.len()));
+    }
+
+    // The same reader accepts both compressed and uncompressed snapshots
+    let from_compressed: LruCache<String, String> =
+        LruCache::read_snapshot(compressed.as_slice()).map_err(|e| e.to_string())?;
+    let from_plain: LruCache<String, String> = LruCache::read_snapshot(plain.as_slice()).map_err(|e| e.to_string())?;
+
+    let expected = drain_lru_first(original);
+
+    if drain_lru_first(from_compressed) != expected {
+        Err(String::from("Cache restored from compressed snapshot does not match the original"))
+    } else if drain_lru_first(from_plain) != expected {
+        Err(String::from("Cache restored from plain snapshot does not match the original"))
+    } else {
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[cfg(feature = "compression")]
+#[test]
+fn should_reject_truncated_compressed_stream() -> Result<(), String> {
+    let mut compressed = Vec::new();
+    compressible_cache()
+        .write_snapshot_compressed(&mut compressed, 0)
+        .map_err(|e| e.to_string())?;
+    compressed.truncate(compressed.len() - 10);
+
+    match LruCache::<String, String>::read_snapshot(compressed.as_slice()) {
+        Err(SnapshotError::Decompression(_)) => Ok(()),
+        other => Err(format!("Expected Decompression. Got {:?}", other.err())),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[cfg(feature = "compression")]
+#[test]
+fn should_refuse_to_load_compressed_archive_in_place() -> Result<(), String> {
+    let mut compressed = Vec::new();
+    compressible_cache()
+        .write_snapshot_compressed(&mut compressed, 0)
+        .map_err(|e| e.to_string())?;
+
+    match LruCache::<&str, &str>::load_archived(&compressed) {
+        Err(SnapshotError::CompressedArchive) => Ok(()),
+        other => Err(format!("Expected CompressedArchive. Got {:?}", other.err())),
+    }
+}
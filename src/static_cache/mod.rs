@@ -0,0 +1,364 @@
+//! Fixed-capacity LRU cache that never allocates
+//!
+//! [`StaticLruCache`] keeps its entries in an array of `N` nodes, finds them through an open-addressed table of `N`
+//! slots (linear probing with backward-shift deletion), and records recency with an intrusive doubly linked list of
+//! node indices.  Removing an entry moves the last node into the hole, so the occupied nodes are always `0..len`.
+//!
+//! The cache can be built with `const fn` [`StaticLruCache::new`], so it can live in a `static` on targets with no
+//! allocator.  Keys are hashed with FNV-1a, which needs no random seed.  Since the probe table has exactly `N` slots,
+//! a lookup for a missing key in a full cache may probe every slot; the cache is intended for small `N`.
+use core::{
+    borrow::Borrow,
+    hash::{Hash, Hasher},
+};
+
+const NIL: usize = usize::MAX;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// FNV-1a hasher
+struct FnvHasher(u64);
+
+impl FnvHasher {
+    const fn new() -> Self {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+struct Node<K, V> {
+    key: K,
+    value: V,
+    /// Slot in the probe table where this key would ideally live
+    home: usize,
+    /// Slot in the probe table that currently points at this node
+    slot: usize,
+    prev: usize,
+    next: usize,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache holding at most `N` items in fixed-size arrays
+pub struct StaticLruCache<K, V, const N: usize> {
+    nodes: [Option<Node<K, V>>; N],
+    table: [usize; N],
+    len: usize,
+    head: usize,
+    tail: usize,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, const N: usize> StaticLruCache<K, V, N> {
+    /// Creates an empty cache.  `N` must be greater than zero
+    pub const fn new() -> Self {
+        const { assert!(N > 0, "StaticLruCache capacity must be greater than zero") };
+
+        StaticLruCache {
+            nodes: [const { None }; N],
+            table: [NIL; N],
+            len: 0,
+            head: NIL,
+            tail: NIL,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in the cache
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains no items
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the cache can hold
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn node(&self, idx: usize) -> &Node<K, V> {
+        self.nodes[idx].as_ref().expect("linked node is occupied")
+    }
+
+    fn node_mut(&mut self, idx: usize) -> &mut Node<K, V> {
+        self.nodes[idx].as_mut().expect("linked node is occupied")
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Detaches a node from the recency list
+    fn unlink(&mut self, idx: usize) {
+        let (prev, next) = {
+            let node = self.node(idx);
+            (node.prev, node.next)
+        };
+
+        match prev {
+            NIL => self.head = next,
+            p => self.node_mut(p).next = next,
+        }
+
+        match next {
+            NIL => self.tail = prev,
+            n => self.node_mut(n).prev = prev,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attaches a detached node to the front (MRU end) of the recency list
+    fn link_front(&mut self, idx: usize) {
+        let old_head = self.head;
+        {
+            let node = self.node_mut(idx);
+            node.prev = NIL;
+            node.next = old_head;
+        }
+
+        match old_head {
+            NIL => self.tail = idx,
+            h => self.node_mut(h).prev = idx,
+        }
+
+        self.head = idx;
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the node at `idx` from the probe table, the recency list and the node array
+    fn remove_node(&mut self, idx: usize) -> (K, V) {
+        self.unlink(idx);
+        self.remove_slot(self.node(idx).slot);
+
+        let removed = self.nodes[idx].take().expect("removed node is occupied");
+        let last = self.len - 1;
+        self.len = last;
+
+        // Keep the occupied nodes contiguous by moving the last node into the hole
+        if idx != last {
+            self.nodes[idx] = self.nodes[last].take();
+
+            let (prev, next, slot) = {
+                let moved = self.node(idx);
+                (moved.prev, moved.next, moved.slot)
+            };
+
+            match prev {
+                NIL => self.head = idx,
+                p => self.node_mut(p).next = idx,
+            }
+
+            match next {
+                NIL => self.tail = idx,
+                n => self.node_mut(n).prev = idx,
+            }
+
+            self.table[slot] = idx;
+        }
+
+        (removed.key, removed.value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Empties a probe table slot, shifting later entries of the probe sequence back so lookups still find them
+    fn remove_slot(&mut self, slot: usize) {
+        self.table[slot] = NIL;
+        let mut hole = slot;
+        let mut probe = slot;
+
+        loop {
+            probe = (probe + 1) % N;
+            let idx = self.table[probe];
+
+            if idx == NIL {
+                break;
+            }
+
+            let home = self.node(idx).home;
+
+            // The entry can move into the hole unless its home lies cyclically within (hole, probe]
+            let stays = if hole <= probe {
+                hole < home && home <= probe
+            } else {
+                hole < home || home <= probe
+            };
+
+            if !stays {
+                self.table[hole] = idx;
+                self.node_mut(idx).slot = hole;
+                self.table[probe] = NIL;
+                hole = probe;
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the most recently used item
+    pub fn pop_mru(&mut self) -> Option<V> {
+        match self.head {
+            NIL => None,
+            idx => Some(self.remove_node(idx).1),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes the least recently used item
+    pub fn pop_lru(&mut self) -> Option<V> {
+        match self.tail {
+            NIL => None,
+            idx => Some(self.remove_node(idx).1),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, const N: usize> StaticLruCache<K, V, N>
+where
+    K: Eq + Hash,
+{
+    fn home_slot<Q: Hash + ?Sized>(key: &Q) -> usize {
+        let mut hasher = FnvHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() % N as u64) as usize
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the index of the node holding `key`
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut slot = Self::home_slot(key);
+
+        for _ in 0..N {
+            match self.table[slot] {
+                NIL => return None,
+                idx if self.node(idx).key.borrow() == key => return Some(idx),
+                _ => slot = (slot + 1) % N,
+            }
+        }
+
+        None
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a reference to an item, making it the most recently used
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.find(key)?;
+
+        // Update key's order to MRU
+        self.unlink(idx);
+        self.link_front(idx);
+        Some(&self.node(idx).value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a copy of an item, making it the most recently used
+    pub fn get_cloned<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.get(key).cloned()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetch an item without changing its position in the recency order
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).map(|idx| &self.node(idx).value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains the key. The item's position in the recency order is not changed
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an item, returning its value if it was present
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.find(key)?;
+        Some(self.remove_node(idx).1)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts a new item.
+    /// * If the item already exists, it returns the old value else it returns `None`
+    /// * If the addition of the new item exceeds the cache's capacity, the oldest item is evicted before the new item is
+    ///   added
+    pub fn put(&mut self, key: K, new_value: V) -> Option<V> {
+        if let Some(idx) = self.find(&key) {
+            self.unlink(idx);
+            self.link_front(idx);
+            return Some(core::mem::replace(&mut self.node_mut(idx).value, new_value));
+        }
+
+        if self.len == N {
+            self.pop_lru();
+        }
+
+        let home = Self::home_slot(&key);
+        let mut slot = home;
+
+        while self.table[slot] != NIL {
+            slot = (slot + 1) % N;
+        }
+
+        let idx = self.len;
+        self.len += 1;
+        self.table[slot] = idx;
+        self.nodes[idx] = Some(Node {
+            key,
+            value: new_value,
+            home,
+            slot,
+            prev: NIL,
+            next: NIL,
+        });
+        self.link_front(idx);
+
+        None
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, const N: usize> Default for StaticLruCache<K, V, N> {
+    fn default() -> Self {
+        StaticLruCache::new()
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(all(test, feature = "std"))]
+mod unit_tests;
@@ -0,0 +1,170 @@
+//! The scenarios from the main cache's unit tests, run against the static variant
+use super::*;
+use crate::test_utils::*;
+use std::{format, string::String};
+
+const CAPACITY: usize = 10;
+
+fn default_prefilled_cache() -> StaticLruCache<String, String, CAPACITY> {
+    let mut c = StaticLruCache::new();
+
+    for idx in 0..CAPACITY {
+        let _ = c.put(gen_item_key(idx), gen_item_value(idx as u32));
+    }
+
+    c
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_put_an_item() -> Result<(), String> {
+    let mut c: StaticLruCache<String, String, CAPACITY> = StaticLruCache::new();
+    let k = gen_item_key(1);
+
+    c.put(k.clone(), gen_item_value(1));
+    c.get(&k).ok_or(format!("{k} Not Found"))?;
+
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn last_inserted_item_should_be_mru() -> Result<(), String> {
+    let mut c = default_prefilled_cache();
+    let v = gen_item_value(CAPACITY as u32 - 1);
+
+    match c.pop_mru() {
+        Some(mru) if mru == v => Ok(()),
+        Some(mru) => Err(format!("MRU item should be '{v}'. Got '{mru}' instead")),
+        None => Err(format!("MRU item should be '{v}'. Got 'None' instead")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_pop_expected_mru_after_reorder() -> Result<(), String> {
+    let mut c = default_prefilled_cache();
+    let v = gen_item_value(6);
+
+    c.get("item-6").ok_or("item-6 not found")?;
+
+    match c.pop_mru() {
+        Some(mru) if mru == v => Ok(()),
+        Some(mru) => Err(format!("MRU item should be '{v}'. Got '{mru}' instead")),
+        None => Err(format!("MRU item should be '{v}'. Got 'None' instead")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_fail_to_get_nonexistent_item() -> Result<(), String> {
+    let mut c = default_prefilled_cache();
+
+    if c.get("item-10").is_some() {
+        Err(String::from("Found item 'item-10' that should not exist in cache"))
+    } else {
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_fail_to_get_evicted_item() -> Result<(), String> {
+    let mut c = default_prefilled_cache();
+
+    // Adding a new item to a full cache evicts the oldest item
+    c.put(gen_item_key(10), gen_item_value(10));
+
+    if c.get("item-0").is_some() {
+        Err(String::from("Found item 'item-0' when it should have been evicted"))
+    } else if c.len() != CAPACITY {
+        Err(format!("Expected {CAPACITY} items. Got {}", c.len()))
+    } else {
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_pop_mru_after_item_eviction() -> Result<(), String> {
+    let mut c = default_prefilled_cache();
+    let v = gen_item_value(10);
+
+    c.put(gen_item_key(10), v.clone());
+
+    match c.pop_mru() {
+        Some(mru) if mru == v => Ok(()),
+        Some(mru) => Err(format!("MRU item should be '{v}'. Got '{mru}' instead")),
+        None => Err(format!("MRU item should be '{v}'. Got 'None' instead")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_replace_existing_item() -> Result<(), String> {
+    let mut c = default_prefilled_cache();
+
+    match c.put(gen_item_key(3), gen_item_value(33)) {
+        Some(old) if old == gen_item_value(3) => (),
+        old => return Err(format!("Expected old value 'value-3'. Got {old:?}")),
+    }
+
+    match c.peek("item-3") {
+        Some(v) if *v == gen_item_value(33) => Ok(()),
+        v => Err(format!("Expected 'value-33'. Got {v:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_match_main_cache_under_churn() -> Result<(), String> {
+    let mut ours: StaticLruCache<u32, u32, 7> = StaticLruCache::new();
+    let mut reference = crate::LruCache::new(core::num::NonZeroUsize::new(7).unwrap());
+
+    for i in 0..2_000u32 {
+        let k = (i * 31) % 23;
+
+        if ours.put(k, i) != reference.put(k, i) {
+            return Err(format!("put({k}) diverged at step {i}"));
+        }
+
+        let probe = (i * 17) % 19;
+        if ours.get(&probe) != reference.get(&probe) {
+            return Err(format!("get({probe}) diverged at step {i}"));
+        }
+
+        if i % 11 == 0 && ours.remove(&(i % 23)) != reference.remove(&(i % 23)) {
+            return Err(format!("remove diverged at step {i}"));
+        }
+
+        if i % 29 == 0 && ours.pop_lru() != reference.pop_lru() {
+            return Err(format!("pop_lru diverged at step {i}"));
+        }
+    }
+
+    while let Some(v) = reference.pop_lru() {
+        if ours.pop_lru() != Some(v) {
+            return Err(String::from("Final recency order diverged"));
+        }
+    }
+
+    if ours.is_empty() {
+        Ok(())
+    } else {
+        Err(String::from("Static cache holds more items than the reference cache"))
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_be_usable_in_a_static() -> Result<(), String> {
+    static CACHE: std::sync::Mutex<StaticLruCache<u32, u32, 4>> = std::sync::Mutex::new(StaticLruCache::new());
+
+    let mut c = CACHE.lock().map_err(|e| e.to_string())?;
+    c.put(1, 10);
+
+    match c.get(&1) {
+        Some(10) => Ok(()),
+        other => Err(format!("Expected Some(10). Got {other:?}")),
+    }
+}
@@ -0,0 +1,344 @@
+//! Activity counters reported by the caches
+//!
+//! [`LruCache`](crate::LruCache) counts its own activity with plain integers, which cost too little to be worth turning
+//! off, while the thread-safe caches keep atomic counters of their own.  A cache built with
+//! [`LruCacheBuilder::windowed_stats`](crate::LruCacheBuilder::windowed_stats) also keeps a [`StatsWindow`] of its
+//! recent activity
+use alloc::{format, string::String};
+use core::{iter::Sum, ops::Add, time::Duration};
+
+/// Intervals kept by a [`StatsWindow`]
+#[cfg(feature = "std")]
+pub(crate) const WINDOW_INTERVALS: usize = 60;
+
+/// Buckets of a [`DurationHistogram`].  Bucket `b` counts durations of `2^(b-1)` to `2^b - 1` nanoseconds, and bucket
+/// `0` those of no time at all
+const BUCKETS: usize = u64::BITS as usize + 1;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Snapshot of a cache's activity since it was created or its statistics were last reset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups that found an item
+    pub hits: u64,
+    /// Lookups that did not find an item
+    pub misses: u64,
+    /// Items added under a new key
+    pub insertions: u64,
+    /// Values replaced under an existing key
+    pub updates: u64,
+    /// Items removed to make room for a new item
+    pub evictions: u64,
+    /// Items removed or popped by the caller
+    pub removals: u64,
+    /// Items in the cache when the snapshot was taken
+    pub entries: u64,
+    /// Time taken by the loaders that produced missing values
+    pub load_time: LoadTimeStats,
+    /// Time that evicted items had spent in the cache since their value was stored
+    pub eviction_age: DurationHistogram,
+    /// Time that evicted items had gone without being stored or fetched
+    pub eviction_idle: DurationHistogram,
+}
+
+impl CacheStats {
+    /// Fraction of lookups that found an item, or `0.0` if there have been no lookups
+    pub fn hit_ratio(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Formats the statistics in the Prometheus text exposition format, as counters named `{name}_hits_total`,
+    /// `{name}_misses_total`, `{name}_insertions_total`, `{name}_updates_total`, `{name}_evictions_total` and
+    /// `{name}_removals_total`, and the gauge `{name}_entries`.  Characters that may not appear in a metric name are
+    /// replaced by `_`, as is a leading digit
+    pub fn to_prometheus(&self, name: &str) -> String {
+        prometheus(name, &[(String::new(), *self)])
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The metric families written by [`CacheStats::to_prometheus`]: the suffix of each name, its type, its help text and
+/// its value
+type Family = (&'static str, &'static str, &'static str, fn(&CacheStats) -> u64);
+
+const FAMILIES: [Family; 7] = [
+    ("hits_total", "counter", "Lookups that found an item", |stats| stats.hits),
+    ("misses_total", "counter", "Lookups that did not find an item", |stats| stats.misses),
+    ("insertions_total", "counter", "Items added under a new key", |stats| stats.insertions),
+    ("updates_total", "counter", "Values replaced under an existing key", |stats| stats.updates),
+    ("evictions_total", "counter", "Items removed to make room for a new item", |stats| stats.evictions),
+    ("removals_total", "counter", "Items removed or popped by the caller", |stats| stats.removals),
+    ("entries", "gauge", "Items in the cache", |stats| stats.entries),
+];
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Formats each metric family in the Prometheus text exposition format, with a sample for each set of statistics.
+/// Each set comes with its labels, such as `{shard="0"}`, or an empty string
+pub(crate) fn prometheus(name: &str, samples: &[(String, CacheStats)]) -> String {
+    let name = metric_name(name);
+    let mut text = String::new();
+
+    for (suffix, kind, help, value) in FAMILIES {
+        text += &format!("# HELP {name}_{suffix} {help}\n# TYPE {name}_{suffix} {kind}\n");
+
+        for (labels, stats) in samples {
+            text += &format!("{name}_{suffix}{labels} {}\n", value(stats));
+        }
+    }
+
+    text
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Turns a cache's name into a valid metric name, which may only contain ASCII letters, digits and underscores, and
+/// may not start with a digit
+fn metric_name(name: &str) -> String {
+    let name: String = name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+
+    match name.starts_with(|c: char| c.is_ascii_digit()) || name.is_empty() {
+        true => format!("_{name}"),
+        false => name,
+    }
+}
+
+impl Add for CacheStats {
+    type Output = CacheStats;
+
+    fn add(self, other: CacheStats) -> CacheStats {
+        CacheStats {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+            insertions: self.insertions + other.insertions,
+            updates: self.updates + other.updates,
+            evictions: self.evictions + other.evictions,
+            removals: self.removals + other.removals,
+            entries: self.entries + other.entries,
+            load_time: self.load_time + other.load_time,
+            eviction_age: self.eviction_age + other.eviction_age,
+            eviction_idle: self.eviction_idle + other.eviction_idle,
+        }
+    }
+}
+
+impl Sum for CacheStats {
+    fn sum<I: Iterator<Item = CacheStats>>(iter: I) -> CacheStats {
+        iter.fold(CacheStats::default(), Add::add)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Durations of the loads run by `get_or_insert_with` and the async caches' `get_with`, measured on the cache's clock.
+/// Totals are exact, while percentiles come from a histogram with a bucket per power of two, so they may overstate a
+/// load time by up to a factor of two, but never exceed [`max`](Self::max)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadTimeStats {
+    /// Loads that finished
+    pub count: u64,
+    /// Time taken by every load together
+    pub total: Duration,
+    /// Time taken by the slowest load
+    pub max: Duration,
+    histogram: DurationHistogram,
+}
+
+impl LoadTimeStats {
+    /// Average time taken by a load, or zero if there have been none
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => Duration::from_nanos((self.total.as_nanos() / u128::from(count)) as u64),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Median load time
+    pub fn p50(&self) -> Duration {
+        self.percentile(50)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Time within which 90% of loads finished
+    pub fn p90(&self) -> Duration {
+        self.percentile(90)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Time within which 99% of loads finished
+    pub fn p99(&self) -> Duration {
+        self.percentile(99)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    fn percentile(&self, percent: u64) -> Duration {
+        self.histogram.percentile(percent).min(self.max)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Counts a load that took `nanos` nanoseconds
+    #[cfg(feature = "std")]
+    pub(crate) fn record(&mut self, nanos: u64) {
+        let elapsed = Duration::from_nanos(nanos);
+        self.count += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+        self.histogram.record(nanos);
+    }
+}
+
+impl Add for LoadTimeStats {
+    type Output = LoadTimeStats;
+
+    fn add(mut self, other: LoadTimeStats) -> LoadTimeStats {
+        self.count += other.count;
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+        self.histogram = self.histogram + other.histogram;
+        self
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Counts of durations in buckets that each cover a power of two of nanoseconds: no time at all, 1ns, 2-3ns, 4-7ns and
+/// so on up to the longest duration in nanoseconds that a `u64` holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DurationHistogram {
+    buckets: [u64; BUCKETS],
+}
+
+impl Default for DurationHistogram {
+    fn default() -> Self {
+        DurationHistogram { buckets: [0; BUCKETS] }
+    }
+}
+
+impl DurationHistogram {
+    /// Durations counted in every bucket
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the longest duration that each bucket covers, shortest first, with the number of durations it counted
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets.iter().enumerate().map(|(bucket, &count)| (Duration::from_nanos(upper_bound(bucket)), count))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the longest duration in the bucket holding the duration at the given percentile, or zero if nothing has
+    /// been counted
+    pub fn percentile(&self, percent: u64) -> Duration {
+        let rank = (self.count() * percent).div_ceil(100).max(1);
+        let mut seen = 0;
+
+        for (bucket, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+
+            if seen >= rank {
+                return Duration::from_nanos(upper_bound(bucket));
+            }
+        }
+
+        Duration::ZERO
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Counts a duration of `nanos` nanoseconds
+    pub(crate) fn record(&mut self, nanos: u64) {
+        self.buckets[(u64::BITS - nanos.leading_zeros()) as usize] += 1;
+    }
+}
+
+impl Add for DurationHistogram {
+    type Output = DurationHistogram;
+
+    fn add(mut self, other: DurationHistogram) -> DurationHistogram {
+        for (count, other_count) in self.buckets.iter_mut().zip(other.buckets) {
+            *count += other_count;
+        }
+
+        self
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The longest duration, in nanoseconds, that a histogram bucket covers
+fn upper_bound(bucket: usize) -> u64 {
+    match bucket {
+        0 => 0,
+        bucket => u64::MAX >> (u64::BITS as usize - bucket),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Activity counted over the last [`WINDOW_INTERVALS`] intervals of equal width, kept in a ring indexed by interval
+/// number.  A slot left over from an earlier lap of the ring is cleared when activity next lands in it, and skipped
+/// when summed, so the ring only needs the time of each event to keep itself current
+#[cfg(feature = "std")]
+pub(crate) struct StatsWindow {
+    /// Width of each interval, in nanoseconds
+    width: u64,
+    slots: [(u64, IntervalCounts); WINDOW_INTERVALS],
+}
+
+/// The activity counted in one interval of a [`StatsWindow`]
+#[derive(Clone, Copy, Default)]
+pub(crate) struct IntervalCounts {
+    pub(crate) hits: u64,
+    pub(crate) misses: u64,
+    pub(crate) insertions: u64,
+    pub(crate) updates: u64,
+    pub(crate) evictions: u64,
+    pub(crate) removals: u64,
+}
+
+#[cfg(feature = "std")]
+impl StatsWindow {
+    pub(crate) fn new(width: Duration) -> Self {
+        StatsWindow {
+            width: crate::clock::nanos(width).max(1),
+            slots: [(0, IntervalCounts::default()); WINDOW_INTERVALS],
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Counts activity at time `now` in the interval that holds it
+    pub(crate) fn record(&mut self, now: u64, count: fn(&mut IntervalCounts)) {
+        let interval = now / self.width;
+        let (start, counts) = &mut self.slots[interval as usize % WINDOW_INTERVALS];
+
+        if *start != interval {
+            *start = interval;
+            *counts = IntervalCounts::default();
+        }
+
+        count(counts);
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Sums the activity of the interval holding `now` and of those before it, as many as it takes to cover `window`
+    pub(crate) fn sum(&self, now: u64, window: Duration) -> CacheStats {
+        let current = now / self.width;
+        let covered = crate::clock::nanos(window).div_ceil(self.width).clamp(1, WINDOW_INTERVALS as u64);
+        let recent = self.slots.iter().filter(|&&(interval, _)| interval + covered > current && interval <= current);
+
+        recent.fold(CacheStats::default(), |stats, (_, counts)| CacheStats {
+            hits: stats.hits + counts.hits,
+            misses: stats.misses + counts.misses,
+            insertions: stats.insertions + counts.insertions,
+            updates: stats.updates + counts.updates,
+            evictions: stats.evictions + counts.evictions,
+            removals: stats.removals + counts.removals,
+            ..stats
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Forgets all the activity counted so far
+    pub(crate) fn clear(&mut self) {
+        *self = StatsWindow::new(Duration::from_nanos(self.width));
+    }
+}
@@ -0,0 +1,62 @@
+//! Synchronisation primitives shared between threads
+//!
+//! These are normally the standard library's.  Building with `RUSTFLAGS="--cfg loom"` swaps in loom's model-checked
+//! versions, so that `tests/loom.rs` can run the thread-safe caches under every interleaving of a few threads.  Loom's
+//! locks never report poisoning, so [`ResetPoison`] does nothing under loom
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+#[cfg(all(feature = "std", not(loom)))]
+pub(crate) use std::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(all(feature = "std", loom))]
+pub(crate) use loom::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Access to a lock's poisoned state, which loom's locks do not track
+#[cfg(feature = "std")]
+pub(crate) trait ResetPoison {
+    fn poisoned(&self) -> bool;
+    fn reset_poison(&self);
+}
+
+#[cfg(all(feature = "std", not(loom)))]
+impl<T> ResetPoison for Mutex<T> {
+    fn poisoned(&self) -> bool {
+        self.is_poisoned()
+    }
+
+    fn reset_poison(&self) {
+        self.clear_poison()
+    }
+}
+
+#[cfg(all(feature = "std", not(loom)))]
+impl<T> ResetPoison for RwLock<T> {
+    fn poisoned(&self) -> bool {
+        self.is_poisoned()
+    }
+
+    fn reset_poison(&self) {
+        self.clear_poison()
+    }
+}
+
+#[cfg(all(feature = "std", loom))]
+impl<T> ResetPoison for Mutex<T> {
+    fn poisoned(&self) -> bool {
+        false
+    }
+
+    fn reset_poison(&self) {}
+}
+
+#[cfg(all(feature = "std", loom))]
+impl<T> ResetPoison for RwLock<T> {
+    fn poisoned(&self) -> bool {
+        false
+    }
+
+    fn reset_poison(&self) {}
+}
@@ -1,4 +1,10 @@
-use std::hint::black_box;
+use crate::Clock;
+use alloc::{format, string::String, sync::Arc};
+use core::{
+    hint::black_box,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
 
 pub fn gen_item_key(idx: usize) -> String {
     black_box(format!("item-{idx}"))
@@ -7,3 +13,248 @@ pub fn gen_item_key(idx: usize) -> String {
 pub fn gen_item_value(val: u32) -> String {
     black_box(format!("value-{val}"))
 }
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A [`Clock`] that only moves when advanced.  Clones share one time, so a test can keep a clone of the clock it gave
+/// a cache and advance it while other threads use the cache
+#[derive(Clone, Debug, Default)]
+pub struct MockClock {
+    now: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    /// Starts a clock at time zero, which it takes to be the Unix epoch when converting wall-clock times
+    pub fn new() -> Self {
+        MockClock::default()
+    }
+
+    /// Moves the clock, and every clone of it, forward
+    pub fn advance(&self, by: Duration) {
+        let by = u64::try_from(by.as_nanos()).unwrap_or(u64::MAX);
+        self.now.fetch_add(by, Ordering::AcqRel);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::Acquire)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Checks that `text` follows the Prometheus text exposition format, as far as the caches use it, and returns the value
+/// of each sample by its name and labels.  Each metric family must be described by one `# HELP` and one `# TYPE`
+/// line before its samples, which must follow one another, and counters must be named `..._total`
+#[cfg(test)]
+pub(crate) fn parse_prometheus(text: &str) -> Result<alloc::collections::BTreeMap<String, f64>, String> {
+    use alloc::{collections::BTreeMap, string::ToString};
+
+    let valid_name = |name: &str| {
+        name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    let mut samples = BTreeMap::new();
+    let mut kinds = BTreeMap::new();
+    let mut family = None;
+
+    if !text.ends_with('\n') {
+        return Err(String::from("The text does not end with a line feed"));
+    }
+
+    for line in text.lines() {
+        if let Some(help) = line.strip_prefix("# HELP ") {
+            match help.split_once(' ') {
+                Some((name, help)) if valid_name(name) && !help.is_empty() && !kinds.contains_key(name) => {
+                    family = Some(name);
+                }
+                _ => return Err(format!("Bad or repeated HELP line: {line}")),
+            }
+        } else if let Some(kind) = line.strip_prefix("# TYPE ") {
+            match kind.split_once(' ') {
+                Some((name, kind @ ("counter" | "gauge"))) if family == Some(name) && !kinds.contains_key(name) => {
+                    kinds.insert(name, kind);
+                }
+                _ => return Err(format!("Bad, repeated or misplaced TYPE line: {line}")),
+            }
+        } else {
+            let (series, value) = line.rsplit_once(' ').ok_or(format!("Sample without a value: {line}"))?;
+            let (name, labels) = series.split_once('{').unwrap_or((series, "}"));
+            let labels = labels.strip_suffix('}').ok_or(format!("Unterminated labels: {line}"))?;
+            let valid_labels = labels.is_empty()
+                || labels.split(',').all(|label| match label.split_once('=') {
+                    Some((key, value)) => {
+                        valid_name(key) && value.len() >= 2 && value.starts_with('"') && value.ends_with('"')
+                    }
+                    None => false,
+                });
+            let value: f64 = value.parse().map_err(|_| format!("Value is not a number: {line}"))?;
+
+            match (family == Some(name), kinds.get(name)) {
+                (true, Some(&"counter")) if name.ends_with("_total") && value >= 0.0 && valid_labels => {}
+                (true, Some(&"gauge")) if valid_labels => {}
+                _ => return Err(format!("Sample outside its family, or with bad labels or value: {line}")),
+            }
+
+            if samples.insert(series.to_string(), value).is_some() {
+                return Err(format!("Repeated sample: {line}"));
+            }
+        }
+    }
+
+    Ok(samples)
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A [`CacheEventListener`](crate::CacheEventListener) that logs every event it hears of.  Clones share one log
+#[cfg(all(test, feature = "std"))]
+#[derive(Clone, Default)]
+pub(crate) struct RecordingListener {
+    log: Arc<std::sync::Mutex<alloc::vec::Vec<(crate::listener::Event, u32, u32)>>>,
+}
+
+#[cfg(all(test, feature = "std"))]
+impl RecordingListener {
+    /// The events heard so far, oldest first
+    pub(crate) fn events(&self) -> alloc::vec::Vec<(crate::listener::Event, u32, u32)> {
+        self.log.lock().unwrap().clone()
+    }
+
+    fn record(&self, event: crate::listener::Event, key: &u32, value: &u32) {
+        self.log.lock().unwrap().push((event, *key, *value));
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+impl crate::CacheEventListener<u32, u32> for RecordingListener {
+    fn on_insert(&self, key: &u32, value: &u32) {
+        self.record(crate::listener::Event::Insert, key, value);
+    }
+
+    fn on_update(&self, key: &u32, value: &u32) {
+        self.record(crate::listener::Event::Update, key, value);
+    }
+
+    fn on_hit(&self, key: &u32, value: &u32) {
+        self.record(crate::listener::Event::Hit, key, value);
+    }
+
+    fn on_remove(&self, key: &u32, value: &u32, cause: crate::RemovalCause) {
+        self.record(crate::listener::Event::Remove(cause), key, value);
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A call made to a [`MockStore`]
+#[cfg(all(test, feature = "std"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum StoreCall {
+    Write(u32, u32),
+    Delete(u32),
+}
+
+/// A [`BackingStore`](crate::BackingStore) that keeps its data in a map and logs every call made to it, including
+/// those that fail.  Clones share one map and log, so a test can keep a clone of the store it gave a cache
+#[cfg(all(test, feature = "std"))]
+#[derive(Clone, Default)]
+pub(crate) struct MockStore {
+    state: Arc<std::sync::Mutex<MockStoreState>>,
+}
+
+#[cfg(all(test, feature = "std"))]
+#[derive(Default)]
+struct MockStoreState {
+    data: alloc::collections::BTreeMap<u32, u32>,
+    calls: alloc::vec::Vec<StoreCall>,
+    failing: bool,
+    fail_next: u32,
+}
+
+#[cfg(all(test, feature = "std"))]
+impl MockStore {
+    /// The calls made so far, oldest first
+    pub(crate) fn calls(&self) -> alloc::vec::Vec<StoreCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// The value stored under `key`
+    pub(crate) fn value(&self, key: u32) -> Option<u32> {
+        self.state.lock().unwrap().data.get(&key).copied()
+    }
+
+    /// Makes every later call fail, or succeed again
+    pub(crate) fn set_failing(&self, failing: bool) {
+        self.state.lock().unwrap().failing = failing;
+    }
+
+    /// Makes the next `calls` calls fail
+    pub(crate) fn fail_next(&self, calls: u32) {
+        self.state.lock().unwrap().fail_next = calls;
+    }
+
+    fn call(&self, call: StoreCall) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(call);
+
+        if state.failing || state.fail_next > 0 {
+            state.fail_next = state.fail_next.saturating_sub(1);
+            return Err(format!("{call:?} failed"));
+        }
+
+        match call {
+            StoreCall::Write(k, v) => state.data.insert(k, v),
+            StoreCall::Delete(k) => state.data.remove(&k),
+        };
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+impl crate::BackingStore<u32, u32> for MockStore {
+    type Error = String;
+
+    fn write(&mut self, key: &u32, value: &u32) -> Result<(), String> {
+        self.call(StoreCall::Write(*key, *value))
+    }
+
+    fn delete(&mut self, key: &u32) -> Result<(), String> {
+        self.call(StoreCall::Delete(*key))
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A directory under the system's temporary directory, removed with everything in it when dropped
+#[cfg(all(test, any(feature = "bincode", feature = "json")))]
+pub(crate) struct TempDir(std::path::PathBuf);
+
+#[cfg(all(test, any(feature = "bincode", feature = "json")))]
+impl TempDir {
+    /// Creates an empty directory, whose name must be unique to the test
+    pub(crate) fn new(name: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!("lru-cache-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    pub(crate) fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+
+    /// The names of the files in the directory, in order
+    pub(crate) fn file_names(&self) -> alloc::vec::Vec<String> {
+        let mut names: alloc::vec::Vec<String> = std::fs::read_dir(&self.0)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+#[cfg(all(test, any(feature = "bincode", feature = "json")))]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,163 @@
+//! The on-disk tier of a [`TieredCache`](super::TieredCache)
+use crate::{
+    JsonFileError, LruCache,
+    json_file::{temp_path, write_and_sync},
+};
+use serde::{Serialize, de::DeserializeOwned, de::IgnoredAny};
+use std::{
+    fs::{self, File},
+    hash::Hash,
+    io::{self, BufReader},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+const EXTENSION: &str = "json";
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A directory holding one JSON file per item, each containing the item's key and value.  Files are numbered in the
+/// order they were written, which is also the order in which they were demoted, so that the recency order survives a
+/// restart.  An in-memory LRU cache maps each key on disk to its file number
+pub(super) struct DiskTier<K> {
+    dir: PathBuf,
+    index: LruCache<K, u64>,
+    next_file: u64,
+}
+
+impl<K> DiskTier<K>
+where
+    K: Eq + Hash + Serialize + DeserializeOwned,
+{
+    /// Opens the tier held in `dir`, creating the directory if necessary.  Files left behind by an interrupted write,
+    /// files that cannot be read, and older copies of a key written again before a crash are deleted.  If there are
+    /// more files than `capacity`, the oldest are deleted
+    pub(super) fn open(dir: PathBuf, capacity: NonZeroUsize) -> Result<Self, JsonFileError> {
+        fs::create_dir_all(&dir)?;
+        let mut numbered = Vec::new();
+
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+
+            match file_number(&path) {
+                Some(number) => numbered.push((number, path)),
+                None if is_temp_file(&path) => fs::remove_file(&path)?,
+                None => {}
+            }
+        }
+
+        numbered.sort_unstable_by_key(|(number, _)| *number);
+
+        let mut tier = DiskTier {
+            next_file: numbered.last().map_or(0, |(number, _)| number + 1),
+            index: LruCache::new(capacity),
+            dir,
+        };
+
+        for (number, path) in numbered {
+            match read_file::<K, IgnoredAny>(&path) {
+                Ok((key, _)) => tier.index_file(key, number)?,
+                Err(JsonFileError::Corrupt(_)) => fs::remove_file(&path)?,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(tier)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Writes an item to a new file, replacing any file already holding the key.  If the tier is full, the least
+    /// recently demoted item is deleted
+    pub(super) fn insert<V: Serialize>(&mut self, key: K, value: &V) -> Result<(), JsonFileError> {
+        let number = self.next_file;
+        let path = self.path(number);
+        let temp = temp_path(&path);
+
+        if let Err(e) = write_and_sync(&temp, &(&key, value)).and_then(|_| Ok(fs::rename(&temp, &path)?)) {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        }
+
+        self.next_file += 1;
+        self.index_file(key, number)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Reads an item's value, returning `None` if the key is not on disk
+    pub(super) fn read<V: DeserializeOwned>(&self, key: &K) -> Result<Option<V>, JsonFileError> {
+        match self.index.peek(key) {
+            Some(&number) => Ok(Some(read_file::<IgnoredAny, V>(&self.path(number))?.1)),
+            None => Ok(None),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Deletes an item's file, returning `true` if the key was on disk
+    pub(super) fn remove(&mut self, key: &K) -> Result<bool, JsonFileError> {
+        match self.index.remove(key) {
+            Some(number) => self.delete_file(number).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(super) fn contains_key(&self, key: &K) -> bool {
+        self.index.contains_key(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(super) fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(super) fn capacity(&self) -> NonZeroUsize {
+        self.index.capacity()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Records that `number` holds the key, deleting the file that held it before and the file of any item evicted to
+    /// make room
+    fn index_file(&mut self, key: K, number: u64) -> Result<(), JsonFileError> {
+        if let Some(old) = self.index.remove(&key) {
+            self.delete_file(old)?;
+        }
+
+        if let Some((_, evicted)) = self.index.push(key, number) {
+            self.delete_file(evicted)?;
+        }
+
+        Ok(())
+    }
+
+    fn delete_file(&self, number: u64) -> Result<(), JsonFileError> {
+        match fs::remove_file(self.path(number)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn path(&self, number: u64) -> PathBuf {
+        self.dir.join(format!("{number:016x}.{EXTENSION}"))
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Returns the number of a file written by [`DiskTier::insert`]
+fn file_number(path: &Path) -> Option<u64> {
+    if path.extension()? != EXTENSION {
+        return None;
+    }
+
+    u64::from_str_radix(path.file_stem()?.to_str()?, 16).ok()
+}
+
+/// Returns `true` for the temporary files that an interrupted write may leave behind
+fn is_temp_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.') && name.ends_with(".tmp"))
+}
+
+fn read_file<K: DeserializeOwned, V: DeserializeOwned>(path: &Path) -> Result<(K, V), JsonFileError> {
+    Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+}
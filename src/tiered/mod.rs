@@ -0,0 +1,150 @@
+//! Two-tier cache, keeping the hottest items in memory and the rest on disk
+//!
+//! A [`TieredCache`] puts an in-memory [`LruCache`] in front of a directory of files.  An item evicted from memory is
+//! demoted to disk rather than dropped, and a lookup that misses in memory looks on disk before reporting a miss.  An
+//! item found on disk is promoted back into memory, and its file deleted, so that each item lives in one tier only.
+//! Each tier has a capacity of its own, and the disk tier evicts its least recently demoted item when it is full.
+//!
+//! # Files
+//!
+//! Every item on disk is a small JSON file holding its key and value.  Files are written under a temporary name and
+//! renamed into place, so a crash part way through a write never leaves a damaged item.  Reopening the directory with
+//! [`TieredCache::open`] rebuilds the index of the disk tier from the files, tidying up anything a crash left behind.
+//! Items still in memory when the process stops are not written out
+use crate::{JsonFileError, LruCache};
+use core::{hash::Hash, num::NonZeroUsize};
+use disk::DiskTier;
+use serde::{Serialize, de::DeserializeOwned};
+use std::path::Path;
+
+mod disk;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache that demotes the items it evicts to a directory on disk
+pub struct TieredCache<K, V> {
+    memory: LruCache<K, V>,
+    disk: DiskTier<K>,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V> TieredCache<K, V>
+where
+    K: Eq + Hash + Clone + Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+{
+    /// Opens a cache holding at most `memory_capacity` items in memory and `disk_capacity` items in `dir`, which is
+    /// created if it does not exist.  Items already in `dir` are kept, up to `disk_capacity` of the most recently
+    /// demoted
+    pub fn open(
+        dir: impl AsRef<Path>,
+        memory_capacity: NonZeroUsize,
+        disk_capacity: NonZeroUsize,
+    ) -> Result<Self, JsonFileError> {
+        Ok(TieredCache {
+            memory: LruCache::new(memory_capacity),
+            disk: DiskTier::open(dir.as_ref().to_path_buf(), disk_capacity)?,
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a reference to an item, making it the most recently used.  An item found on disk is promoted
+    /// into memory, which may demote the least recently used item in memory to disk
+    pub fn get(&mut self, key: &K) -> Result<Option<&V>, JsonFileError> {
+        if self.memory.get(key).is_none() {
+            let Some(value) = self.disk.read(key)? else {
+                return Ok(None);
+            };
+
+            // Making room may evict the key from a full disk tier, in which case there is no file left to delete
+            self.make_room(key)?;
+            self.disk.remove(key)?;
+            self.memory.put(key.clone(), value);
+        }
+
+        Ok(self.memory.peek(key))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetch a reference to an item held in memory without changing its position in the recency order.  The disk is
+    /// not consulted
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        self.memory.peek(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if either tier contains the key.  The item's position in the recency order is not changed
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.memory.contains_key(key) || self.disk.contains_key(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts an item into memory, returning the value it replaced there.  Any copy of the key on disk is deleted.  If
+    /// memory is full, the least recently used item is demoted to disk first; if that fails, the error is returned and
+    /// the item is not inserted
+    pub fn put(&mut self, key: K, new_value: V) -> Result<Option<V>, JsonFileError> {
+        self.make_room(&key)?;
+        self.disk.remove(&key)?;
+        Ok(self.memory.put(key, new_value))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Removes an item from whichever tier holds it, returning its value if it was present
+    pub fn remove(&mut self, key: &K) -> Result<Option<V>, JsonFileError> {
+        if let Some(value) = self.memory.remove(key) {
+            return Ok(Some(value));
+        }
+
+        let value = self.disk.read(key)?;
+        self.disk.remove(key)?;
+        Ok(value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in both tiers
+    pub fn len(&self) -> usize {
+        self.memory.len() + self.disk.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if neither tier contains any items
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items held in memory
+    pub fn memory_len(&self) -> usize {
+        self.memory.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items held on disk
+    pub fn disk_len(&self) -> usize {
+        self.disk.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the memory and disk tiers can hold
+    pub fn capacities(&self) -> (NonZeroUsize, NonZeroUsize) {
+        (self.memory.capacity(), self.disk.capacity())
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Demotes the least recently used item to disk if memory is full and does not already hold `key`
+    fn make_room(&mut self, key: &K) -> Result<(), JsonFileError> {
+        if self.memory.contains_key(key) || self.memory.len() < self.memory.capacity().get() {
+            return Ok(());
+        }
+
+        if let Some((lru_key, lru_value)) = self.memory.iter_lru_first().next() {
+            self.disk.insert(lru_key.clone(), lru_value)?;
+        }
+
+        self.memory.evict_to(self.memory.len() - 1);
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
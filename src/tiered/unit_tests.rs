@@ -0,0 +1,93 @@
+use super::*;
+use crate::test_utils::TempDir;
+use std::fs;
+
+const fn cap(n: usize) -> NonZeroUsize {
+    NonZeroUsize::new(n).unwrap()
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_demote_evicted_items_and_promote_them_on_get() -> Result<(), String> {
+    let dir = TempDir::new("tiered-promote");
+    let mut c = TieredCache::open(dir.path(), cap(2), cap(3)).map_err(|e| e.to_string())?;
+
+    for key in 1..=3 {
+        c.put(key, key * 10).map_err(|e| e.to_string())?;
+    }
+
+    let demoted = (c.peek(&1).copied(), c.contains_key(&1), c.memory_len(), c.disk_len());
+
+    // Promoting 1 demotes 2, the least recently used item in memory
+    let promoted = c.get(&1).map_err(|e| e.to_string())?.copied();
+
+    match (demoted, promoted, c.peek(&1).copied(), c.peek(&2).copied(), c.disk_len(), c.len()) {
+        ((None, true, 2, 1), Some(10), Some(10), None, 1, 3) => Ok(()),
+        other => Err(format!("Expected ((None, true, 2, 1), Some(10), Some(10), None, 1, 3). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_report_miss_only_when_both_tiers_miss() -> Result<(), String> {
+    let dir = TempDir::new("tiered-miss");
+    let mut c = TieredCache::open(dir.path(), cap(1), cap(1)).map_err(|e| e.to_string())?;
+
+    c.put(1, 10).map_err(|e| e.to_string())?;
+    c.put(2, 20).map_err(|e| e.to_string())?;
+
+    let on_disk = c.get(&1).map_err(|e| e.to_string())?.copied();
+    let missing = c.get(&9).map_err(|e| e.to_string())?.copied();
+
+    match (on_disk, missing, c.contains_key(&9)) {
+        (Some(10), None, false) => Ok(()),
+        other => Err(format!("Expected (Some(10), None, false). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_evict_least_recently_demoted_item_when_disk_is_full() -> Result<(), String> {
+    let dir = TempDir::new("tiered-disk-full");
+    let mut c = TieredCache::open(dir.path(), cap(1), cap(2)).map_err(|e| e.to_string())?;
+
+    // 1, 2 and 3 are demoted in turn, and 1 is then evicted from the disk
+    for key in 1..=4 {
+        c.put(key, key * 10).map_err(|e| e.to_string())?;
+    }
+
+    let evicted = c.get(&1).map_err(|e| e.to_string())?.copied();
+
+    match (evicted, c.disk_len(), dir.file_names().len(), c.contains_key(&2), c.contains_key(&3)) {
+        (None, 2, 2, true, true) => Ok(()),
+        other => Err(format!("Expected (None, 2, 2, true, true). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_reopen_disk_tier_after_crash() -> Result<(), String> {
+    let dir = TempDir::new("tiered-reopen");
+
+    {
+        let mut c = TieredCache::open(dir.path(), cap(1), cap(3)).map_err(|e| e.to_string())?;
+
+        for key in 1..=4 {
+            c.put(key, key * 10).map_err(|e| e.to_string())?;
+        }
+    }
+
+    // Leave behind what a crash might: a half-written temporary file and a damaged item
+    fs::write(dir.path().join(".00000000000000ff.json.1.tmp"), "[1,").map_err(|e| e.to_string())?;
+    fs::write(dir.path().join("00000000000000fe.json"), "[9,").map_err(|e| e.to_string())?;
+
+    // Reopening with less room on disk keeps the most recently demoted items, 2 and 3
+    let mut c = TieredCache::<u32, u32>::open(dir.path(), cap(1), cap(2)).map_err(|e| e.to_string())?;
+    let reopened = (c.disk_len(), dir.file_names().len(), c.contains_key(&1), c.contains_key(&4));
+    let value = c.get(&3).map_err(|e| e.to_string())?.copied();
+
+    match (reopened, value) {
+        ((2, 2, false, false), Some(30)) => Ok(()),
+        other => Err(format!("Expected ((2, 2, false, false), Some(30)). Got {other:?}")),
+    }
+}
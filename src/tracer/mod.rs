@@ -0,0 +1,97 @@
+//! Reporting through the [`tracing`](https://crates.io/crates/tracing) crate
+//!
+//! The events are listed under [`LruCacheBuilder::tracing`](crate::LruCacheBuilder::tracing)
+use crate::RemovalCause;
+use core::{fmt, time::Duration};
+use tracing::field::{DisplayValue, display};
+
+/// Formats a key for the `key` field
+pub(crate) type KeyFmt<K> = fn(&K, &mut fmt::Formatter<'_>) -> fmt::Result;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Emits the events of a traced cache
+pub(crate) struct Tracer<K> {
+    name: &'static str,
+    key_fmt: Option<KeyFmt<K>>,
+    /// Index of the [`ShardedLruCache`](crate::ShardedLruCache) shard that the cache serves as, for the `shard` field
+    shard: Option<usize>,
+}
+
+impl<K> Tracer<K> {
+    pub(crate) fn new(name: &'static str, key_fmt: Option<KeyFmt<K>>) -> Self {
+        Tracer {
+            name,
+            key_fmt,
+            shard: None,
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Adds the `shard` field to every event
+    pub(crate) fn for_shard(self, shard: usize) -> Self {
+        Tracer {
+            shard: Some(shard),
+            ..self
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the `key` field, if keys are formatted
+    fn key<'a>(&self, key: &'a K) -> Option<DisplayValue<TracedKey<'a, K>>> {
+        self.key_fmt.map(|fmt| display(TracedKey { key, fmt }))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn hit(&self, key: &K) {
+        tracing::trace!(cache = self.name, shard = self.shard, key = self.key(key), "hit");
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn miss(&self) {
+        tracing::trace!(cache = self.name, shard = self.shard, "miss");
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn inserted(&self, key: &K) {
+        tracing::trace!(cache = self.name, shard = self.shard, key = self.key(key), "inserted");
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn updated(&self, key: &K) {
+        tracing::trace!(cache = self.name, shard = self.shard, key = self.key(key), "updated");
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn removed(&self, key: &K, cause: RemovalCause) {
+        let (name, shard, key) = (self.name, self.shard, self.key(key));
+
+        match cause {
+            RemovalCause::Capacity => tracing::debug!(cache = name, shard, key, "evicted"),
+            RemovalCause::Expired => tracing::debug!(cache = name, shard, key, "expired"),
+            cause => tracing::trace!(cache = name, shard, key, ?cause, "removed"),
+        }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    pub(crate) fn loaded(&self, key: &K, latency: Duration) {
+        let latency_ns = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+        tracing::debug!(cache = self.name, shard = self.shard, key = self.key(key), latency_ns, "loaded");
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A key with the formatter chosen for it
+struct TracedKey<'a, K> {
+    key: &'a K,
+    fmt: KeyFmt<K>,
+}
+
+impl<K> fmt::Display for TracedKey<'_, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.fmt)(self.key, f)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod unit_tests;
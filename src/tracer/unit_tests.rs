@@ -0,0 +1,218 @@
+use crate::{ConcurrentLruCache, LruCache, LruCacheBuilder, test_utils::MockClock};
+use core::num::NonZeroUsize;
+use std::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    sync::{Arc, Mutex},
+    time::Duration,
+    vec::Vec,
+};
+use tracing::{
+    Event, Level, Metadata, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    subscriber::{Interest, with_default},
+};
+
+/// An event's level and its fields, formatted, including the message
+type Recorded = (Level, BTreeMap<String, String>);
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Subscriber that keeps every event
+#[derive(Clone, Default)]
+struct Collector {
+    events: Arc<Mutex<Vec<Recorded>>>,
+}
+
+impl Subscriber for Collector {
+    fn register_callsite(&self, _: &'static Metadata<'static>) -> Interest {
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        self.events.lock().unwrap().push((*event.metadata().level(), fields.0));
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[derive(Default)]
+struct Fields(BTreeMap<String, String>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn core::fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{value:?}"));
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Runs `f` with a collecting subscriber, returning the events it saw
+fn traced(f: impl FnOnce()) -> Vec<Recorded> {
+    let collector = Collector::default();
+    with_default(collector.clone(), f);
+    collector.events.lock().unwrap().clone()
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Builds the expected record of an event from its level and fields
+fn event(level: Level, fields: &[(&str, &str)]) -> Recorded {
+    (level, fields.iter().map(|&(name, value)| (name.to_string(), value.to_string())).collect())
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_emit_events_for_a_hit_a_miss_and_an_eviction() -> Result<(), String> {
+    let events = traced(|| {
+        let mut c = LruCacheBuilder::new(NonZeroUsize::new(2).unwrap())
+            .tracing("sessions")
+            .trace_keys_debug()
+            .build();
+        c.put("a", 1);
+        c.put("b", 2);
+        c.get(&"a");
+        c.get(&"z");
+        c.put("c", 3);
+        c.put("a", 10);
+    });
+
+    let cache = ("cache", "sessions");
+    let expected = [
+        event(Level::TRACE, &[cache, ("key", "\"a\""), ("message", "inserted")]),
+        event(Level::TRACE, &[cache, ("key", "\"b\""), ("message", "inserted")]),
+        event(Level::TRACE, &[cache, ("key", "\"a\""), ("message", "hit")]),
+        event(Level::TRACE, &[cache, ("message", "miss")]),
+        event(Level::DEBUG, &[cache, ("key", "\"b\""), ("message", "evicted")]),
+        event(Level::TRACE, &[cache, ("key", "\"c\""), ("message", "inserted")]),
+        event(Level::TRACE, &[cache, ("key", "\"a\""), ("message", "updated")]),
+    ];
+
+    match events == expected {
+        true => Ok(()),
+        false => Err(format!("Expected {expected:?}. Got {events:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_leave_keys_out_unless_asked_and_report_expiry() -> Result<(), String> {
+    let clock = MockClock::new();
+    let events = traced(|| {
+        let mut c = LruCacheBuilder::new(NonZeroUsize::new(2).unwrap())
+            .tracing("tokens")
+            .clock(clock.clone())
+            .ttl(Duration::from_secs(1))
+            .build();
+        c.put(1, 1);
+        clock.advance(Duration::from_secs(2));
+        c.get(&1);
+        c.put(2, 2);
+        c.remove(&2);
+    });
+
+    let cache = ("cache", "tokens");
+    let expected = [
+        event(Level::TRACE, &[cache, ("message", "inserted")]),
+        event(Level::DEBUG, &[cache, ("message", "expired")]),
+        event(Level::TRACE, &[cache, ("message", "miss")]),
+        event(Level::TRACE, &[cache, ("message", "inserted")]),
+        event(Level::TRACE, &[cache, ("cause", "Explicit"), ("message", "removed")]),
+    ];
+
+    match events == expected {
+        true => Ok(()),
+        false => Err(format!("Expected {expected:?}. Got {events:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_report_the_latency_of_each_load() -> Result<(), String> {
+    let clock = MockClock::new();
+    let events = traced(|| {
+        let cache = ConcurrentLruCache::from(
+            LruCacheBuilder::new(NonZeroUsize::new(2).unwrap())
+                .tracing("users")
+                .trace_keys_display()
+                .clock(clock.clone())
+                .build(),
+        );
+        cache.get_or_insert_with(7, || {
+            clock.advance(Duration::from_millis(25));
+            70
+        });
+    });
+
+    let cache = ("cache", "users");
+    let expected = [
+        event(Level::TRACE, &[cache, ("message", "miss")]),
+        event(Level::TRACE, &[cache, ("key", "7"), ("message", "inserted")]),
+        event(Level::DEBUG, &[cache, ("key", "7"), ("latency_ns", "25000000"), ("message", "loaded")]),
+    ];
+
+    match events == expected {
+        true => Ok(()),
+        false => Err(format!("Expected {expected:?}. Got {events:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn untraced_cache_should_emit_nothing() -> Result<(), String> {
+    let events = traced(|| {
+        let mut c = LruCache::new(NonZeroUsize::new(1).unwrap());
+        c.put(1, 1);
+        c.put(2, 2);
+        c.get(&2);
+    });
+
+    match events[..] {
+        [] => Ok(()),
+        _ => Err(format!("Expected no events from a cache built without tracing. Got {events:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn sharded_cache_should_add_the_shard_to_every_event() -> Result<(), String> {
+    let mut shard = None;
+    let events = traced(|| {
+        let cache = crate::ShardedLruCache::with_shards(NonZeroUsize::new(4).unwrap(), NonZeroUsize::new(2).unwrap())
+            .with_tracing("sharded");
+        cache.put(1, 1);
+        let _ = cache.get(&1);
+        shard = cache.shard_stats().iter().position(|shard| shard.len == 1).map(|idx| idx.to_string());
+    });
+
+    let shard = shard.ok_or("Expected key 1 in a shard")?;
+    let fields = [("cache", "sharded"), ("shard", shard.as_str())];
+    let expected = [
+        event(Level::TRACE, &[fields[0], fields[1], ("message", "inserted")]),
+        event(Level::TRACE, &[fields[0], fields[1], ("message", "hit")]),
+    ];
+
+    match events == expected {
+        true => Ok(()),
+        false => Err(format!("Expected {expected:?}. Got {events:?}")),
+    }
+}
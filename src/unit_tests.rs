@@ -1,17 +1,29 @@
 use super::*;
 use test_utils::*;
-use std::{num::NonZero, sync::{Arc, Barrier, Mutex}, thread};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    num::NonZero,
+    sync::{Arc, Barrier, Mutex},
+    thread,
+    time::{Duration, UNIX_EPOCH},
+};
 
 const CAPACITY: NonZero<usize> = NonZeroUsize::new(10).unwrap();
 
 fn default_empty_cache<K, V>() -> LruCache<K, V>
 where
-    K: Clone + Eq + Hash,
+    K: Eq + Hash,
     V: Clone,
 {
     LruCache::new(CAPACITY)
 }
 
+mod shared_scenarios {
+    use super::*;
+
+    crate::scenarios::cache_scenarios!(default_empty_cache::<String, String>);
+}
+
 fn default_prefilled_cache() -> LruCache<String, String> {
     let mut c = default_empty_cache();
 
@@ -54,7 +66,7 @@ fn last_inserted_item_should_be_mru() -> Result<(), String> {
     let v = gen_item_value(CAPACITY.get() as u32 - 1);
 
     match c.get(&k) {
-        Some(mru) if mru == v => Ok(()),
+        Some(mru) if *mru == v => Ok(()),
         Some(mru) => Err(format!("MRU item should be '{v}'. Got '{mru}' instead")),
         None => Err(format!("MRU item should be '{k}'. Got 'None' instead")),
     }
@@ -164,3 +176,3503 @@ fn thread2_should_add_new_item() -> Result<(), String> {
         Err(String::from("Expected item 'pear' not found"))
     }
 }
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_get_string_key_using_str() -> Result<(), String> {
+    let mut c = default_prefilled_cache();
+    let v = gen_item_value(3);
+
+    match c.get("item-3") {
+        Some(found) if *found == v => Ok(()),
+        Some(found) => Err(format!("Expected '{v}'. Got '{found}' instead")),
+        None => Err(String::from("Expected item 'item-3' not found")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_get_byte_vec_key_using_slice() -> Result<(), String> {
+    let mut c: LruCache<Vec<u8>, u32> = default_empty_cache();
+
+    c.put(b"apple".to_vec(), 1);
+    c.put(b"pear".to_vec(), 2);
+
+    match c.get(&b"apple"[..]) {
+        Some(1) => Ok(()),
+        Some(other) => Err(format!("Expected 1. Got {other} instead")),
+        None => Err(String::from("Expected item 'apple' not found")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn borrowed_get_should_promote_to_mru() -> Result<(), String> {
+    let mut c = default_prefilled_cache();
+    let v = gen_item_value(4);
+
+    c.get("item-4").ok_or("item-4 not found")?;
+
+    match c.pop_mru() {
+        Some(mru) if mru == v => Ok(()),
+        Some(mru) => Err(format!("MRU item should be '{v}'. Got '{mru}' instead")),
+        None => Err(format!("MRU item should be '{v}'. Got 'None' instead")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn peek_should_not_change_recency() -> Result<(), String> {
+    let mut c = default_prefilled_cache();
+    let lru_v = gen_item_value(0);
+
+    c.peek("item-0").ok_or("item-0 not found")?;
+
+    if c.peek(&gen_item_key(0)).is_none() {
+        return Err(String::from("Peek with an owned key should find item-0"));
+    }
+
+    match c.pop_lru() {
+        Some(lru) if lru == lru_v => Ok(()),
+        Some(lru) => Err(format!("LRU item should be '{lru_v}'. Got '{lru}' instead")),
+        None => Err(format!("LRU item should be '{lru_v}'. Got 'None' instead")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_check_contains_key_using_str() -> Result<(), String> {
+    let c = default_prefilled_cache();
+
+    if !c.contains_key("item-2") {
+        return Err(String::from("Expected item 'item-2' not found"));
+    }
+
+    if c.contains_key("item-10") {
+        Err(String::from("Found item 'item-10' that should not exist in cache"))
+    } else {
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_remove_item_using_str() -> Result<(), String> {
+    let mut c = default_prefilled_cache();
+    let v = gen_item_value(5);
+
+    match c.remove("item-5") {
+        Some(removed) if removed == v => (),
+        Some(removed) => return Err(format!("Expected '{v}'. Got '{removed}' instead")),
+        None => return Err(String::from("Expected item 'item-5' not found")),
+    }
+
+    if c.contains_key("item-5") || c.recency_rank("item-5").is_some() {
+        Err(String::from("Item 'item-5' should have been removed from both store and order"))
+    } else {
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_pop_entry_using_str() -> Result<(), String> {
+    let mut c = default_prefilled_cache();
+    let k = gen_item_key(7);
+    let v = gen_item_value(7);
+
+    match c.pop_entry("item-7") {
+        Some((popped_k, popped_v)) if popped_k == k && popped_v == v => (),
+        Some((popped_k, popped_v)) => {
+            return Err(format!("Expected ('{k}', '{v}'). Got ('{popped_k}', '{popped_v}') instead"));
+        }
+        None => return Err(String::from("Expected item 'item-7' not found")),
+    }
+
+    if c.pop_entry("item-7").is_some() {
+        Err(String::from("Item 'item-7' should not be popped twice"))
+    } else {
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_report_recency_rank_using_str() -> Result<(), String> {
+    let c = default_prefilled_cache();
+
+    // The last inserted item is the MRU and the first inserted item is the LRU
+    match (c.recency_rank("item-9"), c.recency_rank("item-0"), c.recency_rank("item-10")) {
+        (Some(0), Some(9), None) => Ok(()),
+        ranks => Err(format!("Expected (Some(0), Some(9), None). Got {ranks:?} instead")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_promote_item_using_str() -> Result<(), String> {
+    let mut c = default_prefilled_cache();
+    let v = gen_item_value(0);
+
+    if !c.promote("item-0") {
+        return Err(String::from("Expected item 'item-0' not found"));
+    }
+
+    if c.promote("item-10") {
+        return Err(String::from("Promoting a missing item should return false"));
+    }
+
+    match c.pop_mru() {
+        Some(mru) if mru == v => Ok(()),
+        Some(mru) => Err(format!("MRU item should be '{v}'. Got '{mru}' instead")),
+        None => Err(format!("MRU item should be '{v}'. Got 'None' instead")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_demote_item_using_str() -> Result<(), String> {
+    let mut c = default_prefilled_cache();
+    let demoted_k = gen_item_key(9);
+
+    if !c.demote("item-9") {
+        return Err(String::from("Expected item 'item-9' not found"));
+    }
+
+    // Adding a new item to a full cache should now evict the demoted item
+    c.put(gen_item_key(10), gen_item_value(10));
+
+    if c.contains_key(demoted_k.as_str()) {
+        Err(format!("Found item '{demoted_k}' when it should have been evicted"))
+    } else {
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+/// Deterministic FNV-1a hasher used to check that the cache behaves the same regardless of the hash builder
+#[derive(Default)]
+struct FnvHasher(u64);
+
+impl std::hash::Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+}
+
+type FnvBuildHasher = std::hash::BuildHasherDefault<FnvHasher>;
+
+fn replay_workload<S: BuildHasher>(mut c: LruCache<String, String, S>) -> Vec<Option<String>> {
+    let mut results = Vec::new();
+
+    for idx in 0..CAPACITY.get() * 2 {
+        results.push(c.put(gen_item_key(idx % 13), gen_item_value(idx as u32)));
+        results.push(c.get_cloned(gen_item_key(idx % 7).as_str()));
+    }
+
+    while let Some(v) = c.pop_lru() {
+        results.push(Some(v));
+    }
+
+    results
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn custom_hasher_should_behave_like_default_hasher() -> Result<(), String> {
+    let default_results = replay_workload(LruCache::new(CAPACITY));
+    let fnv_results = replay_workload(LruCache::with_hasher(CAPACITY, FnvBuildHasher::default()));
+    let lazy_results = replay_workload(LruCache::with_capacity_and_hasher(CAPACITY, 1, FnvBuildHasher::default()));
+
+    if default_results != fnv_results {
+        Err(String::from("Cache using FNV hasher diverged from cache using RandomState"))
+    } else if default_results != lazy_results {
+        Err(String::from("Cache with small initial capacity diverged from fully preallocated cache"))
+    } else {
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[cfg(feature = "ahash")]
+#[test]
+fn ahash_cache_should_behave_like_default_hasher() -> Result<(), String> {
+    if replay_workload(LruCache::new(CAPACITY)) == replay_workload(AHashLruCache::with_ahash(CAPACITY)) {
+        Ok(())
+    } else {
+        Err(String::from("Cache using ahash diverged from cache using RandomState"))
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[cfg(feature = "fxhash")]
+#[test]
+fn fxhash_cache_should_behave_like_default_hasher() -> Result<(), String> {
+    if replay_workload(LruCache::new(CAPACITY)) == replay_workload(FxLruCache::with_fxhash(CAPACITY)) {
+        Ok(())
+    } else {
+        Err(String::from("Cache using fxhash diverged from cache using RandomState"))
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_round_trip_through_parts() -> Result<(), String> {
+    let (capacity, store, order) = default_prefilled_cache().into_parts();
+
+    // Bulk-transform every key, preserving the recency order
+    let store: HashMap<String, String, DefaultHashBuilder> = store.into_iter().map(|(k, v)| (k.to_uppercase(), v)).collect();
+    let order = order.into_iter().map(|k| k.to_uppercase()).collect();
+
+    let mut c = LruCache::from_parts(capacity, store, order).map_err(|e| e.to_string())?;
+    let v = gen_item_value(0);
+
+    match c.pop_lru() {
+        Some(lru) if lru == v && c.contains_key("ITEM-9") => Ok(()),
+        Some(lru) => Err(format!("LRU item should be '{v}'. Got '{lru}' instead")),
+        None => Err(format!("LRU item should be '{v}'. Got 'None' instead")),
+    }
+}
+
+fn parts(keys: &[&str], order: &[&str]) -> (HashMap<String, u32, DefaultHashBuilder>, VecDeque<String>) {
+    let mut store = HashMap::with_hasher(DefaultHashBuilder::default());
+
+    for (idx, k) in keys.iter().enumerate() {
+        store.insert(k.to_string(), idx as u32);
+    }
+
+    (store, order.iter().map(|k| k.to_string()).collect())
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn from_parts_should_reject_length_mismatch() -> Result<(), String> {
+    let (store, order) = parts(&["apple", "pear"], &["apple"]);
+
+    match LruCache::from_parts(CAPACITY, store, order) {
+        Err(CacheError::LengthMismatch { store_len: 2, order_len: 1 }) => Ok(()),
+        Err(e) => Err(format!("Expected LengthMismatch. Got '{e}' instead")),
+        Ok(_) => Err(String::from("Expected LengthMismatch. Got a cache instead")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn from_parts_should_reject_key_missing_from_store() -> Result<(), String> {
+    let (store, order) = parts(&["apple", "pear"], &["apple", "plum"]);
+
+    match LruCache::from_parts(CAPACITY, store, order) {
+        Err(CacheError::KeyNotInStore) => Ok(()),
+        Err(e) => Err(format!("Expected KeyNotInStore. Got '{e}' instead")),
+        Ok(_) => Err(String::from("Expected KeyNotInStore. Got a cache instead")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn from_parts_should_reject_duplicate_keys() -> Result<(), String> {
+    let (store, order) = parts(&["apple", "pear"], &["apple", "apple"]);
+
+    match LruCache::from_parts(CAPACITY, store, order) {
+        Err(CacheError::DuplicateKey) => Ok(()),
+        Err(e) => Err(format!("Expected DuplicateKey. Got '{e}' instead")),
+        Ok(_) => Err(String::from("Expected DuplicateKey. Got a cache instead")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn from_parts_should_reject_too_many_items() -> Result<(), String> {
+    let (store, order) = parts(&["apple", "pear", "plum"], &["apple", "pear", "plum"]);
+
+    match LruCache::from_parts(NonZeroUsize::new(2).unwrap(), store, order) {
+        Err(CacheError::CapacityExceeded { len: 3, capacity: 2 }) => Ok(()),
+        Err(e) => Err(format!("Expected CapacityExceeded. Got '{e}' instead")),
+        Ok(_) => Err(String::from("Expected CapacityExceeded. Got a cache instead")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn from_parts_should_reject_unaddressable_capacity() -> Result<(), String> {
+    let (store, order) = parts(&["apple"], &["apple"]);
+
+    match LruCache::from_parts(NonZeroUsize::MAX, store, order) {
+        Err(CacheError::CapacityUnsupported { capacity: usize::MAX, .. }) => Ok(()),
+        Err(e) => Err(format!("Expected CapacityUnsupported. Got '{e}' instead")),
+        Ok(_) => Err(String::from("Expected CapacityUnsupported. Got a cache instead")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn try_with_capacity_and_hasher_should_reject_unaddressable_capacity() -> Result<(), String> {
+    let result = LruCache::<u32, u32>::try_with_capacity_and_hasher(NonZeroUsize::MAX, 0, DefaultHashBuilder::default());
+
+    match result {
+        Err(CacheError::CapacityUnsupported { capacity: usize::MAX, .. }) => Ok(()),
+        Err(e) => Err(format!("Expected CapacityUnsupported. Got '{e}' instead")),
+        Ok(_) => Err(String::from("Expected CapacityUnsupported. Got a cache instead")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+/// Replays a workload against the cache and against a naive model that keeps the recency order in a `VecDeque`
+#[test]
+fn should_match_naive_model_under_churn() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(7).unwrap());
+    let mut model: VecDeque<(u32, u32)> = VecDeque::new();
+
+    for i in 0..2_000u32 {
+        let k = (i * 31) % 23;
+
+        // put
+        let expected = match model.iter().position(|(mk, _)| *mk == k) {
+            Some(pos) => model.remove(pos).map(|(_, v)| v),
+            None => {
+                if model.len() == 7 {
+                    model.pop_back();
+                }
+                None
+            }
+        };
+        model.push_front((k, i));
+
+        if c.put(k, i) != expected {
+            return Err(format!("put({k}) diverged at step {i}"));
+        }
+
+        // get
+        let probe = (i * 17) % 19;
+        let expected = model.iter().position(|(mk, _)| *mk == probe).and_then(|pos| model.remove(pos));
+        if let Some(entry) = expected {
+            model.push_front(entry);
+        }
+
+        if c.get(&probe) != expected.as_ref().map(|(_, v)| v) {
+            return Err(format!("get({probe}) diverged at step {i}"));
+        }
+
+        // demote
+        if i % 7 == 0 {
+            let target = (i * 13) % 23;
+            let expected = model.iter().position(|(mk, _)| *mk == target).and_then(|pos| model.remove(pos));
+            if let Some(entry) = expected {
+                model.push_back(entry);
+            }
+
+            if c.demote(&target) != expected.is_some() {
+                return Err(format!("demote({target}) diverged at step {i}"));
+            }
+        }
+
+        // remove
+        if i % 11 == 0 {
+            let target = i % 23;
+            let expected = model.iter().position(|(mk, _)| *mk == target).and_then(|pos| model.remove(pos));
+
+            if c.remove(&target) != expected.map(|(_, v)| v) {
+                return Err(format!("remove({target}) diverged at step {i}"));
+            }
+        }
+    }
+
+    let (_, _, order) = c.into_parts();
+
+    if order.iter().eq(model.iter().map(|(k, _)| k)) {
+        Ok(())
+    } else {
+        Err(format!("Final recency order {order:?} does not match model {model:?}"))
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_stay_consistent_under_alternating_puts_and_removes() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(5).unwrap());
+
+    for i in 0..1_000u32 {
+        c.put(i % 13, i);
+
+        if i % 2 == 0 {
+            c.remove(&((i * 3) % 13));
+        }
+
+        if i % 5 == 0 {
+            c.pop_mru();
+        }
+
+        if i % 7 == 0 {
+            c.pop_lru();
+        }
+
+        c.debug_validate().map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+thread_local! {
+    static LIVE_KEYS: std::cell::Cell<isize> = const { std::cell::Cell::new(0) };
+}
+
+/// A key that counts how many instances of itself are alive on the current thread
+struct CountedKey(u32);
+
+impl CountedKey {
+    fn new(id: u32) -> Self {
+        LIVE_KEYS.with(|n| n.set(n.get() + 1));
+        CountedKey(id)
+    }
+
+    fn live() -> isize {
+        LIVE_KEYS.with(|n| n.get())
+    }
+}
+
+impl Clone for CountedKey {
+    fn clone(&self) -> Self {
+        CountedKey::new(self.0)
+    }
+}
+
+impl Drop for CountedKey {
+    fn drop(&mut self) {
+        LIVE_KEYS.with(|n| n.set(n.get() - 1));
+    }
+}
+
+impl PartialEq for CountedKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for CountedKey {}
+
+impl Hash for CountedKey {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl Borrow<u32> for CountedKey {
+    fn borrow(&self) -> &u32 {
+        &self.0
+    }
+}
+
+#[test]
+fn should_hold_exactly_one_instance_of_each_key() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(8).unwrap());
+
+    for i in 0..500u32 {
+        c.put(CountedKey::new(i % 19), i);
+        c.get(&((i * 7) % 19));
+        c.promote(&((i * 3) % 19));
+
+        if i % 4 == 0 {
+            c.remove(&((i * 5) % 19));
+        }
+
+        if i % 9 == 0 {
+            drop(c.push(CountedKey::new(i % 23), i));
+        }
+
+        if CountedKey::live() != c.len() as isize {
+            return Err(format!("Step {i}: {} live keys for {} entries", CountedKey::live(), c.len()));
+        }
+    }
+
+    drop(c);
+
+    match CountedKey::live() {
+        0 => Ok(()),
+        n => Err(format!("{n} keys outlived the cache")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+/// A value that deliberately cannot be cloned, standing in for a file handle or connection
+#[derive(Debug, PartialEq)]
+struct Connection(u32);
+
+#[test]
+fn should_cache_values_that_are_not_clone() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(2).unwrap());
+    c.put("db", Connection(1));
+    c.put("queue", Connection(2));
+
+    if c.get("db") != Some(&Connection(1)) {
+        return Err(String::from("Expected to borrow connection 1"));
+    }
+
+    // "queue" is now the least recently used, so it is evicted
+    c.put("cache", Connection(3));
+
+    match (c.contains_key("queue"), c.pop_lru()) {
+        (false, Some(Connection(1))) => Ok(()),
+        other => Err(format!("Expected 'queue' to be evicted and 'db' to be next. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn get_cloned_should_return_owned_copy() -> Result<(), String> {
+    let mut c = default_prefilled_cache();
+    let owned = c.get_cloned("item-4");
+    c.clear();
+
+    match owned {
+        Some(v) if v == gen_item_value(4) => Ok(()),
+        other => Err(format!("Expected an owned 'value-4'. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+/// A key that deliberately cannot be cloned, standing in for a struct that owns an mmap region
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct RegionKey {
+    id: u32,
+}
+
+impl Borrow<u32> for RegionKey {
+    fn borrow(&self) -> &u32 {
+        &self.id
+    }
+}
+
+#[test]
+fn should_cache_keys_that_are_not_clone() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(3).unwrap());
+
+    for id in 0..4 {
+        c.put(RegionKey { id }, id * 10);
+    }
+
+    if c.get(&0).is_some() {
+        return Err(String::from("Region 0 should have been evicted"));
+    }
+
+    if c.get(&1) != Some(&10) {
+        return Err(String::from("Expected region 1 to be cached"));
+    }
+
+    if c.remove(&2) != Some(20) {
+        return Err(String::from("Expected to remove region 2"));
+    }
+
+    match (c.pop_mru(), c.pop_entry(&3), c.is_empty()) {
+        (Some(10), Some((RegionKey { id: 3 }, 30)), true) => Ok(()),
+        other => Err(format!("Unexpected final state {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+fn buffered_cache(capacity: usize, buffer: usize) -> LruCache<u32, u32> {
+    LruCacheBuilder::new(NonZeroUsize::new(capacity).unwrap())
+        .read_buffer(NonZeroUsize::new(buffer).unwrap())
+        .build()
+}
+
+#[test]
+fn should_not_evict_entry_read_while_buffered() -> Result<(), String> {
+    let mut c = buffered_cache(3, 16);
+
+    for k in 0..3 {
+        c.put(k, k);
+    }
+
+    // Key 0 is the least recently used until the buffered reads are applied
+    for _ in 0..10 {
+        c.get_deferred(&0).ok_or("Key 0 Not Found")?;
+    }
+
+    if c.recency_rank(&0) != Some(2) {
+        return Err(String::from("Buffered reads should not reorder the recency list"));
+    }
+
+    c.put(3, 3);
+
+    match (c.contains_key(&0), c.contains_key(&1)) {
+        (true, false) => Ok(()),
+        other => Err(format!("Expected key 1 to be evicted instead of key 0, got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn run_pending_tasks_should_apply_buffered_reads() -> Result<(), String> {
+    let mut c = buffered_cache(3, 16);
+
+    for k in 0..3 {
+        c.put(k, k);
+    }
+
+    c.get_deferred(&0).ok_or("Key 0 Not Found")?;
+    let before = c.recency_rank(&0);
+    c.run_pending_tasks();
+
+    match (before, c.recency_rank(&0)) {
+        (Some(2), Some(0)) => Ok(()),
+        other => Err(format!("Expected key 0 to move from least to most recently used, got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn buffered_reads_should_not_change_results() -> Result<(), String> {
+    let mut exact = LruCache::new(NonZeroUsize::new(7).unwrap());
+    let mut buffered = buffered_cache(7, 4);
+
+    for i in 0..2_000u32 {
+        let probe = (i * 17) % 19;
+
+        if exact.get(&probe) != buffered.get(&probe) {
+            return Err(format!("get({probe}) diverged at step {i}"));
+        }
+
+        if i % 3 == 0 {
+            let k = (i * 31) % 23;
+
+            if exact.push(k, i) != buffered.push(k, i) {
+                return Err(format!("push({k}) diverged at step {i}"));
+            }
+        }
+
+        if i % 11 == 0 && exact.remove(&(i % 23)) != buffered.remove(&(i % 23)) {
+            return Err(format!("remove({}) diverged at step {i}", i % 23));
+        }
+
+        buffered.debug_validate().map_err(|e| e.to_string())?;
+    }
+
+    let (_, _, expected) = exact.into_parts();
+    let (_, _, order) = buffered.into_parts();
+
+    if order == expected {
+        Ok(())
+    } else {
+        Err(format!("Final recency order {order:?} does not match {expected:?}"))
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn sampled_eviction_should_evict_entry_that_was_not_used() -> Result<(), String> {
+    // Sampling many more entries than the cache holds makes it all but certain that every entry is compared
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(4).unwrap())
+        .eviction_mode(EvictionMode::Sampled {
+            sample_size: NonZeroUsize::new(64).unwrap(),
+        })
+        .build();
+
+    for k in 0..4 {
+        c.put(k, k);
+    }
+
+    for k in [0, 1, 3] {
+        c.get(&k).ok_or(format!("Key {k} Not Found"))?;
+    }
+
+    c.put(4, 4);
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (c.contains_key(&2), c.len()) {
+        (false, 4) => Ok(()),
+        other => Err(format!("Expected key 2 to be evicted, got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn memory_usage_should_cover_entries_and_index() -> Result<(), String> {
+    let capacity = NonZeroUsize::new(1024).unwrap();
+    let mut c: LruCache<u64, u64> = LruCache::with_capacity_and_hasher(capacity, 0, DefaultHashBuilder::default());
+    let empty = c.memory_usage();
+
+    for i in 0..1024 {
+        c.put(i, i);
+    }
+
+    let full = c.memory_usage();
+    c.clear();
+
+    // Each entry needs at least its key and value, and the index needs a slot per entry
+    let minimum = 1024 * (mem::size_of::<(u64, u64)>() + 1);
+
+    if full < minimum || full <= empty {
+        Err(format!("Estimated {full} bytes for 1024 entries, {empty} when empty, expected at least {minimum}"))
+    } else if c.memory_usage() < full {
+        Err(String::from("Clearing the cache should keep its storage"))
+    } else {
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+/// Inserts keys into a cache that starts small and grows its index incrementally, removing some and letting the
+/// capacity evict others, and checks that every key still held is reachable throughout.  Returns the number of times
+/// the index started to grow
+fn check_incremental_growth(capacity: usize, inserts: u32) -> Result<usize, String> {
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(capacity).unwrap())
+        .initial_capacity(16)
+        .incremental_growth(true)
+        .build();
+    let mut held = std::collections::HashSet::new();
+    let mut growth_phases = 0;
+
+    for i in 0..inserts {
+        let was_draining = c.index.is_draining();
+
+        if let Some((evicted, _)) = c.push(i, i) {
+            held.remove(&evicted);
+        }
+        held.insert(i);
+
+        if i % 7 == 3 && c.remove(&(i / 2)).is_some() {
+            held.remove(&(i / 2));
+        }
+
+        if c.index.is_draining() && !was_draining {
+            growth_phases += 1;
+        }
+
+        if c.get(&i) != Some(&i) {
+            return Err(format!("Key {i} unreachable straight after insertion"));
+        }
+
+        if i % 1_000 == 0 || c.index.is_draining() && i % 100 == 0 {
+            c.debug_validate().map_err(|e| e.to_string())?;
+
+            if let Some(missing) = held.iter().find(|k| c.peek(*k) != Some(*k)) {
+                return Err(format!("Key {missing} unreachable after inserting {i}"));
+            }
+        }
+    }
+
+    Ok(growth_phases)
+}
+
+#[test]
+fn incremental_growth_should_keep_every_key_reachable() -> Result<(), String> {
+    let unbounded = check_incremental_growth(100_000, 30_000)?;
+    let evicting = check_incremental_growth(4_000, 30_000)?;
+
+    if unbounded >= 5 && evicting >= 3 {
+        Ok(())
+    } else {
+        Err(format!("Expected several growth phases. Got {unbounded} without eviction and {evicting} with"))
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn doorkeeper_should_never_turn_away_cached_keys() -> Result<(), String> {
+    let capacity = NonZeroUsize::new(500).unwrap();
+    let mut guarded = LruCacheBuilder::new(capacity).doorkeeper(true).build();
+    let mut plain = LruCache::new(capacity);
+
+    for i in 0..50_000u32 {
+        let key = (i.wrapping_mul(2_654_435_761) >> 20) % 2_000;
+
+        match i % 5 {
+            0 => _ = (guarded.remove(&key), plain.remove(&key)),
+            1 | 2 => _ = (guarded.put(key, i), plain.put(key, i)),
+            _ if guarded.get(&key) != plain.get(&key) => return Err(format!("Lookups for {key} diverged at step {i}")),
+            _ => (),
+        }
+
+        if i % 1_000 == 0 {
+            guarded.debug_validate().map_err(|e| e.to_string())?;
+        }
+    }
+
+    match (guarded.doorkeeper_rejections(), plain.doorkeeper_rejections()) {
+        (1.., 0) => Ok(()),
+        counts => Err(format!("Expected rejections only with a doorkeeper. Got {counts:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn doorkeeper_should_keep_rejecting_misses_after_churn() -> Result<(), String> {
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(1_000).unwrap()).doorkeeper(true).build();
+
+    // Without rebuilds, a hundred cache-fulls of evicted keys would leave every bit of the filter set
+    for i in 0..100_000u64 {
+        c.put(i, i);
+    }
+
+    let misses = (1_000_000..1_010_000u64).filter(|k| c.get(k).is_none()).count();
+    let rejections = c.doorkeeper_rejections();
+
+    if misses == 10_000 && rejections >= 9_900 {
+        Ok(())
+    } else {
+        Err(format!("Expected at least 9900 of 10000 misses to be rejected. Got {rejections} of {misses}"))
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+fn cache_with_mock_clock(capacity: usize) -> (LruCache<u32, u32>, MockClock) {
+    let clock = MockClock::new();
+    (LruCache::with_clock(NonZeroUsize::new(capacity).unwrap(), clock.clone()), clock)
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn recency_rank_should_skip_expired_items() -> Result<(), String> {
+    let (mut c, clock) = cache_with_mock_clock(4);
+    c.put(1, 10);
+    c.put_with_ttl(2, 20, Duration::from_secs(10));
+    c.put_with_ttl(3, 30, Duration::from_secs(10));
+
+    clock.advance(Duration::from_secs(10));
+
+    match (c.recency_rank(&3), c.recency_rank(&2), c.recency_rank(&1)) {
+        (None, None, Some(0)) => Ok(()),
+        other => Err(format!("Expected only key 1 ranked, at 0. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn put_with_ttl_should_expire_the_item_at_its_deadline() -> Result<(), String> {
+    let (mut c, clock) = cache_with_mock_clock(4);
+    c.put_with_ttl(1, 10, Duration::from_secs(10));
+    c.put_with_ttl(2, 20, Duration::from_secs(20));
+    c.put(3, 30);
+
+    clock.advance(Duration::from_secs(9));
+    let before = (c.get(&1).copied(), c.len());
+
+    clock.advance(Duration::from_secs(1));
+    let after = (c.get(&1).copied(), c.len());
+
+    clock.advance(Duration::from_secs(3_600));
+
+    match (before, after, c.get_cloned(&2), c.get_cloned(&3)) {
+        ((Some(10), 3), (None, 2), None, Some(30)) => Ok(()),
+        other => Err(format!("Expected items to expire at their deadlines and be removed by get. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn peek_and_contains_key_should_treat_expired_items_as_absent() -> Result<(), String> {
+    let (mut c, clock) = cache_with_mock_clock(4);
+    c.put_with_ttl(1, 10, Duration::from_secs(1));
+    clock.advance(Duration::from_secs(1));
+
+    // Neither can remove the item through a shared borrow, so it still counts towards the length
+    match (c.peek(&1), c.contains_key(&1), c.get_deferred(&1), c.len()) {
+        (None, false, None, 1) => Ok(()),
+        other => Err(format!("Expected the expired item to be invisible but not yet removed. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn pops_should_skip_expired_items() -> Result<(), String> {
+    let (mut c, clock) = cache_with_mock_clock(4);
+    c.put_with_ttl(1, 10, Duration::from_secs(1));
+    c.put(2, 20);
+    c.put(3, 30);
+    c.put_with_ttl(4, 40, Duration::from_secs(1));
+    clock.advance(Duration::from_secs(2));
+
+    match (c.pop_lru(), c.pop_mru(), c.is_empty()) {
+        (Some(20), Some(30), true) => Ok(()),
+        other => Err(format!("Expected the pops to discard the expired items at either end. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn expired_items_should_make_room_without_an_eviction() -> Result<(), String> {
+    let (mut c, clock) = cache_with_mock_clock(2);
+    c.put_with_ttl(1, 10, Duration::from_secs(1));
+    c.put(2, 20);
+    clock.advance(Duration::from_secs(1));
+
+    match (c.push(3, 30), c.put(2, 21), c.get_cloned(&3), c.len()) {
+        (None, Some(20), Some(30), 2) => Ok(()),
+        other => Err(format!("Expected the expired item to make room for the new one. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn replacing_an_expired_item_should_not_return_its_value() -> Result<(), String> {
+    let (mut c, clock) = cache_with_mock_clock(2);
+    c.put_with_ttl(1, 10, Duration::from_secs(1));
+    c.put_with_ttl(2, 20, Duration::from_secs(1));
+    clock.advance(Duration::from_secs(1));
+
+    let replaced = (c.put(1, 11), c.push(2, 21), c.remove(&1));
+
+    // Without a time-to-live, the new items never expire
+    c.put(1, 12);
+    clock.advance(Duration::from_secs(3_600));
+
+    match (replaced, c.get_cloned(&1), c.get_cloned(&2)) {
+        ((None, None, Some(11)), Some(12), Some(21)) => Ok(()),
+        other => Err(format!("Expected expired values to be treated as absent. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn iteration_should_exclude_expired_items() -> Result<(), String> {
+    let (mut c, clock) = cache_with_mock_clock(4);
+    c.put(1, 10);
+    c.put_with_ttl(2, 20, Duration::from_secs(1));
+    c.put(3, 30);
+    clock.advance(Duration::from_secs(1));
+
+    let items: Vec<_> = c.iter_lru_first().map(|(&k, &v)| (k, v)).collect();
+
+    match &items[..] {
+        [(1, 10), (3, 30)] => Ok(()),
+        other => Err(format!("Expected only the live items. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn default_ttl_should_apply_unless_an_item_has_its_own() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(8).unwrap())
+        .clock(clock.clone())
+        .ttl(Duration::from_secs(10))
+        .build();
+
+    c.put(1, 10);
+    c.push(2, 20);
+    c.put_with_ttl(3, 30, Duration::from_secs(5));
+    c.put_with_ttl(4, 40, Duration::from_secs(20));
+
+    let mut live = Vec::new();
+
+    for elapsed in [4, 5, 9, 10, 19, 20] {
+        clock.advance(Duration::from_secs(elapsed) - Duration::from_nanos(clock.now()));
+        live.push((elapsed, (1..=4).filter(|k| c.contains_key(k)).collect::<Vec<_>>()));
+    }
+
+    let expected = [
+        (4, vec![1, 2, 3, 4]),
+        (5, vec![1, 2, 4]),
+        (9, vec![1, 2, 4]),
+        (10, vec![4]),
+        (19, vec![4]),
+        (20, vec![]),
+    ];
+
+    match live == expected {
+        true => Ok(()),
+        false => Err(format!("Expected each item to expire at its own deadline. Got {live:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn without_a_default_ttl_items_should_never_expire() -> Result<(), String> {
+    let (mut c, clock) = cache_with_mock_clock(2);
+    c.put(1, 10);
+    clock.advance(Duration::from_secs(u32::MAX as u64));
+
+    match c.get(&1) {
+        Some(10) => Ok(()),
+        other => Err(format!("Expected the item to remain. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+fn cache_with_deadlines(ttl: Option<u64>, time_to_idle: u64) -> (LruCache<u32, u32>, MockClock) {
+    let clock = MockClock::new();
+    let mut builder = LruCacheBuilder::new(NonZeroUsize::new(4).unwrap())
+        .clock(clock.clone())
+        .time_to_idle(Duration::from_secs(time_to_idle));
+
+    if let Some(ttl) = ttl {
+        builder = builder.ttl(Duration::from_secs(ttl));
+    }
+
+    (builder.build(), clock)
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn get_should_keep_an_item_from_going_idle() -> Result<(), String> {
+    let (mut c, clock) = cache_with_deadlines(None, 10);
+    c.put(1, 10);
+    c.put(2, 20);
+
+    for _ in 0..5 {
+        clock.advance(Duration::from_secs(6));
+        c.get(&1);
+    }
+
+    match (c.get_cloned(&1), c.get_cloned(&2), c.len()) {
+        (Some(10), None, 1) => Ok(()),
+        other => Err(format!("Expected only the item in use to remain. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn peek_should_not_restart_the_idle_time() -> Result<(), String> {
+    let (mut c, clock) = cache_with_deadlines(None, 10);
+    c.put(1, 10);
+    clock.advance(Duration::from_secs(6));
+    let peeked = c.peek(&1).copied();
+    clock.advance(Duration::from_secs(4));
+
+    match (peeked, c.peek(&1).copied(), c.get_cloned(&1)) {
+        (Some(10), None, None) => Ok(()),
+        other => Err(format!("Expected the item to go idle despite being peeked. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn ttl_should_expire_an_item_in_use_before_it_goes_idle() -> Result<(), String> {
+    let (mut c, clock) = cache_with_deadlines(Some(15), 10);
+    c.put(1, 10);
+    let mut seen = Vec::new();
+
+    for _ in 0..4 {
+        clock.advance(Duration::from_secs(5));
+        seen.push(c.get(&1).copied());
+    }
+
+    match seen[..] {
+        [Some(10), Some(10), None, None] => Ok(()),
+        _ => Err(format!("Expected the time-to-live to expire the item at 15s. Got {seen:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn time_to_idle_should_expire_an_unused_item_before_its_ttl() -> Result<(), String> {
+    let (mut c, clock) = cache_with_deadlines(Some(15), 10);
+    c.put(1, 10);
+    c.put_with_ttl(2, 20, Duration::from_secs(60));
+    clock.advance(Duration::from_secs(9));
+    let before = (c.contains_key(&1), c.contains_key(&2));
+    clock.advance(Duration::from_secs(1));
+
+    match (before, c.get_cloned(&1), c.get_cloned(&2)) {
+        ((true, true), None, None) => Ok(()),
+        other => Err(format!("Expected both items to go idle at 10s. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn purge_expired_should_free_room_without_evicting_live_items() -> Result<(), String> {
+    let (mut c, clock) = cache_with_mock_clock(4);
+    c.put(1, 10);
+    c.put(2, 20);
+    c.put_with_ttl(3, 30, Duration::from_secs(5));
+    c.put_with_ttl(4, 40, Duration::from_secs(5));
+    clock.advance(Duration::from_secs(5));
+
+    let purged = c.purge_expired();
+    let len = c.len();
+    let evicted = (c.push(5, 50), c.push(6, 60));
+    let live = (1..=6).filter(|k| c.contains_key(k)).collect::<Vec<_>>();
+
+    match (purged, len, evicted, &live[..]) {
+        (2, 2, (None, None), [1, 2, 5, 6]) => Ok(()),
+        other => Err(format!("Expected the expired items to make room for the new ones. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn purge_expired_should_remove_idle_items_and_keep_live_ones() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(4).unwrap())
+        .clock(clock.clone())
+        .time_to_idle(Duration::from_secs(10))
+        .build();
+
+    for k in 0..4 {
+        c.put(k, k);
+    }
+
+    clock.advance(Duration::from_secs(6));
+    c.get(&1);
+    clock.advance(Duration::from_secs(6));
+
+    match (c.purge_expired(), c.purge_expired(), c.len(), c.peek(&1)) {
+        (3, 0, 1, Some(1)) => Ok(()),
+        other => Err(format!("Expected the three idle items to be purged once. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn purge_expired_should_do_nothing_without_deadlines() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(2).unwrap());
+    c.put(1, 10);
+
+    match (c.purge_expired(), c.len()) {
+        (0, 1) => Ok(()),
+        other => Err(format!("Expected nothing to be purged. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn put_with_expiry_at_should_expire_the_item_at_the_deadline() -> Result<(), String> {
+    let (mut c, clock) = cache_with_mock_clock(4);
+    c.put_with_expiry_at(1, 10, UNIX_EPOCH + Duration::from_secs(10));
+
+    clock.advance(Duration::from_secs(9));
+    let before = c.get_cloned(&1);
+    clock.advance(Duration::from_secs(1));
+
+    match (before, c.get_cloned(&1)) {
+        (Some(10), None) => Ok(()),
+        other => Err(format!("Expected the item to expire after 10s. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn put_with_expiry_at_should_not_store_or_evict_when_the_deadline_has_passed() -> Result<(), String> {
+    let (mut c, clock) = cache_with_mock_clock(2);
+    clock.advance(Duration::from_secs(5));
+    c.put(1, 10);
+    c.put(2, 20);
+    let past = UNIX_EPOCH + Duration::from_secs(4);
+
+    let new_key = c.put_with_expiry_at(3, 30, past);
+    let existing_key = c.put_with_expiry_at(2, 21, past);
+    let live = (1..=3).filter(|k| c.contains_key(k)).collect::<Vec<_>>();
+
+    match (new_key, existing_key, &live[..], c.len()) {
+        (None, Some(20), [1], 1) => Ok(()),
+        other => Err(format!("Expected the expired items to be dropped without evicting. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn put_with_expiry_at_should_convert_system_time_on_the_system_clock() -> Result<(), String> {
+    let mut c = LruCache::with_clock(CAPACITY, SystemClock::new());
+    c.put_with_expiry_at(1, 10, SystemTime::now() + Duration::from_secs(60));
+
+    match c.entry_info(&1).and_then(|info| info.remaining_ttl()) {
+        Some(remaining) if remaining > Duration::from_secs(55) && remaining <= Duration::from_secs(60) => Ok(()),
+        other => Err(format!("Expected about 60s left. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn entry_info_should_report_age_idle_time_and_remaining_ttl() -> Result<(), String> {
+    let (mut c, clock) = cache_with_mock_clock(4);
+    c.put_with_ttl(1, 10, Duration::from_secs(120));
+    c.put(2, 20);
+
+    clock.advance(Duration::from_secs(30));
+    c.get(&1);
+    clock.advance(Duration::from_secs(30));
+
+    let info = c.entry_info(&1).ok_or("Key 1 Not Found")?;
+    let measured = (info.age(), info.idle(), info.remaining_ttl(), c.entry_info(&2).map(|i| i.remaining_ttl()));
+    let secs = Duration::from_secs;
+
+    match measured {
+        (age, idle, Some(remaining), Some(None)) if (age, idle, remaining) == (secs(60), secs(30), secs(60)) => Ok(()),
+        other => Err(format!("Expected an age of 60s, 30s idle and 60s to live. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn entry_info_should_not_count_as_a_use() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(2).unwrap())
+        .clock(clock.clone())
+        .time_to_idle(Duration::from_secs(10))
+        .build();
+    c.put(1, 10);
+    c.put(2, 20);
+
+    clock.advance(Duration::from_secs(6));
+    let info = c.entry_info(&1).ok_or("Key 1 Not Found")?;
+    c.put(3, 30);
+
+    match (info.idle(), info.remaining_ttl(), c.contains_key(&1)) {
+        (idle, Some(remaining), false) if idle == Duration::from_secs(6) && remaining == Duration::from_secs(4) => {
+            Ok(())
+        }
+        other => Err(format!("Expected key 1 to stay least recently used and idle. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn entry_info_should_be_none_without_a_clock() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(2).unwrap());
+    c.put(1, 10);
+
+    match c.entry_info(&1) {
+        None => Ok(()),
+        other => Err(format!("Expected no timing information. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn track_access_should_count_hits_but_not_peeks() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(4).unwrap())
+        .clock(clock.clone())
+        .track_access(true)
+        .build();
+    c.put(1, 10);
+    c.put(2, 20);
+    c.put(3, 30);
+
+    for _ in 0..3 {
+        c.get(&1);
+    }
+
+    clock.advance(Duration::from_secs(5));
+    c.get(&2);
+    c.peek(&1);
+    c.peek(&3);
+    c.contains_key(&3);
+    c.put(1, 11);
+    c.get(&1);
+    c.remove(&2);
+    c.put(2, 21);
+    let metadata: Vec<_> = c.iter_with_metadata().map(|(&k, &v, meta)| (k, v, meta.hits())).collect();
+    let info = c.entry_info(&1).ok_or("Key 1 Not Found")?;
+    let secs = |n| Duration::from_secs(n).as_nanos() as u64;
+    let accessed: Vec<_> = c.iter_with_metadata().map(|(_, _, meta)| meta.last_accessed()).collect();
+
+    match (&metadata[..], info.hits(), &accessed[..]) {
+        ([(2, 21, 0), (1, 11, 4), (3, 30, 0)], Some(4), [t2, t1, 0]) if (*t2, *t1) == (secs(5), secs(5)) => Ok(()),
+        other => Err(format!("Expected key 1 hit 4 times and no other hits. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn track_access_should_restart_the_count_of_an_expired_item() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(2).unwrap())
+        .clock(clock.clone())
+        .track_access(true)
+        .build();
+    c.put_with_ttl(1, 10, Duration::from_secs(1));
+    c.get(&1);
+    c.get(&1);
+
+    clock.advance(Duration::from_secs(1));
+    c.put(1, 11);
+    c.get(&1);
+
+    match c.entry_info(&1).and_then(|info| info.hits()) {
+        Some(1) => Ok(()),
+        other => Err(format!("Expected one hit since the value was replaced. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn track_access_should_only_cost_memory_for_the_counts() -> Result<(), String> {
+    let filled = |track_access| {
+        let mut c = LruCacheBuilder::new(NonZeroUsize::new(64).unwrap())
+            .track_access(track_access)
+            .build();
+
+        for k in 0..64u64 {
+            c.put(k, k);
+            c.get(&k);
+        }
+
+        c.memory_usage()
+    };
+    let (untracked, tracked) = (filled(false), filled(true));
+    let counts = tracked.saturating_sub(untracked);
+
+    match counts / mem::size_of::<u64>() {
+        64..=128 => Ok(()),
+        other => Err(format!("Expected room for 64 to 128 counts beside {untracked} bytes. Got {other}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn hits_should_not_be_counted_unless_tracking_access() -> Result<(), String> {
+    let (mut c, _) = cache_with_mock_clock(2);
+    c.put(1, 10);
+    c.get(&1);
+    let hits: Vec<_> = c.iter_with_metadata().map(|(_, _, meta)| meta.hits()).collect();
+
+    match (c.entry_info(&1).map(|info| info.hits()), &hits[..]) {
+        (Some(None), [0]) => Ok(()),
+        other => Err(format!("Expected no hit counts. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+fn cache_refreshing_ttl(time_to_idle: Option<u64>) -> (LruCache<u32, u32>, MockClock) {
+    let clock = MockClock::new();
+    let mut builder = LruCacheBuilder::new(NonZeroUsize::new(4).unwrap())
+        .clock(clock.clone())
+        .ttl(Duration::from_secs(10))
+        .refresh_ttl_on_get(true);
+
+    if let Some(time_to_idle) = time_to_idle {
+        builder = builder.time_to_idle(Duration::from_secs(time_to_idle));
+    }
+
+    (builder.build(), clock)
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn refresh_ttl_on_get_should_slide_the_deadline_of_items_in_use() -> Result<(), String> {
+    let (mut c, clock) = cache_refreshing_ttl(None);
+    c.put(1, 10);
+    c.put_with_ttl(2, 20, Duration::from_secs(20));
+
+    for _ in 0..4 {
+        clock.advance(Duration::from_secs(8));
+        c.get(&1);
+    }
+
+    // At 32s, key 1 was last used at 32s and key 2 has never been used since it was stored with a 20s time-to-live
+    let in_use = c.entry_info(&1).and_then(|info| info.remaining_ttl());
+    let unused = c.contains_key(&2);
+    clock.advance(Duration::from_secs(10));
+
+    match (in_use, unused, c.get_cloned(&1)) {
+        (Some(remaining), false, None) if remaining == Duration::from_secs(10) => Ok(()),
+        other => Err(format!("Expected key 1 to live until 10s after its last use. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn refresh_ttl_on_get_should_still_expire_idle_items_first() -> Result<(), String> {
+    let (mut c, clock) = cache_refreshing_ttl(Some(5));
+    c.put(1, 10);
+    c.put(2, 20);
+
+    clock.advance(Duration::from_secs(4));
+    c.get(&1);
+    clock.advance(Duration::from_secs(4));
+    let at_8s = (c.contains_key(&1), c.contains_key(&2));
+    clock.advance(Duration::from_secs(1));
+
+    // Key 1's time-to-live was renewed to 14s, but it goes idle at 9s
+    match (at_8s, c.contains_key(&1)) {
+        ((true, false), false) => Ok(()),
+        other => Err(format!("Expected key 1 to go idle at 9s despite its renewed time-to-live. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn refresh_ttl_on_get_should_not_move_an_absolute_deadline() -> Result<(), String> {
+    let (mut c, clock) = cache_refreshing_ttl(None);
+    c.put_with_expiry_at(1, 10, UNIX_EPOCH + Duration::from_secs(15));
+    let mut seen = Vec::new();
+
+    for _ in 0..4 {
+        clock.advance(Duration::from_secs(5));
+        seen.push(c.get_cloned(&1));
+    }
+
+    match seen[..] {
+        [Some(10), Some(10), None, None] => Ok(()),
+        _ => Err(format!("Expected key 1 to expire at its deadline of 15s despite being used. Got {seen:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn ttl_should_not_slide_by_default() -> Result<(), String> {
+    let (mut c, clock) = cache_with_mock_clock(4);
+    c.put_with_ttl(1, 10, Duration::from_secs(10));
+
+    for _ in 0..3 {
+        clock.advance(Duration::from_secs(4));
+        c.get(&1);
+    }
+
+    match c.get_cloned(&1) {
+        None => Ok(()),
+        other => Err(format!("Expected key 1 to expire 10s after it was stored. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn purge_expired_should_follow_a_deadline_that_moved() -> Result<(), String> {
+    let (mut c, clock) = cache_with_mock_clock(4);
+    c.put_with_ttl(1, 10, Duration::from_secs(10));
+    c.put_with_ttl(1, 11, Duration::from_secs(30));
+    c.put_with_ttl(2, 20, Duration::from_secs(30));
+    c.put_with_ttl(2, 21, Duration::from_secs(10));
+
+    clock.advance(Duration::from_secs(10));
+    let early = (c.purge_expired(), c.peek(&1).copied(), c.peek(&2).copied());
+    clock.advance(Duration::from_secs(20));
+
+    match (early, c.purge_expired(), c.len()) {
+        ((1, Some(11), None), 1, 0) => Ok(()),
+        other => Err(format!("Expected each item to be purged at its latest deadline. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn purge_expired_should_remove_every_item_sharing_a_deadline() -> Result<(), String> {
+    let (mut c, clock) = cache_with_mock_clock(1_100);
+
+    for k in 0..1_000 {
+        c.put_with_ttl(k, k, Duration::from_secs(10));
+    }
+
+    for k in 1_000..1_010 {
+        c.put_with_ttl(k, k, Duration::from_secs(3_600));
+    }
+
+    clock.advance(Duration::from_secs(10));
+
+    match (c.purge_expired(), c.len(), c.deadlines.len()) {
+        (1_000, 10, 10) => Ok(()),
+        other => Err(format!("Expected the 1,000 items sharing a deadline to go at once. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn purge_expired_should_skip_deadlines_of_removed_items() -> Result<(), String> {
+    let (mut c, clock) = cache_with_mock_clock(4);
+    c.put_with_ttl(1, 10, Duration::from_secs(5));
+    c.remove(&1);
+    c.put(1, 11);
+    clock.advance(Duration::from_secs(5));
+
+    match (c.purge_expired(), c.peek(&1), c.deadlines.len()) {
+        (0, Some(11), 0) => Ok(()),
+        other => Err(format!("Expected the stale deadline to be dropped without purging. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn stale_deadlines_should_not_accumulate() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(4).unwrap())
+        .clock(clock.clone())
+        .time_to_idle(Duration::from_secs(10))
+        .build();
+
+    for k in 0..4 {
+        c.put(k, k);
+    }
+
+    for _ in 0..1_000 {
+        clock.advance(Duration::from_secs(1));
+        c.get(&0);
+    }
+
+    let stale = c.deadlines.len();
+    let purged = c.purge_expired();
+
+    match (stale <= 2 * 4 + 64, purged, c.peek(&0)) {
+        (true, 3, Some(0)) => Ok(()),
+        other => Err(format!("Expected at most 72 deadlines, {stale} found, and three idle items. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+fn cache_refreshing_early(capacity: usize) -> (LruCache<u32, u32>, MockClock) {
+    let clock = MockClock::new();
+    let c = LruCacheBuilder::new(NonZeroUsize::new(capacity).unwrap())
+        .clock(clock.clone())
+        .ttl(Duration::from_secs(100))
+        .early_expiration(Duration::from_secs(20), 1.0)
+        .seed(42)
+        .build();
+
+    (c, clock)
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn early_expiration_should_spread_refreshes_out_before_the_deadline() -> Result<(), String> {
+    let (mut c, clock) = cache_refreshing_early(100);
+
+    for k in 0..100 {
+        c.put(k, k);
+        c.set_load_cost(&k, Duration::from_secs(10));
+    }
+
+    let mut refreshed_at = BTreeMap::new();
+    clock.advance(Duration::from_secs(70));
+
+    for second in 70..100 {
+        for k in 0..100 {
+            if c.get(&k).is_none() && refreshed_at.insert(k, second).is_some() {
+                return Err(format!("Expected key {k} to be refreshed once. Refreshed again at {second}s"));
+            }
+        }
+
+        clock.advance(Duration::from_secs(1));
+    }
+
+    let earliest = refreshed_at.values().min().copied();
+    let times: BTreeSet<_> = refreshed_at.values().collect();
+
+    match (refreshed_at.len(), earliest, times.len() >= 5) {
+        (100, Some(80..), true) => Ok(()),
+        other => Err(format!("Expected 100 refreshes spread over the last 20s. Got {other:?} from {refreshed_at:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn early_expiration_should_ignore_items_without_a_load_cost() -> Result<(), String> {
+    let (mut c, clock) = cache_refreshing_early(4);
+    c.put(1, 10);
+    clock.advance(Duration::from_secs(99));
+
+    match (c.get(&1).copied(), c.get(&1).copied()) {
+        (Some(10), Some(10)) => Ok(()),
+        other => Err(format!("Expected a hit. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn set_load_cost_should_report_missing_keys() -> Result<(), String> {
+    let (mut c, clock) = cache_refreshing_early(4);
+    c.put(1, 10);
+    c.put_with_ttl(2, 20, Duration::from_secs(1));
+    clock.advance(Duration::from_secs(1));
+
+    match (
+        c.set_load_cost(&1, Duration::from_secs(1)),
+        c.set_load_cost(&2, Duration::from_secs(1)),
+        c.set_load_cost(&3, Duration::from_secs(1)),
+    ) {
+        (true, false, false) => Ok(()),
+        other => Err(format!("Expected only the live key to be found. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+fn cache_with_ttl_jitter(capacity: usize) -> (LruCache<u32, u32>, MockClock) {
+    let clock = MockClock::new();
+    let c = LruCacheBuilder::new(NonZeroUsize::new(capacity).unwrap())
+        .clock(clock.clone())
+        .ttl(Duration::from_secs(100))
+        .ttl_jitter(0.2)
+        .seed(42)
+        .build();
+
+    (c, clock)
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn ttl_jitter_should_spread_expirations_across_the_window() -> Result<(), String> {
+    let (mut c, clock) = cache_with_ttl_jitter(1_000);
+
+    for k in 0..1_000 {
+        c.put(k, k);
+    }
+
+    let mut expired_at = BTreeMap::new();
+    clock.advance(Duration::from_secs(79));
+
+    for second in 79..=121 {
+        for k in 0..1_000 {
+            if !expired_at.contains_key(&k) && c.peek(&k).is_none() {
+                expired_at.insert(k, second);
+            }
+        }
+
+        clock.advance(Duration::from_secs(1));
+    }
+
+    let per_second = expired_at.values().fold(BTreeMap::new(), |mut counts, second| {
+        *counts.entry(*second).or_insert(0) += 1;
+        counts
+    });
+    let busiest = per_second.values().max().copied();
+
+    match (expired_at.len(), per_second.keys().next(), per_second.keys().last(), busiest) {
+        (1_000, Some(80..=82), Some(118..=120), Some(..=50)) => Ok(()),
+        other => Err(format!("Expected expirations spread between 80s and 120s. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn ttl_jitter_should_not_move_absolute_deadlines() -> Result<(), String> {
+    let (mut c, clock) = cache_with_ttl_jitter(100);
+    let deadline = UNIX_EPOCH + Duration::from_secs(3_600);
+
+    for k in 0..100 {
+        c.put_with_expiry_at(k, k, deadline);
+    }
+
+    clock.advance(Duration::from_secs(3_590));
+    let before = (0..100).filter(|k| c.contains_key(k)).count();
+    clock.advance(Duration::from_secs(10));
+    let after = (0..100).filter(|k| c.contains_key(k)).count();
+
+    match (before, after) {
+        (100, 0) => Ok(()),
+        other => Err(format!("Expected every item to expire at its deadline. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+/// Negative values stand for errors, which live for 5s, zero is never stale, and anything else lives for 300s
+fn cache_with_ttl_fn(capacity: usize) -> (LruCache<u32, i32>, MockClock) {
+    let clock = MockClock::new();
+    let c = LruCacheBuilder::new(NonZeroUsize::new(capacity).unwrap())
+        .clock(clock.clone())
+        .ttl(Duration::from_secs(60))
+        .ttl_fn(|_, value: &i32| match value {
+            ..0 => Some(Duration::from_secs(5)),
+            0 => None,
+            _ => Some(Duration::from_secs(300)),
+        })
+        .build();
+
+    (c, clock)
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn ttl_fn_should_choose_each_items_ttl_from_its_value() -> Result<(), String> {
+    let (mut c, clock) = cache_with_ttl_fn(4);
+
+    for (k, v) in [(1, -1), (2, 0), (3, 1)] {
+        c.put(k, v);
+    }
+
+    clock.advance(Duration::from_secs(5));
+    let after_5s = (c.peek(&1).copied(), c.peek(&2).copied(), c.peek(&3).copied());
+    clock.advance(Duration::from_secs(295));
+    let after_300s = (c.peek(&2).copied(), c.peek(&3).copied());
+
+    match (after_5s, after_300s) {
+        ((None, Some(0), Some(1)), (Some(0), None)) => Ok(()),
+        other => Err(format!("Expected errors to expire after 5s and successes after 300s. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn put_with_ttl_should_override_the_ttl_fn() -> Result<(), String> {
+    let (mut c, clock) = cache_with_ttl_fn(4);
+    c.put_with_ttl(1, -1, Duration::from_secs(60));
+    c.put_with_ttl(2, 0, Duration::from_secs(60));
+    clock.advance(Duration::from_secs(59));
+    let before = (c.peek(&1).copied(), c.peek(&2).copied());
+    clock.advance(Duration::from_secs(1));
+
+    match (before, c.peek(&1), c.peek(&2)) {
+        ((Some(-1), Some(0)), None, None) => Ok(()),
+        other => Err(format!("Expected both items to live for exactly 60s. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn ttl_fn_should_apply_when_a_value_is_replaced() -> Result<(), String> {
+    let (mut c, clock) = cache_with_ttl_fn(4);
+    c.put(1, 1);
+    c.push(1, -1);
+    clock.advance(Duration::from_secs(5));
+
+    match c.peek(&1) {
+        None => Ok(()),
+        other => Err(format!("Expected the replacement error to expire after 5s. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+fn lfu_cache(capacity: usize) -> LruCache<u32, u32> {
+    LruCacheBuilder::new(NonZeroUsize::new(capacity).unwrap())
+        .eviction_mode(EvictionMode::Lfu)
+        .build()
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn lfu_should_evict_the_least_frequently_used_entry() -> Result<(), String> {
+    let mut c = lfu_cache(3);
+
+    for k in 0..3 {
+        c.put(k, k);
+    }
+
+    // Key 0 is the least recently used but the most frequently used
+    for k in [1, 2, 0, 0, 0, 2] {
+        c.get(&k);
+    }
+
+    c.put(3, 3);
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (c.contains_key(&0), c.contains_key(&1), c.contains_key(&2), c.len()) {
+        (true, false, true, 3) => Ok(()),
+        other => Err(format!("Expected key 1 to be evicted. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn lfu_should_break_ties_by_recency() -> Result<(), String> {
+    let mut c = lfu_cache(3);
+
+    for k in 0..3 {
+        c.put(k, k);
+    }
+
+    for k in [2, 0, 1] {
+        c.get(&k);
+    }
+
+    match (c.pop_lru(), c.pop_lru(), c.pop_lru(), c.pop_lru()) {
+        (Some(2), Some(0), Some(1), None) => Ok(()),
+        other => Err(format!("Expected the entries in order of their last use. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn lfu_should_keep_hot_keys_through_a_burst_of_one_off_keys() -> Result<(), String> {
+    let mut c = lfu_cache(10);
+
+    for k in 0..5 {
+        c.put(k, k);
+
+        for _ in 0..10 {
+            c.get(&k);
+        }
+    }
+
+    for k in 100..1_100 {
+        c.put(k, k);
+    }
+
+    let hot = (0..5).filter(|k| c.contains_key(k)).count();
+
+    match (hot, c.len()) {
+        (5, 10) => Ok(()),
+        other => Err(format!("Expected all 5 hot keys to survive. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn lfu_demote_should_make_the_entry_the_next_victim() -> Result<(), String> {
+    let mut c = lfu_cache(3);
+
+    for k in 0..3 {
+        c.put(k, k);
+        c.get(&k);
+    }
+
+    c.get(&1);
+    c.demote(&1);
+    c.put(3, 3);
+
+    match (c.contains_key(&1), c.len()) {
+        (false, 3) => Ok(()),
+        other => Err(format!("Expected demoted key 1 to be evicted. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn lfu_should_not_accumulate_out_of_date_uses() -> Result<(), String> {
+    let mut c = lfu_cache(4);
+
+    for k in 0..4 {
+        c.put(k, k);
+    }
+
+    let fresh = c.memory_usage();
+
+    for round in 0..100_000 {
+        c.get(&(round % 4));
+    }
+
+    // Out of date uses are dropped once they outnumber the entries by 64, so the queue holds at most 72 records
+    let used = c.memory_usage();
+
+    match (used <= fresh + 2 * 72 * 24, c.pop_lru(), c.len()) {
+        (true, Some(_), 3) => Ok(()),
+        other => Err(format!("Expected at most {} bytes, {used} used. Got {other:?}", fresh + 2 * 72 * 24)),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+fn segmented_cache(capacity: usize, protected_percent: u8) -> LruCache<u32, u32> {
+    LruCacheBuilder::new(NonZeroUsize::new(capacity).unwrap())
+        .eviction_mode(EvictionMode::Segmented { protected_percent })
+        .build()
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn segmented_lru_should_keep_hot_keys_through_a_scan() -> Result<(), String> {
+    let mut survivors = Vec::new();
+
+    for mode in [EvictionMode::SEGMENTED, EvictionMode::Exact] {
+        let mut c = LruCacheBuilder::new(NonZeroUsize::new(100).unwrap()).eviction_mode(mode).build();
+
+        for k in 0..50 {
+            c.put(k, k);
+            c.get(&k);
+        }
+
+        for k in 1_000..100_000 {
+            c.put(k, k);
+        }
+
+        c.debug_validate().map_err(|e| e.to_string())?;
+        survivors.push((0..50).filter(|k| c.contains_key(k)).count());
+    }
+
+    match survivors[..] {
+        [50, 0] => Ok(()),
+        _ => Err(format!("Expected every hot key to survive under SLRU and none under LRU. Got {survivors:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn segmented_lru_should_return_protected_overflow_to_probation() -> Result<(), String> {
+    // Two of the five entries may be protected
+    let mut c = segmented_cache(5, 40);
+
+    for k in 1..=5 {
+        c.put(k, k);
+    }
+
+    for k in [1, 2, 3] {
+        c.get(&k);
+    }
+
+    c.debug_validate().map_err(|e| e.to_string())?;
+    let order: Vec<_> = core::iter::from_fn(|| c.pop_lru()).collect();
+
+    match order[..] {
+        [4, 5, 1, 2, 3] => Ok(()),
+        _ => Err(format!("Expected key 1 to be the most recent probationary entry. Got {order:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn segmented_lru_should_evict_protected_entries_once_probation_is_empty() -> Result<(), String> {
+    let mut c = segmented_cache(3, 100);
+
+    for k in 0..3 {
+        c.put(k, k);
+        c.get(&k);
+    }
+
+    c.put(3, 3);
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (c.contains_key(&0), c.contains_key(&3), c.len()) {
+        (false, true, 3) => Ok(()),
+        other => Err(format!("Expected the least recently used protected key to be evicted. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+/// Applies a random mix of operations to a cache of `u32` keys below 32, validating it after each one
+fn exercise_mixed_operations(c: &mut LruCache<u32, u32>) {
+    let mut seed = 0x9e37_79b9_u32;
+
+    for step in 0..20_000 {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        let key = seed % 32;
+
+        match seed % 7 {
+            0 | 1 => drop(c.put(key, step)),
+            2 | 3 => drop(c.get(&key)),
+            4 => drop(c.remove(&key)),
+            5 => drop(c.demote(&key)),
+            _ => drop(c.pop_lru()),
+        }
+
+        c.debug_assert_valid();
+    }
+
+    c.clear();
+    c.debug_assert_valid();
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn segmented_lru_should_stay_consistent_under_mixed_operations() -> Result<(), String> {
+    let mut c = segmented_cache(16, 50);
+    exercise_mixed_operations(&mut c);
+
+    match c.len() {
+        0 => Ok(()),
+        other => Err(format!("Expected an empty cache. Got {other} entries")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+fn two_queue_cache(capacity: usize) -> LruCache<u32, u32> {
+    LruCacheBuilder::new(NonZeroUsize::new(capacity).unwrap())
+        .eviction_mode(EvictionMode::TWO_QUEUE)
+        .build()
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn two_queue_should_evict_new_entries_in_insertion_order_however_often_they_are_used() -> Result<(), String> {
+    let mut c = two_queue_cache(4);
+
+    for k in 1..=4 {
+        c.put(k, k);
+    }
+
+    for _ in 0..10 {
+        c.get(&1);
+    }
+
+    c.put(5, 5);
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (c.contains_key(&1), c.contains_key(&2), c.recency_rank(&5), c.recency_rank(&1)) {
+        (false, true, Some(0), None) => Ok(()),
+        other => Err(format!("Expected the oldest new entry to be evicted despite its uses. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn two_queue_should_admit_keys_that_return_while_remembered_to_the_main_queue() -> Result<(), String> {
+    // One new entry fits in its share, and the ghost queue remembers two evicted keys
+    let mut c = two_queue_cache(4);
+
+    for k in 1..=5 {
+        c.put(k, k);
+    }
+
+    // Key 1 was evicted and is remembered, so it returns to the main queue ahead of every new entry
+    c.put(1, 10);
+    c.put(6, 6);
+    c.debug_validate().map_err(|e| e.to_string())?;
+    let ranks = (c.recency_rank(&1), c.recency_rank(&6));
+
+    for k in 100..110 {
+        c.put(k, k);
+    }
+
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (ranks, c.get(&1).copied(), c.contains_key(&2), c.len()) {
+        ((Some(0), Some(1)), Some(10), false, 4) => Ok(()),
+        other => Err(format!("Expected key 1 to outlast a run of new keys in the main queue. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn two_queue_should_only_remember_as_many_evicted_keys_as_the_ghost_queue_holds() -> Result<(), String> {
+    let mut c = two_queue_cache(4);
+
+    // Keys 1, 2 and 3 are evicted in turn, leaving only 2 and 3 remembered
+    for k in 1..=7 {
+        c.put(k, k);
+    }
+
+    c.put(3, 3);
+    c.put(1, 1);
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (c.recency_rank(&3), c.recency_rank(&1)) {
+        (Some(0), Some(1)) => Ok(()),
+        other => Err(format!("Expected key 3 in the main queue and key 1 among the new entries. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn two_queue_should_keep_hot_keys_through_a_scan() -> Result<(), String> {
+    let mut survivors = Vec::new();
+
+    for mode in [EvictionMode::TWO_QUEUE, EvictionMode::Exact] {
+        let mut c = LruCacheBuilder::new(NonZeroUsize::new(100).unwrap()).eviction_mode(mode).build();
+
+        // The hot keys are evicted once and remembered, so they return to the main queue
+        for k in (0..50).chain(1_000..1_100).chain(0..50) {
+            c.put(k, k);
+        }
+
+        for k in 2_000..100_000 {
+            c.put(k, k);
+        }
+
+        c.debug_validate().map_err(|e| e.to_string())?;
+        survivors.push((0..50).filter(|k| c.contains_key(k)).count());
+    }
+
+    match survivors[..] {
+        [50, 0] => Ok(()),
+        _ => Err(format!("Expected every hot key to survive under 2Q and none under LRU. Got {survivors:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn two_queue_should_count_the_ghost_queue_in_memory_usage() -> Result<(), String> {
+    let mut c = two_queue_cache(64);
+
+    for k in 0..64 {
+        c.put(k, k);
+    }
+
+    let full = c.memory_usage();
+
+    for k in 64..1_000 {
+        c.put(k, k);
+    }
+
+    let remembering = c.memory_usage();
+
+    match remembering >= full + 32 * mem::size_of::<u64>() {
+        true => Ok(()),
+        false => Err(format!("Expected the 32 remembered hashes to add to {full} bytes. Got {remembering} bytes")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn two_queue_should_stay_consistent_under_mixed_operations() -> Result<(), String> {
+    let mut c = two_queue_cache(16);
+    exercise_mixed_operations(&mut c);
+
+    match c.len() {
+        0 => Ok(()),
+        other => Err(format!("Expected an empty cache. Got {other} entries")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn frequency_sketch_should_saturate_at_fifteen() -> Result<(), String> {
+    let mut sketch = frequency_sketch::FrequencySketch::new(64);
+
+    for _ in 0..20 {
+        sketch.increment(0x1234_5678);
+    }
+
+    sketch.increment(0x8765_4321);
+
+    match (sketch.frequency(0x1234_5678), sketch.frequency(0x8765_4321), sketch.frequency(42)) {
+        (15, 1, 0) => Ok(()),
+        other => Err(format!("Expected estimates of 15, 1 and 0. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn frequency_sketch_should_halve_every_count_once_the_sample_is_full() -> Result<(), String> {
+    let mut sketch = frequency_sketch::FrequencySketch::new(1_024);
+    let hot = 0xdead_beef_u64;
+
+    for _ in 0..8 {
+        sketch.increment(hot);
+    }
+
+    let before = sketch.frequency(hot);
+    let mut filler = 0_u64;
+
+    while sketch.stats().resets == 0 {
+        filler += 1;
+        sketch.increment(filler.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    }
+
+    let stats = sketch.stats();
+
+    // Collisions with the filler keys may leave the hot key one above half its count
+    match (before, sketch.frequency(hot), stats.additions, stats.sample_size) {
+        (8, 4..=5, 5_120, 10_240) => Ok(()),
+        other => Err(format!("Expected the count of 8 and the additions to be halved. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+fn tiny_lfu_cache(capacity: usize, window_percent: u8) -> LruCache<u32, u32> {
+    LruCacheBuilder::new(NonZeroUsize::new(capacity).unwrap())
+        .eviction_mode(EvictionMode::TinyLfu { window_percent })
+        .build()
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn tiny_lfu_should_only_admit_candidates_used_more_often_than_the_victim() -> Result<(), String> {
+    // One window entry in front of nine in the main region, oldest first from key 0
+    let mut c = tiny_lfu_cache(10, 10);
+
+    for k in 0..10 {
+        c.put(k, k);
+    }
+
+    // Key 9 leaves the window used as often as key 0, the main region's victim, so it is the one evicted
+    c.put(10, 10);
+    let tie = (c.contains_key(&9), c.contains_key(&0));
+
+    // Once used again, key 10 wins its comparison with key 0 and displaces it
+    c.get(&10);
+    let frequencies = (c.estimated_frequency(&10), c.estimated_frequency(&0));
+    c.put(11, 11);
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (tie, frequencies, c.contains_key(&10), c.contains_key(&0), c.len()) {
+        ((false, true), (Some(2), Some(1)), true, false, 10) => Ok(()),
+        other => Err(format!("Expected ties to keep the victim and a higher count to displace it. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn tiny_lfu_should_keep_hot_keys_through_a_scan() -> Result<(), String> {
+    let mut survivors = Vec::new();
+
+    for mode in [EvictionMode::TINY_LFU, EvictionMode::Exact] {
+        let mut c = LruCacheBuilder::new(NonZeroUsize::new(100).unwrap()).eviction_mode(mode).build();
+
+        for _ in 0..3 {
+            for k in 0..50 {
+                if c.get(&k).is_none() {
+                    c.put(k, k);
+                }
+            }
+        }
+
+        for k in 1_000..100_000 {
+            c.put(k, k);
+        }
+
+        c.debug_validate().map_err(|e| e.to_string())?;
+        survivors.push((0..50).filter(|k| c.contains_key(k)).count());
+    }
+
+    // The hot key read last is still in the window, and ties with the main region's victim when the scan arrives
+    match survivors[..] {
+        [49, 0] => Ok(()),
+        _ => Err(format!("Expected the hot keys of the main region to survive under W-TinyLFU. Got {survivors:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn tiny_lfu_should_let_new_favourites_displace_old_ones_as_counts_age() -> Result<(), String> {
+    let mut c: LruCache<u32, u32, FnvBuildHasher> = LruCacheBuilder::new(NonZeroUsize::new(100).unwrap())
+        .eviction_mode(EvictionMode::TinyLfu { window_percent: 1 })
+        .hasher(FnvBuildHasher::default())
+        .build();
+
+    // Reading through each set of keys in turn, twenty times over, saturates their counts
+    for favourites in [0..100, 1_000..1_100] {
+        for _ in 0..20 {
+            for k in favourites.clone() {
+                if c.get(&k).is_none() {
+                    c.put(k, k);
+                }
+            }
+        }
+    }
+
+    c.debug_validate().map_err(|e| e.to_string())?;
+    let resets = c.sketch_stats().map(|stats| stats.resets);
+    let new_favourites = (1_000..1_100).filter(|k| c.contains_key(k)).count();
+
+    match (resets, new_favourites) {
+        (Some(1..), 90..) => Ok(()),
+        other => Err(format!("Expected halving to let most of the new favourites in. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn tiny_lfu_should_stay_consistent_under_mixed_operations() -> Result<(), String> {
+    let mut c = tiny_lfu_cache(16, 25);
+    exercise_mixed_operations(&mut c);
+
+    match c.len() {
+        0 => Ok(()),
+        other => Err(format!("Expected an empty cache. Got {other} entries")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn sketch_stats_should_only_be_reported_under_tiny_lfu() -> Result<(), String> {
+    let mut plain = tiny_lfu_cache(1_000, 1);
+    let exact: LruCache<u32, u32> = LruCache::new(NonZeroUsize::new(1_000).unwrap());
+    plain.put(1, 1);
+    plain.get(&1);
+
+    match (plain.sketch_stats(), exact.sketch_stats(), exact.estimated_frequency(&1)) {
+        (Some(SketchStats { counters: 16_384, additions: 2, sample_size: 10_000, resets: 0 }), None, None) => Ok(()),
+        other => Err(format!("Expected sketch counters only under W-TinyLFU. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+fn fifo_cache(capacity: usize, eviction_mode: EvictionMode) -> LruCache<u32, u32> {
+    LruCacheBuilder::new(NonZeroUsize::new(capacity).unwrap())
+        .eviction_mode(eviction_mode)
+        .build()
+}
+
+/// Fills a cache of three with keys 1 to 3, reads key 1, replaces key 2 and then inserts keys 4 and 5, returning the
+/// keys evicted by each insertion
+fn replay_reads_and_replacements(mut c: LruCache<u32, u32>) -> Vec<u32> {
+    for k in 1..=3 {
+        c.put(k, k);
+    }
+
+    c.get(&1);
+    c.put(2, 20);
+
+    let victims = [4, 5].into_iter().filter_map(|k| c.push(k, k)).map(|(key, _)| key).collect();
+
+    c.debug_assert_valid();
+    victims
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn fifo_should_evict_in_insertion_order_where_lru_evicts_in_recency_order() -> Result<(), String> {
+    let lru = replay_reads_and_replacements(fifo_cache(3, EvictionMode::Exact));
+    let fifo = replay_reads_and_replacements(fifo_cache(3, EvictionMode::FIFO));
+    let refreshing = replay_reads_and_replacements(fifo_cache(3, EvictionMode::Fifo { refresh_on_put: true }));
+
+    match (&lru[..], &fifo[..], &refreshing[..]) {
+        ([3, 1], [1, 2], [1, 3]) => Ok(()),
+        other => Err(format!("Expected victims [3, 1] under LRU and [1, 2] or [1, 3] under FIFO. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn fifo_should_not_reorder_entries_on_any_kind_of_read() -> Result<(), String> {
+    let mut c: LruCache<u32, u32> = LruCacheBuilder::new(NonZeroUsize::new(4).unwrap())
+        .eviction_mode(EvictionMode::FIFO)
+        .read_buffer(NonZeroUsize::new(2).unwrap())
+        .build();
+
+    for k in 1..=4 {
+        c.put(k, k);
+    }
+
+    c.get(&1);
+    c.get_deferred(&2);
+    let promoted = c.promote(&3);
+    c.put(1, 10);
+
+    #[cfg(feature = "compat")]
+    if let Some(value) = c.get_mut_ref(&2) {
+        *value = 20;
+    }
+
+    c.debug_validate().map_err(|e| e.to_string())?;
+    let order: Vec<_> = core::iter::from_fn(|| c.pop_lru()).collect();
+
+    match (promoted, &order[..]) {
+        (true, [10, 2 | 20, 3, 4]) => Ok(()),
+        other => Err(format!("Expected reads to leave the insertion order alone. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+fn random_cache(capacity: usize, seed: u64) -> LruCache<u32, u32> {
+    LruCacheBuilder::new(NonZeroUsize::new(capacity).unwrap())
+        .eviction_mode(EvictionMode::Random)
+        .seed(seed)
+        .build()
+}
+
+/// Fills a cache of eight built with `seed`, reads every key, and returns the keys evicted by inserting eight more
+fn random_victims(seed: u64) -> Vec<u32> {
+    let mut c = random_cache(8, seed);
+
+    for k in 0..8 {
+        c.put(k, k);
+    }
+
+    for k in 0..8 {
+        c.get(&k);
+    }
+
+    let victims = (8..16).filter_map(|k| c.push(k, k)).map(|(key, _)| key).collect();
+    c.debug_assert_valid();
+    victims
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn random_eviction_should_repeat_its_choices_for_the_same_seed() -> Result<(), String> {
+    match (random_victims(7), random_victims(7), random_victims(8)) {
+        (first, again, other) if first.len() == 8 && first == again && first != other => Ok(()),
+        other => Err(format!("Expected the same victims for the same seed only. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn random_eviction_should_choose_every_entry_about_equally_often() -> Result<(), String> {
+    let mut evictions = [0; 8];
+
+    for seed in 0..4_000 {
+        let mut c = random_cache(8, seed);
+
+        for k in 0..8 {
+            c.put(k, k);
+        }
+
+        // Removing and restoring a key leaves it in a different position from the order of insertion
+        c.remove(&3);
+        c.put(3, 3);
+
+        if let Some((key, _)) = c.push(8, 8) {
+            evictions[key as usize] += 1;
+        }
+    }
+
+    match evictions.iter().all(|&count| (400..600).contains(&count)) {
+        true => Ok(()),
+        false => Err(format!("Expected each of 8 keys to be evicted about 500 times in 4000. Got {evictions:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn random_eviction_should_not_reorder_entries_on_reads() -> Result<(), String> {
+    let mut c = random_cache(4, 1);
+
+    for k in 1..=4 {
+        c.put(k, k);
+    }
+
+    c.get(&1);
+    c.promote(&2);
+    let ranks: Vec<_> = (1..=4).map(|k| c.recency_rank(&k)).collect();
+
+    match &ranks[..] {
+        [Some(3), Some(2), Some(1), Some(0)] => Ok(()),
+        other => Err(format!("Expected the order of insertion. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn random_eviction_should_pop_every_entry_after_many_removals() -> Result<(), String> {
+    let mut c = random_cache(1_000, 3);
+
+    for k in 0..1_000 {
+        c.put(k, k);
+    }
+
+    // Leaves most storage positions vacant, so that most draws miss
+    for k in 0..990 {
+        c.remove(&k);
+    }
+
+    let mut popped: Vec<_> = core::iter::from_fn(|| c.pop_lru()).collect();
+    popped.sort_unstable();
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (popped == (990..1_000).collect::<Vec<_>>(), c.is_empty()) {
+        (true, true) => Ok(()),
+        other => Err(format!("Expected keys 990 to 999 to be popped, emptying the cache. Got {popped:?}, {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+/// Loops ten times over twenty more keys than a cache of a hundred holds, reading each key and inserting it on a miss,
+/// and returns the hit ratio of the last nine loops
+fn looping_scan_hit_ratio(eviction_mode: EvictionMode) -> f64 {
+    let mut c: LruCache<u32, u32> = LruCacheBuilder::new(NonZeroUsize::new(100).unwrap())
+        .eviction_mode(eviction_mode)
+        .build();
+    let mut hits = 0;
+
+    for pass in 0..10 {
+        for k in 0..120 {
+            match c.get(&k) {
+                Some(_) => hits += usize::from(pass > 0),
+                None => drop(c.put(k, k)),
+            }
+        }
+    }
+
+    c.debug_assert_valid();
+    hits as f64 / (9 * 120) as f64
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn mru_should_keep_part_of_a_looping_scan_that_lru_keeps_none_of() -> Result<(), String> {
+    match (looping_scan_hit_ratio(EvictionMode::Exact), looping_scan_hit_ratio(EvictionMode::Mru)) {
+        (lru, mru) if lru == 0.0 && mru > 0.5 => Ok(()),
+        other => Err(format!("Expected no hits under LRU and over half under MRU. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn mru_should_leave_pop_lru_and_pop_mru_their_literal_meanings() -> Result<(), String> {
+    let mut c: LruCache<u32, u32> = LruCacheBuilder::new(NonZeroUsize::new(4).unwrap())
+        .eviction_mode(EvictionMode::Mru)
+        .build();
+
+    for k in 1..=4 {
+        c.put(k, k);
+    }
+
+    c.get(&1);
+    let evicted = c.push(5, 5).map(|(key, _)| key);
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (evicted, c.pop_lru(), c.pop_mru()) {
+        (Some(1), Some(2), Some(5)) => Ok(()),
+        other => Err(format!("Expected key 1 evicted, then key 2 and key 5 popped. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_evict_past_a_pinned_entry_in_recency_order() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(4).unwrap());
+
+    for k in 1..=4 {
+        c.put(k, k);
+    }
+
+    let pinned = c.pin(&1);
+    let victims: Vec<_> = (5..=7).filter_map(|k| c.push(k, k)).map(|(key, _)| key).collect();
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (pinned, &victims[..], c.peek(&1), c.len(), c.recency_rank(&1)) {
+        (true, [2, 3, 4], Some(1), 4, Some(3)) => Ok(()),
+        other => Err(format!("Expected keys 2, 3 and 4 evicted around pinned key 1. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_evict_a_pinned_entry_once_it_is_unpinned() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(2).unwrap());
+    c.put(1, 1);
+    c.put(2, 2);
+    c.pin(&1);
+    let while_pinned = c.push(3, 3).map(|(key, _)| key);
+    let unpinned = c.unpin(&1);
+    let after = c.push(4, 4).map(|(key, _)| key);
+
+    match (while_pinned, unpinned, after, c.is_pinned(&1), c.unpin(&9)) {
+        (Some(2), true, Some(1), false, false) => Ok(()),
+        other => Err(format!("Expected key 2 evicted while key 1 was pinned, then key 1. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_reject_new_keys_when_every_entry_is_pinned() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(2).unwrap());
+    c.put(1, 1);
+    c.put(2, 2);
+    c.pin(&1);
+    c.pin(&2);
+
+    let tried = c.try_put(3, 3);
+    let put = c.put(4, 4);
+    let pushed = c.push(5, 5);
+    let replaced = c.try_put(1, 10);
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (tried, put, pushed, replaced, c.len(), c.contains_key(&3), c.is_pinned(&1)) {
+        (Err(CacheError::AllPinned { capacity: 2 }), None, None, Ok(Some(1)), 2, false, true) => Ok(()),
+        other => Err(format!("Expected new keys to be turned away while replacements succeed. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn every_eviction_mode_should_keep_pinned_entries() -> Result<(), String> {
+    let modes = [
+        EvictionMode::Exact,
+        EvictionMode::Sampled {
+            sample_size: NonZeroUsize::new(5).unwrap(),
+        },
+        EvictionMode::Lfu,
+        EvictionMode::SEGMENTED,
+        EvictionMode::TWO_QUEUE,
+        EvictionMode::TINY_LFU,
+        EvictionMode::FIFO,
+        EvictionMode::Random,
+        EvictionMode::Mru,
+        EvictionMode::CostAware,
+        EvictionMode::S3_FIFO,
+    ];
+
+    for mode in modes {
+        let mut c: LruCache<u32, u32> = LruCacheBuilder::new(NonZeroUsize::new(16).unwrap())
+            .eviction_mode(mode)
+            .build();
+
+        for k in 0..16 {
+            c.put(k, k);
+        }
+
+        for k in (0..16).step_by(3) {
+            c.pin(&k);
+        }
+
+        for k in 16..1_000 {
+            c.put(k, k);
+            c.get(&(k % 32));
+        }
+
+        c.debug_validate().map_err(|e| e.to_string())?;
+        let kept = (0..16).step_by(3).filter(|k| c.contains_key(k)).count();
+
+        if kept != 6 || c.len() != 16 {
+            return Err(format!("Expected all 6 pinned entries kept under {mode:?}. Got {kept} of {}", c.len()));
+        }
+    }
+
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_evict_lower_tiers_first_in_recency_order() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(6).unwrap());
+    let tiers = [Priority::Critical, Priority::BestEffort, Priority::Normal, Priority::BestEffort, Priority::Normal];
+
+    for (k, priority) in (1..).zip(tiers) {
+        c.put_with_priority(k, k, priority);
+    }
+
+    c.put_with_priority(6, 6, Priority::Critical);
+    let victims: Vec<_> = (7..=12).filter_map(|k| c.push(k, k)).map(|(key, _)| key).collect();
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (&victims[..], c.contains_key(&1), c.contains_key(&6), c.priority(&9)) {
+        ([2, 4, 3, 5, 7, 8], true, true, Some(Priority::Normal)) => Ok(()),
+        other => Err(format!("Expected best-effort, then normal entries evicted, oldest first. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_evict_critical_entries_once_no_others_are_left() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(2).unwrap());
+    c.put_with_priority(1, 1, Priority::Critical);
+    c.put_with_priority(2, 2, Priority::Critical);
+    let first = c.push(3, 3).map(|(key, _)| key);
+    let second = c.push(4, 4).map(|(key, _)| key);
+
+    match (first, second, c.contains_key(&2)) {
+        (Some(1), Some(3), true) => Ok(()),
+        other => Err(format!("Expected key 1 evicted, then the normal key 3 before key 2. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn set_priority_should_move_an_entry_between_tiers_without_changing_its_recency() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(3).unwrap());
+
+    for k in 1..=3 {
+        c.put(k, k);
+    }
+
+    let demoted = c.set_priority(&3, Priority::BestEffort);
+    let promoted = c.set_priority(&1, Priority::Critical);
+    let ranks = (c.recency_rank(&1), c.recency_rank(&3));
+    let first = c.push(4, 4).map(|(key, _)| key);
+    let second = c.push(5, 5).map(|(key, _)| key);
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (demoted, promoted, ranks, first, second, c.priority(&1), c.set_priority(&9, Priority::Normal)) {
+        (true, true, (Some(2), Some(0)), Some(3), Some(2), Some(Priority::Critical), false) => Ok(()),
+        other => Err(format!("Expected key 3 evicted first, then key 2, with ranks unchanged. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn pop_lru_should_draw_from_every_tier_alike() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(3).unwrap());
+    c.put_with_priority(1, 1, Priority::Critical);
+    c.put_with_priority(2, 2, Priority::BestEffort);
+    c.put(3, 3);
+
+    match (c.pop_lru(), c.pop_lru(), c.pop_lru()) {
+        (Some(1), Some(2), Some(3)) => Ok(()),
+        other => Err(format!("Expected items popped in recency order regardless of priority. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn every_eviction_mode_should_evict_best_effort_entries_first() -> Result<(), String> {
+    let modes = [
+        EvictionMode::Exact,
+        EvictionMode::Sampled {
+            sample_size: NonZeroUsize::new(5).unwrap(),
+        },
+        EvictionMode::Lfu,
+        EvictionMode::SEGMENTED,
+        EvictionMode::TWO_QUEUE,
+        EvictionMode::TINY_LFU,
+        EvictionMode::FIFO,
+        EvictionMode::Random,
+        EvictionMode::Mru,
+        EvictionMode::CostAware,
+        EvictionMode::S3_FIFO,
+    ];
+
+    for mode in modes {
+        let mut c: LruCache<u32, u32> = LruCacheBuilder::new(NonZeroUsize::new(16).unwrap())
+            .eviction_mode(mode)
+            .build();
+
+        for k in 0..16 {
+            let priority = if k % 4 == 0 { Priority::Critical } else { Priority::Normal };
+            c.put_with_priority(k, k, priority);
+        }
+
+        // The first insertion evicts a normal entry, and every later one the best-effort entry before it
+        for k in 16..1_000 {
+            c.put_with_priority(k, k, Priority::BestEffort);
+            c.get(&(k % 32));
+        }
+
+        c.debug_validate().map_err(|e| e.to_string())?;
+        let critical = (0..16).step_by(4).filter(|k| c.contains_key(k)).count();
+        let normal = (0..16).filter(|k| k % 4 != 0 && c.contains_key(k)).count();
+
+        if (critical, normal, c.len()) != (4, 11, 16) {
+            return Err(format!("Expected 4 critical and 11 normal entries under {mode:?}. Got {critical}, {normal}"));
+        }
+    }
+
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+/// Keys and causes passed to a removal callback, in the order it was called
+type RemovalLog = Arc<Mutex<Vec<(u32, RemovalCause)>>>;
+
+/// Returns a callback for [`LruCacheBuilder::on_removal`] and the log of what it is called with
+fn removal_log() -> (RemovalLog, impl FnMut(&u32, &u32, RemovalCause) + Send + Sync + 'static) {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let writer = Arc::clone(&log);
+    (log, move |key: &u32, _: &u32, cause| writer.lock().unwrap().push((*key, cause)))
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn on_removal_should_report_every_removal_with_its_cause() -> Result<(), String> {
+    use RemovalCause::*;
+
+    let (log, on_removal) = removal_log();
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(3).unwrap()).on_removal(on_removal).build();
+
+    for k in 1..=4 {
+        c.put(k, k);
+    }
+
+    let pushed = c.push(5, 5).map(|(key, _)| key);
+    c.put(3, 30);
+    c.remove(&4);
+    c.pop_lru();
+    c.put(6, 6);
+    c.put(7, 7);
+    c.pin(&6);
+    let shrunk = c.evict_to(1);
+    c.clear();
+    c.debug_validate().map_err(|e| e.to_string())?;
+    let reported = log.lock().unwrap().clone();
+    let expected = [(1, Capacity), (2, Capacity), (3, Replaced), (4, Explicit), (5, Explicit)];
+    let expected = [&expected[..], &[(3, Capacity), (7, Capacity), (6, Cleared)]].concat();
+
+    match (pushed, shrunk, c.len()) {
+        (Some(2), 2, 0) if reported == expected => Ok(()),
+        other => Err(format!("Expected {expected:?}. Got {reported:?} with {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn on_removal_should_report_expired_items_however_they_are_found() -> Result<(), String> {
+    let (log, on_removal) = removal_log();
+    let clock = MockClock::new();
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(4).unwrap())
+        .clock(clock.clone())
+        .on_removal(on_removal)
+        .build();
+
+    for k in 1..=4 {
+        c.put_with_ttl(k, k, Duration::from_secs(k.into()));
+    }
+
+    clock.advance(Duration::from_secs(4));
+    c.get(&1);
+    c.put(2, 20);
+    c.remove(&3);
+    c.purge_expired();
+    let reported = log.lock().unwrap().clone();
+    let expired = reported.iter().filter(|(_, cause)| *cause == RemovalCause::Expired);
+    let keys: Vec<_> = expired.map(|&(key, _)| key).collect();
+
+    match (&keys[..], reported.len()) {
+        ([1, 2, 3, 4], 4) => Ok(()),
+        _ => Err(format!("Expected keys 1 to 4 reported as expired. Got {reported:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn on_removal_should_let_write_back_pick_out_capacity_evictions() -> Result<(), String> {
+    let written = Arc::new(Mutex::new(Vec::new()));
+    let writer = Arc::clone(&written);
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(2).unwrap())
+        .on_removal(move |key: &u32, value: &u32, cause: RemovalCause| {
+            if cause.was_evicted() {
+                writer.lock().unwrap().push((*key, *value));
+            }
+        })
+        .build();
+
+    c.put(1, 1);
+    c.put(2, 2);
+    c.put(1, 10);
+    c.put(3, 3);
+    c.remove(&1);
+    c.clear();
+    let written = written.lock().unwrap().clone();
+
+    match written[..] {
+        [(2, 2)] => Ok(()),
+        _ => Err(format!("Expected only key 2 written back. Got {written:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_stay_consistent_when_the_removal_callback_panics() -> Result<(), String> {
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(2).unwrap())
+        .on_removal(|key: &u32, _: &u32, _| assert_ne!(*key, 1, "cannot remove key 1"))
+        .build();
+    c.put(1, 1);
+    c.put(2, 2);
+
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| c.put(3, 3))).is_err();
+    c.debug_validate().map_err(|e| e.to_string())?;
+    let after_panic = (c.len(), c.contains_key(&1), c.contains_key(&3));
+    c.put(4, 4);
+
+    match (panicked, after_panic, c.len(), c.contains_key(&2)) {
+        (true, (1, false, false), 2, true) => Ok(()),
+        other => Err(format!("Expected key 1 gone and key 3 not stored, with the cache still usable. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn stats_should_count_every_kind_of_activity() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(3).unwrap());
+    c.put(1, 1);
+    c.put(2, 2);
+    c.put(3, 3);
+    c.put(1, 10);
+    let found = [1, 4, 2].map(|key| c.get(&key).is_some());
+    c.put(4, 4);
+    c.push(4, 40);
+    let popped = c.pop_lru();
+    let removed = (c.remove(&2), c.remove(&9));
+    let _ = (c.peek(&4), c.contains_key(&4));
+
+    let expected = CacheStats {
+        hits: 2,
+        misses: 1,
+        insertions: 4,
+        updates: 2,
+        evictions: 1,
+        removals: 2,
+        entries: 1,
+        ..CacheStats::default()
+    };
+
+    match (c.stats(), c.hit_ratio(), found, popped, removed) {
+        (stats, ratio, [true, false, true], Some(10), (Some(2), None)) if stats == expected && ratio == 2.0 / 3.0 => {
+            Ok(())
+        }
+        other => Err(format!("Expected {expected:?} with a hit ratio of 2/3. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn hit_ratio_should_be_zero_without_lookups() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(2).unwrap());
+    c.put(1, 1);
+    let before_reset = c.hit_ratio();
+    let _ = c.get(&1);
+    c.reset_stats();
+
+    match (before_reset, c.hit_ratio(), c.stats()) {
+        (0.0, 0.0, stats) if stats == CacheStats { entries: 1, ..CacheStats::default() } => Ok(()),
+        other => Err(format!("Expected a zero hit ratio before any lookup and after a reset. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn stats_should_bucket_the_age_and_idle_time_of_evicted_items() -> Result<(), String> {
+    let clock = MockClock::new();
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(2).unwrap())
+        .clock(clock.clone())
+        .build();
+    c.put(1, 1);
+    c.put(2, 2);
+    clock.advance(Duration::from_secs(9));
+    let _ = (c.get(&1).is_some(), c.get(&2).is_some());
+    clock.advance(Duration::from_secs(1));
+
+    // Items 1 and 2 go at 10s old, idle for 1s, then item 3 goes as soon as it arrives
+    for key in 3..6 {
+        c.put(key, key);
+    }
+
+    let stats = c.stats();
+    let filled = |histogram: DurationHistogram| -> Vec<(Duration, u64)> {
+        histogram.buckets().filter(|&(_, count)| count > 0).collect()
+    };
+    let bucket = |bits: u32| Duration::from_nanos((1 << bits) - 1);
+    let expected_age = [(Duration::ZERO, 1), (bucket(34), 2)];
+    let expected_idle = [(Duration::ZERO, 1), (bucket(30), 2)];
+
+    match (stats.evictions, filled(stats.eviction_age), filled(stats.eviction_idle)) {
+        (3, age, idle) if age == expected_age && idle == expected_idle => Ok(()),
+        other => Err(format!("Expected 3 evictions, ages {expected_age:?}, idle {expected_idle:?}. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn stats_should_not_bucket_evictions_without_a_clock() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(1).unwrap());
+    c.put(1, 1);
+    c.put(2, 2);
+    let stats = c.stats();
+
+    match (stats.evictions, stats.eviction_age.count(), stats.eviction_idle.count()) {
+        (1, 0, 0) => Ok(()),
+        other => Err(format!("Expected one eviction with no age recorded. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+/// Returns a cache counting activity by the minute, and its clock
+fn cache_with_windowed_stats(capacity: usize) -> (LruCache<u32, u32>, MockClock) {
+    let clock = MockClock::new();
+    let c = LruCacheBuilder::new(NonZeroUsize::new(capacity).unwrap())
+        .clock(clock.clone())
+        .windowed_stats(Duration::from_secs(60))
+        .build();
+    (c, clock)
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn stats_window_should_only_count_recent_intervals() -> Result<(), String> {
+    let (mut c, clock) = cache_with_windowed_stats(8);
+    let minutes = |n: u64| Duration::from_secs(60 * n);
+
+    // Ten minutes of misses, then five of hits
+    for _ in 0..10 {
+        c.get(&1);
+        clock.advance(minutes(1));
+    }
+
+    c.put(1, 1);
+
+    for _ in 0..5 {
+        c.get(&1);
+        c.get(&1);
+        c.get(&2);
+        clock.advance(minutes(1));
+    }
+
+    let last_five = c.stats_window(minutes(5));
+    let last_ten = c.stats_window(minutes(10));
+    let ratios = (last_five.hit_ratio(), c.hit_ratio());
+
+    match (last_five.hits, last_five.misses, last_five.insertions, last_ten.hits, last_ten.misses) {
+        (8, 4, 0, 10, 9) if ratios == (8.0 / 12.0, 10.0 / 25.0) => Ok(()),
+        other => Err(format!("Expected the windows to skip older misses. Got {other:?} and ratios {ratios:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn stats_window_should_forget_intervals_beyond_the_ring() -> Result<(), String> {
+    let (mut c, clock) = cache_with_windowed_stats(2);
+    c.put(1, 1);
+    c.put(2, 2);
+    c.put(3, 3);
+    c.remove(&3);
+    let before = c.stats_window(Duration::from_secs(60));
+
+    clock.advance(Duration::from_secs(60 * 60));
+    c.get(&2);
+    let after = c.stats_window(Duration::from_secs(3600 * 24));
+    c.reset_stats();
+    let reset = c.stats_window(Duration::from_secs(60));
+
+    match ((before.insertions, before.evictions, before.removals), (after.hits, after.insertions), reset.hits) {
+        ((3, 1, 1), (1, 0), 0) if after.entries == 1 => Ok(()),
+        other => Err(format!("Expected the first hour to drop out of the window. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn stats_window_should_be_empty_unless_enabled() -> Result<(), String> {
+    let (mut c, _) = cache_with_mock_clock(2);
+    c.put(1, 1);
+    c.get(&1);
+
+    match c.stats_window(Duration::from_secs(60)) {
+        CacheStats { hits: 0, insertions: 0, entries: 1, .. } => Ok(()),
+        other => Err(format!("Expected no windowed counts. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn to_prometheus_should_report_a_scripted_workload() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(2).unwrap());
+    c.put("a", 1);
+    c.put("b", 2);
+    c.put("c", 3);
+    c.put("b", 20);
+    let _ = (c.get(&"b").is_some(), c.get(&"a").is_some());
+    c.remove(&"c");
+
+    let text = c.stats().to_prometheus("sessions");
+    let samples = parse_prometheus(&text)?;
+    let expected: BTreeMap<String, f64> = [
+        ("sessions_hits_total", 1.0),
+        ("sessions_misses_total", 1.0),
+        ("sessions_insertions_total", 3.0),
+        ("sessions_updates_total", 1.0),
+        ("sessions_evictions_total", 1.0),
+        ("sessions_removals_total", 1.0),
+        ("sessions_entries", 1.0),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value))
+    .collect();
+
+    match (samples == expected, text.contains("# TYPE sessions_entries gauge\n")) {
+        (true, true) => Ok(()),
+        other => Err(format!("Expected {expected:?} with entries as a gauge. Got {other:?} from:\n{text}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn to_prometheus_should_turn_the_name_into_a_valid_metric_name() -> Result<(), String> {
+    let names = ["user-cache.v2", "2nd level"].map(|name| {
+        let samples = parse_prometheus(&CacheStats::default().to_prometheus(name));
+        samples.map(|samples| samples.into_keys().find(|key| key.ends_with("_entries")))
+    });
+
+    match names.each_ref().map(|name| name.as_ref().ok().and_then(Option::as_deref)) {
+        [Some("user_cache_v2_entries"), Some("_2nd_level_entries")] => Ok(()),
+        _ => Err(format!("Expected every name to be made valid. Got {names:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+fn cost_aware_cache(capacity: usize) -> LruCache<u32, u32> {
+    LruCacheBuilder::new(NonZeroUsize::new(capacity).unwrap())
+        .eviction_mode(EvictionMode::CostAware)
+        .build()
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn cost_aware_should_keep_a_cold_expensive_entry_over_hot_cheap_ones() -> Result<(), String> {
+    let mut c = cost_aware_cache(4);
+    c.put_with_cost(0, 0, 1_000);
+
+    for round in 0..10 {
+        for k in 1..=3 {
+            c.put(k, k);
+        }
+
+        // Fresh cheap keys push out the hot cheap entries, never the expensive one
+        c.put(100 + round, round);
+    }
+
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (c.contains_key(&0), c.cost_clock()) {
+        (true, Some(clock)) if clock < 1_000 => Ok(()),
+        other => Err(format!("Expected key 0 to outlive every cheap entry. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn cost_aware_should_eventually_evict_an_expensive_entry_that_is_never_used() -> Result<(), String> {
+    let mut c = cost_aware_cache(4);
+    c.put_with_cost(0, 0, 20);
+
+    let evicted_after = (1..1_000).find(|&k| c.push(k, k).is_some_and(|(key, _)| key == 0));
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (evicted_after, c.cost_clock()) {
+        (Some(40..=200), Some(clock)) if clock >= 20 => Ok(()),
+        other => Err(format!("Expected the clock to overtake key 0's priority of 20. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn cost_aware_should_evict_in_lru_order_when_every_cost_is_one() -> Result<(), String> {
+    let lru = replay_reads_and_replacements(fifo_cache(3, EvictionMode::Exact));
+    let cost_aware = replay_reads_and_replacements(fifo_cache(3, EvictionMode::CostAware));
+
+    match (&lru[..], &cost_aware[..]) {
+        ([3, 1], [3, 1]) => Ok(()),
+        other => Err(format!("Expected the same victims as LRU. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn cost_aware_should_make_a_demoted_entry_the_next_victim() -> Result<(), String> {
+    let mut c = cost_aware_cache(3);
+    c.put_with_cost(1, 1, 50);
+    c.put_with_cost(2, 2, 50);
+    c.put(3, 3);
+    c.demote(&2);
+
+    match (c.push(4, 4), c.push(5, 5), c.cost_clock()) {
+        (Some((2, 2)), Some((3, 3)), Some(1)) => Ok(()),
+        other => Err(format!("Expected key 2 evicted first, then the cheap key 3. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+/// A hasher that gives every key the same hash
+#[derive(Default)]
+struct Colliding;
+
+impl std::hash::Hasher for Colliding {
+    fn finish(&self) -> u64 {
+        0
+    }
+
+    fn write(&mut self, _: &[u8]) {}
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn put_with_cost_should_leave_a_colliding_key_alone_when_the_item_is_turned_away() -> Result<(), String> {
+    let mut c: LruCache<u32, u32, std::hash::BuildHasherDefault<Colliding>> =
+        LruCacheBuilder::new(NonZeroUsize::new(1).unwrap())
+            .eviction_mode(EvictionMode::CostAware)
+            .hasher(std::hash::BuildHasherDefault::default())
+            .build();
+    c.put(1, 1);
+    c.pin(&1);
+    let turned_away = c.put_with_cost(2, 2, 100);
+    c.unpin(&1);
+
+    match (turned_away, c.contains_key(&2), c.push(3, 3), c.cost_clock()) {
+        (None, false, Some((1, 1)), Some(1)) => Ok(()),
+        other => Err(format!("Expected key 1 evicted at its own cost of one. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn cost_aware_should_not_pass_a_cost_on_to_the_next_entry_in_the_same_place() -> Result<(), String> {
+    let mut c = cost_aware_cache(1);
+    c.put_with_cost(1, 1, 100);
+    let first = c.push(2, 2);
+    let second = c.push(3, 3);
+
+    match (first, second, c.cost_clock()) {
+        (Some((1, 1)), Some((2, 2)), Some(101)) => Ok(()),
+        other => Err(format!("Expected key 2 to cost one on top of a clock of 100. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn cost_aware_should_stay_consistent_under_mixed_operations() -> Result<(), String> {
+    let mut c = cost_aware_cache(16);
+    exercise_mixed_operations(&mut c);
+
+    for k in 0..1_000 {
+        c.put_with_cost(k, k, u64::from(k % 7));
+        c.get(&(k / 2));
+    }
+
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match c.len() {
+        16 => Ok(()),
+        other => Err(format!("Expected a full cache. Got {other} entries")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+/// Builds a cache of four with a small queue of two, and uses keys 1 and 2 before key 5 forces the first eviction.  The
+/// main queue then holds 2 and 1, newest first, and the small queue holds 5 and 4, while the ghost queue remembers 3
+fn s3_fifo_cache_after_promotions() -> (LruCache<u32, u32>, Option<u32>) {
+    let mut c: LruCache<u32, u32> = LruCacheBuilder::new(NonZeroUsize::new(4).unwrap())
+        .eviction_mode(EvictionMode::S3Fifo { small_percent: 50 })
+        .build();
+
+    for k in 1..=4 {
+        c.put(k, k);
+    }
+
+    c.get(&1);
+    c.get(&2);
+    let evicted = c.push(5, 5).map(|(key, _)| key);
+    c.debug_assert_valid();
+    (c, evicted)
+}
+
+fn s3_fifo_queues(small: usize, main: usize, ghost: usize) -> Option<S3FifoQueues> {
+    Some(S3FifoQueues { small, main, ghost })
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn s3_fifo_should_move_used_small_entries_to_the_main_queue_and_evict_the_rest() -> Result<(), String> {
+    let (c, evicted) = s3_fifo_cache_after_promotions();
+
+    match (evicted, c.s3_fifo_queues(), c.recency_rank(&2), c.recency_rank(&1)) {
+        (Some(3), queues, Some(0), Some(1)) if queues == s3_fifo_queues(2, 2, 1) => Ok(()),
+        other => Err(format!("Expected keys 1 and 2 promoted and key 3 evicted. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn s3_fifo_should_readmit_remembered_keys_to_the_main_queue() -> Result<(), String> {
+    let (mut c, _) = s3_fifo_cache_after_promotions();
+
+    // Key 4 is the oldest unused small entry, so it makes room, and key 3 skips the small queue
+    let evicted = c.push(3, 30).map(|(key, _)| key);
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (evicted, c.s3_fifo_queues(), c.recency_rank(&3), c.recency_rank(&5)) {
+        (Some(4), queues, Some(0), Some(3)) if queues == s3_fifo_queues(1, 3, 2) => Ok(()),
+        other => Err(format!("Expected key 3 at the front of the main queue. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn s3_fifo_should_reinsert_used_main_entries_instead_of_evicting_them() -> Result<(), String> {
+    let (mut c, _) = s3_fifo_cache_after_promotions();
+    c.push(3, 30);
+
+    // The small queue is below its share, so eviction turns to the main queue.  Key 1 is its oldest entry but has
+    // been used, so it goes round again and key 2 is evicted in its place
+    c.get(&1);
+    let evicted = c.push(6, 6).map(|(key, _)| key);
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match (evicted, c.recency_rank(&1), c.recency_rank(&3), c.s3_fifo_queues()) {
+        (Some(2), Some(0), Some(1), queues) if queues == s3_fifo_queues(2, 2, 2) => Ok(()),
+        other => Err(format!("Expected key 2 evicted and key 1 back at the front. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn s3_fifo_should_keep_hot_keys_through_a_scan() -> Result<(), String> {
+    let mut c: LruCache<u32, u32> = LruCacheBuilder::new(NonZeroUsize::new(100).unwrap())
+        .eviction_mode(EvictionMode::S3_FIFO)
+        .build();
+
+    for round in 0..5 {
+        for k in 0..50 {
+            if c.get(&k).is_none() {
+                c.put(k, round);
+            }
+        }
+    }
+
+    for k in 1_000..2_000 {
+        c.put(k, k);
+    }
+
+    c.debug_validate().map_err(|e| e.to_string())?;
+    let hot = (0..50).filter(|k| c.contains_key(k)).count();
+
+    match hot {
+        50 => Ok(()),
+        other => Err(format!("Expected all 50 hot keys to survive the scan. Got {other}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn s3_fifo_should_stay_consistent_under_mixed_operations() -> Result<(), String> {
+    let mut c: LruCache<u32, u32> = LruCacheBuilder::new(NonZeroUsize::new(16).unwrap())
+        .eviction_mode(EvictionMode::S3Fifo { small_percent: 25 })
+        .build();
+    exercise_mixed_operations(&mut c);
+
+    match (c.len(), c.s3_fifo_queues().map(|queues| queues.small + queues.main)) {
+        (0, Some(0)) => Ok(()),
+        other => Err(format!("Expected an empty cache. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn s3_fifo_queues_should_only_be_reported_under_s3_fifo() -> Result<(), String> {
+    let c: LruCache<u32, u32> = LruCache::new(NonZeroUsize::new(4).unwrap());
+
+    match c.s3_fifo_queues() {
+        None => Ok(()),
+        other => Err(format!("Expected no queues under LRU. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+/// Draws `count` keys from `0..keys` with a Zipfian distribution, so that key 0 is the most used
+fn zipfian_keys(keys: usize, count: usize, seed: u64) -> Vec<usize> {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    let mut total = 0.0;
+    let cumulative: Vec<f64> = (1..=keys)
+        .map(|rank| {
+            total += 1.0 / (rank as f64).powf(1.1);
+            total
+        })
+        .collect();
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    (0..count).map(|_| cumulative.partition_point(|&weight| weight < rng.random::<f64>() * total)).collect()
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn hottest_should_report_the_most_used_keys_of_a_zipfian_workload() -> Result<(), String> {
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(100).unwrap())
+        .track_hot_keys(NonZeroUsize::new(32).unwrap())
+        .build();
+    let mut uses = BTreeMap::new();
+
+    for key in zipfian_keys(10_000, 50_000, 7) {
+        *uses.entry(key).or_insert(0_u64) += 1;
+
+        if c.get(&key).is_none() {
+            c.put(key, key);
+        }
+    }
+
+    let mut by_uses: Vec<_> = uses.into_iter().collect();
+    by_uses.sort_by_key(|&(_, n)| core::cmp::Reverse(n));
+    let hottest = c.hottest(10);
+    let reported: BTreeSet<_> = hottest.iter().map(|&(key, _)| key).collect();
+    let missing: Vec<_> = by_uses[..5].iter().filter(|(key, _)| !reported.contains(key)).collect();
+    let undercounted = hottest.iter().any(|(key, estimate)| by_uses.iter().any(|(k, n)| k == key && estimate < n));
+
+    match (hottest.len(), missing.is_empty(), undercounted) {
+        (10, true, false) => Ok(()),
+        other => Err(format!("Expected the true top 5 among 10 estimates. Got {other:?}: {hottest:?} {missing:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn reset_frequency_stats_should_clear_the_report() -> Result<(), String> {
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(4).unwrap())
+        .track_hot_keys(NonZeroUsize::new(4).unwrap())
+        .build();
+    c.put(1, 1);
+    c.get(&1);
+    c.put(2, 2);
+    let before = c.hottest(4);
+    c.reset_frequency_stats();
+    c.get(&2);
+
+    match (&before[..], &c.hottest(4)[..]) {
+        ([(1, 2), (2, 1)], [(2, 1)]) => Ok(()),
+        other => Err(format!("Expected [(1, 2), (2, 1)] then [(2, 1)]. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn hottest_should_be_empty_unless_tracking() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(4).unwrap());
+    c.put(1, 1);
+    c.get(&1);
+
+    match c.hottest(4)[..] {
+        [] => Ok(()),
+        ref other => Err(format!("Expected no hot keys. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn debug_validate_should_describe_the_inconsistency_it_finds() -> Result<(), String> {
+    let mut c = LruCache::new(NonZeroUsize::new(4).unwrap());
+    c.put(1, 1);
+    c.put(2, 2);
+    let healthy = c.debug_validate();
+    let head = c.nodes.head().ok_or("Expected a head entry")?;
+    c.nodes.node_mut(head).hash ^= 1;
+
+    match (healthy, c.debug_validate().map_err(|e| e.to_string())) {
+        (Ok(()), Err(message)) if message.ends_with("holds a stale hash") => Ok(()),
+        other => Err(format!("Expected a healthy cache, then a stale hash. Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn evict_to_should_trim_the_ghost_queue() -> Result<(), String> {
+    let mut c = two_queue_cache(8);
+
+    for key in 0..8 {
+        c.put(key, key);
+    }
+
+    let evicted = c.evict_to(0);
+    c.debug_validate().map_err(|e| e.to_string())?;
+
+    match evicted {
+        8 => Ok(()),
+        other => Err(format!("Expected 8 evictions. Got {other}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn listener_should_hear_every_event_of_a_scripted_workload() -> Result<(), String> {
+    use listener::Event::*;
+    use RemovalCause::*;
+
+    let listener = RecordingListener::default();
+    let clock = MockClock::new();
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(2).unwrap())
+        .clock(clock.clone())
+        .listener(listener.clone())
+        .build();
+
+    c.put(1, 1);
+    c.put(2, 2);
+    c.get(&1);
+    c.get(&3);
+    c.put(1, 10);
+    c.put(3, 3);
+    c.put_with_ttl(4, 4, Duration::from_secs(1));
+    clock.advance(Duration::from_secs(1));
+    c.put(4, 40);
+    c.remove(&4);
+    c.clear();
+    let expected = [
+        (Insert, 1, 1),
+        (Insert, 2, 2),
+        (Hit, 1, 1),
+        (Remove(Replaced), 1, 1),
+        (Update, 1, 10),
+        (Remove(Capacity), 2, 2),
+        (Insert, 3, 3),
+        (Remove(Capacity), 1, 10),
+        (Insert, 4, 4),
+        (Remove(Expired), 4, 4),
+        (Insert, 4, 40),
+        (Remove(Explicit), 4, 40),
+        (Remove(Cleared), 3, 3),
+    ];
+
+    match listener.events() {
+        events if events == expected => Ok(()),
+        events => Err(format!("Expected {expected:?}. Got {events:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn listeners_should_each_hear_every_event_in_the_order_they_were_added() -> Result<(), String> {
+    let order = Arc::new(Mutex::new(Vec::new()));
+
+    struct Tagged(u8, Arc<Mutex<Vec<(u8, u32)>>>);
+
+    impl CacheEventListener<u32, u32> for Tagged {
+        fn on_insert(&self, key: &u32, _: &u32) {
+            self.1.lock().unwrap().push((self.0, *key));
+        }
+    }
+
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(2).unwrap())
+        .listener(Tagged(1, Arc::clone(&order)))
+        .listener(Tagged(2, Arc::clone(&order)))
+        .build();
+    c.put(1, 1);
+    c.put(2, 2);
+    c.get(&1);
+    let heard = order.lock().unwrap().clone();
+
+    match heard[..] {
+        [(1, 1), (2, 1), (1, 2), (2, 2)] => Ok(()),
+        _ => Err(format!("Expected each insertion heard by listener 1 then 2. Got {heard:?}")),
+    }
+}
@@ -0,0 +1,262 @@
+//! Writing back to a backing store
+//!
+//! A [`WriteBackCache`] takes writes at the speed of the cache: `put` only changes the cache and marks the item dirty.
+//! Dirty items reach the [`BackingStore`] when they are about to be evicted, when [`flush`](WriteBackCache::flush) is
+//! called, or when the cache is dropped.  Clean items, whose value the store already holds, are never written again.
+//!
+//! # Failed writes
+//!
+//! A dirty item that cannot be written as it is evicted is dealt with according to the cache's
+//! [`WriteFailurePolicy`]: the write can be retried, the item evicted anyway and its value lost, or the eviction
+//! refused, keeping the item and returning the store's error from the `put` that needed the room.  A failed
+//! [`flush`](WriteBackCache::flush) stops at the first error and leaves the items it did not write dirty.
+//!
+//! Removals are not deferred: [`remove`](WriteBackCache::remove) deletes the key from the store before removing it from
+//! the cache
+use crate::{BackingStore, CacheStats, DefaultHashBuilder, LruCache};
+use core::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    num::NonZeroUsize,
+};
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// What a [`WriteBackCache`] does when the store fails to take a dirty item that is being evicted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WriteFailurePolicy {
+    /// Refuse to evict the item, and return the store's error from the `put` that needed the room
+    #[default]
+    Refuse,
+    /// Try the write this many more times, then refuse to evict the item as [`Refuse`](Self::Refuse) does
+    Retry(u32),
+    /// Evict the item anyway, losing its value, and count it in
+    /// [`discarded_writes`](WriteBackCache::discarded_writes)
+    Discard,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// A cached value, with whether it has changed since it was last written to the store
+struct Slot<V> {
+    value: V,
+    dirty: bool,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache whose writes reach a [`BackingStore`] on eviction or when flushed
+pub struct WriteBackCache<K, V, B, S = DefaultHashBuilder>
+where
+    K: Eq + Hash,
+    B: BackingStore<K, V>,
+    S: BuildHasher,
+{
+    cache: LruCache<K, Slot<V>, S>,
+    store: B,
+    policy: WriteFailurePolicy,
+    discarded: u64,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, B> WriteBackCache<K, V, B>
+where
+    K: Eq + Hash,
+    B: BackingStore<K, V>,
+{
+    /// Creates a cache that holds at most `capacity` items and writes them back to `store`
+    pub fn new(capacity: NonZeroUsize, store: B) -> Self {
+        WriteBackCache {
+            cache: LruCache::new(capacity),
+            store,
+            policy: WriteFailurePolicy::default(),
+            discarded: 0,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, B, S> WriteBackCache<K, V, B, S>
+where
+    K: Eq + Hash,
+    B: BackingStore<K, V>,
+    S: BuildHasher,
+{
+    /// Sets what happens when a dirty item cannot be written as it is evicted.  The default is
+    /// [`WriteFailurePolicy::Refuse`]
+    pub fn with_failure_policy(mut self, policy: WriteFailurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a reference to an item, making it the most recently used.  The store is not consulted
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.get(key).map(|slot| &slot.value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetch a reference to an item without changing its position in the recency order
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.peek(key).map(|slot| &slot.value)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains the key. The item's position in the recency order is not changed
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.contains_key(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the item has changed since it was last written to the store
+    pub fn is_dirty<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.peek(key).is_some_and(|slot| slot.dirty)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Inserts an item and marks it dirty, returning the value it replaced.  If the cache is full, the least recently
+    /// used item is written to the store first if it is dirty, and then evicted.  If that write fails and the failure
+    /// policy refuses the eviction, the store's error is returned and the cache is not changed
+    pub fn put(&mut self, key: K, new_value: V) -> Result<Option<V>, B::Error> {
+        if !self.cache.contains_key(&key) && self.cache.len() >= self.cache.capacity().get() {
+            self.write_back_lru()?;
+            self.cache.evict_to(self.cache.len() - 1);
+        }
+
+        let slot = Slot {
+            value: new_value,
+            dirty: true,
+        };
+
+        Ok(self.cache.put(key, slot).map(|old| old.value))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Deletes the item from the store and then from the cache, returning its cached value if it was present.  If the
+    /// store fails, its error is returned and the cache is not changed
+    pub fn remove(&mut self, key: &K) -> Result<Option<V>, B::Error> {
+        self.store.delete(key)?;
+        Ok(self.cache.remove(key).map(|slot| slot.value))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Writes every dirty item to the store, from least to most recently used, and marks it clean.  Stops at the first
+    /// error, leaving the items not yet written dirty
+    pub fn flush(&mut self) -> Result<(), B::Error> {
+        let store = &mut self.store;
+
+        self.cache.try_for_each_lru_first_mut(|key, slot| {
+            if slot.dirty {
+                store.write(key, &slot.value)?;
+                slot.dirty = false;
+            }
+
+            Ok(())
+        })
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items that have changed since they were last written to the store
+    pub fn dirty_len(&self) -> usize {
+        self.cache.iter_lru_first().filter(|(_, slot)| slot.dirty).count()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of dirty items evicted without being written, under [`WriteFailurePolicy::Discard`]
+    pub fn discarded_writes(&self) -> u64 {
+        self.discarded
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in the cache
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains no items
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the cache can hold
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.cache.capacity()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns a snapshot of the cache's statistics
+    pub fn stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the backing store
+    pub fn store(&self) -> &B {
+        &self.store
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Writes the least recently used item to the store if it is dirty, so that it can be evicted.  Returns an error if
+    /// the item must be kept
+    fn write_back_lru(&mut self) -> Result<(), B::Error> {
+        let Some((key, slot)) = self.cache.iter_lru_first().next() else {
+            return Ok(());
+        };
+
+        if !slot.dirty {
+            return Ok(());
+        }
+
+        let mut result = self.store.write(key, &slot.value);
+
+        if let WriteFailurePolicy::Retry(attempts) = self.policy {
+            for _ in 0..attempts {
+                if result.is_ok() {
+                    break;
+                }
+
+                result = self.store.write(key, &slot.value);
+            }
+        }
+
+        match (result, self.policy) {
+            (Err(_), WriteFailurePolicy::Discard) => {
+                self.discarded += 1;
+                Ok(())
+            }
+            (result, _) => result,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, B, S> Drop for WriteBackCache<K, V, B, S>
+where
+    K: Eq + Hash,
+    B: BackingStore<K, V>,
+    S: BuildHasher,
+{
+    fn drop(&mut self) {
+        // Nobody is left to report a failure to, so the dirty items that cannot be written are lost
+        let _ = self.flush();
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(all(test, feature = "std"))]
+mod unit_tests;
@@ -0,0 +1,131 @@
+use super::*;
+use crate::test_utils::{MockStore, StoreCall};
+
+const CAPACITY: NonZeroUsize = NonZeroUsize::new(2).unwrap();
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_write_dirty_item_only_when_evicted() -> Result<(), String> {
+    let store = MockStore::default();
+    let mut c = WriteBackCache::new(CAPACITY, store.clone());
+
+    c.put(1, 10)?;
+    c.put(2, 20)?;
+    let calls_before_eviction = store.calls();
+
+    // Evicts 1, which is dirty
+    c.put(3, 30)?;
+
+    match (
+        calls_before_eviction.is_empty(),
+        store.calls(),
+        c.is_dirty(&2),
+        c.is_dirty(&3),
+    ) {
+        (true, calls, true, true) if calls == [StoreCall::Write(1, 10)] => Ok(()),
+        other => Err(format!("Expected (true, [Write(1, 10)], true, true). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_flush_dirty_items_lru_first_and_never_rewrite_clean_ones() -> Result<(), String> {
+    let store = MockStore::default();
+    let mut c = WriteBackCache::new(CAPACITY, store.clone());
+
+    c.put(1, 10)?;
+    c.put(2, 20)?;
+    c.flush()?;
+
+    // Evicts 1, which is clean, and then 3, which is not
+    c.put(3, 30)?;
+    c.get(&2);
+    c.put(4, 40)?;
+
+    // Order from least to most recently used is now 4, 2
+    c.put(2, 21)?;
+    c.flush()?;
+    c.flush()?;
+
+    let expected = [
+        StoreCall::Write(1, 10),
+        StoreCall::Write(2, 20),
+        StoreCall::Write(3, 30),
+        StoreCall::Write(4, 40),
+        StoreCall::Write(2, 21),
+    ];
+
+    match (store.calls(), c.dirty_len()) {
+        (calls, 0) if calls == expected => Ok(()),
+        other => Err(format!("Expected ({expected:?}, 0). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_refuse_eviction_when_store_fails() -> Result<(), String> {
+    let store = MockStore::default();
+    let mut c = WriteBackCache::new(CAPACITY, store.clone());
+
+    c.put(1, 10)?;
+    c.put(2, 20)?;
+    store.set_failing(true);
+
+    let put = c.put(3, 30);
+
+    match (put, c.peek(&1), c.is_dirty(&1), c.contains_key(&3), store.value(1)) {
+        (Err(_), Some(10), true, false, None) => Ok(()),
+        other => Err(format!("Expected (Err, Some(10), true, false, None). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_retry_or_discard_failed_writes_as_configured() -> Result<(), String> {
+    let retrying_store = MockStore::default();
+    let mut retrying =
+        WriteBackCache::new(CAPACITY, retrying_store.clone()).with_failure_policy(WriteFailurePolicy::Retry(2));
+    let discarding_store = MockStore::default();
+    let mut discarding =
+        WriteBackCache::new(CAPACITY, discarding_store.clone()).with_failure_policy(WriteFailurePolicy::Discard);
+
+    for c in [&mut retrying, &mut discarding] {
+        c.put(1, 10)?;
+        c.put(2, 20)?;
+    }
+
+    // The third attempt succeeds
+    retrying_store.fail_next(2);
+    retrying.put(3, 30)?;
+
+    discarding_store.fail_next(1);
+    discarding.put(3, 30)?;
+
+    match (
+        retrying_store.calls().len(),
+        retrying_store.value(1),
+        discarding.discarded_writes(),
+        discarding.contains_key(&1),
+        discarding_store.value(1),
+    ) {
+        (3, Some(10), 1, false, None) => Ok(()),
+        other => Err(format!("Expected (3, Some(10), 1, false, None). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_flush_when_dropped() -> Result<(), String> {
+    let store = MockStore::default();
+    let mut c = WriteBackCache::new(CAPACITY, store.clone());
+
+    c.put(1, 10)?;
+    c.put(2, 20)?;
+    c.remove(&1)?;
+    drop(c);
+
+    match (store.calls(), store.value(2)) {
+        (calls, Some(20)) if calls == [StoreCall::Delete(1), StoreCall::Write(2, 20)] => Ok(()),
+        other => Err(format!("Expected ([Delete(1), Write(2, 20)], Some(20)). Got {other:?}")),
+    }
+}
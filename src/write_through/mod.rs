@@ -0,0 +1,147 @@
+//! Writing through to a backing store
+//!
+//! A [`WriteThroughCache`] sits in front of a [`BackingStore`], such as a database table, so that callers talk only to
+//! the cache.  Every `put` and `remove` is first applied to the store and reaches the cache only once the store has
+//! accepted it, so the cache never holds a value the store does not.  When the store fails, the error is handed back
+//! and the cache is left as it was.
+//!
+//! Reads are served from the cache alone.  Since every value in the cache is already in the store, entries that the
+//! cache evicts are simply dropped
+use crate::{DefaultHashBuilder, LruCache};
+use core::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    num::NonZeroUsize,
+};
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// The durable store behind a [`WriteThroughCache`]
+pub trait BackingStore<K, V> {
+    type Error;
+
+    /// Stores `value` under `key`, replacing any value already there
+    fn write(&mut self, key: &K, value: &V) -> Result<(), Self::Error>;
+
+    /// Removes the value stored under `key`.  Removing a key that is not there is not an error
+    fn delete(&mut self, key: &K) -> Result<(), Self::Error>;
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache whose writes are forwarded to a [`BackingStore`] before they are applied
+pub struct WriteThroughCache<K, V, B, S = DefaultHashBuilder> {
+    cache: LruCache<K, V, S>,
+    store: B,
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, B> WriteThroughCache<K, V, B>
+where
+    K: Eq + Hash,
+    B: BackingStore<K, V>,
+{
+    /// Creates a cache that holds at most `capacity` items and writes through to `store`
+    pub fn new(capacity: NonZeroUsize, store: B) -> Self {
+        WriteThroughCache::with_cache(LruCache::new(capacity), store)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V, B, S> WriteThroughCache<K, V, B, S>
+where
+    K: Eq + Hash,
+    B: BackingStore<K, V>,
+    S: BuildHasher,
+{
+    /// Writes through to `store` from a cache configured elsewhere, for instance by
+    /// [`LruCacheBuilder`](crate::LruCacheBuilder).  Items already in `cache` are assumed to be in the store
+    pub fn with_cache(cache: LruCache<K, V, S>, store: B) -> Self {
+        WriteThroughCache { cache, store }
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Attempt to fetch a reference to an item, making it the most recently used.  The store is not consulted
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.get(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Fetch a reference to an item without changing its position in the recency order
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.peek(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains the key. The item's position in the recency order is not changed
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.cache.contains_key(key)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Writes the item to the store and then inserts it, returning the value it replaced in the cache.  If the store
+    /// fails, its error is returned and the cache is not changed
+    pub fn put(&mut self, key: K, new_value: V) -> Result<Option<V>, B::Error> {
+        self.store.write(&key, &new_value)?;
+        Ok(self.cache.put(key, new_value))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Deletes the item from the store and then from the cache, returning its cached value if it was present.  The store
+    /// is asked to delete the key even if the cache has already evicted it.  If the store fails, its error is returned
+    /// and the cache is not changed
+    pub fn remove(&mut self, key: &K) -> Result<Option<V>, B::Error> {
+        self.store.delete(key)?;
+        Ok(self.cache.remove(key))
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the number of items in the cache
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns `true` if the cache contains no items
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the maximum number of items the cache can hold
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.cache.capacity()
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the in-memory cache, for instance to read its statistics
+    pub fn cache(&self) -> &LruCache<K, V, S> {
+        &self.cache
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Returns the backing store
+    pub fn store(&self) -> &B {
+        &self.store
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Separates the cache from its backing store
+    pub fn into_inner(self) -> (LruCache<K, V, S>, B) {
+        (self.cache, self.store)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(all(test, feature = "std"))]
+mod unit_tests;
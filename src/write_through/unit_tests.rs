@@ -0,0 +1,77 @@
+use super::*;
+use crate::test_utils::{MockStore, StoreCall};
+
+const CAPACITY: NonZeroUsize = NonZeroUsize::new(2).unwrap();
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_write_to_store_before_cache() -> Result<(), String> {
+    let store = MockStore::default();
+    let mut c = WriteThroughCache::new(CAPACITY, store.clone());
+
+    c.put(1, 10)?;
+    c.put(2, 20)?;
+    c.put(1, 11)?;
+    c.remove(&2)?;
+
+    // Removing a key the cache does not hold still deletes it from the store
+    c.remove(&3)?;
+
+    let expected = vec![
+        StoreCall::Write(1, 10),
+        StoreCall::Write(2, 20),
+        StoreCall::Write(1, 11),
+        StoreCall::Delete(2),
+        StoreCall::Delete(3),
+    ];
+
+    match (store.calls(), c.peek(&1), c.contains_key(&2), store.value(1), store.value(2)) {
+        (calls, Some(11), false, Some(11), None) if calls == expected => Ok(()),
+        other => Err(format!("Expected ({expected:?}, Some(11), false, Some(11), None). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_leave_cache_unchanged_when_store_fails() -> Result<(), String> {
+    let store = MockStore::default();
+    let mut c = WriteThroughCache::new(CAPACITY, store.clone());
+
+    c.put(1, 10)?;
+    c.put(2, 20)?;
+    store.set_failing(true);
+
+    let put = c.put(1, 11);
+    let insert = c.put(3, 30);
+    let remove = c.remove(&2);
+
+    // The failed insert of 3 must not have evicted 1
+    match (put, insert, remove, c.peek(&1), c.peek(&2), c.contains_key(&3)) {
+        (Err(_), Err(_), Err(_), Some(10), Some(20), false) => Ok(()),
+        other => Err(format!("Expected (Err, Err, Err, Some(10), Some(20), false). Got {other:?}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_not_touch_store_on_reads_or_evictions() -> Result<(), String> {
+    let store = MockStore::default();
+    let mut c = WriteThroughCache::new(CAPACITY, store.clone());
+
+    c.put(1, 10)?;
+    c.put(2, 20)?;
+    let calls_after_writes = store.calls().len();
+
+    c.get(&1);
+    c.get(&4);
+    c.peek(&2);
+    c.contains_key(&5);
+
+    // Evicts 2, which is already in the store
+    c.put(3, 30)?;
+
+    match (store.calls().len() - calls_after_writes, c.contains_key(&2), store.value(2)) {
+        (1, false, Some(20)) => Ok(()),
+        other => Err(format!("Expected (1, false, Some(20)). Got {other:?}")),
+    }
+}
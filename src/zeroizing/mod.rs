@@ -0,0 +1,48 @@
+//! Caches whose values are wiped as soon as the cache lets go of them
+//!
+//! A [`ZeroizingLruCache`] stores every value inside [`Zeroizing`], which zeroizes the value when it is dropped.
+//! Because the cache drops a value whenever it is evicted, removed, overwritten, cleared or the cache itself is dropped,
+//! no code path can leave a value's contents behind in freed memory.
+//!
+//! Values handed back to the caller (by `put` returning the overwritten value, `remove`, `pop_lru` etc.) are still
+//! wrapped in `Zeroizing`, so they are wiped when the caller drops them.  If the caller ignores the returned value, it
+//! is wiped immediately.
+//!
+//! Values that keep their contents on the heap (such as `Vec<u8>` or `String`) are fully protected.  For values stored
+//! inline (such as `[u8; 32]`) note that entries live in a slab, a single `Vec` of slots, and reallocating the slab to
+//! make room for more entries moves values without zeroizing the old copies.  Caches created with
+//! [`ZeroizingLruCache::new_zeroizing`] or
+//! [`LruCacheBuilder::build_zeroizing`](crate::LruCacheBuilder::build_zeroizing) allocate their full capacity up
+//! front, so their slab is never reallocated.  The `unsafe-fast` feature allocates each entry separately and never
+//! moves values
+use crate::{DefaultHashBuilder, LruCache};
+use core::{hash::Hash, num::NonZeroUsize};
+use zeroize::Zeroize;
+
+pub use zeroize::Zeroizing;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// LRU cache that zeroizes its values when they leave the cache
+pub type ZeroizingLruCache<K, V, S = DefaultHashBuilder> = LruCache<K, Zeroizing<V>, S>;
+
+// ---------------------------------------------------------------------------------------------------------------------
+impl<K, V> LruCache<K, Zeroizing<V>>
+where
+    K: Eq + Hash,
+    V: Zeroize,
+{
+    /// Creates a cache whose values are zeroized when they leave the cache
+    pub fn new_zeroizing(capacity: NonZeroUsize) -> Self {
+        LruCache::new(capacity)
+    }
+
+    // -----------------------------------------------------------------------------------------------------------------
+    /// Wraps `new_value` in [`Zeroizing`] and inserts it
+    pub fn put_secret(&mut self, key: K, new_value: V) -> Option<Zeroizing<V>> {
+        self.put(key, Zeroizing::new(new_value))
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[cfg(all(test, feature = "std"))]
+mod unit_tests;
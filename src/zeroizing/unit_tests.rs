@@ -0,0 +1,142 @@
+use super::*;
+use std::{
+    string::String,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    vec::Vec,
+};
+
+/// Secret that counts how many times it has been zeroized
+#[derive(Clone)]
+struct Secret {
+    bytes: Vec<u8>,
+    wipes: Arc<AtomicUsize>,
+}
+
+impl Zeroize for Secret {
+    fn zeroize(&mut self) {
+        self.bytes.zeroize();
+        self.wipes.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+struct Fixture {
+    cache: ZeroizingLruCache<u32, Secret>,
+    wipes: Arc<AtomicUsize>,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        Fixture {
+            cache: LruCache::new_zeroizing(NonZeroUsize::new(2).unwrap()),
+            wipes: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn secret(&self, byte: u8) -> Secret {
+        Secret {
+            bytes: std::vec![byte; 32],
+            wipes: Arc::clone(&self.wipes),
+        }
+    }
+
+    fn wipes(&self) -> usize {
+        self.wipes.load(Ordering::SeqCst)
+    }
+}
+
+fn expect_wipes(f: &Fixture, expected: usize, path: &str) -> Result<(), String> {
+    match f.wipes() {
+        n if n == expected => Ok(()),
+        n => Err(std::format!("Expected {expected} wipes after {path}. Got {n}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_zeroize_evicted_value() -> Result<(), String> {
+    let mut f = Fixture::new();
+
+    for key in 0..3 {
+        let secret = f.secret(key as u8);
+        f.cache.put_secret(key, secret);
+    }
+
+    expect_wipes(&f, 1, "eviction")
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_zeroize_removed_value_when_caller_drops_it() -> Result<(), String> {
+    let mut f = Fixture::new();
+    let secret = f.secret(1);
+    f.cache.put_secret(1, secret);
+
+    let removed = f.cache.remove(&1).ok_or("Expected item 1 not found")?;
+    expect_wipes(&f, 0, "remove while caller holds the value")?;
+
+    drop(removed);
+    expect_wipes(&f, 1, "caller dropped removed value")
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_zeroize_overwritten_value() -> Result<(), String> {
+    let mut f = Fixture::new();
+    let first = f.secret(1);
+    let second = f.secret(2);
+
+    f.cache.put_secret(1, first);
+    f.cache.put_secret(1, second);
+
+    expect_wipes(&f, 1, "overwrite with ignored return value")
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_zeroize_cleared_values() -> Result<(), String> {
+    let mut f = Fixture::new();
+
+    for key in 0..2 {
+        let secret = f.secret(key as u8);
+        f.cache.put_secret(key, secret);
+    }
+
+    f.cache.clear();
+    expect_wipes(&f, 2, "clear")
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_zeroize_values_when_cache_is_dropped() -> Result<(), String> {
+    let mut f = Fixture::new();
+
+    for key in 0..2 {
+        let secret = f.secret(key as u8);
+        f.cache.put_secret(key, secret);
+    }
+
+    let wipes = Arc::clone(&f.wipes);
+    drop(f.cache);
+
+    match wipes.load(Ordering::SeqCst) {
+        2 => Ok(()),
+        n => Err(std::format!("Expected 2 wipes after dropping the cache. Got {n}")),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn should_allocate_full_capacity_when_built_zeroizing() -> Result<(), String> {
+    let capacity = NonZeroUsize::new(64).unwrap();
+    let built: ZeroizingLruCache<u32, [u8; 32]> =
+        crate::LruCacheBuilder::new(capacity).initial_capacity(1).build_zeroizing();
+    let created: ZeroizingLruCache<u32, [u8; 32]> = LruCache::new_zeroizing(capacity);
+
+    match (built.nodes.heap_size(), created.nodes.heap_size()) {
+        (b, c) if b == c => Ok(()),
+        other => Err(std::format!("Expected the same storage as new_zeroizing. Got {other:?}")),
+    }
+}
@@ -0,0 +1,147 @@
+//! Model checks the thread-safe caches under every interleaving of a few threads.  Only built with `--cfg loom`, which
+//! swaps the crate's locks and atomics for loom's:
+//!
+//! `RUSTFLAGS="--cfg loom" cargo test --release --test loom`
+#![cfg(loom)]
+
+use loom::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+use lru_cache::{ConcurrentLruCache, LruCache, RwLruCache};
+use std::{
+    hash::{BuildHasherDefault, DefaultHasher},
+    num::NonZeroUsize,
+};
+
+/// A fixed hasher, so that every execution of a model behaves the same
+type Fixed = BuildHasherDefault<DefaultHasher>;
+
+fn cache_of(capacity: usize, entries: &[(u32, u32)]) -> LruCache<u32, u32, Fixed> {
+    let mut cache = LruCache::with_hasher(NonZeroUsize::new(capacity).unwrap(), Fixed::default());
+
+    for &(key, value) in entries {
+        cache.put(key, value);
+    }
+
+    cache
+}
+
+/// Empties a cache, returning its values from least to most recently used
+fn drain(mut cache: LruCache<u32, u32, Fixed>) -> Vec<u32> {
+    let len = cache.len();
+    let values: Vec<u32> = core::iter::from_fn(|| cache.pop_lru()).collect();
+    assert_eq!(values.len(), len, "len disagrees with the entries popped");
+    values
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn concurrent_cache_should_neither_lose_nor_invent_values() {
+    loom::model(|| {
+        let cache = Arc::new(ConcurrentLruCache::from(cache_of(2, &[(0, 100)])));
+
+        let pusher = {
+            let cache = Arc::clone(&cache);
+            thread::spawn(move || {
+                let displaced = cache.push(1, 101).map(|(_, v)| v);
+                let read = cache.get(&0);
+                (displaced, read)
+            })
+        };
+
+        let popper = {
+            let cache = Arc::clone(&cache);
+            thread::spawn(move || {
+                let displaced = cache.push(2, 102).map(|(_, v)| v);
+                let popped = cache.pop_lru();
+                assert!(cache.len() <= 2);
+                (displaced, popped)
+            })
+        };
+
+        let (displaced_1, read) = pusher.join().unwrap();
+        let (displaced_2, popped) = popper.join().unwrap();
+
+        assert!(matches!(read, None | Some(100)), "get returned {read:?}, which was never stored under key 0");
+
+        let cache = Arc::try_unwrap(cache).ok().unwrap().into_inner();
+        let mut values = drain(cache);
+        values.extend([displaced_1, displaced_2, popped].into_iter().flatten());
+        values.sort_unstable();
+
+        assert_eq!(values, [100, 101, 102], "every value should be either still cached or handed back exactly once");
+    });
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn try_methods_should_hand_back_what_they_could_not_store() {
+    loom::model(|| {
+        let cache = Arc::new(ConcurrentLruCache::from(cache_of(2, &[])));
+
+        let writer = {
+            let cache = Arc::clone(&cache);
+            thread::spawn(move || cache.try_put(1, 101).err().map(|blocked| blocked.into_inner()))
+        };
+
+        let held = cache.with_lock(|c| c.put(2, 102));
+        let refused = writer.join().unwrap();
+
+        assert_eq!(held, None);
+        match refused {
+            Some(item) => assert_eq!((item, cache.get(&1)), ((1, 101), None)),
+            None => assert_eq!(cache.get(&1), Some(101)),
+        }
+    });
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn rw_cache_should_apply_a_read_before_the_eviction_that_follows_it() {
+    loom::model(|| {
+        let cache = Arc::new(RwLruCache::from(cache_of(2, &[(0, 100), (1, 101)])));
+
+        let reader = {
+            let cache = Arc::clone(&cache);
+            thread::spawn(move || cache.get(&0))
+        };
+
+        cache.put(2, 102);
+        let read = reader.join().unwrap();
+
+        // Key 0 was least recently used, so it only survives the eviction if the read promoted it first, and the read
+        // only finds it if it came first
+        let survived = cache.contains_key(&0);
+        assert_eq!(read.is_some(), survived, "read {read:?}, but key 0 survived: {survived}");
+        assert_eq!(cache.contains_key(&1), !survived);
+        assert_eq!(cache.len(), 2);
+    });
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[test]
+fn get_or_insert_with_should_load_a_missing_key_once() {
+    loom::model(|| {
+        let cache = Arc::new(ConcurrentLruCache::from(cache_of(2, &[])));
+        let loads = Arc::new(AtomicUsize::new(0));
+
+        let load = |cache: &ConcurrentLruCache<u32, u32, Fixed>, loads: &AtomicUsize| {
+            cache.get_or_insert_with(1, || {
+                loads.fetch_add(1, Ordering::Relaxed);
+                101
+            })
+        };
+
+        let other = {
+            let (cache, loads) = (Arc::clone(&cache), Arc::clone(&loads));
+            thread::spawn(move || load(&cache, &loads))
+        };
+
+        assert_eq!((load(&cache, &loads), other.join().unwrap()), (101, 101));
+        assert_eq!(loads.load(Ordering::Relaxed), 1);
+    });
+}
@@ -0,0 +1,101 @@
+//! Hit ratios of the eviction modes on a skewed workload, whose popular keys stay popular throughout
+use lru_cache::{EvictionMode, LruCache, LruCacheBuilder};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::num::NonZeroUsize;
+
+const CAPACITY: usize = 1_000;
+const KEY_SPACE: u64 = 10_000;
+const OPERATIONS: usize = 200_000;
+
+/// Keys drawn so that low keys are far more popular than high ones, with roughly the cache's capacity worth of hot keys
+fn skewed_workload() -> Vec<u64> {
+    let mut rng = StdRng::seed_from_u64(0x5eed);
+
+    (0..OPERATIONS)
+        .map(|_| {
+            let u: f64 = rng.random();
+            (u.powi(4) * KEY_SPACE as f64) as u64
+        })
+        .collect()
+}
+
+/// Reads each key, inserting it on a miss, and returns the fraction of reads that hit
+fn hit_ratio(cache: &mut LruCache<u64, u64>, workload: &[u64]) -> f64 {
+    for &key in workload {
+        if cache.get(&key).is_none() {
+            cache.put(key, key);
+        }
+    }
+
+    cache.hit_ratio()
+}
+
+fn cache_with_mode(eviction_mode: EvictionMode) -> LruCache<u64, u64> {
+    LruCacheBuilder::new(NonZeroUsize::new(CAPACITY).unwrap())
+        .eviction_mode(eviction_mode)
+        .build()
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn every_policy_should_exploit_skew() -> Result<(), String> {
+    let workload = skewed_workload();
+
+    let modes = [
+        EvictionMode::Exact,
+        EvictionMode::Lfu,
+        EvictionMode::SEGMENTED,
+        EvictionMode::TWO_QUEUE,
+        EvictionMode::TINY_LFU,
+        EvictionMode::Random,
+        EvictionMode::CostAware,
+        EvictionMode::S3_FIFO,
+    ];
+
+    for mode in modes {
+        let ratio = hit_ratio(&mut cache_with_mode(mode), &workload);
+
+        // A cache holding a tenth of the keys sees far more than a tenth of the reads hit
+        if ratio <= 0.3 {
+            return Err(format!("{mode:?} hit ratio {ratio:.3} is implausibly low"));
+        }
+    }
+
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn lfu_should_beat_lru_when_popularity_is_stable() -> Result<(), String> {
+    let workload = skewed_workload();
+    let lru = hit_ratio(&mut cache_with_mode(EvictionMode::Exact), &workload);
+    let lfu = hit_ratio(&mut cache_with_mode(EvictionMode::Lfu), &workload);
+
+    if lfu > lru + 0.02 {
+        Ok(())
+    } else {
+        Err(format!("LFU hit ratio {lfu:.3} is not measurably above LRU's {lru:.3}"))
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn sampled_eviction_should_stay_close_to_exact_lru() -> Result<(), String> {
+    let workload = skewed_workload();
+    let exact = hit_ratio(&mut cache_with_mode(EvictionMode::Exact), &workload);
+
+    for sample_size in [5, 10] {
+        let mode = EvictionMode::Sampled {
+            sample_size: NonZeroUsize::new(sample_size).unwrap(),
+        };
+        let sampled = hit_ratio(&mut cache_with_mode(mode), &workload);
+
+        if (exact - sampled).abs() > 0.03 {
+            return Err(format!(
+                "Sampling {sample_size} entries gave a hit ratio of {sampled:.3} against {exact:.3} for exact LRU"
+            ));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+//! Proves that `StaticLruCache` never touches the heap.  This lives in its own test binary because it replaces the
+//! global allocator
+use lru_cache::static_cache::StaticLruCache;
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn static_cache_should_never_allocate() -> Result<(), String> {
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    let mut c: StaticLruCache<u32, [u8; 16], 32> = StaticLruCache::new();
+
+    for i in 0..10_000u32 {
+        c.put(i % 97, [i as u8; 16]);
+        c.get(&((i * 7) % 97));
+
+        if i % 13 == 0 {
+            c.remove(&(i % 97));
+        }
+
+        if i % 17 == 0 {
+            c.pop_lru();
+            c.pop_mru();
+        }
+    }
+
+    let allocations = ALLOCATIONS.load(Ordering::SeqCst) - before;
+
+    if allocations == 0 {
+        Ok(())
+    } else {
+        Err(format!("Expected no allocations. Got {allocations}"))
+    }
+}
@@ -0,0 +1,135 @@
+//! Proves that a full cache reuses the storage freed by eviction and removal instead of allocating, and that reads and
+//! inserts into preallocated storage never allocate.  This lives in its own test binary because it replaces the global
+//! allocator.  `cargo bench --bench alloc_counting` prints the same measurements for this crate and for `lru`
+use lru_cache::{EvictionMode, LruCache, LruCacheBuilder};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    num::NonZeroUsize,
+};
+
+struct CountingAllocator;
+
+thread_local! {
+    /// Allocations made by this thread.  Tests run in parallel, so a global count would include other tests' allocations
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Allocations made by the current thread so far
+fn allocations() -> usize {
+    ALLOCATIONS.get()
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.set(ALLOCATIONS.get() + 1);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// -----------------------------------------------------------------------------------------------------------------
+#[test]
+fn full_cache_should_not_allocate() -> Result<(), String> {
+    let mut c: LruCache<u64, u64> = LruCache::new(NonZeroUsize::new(64).unwrap());
+
+    for i in 0..64 {
+        c.put(i, i);
+    }
+
+    let before = allocations();
+
+    for i in 0..100_000u64 {
+        c.put(i % 256, i);
+        c.get(&((i * 7) % 256));
+
+        if i % 3 == 0 {
+            c.remove(&((i * 5) % 256));
+        }
+    }
+
+    let allocations = allocations() - before;
+
+    if allocations == 0 {
+        Ok(())
+    } else {
+        Err(format!("Expected no allocations once the cache is full. Got {allocations}"))
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+/// Every insert below evicts, so each one frees an entry's storage and immediately needs it again
+fn evicting_inserts_allocate(mut c: LruCache<u64, u64>) -> usize {
+    let capacity = c.capacity().get() as u64;
+
+    for i in 0..capacity {
+        c.put(i, i);
+    }
+
+    let before = allocations();
+
+    for i in capacity..capacity + 100_000 {
+        c.put(i, i);
+    }
+
+    allocations() - before
+}
+
+#[test]
+fn evicting_inserts_should_reuse_freed_entries() -> Result<(), String> {
+    let capacity = NonZeroUsize::new(1_000).unwrap();
+    let sampled = LruCacheBuilder::new(capacity)
+        .eviction_mode(EvictionMode::Sampled {
+            sample_size: NonZeroUsize::new(5).unwrap(),
+        })
+        .build();
+
+    match (evicting_inserts_allocate(LruCache::new(capacity)), evicting_inserts_allocate(sampled)) {
+        (0, 0) => Ok(()),
+        (exact, sampled) => Err(format!(
+            "Expected no allocations from evicting inserts. Got {exact} with exact and {sampled} with sampled eviction"
+        )),
+    }
+}
+
+// -----------------------------------------------------------------------------------------------------------------
+/// Allocations made while running `f`
+fn allocations_during(f: impl FnOnce()) -> usize {
+    let before = allocations();
+    f();
+    allocations() - before
+}
+
+#[test]
+fn hits_should_not_allocate() -> Result<(), String> {
+    let keys: Vec<String> = (0..1_000).map(|i| format!("key-{i}")).collect();
+    let mut c = LruCache::new(NonZeroUsize::new(keys.len()).unwrap());
+
+    for key in &keys {
+        c.put(key.clone(), key.repeat(4));
+    }
+
+    match allocations_during(|| keys.iter().for_each(|key| assert!(c.get(key.as_str()).is_some()))) {
+        0 => Ok(()),
+        allocations => Err(format!("Expected no allocations from {} hits. Got {allocations}", keys.len())),
+    }
+}
+
+/// The pointer-linked storage selected by `unsafe-fast` allocates each entry separately the first time it is used
+#[cfg(not(feature = "unsafe-fast"))]
+#[test]
+fn puts_into_preallocated_storage_should_not_allocate() -> Result<(), String> {
+    let items: Vec<(String, String)> = (0..1_000).map(|i| (format!("key-{i}"), format!("value-{i}"))).collect();
+    let mut c = LruCache::new(NonZeroUsize::new(items.len()).unwrap());
+
+    match allocations_during(|| items.into_iter().for_each(|(k, v)| _ = c.put(k, v))) {
+        0 => Ok(()),
+        allocations => Err(format!("Expected no allocations from inserts into a new cache. Got {allocations}")),
+    }
+}
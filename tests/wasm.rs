@@ -0,0 +1,85 @@
+//! Expiry under the JavaScript clock.  With `wasm-bindgen-test-runner` installed, run with
+//! `cargo test --target wasm32-unknown-unknown --features wasm --test wasm`
+#![cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+
+use lru_cache::{JsClock, LruCache, LruCacheBuilder};
+use std::{
+    num::NonZeroUsize,
+    time::{Duration, UNIX_EPOCH},
+};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+// ---------------------------------------------------------------------------------------------------------------------
+/// Busy-waits, since the tests run on a single thread that cannot sleep
+fn wait(duration: Duration) {
+    let until = js_sys::Date::now() + duration.as_secs_f64() * 1_000.0;
+
+    while js_sys::Date::now() < until {}
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[wasm_bindgen_test]
+fn put_with_ttl_should_expire_the_item_under_the_default_clock() -> Result<(), String> {
+    let mut c: LruCache<u32, u32> = LruCache::new(NonZeroUsize::new(4).unwrap());
+    c.put_with_ttl(1, 10, Duration::from_millis(20));
+    c.put(2, 20);
+    let before = c.get(&1).copied();
+
+    wait(Duration::from_millis(30));
+
+    match (before, c.get(&1).copied(), c.get(&2).copied()) {
+        (Some(10), None, Some(20)) => Ok(()),
+        other => Err(format!("Expected item 1 to expire after 20ms. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[wasm_bindgen_test]
+fn entry_info_should_measure_time_on_the_js_clock() -> Result<(), String> {
+    let mut c = LruCacheBuilder::new(NonZeroUsize::new(4).unwrap())
+        .clock(JsClock::new())
+        .ttl(Duration::from_secs(60))
+        .build();
+    c.put(1, 10);
+
+    wait(Duration::from_millis(10));
+
+    match c.entry_info(&1).map(|info| (info.age(), info.remaining_ttl())) {
+        Some((age, Some(remaining))) if age >= Duration::from_millis(10) && remaining < Duration::from_secs(60) => Ok(()),
+        other => Err(format!("Expected the item to have aged by at least 10ms. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[wasm_bindgen_test]
+fn purge_expired_should_remove_items_under_the_js_clock() -> Result<(), String> {
+    let mut c = LruCache::with_clock(NonZeroUsize::new(8).unwrap(), JsClock::new());
+
+    for k in 0..4 {
+        c.put_with_ttl(k, k, Duration::from_millis(20));
+        c.put(k + 4, k);
+    }
+
+    wait(Duration::from_millis(30));
+
+    match (c.purge_expired(), c.len()) {
+        (4, 4) => Ok(()),
+        other => Err(format!("Expected four items to be purged. Got {other:?}")),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------------------------------
+#[wasm_bindgen_test]
+fn put_with_expiry_at_should_expire_the_item_under_the_js_clock() -> Result<(), String> {
+    let mut c: LruCache<u32, u32> = LruCache::new(NonZeroUsize::new(4).unwrap());
+    let now = UNIX_EPOCH + Duration::from_secs_f64(js_sys::Date::now() / 1_000.0);
+    c.put_with_expiry_at(1, 10, now + Duration::from_millis(20));
+    let before = c.get(&1).copied();
+
+    wait(Duration::from_millis(30));
+
+    match (before, c.get(&1).copied()) {
+        (Some(10), None) => Ok(()),
+        other => Err(format!("Expected item 1 to expire after 20ms. Got {other:?}")),
+    }
+}